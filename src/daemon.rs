@@ -0,0 +1,153 @@
+//! `--daemon`: running the HTTP transport as a long-lived service (e.g. under
+//! systemd). The process stays in the foreground — supervisors expect that —
+//! but writes a PID file, logs to rotating files instead of stderr, and
+//! restarts the serving loop when the transport fails.
+
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use miette::miette;
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+
+use crate::logging::{LogFormat, LogLayer, fmt_layer};
+
+/// First delay before restarting a failed serving loop; doubled on each
+/// consecutive failure up to [`MAX_BACKOFF`].
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A serving loop that ran at least this long before failing is considered
+/// healthy, and the next restart starts from [`INITIAL_BACKOFF`] again.
+const HEALTHY_RUN: Duration = Duration::from_secs(300);
+
+/// Number of rotated log files kept in the log directory.
+const MAX_LOG_FILES: usize = 7;
+
+/// A PID file that is removed again when dropped.
+#[derive(Debug)]
+pub struct PidFile(PathBuf);
+
+impl PidFile {
+    /// Writes the current process id to `path`. Fails if the file names a
+    /// process that is still running; stale files are overwritten.
+    pub fn create(path: &Path) -> miette::Result<Self> {
+        if let Some(pid) = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|pid| pid.trim().parse::<u32>().ok())
+            && process_running(pid)
+        {
+            return Err(miette!(
+                "{} belongs to running process {pid}; is mq-mcp already running?",
+                path.display()
+            ));
+        }
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .map_err(|e| miette!("failed to write PID file {}: {e}", path.display()))?;
+        Ok(Self(path.to_path_buf()))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn process_running(pid: u32) -> bool {
+    pid != std::process::id() && Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Without procfs there is no portable liveness check; assume the file is
+/// stale.
+#[cfg(not(target_os = "linux"))]
+fn process_running(_pid: u32) -> bool {
+    false
+}
+
+/// A log layer writing `format` to daily-rotated `mq-mcp.<date>.log` files
+/// in `dir`. Keep the returned guard alive for the lifetime of the process
+/// so buffered lines are flushed.
+pub fn log_layer(dir: &Path, format: LogFormat) -> miette::Result<(LogLayer, WorkerGuard)> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("mq-mcp")
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(dir)
+        .map_err(|e| miette!("failed to open log directory {}: {e}", dir.display()))?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    Ok((fmt_layer(format, writer, false), guard))
+}
+
+/// Runs `serve` until it returns `Ok` (a requested shutdown), restarting it
+/// with exponential backoff whenever it fails.
+pub async fn run<F, Fut>(mut serve: F) -> miette::Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = miette::Result<()>>,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        let started = Instant::now();
+        let Err(e) = serve().await else {
+            return Ok(());
+        };
+        if started.elapsed() >= HEALTHY_RUN {
+            backoff = INITIAL_BACKOFF;
+        }
+        tracing::error!("serving loop failed: {e:?}; restarting in {backoff:?}");
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file_is_written_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mq-mcp.pid");
+        {
+            let _pid_file = PidFile::create(&path).unwrap();
+            let pid = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(pid.trim(), std::process::id().to_string());
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_pid_file_overwrites_stale_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mq-mcp.pid");
+        std::fs::write(&path, "not a pid").unwrap();
+        assert!(PidFile::create(&path).is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_restarts_until_ok() {
+        let mut attempts = 0;
+        let result = run(|| {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt < 3 {
+                    Err(miette!("transport error"))
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+    }
+}
@@ -1,2 +1,39 @@
+//! mq-mcp as a library: the [`Server`] behind the `mq-mcp` binary, for
+//! embedding it in another program or driving its tools in-process from
+//! tests.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let server = mq_mcp::Server::new(None)?;
+//! let arguments = serde_json::json!({ "markdown": "# Install\n\n## Usage" });
+//! let result = server
+//!     .call("extract_headings", arguments.as_object().unwrap().clone())
+//!     .await?;
+//! println!("{:?}", result.structured_content);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Server`] also implements rmcp's `ServerHandler`, so it can be served
+//! over any rmcp transport, as [`start`] and [`start_http`] do.
+
 pub mod server;
-pub use server::{HttpConfig, start, start_http};
+#[cfg(feature = "embeddings")]
+pub use server::EmbeddingsConfig;
+#[cfg(feature = "pandoc")]
+pub use server::PandocConfig;
+pub use server::{
+    Check, CheckStatus, DEFAULT_IDEMPOTENCY_TTL, DoctorReport, EmojiMode, EmojiPlatform,
+    LineEnding, Locale, PostProcess, Quotas, RedactionRule, Server, ServerConfig, doctor, invoke,
+    start,
+};
+#[cfg(feature = "fetch")]
+pub use server::{
+    ConfluenceConfig, DEFAULT_BREAKER_COOLDOWN, DEFAULT_BREAKER_THRESHOLD, DEFAULT_CACHE_MAX_BYTES,
+    DEFAULT_FETCH_RETRIES, FetchConfig, GdocConfig, GithubConfig, NotionConfig, SlackConfig,
+    WebhookConfig,
+};
+#[cfg(feature = "s3")]
+pub use server::{DEFAULT_LINK_TTL, S3Config};
+#[cfg(not(target_os = "wasi"))]
+pub use server::{HttpConfig, ReplayReport, replay, start_http, start_recorded};
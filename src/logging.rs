@@ -0,0 +1,65 @@
+//! The log output: stderr, or the file given with `--log-file`, in the
+//! `--log-format` picked. `--daemon` mode writes the same formats to
+//! rotating files instead.
+
+use std::{fs::OpenOptions, io::IsTerminal, path::Path, sync::Mutex};
+
+use miette::miette;
+use tracing_subscriber::{EnvFilter, Layer, Registry, fmt::MakeWriter};
+
+/// A layer of the global subscriber.
+pub type LogLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// How log lines are formatted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// One line per event, with the fields of the spans it's in
+    #[default]
+    Full,
+    /// One shorter line per event
+    Compact,
+    /// Several lines per event, for reading in a terminal
+    Pretty,
+    /// One JSON object per event, for log shippers
+    Json,
+}
+
+/// A layer writing to `file`, appending to it, or to stderr without one.
+pub fn output_layer(file: Option<&Path>, format: LogFormat) -> miette::Result<LogLayer> {
+    let Some(path) = file else {
+        // Colors are only used on a terminal: clients save the stderr of
+        // stdio servers to log files, and Windows consoles without VT
+        // processing print the escape codes.
+        return Ok(fmt_layer(
+            format,
+            std::io::stderr,
+            std::io::stderr().is_terminal(),
+        ));
+    };
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| miette!("failed to open log file {}: {e}", path.display()))?;
+    Ok(fmt_layer(format, Mutex::new(file), false))
+}
+
+/// A layer writing events in `format` to `writer`, filtered by `RUST_LOG`.
+pub fn fmt_layer<W>(format: LogFormat, writer: W, ansi: bool) -> LogLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi && format != LogFormat::Json)
+        .with_thread_names(true)
+        .with_target(true)
+        .with_line_number(true);
+    let filter = EnvFilter::from_default_env();
+    match format {
+        LogFormat::Full => layer.with_filter(filter).boxed(),
+        LogFormat::Compact => layer.compact().with_filter(filter).boxed(),
+        LogFormat::Pretty => layer.pretty().with_filter(filter).boxed(),
+        LogFormat::Json => layer.json().with_filter(filter).boxed(),
+    }
+}
@@ -1,15 +1,382 @@
-pub mod server;
+#[cfg(not(target_os = "wasi"))]
+mod daemon;
+mod logging;
+#[cfg(feature = "otel")]
+mod telemetry;
 
-use std::path::PathBuf;
+#[cfg(not(target_os = "wasi"))]
+use std::path::Path;
+use std::{path::PathBuf, time::Duration};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use logging::{LogFormat, LogLayer};
+use miette::miette;
+use mq_mcp::server;
+#[cfg(feature = "embeddings")]
+use server::EmbeddingsConfig;
+#[cfg(not(target_os = "wasi"))]
 use server::HttpConfig;
-use tracing_subscriber::EnvFilter;
+#[cfg(feature = "pandoc")]
+use server::PandocConfig;
+#[cfg(feature = "s3")]
+use server::S3Config;
+#[cfg(feature = "fetch")]
+use server::{
+    ConfluenceConfig, DEFAULT_BREAKER_COOLDOWN, DEFAULT_BREAKER_THRESHOLD, DEFAULT_CACHE_MAX_BYTES,
+    DEFAULT_FETCH_RETRIES, FetchConfig, GdocConfig, GithubConfig, NotionConfig, SlackConfig,
+    WebhookConfig,
+};
+use server::{
+    DEFAULT_DAILY_FORMAT, DEFAULT_IDEMPOTENCY_TTL, DailyNotesConfig, Locale, PostProcess, Quotas,
+    RedactionRule, ServerConfig,
+};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 /// Model Context Protocol server for mq
 #[derive(Debug, Parser)]
 #[command(name = "mq-mcp", version)]
 struct Cli {
+    /// Path to an mq-db (.mq-db) store file to expose via the db_* tools
+    /// (db_sql, db_mq, db_list_documents, db_stats, db_index). If it doesn't
+    /// exist yet, db_index will create it on first use. Omit to disable the
+    /// db_* tools entirely.
+    #[cfg(feature = "db")]
+    #[arg(long)]
+    db: Option<PathBuf>,
+
+    /// Path of the full-text search index file for index_directory and
+    /// search_index. Created on first use; omit to disable those tools.
+    #[cfg(feature = "index")]
+    #[arg(long, value_name = "PATH")]
+    index: Option<PathBuf>,
+
+    /// Don't watch the indexed directories for changes; the index is then
+    /// only updated by index_directory
+    #[cfg(feature = "index")]
+    #[arg(long, requires = "index")]
+    no_index_watch: bool,
+
+    /// Path of the SQLite database persisting saved queries, scheduled
+    /// pipelines and the audit log across restarts. Created and migrated on
+    /// startup; omit to keep them in memory.
+    #[arg(long, value_name = "PATH")]
+    state: Option<PathBuf>,
+
+    /// Return text results larger than this many bytes as a resource link
+    /// (`mq://results/<id>`) the client can read lazily, instead of inline
+    #[arg(long, value_name = "BYTES")]
+    resource_threshold: Option<usize>,
+
+    /// Language for tool descriptions, instructions and error messages
+    /// (`en` or `ja`)
+    #[arg(long, default_value = "en")]
+    locale: Locale,
+
+    /// Maximum bytes (tool arguments plus returned text) a session may
+    /// process per rolling hour
+    #[arg(long, value_name = "BYTES")]
+    hourly_quota: Option<u64>,
+
+    /// Maximum bytes a session may process per rolling day
+    #[arg(long, value_name = "BYTES")]
+    daily_quota: Option<u64>,
+
+    /// Redact matches from every tool result (repeatable): a preset
+    /// (`email`, `api-key`, `ipv4`) or a custom `name=regex` rule
+    #[arg(long = "redact", value_name = "RULE")]
+    redactions: Vec<RedactionRule>,
+
+    /// Default post-processing of markdown results, as comma-separated
+    /// steps: trim, collapse-blank-lines, strip-html-comments,
+    /// max-line-width=N, emoji=unicode|strip,
+    /// emoji-platform=github|slack|discord, strict-commonmark,
+    /// expand-tabs=N, final-newline, line-endings=lf|crlf
+    #[arg(long, value_name = "STEPS")]
+    postprocess: Option<PostProcess>,
+
+    /// End each markdown result with a footer citing its source document,
+    /// heading path and line range (calls can override this with the
+    /// `provenance` argument)
+    #[arg(long)]
+    provenance: bool,
+
+    /// How long the result of a mutating call made with an idempotency_key
+    /// is replayed when the key is reused
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_IDEMPOTENCY_TTL.as_secs())]
+    idempotency_ttl: u64,
+
+    /// Leave deprecated tools out of the tool list and refuse calls to them,
+    /// to check that a setup no longer depends on them
+    #[arg(long)]
+    hide_deprecated_tools: bool,
+
+    /// Record the shapes of successful queries, with their string literals
+    /// blanked out, in the state database and list the most common in the
+    /// `cookbook` tool and the `mq://cookbook` resource
+    #[arg(long)]
+    cookbook: bool,
+
+    /// YAML file of settings that are reloaded when it changes, without a
+    /// restart; its keys override the flags of the same name
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Directory of .mq modules whose functions every query can call;
+    /// reloaded when a module changes
+    #[arg(long, value_name = "DIR")]
+    modules: Option<PathBuf>,
+
+    /// Vault (directory of notes) in which daily_note, append_to_daily_note
+    /// and create_note find and create notes
+    #[arg(long, value_name = "DIR")]
+    vault: Option<PathBuf>,
+
+    /// Folder of the daily notes, relative to the vault
+    #[arg(long, value_name = "DIR", default_value = "", requires = "vault")]
+    daily_folder: PathBuf,
+
+    /// strftime format of a daily note's file name, without the extension;
+    /// `/` in it makes subfolders, e.g. `%Y/%m/%Y-%m-%d`
+    #[arg(long, value_name = "FORMAT", default_value = DEFAULT_DAILY_FORMAT, requires = "vault")]
+    daily_format: String,
+
+    /// Template of new daily notes: the name of a template in --templates,
+    /// or else a file relative to the vault
+    #[arg(long, value_name = "PATH", requires = "vault")]
+    daily_template: Option<PathBuf>,
+
+    /// Directory of note templates for list_templates, render_template and
+    /// create_note
+    #[arg(long, value_name = "DIR")]
+    templates: Option<PathBuf>,
+
+    /// Request header that query_url calls may set (repeatable), e.g.
+    /// `Authorization`, `Cookie` or `User-Agent`
+    #[cfg(feature = "fetch")]
+    #[arg(long = "allow-fetch-header", value_name = "NAME")]
+    allowed_fetch_headers: Vec<String>,
+
+    /// Restrict custom request headers to this host (repeatable); by
+    /// default they may be sent to any host
+    #[cfg(feature = "fetch")]
+    #[arg(long = "fetch-header-host", value_name = "HOST")]
+    fetch_header_hosts: Vec<String>,
+
+    /// Cache fetched pages for this many seconds, revalidating them with
+    /// ETag / Last-Modified afterwards. Omit to disable the page cache.
+    #[cfg(feature = "fetch")]
+    #[arg(long, value_name = "SECONDS")]
+    fetch_cache_ttl: Option<u64>,
+
+    /// Maximum total size of the page cache
+    #[cfg(feature = "fetch")]
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_CACHE_MAX_BYTES)]
+    fetch_cache_size: usize,
+
+    /// How many times a fetch that failed with a connection error, timeout,
+    /// 429 or 5xx is retried, with exponential backoff
+    #[cfg(feature = "fetch")]
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_FETCH_RETRIES)]
+    fetch_retries: u32,
+
+    /// Consecutive failures after which a host is not contacted for
+    /// --fetch-breaker-cooldown seconds; 0 disables the circuit breaker
+    #[cfg(feature = "fetch")]
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_BREAKER_THRESHOLD)]
+    fetch_breaker_threshold: u32,
+
+    /// How long a host is skipped once its circuit breaker opens
+    #[cfg(feature = "fetch")]
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_BREAKER_COOLDOWN.as_secs())]
+    fetch_breaker_cooldown: u64,
+
+    /// Don't honour robots.txt or Crawl-delay. Only use this for sites you
+    /// operate or have permission to crawl.
+    #[cfg(feature = "fetch")]
+    #[arg(long)]
+    ignore_robots_txt: bool,
+
+    /// Token for query_github, for private repositories and higher rate
+    /// limits
+    #[cfg(feature = "fetch")]
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "GITHUB_TOKEN",
+        hide_env_values = true
+    )]
+    github_token: Option<String>,
+
+    /// GitHub REST API root, for GitHub Enterprise Server
+    #[cfg(feature = "fetch")]
+    #[arg(long, value_name = "URL")]
+    github_api_url: Option<String>,
+
+    /// Base URL of the Confluence site for query_confluence, e.g.
+    /// https://example.atlassian.net/wiki
+    #[cfg(feature = "fetch")]
+    #[arg(long, value_name = "URL")]
+    confluence_url: Option<String>,
+
+    /// Account email for Confluence Cloud (basic auth with the API token);
+    /// omit to send the token as a Data Center personal access token
+    #[cfg(feature = "fetch")]
+    #[arg(long, value_name = "EMAIL", env = "CONFLUENCE_USER")]
+    confluence_user: Option<String>,
+
+    /// Confluence API token or personal access token
+    #[cfg(feature = "fetch")]
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "CONFLUENCE_TOKEN",
+        hide_env_values = true
+    )]
+    confluence_token: Option<String>,
+
+    /// Notion integration token for query_notion
+    #[cfg(feature = "fetch")]
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "NOTION_TOKEN",
+        hide_env_values = true
+    )]
+    notion_token: Option<String>,
+
+    /// OAuth 2.0 access token with a Drive read scope for query_gdoc
+    #[cfg(feature = "fetch")]
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "GOOGLE_OAUTH_TOKEN",
+        hide_env_values = true
+    )]
+    google_token: Option<String>,
+
+    /// Slack token for fetching threads in slack_to_markdown
+    #[cfg(feature = "fetch")]
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "SLACK_TOKEN",
+        hide_env_values = true
+    )]
+    slack_token: Option<String>,
+
+    /// Host scheduled pipelines may POST their results to (repeatable,
+    /// e.g. --webhook-host hooks.example.com). Webhooks are disabled
+    /// without one.
+    #[cfg(feature = "fetch")]
+    #[arg(long = "webhook-host", value_name = "HOST")]
+    webhook_hosts: Vec<String>,
+
+    /// Secret for signing webhook deliveries with HMAC-SHA256
+    #[cfg(feature = "fetch")]
+    #[arg(
+        long,
+        value_name = "SECRET",
+        env = "MQ_MCP_WEBHOOK_SECRET",
+        hide_env_values = true
+    )]
+    webhook_secret: Option<String>,
+
+    /// pandoc executable for pandoc_to_markdown and markdown_to_pandoc
+    #[cfg(feature = "pandoc")]
+    #[arg(long, value_name = "PATH", default_value = "pandoc")]
+    pandoc: PathBuf,
+
+    /// OpenAI-compatible embeddings endpoint for embed_sections and
+    /// semantic_search, e.g. http://localhost:11434/v1/embeddings for a
+    /// local Ollama
+    #[cfg(feature = "embeddings")]
+    #[arg(long, value_name = "URL")]
+    embeddings_url: Option<String>,
+
+    /// Embedding model name sent to the endpoint
+    #[cfg(feature = "embeddings")]
+    #[arg(long, value_name = "MODEL")]
+    embeddings_model: Option<String>,
+
+    /// API key for the embeddings endpoint
+    #[cfg(feature = "embeddings")]
+    #[arg(
+        long,
+        value_name = "KEY",
+        env = "EMBEDDINGS_API_KEY",
+        hide_env_values = true
+    )]
+    embeddings_api_key: Option<String>,
+
+    /// S3 bucket to upload results over --resource-threshold to; they are
+    /// returned as presigned links instead of mq://results/ resources
+    #[cfg(feature = "s3")]
+    #[arg(long, value_name = "BUCKET", requires = "resource_threshold")]
+    s3_bucket: Option<String>,
+
+    /// Endpoint of an S3-compatible service (MinIO, R2, ...), addressed
+    /// path-style; AWS S3 when omitted
+    #[cfg(feature = "s3")]
+    #[arg(long, value_name = "URL")]
+    s3_endpoint: Option<String>,
+
+    /// Region requests to the bucket are signed for
+    #[cfg(feature = "s3")]
+    #[arg(long, value_name = "REGION", env = "AWS_REGION")]
+    s3_region: Option<String>,
+
+    /// Prefix of the uploaded object keys, e.g. mq-mcp/
+    #[cfg(feature = "s3")]
+    #[arg(long, value_name = "PREFIX", default_value = "")]
+    s3_prefix: String,
+
+    /// Access key id for the bucket
+    #[cfg(feature = "s3")]
+    #[arg(long, value_name = "ID", env = "AWS_ACCESS_KEY_ID")]
+    s3_access_key_id: Option<String>,
+
+    /// Secret access key for the bucket
+    #[cfg(feature = "s3")]
+    #[arg(
+        long,
+        value_name = "KEY",
+        env = "AWS_SECRET_ACCESS_KEY",
+        hide_env_values = true
+    )]
+    s3_secret_access_key: Option<String>,
+
+    /// Seconds the links to uploaded results stay valid (at most 604800)
+    #[cfg(feature = "s3")]
+    #[arg(long, value_name = "SECONDS", default_value_t = server::DEFAULT_LINK_TTL.as_secs())]
+    s3_link_ttl: u64,
+
+    /// Format of the log lines: full, compact, pretty (several lines per
+    /// event) or json (one object per line, for log shippers)
+    #[arg(long, value_enum, default_value = "full")]
+    log_format: LogFormat,
+
+    /// Append the logs to this file instead of writing them to stderr
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Export tool call spans and metrics over OTLP/HTTP to this collector,
+    /// e.g. http://localhost:4318 (`/v1/traces` and `/v1/metrics` are
+    /// appended)
+    #[cfg(feature = "otel")]
+    #[arg(long, value_name = "URL", env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+
+    #[command(flatten)]
+    transport: TransportArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// The flags that pick how the server is served.
+#[cfg(not(target_os = "wasi"))]
+#[derive(Debug, clap::Args)]
+struct TransportArgs {
     /// Serve over Streamable HTTP instead of stdio (remote MCP)
     #[arg(long)]
     http: bool,
@@ -23,35 +390,422 @@ struct Cli {
     #[arg(long = "allowed-host")]
     allowed_hosts: Vec<String>,
 
-    /// Path to an mq-db (.mq-db) store file to expose via the db_* tools
-    /// (db_sql, db_mq, db_list_documents, db_stats, db_index). If it doesn't
-    /// exist yet, db_index will create it on first use. Omit to disable the
-    /// db_* tools entirely.
-    #[arg(long)]
-    db: Option<PathBuf>,
+    /// Run as a long-lived service: log to rotating files, write a PID file
+    /// and restart the HTTP server if the transport fails. Requires --http;
+    /// the process stays in the foreground for supervisors such as systemd.
+    #[arg(long, requires = "http", conflicts_with = "log_file")]
+    daemon: bool,
+
+    /// Where to write the process id in --daemon mode
+    #[arg(long, value_name = "PATH", requires = "daemon")]
+    pid_file: Option<PathBuf>,
+
+    /// Directory for the daily-rotated log files in --daemon mode
+    #[arg(long, value_name = "DIR", default_value = ".", requires = "daemon")]
+    log_dir: PathBuf,
+
+    /// Record every JSON-RPC message of the stdio session to a new JSON
+    /// Lines file in this directory, for reproducing bugs with --replay
+    #[arg(long, value_name = "DIR", conflicts_with = "http")]
+    record: Option<PathBuf>,
+
+    /// Replay the client's messages of a file written by --record to a
+    /// fresh server, print the server's messages and report the responses
+    /// that differ from the recorded ones
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["http", "record"])]
+    replay: Option<PathBuf>,
 }
 
-#[tokio::main]
-async fn main() -> miette::Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .with_thread_names(true)
-        .with_target(true)
-        .with_line_number(true)
-        .init();
+/// wasm32-wasip1 builds serve stdio only, so they have no transport flags.
+#[cfg(target_os = "wasi")]
+#[derive(Debug, clap::Args)]
+struct TransportArgs {}
+
+/// Commands run instead of starting a server.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Call a tool once and print its result, without an MCP client. The
+    /// server flags (--db, --locale, ...) apply as when serving
+    Run {
+        /// Name of the tool, as listed by tools/list
+        tool: String,
+        /// The tool's arguments, as a JSON object
+        #[arg(long, value_name = "JSON", default_value = "{}")]
+        params: String,
+        /// Print the whole tool result as JSON instead of its text content
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check the engine, sample queries, configured files and the
+    /// reachability of configured integrations, and report each check
+    Doctor {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the tools with their descriptions, as tools/list does
+    Tools {
+        /// Print the manifest as JSON (`{"tools": [...]}`, as in a tools/list
+        /// result), with each tool's input and output schema
+        #[arg(long)]
+        json: bool,
+    },
+    #[command(flatten)]
+    State(StateCommand),
+}
+
+/// Commands run on the --state file.
+#[derive(Debug, Subcommand)]
+enum StateCommand {
+    /// Print the saved queries, scheduled pipelines and settings as a JSON
+    /// bundle, as the export_state tool does
+    ExportState {
+        /// Write the bundle to this file instead of stdout
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Import a bundle written by export-state, as the import_state tool
+    /// does
+    ImportState {
+        /// The bundle to import
+        file: PathBuf,
+        /// Delete the saved queries and pipelines that aren't in the bundle
+        #[arg(long)]
+        replace: bool,
+    },
+}
+
+/// Calls `tool` with the JSON object `params` and prints the result.
+async fn run_tool(
+    config: ServerConfig,
+    tool: &str,
+    params: &str,
+    json: bool,
+) -> miette::Result<()> {
+    let arguments = match serde_json::from_str(params) {
+        Ok(serde_json::Value::Object(arguments)) => arguments,
+        Ok(_) => return Err(miette!("--params must be a JSON object")),
+        Err(e) => return Err(miette!("invalid --params: {e}")),
+    };
+    let result = server::invoke(config, tool, arguments).await?;
+    if json {
+        let json = serde_json::to_string_pretty(&result)
+            .map_err(|e| miette!("failed to serialize the result: {e}"))?;
+        println!("{json}");
+    } else {
+        for content in &result.content {
+            match content.as_text() {
+                Some(text) => println!("{}", text.text),
+                None => println!("{}", serde_json::to_string(content).unwrap_or_default()),
+            }
+        }
+    }
+    if result.is_error == Some(true) {
+        return Err(miette!("{tool} returned an error"));
+    }
+    Ok(())
+}
+
+/// Runs the doctor checks for `config` and prints the report. Fails if a
+/// check failed.
+async fn print_doctor(config: ServerConfig, json: bool) -> miette::Result<()> {
+    let report = server::doctor(config).await;
+    if json {
+        let json = serde_json::to_string_pretty(&report)
+            .map_err(|e| miette!("failed to serialize the report: {e}"))?;
+        println!("{json}");
+    } else {
+        println!("mq-mcp {}", report.version);
+        for check in &report.checks {
+            println!(
+                "[{:<4}] {}: {}",
+                check.status.label(),
+                check.name,
+                check.detail
+            );
+        }
+    }
+    let failed = report
+        .checks
+        .iter()
+        .filter(|check| check.status == server::CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        return Err(miette!("{failed} of {} checks failed", report.checks.len()));
+    }
+    Ok(())
+}
+
+/// Replays `recording` to a server built from `config` and prints the
+/// server's messages. Fails if a response differs from the recorded one.
+#[cfg(not(target_os = "wasi"))]
+async fn print_replay(config: ServerConfig, recording: &Path) -> miette::Result<()> {
+    let report = server::replay(config, recording).await?;
+    for message in &report.messages {
+        println!("{message}");
+    }
+    if !report.differing.is_empty() {
+        let ids: Vec<String> = report.differing.iter().map(|id| id.to_string()).collect();
+        return Err(miette!(
+            "{} of {} responses differ from the recording (ids {})",
+            ids.len(),
+            report.responses,
+            ids.join(", ")
+        ));
+    }
+    eprintln!("all {} recorded responses reproduced", report.responses);
+    Ok(())
+}
+
+/// Prints the tools of a server built from `config`.
+fn print_tools(config: ServerConfig, json: bool) -> miette::Result<()> {
+    let server = server::Server::with_config(config).map_err(|e| miette!("{e}"))?;
+    let tools = server.tools();
+    if json {
+        let manifest = serde_json::to_string_pretty(&serde_json::json!({ "tools": tools }))
+            .map_err(|e| miette!("failed to serialize the tools: {e}"))?;
+        println!("{manifest}");
+        return Ok(());
+    }
+    let width = tools.iter().map(|tool| tool.name.len()).max().unwrap_or(0);
+    for tool in &tools {
+        println!(
+            "{:width$}  {}",
+            tool.name,
+            tool.description.as_deref().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// Runs `command` on the state store of `config`.
+async fn state_command(command: StateCommand, config: ServerConfig) -> miette::Result<()> {
+    let Some(state) = config.state_path.clone() else {
+        return Err(miette!(
+            "state commands need the state file given with --state <PATH>"
+        ));
+    };
+    let server = server::Server::with_config(config)
+        .map_err(|e| miette!("failed to open the state store {}: {e}", state.display()))?;
+    let call = async |tool: &str, arguments: serde_json::Value| {
+        let arguments = arguments.as_object().cloned().unwrap_or_default();
+        let result = server
+            .call(tool, arguments)
+            .await
+            .map_err(|e| match e.data {
+                Some(data) => miette!("{}: {data}", e.message),
+                None => miette!("{}", e.message),
+            })?;
+        if result.is_error == Some(true) {
+            return Err(miette!("{tool} failed"));
+        }
+        result
+            .structured_content
+            .ok_or_else(|| miette!("{tool} returned no structured content"))
+    };
+    match command {
+        StateCommand::ExportState { output } => {
+            let bundle = call("export_state", serde_json::json!({})).await?;
+            let json = serde_json::to_string_pretty(&bundle)
+                .map_err(|e| miette!("failed to serialize the bundle: {e}"))?;
+            match output {
+                Some(path) => std::fs::write(&path, json + "\n")
+                    .map_err(|e| miette!("failed to write {}: {e}", path.display()))?,
+                None => println!("{json}"),
+            }
+        }
+        StateCommand::ImportState { file, replace } => {
+            let bundle: serde_json::Value = std::fs::read_to_string(&file)
+                .map_err(|e| miette!("failed to read {}: {e}", file.display()))
+                .and_then(|text| {
+                    serde_json::from_str(&text)
+                        .map_err(|e| miette!("invalid bundle {}: {e}", file.display()))
+                })?;
+            let imported = call(
+                "import_state",
+                serde_json::json!({ "bundle": bundle, "replace": replace }),
+            )
+            .await?;
+            println!(
+                "imported {} saved queries and {} pipelines into {}",
+                imported["saved_queries"],
+                imported["pipelines"],
+                state.display()
+            );
+            let differing: Vec<&str> = imported["differing_settings"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|name| name.as_str())
+                .collect();
+            if !differing.is_empty() {
+                println!(
+                    "settings that differ from the bundle's (not changed): {}",
+                    differing.join(", ")
+                );
+            }
+        }
+    }
+    Ok(())
+}
 
+#[cfg_attr(not(target_os = "wasi"), tokio::main)]
+#[cfg_attr(target_os = "wasi", tokio::main(flavor = "current_thread"))]
+async fn main() -> miette::Result<()> {
     let cli = Cli::parse();
 
-    if cli.http {
-        server::start_http(
-            HttpConfig {
-                bind: cli.bind,
-                allowed_hosts: cli.allowed_hosts,
-            },
-            cli.db,
-        )
-        .await
+    #[cfg(not(target_os = "wasi"))]
+    let (layer, _log_guard) = if cli.transport.daemon {
+        let (layer, guard) = daemon::log_layer(&cli.transport.log_dir, cli.log_format)?;
+        (layer, Some(guard))
+    } else {
+        let layer = logging::output_layer(cli.log_file.as_deref(), cli.log_format)?;
+        (layer, None)
+    };
+    #[cfg(target_os = "wasi")]
+    let layer = logging::output_layer(cli.log_file.as_deref(), cli.log_format)?;
+    let layers: Vec<LogLayer> = vec![layer];
+    // Dropped last, flushing the spans and metrics not exported yet.
+    #[cfg(feature = "otel")]
+    let exporters = cli
+        .otlp_endpoint
+        .as_deref()
+        .map(telemetry::Telemetry::init)
+        .transpose()?;
+    #[cfg(feature = "otel")]
+    let layers: Vec<LogLayer> = layers
+        .into_iter()
+        .chain(exporters.iter().map(telemetry::Telemetry::layer))
+        .collect();
+    tracing_subscriber::registry().with(layers).init();
+
+    let config = ServerConfig {
+        #[cfg(feature = "db")]
+        db_path: cli.db,
+        #[cfg(feature = "index")]
+        index_path: cli.index,
+        #[cfg(feature = "index")]
+        index_watch: !cli.no_index_watch,
+        state_path: cli.state,
+        resource_threshold: cli.resource_threshold,
+        locale: cli.locale,
+        quotas: Quotas {
+            hourly: cli.hourly_quota,
+            daily: cli.daily_quota,
+        },
+        redactions: cli.redactions,
+        postprocess: cli.postprocess.unwrap_or_default(),
+        provenance: cli.provenance,
+        idempotency_ttl: Some(Duration::from_secs(cli.idempotency_ttl)),
+        hide_deprecated_tools: cli.hide_deprecated_tools,
+        cookbook: cli.cookbook,
+        config_path: cli.config,
+        modules_dir: cli.modules,
+        vault: cli.vault,
+        daily_notes: DailyNotesConfig {
+            folder: cli.daily_folder,
+            format: cli.daily_format,
+            template: cli.daily_template,
+        },
+        templates_dir: cli.templates,
+        #[cfg(feature = "fetch")]
+        fetch: FetchConfig {
+            allowed_headers: cli.allowed_fetch_headers,
+            header_hosts: cli.fetch_header_hosts,
+            cache_ttl: cli.fetch_cache_ttl.map(Duration::from_secs),
+            cache_max_bytes: cli.fetch_cache_size,
+            retries: cli.fetch_retries,
+            breaker_threshold: cli.fetch_breaker_threshold,
+            breaker_cooldown: Duration::from_secs(cli.fetch_breaker_cooldown),
+            ignore_robots: cli.ignore_robots_txt,
+        },
+        #[cfg(feature = "fetch")]
+        github: GithubConfig {
+            token: cli.github_token,
+            api_url: cli.github_api_url,
+        },
+        #[cfg(feature = "fetch")]
+        confluence: ConfluenceConfig {
+            base_url: cli.confluence_url,
+            user: cli.confluence_user,
+            token: cli.confluence_token,
+        },
+        #[cfg(feature = "fetch")]
+        notion: NotionConfig {
+            token: cli.notion_token,
+        },
+        #[cfg(feature = "fetch")]
+        gdoc: GdocConfig {
+            token: cli.google_token,
+        },
+        #[cfg(feature = "fetch")]
+        slack: SlackConfig {
+            token: cli.slack_token,
+        },
+        #[cfg(feature = "fetch")]
+        webhook: WebhookConfig {
+            allowed_hosts: cli.webhook_hosts,
+            secret: cli.webhook_secret,
+        },
+        #[cfg(feature = "pandoc")]
+        pandoc: PandocConfig { path: cli.pandoc },
+        #[cfg(feature = "embeddings")]
+        embeddings: EmbeddingsConfig {
+            url: cli.embeddings_url,
+            model: cli.embeddings_model,
+            api_key: cli.embeddings_api_key,
+        },
+        #[cfg(feature = "s3")]
+        s3: S3Config {
+            bucket: cli.s3_bucket,
+            endpoint: cli.s3_endpoint,
+            region: cli.s3_region,
+            prefix: cli.s3_prefix,
+            access_key_id: cli.s3_access_key_id,
+            secret_access_key: cli.s3_secret_access_key,
+            link_ttl: Some(Duration::from_secs(cli.s3_link_ttl)),
+        },
+    };
+    match cli.command {
+        Some(Command::Run { tool, params, json }) => {
+            return run_tool(config, &tool, &params, json).await;
+        }
+        Some(Command::Doctor { json }) => return print_doctor(config, json).await,
+        Some(Command::Tools { json }) => return print_tools(config, json),
+        Some(Command::State(command)) => return state_command(command, config).await,
+        None => {}
+    }
+
+    serve(cli.transport, config).await
+}
+
+/// Serves over the transport the flags pick.
+#[cfg(not(target_os = "wasi"))]
+async fn serve(transport: TransportArgs, config: ServerConfig) -> miette::Result<()> {
+    let http = HttpConfig {
+        bind: transport.bind,
+        allowed_hosts: transport.allowed_hosts,
+    };
+
+    if transport.daemon {
+        let _pid_file = transport
+            .pid_file
+            .as_deref()
+            .map(daemon::PidFile::create)
+            .transpose()?;
+        daemon::run(|| server::start_http(http.clone(), config.clone())).await
+    } else if transport.http {
+        server::start_http(http, config).await
+    } else if let Some(recording) = &transport.replay {
+        print_replay(config, recording).await
+    } else if let Some(dir) = &transport.record {
+        server::start_recorded(config, dir).await
     } else {
-        server::start(cli.db).await
+        server::start(config).await
     }
 }
+
+#[cfg(target_os = "wasi")]
+async fn serve(_transport: TransportArgs, config: ServerConfig) -> miette::Result<()> {
+    server::start(config).await
+}
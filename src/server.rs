@@ -1,79 +1,373 @@
+mod admonitions;
+mod ast;
+mod changelog;
+#[cfg(feature = "fetch")]
+mod circuit;
+mod citations;
+mod commonmark;
+mod compatibility;
+mod config;
+#[cfg(feature = "fetch")]
+mod confluence;
+mod cookbook;
+mod correlation;
+#[cfg(feature = "fetch")]
+mod cron;
+mod daily;
+#[cfg(feature = "db")]
+mod db;
+#[cfg(any(feature = "index", feature = "embeddings"))]
+mod dedup;
+mod definitions;
+mod deprecation;
+mod diagrams;
+#[cfg(feature = "fetch")]
+mod digest;
+mod doctor;
+mod dry_run;
+mod duplicates;
+mod editing;
+mod elicit;
+mod email;
+#[cfg(feature = "embeddings")]
+mod embeddings;
+mod emoji;
+mod engine;
+#[cfg(feature = "fetch")]
+mod fetch;
+#[cfg(feature = "fetch")]
+mod gdoc;
+#[cfg(feature = "fetch")]
+mod github;
+mod graph;
+mod i18n;
+mod idempotency;
+#[cfg(feature = "index")]
+mod index;
+mod infer;
+mod instructions;
+mod invoke;
+mod keywords;
+mod language;
+mod lint;
+mod math;
+#[cfg(feature = "otel")]
+mod metrics;
+#[cfg(feature = "fetch")]
+mod notion;
+mod openapi;
+mod outline;
+mod output;
+#[cfg(feature = "fetch")]
+mod page_cache;
+#[cfg(feature = "pandoc")]
+mod pandoc;
+mod paths;
+mod pipeline;
+mod plaintext;
+mod policy;
+mod postprocess;
+mod profile;
+mod protocol;
+mod provenance;
+mod quota;
+mod ranking;
+#[cfg(not(target_os = "wasi"))]
+mod record;
+mod redact;
+mod release_notes;
+mod reload;
+mod report;
+mod results;
+#[cfg(feature = "fetch")]
+mod robots;
+mod roots;
+#[cfg(feature = "s3")]
+mod s3;
+mod sampling;
+mod sanitize;
+mod saved_queries;
+#[cfg(feature = "fetch")]
+mod schedule;
+mod scratchpad;
+mod sections;
+#[cfg(feature = "fetch")]
+mod slack;
+mod sort;
+mod source_map;
+mod state;
+mod stats;
+mod stdio;
+mod storage;
+mod suggest;
+mod summaries;
+mod tasks;
+mod templates;
+mod trace;
+mod translation;
+mod tutorial;
+mod variables;
+mod vault;
+mod vault_query;
+#[cfg(feature = "fetch")]
+mod webhook;
+#[cfg(feature = "fetch")]
+mod wiki;
+mod wikilinks;
+
+pub use config::ServerConfig;
+#[cfg(feature = "fetch")]
+pub use confluence::ConfluenceConfig;
+pub use daily::{DEFAULT_DAILY_FORMAT, DailyNotesConfig};
+pub use doctor::{Check, CheckStatus, DoctorReport, doctor};
+#[cfg(feature = "embeddings")]
+pub use embeddings::EmbeddingsConfig;
+pub use emoji::{EmojiMode, EmojiPlatform};
+#[cfg(feature = "fetch")]
+pub use fetch::{
+    DEFAULT_BREAKER_COOLDOWN, DEFAULT_BREAKER_THRESHOLD, DEFAULT_CACHE_MAX_BYTES,
+    DEFAULT_FETCH_RETRIES, FetchConfig,
+};
+#[cfg(feature = "fetch")]
+pub use gdoc::GdocConfig;
+#[cfg(feature = "fetch")]
+pub use github::GithubConfig;
+pub use i18n::Locale;
+pub use idempotency::DEFAULT_IDEMPOTENCY_TTL;
+pub use invoke::invoke;
+#[cfg(feature = "fetch")]
+pub use notion::NotionConfig;
+#[cfg(feature = "pandoc")]
+pub use pandoc::PandocConfig;
+pub use postprocess::{LineEnding, PostProcess};
+pub use quota::Quotas;
+#[cfg(not(target_os = "wasi"))]
+pub use record::{ReplayReport, replay, start_recorded};
+pub use redact::RedactionRule;
+#[cfg(feature = "s3")]
+pub use s3::{DEFAULT_LINK_TTL, S3Config};
+#[cfg(feature = "fetch")]
+pub use slack::SlackConfig;
+pub use stdio::start;
+#[cfg(feature = "fetch")]
+pub use webhook::WebhookConfig;
+
+use engine::{QueryEngine, QueryValue};
+#[cfg(not(target_os = "wasi"))]
 use miette::miette;
+use output::{
+    FunctionInfo, FunctionsOutput, LocatedOutput, LocatedValue, QueryOutput, SelectorInfo,
+    SelectorsOutput, json_result, output_schema, query_result, structured_result,
+};
+use quota::Usage;
+use results::ResultStore;
+#[cfg(not(target_os = "wasi"))]
+use rmcp::transport::streamable_http_server::{
+    StreamableHttpServerConfig, StreamableHttpService, session::local::LocalSessionManager,
+};
 use rmcp::{
-    ErrorData, ServerHandler, ServiceExt,
-    handler::server::{tool::ToolRouter, wrapper::Parameters},
-    model::{CallToolResult, ContentBlock, ProtocolVersion, ServerCapabilities, ServerInfo},
-    schemars, tool, tool_handler, tool_router,
-    transport::streamable_http_server::{
-        StreamableHttpServerConfig, StreamableHttpService, session::local::LocalSessionManager,
+    ErrorData, RoleServer, ServerHandler,
+    handler::server::{
+        tool::{ToolCallContext, ToolRouter},
+        wrapper::Parameters,
     },
+    model::{
+        CallToolRequestParams, CallToolResult, ContentBlock, InitializeRequestParams,
+        InitializeResult, ListResourcesResult, ListToolsResult, PaginatedRequestParams,
+        ProtocolVersion, ReadResourceRequestParams, ReadResourceResult, ResourceContents,
+        ServerCapabilities, ServerInfo, SubscribeRequestParams, Tool, UnsubscribeRequestParams,
+    },
+    schemars,
+    service::{NotificationContext, RequestContext},
+    tool, tool_router,
 };
-use std::{
-    path::{Path, PathBuf},
-    sync::{Arc, Mutex},
-};
-use tokio::io::{stdin, stdout};
+use roots::Roots;
+use std::{path::PathBuf, sync::Arc, time::Instant};
+use suggest::query_error;
+use tracing::Instrument;
 type McpResult = Result<CallToolResult, ErrorData>;
 
-/// Shared, mutable handle to the loaded `mq-db` store. Guarded by a plain
-/// (synchronous) `Mutex` — DB tool methods are synchronous, so there's no
-/// `.await` while held, and this avoids pulling in tokio's `sync` feature.
-type SharedDb = Arc<Mutex<mq_db::DocumentStore>>;
-
-/// Loads the store at `db_path` if it exists, otherwise starts with an
-/// empty one (so `db_index` can populate and later save it to that path).
-fn load_or_create_db(db_path: &Path) -> mq_db::DocumentStore {
-    if db_path.exists() {
-        match mq_db::DocumentStore::load(db_path) {
-            Ok(store) => return store,
-            Err(e) => tracing::error!(
-                "failed to load database at {}: {e} — starting with an empty store",
-                db_path.display()
-            ),
-        }
+/// What every session of a server shares: the database, page cache, search
+/// index, scheduled pipelines, state store, call statistics and reloadable
+/// settings.
+#[derive(Clone)]
+struct Shared {
+    #[cfg(feature = "db")]
+    db: db::SharedDb,
+    #[cfg(feature = "fetch")]
+    fetcher: fetch::Fetcher,
+    #[cfg(feature = "index")]
+    index: index::SearchIndex,
+    #[cfg(feature = "fetch")]
+    scheduler: schedule::Scheduler,
+    storage: storage::Storage,
+    stats: stats::Stats,
+    live: reload::Live,
+}
+
+impl Shared {
+    fn open(config: &ServerConfig) -> Result<Self, String> {
+        Ok(Self {
+            #[cfg(feature = "db")]
+            db: db::open(config.db_path.as_deref()),
+            #[cfg(feature = "fetch")]
+            fetcher: fetch::Fetcher::new(&config.fetch),
+            #[cfg(feature = "index")]
+            index: Default::default(),
+            #[cfg(feature = "fetch")]
+            scheduler: Default::default(),
+            storage: storage::Storage::open(config.state_path.as_deref())?,
+            stats: Default::default(),
+            live: reload::Live::open(config)?,
+        })
     }
-    mq_db::DocumentStore::new()
 }
 
-#[derive(Clone, Default)]
-pub struct Server {
-    pub tool_router: ToolRouter<Self>,
-    /// Configured `--db` path, if any. `None` means no database was
-    /// configured at startup — DB tools report a clear error rather than
-    /// silently operating on an empty, unsaveable store.
-    db_path: Option<PathBuf>,
-    db: SharedDb,
+/// The section-module call selecting the sections whose heading contains
+/// `title`.
+fn section_filter(title: &str) -> String {
+    let escaped = title.replace('\\', r"\\").replace('"', r#"\""#);
+    format!(r#"section::section("{escaped}")"#)
 }
 
-#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
-struct DbSqlInput {
-    #[schemars(
-        description = "SQL query to run against the loaded mq-db database (SELECT, CREATE TABLE, INSERT INTO, DROP TABLE, DESC, SHOW TABLES). Virtual schema: documents(id, path, title, tags), blocks(id, document_id, block_type, content, pre, post, depth, lang, properties)."
-    )]
-    query: String,
+/// Builds the section-module query extracting the section(s) whose heading
+/// contains `title`.
+fn section_query(title: &str) -> String {
+    format!(
+        r#"import "section" | {} | section::collect()"#,
+        section_filter(title)
+    )
 }
 
-#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
-struct DbMqInput {
-    #[schemars(
-        description = "mq program to run against every document in the loaded mq-db database (only documents indexed from a file path — not ones added as raw strings)"
-    )]
-    code: String,
+/// Creates an engine set up with the current call's engine profile (its
+/// user modules and limits) and session variables, as used by every query
+/// tool.
+fn new_engine() -> engine::Engine {
+    let mut engine = engine::Engine::new();
+    profile::setup(&mut engine);
+    variables::define(&engine);
+    engine
 }
 
-#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
-struct DbIndexInput {
-    #[schemars(description = "Markdown files or directories to (re)index into the database")]
-    paths: Vec<String>,
-    #[schemars(description = "Recursively walk directories (default: false)")]
-    recursive: Option<bool>,
-    #[schemars(
-        description = "Remove catalogued documents whose path is no longer present in `paths` (default: false)"
-    )]
-    prune: Option<bool>,
+/// Parses markdown (or HTML) tool input into its top-level nodes.
+fn parse_markdown(markdown: &str) -> Result<Vec<mq_markdown::Node>, ErrorData> {
+    let parsed = mq_markdown::Markdown::from_html_str(markdown).map_err(|e| {
+        ErrorData::parse_error(
+            "Failed to parse markdown",
+            Some(serde_json::Value::String(e.to_string())),
+        )
+    })?;
+    Ok(parsed.nodes)
 }
 
-#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+/// Parses tool input as markdown only, without the HTML conversion of
+/// [`parse_markdown`], which reads `<` in code and formulas as tags and
+/// can shift line numbers.
+fn parse_markdown_only(markdown: &str) -> Result<Vec<mq_markdown::Node>, ErrorData> {
+    let parsed = mq_markdown::Markdown::from_markdown_str(markdown).map_err(|e| {
+        ErrorData::parse_error(
+            "Failed to parse markdown",
+            Some(serde_json::Value::String(e.to_string())),
+        )
+    })?;
+    Ok(parsed.nodes)
+}
+
+/// Splits top-level nodes into runs where each node starts where the
+/// previous one ends. Paragraphs are flattened into their inline nodes,
+/// so a run is a paragraph, or a single block node.
+fn inline_runs(nodes: &[mq_markdown::Node]) -> impl Iterator<Item = &[mq_markdown::Node]> {
+    nodes.chunk_by(|a, b| {
+        matches!(
+            (a.position(), b.position()),
+            (Some(a), Some(b)) if a.end == b.start
+        )
+    })
+}
+
+/// Parses markdown (or HTML) tool input into runtime values, one per
+/// top-level node.
+fn parse_input(markdown: &str) -> Result<Vec<engine::Value>, ErrorData> {
+    Ok(parse_markdown(markdown)?
+        .into_iter()
+        .map(engine::Value::from)
+        .collect())
+}
+
+/// Runs `query` once per node and returns the non-empty results with the
+/// index of the input they came from, ordered per `sort`, and the number of
+/// results before empty ones were dropped.
+fn eval_nodes_sorted(
+    nodes: Vec<mq_markdown::Node>,
+    query: &str,
+    sort: &sort::SortOptions,
+) -> Result<(Vec<(usize, engine::Value)>, usize), ErrorData> {
+    let values = new_engine()
+        .eval(query, nodes.into_iter().map(engine::Value::from))
+        .map_err(|e| query_error(query, e))?;
+    let count = values.len();
+    let mut values: Vec<_> = values
+        .into_iter()
+        .enumerate()
+        .filter(|(_, value)| !value.is_blank())
+        .collect();
+    sort.sort(&mut values)?;
+    Ok((values, count))
+}
+
+#[derive(Clone, Default)]
+pub struct Server {
+    pub tool_router: ToolRouter<Self>,
+    /// Startup configuration. `config.db_path == None` means no database
+    /// was configured — DB tools report a clear error rather than silently
+    /// operating on an empty, unsaveable store.
+    config: Arc<ServerConfig>,
+    #[cfg(feature = "db")]
+    db: db::SharedDb,
+    /// Large results offloaded as resources (see
+    /// [`ServerConfig::resource_threshold`]).
+    results: ResultStore,
+    /// Workspace roots advertised by the client; file paths passed to tools
+    /// are scoped to them.
+    roots: Roots,
+    /// Bytes processed by this session, checked against
+    /// [`ServerConfig::quotas`].
+    usage: Usage,
+    /// HTTP client and page cache for the URL-based tools.
+    #[cfg(feature = "fetch")]
+    fetcher: fetch::Fetcher,
+    /// The on-disk search index (see [`ServerConfig::index_path`]).
+    #[cfg(feature = "index")]
+    index: index::SearchIndex,
+    /// Results of mutating calls made with an `idempotency_key`, replayed
+    /// when the key is reused.
+    idempotency: idempotency::IdempotencyCache,
+    /// Query results stored with `set_variable`, defined in later queries.
+    variables: variables::Variables,
+    /// This session's scratchpad, the `mq://scratchpad` resource.
+    scratchpad: scratchpad::Scratchpad,
+    /// Pipelines saved with `schedule_pipeline`, shared by every session.
+    #[cfg(feature = "fetch")]
+    scheduler: schedule::Scheduler,
+    /// Pipeline resources this session subscribed to.
+    #[cfg(feature = "fetch")]
+    subscriptions: schedule::Subscriptions,
+    /// Saved queries, pipeline definitions and the audit log, shared by
+    /// every session (see [`ServerConfig::state_path`]).
+    storage: storage::Storage,
+    /// Sections embedded by this session, for `semantic_search`.
+    #[cfg(feature = "embeddings")]
+    embeddings: embeddings::EmbeddingStore,
+    /// Call counts and latencies, shared by every session.
+    stats: stats::Stats,
+    /// Counts this server as an open session in `stats` once the client
+    /// has initialized it.
+    session: stats::Session,
+    /// The config file and user modules, reloaded when they change.
+    live: reload::Live,
+}
+
+#[derive(Debug, Default, rmcp::serde::Deserialize, schemars::JsonSchema)]
 struct QueryForHtml {
     #[schemars(description = "The HTML to process")]
     html: String,
@@ -81,9 +375,11 @@ struct QueryForHtml {
         description = "The mq query to execute. Selectors and functions listed in the available_selectors and available_functions tools can be used."
     )]
     query: Option<String>,
+    #[serde(flatten)]
+    sanitize: sanitize::SanitizeOptions,
 }
 
-#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+#[derive(Debug, Default, rmcp::serde::Deserialize, schemars::JsonSchema)]
 struct QueryForMarkdown {
     #[schemars(description = "The markdown to process")]
     markdown: String,
@@ -91,6 +387,10 @@ struct QueryForMarkdown {
         description = "The mq query to execute. Selectors and functions listed in the available_selectors and available_functions tools can be used ."
     )]
     query: String,
+    #[serde(flatten)]
+    math: math::MathOptions,
+    #[serde(flatten)]
+    sort: sort::SortOptions,
 }
 
 #[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
@@ -99,6 +399,14 @@ struct MarkdownInput {
     markdown: String,
 }
 
+#[derive(Debug, Default, rmcp::serde::Deserialize, schemars::JsonSchema)]
+struct ExtractInput {
+    #[schemars(description = "The markdown content to process")]
+    markdown: String,
+    #[serde(flatten)]
+    sort: sort::SortOptions,
+}
+
 #[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
 struct ExtractSectionInput {
     #[schemars(description = "The markdown content to process")]
@@ -107,410 +415,437 @@ struct ExtractSectionInput {
     title: String,
 }
 
+/// Converts `html` to markdown nodes, sanitized per `sanitize`.
+fn convert_html(
+    html: &str,
+    sanitize: &sanitize::SanitizeOptions,
+) -> Result<Vec<mq_markdown::Node>, ErrorData> {
+    let markdown = mq_markdown::Markdown::from_html_str_with_options(
+        &sanitize.preprocess(html),
+        sanitize.conversion_options(),
+    )
+    .map_err(|e| {
+        ErrorData::parse_error(
+            "Failed to parse html",
+            Some(serde_json::Value::String(e.to_string())),
+        )
+    })?;
+    Ok(sanitize.sanitize(markdown.nodes))
+}
+
 impl Server {
     fn eval_query(&self, markdown: &str, query: &str) -> McpResult {
-        let mut engine = mq_lang::DefaultEngine::default();
-        engine.load_builtin_module();
+        query_result(self.query_values(markdown, query)?)
+    }
 
-        let parsed = mq_markdown::Markdown::from_html_str(markdown).map_err(|e| {
-            ErrorData::parse_error(
-                "Failed to parse markdown",
-                Some(serde_json::Value::String(e.to_string())),
-            )
-        })?;
+    fn eval_query_sorted(
+        &self,
+        markdown: &str,
+        query: &str,
+        sort: &sort::SortOptions,
+    ) -> McpResult {
+        query_result(self.query_nodes_sorted(parse_markdown(markdown)?, query, sort)?)
+    }
 
-        let values = engine
+    /// Converts `html` to markdown (sanitized per `sanitize`) and runs
+    /// `query` once per top-level node.
+    fn eval_html(
+        &self,
+        html: &str,
+        query: &str,
+        sanitize: &sanitize::SanitizeOptions,
+    ) -> McpResult {
+        let values = new_engine()
             .eval(
                 query,
-                parsed.nodes.into_iter().map(mq_lang::RuntimeValue::from),
+                convert_html(html, sanitize)?
+                    .into_iter()
+                    .map(engine::Value::from),
             )
-            .map_err(|e| {
-                ErrorData::invalid_request(
-                    "Failed to query",
-                    Some(serde_json::Value::String(e.to_string())),
-                )
-            })?;
+            .map_err(|e| query_error(query, e))?;
 
-        Ok(CallToolResult::success(
+        query_result(
             values
                 .into_iter()
                 .filter_map(|value| {
-                    if value.is_none() || value.is_empty() {
+                    if value.is_blank() {
                         None
                     } else {
-                        Some(ContentBlock::text(value.to_string()))
+                        Some(value.to_string())
                     }
                 })
-                .collect(),
-        ))
+                .collect::<Vec<_>>(),
+        )
     }
 
     fn eval_aggregate(&self, markdown: &str, query: &str) -> McpResult {
-        let mut engine = mq_lang::DefaultEngine::default();
-        engine.load_builtin_module();
+        query_result(self.aggregate_values(markdown, query)?)
+    }
 
-        let parsed = mq_markdown::Markdown::from_html_str(markdown).map_err(|e| {
-            ErrorData::parse_error(
-                "Failed to parse markdown",
-                Some(serde_json::Value::String(e.to_string())),
-            )
-        })?;
+    /// Runs `query` once per top-level node and returns the non-empty
+    /// results rendered as markdown.
+    fn query_values(&self, markdown: &str, query: &str) -> Result<Vec<String>, ErrorData> {
+        self.query_nodes(parse_markdown(markdown)?, query)
+    }
+
+    /// Runs `query` once per node of already parsed input. Results are in
+    /// document order.
+    fn query_nodes(
+        &self,
+        nodes: Vec<mq_markdown::Node>,
+        query: &str,
+    ) -> Result<Vec<String>, ErrorData> {
+        self.query_nodes_sorted(nodes, query, &sort::SortOptions::default())
+    }
 
-        let all_nodes: Vec<mq_lang::RuntimeValue> = parsed
-            .nodes
+    /// [`Self::query_nodes`], ordering the results per `sort`.
+    fn query_nodes_sorted(
+        &self,
+        nodes: Vec<mq_markdown::Node>,
+        query: &str,
+        sort: &sort::SortOptions,
+    ) -> Result<Vec<String>, ErrorData> {
+        let citations = provenance::Citations::current(&nodes);
+        let (values, count) = eval_nodes_sorted(nodes, query, sort)?;
+        let aligned = citations
+            .as_ref()
+            .is_some_and(|citations| citations.aligned(count));
+
+        Ok(values
             .into_iter()
-            .map(mq_lang::RuntimeValue::from)
-            .collect();
-        let input = mq_lang::RuntimeValue::Array(all_nodes);
+            .map(|(index, value)| {
+                let text = value.to_string();
+                match &citations {
+                    Some(citations) => citations.cite(text, &value, aligned.then_some(index)),
+                    None => text,
+                }
+            })
+            .collect())
+    }
+
+    /// Runs `query` once over all top-level nodes as a single array (as the
+    /// section module expects), flattening array results.
+    fn aggregate_values(&self, markdown: &str, query: &str) -> Result<Vec<String>, ErrorData> {
+        let input = engine::Value::array(parse_input(markdown)?);
 
-        let values = engine
+        let values = new_engine()
             .eval(query, std::iter::once(input))
-            .map_err(|e| {
-                ErrorData::invalid_request(
-                    "Failed to query",
-                    Some(serde_json::Value::String(e.to_string())),
-                )
-            })?;
+            .map_err(|e| query_error(query, e))?;
 
-        Ok(CallToolResult::success(
-            values
-                .into_iter()
-                .flat_map(|value| match value {
-                    mq_lang::RuntimeValue::Array(arr) => arr
-                        .into_iter()
-                        .filter(|v| !v.is_none() && !v.is_empty())
-                        .map(|v| ContentBlock::text(v.to_string()))
-                        .collect::<Vec<_>>(),
-                    v if v.is_none() || v.is_empty() => vec![],
-                    v => vec![ContentBlock::text(v.to_string())],
-                })
-                .collect(),
-        ))
+        Ok(values
+            .into_iter()
+            .flat_map(|value| match value.into_array() {
+                Ok(arr) => arr
+                    .into_iter()
+                    .filter(|v| !v.is_blank())
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>(),
+                Err(v) if v.is_blank() => vec![],
+                Err(v) => vec![v.to_string()],
+            })
+            .collect())
     }
 }
 
-#[derive(Debug, rmcp::serde::Serialize, rmcp::serde::Deserialize, schemars::JsonSchema)]
-struct FunctionInfo {
-    #[schemars(description = "The function name")]
-    name: String,
-    #[schemars(description = "The function description")]
-    description: String,
-    #[schemars(description = "The function parameters")]
-    params: Vec<String>,
-    #[schemars(description = "Whether this is a built-in function")]
-    is_builtin: bool,
-}
-
-#[derive(Debug, rmcp::serde::Serialize, rmcp::serde::Deserialize, schemars::JsonSchema)]
-struct SelectorInfo {
-    #[schemars(description = "The function name")]
-    name: String,
-    #[schemars(description = "The function description")]
-    description: String,
-    #[schemars(description = "The function parameters")]
-    params: Vec<String>,
-}
-
 #[tool_router]
 impl Server {
+    /// Builds a server with the default configuration and the database at
+    /// `db_path`, which is ignored without the `db` feature.
     pub fn new(db_path: Option<PathBuf>) -> Result<Self, Box<dyn std::error::Error>> {
-        let db = db_path
-            .as_deref()
-            .map(load_or_create_db)
-            .unwrap_or_default();
-        Ok(Self {
-            tool_router: Self::tool_router(),
+        #[cfg(not(feature = "db"))]
+        let _ = db_path;
+        Self::with_config(ServerConfig {
+            #[cfg(feature = "db")]
             db_path,
-            db: Arc::new(Mutex::new(db)),
+            ..Default::default()
         })
     }
 
-    /// Builds a new `Server` sharing an already-loaded database — used by
-    /// the Streamable HTTP transport, which constructs one `Server` per
-    /// session and would otherwise reload the store from disk every time.
-    fn with_shared_db(db_path: Option<PathBuf>, db: SharedDb) -> Self {
-        Self {
-            tool_router: Self::tool_router(),
-            db_path,
-            db,
-        }
+    pub fn with_config(config: ServerConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let shared = Shared::open(&config)?;
+        Ok(Self::with_shared(Arc::new(config), shared))
     }
 
-    /// Returns the locked store, or a descriptive error if no `--db` path
-    /// was configured at startup.
-    fn require_db(&self) -> Result<std::sync::MutexGuard<'_, mq_db::DocumentStore>, ErrorData> {
-        if self.db_path.is_none() {
-            return Err(ErrorData::invalid_request(
-                "no database configured — restart mq-mcp with --db <path> to enable db_* tools",
-                None,
-            ));
+    /// Builds a new `Server` sharing an already-loaded database (and the
+    /// page cache, search index, scheduled pipelines and state store) —
+    /// used by the Streamable HTTP transport, which constructs one `Server`
+    /// per session and would otherwise reload the store from disk every
+    /// time.
+    fn with_shared(config: Arc<ServerConfig>, shared: Shared) -> Self {
+        let tool_router = Self::tool_router()
+            + Self::trace_router()
+            + Self::lint_router()
+            + Self::compatibility_router()
+            + Self::infer_router()
+            + Self::tutorial_router()
+            + Self::cookbook_router()
+            + Self::sampling_router()
+            + Self::email_router()
+            + Self::openapi_router()
+            + Self::changelog_router()
+            + Self::release_notes_router()
+            + Self::plaintext_router()
+            + Self::ast_router()
+            + Self::diagrams_router()
+            + Self::math_router()
+            + Self::language_router()
+            + Self::translation_router()
+            + Self::keywords_router()
+            + Self::duplicates_router()
+            + Self::ranking_router()
+            + Self::variables_router()
+            + Self::scratchpad_router()
+            + Self::report_router()
+            + Self::editing_router()
+            + Self::outline_router()
+            + Self::summaries_router()
+            + Self::definitions_router()
+            + Self::admonitions_router()
+            + Self::wikilinks_router()
+            + Self::graph_router()
+            + Self::vault_query_router()
+            + Self::tasks_router()
+            + Self::daily_router()
+            + Self::templates_router()
+            + Self::citations_router()
+            + Self::pipeline_router()
+            + Self::saved_queries_router()
+            + Self::state_router()
+            + Self::stats_router();
+        #[cfg(feature = "fetch")]
+        let tool_router = tool_router
+            + Self::fetch_router()
+            + Self::wiki_router()
+            + Self::github_router()
+            + Self::confluence_router()
+            + Self::notion_router()
+            + Self::gdoc_router()
+            + Self::slack_router()
+            + Self::schedule_router();
+        #[cfg(feature = "db")]
+        let tool_router = tool_router + Self::db_router();
+        #[cfg(feature = "index")]
+        let tool_router = tool_router + Self::index_router();
+        #[cfg(feature = "pandoc")]
+        let tool_router = tool_router + Self::pandoc_router();
+        #[cfg(feature = "embeddings")]
+        let tool_router = tool_router + Self::embeddings_router();
+        Self {
+            tool_router,
+            config,
+            #[cfg(feature = "db")]
+            db: shared.db,
+            results: ResultStore::default(),
+            roots: Roots::default(),
+            usage: Usage::default(),
+            #[cfg(feature = "fetch")]
+            fetcher: shared.fetcher,
+            #[cfg(feature = "index")]
+            index: shared.index,
+            idempotency: Default::default(),
+            variables: Default::default(),
+            scratchpad: Default::default(),
+            #[cfg(feature = "fetch")]
+            scheduler: shared.scheduler,
+            #[cfg(feature = "fetch")]
+            subscriptions: Default::default(),
+            storage: shared.storage,
+            #[cfg(feature = "embeddings")]
+            embeddings: Default::default(),
+            stats: shared.stats,
+            session: Default::default(),
+            live: shared.live,
         }
-        Ok(self.db.lock().unwrap_or_else(|e| e.into_inner()))
-    }
-
-    #[tool(
-        description = "Run a read-only SQL query against the loaded mq-db database and return matching rows as JSON. Requires mq-mcp to have been started with --db <path>."
-    )]
-    fn db_sql(&self, Parameters(DbSqlInput { query }): Parameters<DbSqlInput>) -> McpResult {
-        let store = self.require_db()?;
-        let engine = mq_db::SqlEngine::new(&store).map_err(|e| {
-            ErrorData::internal_error(
-                "Failed to build SQL engine",
-                Some(serde_json::Value::String(e.to_string())),
-            )
-        })?;
-        let out = engine.execute(&query).map_err(|e| {
-            ErrorData::invalid_request(
-                "SQL query failed",
-                Some(serde_json::Value::String(e.to_string())),
-            )
-        })?;
-        Ok(CallToolResult::success(vec![ContentBlock::text(out.to_json())]))
-    }
-
-    #[tool(
-        description = "Run an mq program against every document in the loaded mq-db database and return the results. Requires mq-mcp to have been started with --db <path>."
-    )]
-    fn db_mq(&self, Parameters(DbMqInput { code }): Parameters<DbMqInput>) -> McpResult {
-        let store = self.require_db()?;
-        let results = mq_db::MqEngine::eval_store(&code, &store).map_err(|e| {
-            ErrorData::invalid_request(
-                "mq query failed",
-                Some(serde_json::Value::String(e.to_string())),
-            )
-        })?;
-        Ok(CallToolResult::success(
-            results.into_iter().map(ContentBlock::text).collect(),
-        ))
-    }
-
-    #[tool(
-        description = "List every document currently indexed in the loaded mq-db database (id, path, title, tags, block count). Requires mq-mcp to have been started with --db <path>."
-    )]
-    fn db_list_documents(&self) -> McpResult {
-        let store = self.require_db()?;
-        let docs: Vec<serde_json::Value> = store
-            .documents()
-            .iter()
-            .map(|d| {
-                serde_json::json!({
-                    "id": d.id,
-                    "path": d.path.as_ref().and_then(|p| p.to_str()),
-                    "title": d.zone_maps.title,
-                    "tags": d.zone_maps.tags,
-                    "block_count": d.block_count,
-                })
-            })
-            .collect();
-        Ok(CallToolResult::success(vec![ContentBlock::text(
-            serde_json::to_string(&docs).unwrap_or_default(),
-        )]))
-    }
-
-    #[tool(
-        description = "Return block-type and code-language statistics for the loaded mq-db database. Requires mq-mcp to have been started with --db <path>."
-    )]
-    fn db_stats(&self) -> McpResult {
-        let store = self.require_db()?;
-        let stats = store.stats();
-        let json = serde_json::json!({
-            "documents": stats.documents,
-            "blocks": stats.blocks,
-            "block_type_counts": stats.block_type_counts.iter()
-                .map(|(bt, count)| serde_json::json!({"block_type": bt.as_str(), "count": count}))
-                .collect::<Vec<_>>(),
-            "code_lang_counts": stats.code_lang_counts.iter()
-                .map(|(lang, count)| serde_json::json!({"lang": lang, "count": count}))
-                .collect::<Vec<_>>(),
-        });
-        Ok(CallToolResult::success(vec![ContentBlock::text(
-            json.to_string(),
-        )]))
     }
 
     #[tool(
-        description = "Index or re-index Markdown files/directories into the loaded mq-db database, then persist it to the configured --db path. Skips files whose content hasn't changed since the last index; use `prune` to drop catalogued documents whose file no longer exists. Requires mq-mcp to have been started with --db <path>."
+        description = "Executes an mq query on the provided HTML content and returns the result as Markdown. Selectors and functions listed in the available_selectors and available_functions tools can be used. Scripts are dropped and surviving raw HTML is stripped of active content; use `strict` to drop all raw HTML from untrusted pages.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
     )]
-    fn db_index(
+    fn html_to_markdown(
         &self,
-        Parameters(DbIndexInput {
-            paths,
-            recursive,
-            prune,
-        }): Parameters<DbIndexInput>,
+        Parameters(QueryForHtml {
+            html,
+            query,
+            sanitize,
+        }): Parameters<QueryForHtml>,
     ) -> McpResult {
-        let db_path = self.db_path.clone().ok_or_else(|| {
-            ErrorData::invalid_request(
-                "no database configured — restart mq-mcp with --db <path> to enable db_* tools",
-                None,
-            )
-        })?;
-
-        let files = mq_db::discover::collect_markdown_files(
-            &paths.iter().map(PathBuf::from).collect::<Vec<_>>(),
-            recursive.unwrap_or(false),
-        );
-        if files.is_empty() {
-            return Err(ErrorData::invalid_request(
-                "No Markdown files found in the given paths",
-                None,
-            ));
-        }
-
-        let mut store = self.db.lock().unwrap_or_else(|e| e.into_inner());
-        let report = store
-            .reindex_paths(&files, prune.unwrap_or(false))
-            .map_err(|e| {
-                ErrorData::internal_error(
-                    "Reindex failed",
-                    Some(serde_json::Value::String(e.to_string())),
-                )
-            })?;
-        store.save(&db_path).map_err(|e| {
-            ErrorData::internal_error(
-                "Failed to save database",
-                Some(serde_json::Value::String(e.to_string())),
-            )
-        })?;
-
-        let json = serde_json::json!({
-            "added": report.added.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>(),
-            "updated": report.updated.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>(),
-            "unchanged": report.unchanged,
-            "removed": report.removed.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>(),
-            "failed": report.failed.iter().map(|(p, e)| serde_json::json!({"path": p.to_string_lossy(), "error": e})).collect::<Vec<_>>(),
-        });
-        Ok(CallToolResult::success(vec![ContentBlock::text(
-            json.to_string(),
-        )]))
+        self.eval_html(&html, query.as_deref().unwrap_or("identity()"), &sanitize)
     }
 
     #[tool(
-        description = "Executes an mq query on the provided HTML content and returns the result as Markdown. Selectors and functions listed in the available_selectors and available_functions tools can be used."
+        description = "Extract from markdown content using a custom mq query. Selectors and functions listed in the available_selectors and available_functions tools can be used.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
     )]
-    fn html_to_markdown(
+    fn extract_markdown(
         &self,
-        Parameters(QueryForHtml { html, query }): Parameters<QueryForHtml>,
+        Parameters(QueryForMarkdown {
+            markdown,
+            query,
+            math,
+            sort,
+        }): Parameters<QueryForMarkdown>,
     ) -> McpResult {
-        let mut engine = mq_lang::DefaultEngine::default();
-        engine.load_builtin_module();
-
-        let markdown = mq_markdown::Markdown::from_html_str(&html).map_err(|e| {
-            ErrorData::parse_error(
-                "Failed to parse html",
-                Some(serde_json::Value::String(e.to_string())),
-            )
-        })?;
-        let values = engine
-            .eval(
-                &query.unwrap_or("identity()".to_string()),
-                markdown.nodes.into_iter().map(mq_lang::RuntimeValue::from),
-            )
-            .map_err(|e| {
-                ErrorData::invalid_request(
-                    "Failed to query",
-                    Some(serde_json::Value::String(e.to_string())),
-                )
-            })?;
-
-        Ok(CallToolResult::success(
-            values
-                .into_iter()
-                .filter_map(|value| {
-                    if value.is_none() || value.is_empty() {
-                        None
-                    } else {
-                        Some(ContentBlock::text(value.to_string()))
-                    }
-                })
-                .collect::<Vec<_>>(),
-        ))
+        query_result(self.query_nodes_sorted(math.parse(&markdown)?, &query, &sort)?)
     }
 
     #[tool(
-        description = "Extract from markdown content using a custom mq query. Selectors and functions listed in the available_selectors and available_functions tools can be used."
+        description = "Extract from markdown content using a custom mq query, returning each result with the lines of the input it was extracted from. Selectors and functions listed in the available_selectors and available_functions tools can be used.",
+        output_schema = output_schema::<LocatedOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
     )]
-    fn extract_markdown(
+    fn extract_markdown_v2(
         &self,
-        Parameters(QueryForMarkdown { markdown, query }): Parameters<QueryForMarkdown>,
+        Parameters(QueryForMarkdown {
+            markdown,
+            query,
+            math,
+            sort,
+        }): Parameters<QueryForMarkdown>,
     ) -> McpResult {
-        self.eval_query(&markdown, &query)
+        let nodes = math.parse(&markdown)?;
+        let positions: Vec<_> = nodes.iter().map(mq_markdown::Node::position).collect();
+        let (values, count) = eval_nodes_sorted(nodes, &query, &sort)?;
+        // Computed values (strings, numbers) have no position of their own;
+        // they take that of their input node unless the query aggregated.
+        let aligned = count == positions.len();
+        let values: Vec<LocatedValue> = values
+            .into_iter()
+            .map(|(index, value)| {
+                let position = value
+                    .node()
+                    .and_then(mq_markdown::Node::position)
+                    .or_else(|| aligned.then(|| positions[index].clone()).flatten());
+                LocatedValue {
+                    value: value.to_string(),
+                    line: position.as_ref().map(|position| position.start.line),
+                    end_line: position
+                        .as_ref()
+                        .map(|position| position.end.line.max(position.start.line)),
+                }
+            })
+            .collect();
+        let content = values
+            .iter()
+            .map(|value| ContentBlock::text(value.value.clone()))
+            .collect();
+        structured_result(content, &LocatedOutput { values })
     }
 
-    #[tool(description = "Extract all headings (h1–h6) from markdown content.")]
+    #[tool(
+        description = "Extract all headings (h1–h6) from markdown content.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn extract_headings(
         &self,
-        Parameters(MarkdownInput { markdown }): Parameters<MarkdownInput>,
+        Parameters(ExtractInput { markdown, sort }): Parameters<ExtractInput>,
     ) -> McpResult {
-        self.eval_query(&markdown, ".h")
+        self.eval_query_sorted(&markdown, ".h", &sort)
     }
 
-    #[tool(description = "Extract all fenced code blocks from markdown content.")]
+    #[tool(
+        description = "Extract all fenced code blocks from markdown content.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn extract_code_blocks(
         &self,
-        Parameters(MarkdownInput { markdown }): Parameters<MarkdownInput>,
+        Parameters(ExtractInput { markdown, sort }): Parameters<ExtractInput>,
     ) -> McpResult {
-        self.eval_query(&markdown, ".code")
+        self.eval_query_sorted(&markdown, ".code", &sort)
     }
 
-    #[tool(description = "Extract all unchecked task list items (todos) from markdown content.")]
+    #[tool(
+        description = "Extract all unchecked task list items (todos) from markdown content.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn extract_todos(
         &self,
-        Parameters(MarkdownInput { markdown }): Parameters<MarkdownInput>,
+        Parameters(ExtractInput { markdown, sort }): Parameters<ExtractInput>,
     ) -> McpResult {
-        self.eval_query(&markdown, ".todo")
+        self.eval_query_sorted(&markdown, ".todo", &sort)
     }
 
-    #[tool(description = "Extract all checked task list items (done tasks) from markdown content.")]
+    #[tool(
+        description = "Extract all checked task list items (done tasks) from markdown content.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn extract_done_tasks(
         &self,
-        Parameters(MarkdownInput { markdown }): Parameters<MarkdownInput>,
+        Parameters(ExtractInput { markdown, sort }): Parameters<ExtractInput>,
     ) -> McpResult {
-        self.eval_query(&markdown, ".done")
+        self.eval_query_sorted(&markdown, ".done", &sort)
     }
 
-    #[tool(description = "Extract all links from markdown content.")]
+    #[tool(
+        description = "Extract all links from markdown content.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn extract_links(
         &self,
-        Parameters(MarkdownInput { markdown }): Parameters<MarkdownInput>,
+        Parameters(ExtractInput { markdown, sort }): Parameters<ExtractInput>,
     ) -> McpResult {
-        self.eval_query(&markdown, ".link")
+        self.eval_query_sorted(&markdown, ".link", &sort)
     }
 
-    #[tool(description = "Extract all images from markdown content.")]
+    #[tool(
+        description = "Extract all images from markdown content.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn extract_images(
         &self,
-        Parameters(MarkdownInput { markdown }): Parameters<MarkdownInput>,
+        Parameters(ExtractInput { markdown, sort }): Parameters<ExtractInput>,
     ) -> McpResult {
-        self.eval_query(&markdown, ".image")
+        self.eval_query_sorted(&markdown, ".image", &sort)
     }
 
-    #[tool(description = "Extract all tables from markdown content.")]
+    #[tool(
+        description = "Extract all tables from markdown content.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn extract_tables(
         &self,
-        Parameters(MarkdownInput { markdown }): Parameters<MarkdownInput>,
+        Parameters(ExtractInput { markdown, sort }): Parameters<ExtractInput>,
     ) -> McpResult {
-        self.eval_query(&markdown, ".table")
+        self.eval_query_sorted(&markdown, ".table", &sort)
     }
 
-    #[tool(description = "Extract all paragraph text nodes from markdown content.")]
+    #[tool(
+        description = "Extract all paragraph text nodes from markdown content.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn extract_text(
         &self,
-        Parameters(MarkdownInput { markdown }): Parameters<MarkdownInput>,
+        Parameters(ExtractInput { markdown, sort }): Parameters<ExtractInput>,
     ) -> McpResult {
-        self.eval_query(&markdown, ".text")
+        self.eval_query_sorted(&markdown, ".text", &sort)
     }
 
-    #[tool(description = "Extract all blockquotes from markdown content.")]
+    #[tool(
+        description = "Extract all blockquotes from markdown content.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn extract_blockquotes(
         &self,
-        Parameters(MarkdownInput { markdown }): Parameters<MarkdownInput>,
+        Parameters(ExtractInput { markdown, sort }): Parameters<ExtractInput>,
     ) -> McpResult {
-        self.eval_query(&markdown, ".blockquote")
+        self.eval_query_sorted(&markdown, ".blockquote", &sort)
     }
 
     #[tool(
-        description = "Split markdown content into sections (heading + body) and return all of them as markdown."
+        description = "Split markdown content into sections (heading + body) and return all of them as markdown.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
     )]
     fn extract_sections(
         &self,
@@ -523,21 +858,21 @@ impl Server {
     }
 
     #[tool(
-        description = "Extract a specific section (heading + body) from markdown content by title. Performs a partial, case-sensitive match on the heading text."
+        description = "Extract a specific section (heading + body) from markdown content by title. Performs a partial, case-sensitive match on the heading text.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
     )]
     fn extract_section(
         &self,
         Parameters(ExtractSectionInput { markdown, title }): Parameters<ExtractSectionInput>,
     ) -> McpResult {
-        let escaped = title.replace('\\', r"\\").replace('"', r#"\""#);
-        let query = format!(
-            r#"import "section" | section::section("{escaped}") | section::collect()"#
-        );
-        self.eval_aggregate(&markdown, &query)
+        self.eval_aggregate(&markdown, &section_query(&title))
     }
 
     #[tool(
-        description = "Generate a table of contents from the headings in markdown content. Returns a list of indented entries."
+        description = "Generate a table of contents from the headings in markdown content. Returns a list of indented entries.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
     )]
     fn extract_toc(
         &self,
@@ -549,7 +884,11 @@ impl Server {
         )
     }
 
-    #[tool(description = "Get available selectors that can be used in mq query.")]
+    #[tool(
+        description = "Get available selectors that can be used in mq query.",
+        output_schema = output_schema::<FunctionsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn available_functions(&self) -> McpResult {
         let hir = mq_hir::Hir::default();
         let mut functions = Vec::with_capacity(256);
@@ -574,20 +913,20 @@ impl Server {
             });
         }
 
-        let output = serde_json::json!({
-            "functions": functions,
-            "examples": vec![
-                r#"select(or(.[], .code, .h)) | upcase() | add(" Hello World")"#.to_string(),
-                r#"select(not(.code))"#.to_string(),
-                r#"select(.code.lang == "js")"#.to_string(),
-            ],
-        });
-        let functions_json = serde_json::to_string(&output).expect("Failed to serialize functions");
-
-        Ok(CallToolResult::success(vec![ContentBlock::text(functions_json)]))
+        json_result(&FunctionsOutput {
+            functions,
+            examples: instructions::EXAMPLE_QUERIES
+                .iter()
+                .map(|query| query.to_string())
+                .collect(),
+        })
     }
 
-    #[tool(description = "Get available selectors that can be used in mq query.")]
+    #[tool(
+        description = "Get available selectors that can be used in mq query.",
+        output_schema = output_schema::<SelectorsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
     fn available_selectors(&self) -> McpResult {
         let hir = mq_hir::Hir::default();
         let mut selectors = Vec::with_capacity(256);
@@ -601,40 +940,382 @@ impl Server {
             });
         }
 
-        let output = serde_json::json!({
-            "selectors": selectors,
+        json_result(&SelectorsOutput { selectors })
+    }
+}
+
+impl Server {
+    /// Adds the reserved per-call arguments (`postprocess`, `provenance`,
+    /// `dry_run`, `idempotency_key`, `profile`) that `call_tool` handles to
+    /// `tool`'s input schema, and localizes it.
+    fn describe_tool(&self, tool: Tool) -> Tool {
+        let tool = correlation::describe_argument(tool);
+        let tool = postprocess::describe_argument(tool);
+        let tool = provenance::describe_argument(tool);
+        let tool = dry_run::describe_argument(tool);
+        let tool = idempotency::describe_argument(tool);
+        let tool = profile::describe_argument(tool, &self.live.profile_names());
+        let tool = self.config.locale.localize_tool(tool);
+        deprecation::describe(tool, self.config.locale)
+    }
+
+    /// Whether `tool` is left out of `tools/list` and refused, as
+    /// deprecated tools are with `hide_deprecated_tools`.
+    fn hidden(&self, tool: &str) -> bool {
+        deprecation::find(tool).is_some() && self.live.hide_deprecated_tools()
+    }
+
+    /// Every tool, in name order, as `tools/list` describes them: with the
+    /// reserved per-call arguments and in the configured locale.
+    pub fn tools(&self) -> Vec<Tool> {
+        let mut tools: Vec<Tool> = self
+            .tool_router
+            .list_all()
+            .into_iter()
+            .filter(|tool| !self.hidden(&tool.name))
+            .map(|tool| self.describe_tool(tool))
+            .collect();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        tools
+    }
+
+    /// Applies server-wide shaping to a tool result before it is returned
+    /// to the client.
+    ///
+    /// `protocol_version` is the revision negotiated with the client; older
+    /// revisions get no content types they don't define.
+    async fn finish_result(
+        &self,
+        result: CallToolResult,
+        protocol_version: &ProtocolVersion,
+    ) -> CallToolResult {
+        // Redact first so offloaded copies never hold the original text.
+        let mut result = redact::redact_result(&self.config.redactions, result);
+        if let Some(threshold) = self.config.resource_threshold {
+            let links = protocol::supports_resource_links(protocol_version);
+            #[cfg(feature = "s3")]
+            let content = self.offload_to_s3(result.content, threshold, links).await;
+            #[cfg(not(feature = "s3"))]
+            let content = self.results.offload(result.content, threshold, links);
+            result.content = content;
+            // Structured content would carry the offloaded text inline again,
+            // defeating the point of the resource link, but tools with an
+            // output schema must return it: its oversized strings become
+            // links to stored copies instead.
+            if let Some(structured) = result.structured_content.as_mut() {
+                self.results.offload_strings(structured, threshold);
+            }
+        }
+        result
+    }
+
+    /// Handles a `tools/call` request: strips the reserved per-call
+    /// arguments, checks quotas and idempotency keys, runs the tool in the
+    /// scope of its settings and shapes the result.
+    async fn run_tool_call(
+        &self,
+        mut request: CallToolRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> McpResult {
+        let started = Instant::now();
+        if self.hidden(&request.name) {
+            // As the router answers for tools it doesn't have.
+            return Err(ErrorData::invalid_params("tool not found", None));
+        }
+        let steps = postprocess::take_argument(&mut request.arguments)
+            .map_err(|e| self.config.locale.localize_error(e))?;
+        let provenance = provenance::take_argument(&mut request.arguments, self.config.provenance)
+            .map_err(|e| self.config.locale.localize_error(e))?;
+        let dry_run = dry_run::take_argument(&mut request.arguments)
+            .map_err(|e| self.config.locale.localize_error(e))?;
+        let idempotency_key = idempotency::take_argument(&mut request.arguments)
+            .map_err(|e| self.config.locale.localize_error(e))?;
+        let profile = profile::take_argument(&mut request.arguments)
+            .and_then(|name| self.live.profile(name.as_deref()))
+            .map_err(|e| self.config.locale.localize_error(e))?;
+        // Before default queries fill in, to record only the caller's own.
+        let shape = cookbook::call_shape(&request.name, request.arguments.as_ref());
+        if let Some(tool) = self.tool_router.get(&request.name) {
+            profile.fill_default_query(tool, &mut request.arguments);
+        }
+        let protocol_version = context
+            .peer
+            .peer_info()
+            .map(|info| protocol::negotiate(&info.protocol_version))
+            .unwrap_or_else(|| protocol::SUPPORTED_PROTOCOL_VERSIONS[0].clone());
+        let incoming = request.arguments.as_ref().map_or(0, |arguments| {
+            serde_json::to_string(arguments).map_or(0, |s| s.len())
         });
-        let selectors_json = serde_json::to_string(&output).expect("Failed to serialize selectors");
+        self.usage
+            .admit(self.config.quotas, incoming as u64)
+            .map_err(|e| self.config.locale.localize_error(e))?;
+
+        // Keys only apply to mutating tools, and dry runs change nothing
+        // worth replaying.
+        let mut ticket = None;
+        if let Some(key) = idempotency_key.filter(|_| {
+            !dry_run
+                && self
+                    .tool_router
+                    .get(&request.name)
+                    .is_some_and(dry_run::mutates)
+        }) {
+            let claim = self
+                .idempotency
+                .claim(
+                    &request.name,
+                    &key,
+                    request.arguments.as_ref(),
+                    self.config
+                        .idempotency_ttl
+                        .unwrap_or(DEFAULT_IDEMPOTENCY_TTL),
+                    Instant::now(),
+                )
+                .map_err(|e| self.config.locale.localize_error(e))?;
+            match claim {
+                idempotency::Claim::Run(claimed) => {
+                    self.stats.record_idempotency(false);
+                    ticket = Some(claimed);
+                }
+                idempotency::Claim::Replay(result) => {
+                    self.stats.record_idempotency(true);
+                    return Ok(self.finish_result(result, &protocol_version).await);
+                }
+            }
+        }
 
-        Ok(CallToolResult::success(vec![ContentBlock::text(selectors_json)]))
+        let tool = request.name.to_string();
+        let tcc = ToolCallContext::new(self, request, context);
+        let call = dry_run::scope(dry_run, self.tool_router.call(tcc));
+        let call = variables::scope(self.variables.clone(), call);
+        let call = profile::scope(profile, call);
+        let result = provenance::scope(provenance, call).await;
+        let ok = result
+            .as_ref()
+            .is_ok_and(|result| result.is_error != Some(true));
+        self.stats.record_call(&tool, ok, started.elapsed());
+        #[cfg(feature = "otel")]
+        metrics::record_call(&tool, ok, incoming, started.elapsed());
+        let deprecated = deprecation::find(&tool);
+        self.record_shape(&tool, shape, result.as_ref().ok());
+        self.audit(
+            tool,
+            dry_run,
+            incoming,
+            started.elapsed(),
+            result.as_ref().ok(),
+        );
+        let result = result.map_err(|e| self.config.locale.localize_error(e))?;
+        let result = postprocess::process_result(steps, self.config.postprocess, result);
+        let result = match deprecated {
+            Some(deprecated) => deprecated.tag_result(result),
+            None => result,
+        };
+        if let Some(ticket) = ticket {
+            ticket.complete(&result);
+        }
+        self.usage.record_result(&result);
+        Ok(self.finish_result(result, &protocol_version).await)
     }
 }
 
-#[tool_handler]
+// Implemented by hand rather than via `#[tool_handler]` so every tool result
+// passes through `finish_result`.
 impl ServerHandler for Server {
     fn get_info(&self) -> ServerInfo {
         ServerInfo::new(
             ServerCapabilities::builder()
                 .enable_tools()
                 .enable_tool_list_changed()
+                .enable_resources()
+                .enable_resources_subscribe()
                 .build(),
         )
-        .with_protocol_version(ProtocolVersion::V_2025_06_18)
-        .with_instructions("mq is a tool for processing markdown content with a jq-like syntax.")
+        .with_protocol_version(protocol::SUPPORTED_PROTOCOL_VERSIONS[0].clone())
+        .with_instructions(instructions::build(self))
+    }
+
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        self.session.open(&self.stats);
+        #[cfg(not(target_os = "wasi"))]
+        self.live.watch(context.peer.clone());
+        let supports_roots = context
+            .peer
+            .peer_info()
+            .is_some_and(|info| info.capabilities.roots.is_some());
+        if supports_roots {
+            self.roots.refresh(&context.peer).await;
+        }
     }
-}
 
-pub async fn start(db_path: Option<PathBuf>) -> miette::Result<()> {
-    let transport = (stdin(), stdout());
-    let server = Server::new(db_path).expect("Failed to create server");
+    async fn on_roots_list_changed(&self, context: NotificationContext<RoleServer>) {
+        self.roots.refresh(&context.peer).await;
+    }
 
-    let service = server.serve(transport).await.map_err(|e| miette!(e))?;
-    service.waiting().await.map_err(|e| miette!(e))?;
+    async fn initialize(
+        &self,
+        request: InitializeRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<InitializeResult, ErrorData> {
+        let protocol_version = protocol::negotiate(&request.protocol_version);
+        if context.peer.peer_info().is_none() {
+            context.peer.set_peer_info(request);
+        }
+        Ok(self.get_info().with_protocol_version(protocol_version))
+    }
 
-    Ok(())
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> McpResult {
+        let mut request = request;
+        let ids = match correlation::take_argument(&mut request.arguments) {
+            Ok(correlation_id) => correlation::CallIds::new(correlation_id),
+            Err(e) => {
+                let ids = correlation::CallIds::new(None);
+                return Err(ids.tag_error(self.config.locale.localize_error(e)));
+            }
+        };
+        let span = ids.span(&request.name);
+        match self.run_tool_call(request, context).instrument(span).await {
+            Ok(result) => Ok(ids.tag_result(result)),
+            Err(e) => Err(ids.tag_error(e)),
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        Ok(ListToolsResult::with_all_items(self.tools()))
+    }
+
+    fn get_tool(&self, name: &str) -> Option<Tool> {
+        self.tool_router
+            .get(name)
+            .filter(|_| !self.hidden(name))
+            .cloned()
+            .map(|tool| self.describe_tool(tool))
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, ErrorData> {
+        let resources = [
+            tutorial::resources(),
+            self.cookbook_resources(),
+            vec![self.scratchpad.resource()],
+            self.results.list(),
+        ]
+        .concat();
+        #[cfg(feature = "fetch")]
+        let resources = [resources, self.scheduler.resources()].concat();
+        Ok(ListResourcesResult::with_all_items(resources))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, ErrorData> {
+        #[cfg(feature = "fetch")]
+        if request.uri.starts_with(schedule::PIPELINE_URI_PREFIX)
+            || request.uri == schedule::DIGESTS_URI
+        {
+            let text = self
+                .scheduler
+                .read(&request.uri)
+                .ok_or_else(|| {
+                    ErrorData::resource_not_found(
+                        "Scheduled pipeline not found",
+                        Some(serde_json::json!({ "uri": request.uri })),
+                    )
+                })
+                .map_err(|e| self.config.locale.localize_error(e))?;
+            return Ok(ReadResourceResult::new(vec![ResourceContents::text(
+                text,
+                request.uri,
+            )]));
+        }
+        if request.uri == cookbook::COOKBOOK_URI {
+            let text = self
+                .read_cookbook()
+                .map_err(|e| self.config.locale.localize_error(e))?;
+            return Ok(ReadResourceResult::new(vec![ResourceContents::text(
+                text,
+                request.uri,
+            )]));
+        }
+        if request.uri == scratchpad::SCRATCHPAD_URI {
+            return Ok(ReadResourceResult::new(vec![ResourceContents::text(
+                self.scratchpad.text(),
+                request.uri,
+            )]));
+        }
+        if let Some(text) = tutorial::read(&request.uri) {
+            return Ok(ReadResourceResult::new(vec![ResourceContents::text(
+                text,
+                request.uri,
+            )]));
+        }
+        let text = self
+            .results
+            .get(&request.uri)
+            .ok_or_else(|| {
+                ErrorData::resource_not_found(
+                    "Result not found (it may have been evicted)",
+                    Some(serde_json::json!({ "uri": request.uri })),
+                )
+            })
+            .map_err(|e| self.config.locale.localize_error(e))?;
+        Ok(ReadResourceResult::new(vec![ResourceContents::text(
+            text,
+            request.uri,
+        )]))
+    }
+
+    // Only pipeline results change; offloaded results never do, so
+    // subscribing to them is accepted and has no effect.
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        if request.uri == scratchpad::SCRATCHPAD_URI {
+            self.scratchpad.subscribe(context.peer);
+            return Ok(());
+        }
+        #[cfg(feature = "fetch")]
+        if request.uri.starts_with(schedule::PIPELINE_URI_PREFIX)
+            || request.uri == schedule::DIGESTS_URI
+        {
+            self.scheduler
+                .subscribe(&self.subscriptions, request.uri, context.peer);
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        if request.uri == scratchpad::SCRATCHPAD_URI {
+            self.scratchpad.unsubscribe();
+        }
+        #[cfg(feature = "fetch")]
+        self.subscriptions.remove(&request.uri);
+        Ok(())
+    }
 }
 
 /// Configuration for the remote (Streamable HTTP) MCP transport.
+#[cfg(not(target_os = "wasi"))]
+#[derive(Debug, Clone)]
 pub struct HttpConfig {
     /// Address to bind the HTTP listener to, e.g. `127.0.0.1:8080`.
     pub bind: String,
@@ -645,7 +1326,34 @@ pub struct HttpConfig {
     pub allowed_hosts: Vec<String>,
 }
 
-pub async fn start_http(config: HttpConfig, db_path: Option<PathBuf>) -> miette::Result<()> {
+/// Resolves on Ctrl-C or, on Unix, SIGTERM (what service managers send to
+/// stop the server).
+#[cfg(not(target_os = "wasi"))]
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(signal) => signal,
+                Err(e) => {
+                    tracing::warn!("failed to install SIGTERM handler: {e}");
+                    let _ = tokio::signal::ctrl_c().await;
+                    return;
+                }
+            };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(not(target_os = "wasi"))]
+pub async fn start_http(config: HttpConfig, server: ServerConfig) -> miette::Result<()> {
     let mut server_config = StreamableHttpServerConfig::default();
     if !config.allowed_hosts.is_empty() {
         server_config.allowed_hosts.extend(config.allowed_hosts);
@@ -654,14 +1362,13 @@ pub async fn start_http(config: HttpConfig, db_path: Option<PathBuf>) -> miette:
     // Load the database once and share it across every session — sessions
     // would otherwise each reload the store from disk (and not observe each
     // other's `db_index` writes).
-    let shared_db: SharedDb = Arc::new(Mutex::new(
-        db_path
-            .as_deref()
-            .map(load_or_create_db)
-            .unwrap_or_default(),
-    ));
+    let shared = Shared::open(&server).map_err(|e| miette!(e))?;
+    let server = Arc::new(server);
+    // Scheduled pipelines run on a server of their own, outside any session.
+    #[cfg(feature = "fetch")]
+    Server::with_shared(server.clone(), shared.clone()).start_scheduler();
     let service = StreamableHttpService::new(
-        move || Ok(Server::with_shared_db(db_path.clone(), shared_db.clone())),
+        move || Ok(Server::with_shared(server.clone(), shared.clone())),
         Arc::new(LocalSessionManager::default()),
         server_config,
     );
@@ -674,9 +1381,7 @@ pub async fn start_http(config: HttpConfig, db_path: Option<PathBuf>) -> miette:
     tracing::info!("mq-mcp listening on http://{}/mcp", config.bind);
 
     axum::serve(listener, router)
-        .with_graceful_shutdown(async {
-            let _ = tokio::signal::ctrl_c().await;
-        })
+        .with_graceful_shutdown(shutdown_signal())
         .await
         .map_err(|e| miette!(e))?;
 
@@ -693,6 +1398,7 @@ mod tests {
         QueryForHtml {
             html: "<h1>Test Heading</h1><p>This is a test paragraph.</p>".to_string(),
             query: Some(".h1".to_string()),
+            ..Default::default()
         },
         Ok("# Test Heading")
     )]
@@ -700,6 +1406,7 @@ mod tests {
         QueryForHtml {
             html: "<h1>Test Heading</h1><p>This is a test paragraph.</p>".to_string(),
             query: Some(".text".to_string()),
+            ..Default::default()
         },
         Ok("Test Heading\n\nThis is a test paragraph.")
     )]
@@ -707,6 +1414,7 @@ mod tests {
         QueryForHtml {
             html: "<h1>Test Heading</h1><p>This is a test paragraph.</p>".to_string(),
             query: None,
+            ..Default::default()
         },
         Ok("# Test Heading\n\nThis is a test paragraph.")
     )]
@@ -714,6 +1422,7 @@ mod tests {
         QueryForHtml {
             html: "<h1>Test Heading".to_string(), // malformed HTML
             query: Some(".h1".to_string()),
+            ..Default::default()
         },
         Ok("# Test Heading")
     )]
@@ -721,6 +1430,7 @@ mod tests {
         QueryForHtml {
             html: "<h1>Test Heading</h1>".to_string(),
             query: Some("not_a_function(".to_string()), // invalid query
+            ..Default::default()
         },
         Err("Failed to query")
     )]
@@ -759,6 +1469,7 @@ mod tests {
         QueryForMarkdown {
             markdown: "# Test Heading".to_string(),
             query: ".h1".to_string(),
+            ..Default::default()
         },
         Ok("# Test Heading")
     )]
@@ -766,6 +1477,7 @@ mod tests {
         QueryForMarkdown {
             markdown: "# Test Heading\n\nThis is a test paragraph.".to_string(),
             query: ".text".to_string(),
+            ..Default::default()
         },
         Ok("Test Heading\n\nThis is a test paragraph.")
     )]
@@ -773,6 +1485,7 @@ mod tests {
         QueryForMarkdown {
             markdown: "# Test Heading\n\nThis is a test paragraph.".to_string(),
             query: "identity()".to_string(),
+            ..Default::default()
         },
         Ok("# Test Heading\n\nThis is a test paragraph.")
     )]
@@ -780,6 +1493,7 @@ mod tests {
         QueryForMarkdown {
             markdown: "# Test Heading".to_string(),
             query: "not_a_function(".to_string(), // invalid query
+            ..Default::default()
         },
         Err("Failed to query")
     )]
@@ -787,6 +1501,7 @@ mod tests {
         QueryForMarkdown {
             markdown: "".to_string(),
             query: ".h1".to_string(),
+            ..Default::default()
         },
         Ok("")
     )]
@@ -819,6 +1534,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_extract_markdown_v2_returns_lines() {
+        let server = Server::new(None).unwrap();
+        let result = server
+            .extract_markdown_v2(Parameters(QueryForMarkdown {
+                markdown: "# Title\n\nIntro\n\n## Usage\n\nRun it.".to_string(),
+                query: ".h".to_string(),
+                ..Default::default()
+            }))
+            .unwrap();
+        let values = &result.structured_content.unwrap()["values"];
+        assert_eq!(values[0]["value"], "# Title");
+        assert_eq!(values[0]["line"], 1);
+        assert_eq!(values[1]["value"], "## Usage");
+        assert_eq!(values[1]["line"], 5);
+        assert_eq!(values[1]["end_line"], 5);
+    }
+
     fn ok_texts(result: CallToolResult) -> Vec<String> {
         assert!(!result.is_error.unwrap_or_default());
         result
@@ -834,8 +1567,9 @@ mod tests {
     fn test_extract_headings(#[case] markdown: &str, #[case] expected: Vec<&str>) {
         let server = Server::new(None).unwrap();
         let result = server
-            .extract_headings(Parameters(MarkdownInput {
+            .extract_headings(Parameters(ExtractInput {
                 markdown: markdown.to_string(),
+                ..Default::default()
             }))
             .unwrap();
         assert_eq!(ok_texts(result), expected);
@@ -847,8 +1581,9 @@ mod tests {
     fn test_extract_code_blocks(#[case] markdown: &str, #[case] count: usize) {
         let server = Server::new(None).unwrap();
         let result = server
-            .extract_code_blocks(Parameters(MarkdownInput {
+            .extract_code_blocks(Parameters(ExtractInput {
                 markdown: markdown.to_string(),
+                ..Default::default()
             }))
             .unwrap();
         assert_eq!(ok_texts(result).len(), count);
@@ -861,8 +1596,9 @@ mod tests {
     fn test_extract_todos(#[case] markdown: &str, #[case] expected: Vec<&str>) {
         let server = Server::new(None).unwrap();
         let result = server
-            .extract_todos(Parameters(MarkdownInput {
+            .extract_todos(Parameters(ExtractInput {
                 markdown: markdown.to_string(),
+                ..Default::default()
             }))
             .unwrap();
         assert_eq!(ok_texts(result), expected);
@@ -874,8 +1610,9 @@ mod tests {
     fn test_extract_done_tasks(#[case] markdown: &str, #[case] expected: Vec<&str>) {
         let server = Server::new(None).unwrap();
         let result = server
-            .extract_done_tasks(Parameters(MarkdownInput {
+            .extract_done_tasks(Parameters(ExtractInput {
                 markdown: markdown.to_string(),
+                ..Default::default()
             }))
             .unwrap();
         assert_eq!(ok_texts(result), expected);
@@ -887,8 +1624,9 @@ mod tests {
     fn test_extract_links(#[case] markdown: &str, #[case] count: usize) {
         let server = Server::new(None).unwrap();
         let result = server
-            .extract_links(Parameters(MarkdownInput {
+            .extract_links(Parameters(ExtractInput {
                 markdown: markdown.to_string(),
+                ..Default::default()
             }))
             .unwrap();
         assert_eq!(ok_texts(result).len(), count);
@@ -900,8 +1638,9 @@ mod tests {
     fn test_extract_images(#[case] markdown: &str, #[case] count: usize) {
         let server = Server::new(None).unwrap();
         let result = server
-            .extract_images(Parameters(MarkdownInput {
+            .extract_images(Parameters(ExtractInput {
                 markdown: markdown.to_string(),
+                ..Default::default()
             }))
             .unwrap();
         assert_eq!(ok_texts(result).len(), count);
@@ -914,8 +1653,9 @@ mod tests {
     fn test_extract_tables(#[case] markdown: &str, #[case] count: usize) {
         let server = Server::new(None).unwrap();
         let result = server
-            .extract_tables(Parameters(MarkdownInput {
+            .extract_tables(Parameters(ExtractInput {
                 markdown: markdown.to_string(),
+                ..Default::default()
             }))
             .unwrap();
         assert_eq!(ok_texts(result).len(), count);
@@ -928,8 +1668,9 @@ mod tests {
     fn test_extract_text(#[case] markdown: &str, #[case] count: usize) {
         let server = Server::new(None).unwrap();
         let result = server
-            .extract_text(Parameters(MarkdownInput {
+            .extract_text(Parameters(ExtractInput {
                 markdown: markdown.to_string(),
+                ..Default::default()
             }))
             .unwrap();
         assert_eq!(ok_texts(result).len(), count);
@@ -941,8 +1682,9 @@ mod tests {
     fn test_extract_blockquotes(#[case] markdown: &str, #[case] count: usize) {
         let server = Server::new(None).unwrap();
         let result = server
-            .extract_blockquotes(Parameters(MarkdownInput {
+            .extract_blockquotes(Parameters(ExtractInput {
                 markdown: markdown.to_string(),
+                ..Default::default()
             }))
             .unwrap();
         assert_eq!(ok_texts(result).len(), count);
@@ -1042,94 +1784,127 @@ Use it like this.\n";
     }
 
     #[test]
-    fn test_get_info() {
-        let server = Server::new(None).expect("Failed to create server");
-        let info = server.get_info();
-        assert_eq!(info.protocol_version, ProtocolVersion::V_2025_06_18);
-        assert!(info.instructions.is_some());
-        let instructions = info.instructions.unwrap();
-        assert!(
-            instructions.contains("mq is a tool for processing markdown content"),
-            "Instructions should mention mq"
-        );
-    }
-
-    // ── db_* tools ───────────────────────────────────────────────────────────
-
-    #[test]
-    fn db_tools_report_error_without_configured_db() {
-        let server = Server::new(None).expect("Failed to create server");
-        let err = server
-            .db_sql(Parameters(DbSqlInput {
-                query: "SELECT 1".to_string(),
+    fn test_extract_headings_sort_by_level() {
+        let server = Server::new(None).unwrap();
+        let result = server
+            .extract_headings(Parameters(ExtractInput {
+                markdown: "## B\n\n# A\n\n### C\n\n# D".to_string(),
+                sort: sort::SortOptions {
+                    sort_by: Some(sort::SortBy::Level),
+                    ..Default::default()
+                },
             }))
-            .expect_err("expected an error with no --db configured");
-        assert!(err.message.contains("no database configured"));
+            .unwrap();
+        assert_eq!(ok_texts(result), vec!["# A", "# D", "## B", "### C"]);
     }
 
     #[test]
-    fn db_index_then_sql_and_mq_and_list_and_stats_round_trip() {
-        let dir = tempfile::tempdir().unwrap();
-        std::fs::write(dir.path().join("a.md"), "# Title\n\nHello world\n").unwrap();
-        let db_path = dir.path().join("store.mq-db");
-
-        let server = Server::new(Some(db_path)).expect("Failed to create server");
-
-        let index_result = server
-            .db_index(Parameters(DbIndexInput {
-                paths: vec![dir.path().to_string_lossy().to_string()],
-                recursive: Some(false),
-                prune: Some(false),
-            }))
-            .expect("db_index should succeed");
-        let index_json = ok_texts(index_result).join("");
-        assert!(index_json.contains("\"added\":[") && index_json.contains("a.md"));
-
-        let sql_result = server
-            .db_sql(Parameters(DbSqlInput {
-                query: "SELECT content FROM blocks WHERE block_type = 'heading'".to_string(),
+    fn test_query_tools_return_structured_content() {
+        let server = Server::new(None).unwrap();
+        let result = server
+            .extract_headings(Parameters(ExtractInput {
+                markdown: "# H1\n\n## H2".to_string(),
+                ..Default::default()
             }))
-            .expect("db_sql should succeed");
-        assert!(ok_texts(sql_result).join("").contains("Title"));
+            .unwrap();
+        assert_eq!(
+            result.structured_content,
+            Some(serde_json::json!({ "results": ["# H1", "## H2"] }))
+        );
+    }
 
-        let mq_result = server
-            .db_mq(Parameters(DbMqInput {
-                code: ".h1".to_string(),
+    #[tokio::test]
+    async fn test_offloaded_result_links_structured_strings() {
+        let server = Server::with_config(ServerConfig {
+            resource_threshold: Some(4),
+            ..Default::default()
+        })
+        .unwrap();
+        let result = server
+            .extract_headings(Parameters(ExtractInput {
+                markdown: "# A long heading".to_string(),
+                ..Default::default()
             }))
-            .expect("db_mq should succeed");
-        assert!(ok_texts(mq_result).join("").contains("Title"));
+            .unwrap();
+        let result = server
+            .finish_result(result, &ProtocolVersion::V_2025_06_18)
+            .await;
+        let link = result.content[0].as_resource_link().unwrap();
+        let structured = result.structured_content.unwrap();
+        assert_eq!(
+            structured["results"][0],
+            results::offloaded_marker(&link.uri, "# A long heading".len())
+        );
+        assert_eq!(
+            server.results.get(&link.uri).as_deref(),
+            Some("# A long heading")
+        );
+        assert_eq!(server.results.list().len(), 1);
+    }
 
-        let list_result = server
-            .db_list_documents()
-            .expect("db_list_documents should succeed");
-        let list_json = ok_texts(list_result).join("");
-        assert!(list_json.contains("a.md"));
+    #[test]
+    fn test_every_tool_declares_output_schema() {
+        let server = Server::new(None).unwrap();
+        for tool in server.tool_router.list_all() {
+            assert!(
+                tool.output_schema.is_some(),
+                "{} has no output schema",
+                tool.name
+            );
+        }
+    }
 
-        let stats_result = server.db_stats().expect("db_stats should succeed");
-        let stats_json = ok_texts(stats_result).join("");
-        assert!(stats_json.contains("\"documents\":1"));
+    #[test]
+    fn test_tools_are_sorted() {
+        let server = Server::new(None).unwrap();
+        let tools = server.tools();
+        assert_eq!(tools.len(), server.tool_router.list_all().len());
+        assert!(tools.windows(2).all(|pair| pair[0].name < pair[1].name));
     }
 
     #[test]
-    fn db_index_second_run_reports_unchanged() {
-        let dir = tempfile::tempdir().unwrap();
-        std::fs::write(dir.path().join("a.md"), "# Title\n\nHello world\n").unwrap();
-        let db_path = dir.path().join("store.mq-db");
-
-        let server = Server::new(Some(db_path)).expect("Failed to create server");
-        let index_input = || DbIndexInput {
-            paths: vec![dir.path().to_string_lossy().to_string()],
-            recursive: Some(false),
-            prune: Some(false),
-        };
+    fn test_only_mutating_tools_are_not_read_only() {
+        const MUTATING: &[&str] = &[
+            "append_to_daily_note",
+            "append_to_scratchpad",
+            "clear_scratchpad",
+            "create_note",
+            "daily_note",
+            "db_index",
+            "delete_saved_query",
+            "embed_sections",
+            "import_state",
+            "index_directory",
+            "save_query",
+            "schedule_pipeline",
+            "set_variable",
+            "unschedule_pipeline",
+        ];
+        let server = Server::new(None).unwrap();
+        for tool in server.tool_router.list_all() {
+            let annotations = tool
+                .annotations
+                .as_ref()
+                .unwrap_or_else(|| panic!("{} has no annotations", tool.name));
+            assert_eq!(
+                annotations.read_only_hint,
+                Some(!MUTATING.contains(&tool.name.as_ref())),
+                "{} has an unexpected read_only_hint",
+                tool.name
+            );
+        }
+    }
 
-        server
-            .db_index(Parameters(index_input()))
-            .expect("first db_index should succeed");
-        let second = server
-            .db_index(Parameters(index_input()))
-            .expect("second db_index should succeed");
-        let second_json = ok_texts(second).join("");
-        assert!(second_json.contains("\"unchanged\":1"));
+    #[test]
+    fn test_get_info() {
+        let server = Server::new(None).expect("Failed to create server");
+        let info = server.get_info();
+        assert_eq!(info.protocol_version, ProtocolVersion::V_2025_06_18);
+        assert!(info.instructions.is_some());
+        let instructions = info.instructions.unwrap();
+        assert!(
+            instructions.contains("mq is a tool for processing markdown content"),
+            "Instructions should mention mq"
+        );
     }
 }
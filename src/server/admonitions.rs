@@ -0,0 +1,617 @@
+//! Admonitions (callouts): the note, tip and warning boxes that GitHub
+//! (`> [!NOTE]`), MkDocs (`!!! note`) and Docusaurus (`:::note`) write in
+//! their own syntaxes. Markdown parsers, mq's included, see them as block
+//! quotes, paragraphs and indented code, so they are found here by
+//! scanning the lines of the source. `extract_admonitions` selects them by
+//! kind and runs queries on their content; `convert_admonitions` rewrites
+//! them into another flavor.
+//!
+//! Only top-level admonitions are found: one nested in another is part of
+//! its content. Fenced code blocks are skipped.
+
+use std::ops::Range;
+
+use rmcp::{handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    output::{json_result, output_schema},
+    source_map::{Rewrite, RewriteOptions, RewriteOutput},
+};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, rmcp::serde::Deserialize, Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AdmonitionFlavor {
+    /// GitHub alerts: `> [!NOTE]` and a block quote.
+    Github,
+    /// MkDocs (Python-Markdown) admonitions: `!!! note "Title"` and an
+    /// indented body.
+    Mkdocs,
+    /// Docusaurus admonitions: `:::note[Title]` up to a closing `:::`.
+    Docusaurus,
+}
+
+/// An admonition found in a document.
+#[derive(Debug, PartialEq)]
+struct Admonition {
+    flavor: AdmonitionFlavor,
+    /// Lowercase, as written.
+    kind: String,
+    title: Option<String>,
+    /// 1-based line of the opening line.
+    line: usize,
+    /// From the start of the opening line to the end of the last line,
+    /// leaving out its line break.
+    range: Range<usize>,
+    /// The content, unquoted or unindented.
+    body: String,
+}
+
+/// The lines of `markdown` as their byte range, line break left out.
+fn lines(markdown: &str) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, _) in markdown.match_indices('\n') {
+        lines.push(start..i);
+        start = i + 1;
+    }
+    if start < markdown.len() {
+        lines.push(start..markdown.len());
+    }
+    lines
+}
+
+/// The character and length of the code fence `line` opens, if any.
+fn fence(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start();
+    let c = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let count = trimmed.chars().take_while(|&d| d == c).count();
+    (count >= 3 && line.len() - trimmed.len() < 4).then_some((c, count))
+}
+
+/// Follows the fenced code blocks of a document read line by line.
+#[derive(Debug, Default)]
+pub(super) struct Fences(Option<(char, usize)>);
+
+impl Fences {
+    /// Whether `line`, the next line, opens, closes or is inside a fenced
+    /// code block.
+    pub(super) fn code(&mut self, line: &str) -> bool {
+        let fence = fence(line);
+        match self.0 {
+            Some((c, count)) => {
+                if fence.is_some_and(|(d, n)| d == c && n >= count)
+                    && line.trim().chars().all(|d| d == c)
+                {
+                    self.0 = None;
+                }
+                true
+            }
+            None => {
+                self.0 = fence;
+                fence.is_some()
+            }
+        }
+    }
+}
+
+/// The text of a quoted line without its `>` and the space after it.
+fn unquote(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('>')?;
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+/// Splits `word rest` after the leading run of letters, digits, `-` and
+/// `_`.
+fn word(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+fn title(text: &str) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+struct Scanner<'a> {
+    markdown: &'a str,
+    lines: Vec<Range<usize>>,
+}
+
+impl Scanner<'_> {
+    fn text(&self, index: usize) -> &str {
+        self.markdown[self.lines[index].clone()].trim_end_matches('\r')
+    }
+
+    /// The admonition of the given flavor that opens at line `index`, and
+    /// the index of the line after it.
+    fn admonition(
+        &self,
+        index: usize,
+        flavor: AdmonitionFlavor,
+        kind: &str,
+        title: Option<String>,
+        body: Vec<&str>,
+        next: usize,
+    ) -> (Admonition, usize) {
+        let admonition = Admonition {
+            flavor,
+            kind: kind.to_lowercase(),
+            title,
+            line: index + 1,
+            range: self.lines[index].start..self.lines[next - 1].end,
+            body: body.join("\n").trim_matches('\n').to_string(),
+        };
+        (admonition, next)
+    }
+
+    fn github(&self, index: usize) -> Option<(Admonition, usize)> {
+        let rest = unquote(self.text(index))?.strip_prefix("[!")?;
+        let (kind, rest) = word(rest);
+        let rest = rest.strip_prefix(']')?;
+        if kind.is_empty() {
+            return None;
+        }
+        let mut next = index + 1;
+        let mut body = Vec::new();
+        while next < self.lines.len()
+            && let Some(line) = unquote(self.text(next))
+        {
+            body.push(line);
+            next += 1;
+        }
+        Some(self.admonition(
+            index,
+            AdmonitionFlavor::Github,
+            kind,
+            title(rest),
+            body,
+            next,
+        ))
+    }
+
+    fn mkdocs(&self, index: usize) -> Option<(Admonition, usize)> {
+        let line = self.text(index);
+        let rest = ["???+", "!!!", "???"]
+            .iter()
+            .find_map(|marker| line.strip_prefix(marker))?;
+        let (kind, rest) = word(rest.trim_start());
+        if kind.is_empty() {
+            return None;
+        }
+        // Other classes may follow the kind, then the title in quotes.
+        let title = rest
+            .find('"')
+            .and_then(|start| rest[start..].strip_prefix('"'))
+            .and_then(|quoted| quoted.strip_suffix('"'))
+            .and_then(title);
+        let mut next = index + 1;
+        let mut end = next;
+        let mut body = Vec::new();
+        while next < self.lines.len() {
+            let line = self.text(next);
+            if line.trim().is_empty() {
+                body.push("");
+            } else if let Some(indented) = line.strip_prefix("    ").or(line.strip_prefix('\t')) {
+                body.push(indented);
+                end = next + 1;
+            } else {
+                break;
+            }
+            next += 1;
+        }
+        // Blank lines after the body belong to what follows.
+        body.truncate(end - index - 1);
+        Some(self.admonition(index, AdmonitionFlavor::Mkdocs, kind, title, body, end))
+    }
+
+    fn docusaurus(&self, index: usize) -> Option<(Admonition, usize)> {
+        let line = self.text(index).trim_start();
+        let colons = line.chars().take_while(|&c| c == ':').count();
+        if colons < 3 {
+            return None;
+        }
+        let (kind, rest) = word(line[colons..].trim_start());
+        if kind.is_empty() {
+            return None;
+        }
+        let title = match rest.trim().strip_prefix('[') {
+            Some(bracketed) => bracketed.strip_suffix(']').and_then(title),
+            None => title(rest),
+        };
+        let close = ":".repeat(colons);
+        let end = (index + 1..self.lines.len()).find(|&i| self.text(i).trim() == close)?;
+        let body = (index + 1..end).map(|i| self.text(i)).collect();
+        Some(self.admonition(
+            index,
+            AdmonitionFlavor::Docusaurus,
+            kind,
+            title,
+            body,
+            end + 1,
+        ))
+    }
+}
+
+/// The top-level admonitions of `markdown`, in document order.
+fn scan(markdown: &str) -> Vec<Admonition> {
+    let scanner = Scanner {
+        markdown,
+        lines: lines(markdown),
+    };
+    let mut admonitions = Vec::new();
+    let mut fences = Fences::default();
+    let mut index = 0;
+    while index < scanner.lines.len() {
+        if fences.code(scanner.text(index)) {
+            index += 1;
+            continue;
+        }
+        match scanner
+            .github(index)
+            .or_else(|| scanner.mkdocs(index))
+            .or_else(|| scanner.docusaurus(index))
+        {
+            Some((admonition, next)) => {
+                admonitions.push(admonition);
+                index = next;
+            }
+            None => index += 1,
+        }
+    }
+    admonitions
+}
+
+/// The kind closest to `kind` among those `flavor` supports. MkDocs takes
+/// any kind, styling unknown ones as notes.
+fn kind_for(flavor: AdmonitionFlavor, kind: &str) -> String {
+    match flavor {
+        AdmonitionFlavor::Github => match kind {
+            "tip" | "hint" | "success" | "check" | "done" => "TIP",
+            "important" => "IMPORTANT",
+            "warning" | "attention" => "WARNING",
+            "caution" | "danger" | "error" | "failure" | "fail" | "bug" => "CAUTION",
+            _ => "NOTE",
+        }
+        .to_string(),
+        AdmonitionFlavor::Mkdocs => kind.to_string(),
+        AdmonitionFlavor::Docusaurus => match kind {
+            "note" | "tip" | "info" | "warning" | "danger" => kind,
+            "hint" | "success" | "check" | "done" => "tip",
+            "important" => "info",
+            "caution" | "attention" => "warning",
+            "error" | "failure" | "fail" | "bug" => "danger",
+            _ => "note",
+        }
+        .to_string(),
+    }
+}
+
+/// `admonition` written in `flavor`. GitHub alerts have no titles, so a
+/// title becomes a bold first line.
+fn render(admonition: &Admonition, flavor: AdmonitionFlavor) -> String {
+    let kind = kind_for(flavor, &admonition.kind);
+    let body = admonition.body.as_str();
+    let prefix = |prefix: &str| {
+        body.lines()
+            .map(|line| {
+                if line.trim().is_empty() {
+                    prefix.trim_end().to_string()
+                } else {
+                    format!("{prefix}{line}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let mut lines = Vec::new();
+    match flavor {
+        AdmonitionFlavor::Github => {
+            lines.push(format!("> [!{kind}]"));
+            if let Some(title) = &admonition.title {
+                lines.push(format!("> **{title}**"));
+                if !body.is_empty() {
+                    lines.push(">".to_string());
+                }
+            }
+            if !body.is_empty() {
+                lines.push(prefix("> "));
+            }
+        }
+        AdmonitionFlavor::Mkdocs => {
+            lines.push(match &admonition.title {
+                Some(title) => format!("!!! {kind} \"{title}\""),
+                None => format!("!!! {kind}"),
+            });
+            if !body.is_empty() {
+                lines.push(String::new());
+                lines.push(prefix("    "));
+            }
+        }
+        AdmonitionFlavor::Docusaurus => {
+            lines.push(match &admonition.title {
+                Some(title) => format!(":::{kind}[{title}]"),
+                None => format!(":::{kind}"),
+            });
+            if !body.is_empty() {
+                lines.push(String::new());
+                lines.push(body.to_string());
+                lines.push(String::new());
+            }
+            lines.push(":::".to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+/// Whether `admonition` is of one of `kinds`, or `kinds` is absent.
+fn selected(admonition: &Admonition, kinds: Option<&[String]>) -> bool {
+    kinds.is_none_or(|kinds| {
+        kinds
+            .iter()
+            .any(|kind| kind.eq_ignore_ascii_case(&admonition.kind))
+    })
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ExtractAdmonitionsInput {
+    #[schemars(description = "The markdown document to search")]
+    pub markdown: String,
+    #[schemars(
+        description = "Kinds of admonition to return, e.g. [\"warning\", \"caution\"], case-insensitive (default: all)"
+    )]
+    pub kinds: Option<Vec<String>>,
+    #[schemars(
+        description = "An mq query run on the content of each admonition; admonitions it gives no results for are left out"
+    )]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct AdmonitionInfo {
+    pub flavor: AdmonitionFlavor,
+    #[schemars(description = "The kind in lowercase, e.g. `note` or `warning`")]
+    pub kind: String,
+    pub title: Option<String>,
+    #[schemars(description = "1-based line of the opening line")]
+    pub line: usize,
+    #[schemars(description = "The content as markdown, without the quoting or indentation")]
+    pub markdown: String,
+    #[schemars(description = "The results of `query` on the content, if given")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<Vec<String>>,
+}
+
+/// Output of `extract_admonitions`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ExtractAdmonitionsOutput {
+    pub admonitions: Vec<AdmonitionInfo>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ConvertAdmonitionsInput {
+    #[schemars(description = "The markdown document to convert")]
+    pub markdown: String,
+    #[schemars(description = "`github`, `mkdocs` or `docusaurus`")]
+    pub to: AdmonitionFlavor,
+    #[schemars(description = "Kinds of admonition to convert, case-insensitive (default: all)")]
+    pub kinds: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub rewrite: RewriteOptions,
+}
+
+/// Output of `convert_admonitions`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ConvertAdmonitionsOutput {
+    #[serde(flatten)]
+    pub rewrite: RewriteOutput,
+    #[schemars(description = "Number of admonitions converted")]
+    pub converted: usize,
+}
+
+fn convert(input: &ConvertAdmonitionsInput) -> ConvertAdmonitionsOutput {
+    let mut rewrite = Rewrite::new(&input.markdown);
+    let mut converted = 0;
+    for admonition in scan(&input.markdown) {
+        if admonition.flavor != input.to && selected(&admonition, input.kinds.as_deref()) {
+            rewrite.replace(admonition.range.clone(), &render(&admonition, input.to));
+            converted += 1;
+        }
+    }
+    ConvertAdmonitionsOutput {
+        rewrite: rewrite.finish().render(&input.rewrite),
+        converted,
+    }
+}
+
+#[tool_router(router = admonitions_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "List the admonitions (callouts) of a markdown document in GitHub (`> [!NOTE]`), MkDocs (`!!! note`) and Docusaurus (`:::note`) syntax, which mq queries otherwise see as block quotes and paragraphs. Each has its flavor, kind, title, line and content; select them by kind and run an mq query on their content.",
+        output_schema = output_schema::<ExtractAdmonitionsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn extract_admonitions(
+        &self,
+        Parameters(input): Parameters<ExtractAdmonitionsInput>,
+    ) -> McpResult {
+        let mut admonitions = Vec::new();
+        for admonition in scan(&input.markdown) {
+            if !selected(&admonition, input.kinds.as_deref()) {
+                continue;
+            }
+            let results = match &input.query {
+                Some(query) => match self.query_values(&admonition.body, query)? {
+                    results if results.is_empty() => continue,
+                    results => Some(results),
+                },
+                None => None,
+            };
+            admonitions.push(AdmonitionInfo {
+                flavor: admonition.flavor,
+                kind: admonition.kind,
+                title: admonition.title,
+                line: admonition.line,
+                markdown: admonition.body,
+                results,
+            });
+        }
+        json_result(&ExtractAdmonitionsOutput { admonitions })
+    }
+
+    #[tool(
+        description = "Convert the admonitions (callouts) of a markdown document to GitHub (`> [!NOTE]`), MkDocs (`!!! note`) or Docusaurus (`:::note`) syntax, mapping kinds to those the target supports, e.g. when moving docs between GitHub and a docs site.",
+        output_schema = output_schema::<ConvertAdmonitionsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn convert_admonitions(
+        &self,
+        Parameters(input): Parameters<ConvertAdmonitionsInput>,
+    ) -> McpResult {
+        json_result(&convert(&input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const MARKDOWN: &str = "\
+# Setup
+
+> [!WARNING]
+> Back up your data
+> first.
+
+!!! tip \"Faster builds\"
+
+    Enable the cache.
+
+    It is off by default.
+
+Text.
+
+:::danger[Take care]
+
+Don't run this as root.
+
+:::
+
+```md
+> [!NOTE]
+> Inside code.
+```
+
+> A plain quote.
+";
+
+    #[test]
+    fn test_scan() {
+        let admonitions = scan(MARKDOWN);
+        assert_eq!(
+            admonitions
+                .iter()
+                .map(|a| (
+                    a.flavor,
+                    a.kind.as_str(),
+                    a.title.as_deref(),
+                    a.line,
+                    a.body.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    AdmonitionFlavor::Github,
+                    "warning",
+                    None,
+                    3,
+                    "Back up your data\nfirst."
+                ),
+                (
+                    AdmonitionFlavor::Mkdocs,
+                    "tip",
+                    Some("Faster builds"),
+                    7,
+                    "Enable the cache.\n\nIt is off by default."
+                ),
+                (
+                    AdmonitionFlavor::Docusaurus,
+                    "danger",
+                    Some("Take care"),
+                    15,
+                    "Don't run this as root."
+                ),
+            ]
+        );
+        assert_eq!(MARKDOWN[admonitions[1].range.clone()].lines().count(), 5);
+    }
+
+    #[rstest]
+    #[case(
+        AdmonitionFlavor::Github,
+        "> [!TIP]\n> **Faster builds**\n>\n> Enable the cache.\n>\n> It is off by default."
+    )]
+    #[case(
+        AdmonitionFlavor::Mkdocs,
+        "!!! tip \"Faster builds\"\n\n    Enable the cache.\n\n    It is off by default."
+    )]
+    #[case(
+        AdmonitionFlavor::Docusaurus,
+        ":::tip[Faster builds]\n\nEnable the cache.\n\nIt is off by default.\n\n:::"
+    )]
+    fn test_render(#[case] flavor: AdmonitionFlavor, #[case] expected: &str) {
+        let admonition = scan(MARKDOWN).remove(1);
+        assert_eq!(render(&admonition, flavor), expected);
+    }
+
+    #[rstest]
+    #[case(AdmonitionFlavor::Github, "danger", "CAUTION")]
+    #[case(AdmonitionFlavor::Docusaurus, "important", "info")]
+    #[case(AdmonitionFlavor::Docusaurus, "abstract", "note")]
+    #[case(AdmonitionFlavor::Mkdocs, "caution", "caution")]
+    fn test_kind_for(#[case] flavor: AdmonitionFlavor, #[case] kind: &str, #[case] expected: &str) {
+        assert_eq!(kind_for(flavor, kind), expected);
+    }
+
+    #[test]
+    fn test_convert() {
+        let output = convert(&ConvertAdmonitionsInput {
+            markdown: MARKDOWN.to_string(),
+            to: AdmonitionFlavor::Github,
+            kinds: Some(vec!["Danger".to_string()]),
+            rewrite: RewriteOptions::default(),
+        });
+        assert_eq!(output.converted, 1);
+        let markdown = output.rewrite.markdown.unwrap();
+        assert!(markdown.contains(
+            "Text.\n\n> [!CAUTION]\n> **Take care**\n>\n> Don't run this as root.\n\n```md"
+        ));
+        assert!(markdown.contains("!!! tip"));
+    }
+
+    #[test]
+    fn test_extract_with_query() {
+        let server = Server::new(None).unwrap();
+        let result = server
+            .extract_admonitions(Parameters(ExtractAdmonitionsInput {
+                markdown: MARKDOWN.to_string(),
+                kinds: None,
+                query: Some(".text | select(contains(\"cache\"))".to_string()),
+            }))
+            .unwrap();
+        let admonitions = &result.structured_content.unwrap()["admonitions"];
+        assert_eq!(admonitions.as_array().unwrap().len(), 1);
+        assert_eq!(admonitions[0]["kind"], "tip");
+        assert_eq!(admonitions[0]["results"][0], "Enable the cache.");
+    }
+
+    #[test]
+    fn test_unclosed_docusaurus_fence_is_not_an_admonition() {
+        assert!(scan(":::note\n\nNever closed.\n").is_empty());
+    }
+}
@@ -0,0 +1,611 @@
+//! `ast_json` and `from_ast_json`: markdown syntax trees as JSON, and back.
+
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use serde_json::Value;
+
+use super::{
+    McpResult, Server,
+    output::{QueryOutput, json_result, output_schema, query_result},
+    parse_markdown,
+};
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AstFormat {
+    /// An mdast `root` node, as produced by remark and markdown-rs.
+    #[default]
+    Mdast,
+    /// mq's own node list, the values queries operate on: paragraphs are
+    /// flattened and list items and table cells carry their level and
+    /// position instead of being nested.
+    Mq,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct AstJsonInput {
+    #[schemars(description = "The markdown to parse")]
+    pub markdown: String,
+    #[schemars(
+        description = "An mq query to run first; the tree of its results is returned instead of the whole document"
+    )]
+    pub query: Option<String>,
+    #[schemars(
+        description = "Tree shape: `mdast` (default, https://github.com/syntax-tree/mdast) or `mq` (mq's node list)"
+    )]
+    pub format: Option<AstFormat>,
+    #[schemars(description = "Include source positions on each node (default: false)")]
+    pub positions: Option<bool>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct AstJsonOutput {
+    #[schemars(
+        description = "An mdast root node, or an array of mq nodes each tagged with its `type`"
+    )]
+    pub ast: Value,
+}
+
+/// Parse options matching the markdown mq reads: GFM, front matter and
+/// math.
+fn mdast_options() -> markdown::ParseOptions {
+    markdown::ParseOptions {
+        constructs: markdown::Constructs {
+            frontmatter: true,
+            math_flow: true,
+            math_text: true,
+            ..markdown::Constructs::gfm()
+        },
+        gfm_strikethrough_single_tilde: true,
+        math_text_single_dollar: true,
+        ..markdown::ParseOptions::gfm()
+    }
+}
+
+pub(super) fn parse_mdast(markdown: &str) -> Result<markdown::mdast::Node, ErrorData> {
+    markdown::to_mdast(markdown, &mdast_options()).map_err(|e| {
+        ErrorData::parse_error(
+            "Failed to parse markdown",
+            Some(serde_json::Value::String(e.to_string())),
+        )
+    })
+}
+
+fn to_value<T: Serialize>(tree: &T) -> Result<Value, ErrorData> {
+    serde_json::to_value(tree).map_err(|e| {
+        ErrorData::internal_error(
+            "Failed to serialize the syntax tree",
+            Some(serde_json::Value::String(e.to_string())),
+        )
+    })
+}
+
+/// Removes `position` fields from every node.
+fn strip_positions(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove("position");
+            map.values_mut().for_each(strip_positions);
+        }
+        Value::Array(values) => values.iter_mut().for_each(strip_positions),
+        _ => {}
+    }
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct FromAstJsonInput {
+    #[schemars(
+        description = "The tree to render: an mdast node (usually `root`) or array of mdast nodes, or an array of mq nodes as returned by ast_json with `format: mq`. The `{\"ast\": ...}` object ast_json returns is accepted as is"
+    )]
+    pub ast: Value,
+    #[schemars(
+        description = "Tree shape of `ast`: `mdast` or `mq` (default: detected from the node types)"
+    )]
+    pub format: Option<AstFormat>,
+    #[schemars(
+        description = "An mq query to run on the rendered markdown (default: none, the whole document is returned as one block)"
+    )]
+    pub query: Option<String>,
+}
+
+fn str_field<'a>(node: &'a Value, key: &str) -> &'a str {
+    node[key].as_str().unwrap_or_default()
+}
+
+fn children(node: &Value) -> &[Value] {
+    node["children"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn phrasing(node: &Value) -> String {
+    children(node).iter().map(render_mdast).collect()
+}
+
+fn blocks(nodes: &[Value], separator: &str) -> String {
+    nodes
+        .iter()
+        .map(render_mdast)
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Prefixes the first line of `text` with `first` and indents the rest by
+/// `rest`, leaving blank lines empty.
+fn hang(text: &str, first: &str, rest: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| match (i, line.is_empty()) {
+            (0, _) => format!("{first}{line}").trim_end().to_string(),
+            (_, true) => String::new(),
+            _ => format!("{rest}{line}"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn title(node: &Value) -> String {
+    match node["title"].as_str() {
+        Some(title) => format!(" \"{}\"", title.replace('"', "\\\"")),
+        None => String::new(),
+    }
+}
+
+fn label(node: &Value) -> &str {
+    node["label"]
+        .as_str()
+        .unwrap_or_else(|| str_field(node, "identifier"))
+}
+
+fn reference(node: &Value, text: &str) -> String {
+    match str_field(node, "referenceType") {
+        "shortcut" => format!("[{text}]"),
+        "collapsed" => format!("[{text}][]"),
+        _ => format!("[{text}][{}]", label(node)),
+    }
+}
+
+fn fence(value: &str, marker: char) -> String {
+    let longest = value
+        .lines()
+        .map(|line| {
+            line.trim_start()
+                .chars()
+                .take_while(|c| *c == marker)
+                .count()
+        })
+        .max()
+        .unwrap_or_default();
+    marker.to_string().repeat(longest.max(2) + 1)
+}
+
+fn inline_code(value: &str) -> String {
+    let mut ticks = "`".to_string();
+    while value.contains(&ticks) {
+        ticks.push('`');
+    }
+    if ticks.len() > 1 || value.starts_with('`') || value.ends_with('`') {
+        format!("{ticks} {value} {ticks}")
+    } else {
+        format!("{ticks}{value}{ticks}")
+    }
+}
+
+fn list_item(item: &Value, marker: &str) -> String {
+    let separator = if item["spread"].as_bool().unwrap_or_default() {
+        "\n\n"
+    } else {
+        "\n"
+    };
+    let checkbox = match item["checked"].as_bool() {
+        Some(true) => "[x] ",
+        Some(false) => "[ ] ",
+        None => "",
+    };
+    let content = blocks(children(item), separator);
+    if content.is_empty() && checkbox.is_empty() {
+        return marker.to_string();
+    }
+    let indent = " ".repeat(marker.len() + 1);
+    hang(
+        &format!("{checkbox}{content}"),
+        &format!("{marker} "),
+        &indent,
+    )
+}
+
+fn table(node: &Value) -> String {
+    let row = |row: &Value| {
+        let cells: Vec<String> = children(row)
+            .iter()
+            .map(|cell| phrasing(cell).replace('|', "\\|").replace('\n', " "))
+            .collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    let rows = children(node);
+    let columns = rows.first().map(|r| children(r).len()).unwrap_or_default();
+    let align = node["align"].as_array();
+    let delimiter: Vec<&str> = (0..columns)
+        .map(
+            |i| match align.and_then(|a| a.get(i)).and_then(Value::as_str) {
+                Some("left") => ":---",
+                Some("right") => "---:",
+                Some("center") => ":---:",
+                _ => "---",
+            },
+        )
+        .collect();
+    let mut lines: Vec<String> = rows.iter().map(row).collect();
+    if !lines.is_empty() {
+        lines.insert(1, format!("| {} |", delimiter.join(" | ")));
+    }
+    lines.join("\n")
+}
+
+/// Renders an mdast node as markdown.
+fn render_mdast(node: &Value) -> String {
+    let value = str_field(node, "value");
+    match str_field(node, "type") {
+        "root" => blocks(children(node), "\n\n"),
+        "paragraph" | "tableCell" => phrasing(node),
+        "heading" => {
+            let depth = node["depth"].as_u64().unwrap_or(1).clamp(1, 6) as usize;
+            format!("{} {}", "#".repeat(depth), phrasing(node))
+        }
+        "thematicBreak" => "---".to_string(),
+        "blockquote" => blocks(children(node), "\n\n")
+            .lines()
+            .map(|line| format!("> {line}").trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "list" => {
+            let ordered = node["ordered"].as_bool().unwrap_or_default();
+            let start = node["start"].as_u64().unwrap_or(1);
+            let separator = if node["spread"].as_bool().unwrap_or_default() {
+                "\n\n"
+            } else {
+                "\n"
+            };
+            children(node)
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let marker = if ordered {
+                        format!("{}.", start + i as u64)
+                    } else {
+                        "-".to_string()
+                    };
+                    list_item(item, &marker)
+                })
+                .collect::<Vec<_>>()
+                .join(separator)
+        }
+        "listItem" => list_item(node, "-"),
+        "code" => {
+            let fence = fence(value, '`');
+            let info = [node["lang"].as_str(), node["meta"].as_str()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("{fence}{info}\n{value}\n{fence}")
+        }
+        "math" => format!("$$\n{value}\n$$"),
+        "yaml" => format!("---\n{value}\n---"),
+        "toml" => format!("+++\n{value}\n+++"),
+        "definition" => format!(
+            "[{}]: {}{}",
+            label(node),
+            str_field(node, "url"),
+            title(node)
+        ),
+        "footnoteDefinition" => hang(
+            &blocks(children(node), "\n\n"),
+            &format!("[^{}]: ", label(node)),
+            "    ",
+        ),
+        "table" => table(node),
+        "tableRow" => children(node)
+            .iter()
+            .map(phrasing)
+            .collect::<Vec<_>>()
+            .join(" | "),
+        "emphasis" => format!("*{}*", phrasing(node)),
+        "strong" => format!("**{}**", phrasing(node)),
+        "delete" => format!("~~{}~~", phrasing(node)),
+        "inlineCode" => inline_code(value),
+        "inlineMath" => format!("${value}$"),
+        "break" => "\\\n".to_string(),
+        "link" => format!(
+            "[{}]({}{})",
+            phrasing(node),
+            str_field(node, "url"),
+            title(node)
+        ),
+        "image" => format!(
+            "![{}]({}{})",
+            str_field(node, "alt"),
+            str_field(node, "url"),
+            title(node)
+        ),
+        "linkReference" => reference(node, &phrasing(node)),
+        "imageReference" => format!("!{}", reference(node, str_field(node, "alt"))),
+        "footnoteReference" => format!("[^{}]", label(node)),
+        "mdxFlowExpression" | "mdxTextExpression" => format!("{{{value}}}"),
+        // text, html, mdxjsEsm and unknown literals; unknown parents render
+        // their children.
+        _ if node.get("children").is_some() => phrasing(node),
+        _ => value.to_string(),
+    }
+}
+
+/// Detects the tree shape: mq node types are PascalCase (`Heading`),
+/// mdast ones camelCase (`heading`).
+fn detect_format(ast: &Value) -> AstFormat {
+    let first = match ast {
+        Value::Array(nodes) => nodes.first().unwrap_or(&Value::Null),
+        node => node,
+    };
+    match first["type"].as_str() {
+        Some(kind) if kind.starts_with(|c: char| c.is_ascii_uppercase()) => AstFormat::Mq,
+        _ => AstFormat::Mdast,
+    }
+}
+
+/// Reads one node of mq's node list. `mq_markdown::Node` is an untagged
+/// enum, so serde alone reads every node as the first variant its fields
+/// fit: pick the variant from `type` instead, and read the children the
+/// same way.
+fn mq_node(value: &Value) -> Result<mq_markdown::Node, serde_json::Error> {
+    use mq_markdown::Node;
+
+    let mut value = value.clone();
+    let children = ["values", "children"]
+        .into_iter()
+        .find_map(|key| match value.get_mut(key) {
+            Some(Value::Array(children)) => Some(std::mem::take(children)),
+            _ => None,
+        })
+        .unwrap_or_default()
+        .iter()
+        .map(mq_node)
+        .collect::<Result<Vec<_>, _>>()?;
+    let kind = str_field(&value, "type").to_string();
+    macro_rules! variant {
+        ($($name:ident),*) => {
+            match kind.as_str() {
+                $(stringify!($name) => Node::$name(serde_json::from_value(value)?),)*
+                _ => {
+                    return Err(serde::de::Error::custom(format!("unknown node type `{kind}`")));
+                }
+            }
+        };
+    }
+    let mut node = variant!(
+        Blockquote,
+        Break,
+        Callout,
+        Embed,
+        Definition,
+        Delete,
+        Heading,
+        Emphasis,
+        Footnote,
+        FootnoteRef,
+        Html,
+        Yaml,
+        Toml,
+        Image,
+        ImageRef,
+        CodeInline,
+        MathInline,
+        Link,
+        LinkRef,
+        WikiLink,
+        Math,
+        List,
+        TableAlign,
+        TableRow,
+        TableCell,
+        Code,
+        Strong,
+        HorizontalRule,
+        MdxFlowExpression,
+        MdxJsxFlowElement,
+        MdxJsxTextElement,
+        MdxTextExpression,
+        MdxJsEsm,
+        Text,
+        Fragment
+    );
+    match &mut node {
+        Node::LinkRef(link) => link.values = children,
+        node => node.set_children(children),
+    }
+    Ok(node)
+}
+
+fn from_ast_json(ast: &Value, format: Option<AstFormat>) -> Result<String, ErrorData> {
+    // The whole output of ast_json.
+    let ast = match ast.get("ast") {
+        Some(inner) if ast.get("type").is_none() => inner,
+        _ => ast,
+    };
+    match format.unwrap_or_else(|| detect_format(ast)) {
+        AstFormat::Mdast => Ok(match ast {
+            Value::Array(nodes) => blocks(nodes, "\n\n"),
+            node => render_mdast(node),
+        }),
+        AstFormat::Mq => {
+            let nodes = match ast {
+                Value::Array(nodes) => nodes.as_slice(),
+                node => std::slice::from_ref(node),
+            };
+            let nodes = nodes
+                .iter()
+                .map(mq_node)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| {
+                    ErrorData::invalid_params(
+                        "Invalid mq node tree",
+                        Some(serde_json::Value::String(e.to_string())),
+                    )
+                })?;
+            Ok(mq_markdown::Markdown::new(nodes)
+                .to_string()
+                .trim_end()
+                .to_string())
+        }
+    }
+}
+
+fn ast_json(markdown: &str, format: AstFormat, positions: bool) -> Result<Value, ErrorData> {
+    let mut ast = match format {
+        AstFormat::Mdast => to_value(&parse_mdast(markdown)?)?,
+        AstFormat::Mq => {
+            let nodes: Vec<mq_markdown::Node> = parse_markdown(markdown)?
+                .into_iter()
+                .filter(|node| !node.is_empty() && !node.is_empty_fragment())
+                .collect();
+            to_value(&nodes)?
+        }
+    };
+    if !positions {
+        strip_positions(&mut ast);
+    }
+    Ok(ast)
+}
+
+#[tool_router(router = ast_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Return the parsed syntax tree of markdown as JSON: an mdast root (the format remark and markdown-rs use) or mq's own node list. An optional mq query selects the part of the document to return.",
+        output_schema = output_schema::<AstJsonOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn ast_json(&self, Parameters(input): Parameters<AstJsonInput>) -> McpResult {
+        let markdown = match input.query.as_deref() {
+            Some(query) => self.query_values(&input.markdown, query)?.join("\n\n"),
+            None => input.markdown,
+        };
+        let ast = ast_json(
+            &markdown,
+            input.format.unwrap_or_default(),
+            input.positions.unwrap_or_default(),
+        )?;
+        json_result(&AstJsonOutput { ast })
+    }
+
+    #[tool(
+        name = "from_ast_json",
+        description = "Render a JSON syntax tree back to markdown: an mdast tree (as produced by ast_json, remark or markdown-rs) or mq's node list. Use with ast_json to edit documents node by node. An optional mq query runs on the result.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn render_ast_json(&self, Parameters(input): Parameters<FromAstJsonInput>) -> McpResult {
+        let markdown = from_ast_json(&input.ast, input.format)?;
+        match input.query.as_deref() {
+            Some(query) => self.eval_query(&markdown, query),
+            None => query_result(vec![markdown]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[test]
+    fn test_ast_json_mdast() {
+        assert_eq!(
+            ast_json("# Hi\n\n- [x] *done*\n", AstFormat::Mdast, false).unwrap(),
+            json!({
+                "type": "root",
+                "children": [
+                    {"type": "heading", "depth": 1, "children": [{"type": "text", "value": "Hi"}]},
+                    {
+                        "type": "list",
+                        "ordered": false,
+                        "spread": false,
+                        "children": [{
+                            "type": "listItem",
+                            "spread": false,
+                            "checked": true,
+                            "children": [{
+                                "type": "paragraph",
+                                "children": [{"type": "emphasis", "children": [{"type": "text", "value": "done"}]}]
+                            }]
+                        }]
+                    }
+                ]
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(AstFormat::Mdast, true)]
+    #[case(AstFormat::Mdast, false)]
+    #[case(AstFormat::Mq, true)]
+    fn test_from_ast_json_round_trip(#[case] format: AstFormat, #[case] positions: bool) {
+        let markdown = "\
+# Title
+
+Some *em*, **strong**, `code` and [link](https://example.com \"Title\").
+
+- one
+  - [x] two
+
+3. three
+4. four
+
+> quote
+
+```rust
+fn main() {}
+```
+
+| a | b |
+| :--- | ---: |
+| 1 | 2 |";
+        let ast = ast_json(markdown, format, positions).unwrap();
+        assert_eq!(detect_format(&ast), format);
+        let rendered = from_ast_json(&json!({ "ast": ast }), None).unwrap();
+        match format {
+            AstFormat::Mdast => assert_eq!(rendered, markdown),
+            // mq's renderer has its own table and list layout.
+            AstFormat::Mq => assert_eq!(
+                parse_markdown(&rendered).unwrap(),
+                parse_markdown(markdown).unwrap()
+            ),
+        }
+    }
+
+    #[rstest]
+    #[case(json!({"type": "inlineCode", "value": "a`b"}), "`` a`b ``")]
+    #[case(json!({"type": "code", "value": "```\nx\n```"}), "````\n```\nx\n```\n````")]
+    #[case(
+        json!({"type": "linkReference", "referenceType": "full", "identifier": "x", "children": [{"type": "text", "value": "t"}]}),
+        "[t][x]"
+    )]
+    #[case(json!({"type": "paragraph", "children": [{"type": "text", "value": "a"}, {"type": "break"}, {"type": "text", "value": "b"}]}), "a\\\nb")]
+    fn test_render_mdast(#[case] node: Value, #[case] expected: &str) {
+        assert_eq!(render_mdast(&node), expected);
+    }
+
+    #[test]
+    fn test_ast_json_positions() {
+        let ast = ast_json("text", AstFormat::Mdast, true).unwrap();
+        assert_eq!(ast["children"][0]["position"]["start"]["line"], 1);
+
+        let ast = ast_json("# Hi", AstFormat::Mq, false).unwrap();
+        assert_eq!(ast[0]["type"], "Heading");
+        assert!(ast[0].get("position").is_none());
+    }
+}
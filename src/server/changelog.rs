@@ -0,0 +1,374 @@
+//! `parse_changelog`: CHANGELOG.md files (Keep a Changelog and the layout
+//! conventional-changelog writes) parsed into releases.
+
+use std::{cmp::Ordering, collections::HashMap, sync::LazyLock};
+
+use regex::Regex;
+use rmcp::{handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    output::{json_result, output_schema},
+};
+
+/// Category name for items listed directly under a release heading.
+const UNCATEGORIZED: &str = "Changes";
+
+static HEADING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(#{1,6})\s+(.*?)\s*#*\s*$").unwrap());
+static VERSION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\bv?(\d+(?:\.\d+)+(?:-[0-9A-Za-z.-]+)?(?:\+[0-9A-Za-z.-]+)?)").unwrap()
+});
+static DATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b\d{4}-\d{2}-\d{2}\b").unwrap());
+static INLINE_LINK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\]\(([^)\s]+)\)").unwrap());
+static LINK_DEFINITION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s{0,3}\[([^\]]+)\]:\s*(\S+)").unwrap());
+/// conventional-changelog writes patch releases one level deeper than
+/// major and minor ones, so deeper headings starting with a version are
+/// releases too.
+static LEADING_VERSION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[?v?\d+(?:\.\d+)+").unwrap());
+static LIST_ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[-*+]\s+(.*)$").unwrap());
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ParseChangelogInput {
+    #[schemars(description = "The changelog markdown, e.g. the contents of CHANGELOG.md")]
+    pub markdown: String,
+    #[schemars(description = "Only return releases newer than this version (exclusive)")]
+    pub since: Option<String>,
+    #[schemars(description = "Only return releases up to this version (inclusive)")]
+    pub until: Option<String>,
+    #[schemars(
+        description = "Include the Unreleased section (default: true, unless `until` is set)"
+    )]
+    pub include_unreleased: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct ChangelogCategory {
+    #[schemars(
+        description = "Category heading, e.g. Added, Fixed or Bug Fixes. `Changes` for items outside any category"
+    )]
+    pub name: String,
+    #[schemars(description = "List items as markdown, nested lists included")]
+    pub items: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct ChangelogRelease {
+    #[schemars(description = "Version without a leading `v`, or `Unreleased`")]
+    pub version: String,
+    #[schemars(description = "Release date as written (YYYY-MM-DD)")]
+    pub date: Option<String>,
+    pub yanked: bool,
+    #[schemars(
+        description = "Compare or release link, from the heading or a link reference definition"
+    )]
+    pub url: Option<String>,
+    pub categories: Vec<ChangelogCategory>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ChangelogOutput {
+    #[schemars(
+        description = "Releases in document order, newest first in a conventional changelog"
+    )]
+    pub releases: Vec<ChangelogRelease>,
+}
+
+/// Compares dotted versions numerically; a pre-release sorts before its
+/// release.
+pub(crate) fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn split(version: &str) -> (Vec<u64>, Option<&str>) {
+        let version = version.trim().trim_start_matches('v');
+        let version = version.split('+').next().unwrap_or_default();
+        let (release, pre) = match version.split_once('-') {
+            Some((release, pre)) => (release, Some(pre)),
+            None => (version, None),
+        };
+        let numbers = release
+            .split('.')
+            .map(|part| part.parse().unwrap_or_default())
+            .collect();
+        (numbers, pre)
+    }
+    let ((a_numbers, a_pre), (b_numbers, b_pre)) = (split(a), split(b));
+    let len = a_numbers.len().max(b_numbers.len());
+    for i in 0..len {
+        let ordering = a_numbers
+            .get(i)
+            .unwrap_or(&0)
+            .cmp(b_numbers.get(i).unwrap_or(&0));
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => a.cmp(b),
+    }
+}
+
+/// The release described by a heading, if it names a version or
+/// Unreleased.
+fn release_heading(text: &str) -> Option<ChangelogRelease> {
+    let version = match VERSION.captures(text) {
+        Some(caps) => caps[1].to_string(),
+        None if text.to_ascii_lowercase().contains("unreleased") => "Unreleased".to_string(),
+        None => return None,
+    };
+    Some(ChangelogRelease {
+        version,
+        date: DATE.find(text).map(|m| m.as_str().to_string()),
+        yanked: text.to_ascii_uppercase().contains("[YANKED]"),
+        url: INLINE_LINK.captures(text).map(|caps| caps[1].to_string()),
+        categories: Vec::new(),
+    })
+}
+
+/// Parses releases out of a changelog. Releases are the headings that name
+/// a version, at the level of the first such heading; deeper headings
+/// inside a release are categories.
+pub(crate) fn parse_changelog(markdown: &str) -> Vec<ChangelogRelease> {
+    let mut links = HashMap::new();
+    let mut releases: Vec<ChangelogRelease> = Vec::new();
+    let mut release_level = None;
+    let mut in_fence = false;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        if !in_fence {
+            if let Some(caps) = LINK_DEFINITION.captures(line) {
+                links.insert(caps[1].to_ascii_lowercase(), caps[2].to_string());
+                continue;
+            }
+            if let Some(caps) = HEADING.captures(line) {
+                let level = caps[1].len();
+                let text = &caps[2];
+                match release_level {
+                    Some(release_level) if level < release_level => {
+                        // A shallower heading ends the release list.
+                        if !releases.is_empty() {
+                            break;
+                        }
+                    }
+                    Some(release_level) if level == release_level => {
+                        if let Some(release) = release_heading(text) {
+                            releases.push(release);
+                        }
+                        continue;
+                    }
+                    Some(_) if LEADING_VERSION.is_match(text) => {
+                        releases.extend(release_heading(text));
+                        continue;
+                    }
+                    Some(_) => {
+                        if let Some(release) = releases.last_mut() {
+                            release.categories.push(ChangelogCategory {
+                                name: text.to_string(),
+                                items: Vec::new(),
+                            });
+                        }
+                        continue;
+                    }
+                    None => {
+                        if let Some(release) = release_heading(text) {
+                            release_level = Some(level);
+                            releases.push(release);
+                        }
+                        continue;
+                    }
+                }
+            }
+        }
+
+        let Some(release) = releases.last_mut() else {
+            continue;
+        };
+        if let Some(caps) = LIST_ITEM.captures(line).filter(|_| !in_fence) {
+            if release.categories.is_empty() {
+                release.categories.push(ChangelogCategory {
+                    name: UNCATEGORIZED.to_string(),
+                    items: Vec::new(),
+                });
+            }
+            if let Some(category) = release.categories.last_mut() {
+                category.items.push(caps[1].trim_end().to_string());
+            }
+        } else if line.starts_with([' ', '\t']) || in_fence || trimmed.starts_with("```") {
+            // Continuation of the current item, e.g. a nested list.
+            if let Some(item) = release
+                .categories
+                .last_mut()
+                .and_then(|category| category.items.last_mut())
+            {
+                item.push('\n');
+                item.push_str(line.trim_end());
+            }
+        }
+    }
+
+    for release in &mut releases {
+        if release.url.is_none() {
+            let key = release.version.to_ascii_lowercase();
+            release.url = links
+                .get(&key)
+                .or_else(|| links.get(&format!("v{key}")))
+                .cloned();
+        }
+        release
+            .categories
+            .retain(|category| !category.items.is_empty());
+    }
+    releases
+}
+
+#[tool_router(router = changelog_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Parse a changelog (Keep a Changelog, or the CHANGELOG.md layout of conventional-changelog and similar tools) into releases with version, date, yanked flag, link and categorized items (Added, Fixed, ...). Filter with `since` (exclusive) and `until` (inclusive) versions.",
+        output_schema = output_schema::<ChangelogOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn parse_changelog(&self, Parameters(input): Parameters<ParseChangelogInput>) -> McpResult {
+        let include_unreleased = input.include_unreleased.unwrap_or(input.until.is_none());
+        let releases = parse_changelog(&input.markdown)
+            .into_iter()
+            .filter(|release| {
+                if release.version == "Unreleased" {
+                    return include_unreleased;
+                }
+                input
+                    .since
+                    .as_deref()
+                    .is_none_or(|since| compare_versions(&release.version, since).is_gt())
+                    && input
+                        .until
+                        .as_deref()
+                        .is_none_or(|until| compare_versions(&release.version, until).is_le())
+            })
+            .collect();
+        json_result(&ChangelogOutput { releases })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const KEEP_A_CHANGELOG: &str = "\
+# Changelog
+
+All notable changes to this project will be documented in this file.
+
+## [Unreleased]
+
+### Added
+- Spanish translation.
+
+## [1.1.0] - 2023-03-05
+
+### Added
+- Arabic translation.
+- Version navigation:
+  - previous and next links
+
+### Fixed
+- Typo in the README.
+
+## [1.0.0] - 2017-06-20 [YANKED]
+
+- First release.
+
+[unreleased]: https://github.com/olivierlacan/keep-a-changelog/compare/v1.1.0...HEAD
+[1.1.0]: https://github.com/olivierlacan/keep-a-changelog/compare/v1.0.0...v1.1.0
+";
+
+    #[test]
+    fn test_parse_keep_a_changelog() {
+        let releases = parse_changelog(KEEP_A_CHANGELOG);
+        assert_eq!(
+            releases,
+            vec![
+                ChangelogRelease {
+                    version: "Unreleased".to_string(),
+                    date: None,
+                    yanked: false,
+                    url: Some(
+                        "https://github.com/olivierlacan/keep-a-changelog/compare/v1.1.0...HEAD"
+                            .to_string()
+                    ),
+                    categories: vec![ChangelogCategory {
+                        name: "Added".to_string(),
+                        items: vec!["Spanish translation.".to_string()],
+                    }],
+                },
+                ChangelogRelease {
+                    version: "1.1.0".to_string(),
+                    date: Some("2023-03-05".to_string()),
+                    yanked: false,
+                    url: Some(
+                        "https://github.com/olivierlacan/keep-a-changelog/compare/v1.0.0...v1.1.0"
+                            .to_string()
+                    ),
+                    categories: vec![
+                        ChangelogCategory {
+                            name: "Added".to_string(),
+                            items: vec![
+                                "Arabic translation.".to_string(),
+                                "Version navigation:\n  - previous and next links".to_string(),
+                            ],
+                        },
+                        ChangelogCategory {
+                            name: "Fixed".to_string(),
+                            items: vec!["Typo in the README.".to_string()],
+                        },
+                    ],
+                },
+                ChangelogRelease {
+                    version: "1.0.0".to_string(),
+                    date: Some("2017-06-20".to_string()),
+                    yanked: true,
+                    url: None,
+                    categories: vec![ChangelogCategory {
+                        name: UNCATEGORIZED.to_string(),
+                        items: vec!["First release.".to_string()],
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_conventional_changelog_heading() {
+        let releases = parse_changelog(
+            "# [2.0.0](https://github.com/o/r/compare/v1.9.0...v2.0.0) (2024-01-02)\n\n### Bug Fixes\n\n* **parser:** handle empty input ([abc1234](https://github.com/o/r/commit/abc1234))\n\n## [1.9.1](https://github.com/o/r/compare/v1.9.0...v1.9.1) (2023-12-01)\n\n* fix typo\n",
+        );
+        assert_eq!(releases.len(), 2);
+        assert_eq!(releases[0].version, "2.0.0");
+        assert_eq!(releases[0].date.as_deref(), Some("2024-01-02"));
+        assert_eq!(
+            releases[0].url.as_deref(),
+            Some("https://github.com/o/r/compare/v1.9.0...v2.0.0")
+        );
+        assert_eq!(releases[0].categories[0].name, "Bug Fixes");
+        assert_eq!(releases[1].version, "1.9.1");
+        assert_eq!(releases[1].categories[0].items, vec!["fix typo"]);
+    }
+
+    #[rstest]
+    #[case("1.2.0", "1.10.0", Ordering::Less)]
+    #[case("v2.0", "2.0.0", Ordering::Equal)]
+    #[case("1.0.0-rc.1", "1.0.0", Ordering::Less)]
+    #[case("1.0.0+build.5", "1.0.0", Ordering::Equal)]
+    #[case("0.10.1", "0.9.9", Ordering::Greater)]
+    fn test_compare_versions(#[case] a: &str, #[case] b: &str, #[case] expected: Ordering) {
+        assert_eq!(compare_versions(a, b), expected);
+    }
+}
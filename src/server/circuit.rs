@@ -0,0 +1,111 @@
+//! Per-host circuit breaker for remote fetches: after repeated failures a
+//! host is skipped for a cooldown period instead of piling up requests
+//! that are likely to time out.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Default)]
+struct HostState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// The default breaker is disabled.
+#[derive(Debug, Default)]
+pub(crate) struct CircuitBreaker {
+    /// Consecutive failures that open the circuit; 0 disables the breaker.
+    threshold: u32,
+    cooldown: Duration,
+    hosts: HashMap<String, HostState>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            hosts: HashMap::new(),
+        }
+    }
+
+    /// Whether a request to `host` may be sent. Returns how long until the
+    /// host will be tried again if its circuit is open. Once the cooldown
+    /// has passed, a single trial request is let through and the circuit
+    /// stays open for everyone else until it succeeds.
+    pub(crate) fn check(&mut self, host: &str, now: Instant) -> Result<(), Duration> {
+        let Some(state) = self.hosts.get_mut(host) else {
+            return Ok(());
+        };
+        match state.open_until {
+            Some(until) if now < until => Err(until - now),
+            Some(_) => {
+                state.open_until = Some(now + self.cooldown);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn record_success(&mut self, host: &str) {
+        self.hosts.remove(host);
+    }
+
+    pub(crate) fn record_failure(&mut self, host: &str, now: Instant) {
+        if self.threshold == 0 {
+            return;
+        }
+        let state = self.hosts.entry(host.to_string()).or_default();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.threshold {
+            if state.open_until.is_none() {
+                tracing::warn!("opening circuit for {host} for {:?}", self.cooldown);
+            }
+            state.open_until = Some(now + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_opens_after_threshold_and_probes_after_cooldown() {
+        let cooldown = Duration::from_secs(30);
+        let mut breaker = CircuitBreaker::new(2, cooldown);
+        let now = Instant::now();
+
+        breaker.record_failure("a.test", now);
+        assert!(breaker.check("a.test", now).is_ok());
+        breaker.record_failure("a.test", now);
+        assert_eq!(breaker.check("a.test", now), Err(cooldown));
+        assert!(breaker.check("b.test", now).is_ok());
+
+        let later = now + cooldown;
+        assert!(breaker.check("a.test", later).is_ok());
+        assert!(
+            breaker.check("a.test", later).is_err(),
+            "only one probe at a time"
+        );
+        breaker.record_failure("a.test", later);
+        assert!(breaker.check("a.test", later).is_err());
+
+        let much_later = later + cooldown;
+        assert!(breaker.check("a.test", much_later).is_ok());
+        breaker.record_success("a.test");
+        assert!(breaker.check("a.test", much_later).is_ok());
+    }
+
+    #[test]
+    fn test_threshold_zero_disables_breaker() {
+        let mut breaker = CircuitBreaker::new(0, Duration::from_secs(30));
+        let now = Instant::now();
+        for _ in 0..10 {
+            breaker.record_failure("a.test", now);
+        }
+        assert!(breaker.check("a.test", now).is_ok());
+    }
+}
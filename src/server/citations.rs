@@ -0,0 +1,690 @@
+//! `citations`: the pandoc citations of a document (`[@doe99, p. 33]`,
+//! `[-@doe99]`, in-text `@doe99`) checked against a BibTeX bibliography,
+//! with the keys it lacks and, on request, a references section for the
+//! entries cited. Like wiki-links, citations are plain text to markdown
+//! parsers, so they are found by scanning the source, skipping code.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    sync::LazyLock,
+};
+
+use regex::Regex;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    admonitions::Fences,
+    output::{json_result, output_schema},
+    vault,
+    wikilinks::blank_code,
+};
+
+/// A citation key after `@`, not preceded by a word character (which
+/// makes it part of an email address): `@{key}`, or a word that may have
+/// punctuation inside it, as pandoc reads keys.
+static KEY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|[^\w@.])-?@(?:\{([^{}\s]+)\}|(\w(?:[\w:.#$%&+?<>~/-]*\w)?))").unwrap()
+});
+/// A bracketed group of citations, e.g. `[see @doe99, p. 3; @roe20]`.
+static GROUP: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[([^\[\]]*@[^\[\]]*)\]").unwrap());
+
+#[derive(Debug, Clone, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct Citation {
+    #[schemars(description = "The citation key, without `@`")]
+    pub key: String,
+    #[schemars(description = "1-based line of the citation")]
+    pub line: usize,
+    #[schemars(
+        description = "The citation as written: its bracketed group, e.g. `[see @doe99, p. 33; @roe20]`, or the key of an in-text citation"
+    )]
+    pub text: String,
+}
+
+/// The citations of one line, with code blanked out, in order.
+fn scan_line(line: &str, number: usize) -> Vec<Citation> {
+    let mut text = line.to_string();
+    let mut found = Vec::new();
+    for group in GROUP.captures_iter(line) {
+        let whole = group.get(0).unwrap();
+        // `[@key](url)` and `[@key][ref]` are links.
+        if !line[whole.end()..].starts_with(['(', '[']) {
+            for (_, key) in keys(&group[1]) {
+                found.push((
+                    whole.start(),
+                    Citation {
+                        key,
+                        line: number,
+                        text: whole.as_str().to_string(),
+                    },
+                ));
+            }
+        }
+        text.replace_range(whole.range(), &" ".repeat(whole.len()));
+    }
+    for (at, key) in keys(&text) {
+        found.push((
+            at,
+            Citation {
+                text: format!("@{key}"),
+                key,
+                line: number,
+            },
+        ));
+    }
+    found.sort_by_key(|&(at, _)| at);
+    found.into_iter().map(|(_, citation)| citation).collect()
+}
+
+/// The keys cited in `text`, with their offsets.
+fn keys(text: &str) -> Vec<(usize, String)> {
+    KEY.captures_iter(text)
+        .filter_map(|captures| captures.get(1).or_else(|| captures.get(2)))
+        .map(|key| (key.start(), key.as_str().to_string()))
+        .collect()
+}
+
+/// The citations of `markdown`, in document order. Front matter, fenced
+/// code blocks and code spans are skipped.
+fn parse(markdown: &str) -> Vec<Citation> {
+    let body = vault::front_matter(markdown).map_or(0, |(_, end)| end);
+    let first_line = markdown[..body].matches('\n').count() + 1;
+    let mut citations = Vec::new();
+    let mut fences = Fences::default();
+    for (number, line) in (first_line..).zip(markdown[body..].lines()) {
+        if fences.code(line) {
+            continue;
+        }
+        citations.extend(scan_line(&blank_code(line), number));
+    }
+    citations
+}
+
+/// An entry of a BibTeX file.
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    /// The entry type in lowercase, e.g. `article`.
+    kind: String,
+    key: String,
+    /// Field values by lowercase name, with `@string` macros expanded.
+    fields: BTreeMap<String, String>,
+}
+
+/// Reads BibTeX source one entry at a time.
+struct BibParser<'a> {
+    source: &'a str,
+    at: usize,
+    macros: HashMap<String, String>,
+}
+
+fn invalid_bibtex(source: &str, at: usize) -> ErrorData {
+    ErrorData::invalid_params(
+        "Invalid BibTeX",
+        Some(serde_json::json!({ "line": source[..at].matches('\n').count() + 1 })),
+    )
+}
+
+impl<'a> BibParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.at..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.at = self.source.len() - self.rest().trim_start().len();
+    }
+
+    fn error(&self) -> ErrorData {
+        invalid_bibtex(self.source, self.at)
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ErrorData> {
+        self.skip_whitespace();
+        if !self.rest().starts_with(c) {
+            return Err(self.error());
+        }
+        self.at += c.len_utf8();
+        Ok(())
+    }
+
+    /// A run of characters up to whitespace or one of `stop`.
+    fn word(&mut self, stop: &[char]) -> &'a str {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || stop.contains(&c))
+            .unwrap_or(rest.len());
+        self.at += end;
+        &rest[..end]
+    }
+
+    /// The text up to the brace closing the one just read, braces inside
+    /// it balanced.
+    fn braced(&mut self) -> Result<&'a str, ErrorData> {
+        let start = self.at;
+        let mut depth = 1;
+        for (i, c) in self.rest().char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.at = start + i + 1;
+                        return Ok(&self.source[start..start + i]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err(invalid_bibtex(self.source, start))
+    }
+
+    /// A field value: `{...}`, `"..."`, a number or a macro, or several
+    /// joined with `#`.
+    fn value(&mut self) -> Result<String, ErrorData> {
+        let mut value = String::new();
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with('{') {
+                self.at += 1;
+                value.push_str(self.braced()?);
+            } else if let Some(quoted) = rest.strip_prefix('"') {
+                let mut depth = 0;
+                let end = quoted
+                    .char_indices()
+                    .find(|&(_, c)| {
+                        match c {
+                            '{' => depth += 1,
+                            '}' => depth -= 1,
+                            _ => {}
+                        }
+                        c == '"' && depth == 0
+                    })
+                    .map(|(i, _)| i)
+                    .ok_or_else(|| self.error())?;
+                value.push_str(&quoted[..end]);
+                self.at += end + 2;
+            } else {
+                let word = self.word(&[',', '}', ')', '#']);
+                if word.is_empty() {
+                    return Err(self.error());
+                }
+                value.push_str(
+                    self.macros
+                        .get(&word.to_lowercase())
+                        .map_or(word, String::as_str),
+                );
+            }
+            self.skip_whitespace();
+            if !self.rest().starts_with('#') {
+                return Ok(value);
+            }
+            self.at += 1;
+        }
+    }
+
+    /// `name = value` pairs up to `close`.
+    fn fields(&mut self, close: char) -> Result<BTreeMap<String, String>, ErrorData> {
+        let mut fields = BTreeMap::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with(close) {
+                self.at += 1;
+                return Ok(fields);
+            }
+            let name = self.word(&['=', ',', close]).to_lowercase();
+            if name.is_empty() {
+                return Err(self.error());
+            }
+            self.expect('=')?;
+            let value = self.value()?;
+            fields.insert(name, value);
+            self.skip_whitespace();
+            if self.rest().starts_with(',') {
+                self.at += 1;
+            } else if !self.rest().starts_with(close) {
+                return Err(self.error());
+            }
+        }
+    }
+
+    /// The next entry, `@string` definitions, `@comment`s and `@preamble`s
+    /// being read past; `None` at the end of the source. Text outside
+    /// entries is a comment, as in BibTeX.
+    fn next_entry(&mut self) -> Result<Option<Entry>, ErrorData> {
+        loop {
+            let Some(start) = self.rest().find('@') else {
+                self.at = self.source.len();
+                return Ok(None);
+            };
+            self.at += start + 1;
+            let kind = self.word(&['{', '(']).to_lowercase();
+            self.skip_whitespace();
+            let close = match self.rest().chars().next() {
+                Some('{') => '}',
+                Some('(') => ')',
+                _ => return Err(self.error()),
+            };
+            self.at += 1;
+            match kind.as_str() {
+                "comment" | "preamble" => {
+                    self.braced()?;
+                }
+                "string" => {
+                    for (name, value) in self.fields(close)? {
+                        self.macros.insert(name, value);
+                    }
+                }
+                _ => {
+                    let key = self.word(&[',', close]).to_string();
+                    if key.is_empty() {
+                        return Err(self.error());
+                    }
+                    self.skip_whitespace();
+                    if self.rest().starts_with(',') {
+                        self.at += 1;
+                    }
+                    let fields = self.fields(close)?;
+                    return Ok(Some(Entry { kind, key, fields }));
+                }
+            }
+        }
+    }
+}
+
+/// The entries of the BibTeX `source`, in order.
+fn parse_bibtex(source: &str) -> Result<Vec<Entry>, ErrorData> {
+    let mut parser = BibParser {
+        source,
+        at: 0,
+        macros: [
+            "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+        ]
+        .iter()
+        .map(|month| (month.to_string(), month.to_string()))
+        .collect(),
+    };
+    let mut entries = Vec::new();
+    while let Some(entry) = parser.next_entry()? {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// `value` as plain text: braces dropped, common escapes undone and
+/// whitespace collapsed.
+fn plain(value: &str) -> String {
+    let value = value
+        .replace(['{', '}'], "")
+        .replace("\\&", "&")
+        .replace("\\%", "%")
+        .replace("\\_", "_")
+        .replace("\\$", "$")
+        .replace("---", "—")
+        .replace("--", "–")
+        .replace('~', " ");
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The authors (or else editors) of `entry` as `First Last`, joined as a
+/// list.
+fn names(entry: &Entry) -> Option<String> {
+    let names = entry
+        .fields
+        .get("author")
+        .or_else(|| entry.fields.get("editor"))?;
+    let names: Vec<String> = plain(names)
+        .split(" and ")
+        .map(|name| match name.split_once(',') {
+            Some((last, first)) => format!("{} {}", first.trim(), last.trim()),
+            None => name.trim().to_string(),
+        })
+        .collect();
+    Some(match names.as_slice() {
+        [] => return None,
+        [name] => name.clone(),
+        [first, second] => format!("{first} and {second}"),
+        [rest @ .., last] => format!("{}, and {last}", rest.join(", ")),
+    })
+}
+
+/// The first author's last name, for ordering references.
+fn sort_name(entry: &Entry) -> String {
+    let names = entry
+        .fields
+        .get("author")
+        .or_else(|| entry.fields.get("editor"))
+        .map(|names| plain(names))
+        .unwrap_or_default();
+    let first = names.split(" and ").next().unwrap_or_default();
+    let last = match first.split_once(',') {
+        Some((last, _)) => last,
+        None => first.rsplit(' ').next().unwrap_or_default(),
+    };
+    last.trim().to_lowercase()
+}
+
+/// `entry` as one reference: authors, year, title, where it appeared and
+/// how to find it.
+fn reference(entry: &Entry) -> String {
+    let field = |name: &str| entry.fields.get(name).map(|value| plain(value));
+    let mut parts = Vec::new();
+    let year = field("year")
+        .or_else(|| field("date").map(|date| date.chars().take(4).collect()))
+        .unwrap_or_else(|| "n.d.".to_string());
+    parts.push(match names(entry) {
+        Some(names) => format!("{names} ({year})."),
+        None => format!("({year})."),
+    });
+    if let Some(title) = field("title") {
+        parts.push(match entry.kind.as_str() {
+            "book" | "phdthesis" | "mastersthesis" | "manual" => format!("*{title}*."),
+            _ => format!("{title}."),
+        });
+    }
+    let container = field("journal")
+        .or_else(|| field("journaltitle"))
+        .or_else(|| field("booktitle"));
+    if let Some(container) = container {
+        let mut text = format!("*{container}*");
+        if let Some(volume) = field("volume") {
+            text.push_str(&format!(", {volume}"));
+            if let Some(number) = field("number").or_else(|| field("issue")) {
+                text.push_str(&format!("({number})"));
+            }
+        }
+        if let Some(pages) = field("pages") {
+            text.push_str(&format!(", {pages}"));
+        }
+        parts.push(format!("{text}."));
+    }
+    if let Some(publisher) = field("publisher")
+        .or_else(|| field("school"))
+        .or_else(|| field("institution"))
+    {
+        parts.push(format!("{publisher}."));
+    }
+    if let Some(doi) = field("doi") {
+        parts.push(format!(
+            "https://doi.org/{}",
+            doi.trim_start_matches("https://doi.org/")
+        ));
+    } else if let Some(url) = field("url") {
+        parts.push(url);
+    }
+    parts.join(" ")
+}
+
+/// A references section for `entries`, ordered by first author and year.
+fn references(entries: &[&Entry], heading: &str) -> String {
+    let mut entries = entries.to_vec();
+    entries.sort_by_cached_key(|entry| {
+        (
+            sort_name(entry),
+            entry.fields.get("year").cloned().unwrap_or_default(),
+            entry.key.clone(),
+        )
+    });
+    let mut section = format!("## {heading}\n\n");
+    for entry in entries {
+        section.push_str(&format!("- {}\n", reference(entry)));
+    }
+    section
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct CitationsInput {
+    #[schemars(description = "Markdown content to check")]
+    pub markdown: String,
+    #[schemars(description = "Path of the BibTeX file to check the citations against")]
+    pub bibliography: Option<String>,
+    #[schemars(
+        description = "The BibTeX source to check the citations against, instead of a file"
+    )]
+    pub bibtex: Option<String>,
+    #[schemars(
+        description = "Also return a references section listing the entries cited (default: false)"
+    )]
+    pub references: Option<bool>,
+    #[schemars(description = "Heading of the references section (default: `References`)")]
+    pub heading: Option<String>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct CitedKey {
+    pub key: String,
+    #[schemars(description = "Number of times the key is cited")]
+    pub count: usize,
+    #[schemars(description = "Whether the bibliography has an entry for the key")]
+    pub resolved: bool,
+}
+
+/// Output of `citations`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct CitationsOutput {
+    #[schemars(description = "Every citation, in document order")]
+    pub citations: Vec<Citation>,
+    #[schemars(description = "Each key cited, once, in the order first cited")]
+    pub keys: Vec<CitedKey>,
+    #[schemars(description = "Keys cited that the bibliography has no entry for")]
+    pub unresolved: Vec<String>,
+    #[schemars(description = "Keys of the bibliography's entries that are never cited")]
+    pub uncited: Vec<String>,
+    #[schemars(description = "Keys with more than one entry in the bibliography")]
+    pub duplicates: Vec<String>,
+    #[schemars(description = "The references section, when `references` is set")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub references: Option<String>,
+}
+
+fn citations(
+    markdown: &str,
+    bibtex: &str,
+    input: &CitationsInput,
+) -> Result<CitationsOutput, ErrorData> {
+    let entries = parse_bibtex(bibtex)?;
+    let mut by_key: HashMap<&str, &Entry> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for entry in &entries {
+        if !by_key.contains_key(entry.key.as_str()) {
+            by_key.insert(&entry.key, entry);
+        } else if !duplicates.contains(&entry.key) {
+            duplicates.push(entry.key.clone());
+        }
+    }
+    let citations = parse(markdown);
+    let mut keys: Vec<CitedKey> = Vec::new();
+    for citation in &citations {
+        match keys.iter_mut().find(|key| key.key == citation.key) {
+            Some(key) => key.count += 1,
+            None => keys.push(CitedKey {
+                key: citation.key.clone(),
+                count: 1,
+                resolved: by_key.contains_key(citation.key.as_str()),
+            }),
+        }
+    }
+    let cited: HashSet<&str> = keys.iter().map(|key| key.key.as_str()).collect();
+    let mut uncited = Vec::new();
+    for entry in &entries {
+        if !cited.contains(entry.key.as_str()) && !uncited.contains(&entry.key) {
+            uncited.push(entry.key.clone());
+        }
+    }
+    let references = input.references.unwrap_or(false).then(|| {
+        let cited: Vec<&Entry> = keys
+            .iter()
+            .filter_map(|key| by_key.get(key.key.as_str()).copied())
+            .collect();
+        references(&cited, input.heading.as_deref().unwrap_or("References"))
+    });
+    Ok(CitationsOutput {
+        unresolved: keys
+            .iter()
+            .filter(|key| !key.resolved)
+            .map(|key| key.key.clone())
+            .collect(),
+        citations,
+        keys,
+        uncited,
+        duplicates,
+        references,
+    })
+}
+
+#[tool_router(router = citations_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Check the pandoc citations of markdown (`[@doe99, p. 33]`, `[-@doe99]`, in-text `@doe99`) against a BibTeX bibliography: the keys cited and how often, the keys with no entry, the entries never cited and duplicate keys. Can also render a references section for the entries cited. For checking citation integrity in academic writing.",
+        output_schema = output_schema::<CitationsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn citations(&self, Parameters(input): Parameters<CitationsInput>) -> McpResult {
+        let bibtex = match (&input.bibliography, &input.bibtex) {
+            (Some(path), None) => {
+                let path = self.scoped_path(path)?;
+                fs::read_to_string(&path).map_err(|e| {
+                    ErrorData::invalid_params(
+                        "Failed to read file",
+                        Some(serde_json::json!({ "path": path, "error": e.to_string() })),
+                    )
+                })?
+            }
+            (None, Some(bibtex)) => bibtex.clone(),
+            _ => {
+                return Err(ErrorData::invalid_params(
+                    "Give exactly one of `bibliography` or `bibtex`",
+                    None,
+                ));
+            }
+        };
+        json_result(&citations(&input.markdown, &bibtex, &input)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const BIBTEX: &str = r#"
+Comments outside entries are ignored.
+@string{ acm = "ACM Press" }
+@comment{ an aside with {braces} }
+@article{doe99,
+  author = {Doe, John and Jane Roe},
+  title = {On {Markdown} Queries},
+  journal = "Journal of Text",
+  year = 1999,
+  volume = {12}, number = {3}, pages = {45--67},
+  doi = {10.1000/jot.12.3},
+}
+@book(smith2020,
+  author = "Smith, Ada",
+  title = "Tools \& Methods",
+  publisher = acm # " Books",
+  year = {2020}
+)
+@misc{unused, title = {Never Cited}}
+@misc{unused, title = {Again}}
+"#;
+
+    fn input() -> CitationsInput {
+        CitationsInput {
+            markdown: String::new(),
+            bibliography: None,
+            bibtex: None,
+            references: Some(true),
+            heading: None,
+        }
+    }
+
+    #[rstest]
+    #[case("As shown [@doe99].", vec![("doe99", "[@doe99]")])]
+    #[case(
+        "See [see @doe99, p. 33; -@smith2020].",
+        vec![("doe99", "[see @doe99, p. 33; -@smith2020]"), ("smith2020", "[see @doe99, p. 33; -@smith2020]")]
+    )]
+    #[case("@doe99 says so [p. 4].", vec![("doe99", "@doe99")])]
+    #[case("Keyed [@{doe:99.x}].", vec![("doe:99.x", "[@{doe:99.x}]")])]
+    #[case("Mail ada@example.com, `[@code]` or [@link](https://example.com).", vec![])]
+    fn test_parse(#[case] markdown: &str, #[case] expected: Vec<(&str, &str)>) {
+        assert_eq!(
+            parse(markdown)
+                .iter()
+                .map(|citation| (citation.key.as_str(), citation.text.as_str()))
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_skips_code() {
+        let markdown = "---\nbibliography: refs.bib\n---\n```\n[@fenced]\n```\nText [@doe99].\n";
+        assert_eq!(
+            parse(markdown),
+            vec![Citation {
+                key: "doe99".to_string(),
+                line: 7,
+                text: "[@doe99]".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_bibtex() {
+        let entries = parse_bibtex(BIBTEX).unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.kind.as_str(), entry.key.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("article", "doe99"),
+                ("book", "smith2020"),
+                ("misc", "unused"),
+                ("misc", "unused"),
+            ]
+        );
+        assert_eq!(entries[0].fields["title"], "On {Markdown} Queries");
+        assert_eq!(entries[0].fields["year"], "1999");
+        assert_eq!(entries[1].fields["publisher"], "ACM Press Books");
+        assert!(parse_bibtex("@article{broken, title = {unclosed}").is_err());
+    }
+
+    #[test]
+    fn test_citations() {
+        let markdown = "Intro [@doe99; @missing].\n\nLater @doe99 and [@smith2020, ch. 2].\n";
+        let output = citations(markdown, BIBTEX, &input()).unwrap();
+        assert_eq!(
+            output
+                .keys
+                .iter()
+                .map(|key| (key.key.as_str(), key.count, key.resolved))
+                .collect::<Vec<_>>(),
+            vec![
+                ("doe99", 2, true),
+                ("missing", 1, false),
+                ("smith2020", 1, true),
+            ]
+        );
+        assert_eq!(output.unresolved, vec!["missing"]);
+        assert_eq!(output.uncited, vec!["unused"]);
+        assert_eq!(output.duplicates, vec!["unused"]);
+        assert_eq!(
+            output.references.unwrap(),
+            "## References\n\n\
+             - John Doe and Jane Roe (1999). On Markdown Queries. *Journal of Text*, 12(3), 45–67. https://doi.org/10.1000/jot.12.3\n\
+             - Ada Smith (2020). *Tools & Methods*. ACM Press Books.\n"
+        );
+    }
+
+    #[test]
+    fn test_bibliography_source() {
+        let server = Server::new(None).unwrap();
+        assert!(server.citations(Parameters(input())).is_err());
+    }
+}
@@ -0,0 +1,549 @@
+//! The `strict-commonmark` post-processing step: markdown written so that
+//! it parses back to exactly the tree it was written from. The renderer
+//! behind query results favours the source's spelling, and some of it (a
+//! literal `*` or `_` left unescaped, a fence no longer than the code it
+//! holds, `_` and `*` emphasis side by side) reads differently to the next
+//! parser. The step parses each value and writes it again with one spelling
+//! per construct: ATX headings (setext for multi-line ones), `*` emphasis
+//! and `**` strong, `-` bullets and `.` ordered lists (`*` and `)` for a
+//! list right after one of the same kind), fences longer than any run in
+//! the code, and backslash escapes before every character that could start
+//! markup.
+//!
+//! The result is parsed again and compared with the value's tree; a value
+//! that can't be written back exactly (some emphasis next to punctuation
+//! can't be) is returned unchanged. The extensions the server parses
+//! (tables, strikethrough, task lists, footnotes, math, front matter) keep
+//! their GFM syntax.
+
+use markdown::{
+    Constructs, ParseOptions,
+    mdast::{AlignKind, Heading, List, Node, ReferenceKind},
+};
+
+/// The constructs query results are parsed with.
+fn parse_options() -> ParseOptions {
+    ParseOptions {
+        constructs: Constructs {
+            frontmatter: true,
+            math_flow: true,
+            math_text: true,
+            ..Constructs::gfm()
+        },
+        gfm_strikethrough_single_tilde: true,
+        math_text_single_dollar: true,
+        ..ParseOptions::gfm()
+    }
+}
+
+/// `markdown` written in strict CommonMark, or unchanged if it can't be
+/// written back exactly.
+pub(crate) fn strict(markdown: &str) -> String {
+    write(markdown).unwrap_or_else(|| markdown.to_string())
+}
+
+/// `markdown` written in strict CommonMark, if the result parses to the
+/// same tree.
+fn write(markdown: &str) -> Option<String> {
+    let tree = markdown::to_mdast(markdown, &parse_options()).ok()?;
+    let Node::Root(root) = &tree else {
+        return None;
+    };
+    let written = blocks(&root.children, "\n\n");
+    let reparsed = markdown::to_mdast(&written, &parse_options()).ok()?;
+    (shape(&tree) == shape(&reparsed)).then_some(written)
+}
+
+/// `node` as JSON without source positions, to compare trees parsed from
+/// different text.
+fn shape(node: &Node) -> serde_json::Value {
+    let mut value = serde_json::to_value(node).unwrap_or_default();
+    strip_positions(&mut value);
+    value
+}
+
+fn strip_positions(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            object.remove("position");
+            object.values_mut().for_each(strip_positions);
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(strip_positions),
+        _ => {}
+    }
+}
+
+/// Where inline content is written, which decides what must be escaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Inline {
+    /// A paragraph or a setext heading, where a line can start a block.
+    Paragraph,
+    /// An ATX heading, where a `#` can close the heading.
+    Heading,
+    Cell,
+}
+
+fn blocks(nodes: &[Node], separator: &str) -> String {
+    let mut written = Vec::with_capacity(nodes.len());
+    // Adjacent lists of the same kind only stay apart with other markers.
+    let mut previous_list: Option<(bool, bool)> = None;
+    for node in nodes {
+        written.push(match node {
+            Node::List(list) => {
+                let alternate = previous_list
+                    .is_some_and(|(ordered, alternate)| ordered == list.ordered && !alternate);
+                previous_list = Some((list.ordered, alternate));
+                write_list(list, alternate)
+            }
+            node => {
+                previous_list = None;
+                block(node)
+            }
+        });
+    }
+    written.join(separator)
+}
+
+fn block(node: &Node) -> String {
+    match node {
+        Node::Paragraph(paragraph) => inlines(&paragraph.children, Inline::Paragraph),
+        Node::Heading(heading) => write_heading(heading),
+        Node::ThematicBreak(_) => "***".to_string(),
+        Node::Blockquote(quote) => blocks(&quote.children, "\n\n")
+            .split('\n')
+            .map(|line| {
+                if line.is_empty() {
+                    ">".to_string()
+                } else {
+                    format!("> {line}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Node::List(list) => write_list(list, false),
+        Node::Code(code) => {
+            let info = match (&code.lang, &code.meta) {
+                (Some(lang), Some(meta)) => format!("{lang} {meta}"),
+                (Some(lang), None) => lang.clone(),
+                (None, _) => String::new(),
+            };
+            fenced('`', 3, &info, &code.value)
+        }
+        Node::Math(math) => fenced(
+            '$',
+            2,
+            math.meta.as_deref().unwrap_or_default(),
+            &math.value,
+        ),
+        Node::Html(html) => html.value.clone(),
+        Node::Definition(definition) => format!(
+            "[{}]: {}{}",
+            definition
+                .label
+                .as_deref()
+                .unwrap_or(&definition.identifier),
+            destination(&definition.url),
+            title(definition.title.as_deref())
+        ),
+        Node::FootnoteDefinition(footnote) => hang(
+            &format!(
+                "[^{}]:",
+                footnote.label.as_deref().unwrap_or(&footnote.identifier)
+            ),
+            &blocks(&footnote.children, "\n\n"),
+            4,
+        ),
+        Node::Table(table) => {
+            let mut rows: Vec<String> = table
+                .children
+                .iter()
+                .map(|row| {
+                    let cells: Vec<String> = match row {
+                        Node::TableRow(row) => row
+                            .children
+                            .iter()
+                            .map(|cell| match cell {
+                                Node::TableCell(cell) => inlines(&cell.children, Inline::Cell),
+                                _ => String::new(),
+                            })
+                            .collect(),
+                        _ => Vec::new(),
+                    };
+                    format!("| {} |", cells.join(" | "))
+                })
+                .collect();
+            let delimiters: Vec<&str> = table
+                .align
+                .iter()
+                .map(|align| match align {
+                    AlignKind::Left => ":--",
+                    AlignKind::Right => "--:",
+                    AlignKind::Center => ":-:",
+                    AlignKind::None => "---",
+                })
+                .collect();
+            rows.insert(rows.len().min(1), format!("| {} |", delimiters.join(" | ")));
+            rows.join("\n")
+        }
+        Node::Yaml(yaml) => format!("---\n{}\n---", yaml.value),
+        Node::Toml(toml) => format!("+++\n{}\n+++", toml.value),
+        _ => String::new(),
+    }
+}
+
+fn write_heading(heading: &Heading) -> String {
+    if heading.depth <= 2 && multiline(&heading.children) {
+        let underline = if heading.depth == 1 { "===" } else { "---" };
+        return format!(
+            "{}\n{underline}",
+            inlines(&heading.children, Inline::Paragraph)
+        );
+    }
+    let marker = "#".repeat(heading.depth.into());
+    let content = inlines(&heading.children, Inline::Heading);
+    if content.is_empty() {
+        marker
+    } else {
+        format!("{marker} {content}")
+    }
+}
+
+/// Whether inline `nodes` span several lines.
+fn multiline(nodes: &[Node]) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Text(text) => text.value.contains('\n'),
+        Node::Break(_) => true,
+        node => node.children().is_some_and(|children| multiline(children)),
+    })
+}
+
+fn write_list(list: &List, alternate: bool) -> String {
+    let start = list.start.unwrap_or(1);
+    let items: Vec<String> = list
+        .children
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let Node::ListItem(item) = item else {
+                return String::new();
+            };
+            let marker = match (list.ordered, alternate) {
+                (true, false) => format!("{}.", start as usize + i),
+                (true, true) => format!("{})", start as usize + i),
+                (false, false) => "-".to_string(),
+                (false, true) => "*".to_string(),
+            };
+            let mut content = blocks(&item.children, if item.spread { "\n\n" } else { "\n" });
+            if let Some(checked) = item.checked {
+                content = format!("[{}] {content}", if checked { 'x' } else { ' ' });
+            }
+            hang(&marker, &content, marker.len() + 1)
+        })
+        .collect();
+    items.join(if list.spread { "\n\n" } else { "\n" })
+}
+
+/// `content` after `marker`, its following lines indented by `width`.
+fn hang(marker: &str, content: &str, width: usize) -> String {
+    let indent = " ".repeat(width);
+    let mut lines = content.split('\n');
+    let mut written = match lines.next() {
+        Some(first) if !first.is_empty() => format!("{marker} {first}"),
+        _ => marker.to_string(),
+    };
+    for line in lines {
+        written.push('\n');
+        if !line.is_empty() {
+            written.push_str(&indent);
+            written.push_str(line);
+        }
+    }
+    written
+}
+
+/// A fenced block whose fence is longer than any run of `marker` in
+/// `value`. Backtick fences can't have backticks in their info string, so
+/// those use tildes.
+fn fenced(marker: char, min: usize, info: &str, value: &str) -> String {
+    let marker = if marker == '`' && info.contains('`') {
+        '~'
+    } else {
+        marker
+    };
+    let fence = marker
+        .to_string()
+        .repeat(min.max(longest_run(value, marker) + 1));
+    if value.is_empty() {
+        format!("{fence}{info}\n{fence}")
+    } else {
+        format!("{fence}{info}\n{value}\n{fence}")
+    }
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    text.split(|other| other != c)
+        .map(|run| run.chars().count())
+        .max()
+        .unwrap_or_default()
+}
+
+fn inlines(nodes: &[Node], context: Inline) -> String {
+    let mut written = String::new();
+    write_inlines(&mut written, nodes, context, false);
+    written
+}
+
+/// Writes inline `nodes` to `out`. `in_strong` is whether their parent is
+/// strong, whose `**` an emphasis at its edge mustn't extend.
+fn write_inlines(out: &mut String, nodes: &[Node], context: Inline, in_strong: bool) {
+    for (i, node) in nodes.iter().enumerate() {
+        match node {
+            Node::Text(text) => {
+                let before_link = matches!(
+                    nodes.get(i + 1),
+                    Some(Node::Link(_) | Node::LinkReference(_))
+                );
+                escape(out, &text.value, context, before_link);
+            }
+            Node::Emphasis(emphasis) => {
+                let at_edge = |node: Option<&Node>| matches!(node, Some(Node::Strong(_)));
+                let marker = if (in_strong && (i == 0 || i + 1 == nodes.len()))
+                    || at_edge(emphasis.children.first())
+                    || at_edge(emphasis.children.last())
+                {
+                    "_"
+                } else {
+                    "*"
+                };
+                out.push_str(marker);
+                write_inlines(out, &emphasis.children, context, false);
+                out.push_str(marker);
+            }
+            Node::Strong(strong) => {
+                out.push_str("**");
+                write_inlines(out, &strong.children, context, true);
+                out.push_str("**");
+            }
+            Node::Delete(delete) => {
+                out.push_str("~~");
+                write_inlines(out, &delete.children, context, false);
+                out.push_str("~~");
+            }
+            Node::InlineCode(code) => code_span(out, &code.value, '`'),
+            Node::InlineMath(math) => code_span(out, &math.value, '$'),
+            Node::Break(_) => out.push_str("\\\n"),
+            Node::Link(link) => match autolink(link.url.as_str(), &link.children) {
+                Some(autolink) if link.title.is_none() => out.push_str(&autolink),
+                _ => {
+                    out.push('[');
+                    write_inlines(out, &link.children, context, false);
+                    out.push_str(&format!(
+                        "]({}{})",
+                        destination(&link.url),
+                        title(link.title.as_deref())
+                    ));
+                }
+            },
+            Node::LinkReference(reference) => {
+                out.push('[');
+                write_inlines(out, &reference.children, context, false);
+                out.push(']');
+                out.push_str(&reference_suffix(
+                    reference.reference_kind,
+                    reference.label.as_deref().unwrap_or(&reference.identifier),
+                ));
+            }
+            Node::Image(image) => {
+                out.push_str("![");
+                escape(out, &image.alt, context, false);
+                out.push_str(&format!(
+                    "]({}{})",
+                    destination(&image.url),
+                    title(image.title.as_deref())
+                ));
+            }
+            Node::ImageReference(reference) => {
+                out.push_str("![");
+                escape(out, &reference.alt, context, false);
+                out.push(']');
+                out.push_str(&reference_suffix(
+                    reference.reference_kind,
+                    reference.label.as_deref().unwrap_or(&reference.identifier),
+                ));
+            }
+            Node::FootnoteReference(reference) => out.push_str(&format!(
+                "[^{}]",
+                reference.label.as_deref().unwrap_or(&reference.identifier)
+            )),
+            Node::Html(html) => out.push_str(&html.value),
+            _ => {}
+        }
+    }
+}
+
+/// Writes `text` to `out`, escaping every character that could start
+/// markup where it stands. `before_link` is whether a link follows, which
+/// a trailing `!` would turn into an image.
+fn escape(out: &mut String, text: &str, context: Inline, before_link: bool) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut line_start = context == Inline::Paragraph && (out.is_empty() || out.ends_with('\n'));
+    // Characters since the start of the line, while they are all digits.
+    let mut digits = line_start.then_some(0);
+    for (i, &c) in chars.iter().enumerate() {
+        let next = chars.get(i + 1).copied();
+        let special = match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '~' | '$' | '|' => true,
+            '&' => next.is_some_and(|next| next.is_ascii_alphanumeric() || next == '#'),
+            '#' => line_start || context == Inline::Heading,
+            '>' | '-' | '+' | '=' => line_start,
+            '.' | ')' => digits.is_some_and(|digits| (1..=9).contains(&digits)),
+            '!' => before_link && next.is_none(),
+            _ => false,
+        };
+        if special {
+            out.push('\\');
+        }
+        out.push(c);
+        match c {
+            '\n' if context == Inline::Paragraph => {
+                line_start = true;
+                digits = Some(0);
+            }
+            ' ' if line_start => {}
+            c => {
+                line_start = false;
+                digits = digits
+                    .filter(|_| c.is_ascii_digit())
+                    .map(|digits| digits + 1);
+            }
+        }
+    }
+}
+
+/// Writes `value` as a code span (or inline formula) delimited by a run of
+/// `delimiter` that doesn't occur in it.
+fn code_span(out: &mut String, value: &str, delimiter: char) {
+    let runs: Vec<usize> = value
+        .split(|c| c != delimiter)
+        .map(|run| run.chars().count())
+        .collect();
+    let length = (1..).find(|length| !runs.contains(length)).unwrap_or(1);
+    let fence = delimiter.to_string().repeat(length);
+    // One space is stripped from each side when both have one.
+    let pad = value.starts_with(delimiter)
+        || value.ends_with(delimiter)
+        || (value.starts_with(' ') && value.ends_with(' ') && !value.trim().is_empty());
+    if pad {
+        out.push_str(&format!("{fence} {value} {fence}"));
+    } else {
+        out.push_str(&format!("{fence}{value}{fence}"));
+    }
+}
+
+/// `url` as an autolink (`<https://...>`), if the link's text is the URL.
+fn autolink(url: &str, children: &[Node]) -> Option<String> {
+    let [Node::Text(text)] = children else {
+        return None;
+    };
+    let (scheme, _) = url.split_once(':')?;
+    // Email autolinks show the address without `mailto:`.
+    let shown = match url.strip_prefix("mailto:") {
+        Some(address) if text.value == address => address,
+        _ => url,
+    };
+    let valid = text.value == shown
+        && (2..=32).contains(&scheme.len())
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'))
+        && !url.contains(|c: char| c.is_whitespace() || c.is_control() || c == '<' || c == '>');
+    valid.then(|| format!("<{shown}>"))
+}
+
+/// A link destination, in angle brackets unless it is plain.
+fn destination(url: &str) -> String {
+    if url.is_empty()
+        || url.contains(|c: char| {
+            c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | '(' | ')')
+        })
+    {
+        format!("<{}>", backslash(url, &['\\', '<', '>']))
+    } else {
+        backslash(url, &['\\'])
+    }
+}
+
+fn title(title: Option<&str>) -> String {
+    title
+        .map(|title| format!(" \"{}\"", backslash(title, &['\\', '"'])))
+        .unwrap_or_default()
+}
+
+/// `text` with `chars`, and `&` starting a character reference, escaped.
+fn backslash(text: &str, chars: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut rest = text.chars().peekable();
+    while let Some(c) = rest.next() {
+        let reference = c == '&'
+            && rest
+                .peek()
+                .is_some_and(|next| next.is_ascii_alphanumeric() || *next == '#');
+        if chars.contains(&c) || reference {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn reference_suffix(kind: ReferenceKind, label: &str) -> String {
+    match kind {
+        ReferenceKind::Full => format!("[{label}]"),
+        ReferenceKind::Collapsed => "[]".to_string(),
+        ReferenceKind::Shortcut => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("_a_ and __b__", "*a* and **b**")]
+    #[case("Setext\n===", "# Setext")]
+    #[case("\\# not a heading", "\\# not a heading")]
+    #[case("1\\. not a list", "1\\. not a list")]
+    #[case("2 \\* 3 = 6", "2 \\* 3 = 6")]
+    #[case("&amp;copy;", "\\&copy;")]
+    #[case("````\n```\n````", "````\n```\n````")]
+    #[case("`` a`b ``", "``a`b``")]
+    #[case("a  \nb", "a\\\nb")]
+    #[case("* one\n* two", "- one\n- two")]
+    #[case("1) first\n2) second", "1. first\n2. second")]
+    #[case("- a\n\n* b", "- a\n\n* b")]
+    #[case("* [ ] todo\n* [x] done", "- [ ] todo\n- [x] done")]
+    #[case("a | b\n--- | :-:\n1 | 2", "| a | b |\n| --- | :-: |\n| 1 | 2 |")]
+    fn test_strict(#[case] markdown: &str, #[case] expected: &str) {
+        assert_eq!(strict(markdown), expected);
+    }
+
+    #[rstest]
+    #[case("# Title\n\nSome *emphasis*, **strong** and `code`.")]
+    #[case("> quote\n>\n> - item")]
+    #[case("1. one\n2. two\n   - nested")]
+    #[case("```rust\nfn main() {}\n```")]
+    #[case("[link](https://example.com \"Title\") and ![alt](image.png)")]
+    #[case("<https://example.com>")]
+    #[case("| a | b |\n| --- | --: |\n| 1 | 2 |")]
+    #[case("Line one\nline two")]
+    #[case("Footnote[^1].\n\n[^1]: The note.")]
+    #[case("$x^2$ and\n\n$$\ny\n$$")]
+    #[case("---\ntitle: a\n---\n\n# Doc")]
+    #[case("* a\n\n* b")]
+    fn test_round_trip(#[case] markdown: &str) {
+        let written = write(markdown).expect("written back exactly");
+        assert_eq!(write(&written).as_deref(), Some(written.as_str()));
+    }
+}
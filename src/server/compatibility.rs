@@ -0,0 +1,301 @@
+//! `check_compatibility`: whether a query, typically a saved one shared
+//! between servers, only uses functions and selectors the engine this
+//! server was built with provides, and which of them it deprecates. A query
+//! written against a newer mq-lang can call functions an older server
+//! doesn't have, which otherwise only shows when it fails at run time.
+
+use std::collections::BTreeMap;
+
+use mq_hir::{HirError, SymbolKind};
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    engine::{self, Engine, QueryEngine},
+    lint,
+    output::{json_result, output_schema},
+};
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct CheckCompatibilityInput {
+    #[schemars(description = "The mq query to check; use either this or `name`")]
+    query: Option<String>,
+    #[schemars(description = "Name of a query saved with save_query to check")]
+    name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ReferenceKind {
+    Function,
+    Selector,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Availability {
+    Available,
+    Deprecated,
+    Missing,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct Reference {
+    pub kind: ReferenceKind,
+    pub name: String,
+    #[schemars(
+        description = "`available`, `deprecated` (available, but slated for removal) or `missing` from this engine"
+    )]
+    pub status: Availability,
+    #[schemars(description = "Times the query uses it")]
+    pub uses: usize,
+    #[schemars(description = "1-based line of the first use")]
+    pub line: u32,
+    #[schemars(description = "1-based column of the first use")]
+    pub column: usize,
+    #[schemars(
+        description = "The deprecation notice, or for missing names the engine's suggestions"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Output of `check_compatibility`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct CompatibilityOutput {
+    #[schemars(description = "The mq-lang version of this server's engine")]
+    pub engine_version: String,
+    #[schemars(description = "Whether every function and selector the query uses exists")]
+    pub compatible: bool,
+    pub missing: usize,
+    pub deprecated: usize,
+    #[schemars(
+        description = "The builtin functions and selectors the query uses, each once, by kind and name; functions the query defines itself are left out"
+    )]
+    pub references: Vec<Reference>,
+}
+
+/// Collects the references of a query, keeping the first use of each name.
+#[derive(Default)]
+struct References(BTreeMap<(ReferenceKind, String), Reference>);
+
+impl References {
+    fn add(
+        &mut self,
+        kind: ReferenceKind,
+        name: &str,
+        status: Availability,
+        range: Option<engine::Range>,
+        note: Option<String>,
+    ) {
+        let range = range.unwrap_or_default();
+        self.0
+            .entry((kind, name.to_string()))
+            .and_modify(|reference| reference.uses += 1)
+            .or_insert_with(|| Reference {
+                kind,
+                name: name.to_string(),
+                status,
+                uses: 1,
+                line: range.start.line,
+                column: range.start.column,
+                note,
+            });
+    }
+}
+
+/// The raw name in mq-lang's "Unknown selector `.x`. ..." message; empty
+/// if the message doesn't name it.
+fn unknown_selector(message: &str) -> Option<String> {
+    let rest = message.strip_prefix("Unknown selector")?;
+    Some(
+        rest.strip_prefix(" `")
+            .and_then(|rest| rest.split_once('`'))
+            .map(|(name, _)| name.to_string())
+            .unwrap_or_default(),
+    )
+}
+
+pub(crate) fn check(query: &str) -> Result<CompatibilityOutput, ErrorData> {
+    let mut references = References::default();
+
+    let mut syntax_errors = Vec::new();
+    for (message, range) in Engine::parse_errors(query) {
+        match unknown_selector(&message) {
+            Some(name) => references.add(
+                ReferenceKind::Selector,
+                &name,
+                Availability::Missing,
+                Some(range),
+                Some(message),
+            ),
+            None => syntax_errors.push(message),
+        }
+    }
+    if !syntax_errors.is_empty() {
+        return Err(ErrorData::invalid_params(
+            "Invalid mq query",
+            Some(serde_json::json!({ "errors": syntax_errors })),
+        ));
+    }
+
+    let mut hir = mq_hir::Hir::default();
+    let (source_id, _) = hir.add_code(None, query);
+
+    for error in hir.errors() {
+        if let HirError::UnresolvedSymbol { symbol, .. } = &error
+            && symbol.kind == SymbolKind::Call
+            && symbol.source.source_id == Some(source_id)
+        {
+            references.add(
+                ReferenceKind::Function,
+                symbol.value.as_deref().unwrap_or_default(),
+                Availability::Missing,
+                symbol.source.text_range,
+                Some(error.to_string()),
+            );
+        }
+    }
+
+    for (symbol_id, symbol) in lint::source_symbols(&hir, source_id) {
+        let name = symbol.value.as_deref().unwrap_or_default();
+        match &symbol.kind {
+            SymbolKind::Selector(_) => references.add(
+                ReferenceKind::Selector,
+                name,
+                Availability::Available,
+                symbol.source.text_range,
+                None,
+            ),
+            SymbolKind::Call => {
+                let Some(definition) = hir
+                    .resolve_reference_symbol(symbol_id)
+                    .and_then(|id| hir.symbol(id))
+                    .filter(|definition| definition.source.source_id != Some(source_id))
+                else {
+                    continue;
+                };
+                let (status, notice) = if definition.is_deprecated() {
+                    let notice = definition
+                        .doc
+                        .iter()
+                        .map(|(_, text)| text.trim().to_string())
+                        .find(|text| text.to_lowercase().contains("deprecated"));
+                    (Availability::Deprecated, notice)
+                } else {
+                    (Availability::Available, None)
+                };
+                references.add(
+                    ReferenceKind::Function,
+                    name,
+                    status,
+                    symbol.source.text_range,
+                    notice,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    let references: Vec<Reference> = references.0.into_values().collect();
+    let count = |status| references.iter().filter(|r| r.status == status).count();
+    Ok(CompatibilityOutput {
+        engine_version: Engine::VERSION.to_string(),
+        compatible: count(Availability::Missing) == 0,
+        missing: count(Availability::Missing),
+        deprecated: count(Availability::Deprecated),
+        references,
+    })
+}
+
+#[tool_router(router = compatibility_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Check whether an mq query, given inline or by the name of a saved query, only uses functions and selectors that exist in this server's engine version, and flag the ones it deprecates. Use it before sharing saved queries between servers running different versions.",
+        output_schema = output_schema::<CompatibilityOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn check_compatibility(
+        &self,
+        Parameters(input): Parameters<CheckCompatibilityInput>,
+    ) -> McpResult {
+        let query = match (input.query, input.name) {
+            (Some(query), None) => query,
+            (None, Some(name)) => self.saved_query(&name)?.query,
+            _ => {
+                return Err(ErrorData::invalid_params(
+                    "Give exactly one of `query` or `name`",
+                    None,
+                ));
+            }
+        };
+        json_result(&check(&query)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn statuses(query: &str) -> Vec<(String, Availability)> {
+        check(query)
+            .unwrap()
+            .references
+            .into_iter()
+            .map(|reference| (reference.name, reference.status))
+            .collect()
+    }
+
+    #[rstest]
+    #[case(".h | upcase()", vec![("upcase", Availability::Available), (".h", Availability::Available)])]
+    #[case(".h | upcse()", vec![("upcse", Availability::Missing), (".h", Availability::Available)])]
+    #[case("def f(): upcase(); | f()", vec![("upcase", Availability::Available)])]
+    fn test_check(#[case] query: &str, #[case] expected: Vec<(&str, Availability)>) {
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(name, status)| (name.to_string(), status))
+            .collect();
+        assert_eq!(statuses(query), expected);
+    }
+
+    #[test]
+    fn test_check_unknown_selector() {
+        let output = check(".hedaing | upcase()").unwrap();
+        assert!(!output.compatible);
+        assert_eq!(output.missing, 1);
+        assert_eq!(output.references[1].kind, ReferenceKind::Selector);
+        assert_eq!(output.references[1].name, ".hedaing");
+    }
+
+    #[test]
+    fn test_check_counts_uses() {
+        let output = check(".h | upcase() | upcase()").unwrap();
+        assert!(output.compatible);
+        assert_eq!(output.engine_version, Engine::VERSION);
+        assert_eq!(output.references[0].uses, 2);
+    }
+
+    #[test]
+    fn test_check_rejects_syntax_errors() {
+        assert!(check(".h | upcase(").is_err());
+    }
+
+    #[test]
+    fn test_check_saved_query() {
+        let server = Server::new(None).unwrap();
+        server
+            .storage
+            .save_query("titles", ".h1 | upcse()", None)
+            .unwrap();
+        let result = server
+            .check_compatibility(Parameters(CheckCompatibilityInput {
+                query: None,
+                name: Some("titles".to_string()),
+            }))
+            .unwrap();
+        assert_eq!(result.structured_content.unwrap()["compatible"], false);
+    }
+}
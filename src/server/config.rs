@@ -0,0 +1,110 @@
+use std::{path::PathBuf, time::Duration};
+
+#[cfg(feature = "fetch")]
+use super::{
+    ConfluenceConfig, FetchConfig, GdocConfig, GithubConfig, NotionConfig, SlackConfig,
+    WebhookConfig,
+};
+use super::{DailyNotesConfig, Locale, PostProcess, Quotas, RedactionRule};
+
+/// Runtime configuration shared by every tool call on a [`super::Server`].
+///
+/// Populated from CLI flags in `main.rs`; `Default` gives the behaviour of a
+/// bare `mq-mcp` invocation (no database, everything returned inline).
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    /// Path to an mq-db (.mq-db) store file backing the `db_*` tools.
+    /// `None` disables them.
+    #[cfg(feature = "db")]
+    pub db_path: Option<PathBuf>,
+    /// Path of the full-text index file behind `index_directory` and
+    /// `search_index`. `None` disables them.
+    #[cfg(feature = "index")]
+    pub index_path: Option<PathBuf>,
+    /// Watch the indexed directories and re-index changed files on the
+    /// next search, instead of only on `index_directory`.
+    #[cfg(feature = "index")]
+    pub index_watch: bool,
+    /// Path of the SQLite database holding saved queries, scheduled
+    /// pipelines and the audit log of tool calls. `None` keeps them in
+    /// memory for the life of the process.
+    pub state_path: Option<PathBuf>,
+    /// Text results larger than this many bytes are stored server-side and
+    /// returned as a resource link (`mq://results/<id>`) that the client can
+    /// read lazily via `resources/read`, instead of being inlined (or, with
+    /// an S3 bucket configured, as a link to the uploaded object). `None`
+    /// always inlines.
+    pub resource_threshold: Option<usize>,
+    /// Language of tool descriptions, server instructions and error
+    /// messages.
+    pub locale: Locale,
+    /// Limits on the bytes each session may process (tool arguments plus
+    /// returned text) per rolling hour/day.
+    pub quotas: Quotas,
+    /// Patterns redacted from every tool result before it is returned.
+    pub redactions: Vec<RedactionRule>,
+    /// Default post-processing of the markdown returned by query tools;
+    /// calls can override each step with the `postprocess` argument.
+    pub postprocess: PostProcess,
+    /// Whether query results end with a footer citing their source
+    /// document, heading path and line range; calls can override it with
+    /// the `provenance` argument.
+    pub provenance: bool,
+    /// How long the result of a mutating call made with an
+    /// `idempotency_key` is replayed when the key is reused. `None` uses
+    /// [`super::DEFAULT_IDEMPOTENCY_TTL`].
+    pub idempotency_ttl: Option<Duration>,
+    /// Leave deprecated tools (see [`super::deprecation`]) out of
+    /// `tools/list` and refuse calls to them.
+    pub hide_deprecated_tools: bool,
+    /// Record the anonymized shapes of successful queries in the state
+    /// database, for the cookbook (see [`super::cookbook`]).
+    pub cookbook: bool,
+    /// Path of the YAML file of settings reloaded while the server runs
+    /// (see [`super::reload`]).
+    pub config_path: Option<PathBuf>,
+    /// Directory of `.mq` user modules whose functions every query can
+    /// call, reloaded while the server runs.
+    pub modules_dir: Option<PathBuf>,
+    /// Directory of notes the tools creating notes (`daily_note`,
+    /// `create_note`, ...) write to. `None` disables them.
+    pub vault: Option<PathBuf>,
+    /// The folder, file name format and template of `daily_note` and
+    /// `append_to_daily_note`.
+    pub daily_notes: DailyNotesConfig,
+    /// Directory of the templates behind `list_templates`,
+    /// `render_template` and `create_note`. `None` disables them.
+    pub templates_dir: Option<PathBuf>,
+    /// Settings for the URL-based tools (`query_url`, ...).
+    #[cfg(feature = "fetch")]
+    pub fetch: FetchConfig,
+    /// Credentials and endpoint for `query_github`.
+    #[cfg(feature = "fetch")]
+    pub github: GithubConfig,
+    /// Site and credentials for `query_confluence`.
+    #[cfg(feature = "fetch")]
+    pub confluence: ConfluenceConfig,
+    /// Integration token for `query_notion`.
+    #[cfg(feature = "fetch")]
+    pub notion: NotionConfig,
+    /// OAuth token for `query_gdoc`.
+    #[cfg(feature = "fetch")]
+    pub gdoc: GdocConfig,
+    /// Token for fetching threads in `slack_to_markdown`.
+    #[cfg(feature = "fetch")]
+    pub slack: SlackConfig,
+    /// Allowed hosts and signing secret for scheduled pipeline webhooks.
+    #[cfg(feature = "fetch")]
+    pub webhook: WebhookConfig,
+    /// The pandoc executable behind `pandoc_to_markdown` and
+    /// `markdown_to_pandoc`.
+    #[cfg(feature = "pandoc")]
+    pub pandoc: super::PandocConfig,
+    /// The endpoint behind `embed_sections` and `semantic_search`.
+    #[cfg(feature = "embeddings")]
+    pub embeddings: super::EmbeddingsConfig,
+    /// The bucket large results are offloaded to instead of the session's
+    /// result store.
+    #[cfg(feature = "s3")]
+    pub s3: super::S3Config,
+}
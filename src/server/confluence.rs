@@ -0,0 +1,282 @@
+//! `query_confluence`: Confluence pages fetched through the REST API, with
+//! their storage-format XHTML converted to markdown.
+
+use std::{fmt, sync::LazyLock};
+
+use base64::Engine;
+use regex::{Captures, Regex};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use rmcp::{ErrorData, handler::server::wrapper::Parameters, schemars, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    fetch::parse_url,
+    output::{QueryOutput, output_schema},
+    sanitize::SanitizeOptions,
+};
+
+static CODE_MACRO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<ac:structured-macro\b[^>]*ac:name="code"[^>]*>(.*?)</ac:structured-macro>"#)
+        .unwrap()
+});
+static PANEL_MACRO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?s)<ac:structured-macro\b[^>]*ac:name="(info|note|warning|tip)"[^>]*>.*?<ac:rich-text-body>(.*?)</ac:rich-text-body>\s*</ac:structured-macro>"#,
+    )
+    .unwrap()
+});
+static LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<ac:link\b[^>]*>(.*?)</ac:link>").unwrap());
+static LANGUAGE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<ac:parameter\b[^>]*ac:name="language"[^>]*>([^<]*)</ac:parameter>"#).unwrap()
+});
+static PARAMETERS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<ac:parameter\b[^>]*>.*?</ac:parameter>").unwrap());
+static CDATA: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!\[CDATA\[(.*?)\]\]>").unwrap());
+static PAGE_TITLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"ri:content-title="([^"]*)""#).unwrap());
+static CONFLUENCE_TAGS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"</?(?:ac|ri):[^>]*>").unwrap());
+
+/// Server-side settings for `query_confluence`.
+#[derive(Clone, Default)]
+pub struct ConfluenceConfig {
+    /// Base URL of the site, e.g. `https://example.atlassian.net/wiki`.
+    /// `None` disables the tool.
+    pub base_url: Option<String>,
+    /// Account email for Confluence Cloud, which authenticates with the
+    /// email and an API token (basic auth). Without it, `token` is sent as
+    /// a bearer personal access token (Data Center / Server).
+    pub user: Option<String>,
+    pub token: Option<String>,
+}
+
+impl fmt::Debug for ConfluenceConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfluenceConfig")
+            .field("base_url", &self.base_url)
+            .field("user", &self.user)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl ConfluenceConfig {
+    fn headers(&self) -> Result<HeaderMap, ErrorData> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+        let Some(token) = &self.token else {
+            return Ok(headers);
+        };
+        let authorization = match &self.user {
+            Some(user) => format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(format!("{user}:{token}"))
+            ),
+            None => format!("Bearer {token}"),
+        };
+        let mut value = HeaderValue::from_str(&authorization).map_err(|_| {
+            ErrorData::internal_error("Invalid Confluence token in the server configuration", None)
+        })?;
+        value.set_sensitive(true);
+        headers.insert(header::AUTHORIZATION, value);
+        Ok(headers)
+    }
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct QueryConfluenceInput {
+    #[schemars(description = "The page id (the number in the page URL)")]
+    pub page_id: Option<String>,
+    #[schemars(description = "Space key, used with `title` when `page_id` is not given")]
+    pub space: Option<String>,
+    #[schemars(description = "Exact page title, used with `space`")]
+    pub title: Option<String>,
+    #[schemars(
+        description = "The mq query to execute (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+    #[serde(flatten)]
+    pub sanitize: SanitizeOptions,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Rewrites Confluence storage-format XHTML as plain HTML: code macros
+/// become `<pre><code>`, info/note/warning/tip panels become block quotes,
+/// page links become their text, and other macro markup is unwrapped.
+fn storage_to_html(storage: &str) -> String {
+    let html = CODE_MACRO.replace_all(storage, |caps: &Captures| {
+        let language = LANGUAGE
+            .captures(&caps[1])
+            .map(|language| format!(r#" class="language-{}""#, language[1].trim()))
+            .unwrap_or_default();
+        let code = CDATA
+            .captures(&caps[1])
+            .map(|code| escape_html(&code[1]))
+            .unwrap_or_default();
+        format!("<pre><code{language}>{code}</code></pre>")
+    });
+    let html = PANEL_MACRO.replace_all(&html, |caps: &Captures| {
+        let kind = &caps[1];
+        let label = kind[..1].to_ascii_uppercase() + &kind[1..];
+        format!(
+            "<blockquote><p><strong>{label}:</strong></p>{}</blockquote>",
+            &caps[2]
+        )
+    });
+    let html = LINK.replace_all(&html, |caps: &Captures| {
+        let body = PARAMETERS.replace_all(&caps[1], "");
+        let body = CDATA.replace_all(&body, "$1");
+        let body = CONFLUENCE_TAGS.replace_all(&body, "");
+        if body.trim().is_empty() {
+            PAGE_TITLE
+                .captures(&caps[1])
+                .map(|title| title[1].to_string())
+                .unwrap_or_default()
+        } else {
+            body.into_owned()
+        }
+    });
+    let html = PARAMETERS.replace_all(&html, "");
+    let html = CDATA.replace_all(&html, |caps: &Captures| escape_html(&caps[1]));
+    CONFLUENCE_TAGS.replace_all(&html, "").into_owned()
+}
+
+impl Server {
+    /// Fetches the storage-format body of the page selected by `input`.
+    async fn confluence_storage(
+        &self,
+        base_url: &str,
+        input: &QueryConfluenceInput,
+    ) -> Result<String, ErrorData> {
+        let base_url = base_url.trim_end_matches('/');
+        let mut url = match (&input.page_id, &input.space, &input.title) {
+            (Some(id), _, _) => {
+                let id = id.trim();
+                if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(ErrorData::invalid_params(
+                        "Invalid Confluence page id",
+                        Some(serde_json::json!({ "page_id": id })),
+                    ));
+                }
+                parse_url(&format!("{base_url}/rest/api/content/{id}"))?
+            }
+            (None, Some(space), Some(title)) => {
+                let mut url = parse_url(&format!("{base_url}/rest/api/content"))?;
+                url.query_pairs_mut()
+                    .append_pair("spaceKey", space)
+                    .append_pair("title", title);
+                url
+            }
+            _ => {
+                return Err(ErrorData::invalid_params(
+                    "Either `page_id` or `space` and `title` is required",
+                    None,
+                ));
+            }
+        };
+        url.query_pairs_mut().append_pair("expand", "body.storage");
+
+        let headers = self.config.confluence.headers()?;
+        let page = self.fetcher.fetch_api(url, headers).await?;
+        let response: serde_json::Value = serde_json::from_str(&page.text).map_err(|e| {
+            ErrorData::internal_error(
+                "Invalid Confluence API response",
+                Some(serde_json::json!({ "url": page.url, "error": e.to_string() })),
+            )
+        })?;
+        // A lookup by space and title returns a list of matches.
+        let content = match response.get("results") {
+            Some(results) => results.get(0).ok_or_else(|| {
+                ErrorData::invalid_params(
+                    "Confluence page not found",
+                    Some(serde_json::json!({ "space": input.space, "title": input.title })),
+                )
+            })?,
+            None => &response,
+        };
+        Ok(content["body"]["storage"]["value"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+}
+
+#[tool_router(router = confluence_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Fetch a Confluence page by id, or by space key and title, through the Confluence REST API, convert it to markdown and run an mq query on it. Requires the server to be started with --confluence-url.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = true)
+    )]
+    async fn query_confluence(
+        &self,
+        Parameters(input): Parameters<QueryConfluenceInput>,
+    ) -> McpResult {
+        let Some(base_url) = self.config.confluence.base_url.as_deref() else {
+            return Err(ErrorData::invalid_request(
+                "no Confluence site configured — restart mq-mcp with --confluence-url <url> to enable query_confluence",
+                None,
+            ));
+        };
+        let storage = self.confluence_storage(base_url, &input).await?;
+        self.eval_html(
+            &storage_to_html(&storage),
+            input.query.as_deref().unwrap_or("identity()"),
+            &input.sanitize,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        r#"<ac:structured-macro ac:name="code" ac:schema-version="1"><ac:parameter ac:name="language">rust</ac:parameter><ac:plain-text-body><![CDATA[let v: Vec<u8> = vec![];]]></ac:plain-text-body></ac:structured-macro>"#,
+        r#"<pre><code class="language-rust">let v: Vec&lt;u8&gt; = vec![];</code></pre>"#
+    )]
+    #[case(
+        r#"<ac:structured-macro ac:name="warning"><ac:parameter ac:name="title">Careful</ac:parameter><ac:rich-text-body><p>Back up first.</p></ac:rich-text-body></ac:structured-macro>"#,
+        "<blockquote><p><strong>Warning:</strong></p><p>Back up first.</p></blockquote>"
+    )]
+    #[case(
+        r#"<p>See <ac:link><ri:page ri:content-title="Setup Guide" /></ac:link>.</p>"#,
+        "<p>See Setup Guide.</p>"
+    )]
+    #[case(
+        r#"<p><ac:link><ri:page ri:content-title="Setup" /><ac:plain-text-link-body><![CDATA[the guide]]></ac:plain-text-link-body></ac:link></p>"#,
+        "<p>the guide</p>"
+    )]
+    #[case(
+        r#"<p>Status: <ac:structured-macro ac:name="status"><ac:parameter ac:name="colour">Green</ac:parameter></ac:structured-macro>done</p>"#,
+        "<p>Status: done</p>"
+    )]
+    fn test_storage_to_html(#[case] storage: &str, #[case] expected: &str) {
+        assert_eq!(storage_to_html(storage), expected);
+    }
+
+    #[test]
+    fn test_cloud_credentials_use_basic_auth() {
+        let config = ConfluenceConfig {
+            base_url: Some("https://example.atlassian.net/wiki".to_string()),
+            user: Some("me@example.com".to_string()),
+            token: Some("secret".to_string()),
+        };
+        let headers = config.headers().unwrap();
+        assert!(
+            headers[header::AUTHORIZATION]
+                .to_str()
+                .unwrap()
+                .starts_with("Basic ")
+        );
+        assert!(!format!("{config:?}").contains("secret"));
+    }
+}
@@ -0,0 +1,342 @@
+//! The cookbook: the query shapes that worked, learned from real usage. With
+//! `--cookbook`, the server records the `query` of every successful tool
+//! call in the state database, anonymized to its shape (string literals
+//! blanked out, comments and layout dropped), and the `cookbook` tool and
+//! the `mq://cookbook` resource list the most common shapes with what they
+//! select and call. Agents starting a query can pick one known to work with
+//! the documents this server sees rather than writing it from scratch.
+//!
+//! Nothing is recorded without the flag, and shapes only keep the query's
+//! structure: selectors, function names, numbers and operators.
+
+use rmcp::{
+    ErrorData,
+    handler::server::wrapper::Parameters,
+    model::{CallToolResult, JsonObject, Resource},
+    schemars,
+    serde::Serialize,
+    tool, tool_router,
+};
+
+use super::{
+    McpResult, Server, compatibility,
+    compatibility::ReferenceKind,
+    output::{json_result, output_schema},
+    storage::QueryShape,
+};
+
+/// URI of the cookbook resource.
+pub(crate) const COOKBOOK_URI: &str = "mq://cookbook";
+
+/// Tools whose `query` isn't a query run on a document: SQL, queries only
+/// analyzed or stored, and the tutorial's examples.
+const EXCLUDED_TOOLS: &[&str] = &[
+    "db_sql",
+    "lint_query",
+    "check_compatibility",
+    "save_query",
+    "run_example",
+];
+
+/// Longer queries are one-offs rather than patterns, and aren't recorded.
+const MAX_SHAPE_CHARS: usize = 500;
+
+const DEFAULT_LIMIT: usize = 20;
+
+/// Entries in the cookbook resource.
+const RESOURCE_LIMIT: usize = 50;
+
+/// The shape of `query`: its string literals emptied to `"…"`, its comments
+/// removed and its whitespace collapsed. `None` for empty or overlong
+/// queries.
+pub(crate) fn shape(query: &str) -> Option<String> {
+    let mut shape = String::with_capacity(query.len());
+    let mut chars = query.chars();
+    let mut space = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+                push_token(&mut shape, &mut space, "\"…\"");
+            }
+            '#' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                space = true;
+            }
+            c if c.is_whitespace() => space = true,
+            c => push_token(&mut shape, &mut space, c.encode_utf8(&mut [0; 4])),
+        }
+    }
+    (!shape.is_empty() && shape.chars().count() <= MAX_SHAPE_CHARS).then_some(shape)
+}
+
+/// Appends `text` to `shape`, after a single space if whitespace preceded it.
+fn push_token(shape: &mut String, space: &mut bool, text: &str) {
+    if std::mem::take(space) && !shape.is_empty() {
+        shape.push(' ');
+    }
+    shape.push_str(text);
+}
+
+/// The shape of the query a call to `tool` gives, if the cookbook records
+/// it.
+pub(crate) fn call_shape(tool: &str, arguments: Option<&JsonObject>) -> Option<String> {
+    if EXCLUDED_TOOLS.contains(&tool) {
+        return None;
+    }
+    arguments?.get("query")?.as_str().and_then(shape)
+}
+
+/// A sentence describing what `shape` uses, from its selectors and calls.
+fn describe(shape: &str) -> String {
+    let Ok(output) = compatibility::check(shape) else {
+        return "Not a valid query on this engine.".to_string();
+    };
+    let names = |kind: ReferenceKind| {
+        output
+            .references
+            .iter()
+            .filter(|reference| reference.kind == kind)
+            .map(|reference| format!("`{}`", reference.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let selectors = names(ReferenceKind::Selector);
+    let functions = names(ReferenceKind::Function);
+    match (selectors.is_empty(), functions.is_empty()) {
+        (true, true) => "Uses no selector or built-in function.".to_string(),
+        (false, true) => format!("Selects {selectors}."),
+        (true, false) => format!("Calls {functions}."),
+        (false, false) => format!("Selects {selectors}; calls {functions}."),
+    }
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct CookbookInput {
+    #[schemars(description = "Only list the query shapes used with this tool, e.g. `query_url`")]
+    pub tool: Option<String>,
+    #[schemars(description = "Maximum number of entries (default: 20)")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct CookbookEntry {
+    #[schemars(description = "The query's shape: string literals are blanked out as `\"…\"`")]
+    pub query: String,
+    #[schemars(description = "The selectors and functions the query uses")]
+    pub description: String,
+    #[schemars(description = "Successful calls that used it")]
+    pub uses: u64,
+    #[schemars(description = "The tools it was used with")]
+    pub tools: Vec<String>,
+    #[schemars(description = "When it was last used (RFC 3339)")]
+    pub last_used: String,
+}
+
+impl From<QueryShape> for CookbookEntry {
+    fn from(shape: QueryShape) -> Self {
+        Self {
+            description: describe(&shape.shape),
+            query: shape.shape,
+            uses: shape.uses,
+            tools: shape.tools,
+            last_used: shape.last_used,
+        }
+    }
+}
+
+/// Output of `cookbook`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct CookbookOutput {
+    #[schemars(description = "The most used query shapes first")]
+    pub entries: Vec<CookbookEntry>,
+}
+
+impl Server {
+    fn cookbook_entries(
+        &self,
+        tool: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<CookbookEntry>, ErrorData> {
+        if !self.config.cookbook {
+            return Err(ErrorData::invalid_request(
+                "The cookbook is disabled; start the server with --cookbook",
+                None,
+            ));
+        }
+        Ok(self
+            .storage
+            .query_shapes(tool, limit)?
+            .into_iter()
+            .map(CookbookEntry::from)
+            .collect())
+    }
+
+    /// Counts `shape` (see [`call_shape`]) for `tool` if the call succeeded
+    /// and the cookbook is enabled. Failures to record are logged.
+    pub(super) fn record_shape(
+        &self,
+        tool: &str,
+        shape: Option<String>,
+        result: Option<&CallToolResult>,
+    ) {
+        let Some(shape) = shape.filter(|_| self.config.cookbook) else {
+            return;
+        };
+        if result.is_none_or(|result| result.is_error == Some(true)) {
+            return;
+        }
+        if let Err(e) = self.storage.record_shape(tool, &shape) {
+            tracing::warn!("failed to record a query shape of {tool}: {:?}", e.data);
+        }
+    }
+
+    /// The cookbook resource, if the cookbook is enabled.
+    pub(super) fn cookbook_resources(&self) -> Vec<Resource> {
+        if !self.config.cookbook {
+            return Vec::new();
+        }
+        vec![
+            Resource::new(COOKBOOK_URI, "mq cookbook")
+                .with_description("The query shapes most used successfully on this server")
+                .with_mime_type("text/markdown"),
+        ]
+    }
+
+    /// The text of the cookbook resource.
+    pub(super) fn read_cookbook(&self) -> Result<String, ErrorData> {
+        let entries = self.cookbook_entries(None, RESOURCE_LIMIT)?;
+        let mut text = String::from(
+            "# mq cookbook\n\nThe query shapes most used successfully on this server, with their string literals blanked out as `\"…\"`.\n",
+        );
+        if entries.is_empty() {
+            text.push_str("\nNo query has been recorded yet.\n");
+        }
+        for (i, entry) in entries.iter().enumerate() {
+            text.push_str(&format!(
+                "\n## {}. Used {} time{} with {}\n\n{}\n\n```mq\n{}\n```\n",
+                i + 1,
+                entry.uses,
+                if entry.uses == 1 { "" } else { "s" },
+                entry.tools.join(", "),
+                entry.description,
+                entry.query
+            ));
+        }
+        Ok(text)
+    }
+}
+
+#[tool_router(router = cookbook_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "List the query shapes most used successfully on this server (recorded with --cookbook, string literals blanked out), each with the selectors and functions it uses, how often and with which tools. Start a query from one of them; filter by tool to see what works for a given source.",
+        output_schema = output_schema::<CookbookOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn cookbook(&self, Parameters(input): Parameters<CookbookInput>) -> McpResult {
+        let entries =
+            self.cookbook_entries(input.tool.as_deref(), input.limit.unwrap_or(DEFAULT_LIMIT))?;
+        json_result(&CookbookOutput { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ServerConfig;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(".h2", Some(".h2"))]
+    #[case("  .h  |\n  to_text()  ", Some(".h | to_text()"))]
+    #[case(
+        ".h | select(contains(\"secret \\\" plan\"))",
+        Some(".h | select(contains(\"…\"))")
+    )]
+    #[case(".code # only code\n| to_text()", Some(".code | to_text()"))]
+    #[case("  # nothing\n", None)]
+    fn test_shape(#[case] query: &str, #[case] expected: Option<&str>) {
+        assert_eq!(shape(query).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_call_shape() {
+        let arguments = serde_json::json!({ "query": ".h1" }).as_object().cloned();
+        assert_eq!(
+            call_shape("extract_markdown_v2", arguments.as_ref()).as_deref(),
+            Some(".h1")
+        );
+        assert_eq!(call_shape("lint_query", arguments.as_ref()), None);
+        assert_eq!(call_shape("extract_markdown_v2", None), None);
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe(".h2 | to_text() | upcase()"),
+            "Selects `.h2`; calls `to_text`, `upcase`."
+        );
+    }
+
+    #[test]
+    fn test_cookbook() {
+        let server = Server::with_config(ServerConfig {
+            cookbook: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let ok = CallToolResult::success(Vec::new());
+        for query in [
+            ".h2 | select(contains(\"a\"))",
+            ".h2 | select(contains(\"b\"))",
+        ] {
+            server.record_shape("extract_markdown_v2", shape(query), Some(&ok));
+        }
+        server.record_shape("extract_markdown_v2", shape(".code"), None);
+
+        let output = server
+            .cookbook(Parameters(CookbookInput {
+                tool: None,
+                limit: None,
+            }))
+            .unwrap()
+            .structured_content
+            .unwrap();
+        assert_eq!(output["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            output["entries"][0]["query"],
+            ".h2 | select(contains(\"…\"))"
+        );
+        assert_eq!(output["entries"][0]["uses"], 2);
+        assert!(server.read_cookbook().unwrap().contains("Used 2 times"));
+    }
+
+    #[test]
+    fn test_cookbook_is_opt_in() {
+        let server = Server::new(None).unwrap();
+        let ok = CallToolResult::success(Vec::new());
+        server.record_shape("extract_markdown_v2", shape(".h2"), Some(&ok));
+        assert!(server.storage.query_shapes(None, 10).unwrap().is_empty());
+        assert!(server.cookbook_resources().is_empty());
+        assert!(
+            server
+                .cookbook(Parameters(CookbookInput {
+                    tool: None,
+                    limit: None,
+                }))
+                .is_err()
+        );
+    }
+}
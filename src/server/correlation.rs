@@ -0,0 +1,198 @@
+//! Request ids for tool calls. Every call gets a generated id, and may carry
+//! the client's own in the reserved `correlation_id` argument, which
+//! [`Server::call_tool`](rmcp::ServerHandler::call_tool) strips before the
+//! arguments reach the tool.
+//!
+//! The call runs in a `tool_call` tracing span recording both ids, so the
+//! server's logs for one call can be picked out of concurrent traffic, and
+//! both are returned with every error: in the `data` of a protocol error and
+//! in the `_meta` of a result with `isError`.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use chrono::Utc;
+use rmcp::{
+    ErrorData,
+    model::{CallToolResult, JsonObject, Meta, Tool},
+};
+
+/// Name of the per-call argument accepted by every tool.
+pub(crate) const ARGUMENT: &str = "correlation_id";
+
+/// Key of the generated id in error data and `_meta`.
+pub(crate) const REQUEST_ID_KEY: &str = "request_id";
+
+/// Longest accepted [`ARGUMENT`], in bytes.
+const MAX_CORRELATION_ID_LEN: usize = 128;
+
+/// Calls numbered so far, making ids generated in the same millisecond
+/// distinct.
+static CALLS: AtomicU64 = AtomicU64::new(0);
+
+/// Removes the [`ARGUMENT`] from a call's arguments and returns it.
+pub(crate) fn take_argument(
+    arguments: &mut Option<JsonObject>,
+) -> Result<Option<String>, ErrorData> {
+    match arguments
+        .as_mut()
+        .and_then(|arguments| arguments.remove(ARGUMENT))
+    {
+        None => Ok(None),
+        Some(serde_json::Value::String(id))
+            if !id.is_empty() && id.len() <= MAX_CORRELATION_ID_LEN =>
+        {
+            Ok(Some(id))
+        }
+        Some(value) => Err(ErrorData::invalid_params(
+            "Invalid `correlation_id` argument",
+            Some(serde_json::json!({
+                "expected": format!("non-empty string of at most {MAX_CORRELATION_ID_LEN} bytes"),
+                "got": value,
+            })),
+        )),
+    }
+}
+
+/// Advertises the [`ARGUMENT`] in the input schema of every tool.
+pub(crate) fn describe_argument(mut tool: Tool) -> Tool {
+    let mut schema = (*tool.input_schema).clone();
+    if let Some(serde_json::Value::Object(properties)) = schema.get_mut("properties") {
+        properties.insert(
+            ARGUMENT.to_string(),
+            serde_json::json!({
+                "type": "string",
+                "maxLength": MAX_CORRELATION_ID_LEN,
+                "description": "An id of the caller's for this call, e.g. of the agent task it belongs to. It is logged with the call and returned with any error, next to the server's request_id",
+            }),
+        );
+    }
+    tool.input_schema = Arc::new(schema);
+    tool
+}
+
+/// The ids of one tool call.
+#[derive(Debug, Clone)]
+pub(crate) struct CallIds {
+    request_id: String,
+    correlation_id: Option<String>,
+}
+
+impl CallIds {
+    /// Generates a request id for a call with the client's
+    /// `correlation_id`, if any.
+    pub(crate) fn new(correlation_id: Option<String>) -> Self {
+        let call = CALLS.fetch_add(1, Ordering::Relaxed);
+        Self {
+            request_id: format!("{:x}-{:04x}", Utc::now().timestamp_millis(), call & 0xffff),
+            correlation_id,
+        }
+    }
+
+    /// The span a call of `tool` runs in.
+    pub(crate) fn span(&self, tool: &str) -> tracing::Span {
+        tracing::info_span!(
+            "tool_call",
+            tool,
+            request_id = %self.request_id,
+            correlation_id = self.correlation_id.as_deref(),
+        )
+    }
+
+    fn ids(&self) -> impl Iterator<Item = (&'static str, serde_json::Value)> {
+        [
+            (REQUEST_ID_KEY, Some(&self.request_id)),
+            (ARGUMENT, self.correlation_id.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(key, id)| Some((key, serde_json::Value::String(id?.clone()))))
+    }
+
+    /// `error` with the ids added to its `data`. Data that isn't an object
+    /// moves under `detail`.
+    pub(crate) fn tag_error(&self, mut error: ErrorData) -> ErrorData {
+        let mut data = match error.data.take() {
+            Some(serde_json::Value::Object(data)) => data,
+            None => JsonObject::new(),
+            Some(detail) => JsonObject::from_iter([("detail".to_string(), detail)]),
+        };
+        data.extend(self.ids().map(|(key, id)| (key.to_string(), id)));
+        error.data = Some(serde_json::Value::Object(data));
+        error
+    }
+
+    /// `result` with the ids added to its `_meta` if it reports an error.
+    pub(crate) fn tag_result(&self, mut result: CallToolResult) -> CallToolResult {
+        if result.is_error == Some(true) {
+            result
+                .meta
+                .get_or_insert_with(Meta::new)
+                .0
+                .extend(self.ids().map(|(key, id)| (key.to_string(), id)));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::ContentBlock;
+
+    #[test]
+    fn test_take_argument() {
+        let mut arguments = serde_json::json!({ "correlation_id": "task-7", "markdown": "# A" })
+            .as_object()
+            .cloned();
+        assert_eq!(
+            take_argument(&mut arguments).unwrap().as_deref(),
+            Some("task-7")
+        );
+        assert!(!arguments.unwrap().contains_key(ARGUMENT));
+
+        let mut arguments = serde_json::json!({ "correlation_id": "" })
+            .as_object()
+            .cloned();
+        assert!(take_argument(&mut arguments).is_err());
+    }
+
+    #[test]
+    fn test_request_ids_are_distinct() {
+        assert_ne!(CallIds::new(None).request_id, CallIds::new(None).request_id);
+    }
+
+    #[test]
+    fn test_tag_error() {
+        let ids = CallIds::new(Some("task-7".to_string()));
+        let error = ids.tag_error(ErrorData::invalid_params(
+            "Invalid query",
+            Some(serde_json::json!({ "query": ".h1 |" })),
+        ));
+        let data = error.data.unwrap();
+        assert_eq!(data["query"], ".h1 |");
+        assert_eq!(data[REQUEST_ID_KEY], ids.request_id.as_str());
+        assert_eq!(data[ARGUMENT], "task-7");
+
+        let error = CallIds::new(None).tag_error(ErrorData::internal_error(
+            "failed",
+            Some(serde_json::json!("disk full")),
+        ));
+        let data = error.data.unwrap();
+        assert_eq!(data["detail"], "disk full");
+        assert!(data.get(ARGUMENT).is_none());
+    }
+
+    #[test]
+    fn test_tag_result_only_tags_errors() {
+        let ids = CallIds::new(None);
+        let result = ids.tag_result(CallToolResult::success(vec![ContentBlock::text("ok")]));
+        assert!(result.meta.is_none());
+        let result = ids.tag_result(CallToolResult::error(vec![ContentBlock::text("failed")]));
+        assert_eq!(
+            result.meta.unwrap().0[REQUEST_ID_KEY],
+            ids.request_id.as_str()
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Five-field cron expressions (`minute hour day-of-month month
+//! day-of-week`), evaluated in UTC, for scheduled pipelines.
+//!
+//! Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`) and steps
+//! (`*/15`, `0-30/10`, `5/20`). Day-of-week is 0-7 with both 0 and 7 for
+//! Sunday. As in Vixie cron, when both day fields are restricted a day
+//! matches either. The `@hourly`, `@daily`, `@weekly`, `@monthly` and
+//! `@yearly` shorthands are accepted too.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+
+/// How far ahead [`Cron::next_after`] looks for a matching minute; an
+/// expression such as `0 0 30 2 *` never matches.
+const HORIZON_DAYS: i64 = 5 * 366;
+
+/// A parsed cron expression. Each field is a bitmask of the values it
+/// matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day-of-month and day-of-week fields are `*`.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "expected 5 fields (minute hour day-of-month month day-of-week), got {}",
+                fields.len()
+            ));
+        };
+        let mut weekday_bits = field(weekdays, 0, 7)?;
+        // 7 is Sunday, like 0.
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: field(minutes, 0, 59)?,
+            hours: field(hours, 0, 23)?,
+            days: field(days, 1, 31)?,
+            months: field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+/// Parses one field into a bitmask of the values in `min..=max` it matches.
+fn field(text: &str, min: u32, max: u32) -> Result<u64, String> {
+    let number = |value: &str| {
+        value
+            .parse::<u32>()
+            .map_err(|_| format!("invalid value `{value}` in `{text}`"))
+    };
+    let mut bits = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<usize>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step `{step}` in `{text}`")),
+            },
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // `5/20` runs from 5 to the end of the range.
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("`{part}` is outside {min}-{max}"));
+        }
+        for value in (start..=end).step_by(step) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn matches(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+impl Cron {
+    fn day_matches(&self, at: &DateTime<Utc>) -> bool {
+        let day = matches(self.days, at.day());
+        let weekday = matches(self.weekdays, at.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// The first matching minute strictly after `after`, or `None` if there
+    /// is none within five years.
+    pub(crate) fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let midnight = |date: NaiveDate| date.and_hms_opt(0, 0, 0).map(|at| at.and_utc());
+        let mut at = (after + Duration::minutes(1))
+            .with_second(0)?
+            .with_nanosecond(0)?;
+        let end = at + Duration::days(HORIZON_DAYS);
+        while at < end {
+            if !matches(self.months, at.month()) {
+                let (year, month) = match at.month() {
+                    12 => (at.year() + 1, 1),
+                    month => (at.year(), month + 1),
+                };
+                at = midnight(NaiveDate::from_ymd_opt(year, month, 1)?)?;
+            } else if !self.day_matches(&at) {
+                at = midnight(at.date_naive().succ_opt()?)?;
+            } else if !matches(self.hours, at.hour()) {
+                at = at.with_minute(0)? + Duration::hours(1);
+            } else if !matches(self.minutes, at.minute()) {
+                at += Duration::minutes(1);
+            } else {
+                return Some(at);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[rstest]
+    #[case("*/15 * * * *", "2026-03-01T10:07:30Z", "2026-03-01T10:15:00Z")]
+    #[case("0 9 * * 1-5", "2026-03-06T09:00:00Z", "2026-03-09T09:00:00Z")]
+    #[case("30 2 1 * *", "2026-12-15T00:00:00Z", "2027-01-01T02:30:00Z")]
+    #[case("0 0 * * 7", "2026-03-02T00:00:00Z", "2026-03-08T00:00:00Z")]
+    #[case("0 12 13 * 5", "2026-03-01T00:00:00Z", "2026-03-06T12:00:00Z")]
+    #[case("@daily", "2026-02-28T23:59:00Z", "2026-03-01T00:00:00Z")]
+    #[case("5/20 * * * *", "2026-03-01T10:26:00Z", "2026-03-01T10:45:00Z")]
+    fn test_next_after(#[case] expression: &str, #[case] after: &str, #[case] expected: &str) {
+        let cron: Cron = expression.parse().unwrap();
+        assert_eq!(cron.next_after(at(after)), Some(at(expected)));
+    }
+
+    #[rstest]
+    #[case("* * * *")]
+    #[case("60 * * * *")]
+    #[case("*/0 * * * *")]
+    #[case("5-1 * * * *")]
+    #[case("a * * * *")]
+    fn test_invalid(#[case] expression: &str) {
+        assert!(expression.parse::<Cron>().is_err());
+    }
+
+    #[test]
+    fn test_never_matches() {
+        let cron: Cron = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(cron.next_after(at("2026-01-01T00:00:00Z")), None);
+    }
+}
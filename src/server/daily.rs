@@ -0,0 +1,547 @@
+//! Daily notes: one note per date in the configured vault, as Obsidian's
+//! Daily notes plugin keeps them. `daily_note` fetches a date's note,
+//! creating it from the template when it's missing, and
+//! `append_to_daily_note` adds an entry to it, under a heading if given.
+//! New notes are filled in from the daily note template, as `create_note`
+//! fills in its templates (see `templates`).
+
+use std::{
+    fmt::Write,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use chrono::{Local, NaiveDate};
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use serde_json::Map;
+
+use super::{
+    McpResult, Server, dry_run,
+    output::{json_result, output_schema},
+    paths, sections, templates, vault,
+};
+
+/// Default for [`DailyNotesConfig::format`].
+pub const DEFAULT_DAILY_FORMAT: &str = "%Y-%m-%d";
+
+/// Server-side settings for the daily note tools, which keep the notes in
+/// [`ServerConfig::vault`](super::ServerConfig::vault).
+#[derive(Debug, Clone)]
+pub struct DailyNotesConfig {
+    /// Folder of the notes, relative to the vault.
+    pub folder: PathBuf,
+    /// strftime format of a note's path in the folder, without the
+    /// extension; `/` in it makes subfolders.
+    pub format: String,
+    /// Template of new notes: the name of a template in
+    /// [`ServerConfig::templates_dir`](super::ServerConfig::templates_dir),
+    /// or else a file relative to the vault unless absolute.
+    pub template: Option<PathBuf>,
+}
+
+impl Default for DailyNotesConfig {
+    fn default() -> Self {
+        Self {
+            folder: PathBuf::new(),
+            format: DEFAULT_DAILY_FORMAT.to_string(),
+            template: None,
+        }
+    }
+}
+
+/// A `YYYY-MM-DD` date from tool input.
+pub(super) fn parse_date(date: &str) -> Result<NaiveDate, ErrorData> {
+    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").map_err(|_| {
+        ErrorData::invalid_params(
+            "Invalid date, expected YYYY-MM-DD",
+            Some(serde_json::json!({ "date": date })),
+        )
+    })
+}
+
+impl DailyNotesConfig {
+    /// The path of the note for `date` in the vault, with `/` separators.
+    fn note_path(&self, date: NaiveDate) -> Result<String, ErrorData> {
+        let invalid = || {
+            ErrorData::internal_error(
+                "Invalid daily note format",
+                Some(serde_json::json!({ "format": self.format })),
+            )
+        };
+        let mut name = String::new();
+        write!(name, "{}", date.format(&self.format)).map_err(|_| invalid())?;
+        let path = self.folder.join(format!("{name}.md"));
+        if name.is_empty()
+            || !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(invalid());
+        }
+        Ok(path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"))
+    }
+}
+
+/// A date's note as found, or created, in the vault.
+struct DailyNote {
+    path: String,
+    markdown: String,
+    created: bool,
+}
+
+impl Server {
+    /// The daily note template, if one is configured.
+    fn daily_template(&self, vault: &Path) -> Result<Option<String>, ErrorData> {
+        let Some(template) = &self.config.daily_notes.template else {
+            return Ok(None);
+        };
+        if self.config.templates_dir.is_some()
+            && let Some(template) = self.templates()?.resolve(&template.to_string_lossy())
+        {
+            return Ok(Some(template.markdown.clone()));
+        }
+        paths::read_markdown(&vault.join(template))
+            .map(Some)
+            .map_err(|e| {
+                ErrorData::internal_error(
+                    "Failed to read the daily note template",
+                    Some(serde_json::json!({ "path": template, "error": e.to_string() })),
+                )
+            })
+    }
+
+    /// The note for `date` in the vault, if it exists.
+    fn read_daily_note(&self, date: NaiveDate) -> Result<Option<DailyNote>, ErrorData> {
+        let path = self.config.daily_notes.note_path(date)?;
+        let markdown = match paths::read_markdown(&self.vault_dir()?.join(&path)) {
+            Ok(markdown) => markdown,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(file_error("Failed to read the daily note", &path, e)),
+        };
+        Ok(Some(DailyNote {
+            path,
+            markdown,
+            created: false,
+        }))
+    }
+
+    /// The note for `date`, read from the vault, or else created from the
+    /// template, with `{{title}}` the file name, and written unless
+    /// `dry_run`. A note created by someone else in the meantime is read
+    /// rather than overwritten.
+    fn open_daily_note(&self, date: NaiveDate, dry_run: bool) -> Result<DailyNote, ErrorData> {
+        if let Some(note) = self.read_daily_note(date)? {
+            return Ok(note);
+        }
+        let vault = self.vault_dir()?;
+        let path = self.config.daily_notes.note_path(date)?;
+        let markdown = match self.daily_template(vault)? {
+            Some(template) => {
+                templates::render(
+                    &template,
+                    &templates::Context {
+                        title: vault::strip_extension(path.rsplit('/').next().unwrap_or(&path)),
+                        date,
+                        now: Local::now(),
+                        fields: &Map::new(),
+                        source: None,
+                    },
+                )?
+                .markdown
+            }
+            None => String::new(),
+        };
+        if !dry_run {
+            match templates::write(&vault.join(&path), &markdown, false) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if let Some(note) = self.read_daily_note(date)? {
+                        return Ok(note);
+                    }
+                }
+                Err(e) => return Err(file_error("Failed to write the daily note", &path, e)),
+            }
+        }
+        Ok(DailyNote {
+            path,
+            markdown,
+            created: true,
+        })
+    }
+}
+
+fn file_error(message: &'static str, path: &str, e: io::Error) -> ErrorData {
+    ErrorData::internal_error(
+        message,
+        Some(serde_json::json!({ "path": path, "error": e.to_string() })),
+    )
+}
+
+fn is_list_item(line: &str) -> bool {
+    let line = line.trim_start();
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    ["- ", "* ", "+ "]
+        .iter()
+        .any(|marker| line.starts_with(marker))
+        || (digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") ")))
+}
+
+/// `markdown` with `entry` added at the end of the section under
+/// `heading` (ignoring case), which is added when missing, or else at the
+/// end of the note; and the 1-based line the entry starts at. List items
+/// follow list items directly; anything else is set off by a blank line.
+/// A note with CRLF line endings keeps them.
+fn append(
+    markdown: &str,
+    entry: &str,
+    heading: Option<&str>,
+) -> Result<(String, usize), ErrorData> {
+    let mut lines: Vec<String> = markdown.lines().map(str::to_string).collect();
+    let body = vault::front_matter(markdown).map_or(0, |(_, end)| markdown[..end].lines().count());
+    let (start, end) = match heading {
+        None => (body, lines.len()),
+        Some(heading) => {
+            let title = heading.trim().trim_start_matches('#').trim();
+            let sections = sections::split(markdown)?;
+            let found = sections.iter().position(|section| {
+                section
+                    .title
+                    .as_deref()
+                    .is_some_and(|t| t.eq_ignore_ascii_case(title))
+            });
+            match found {
+                Some(index) => {
+                    let depth = sections[index].depth;
+                    let end = sections[index + 1..]
+                        .iter()
+                        .find(|section| section.title.is_some() && section.depth <= depth)
+                        .map_or(lines.len(), |section| section.line - 1);
+                    (sections[index].line, end)
+                }
+                None => {
+                    let hashes = heading.trim().chars().take_while(|&c| c == '#').count();
+                    let hashes = "#".repeat(if hashes == 0 { 2 } else { hashes });
+                    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+                        lines.pop();
+                    }
+                    if !lines.is_empty() {
+                        lines.push(String::new());
+                    }
+                    lines.push(format!("{hashes} {title}"));
+                    (lines.len(), lines.len())
+                }
+            }
+        }
+    };
+    let entry: Vec<String> = entry
+        .trim_matches('\n')
+        .lines()
+        .map(str::to_string)
+        .collect();
+    let last = (start..end).rev().find(|&i| !lines[i].trim().is_empty());
+    let at = last.map_or(start, |i| i + 1);
+    let previous = last.map(|i| lines[i].as_str()).or_else(|| {
+        start
+            .checked_sub(1)
+            .and_then(|i| lines.get(i))
+            .map(String::as_str)
+    });
+    let blank_before = previous.is_some_and(|previous| {
+        !(is_list_item(previous) && entry.first().is_some_and(|first| is_list_item(first)))
+    });
+    let blank_after = lines.get(at).is_some_and(|line| !line.trim().is_empty());
+    let line = at + usize::from(blank_before) + 1;
+    let mut inserted = Vec::new();
+    if blank_before {
+        inserted.push(String::new());
+    }
+    inserted.extend(entry);
+    if blank_after {
+        inserted.push(String::new());
+    }
+    lines.splice(at..at, inserted);
+    let newline = if markdown.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    Ok((lines.join(newline) + newline, line))
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct DailyNoteInput {
+    #[schemars(description = "The note's date, as YYYY-MM-DD (default: today)")]
+    pub date: Option<String>,
+    #[schemars(
+        description = "Create the note from the template when it doesn't exist (default: true)"
+    )]
+    pub create: Option<bool>,
+}
+
+/// Output of `daily_note`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct DailyNoteOutput {
+    #[schemars(description = "Path of the note in the vault")]
+    pub path: String,
+    #[schemars(description = "The note's date, YYYY-MM-DD")]
+    pub date: String,
+    #[schemars(description = "The note's markdown; null if it doesn't exist and wasn't created")]
+    pub markdown: Option<String>,
+    #[schemars(description = "Whether the note was created by this call")]
+    pub created: bool,
+    #[schemars(description = "Whether this was a dry run that created no note")]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct AppendToDailyNoteInput {
+    #[schemars(description = "The markdown to add, e.g. `- 14:30 Call with Ada`")]
+    pub entry: String,
+    #[schemars(description = "The note's date, as YYYY-MM-DD (default: today)")]
+    pub date: Option<String>,
+    #[schemars(
+        description = "Add the entry at the end of the section under this heading, e.g. `Log`, adding the heading when missing (default: at the end of the note)"
+    )]
+    pub heading: Option<String>,
+}
+
+/// Output of `append_to_daily_note`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct AppendToDailyNoteOutput {
+    #[schemars(description = "Path of the note in the vault")]
+    pub path: String,
+    #[schemars(description = "The note's date, YYYY-MM-DD")]
+    pub date: String,
+    #[schemars(description = "1-based line the entry starts at")]
+    pub line: usize,
+    #[schemars(description = "Whether the note was created for the entry")]
+    pub created: bool,
+    #[schemars(description = "Whether this was a dry run that left the note unchanged")]
+    pub dry_run: bool,
+}
+
+fn date(date: Option<&str>) -> Result<NaiveDate, ErrorData> {
+    date.map_or_else(|| Ok(Local::now().date_naive()), parse_date)
+}
+
+#[tool_router(router = daily_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Get the daily note of a date (default: today) from the configured vault, creating it from the daily note template when it doesn't exist, as Obsidian's Daily notes plugin does.",
+        output_schema = output_schema::<DailyNoteOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    fn daily_note(&self, Parameters(input): Parameters<DailyNoteInput>) -> McpResult {
+        let date = date(input.date.as_deref())?;
+        let dry_run = dry_run::active();
+        let note = if input.create.unwrap_or(true) {
+            Some(self.open_daily_note(date, dry_run)?)
+        } else {
+            self.read_daily_note(date)?
+        };
+        json_result(&DailyNoteOutput {
+            path: match &note {
+                Some(note) => note.path.clone(),
+                None => self.config.daily_notes.note_path(date)?,
+            },
+            date: date.format("%Y-%m-%d").to_string(),
+            created: note.as_ref().is_some_and(|note| note.created),
+            markdown: note.map(|note| note.markdown),
+            dry_run,
+        })
+    }
+
+    #[tool(
+        description = "Append an entry to the daily note of a date (default: today) in the configured vault, at the end of the note or of the section under a heading, creating the note from the template when it doesn't exist. For journals and logs kept by an agent.",
+        output_schema = output_schema::<AppendToDailyNoteOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    fn append_to_daily_note(
+        &self,
+        Parameters(input): Parameters<AppendToDailyNoteInput>,
+    ) -> McpResult {
+        let date = date(input.date.as_deref())?;
+        let dry_run = dry_run::active();
+        let note = self.open_daily_note(date, dry_run)?;
+        let (markdown, line) = append(&note.markdown, &input.entry, input.heading.as_deref())?;
+        if !dry_run {
+            templates::write(&self.vault_dir()?.join(&note.path), &markdown, true)
+                .map_err(|e| file_error("Failed to write the daily note", &note.path, e))?;
+        }
+        json_result(&AppendToDailyNoteOutput {
+            path: note.path,
+            date: date.format("%Y-%m-%d").to_string(),
+            line,
+            created: note.created,
+            dry_run,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ServerConfig;
+    use rstest::rstest;
+    use std::fs;
+
+    fn server(vault: &Path, templates: Option<&Path>, template: &str) -> Server {
+        Server::with_config(ServerConfig {
+            vault: Some(vault.to_path_buf()),
+            daily_notes: DailyNotesConfig {
+                folder: PathBuf::from("Journal"),
+                format: "%Y/%Y-%m-%d".to_string(),
+                template: Some(PathBuf::from(template)),
+            },
+            templates_dir: templates.map(Path::to_path_buf),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_open() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Templates")).unwrap();
+        fs::write(
+            dir.path().join("Templates/Daily.md"),
+            "# {{title}}\n\nDate: {{date}}\n",
+        )
+        .unwrap();
+        let server = server(dir.path(), None, "Templates/Daily.md");
+        let date = parse_date("2026-10-16").unwrap();
+
+        assert!(server.read_daily_note(date).unwrap().is_none());
+        let note = server.open_daily_note(date, true).unwrap();
+        assert_eq!(note.path, "Journal/2026/2026-10-16.md");
+        assert_eq!(note.markdown, "# 2026-10-16\n\nDate: 2026-10-16\n");
+        assert!(note.created);
+        // A dry run writes nothing.
+        assert!(server.read_daily_note(date).unwrap().is_none());
+
+        server.open_daily_note(date, false).unwrap();
+        let note = server.open_daily_note(date, false).unwrap();
+        assert!(!note.created);
+        assert!(dir.path().join("Journal/2026/2026-10-16.md").exists());
+    }
+
+    #[test]
+    fn test_read_strips_bom() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Journal/2026")).unwrap();
+        fs::write(
+            dir.path().join("Journal/2026/2026-10-16.md"),
+            "\u{feff}# Today\n",
+        )
+        .unwrap();
+        let server = server(dir.path(), None, "Templates/Daily.md");
+        let note = server
+            .read_daily_note(parse_date("2026-10-16").unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(note.markdown, "# Today\n");
+    }
+
+    #[test]
+    fn test_named_template() {
+        let vault = tempfile::tempdir().unwrap();
+        let templates = tempfile::tempdir().unwrap();
+        fs::write(templates.path().join("Daily.md"), "# {{date:%A, %B %-d}}\n").unwrap();
+        let server = server(vault.path(), Some(templates.path()), "daily");
+        let note = server
+            .open_daily_note(parse_date("2026-10-16").unwrap(), true)
+            .unwrap();
+        assert_eq!(note.markdown, "# Friday, October 16\n");
+    }
+
+    #[rstest]
+    #[case("../%Y-%m-%d")]
+    #[case("")]
+    #[case("%Q")]
+    fn test_invalid_format(#[case] format: &str) {
+        let config = DailyNotesConfig {
+            format: format.to_string(),
+            ..Default::default()
+        };
+        assert!(config.note_path(parse_date("2026-10-16").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_without_vault() {
+        let server = Server::new(None).unwrap();
+        assert!(
+            server
+                .daily_note(Parameters(DailyNoteInput {
+                    date: None,
+                    create: Some(false),
+                }))
+                .is_err()
+        );
+    }
+
+    #[rstest]
+    #[case("", "- 9:00 Standup", None, "- 9:00 Standup\n", 1)]
+    #[case(
+        "# Today\n\n- 9:00 Standup\n",
+        "- 12:00 Lunch",
+        None,
+        "# Today\n\n- 9:00 Standup\n- 12:00 Lunch\n",
+        4
+    )]
+    #[case(
+        "# Today\n\n## Log\n\n- 9:00 Standup\n\n## Notes\n\nText.\n",
+        "- 12:00 Lunch",
+        Some("Log"),
+        "# Today\n\n## Log\n\n- 9:00 Standup\n- 12:00 Lunch\n\n## Notes\n\nText.\n",
+        6
+    )]
+    #[case(
+        "# Today\n\n## Log\n## Notes\n",
+        "Quiet day.",
+        Some("log"),
+        "# Today\n\n## Log\n\nQuiet day.\n\n## Notes\n",
+        5
+    )]
+    #[case(
+        "---\ntags: [daily]\n---\n# Today\n",
+        "- Idea",
+        Some("### Ideas"),
+        "---\ntags: [daily]\n---\n# Today\n\n### Ideas\n\n- Idea\n",
+        8
+    )]
+    #[case(
+        "# Today\r\n\r\n- 9:00 Standup\r\n",
+        "- 12:00 Lunch",
+        None,
+        "# Today\r\n\r\n- 9:00 Standup\r\n- 12:00 Lunch\r\n",
+        4
+    )]
+    fn test_append(
+        #[case] markdown: &str,
+        #[case] entry: &str,
+        #[case] heading: Option<&str>,
+        #[case] expected: &str,
+        #[case] line: usize,
+    ) {
+        assert_eq!(
+            append(markdown, entry, heading).unwrap(),
+            (expected.to_string(), line)
+        );
+    }
+}
@@ -0,0 +1,476 @@
+//! The `db_*` tools, backed by an [`mq-db`](https://github.com/harehare/mq-db)
+//! store loaded from the `--db` file.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, model::ContentBlock, schemars, tool,
+    tool_router,
+};
+
+use super::{
+    McpResult, Server, dry_run,
+    output::{
+        BlockTypeCount, CodeLangCount, DbDocument, DbDocumentsOutput, DbIndexOutput, DbSqlOutput,
+        DbStatsOutput, IndexFailure, QueryOutput, json_result, output_schema, query_result,
+        structured_result,
+    },
+    profile, query_error,
+};
+
+/// Shared, mutable handle to the loaded `mq-db` store. Guarded by a plain
+/// (synchronous) `Mutex` — DB tool methods are synchronous, so there's no
+/// `.await` while held, and this avoids pulling in tokio's `sync` feature.
+pub(super) type SharedDb = Arc<Mutex<mq_db::DocumentStore>>;
+
+/// The store configured with `--db`, or an empty one without it.
+pub(super) fn open(db_path: Option<&Path>) -> SharedDb {
+    Arc::new(Mutex::new(
+        db_path.map(load_or_create_db).unwrap_or_default(),
+    ))
+}
+
+/// Loads the store at `db_path` if it exists, otherwise starts with an
+/// empty one (so `db_index` can populate and later save it to that path).
+fn load_or_create_db(db_path: &Path) -> mq_db::DocumentStore {
+    if db_path.exists() {
+        match mq_db::DocumentStore::load(db_path) {
+            Ok(store) => return store,
+            Err(e) => tracing::error!(
+                "failed to load database at {}: {e} — starting with an empty store",
+                db_path.display()
+            ),
+        }
+    }
+    mq_db::DocumentStore::new()
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct DbSqlInput {
+    #[schemars(
+        description = "SQL query to run against the loaded mq-db database (SELECT, CREATE TABLE, INSERT INTO, DROP TABLE, DESC, SHOW TABLES). Virtual schema: documents(id, path, title, tags), blocks(id, document_id, block_type, content, pre, post, depth, lang, properties)."
+    )]
+    pub query: String,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct DbMqInput {
+    #[schemars(
+        description = "mq program to run against every document in the loaded mq-db database (only documents indexed from a file path — not ones added as raw strings)"
+    )]
+    pub code: String,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct DbIndexInput {
+    #[schemars(description = "Markdown files or directories to (re)index into the database")]
+    pub paths: Vec<String>,
+    #[schemars(description = "Recursively walk directories (default: false)")]
+    pub recursive: Option<bool>,
+    #[schemars(
+        description = "Remove catalogued documents whose path is no longer present in `paths` (default: false)"
+    )]
+    pub prune: Option<bool>,
+}
+
+impl Server {
+    /// Returns the locked store, or a descriptive error if no `--db` path
+    /// was configured at startup.
+    fn require_db(&self) -> Result<std::sync::MutexGuard<'_, mq_db::DocumentStore>, ErrorData> {
+        if self.config.db_path.is_none() {
+            return Err(ErrorData::invalid_request(
+                "no database configured — restart mq-mcp with --db <path> to enable db_* tools",
+                None,
+            ));
+        }
+        Ok(self.db.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+#[tool_router(router = db_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Run a read-only SQL query against the loaded mq-db database and return matching rows as JSON. Requires mq-mcp to have been started with --db <path>.",
+        output_schema = output_schema::<DbSqlOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn db_sql(&self, Parameters(DbSqlInput { query }): Parameters<DbSqlInput>) -> McpResult {
+        let store = self.require_db()?;
+        let engine = mq_db::SqlEngine::new(&store).map_err(|e| {
+            ErrorData::internal_error(
+                "Failed to build SQL engine",
+                Some(serde_json::Value::String(e.to_string())),
+            )
+        })?;
+        let out = engine.execute(&query).map_err(|e| {
+            ErrorData::invalid_request(
+                "SQL query failed",
+                Some(serde_json::Value::String(e.to_string())),
+            )
+        })?;
+        let content = vec![ContentBlock::text(out.to_json())];
+        structured_result(
+            content,
+            &DbSqlOutput {
+                columns: out.columns,
+                rows: out.rows,
+            },
+        )
+    }
+
+    #[tool(
+        description = "Run an mq program against every document in the loaded mq-db database and return the results. Requires mq-mcp to have been started with --db <path>.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn db_mq(&self, Parameters(DbMqInput { code }): Parameters<DbMqInput>) -> McpResult {
+        profile::check_policy(&code, &*self.live.profile(None)?)
+            .map_err(|e| query_error(&code, e))?;
+        let store = self.require_db()?;
+        let results = mq_db::MqEngine::eval_store(&code, &store).map_err(|e| {
+            ErrorData::invalid_request(
+                "mq query failed",
+                Some(serde_json::Value::String(e.to_string())),
+            )
+        })?;
+        query_result(results)
+    }
+
+    #[tool(
+        description = "List every document currently indexed in the loaded mq-db database (id, path, title, tags, block count). Requires mq-mcp to have been started with --db <path>.",
+        output_schema = output_schema::<DbDocumentsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn db_list_documents(&self) -> McpResult {
+        let store = self.require_db()?;
+        let documents: Vec<DbDocument> = store
+            .documents()
+            .iter()
+            .map(|d| DbDocument {
+                id: d.id,
+                path: d.path.as_ref().and_then(|p| p.to_str()).map(String::from),
+                title: d.zone_maps.title.clone(),
+                tags: d.zone_maps.tags.clone(),
+                block_count: d.block_count,
+            })
+            .collect();
+        // The text block stays a bare array for clients that predate
+        // structured output.
+        let content = vec![ContentBlock::text(
+            serde_json::to_string(&documents).unwrap_or_default(),
+        )];
+        structured_result(content, &DbDocumentsOutput { documents })
+    }
+
+    #[tool(
+        description = "Return block-type and code-language statistics for the loaded mq-db database. Requires mq-mcp to have been started with --db <path>.",
+        output_schema = output_schema::<DbStatsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn db_stats(&self) -> McpResult {
+        let store = self.require_db()?;
+        let stats = store.stats();
+        json_result(&DbStatsOutput {
+            documents: stats.documents,
+            blocks: stats.blocks,
+            block_type_counts: stats
+                .block_type_counts
+                .iter()
+                .map(|(bt, count)| BlockTypeCount {
+                    block_type: bt.as_str().to_string(),
+                    count: *count,
+                })
+                .collect(),
+            code_lang_counts: stats
+                .code_lang_counts
+                .iter()
+                .map(|(lang, count)| CodeLangCount {
+                    lang: lang.clone(),
+                    count: *count,
+                })
+                .collect(),
+        })
+    }
+
+    #[tool(
+        description = "Index or re-index Markdown files/directories into the loaded mq-db database, then persist it to the configured --db path. Skips files whose content hasn't changed since the last index; use `prune` to drop catalogued documents whose file no longer exists. Requires mq-mcp to have been started with --db <path>.",
+        output_schema = output_schema::<DbIndexOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    fn db_index(
+        &self,
+        Parameters(DbIndexInput {
+            paths,
+            recursive,
+            prune,
+        }): Parameters<DbIndexInput>,
+    ) -> McpResult {
+        let db_path = self.config.db_path.clone().ok_or_else(|| {
+            ErrorData::invalid_request(
+                "no database configured — restart mq-mcp with --db <path> to enable db_* tools",
+                None,
+            )
+        })?;
+
+        let paths = paths
+            .iter()
+            .map(|path| self.scoped_path(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let files = mq_db::discover::collect_markdown_files(&paths, recursive.unwrap_or(false));
+        if files.is_empty() {
+            return Err(ErrorData::invalid_request(
+                "No Markdown files found in the given paths",
+                None,
+            ));
+        }
+
+        let dry_run = dry_run::active();
+        let mut store = self.db.lock().unwrap_or_else(|e| e.into_inner());
+        // A dry run reindexes a scratch copy of the saved database, which
+        // matches the loaded one as every db_index call saves it.
+        let mut scratch = dry_run.then(|| load_or_create_db(&db_path));
+        let report = scratch
+            .as_mut()
+            .unwrap_or(&mut *store)
+            .reindex_paths(&files, prune.unwrap_or(false))
+            .map_err(|e| {
+                ErrorData::internal_error(
+                    "Reindex failed",
+                    Some(serde_json::Value::String(e.to_string())),
+                )
+            })?;
+        if !dry_run {
+            store.save(&db_path).map_err(|e| {
+                ErrorData::internal_error(
+                    "Failed to save database",
+                    Some(serde_json::Value::String(e.to_string())),
+                )
+            })?;
+        }
+
+        let lossy = |paths: &[PathBuf]| {
+            paths
+                .iter()
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        json_result(&DbIndexOutput {
+            added: lossy(&report.added),
+            updated: lossy(&report.updated),
+            unchanged: report.unchanged,
+            removed: lossy(&report.removed),
+            failed: report
+                .failed
+                .iter()
+                .map(|(p, e)| IndexFailure {
+                    path: p.to_string_lossy().into_owned(),
+                    error: e.to_string(),
+                })
+                .collect(),
+            dry_run,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ServerConfig;
+
+    fn ok_texts(result: rmcp::model::CallToolResult) -> Vec<String> {
+        assert!(!result.is_error.unwrap_or_default());
+        result
+            .content
+            .into_iter()
+            .map(|c| c.as_text().map(|t| t.text.clone()).unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn db_tools_report_error_without_configured_db() {
+        let server = Server::new(None).expect("Failed to create server");
+        let err = server
+            .db_sql(Parameters(DbSqlInput {
+                query: "SELECT 1".to_string(),
+            }))
+            .expect_err("expected an error with no --db configured");
+        assert!(err.message.contains("no database configured"));
+    }
+
+    #[test]
+    fn db_index_then_sql_and_mq_and_list_and_stats_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.md"), "# Title\n\nHello world\n").unwrap();
+        let db_path = dir.path().join("store.mq-db");
+
+        let server = Server::new(Some(db_path)).expect("Failed to create server");
+
+        let index_result = server
+            .db_index(Parameters(DbIndexInput {
+                paths: vec![dir.path().to_string_lossy().to_string()],
+                recursive: Some(false),
+                prune: Some(false),
+            }))
+            .expect("db_index should succeed");
+        let index_json = ok_texts(index_result).join("");
+        assert!(index_json.contains("\"added\":[") && index_json.contains("a.md"));
+
+        let sql_result = server
+            .db_sql(Parameters(DbSqlInput {
+                query: "SELECT content FROM blocks WHERE block_type = 'heading'".to_string(),
+            }))
+            .expect("db_sql should succeed");
+        assert!(ok_texts(sql_result).join("").contains("Title"));
+
+        let mq_result = server
+            .db_mq(Parameters(DbMqInput {
+                code: ".h1".to_string(),
+            }))
+            .expect("db_mq should succeed");
+        assert!(ok_texts(mq_result).join("").contains("Title"));
+
+        let list_result = server
+            .db_list_documents()
+            .expect("db_list_documents should succeed");
+        let list_json = ok_texts(list_result).join("");
+        assert!(list_json.contains("a.md"));
+
+        let stats_result = server.db_stats().expect("db_stats should succeed");
+        let stats_json = ok_texts(stats_result).join("");
+        assert!(stats_json.contains("\"documents\":1"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn db_index_refuses_symlinked_directory_outside_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("a.md"), "# Secret\n").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.join("link")).unwrap();
+
+        let server = Server::new(Some(root.join("store.mq-db"))).expect("Failed to create server");
+        server.roots.set(vec![root]);
+        for path in ["link", "link/new"] {
+            let err = server
+                .db_index(Parameters(DbIndexInput {
+                    paths: vec![path.to_string()],
+                    recursive: Some(true),
+                    prune: Some(false),
+                }))
+                .expect_err("expected the symlinked directory to be refused");
+            assert_eq!(err.message, "Path is outside the client's workspace roots");
+        }
+    }
+
+    #[test]
+    fn db_index_second_run_reports_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.md"), "# Title\n\nHello world\n").unwrap();
+        let db_path = dir.path().join("store.mq-db");
+
+        let server = Server::new(Some(db_path)).expect("Failed to create server");
+        let index_input = || DbIndexInput {
+            paths: vec![dir.path().to_string_lossy().to_string()],
+            recursive: Some(false),
+            prune: Some(false),
+        };
+
+        server
+            .db_index(Parameters(index_input()))
+            .expect("first db_index should succeed");
+        let second = server
+            .db_index(Parameters(index_input()))
+            .expect("second db_index should succeed");
+        let second_json = ok_texts(second).join("");
+        assert!(second_json.contains("\"unchanged\":1"));
+    }
+
+    #[tokio::test]
+    async fn db_index_dry_run_leaves_database_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.md"), "# Title\n\nHello world\n").unwrap();
+        let db_path = dir.path().join("store.mq-db");
+
+        let server = Server::new(Some(db_path.clone())).expect("Failed to create server");
+        let index_input = || DbIndexInput {
+            paths: vec![dir.path().to_string_lossy().to_string()],
+            recursive: Some(false),
+            prune: Some(false),
+        };
+
+        let dry_run = dry_run::scope(true, async { server.db_index(Parameters(index_input())) })
+            .await
+            .expect("dry-run db_index should succeed");
+        let dry_run_json = ok_texts(dry_run).join("");
+        assert!(dry_run_json.contains("a.md") && dry_run_json.contains("\"dry_run\":true"));
+        assert!(!db_path.exists());
+        let stats_json = ok_texts(server.db_stats().unwrap()).join("");
+        assert!(stats_json.contains("\"documents\":0"));
+
+        let index_json = ok_texts(server.db_index(Parameters(index_input())).unwrap()).join("");
+        assert!(index_json.contains("\"dry_run\":false"));
+        assert!(db_path.exists());
+    }
+
+    #[tokio::test]
+    async fn db_mq_checks_the_query_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let server =
+            Server::new(Some(dir.path().join("store.mq-db"))).expect("Failed to create server");
+        let profile = Arc::new(profile::Profile {
+            policy: Arc::new(crate::server::policy::Policy {
+                deny_functions: vec!["upcase".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let run = |code: &str| {
+            server.db_mq(Parameters(DbMqInput {
+                code: code.to_string(),
+            }))
+        };
+        let (denied, allowed) =
+            profile::scope(profile, async { (run(".h1 | upcase()"), run(".h1")) }).await;
+        let err = denied.expect_err("a denied function should be refused");
+        assert!(
+            err.data
+                .unwrap()
+                .to_string()
+                .contains("denies the function `upcase`")
+        );
+        assert!(allowed.is_ok());
+    }
+
+    #[test]
+    fn db_mq_outside_a_call_checks_the_config_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("mq-mcp.yaml");
+        std::fs::write(&config_path, "policy:\n  deny_functions: [upcase]\n").unwrap();
+        let server = Server::with_config(ServerConfig {
+            db_path: Some(dir.path().join("store.mq-db")),
+            config_path: Some(config_path),
+            ..Default::default()
+        })
+        .expect("Failed to create server");
+        let err = server
+            .db_mq(Parameters(DbMqInput {
+                code: ".h1 | upcase()".to_string(),
+            }))
+            .expect_err("a denied function should be refused");
+        assert!(
+            err.data
+                .unwrap()
+                .to_string()
+                .contains("denies the function `upcase`")
+        );
+    }
+}
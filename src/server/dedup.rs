@@ -0,0 +1,78 @@
+//! Content hashing of multi-document search results, so that text mirrored
+//! across files (copies of the same docs, vendored READMEs) is returned once
+//! with the other places it appears in.
+
+use std::collections::HashMap;
+
+use rmcp::{schemars, serde::Serialize};
+use sha2::{Digest, Sha256};
+
+/// Another place a collapsed result's content was found.
+#[derive(Debug, Clone, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct SharedSource {
+    #[schemars(description = "The file or document")]
+    pub source: String,
+    #[schemars(description = "1-based line of the section's heading")]
+    pub line: usize,
+}
+
+/// SHA-256 (hex) of a result's values, ignoring trailing whitespace and
+/// line endings.
+pub(super) fn content_hash(values: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for value in values {
+        for line in value.trim().lines() {
+            hasher.update(line.trim_end().as_bytes());
+            hasher.update(b"\n");
+        }
+        hasher.update(b"\0");
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// The first result seen with each content hash.
+#[derive(Debug, Default)]
+pub(super) struct Seen(HashMap<String, usize>);
+
+impl Seen {
+    /// The index of an earlier result with `hash`, or `None` after
+    /// recording `index` as the first.
+    pub(super) fn first(&mut self, hash: &str, index: usize) -> Option<usize> {
+        match self.0.get(hash) {
+            Some(&first) => Some(first),
+            None => {
+                self.0.insert(hash.to_string(), index);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_content_hash_normalizes_whitespace() {
+        let hash = content_hash(&values(&["# A\n\ntext\n"]));
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, content_hash(&values(&["# A  \r\n\r\ntext"])));
+        assert_ne!(hash, content_hash(&values(&["# A\n\ntext!"])));
+        assert_ne!(
+            content_hash(&values(&["a", "b"])),
+            content_hash(&values(&["a\nb"]))
+        );
+    }
+
+    #[test]
+    fn test_seen() {
+        let mut seen = Seen::default();
+        assert_eq!(seen.first("x", 0), None);
+        assert_eq!(seen.first("y", 1), None);
+        assert_eq!(seen.first("x", 2), Some(0));
+    }
+}
@@ -0,0 +1,263 @@
+//! `extract_definitions`: the glossary of a document, read from the ways
+//! markdown is commonly written to define terms: definition lists
+//! (`Term` then `: definition` lines), `Term: description` paragraphs and
+//! list items, and paragraphs that open with a bold term.
+
+use markdown::mdast;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    ast::parse_mdast,
+    output::{json_result, output_schema},
+};
+
+/// Most words a `Term: description` term may have.
+const MAX_TERM_WORDS: usize = 4;
+/// Words that make the text before a colon a clause rather than a term,
+/// as in "The reason is simple: ...".
+const VERBS: &[&str] = &["is", "are", "was", "were", "be", "has", "have"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DefinitionKind {
+    /// A term line followed by `: definition` lines.
+    DefinitionList,
+    /// `Term: description`.
+    Colon,
+    /// A paragraph opening with a bold term.
+    Bold,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ExtractDefinitionsInput {
+    #[schemars(description = "The markdown document to extract definitions from")]
+    pub markdown: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct Definition {
+    pub term: String,
+    #[schemars(description = "The definition as plain text")]
+    pub definition: String,
+    #[schemars(description = "How the definition is written")]
+    pub kind: DefinitionKind,
+    #[schemars(description = "1-based line of the term")]
+    pub line: usize,
+    #[schemars(description = "Text of the nearest heading before the definition")]
+    pub section: Option<String>,
+}
+
+/// Output of `extract_definitions`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ExtractDefinitionsOutput {
+    #[schemars(description = "Term/definition pairs in document order")]
+    pub definitions: Vec<Definition>,
+}
+
+/// Collapses the whitespace of `text`, line breaks included.
+fn squash(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The terms and definitions of a definition list: lines not starting with
+/// `:` are terms, defined by the `:` lines after them. Each pair has the
+/// index of its term's line.
+fn definition_list(text: &str) -> Vec<(usize, String, String)> {
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    if lines.len() < 2
+        || lines[0].starts_with(':')
+        || !lines.iter().any(|line| line.starts_with(": "))
+    {
+        return Vec::new();
+    }
+    let mut pairs = Vec::new();
+    let mut term = None;
+    for (index, line) in lines.iter().enumerate() {
+        match line.strip_prefix(": ") {
+            Some(definition) => {
+                if let Some((at, term)) = term {
+                    pairs.push((at, squash(term), squash(definition)));
+                }
+            }
+            None => term = Some((index, *line)),
+        }
+    }
+    pairs
+}
+
+/// Splits `Term: description`, if the text before the colon reads as a
+/// term: a few words, no sentence punctuation and no verb.
+fn colon(text: &str) -> Option<(String, String)> {
+    let (term, definition) = text.split_once(": ")?;
+    let words: Vec<&str> = term.split_whitespace().collect();
+    let definition = squash(definition);
+    let is_term = !words.is_empty()
+        && words.len() <= MAX_TERM_WORDS
+        && !term.contains(['\n', '.', '!', '?', ';', ','])
+        && !words
+            .iter()
+            .any(|word| VERBS.contains(&word.to_lowercase().as_str()));
+    (is_term && !definition.is_empty()).then(|| (squash(term), definition))
+}
+
+/// Splits a paragraph opening with a bold term, as in `**Term**: ...`,
+/// `**Term** - ...` or `**Term.** ...`.
+fn bold(children: &[mdast::Node]) -> Option<(String, String)> {
+    let (first, rest) = children.split_first()?;
+    if !matches!(first, mdast::Node::Strong(_)) {
+        return None;
+    }
+    let term = first.to_string();
+    let term = term.trim().trim_end_matches([':', '.']).trim();
+    let definition: String = rest.iter().map(ToString::to_string).collect();
+    let definition =
+        squash(definition.trim_start_matches(|c: char| c.is_whitespace() || ":.-–—".contains(c)));
+    (!term.is_empty() && !definition.is_empty()).then(|| (squash(term), definition))
+}
+
+fn collect(node: &mdast::Node, section: &mut Option<String>, definitions: &mut Vec<Definition>) {
+    match node {
+        mdast::Node::Heading(_) => *section = Some(squash(&node.to_string())),
+        mdast::Node::Paragraph(paragraph) => {
+            let line = node.position().map_or(0, |p| p.start.line);
+            let text = node.to_string();
+            let mut push = |offset: usize, term: String, definition: String, kind| {
+                definitions.push(Definition {
+                    term,
+                    definition,
+                    kind,
+                    line: line + offset,
+                    section: section.clone(),
+                });
+            };
+            let pairs = definition_list(&text);
+            if !pairs.is_empty() {
+                for (offset, term, definition) in pairs {
+                    push(offset, term, definition, DefinitionKind::DefinitionList);
+                }
+            } else if let Some((term, definition)) = bold(&paragraph.children) {
+                push(0, term, definition, DefinitionKind::Bold);
+            } else if let Some((term, definition)) = colon(&text) {
+                push(0, term, definition, DefinitionKind::Colon);
+            }
+        }
+        mdast::Node::Code(_) | mdast::Node::Table(_) | mdast::Node::Html(_) => {}
+        _ => {
+            for child in node.children().into_iter().flatten() {
+                collect(child, section, definitions);
+            }
+        }
+    }
+}
+
+fn extract(markdown: &str) -> Result<Vec<Definition>, ErrorData> {
+    let mut definitions = Vec::new();
+    collect(&parse_mdast(markdown)?, &mut None, &mut definitions);
+    Ok(definitions)
+}
+
+#[tool_router(router = definitions_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Extract the term/definition pairs of a markdown document, for building a glossary: definition lists (a term line, then `: definition` lines), `Term: description` paragraphs and list items, and paragraphs opening with a bold term. Each pair has its line and nearest heading.",
+        output_schema = output_schema::<ExtractDefinitionsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn extract_definitions(
+        &self,
+        Parameters(input): Parameters<ExtractDefinitionsInput>,
+    ) -> McpResult {
+        json_result(&ExtractDefinitionsOutput {
+            definitions: extract(&input.markdown)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn pairs(markdown: &str) -> Vec<(String, String, DefinitionKind)> {
+        extract(markdown)
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.term, d.definition, d.kind))
+            .collect()
+    }
+
+    #[rstest]
+    #[case(
+        "Cache\n: A store of fetched pages.\n",
+        "Cache",
+        "A store of fetched pages.",
+        DefinitionKind::DefinitionList
+    )]
+    #[case(
+        "- TTL: How long a page\n  stays cached.\n",
+        "TTL",
+        "How long a page stays cached.",
+        DefinitionKind::Colon
+    )]
+    #[case(
+        "**Root**: A directory the server may read.\n",
+        "Root",
+        "A directory the server may read.",
+        DefinitionKind::Bold
+    )]
+    #[case(
+        "**Dry run.** A call that changes *nothing*.\n",
+        "Dry run",
+        "A call that changes nothing.",
+        DefinitionKind::Bold
+    )]
+    #[case(
+        "`mq://results`: Where large results are offloaded.\n",
+        "mq://results",
+        "Where large results are offloaded.",
+        DefinitionKind::Colon
+    )]
+    fn test_extract(
+        #[case] markdown: &str,
+        #[case] term: &str,
+        #[case] definition: &str,
+        #[case] kind: DefinitionKind,
+    ) {
+        assert_eq!(
+            pairs(markdown),
+            vec![(term.to_string(), definition.to_string(), kind)]
+        );
+    }
+
+    #[rstest]
+    #[case("The reason is simple: it is faster.\n")]
+    #[case("See the docs at https://example.com for more.\n")]
+    #[case("In short, and after much thought: no.\n")]
+    #[case("```\nkey: value\n```\n")]
+    fn test_not_definitions(#[case] markdown: &str) {
+        assert!(pairs(markdown).is_empty());
+    }
+
+    #[test]
+    fn test_definition_list_with_several_terms() {
+        let definitions = extract(
+            "# Glossary\n\nIntro.\n\nRoot\n: A directory.\nTTL\n: A lifetime.\n: In seconds.\n",
+        )
+        .unwrap();
+        assert_eq!(
+            definitions
+                .iter()
+                .map(|d| (d.term.as_str(), d.definition.as_str(), d.line))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Root", "A directory.", 5),
+                ("TTL", "A lifetime.", 7),
+                ("TTL", "In seconds.", 7),
+            ]
+        );
+        assert_eq!(definitions[0].section.as_deref(), Some("Glossary"));
+    }
+}
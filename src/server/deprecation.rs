@@ -0,0 +1,128 @@
+//! Tool versions. A tool whose arguments or result change incompatibly is
+//! added again under a versioned name (`extract_markdown_v2`), and the old
+//! version stays, deprecated, for the clients and prompts built against it
+//! until a later release removes it.
+//!
+//! A deprecated tool's description starts with a notice naming its
+//! replacement, its input schema is marked `"deprecated": true`, and its
+//! results carry the notice in `_meta`. `--hide-deprecated-tools` (or the
+//! config file's `hide_deprecated_tools`) leaves deprecated tools out of
+//! `tools/list` and refuses calls to them, to check that a setup no longer
+//! depends on them.
+
+use std::sync::Arc;
+
+use rmcp::model::{CallToolResult, Meta, Tool};
+
+use super::Locale;
+
+/// Key of the notice in a deprecated tool's results' `_meta`.
+const META_KEY: &str = "deprecation";
+
+/// A deprecated tool and the version replacing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Deprecation {
+    pub tool: &'static str,
+    pub replacement: &'static str,
+    /// The release that deprecated the tool.
+    pub since: &'static str,
+}
+
+/// Every deprecated tool.
+pub(crate) const DEPRECATIONS: &[Deprecation] = &[Deprecation {
+    tool: "extract_markdown",
+    replacement: "extract_markdown_v2",
+    since: "0.1.24",
+}];
+
+/// The deprecation of `tool`, if it is deprecated.
+pub(crate) fn find(tool: &str) -> Option<&'static Deprecation> {
+    DEPRECATIONS
+        .iter()
+        .find(|deprecation| deprecation.tool == tool)
+}
+
+/// Marks `tool` as deprecated in its (already localized) description and
+/// input schema, if it is.
+pub(crate) fn describe(mut tool: Tool, locale: Locale) -> Tool {
+    let Some(deprecation) = find(&tool.name) else {
+        return tool;
+    };
+    let description = tool.description.as_deref().unwrap_or_default();
+    tool.description = Some(format!("{} {description}", deprecation.notice(locale)).into());
+    let mut schema = (*tool.input_schema).clone();
+    schema.insert("deprecated".to_string(), serde_json::Value::Bool(true));
+    tool.input_schema = Arc::new(schema);
+    tool
+}
+
+impl Deprecation {
+    fn notice(&self, locale: Locale) -> String {
+        let Self {
+            replacement, since, ..
+        } = self;
+        match locale {
+            Locale::En => format!(
+                "Deprecated since {since}: use {replacement} instead; this tool will be removed in a future release."
+            ),
+            Locale::Ja => format!(
+                "{since} で非推奨になりました。代わりに {replacement} を使用してください。このツールは将来のリリースで削除されます。"
+            ),
+        }
+    }
+
+    /// `result` with the notice in its `_meta`.
+    pub(crate) fn tag_result(&self, mut result: CallToolResult) -> CallToolResult {
+        result.meta.get_or_insert_with(Meta::new).0.insert(
+            META_KEY.to_string(),
+            serde_json::json!({
+                "replacement": self.replacement,
+                "since": self.since,
+            }),
+        );
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{Server, ServerConfig};
+
+    #[test]
+    fn test_deprecated_tools_have_replacements() {
+        let server = Server::new(None).unwrap();
+        for deprecation in DEPRECATIONS {
+            assert!(server.tool_router.has_route(deprecation.tool));
+            assert!(server.tool_router.has_route(deprecation.replacement));
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        let tool = Server::new(None)
+            .unwrap()
+            .tools()
+            .into_iter()
+            .find(|tool| tool.name == "extract_markdown")
+            .unwrap();
+        assert!(
+            tool.description
+                .unwrap()
+                .starts_with("Deprecated since 0.1.24: use extract_markdown_v2 instead")
+        );
+        assert_eq!(tool.input_schema["deprecated"], true);
+    }
+
+    #[test]
+    fn test_hide_deprecated_tools() {
+        let server = Server::with_config(ServerConfig {
+            hide_deprecated_tools: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let names: Vec<_> = server.tools().into_iter().map(|tool| tool.name).collect();
+        assert!(!names.iter().any(|name| name == "extract_markdown"));
+        assert!(names.iter().any(|name| name == "extract_markdown_v2"));
+    }
+}
@@ -0,0 +1,468 @@
+//! `extract_diagrams`: mermaid and PlantUML fenced blocks, with their
+//! titles and optional syntax checks.
+
+use mq_markdown::Node;
+use rmcp::{
+    handler::server::wrapper::Parameters, model::ContentBlock, schemars, serde::Serialize, tool,
+    tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    output::{json_result, output_schema, structured_result},
+    parse_markdown_only,
+};
+
+/// Code block languages extracted when `languages` is not given.
+const DEFAULT_LANGUAGES: &[&str] = &["mermaid", "plantuml", "puml"];
+/// Diagram declarations a mermaid diagram can start with.
+const MERMAID_TYPES: &[&str] = &[
+    "flowchart",
+    "graph",
+    "sequenceDiagram",
+    "classDiagram",
+    "classDiagram-v2",
+    "stateDiagram",
+    "stateDiagram-v2",
+    "erDiagram",
+    "journey",
+    "gantt",
+    "pie",
+    "quadrantChart",
+    "requirementDiagram",
+    "gitGraph",
+    "C4Context",
+    "C4Container",
+    "C4Component",
+    "C4Dynamic",
+    "C4Deployment",
+    "mindmap",
+    "timeline",
+    "zenuml",
+    "sankey-beta",
+    "xychart-beta",
+    "block-beta",
+    "packet-beta",
+    "kanban",
+    "architecture-beta",
+    "radar-beta",
+];
+const FLOWCHART_DIRECTIONS: &[&str] = &["TB", "TD", "BT", "RL", "LR"];
+/// Statements opening a block that is closed by `end` in sequence diagrams.
+const SEQUENCE_BLOCKS: &[&str] = &[
+    "loop", "alt", "opt", "par", "critical", "break", "rect", "box",
+];
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ExtractDiagramsInput {
+    #[schemars(description = "The markdown content to process")]
+    pub markdown: String,
+    #[schemars(
+        description = "Code block languages to extract, case-insensitive (default: mermaid, plantuml, puml)"
+    )]
+    pub languages: Option<Vec<String>>,
+    #[schemars(
+        description = "Check mermaid diagrams for an unknown diagram type, unbalanced brackets and unclosed blocks, and PlantUML diagrams for unmatched @start/@end tags (default: false)"
+    )]
+    pub validate: Option<bool>,
+    #[schemars(
+        description = "Return each diagram as its own content item, headed by its title, instead of one JSON block (default: false)"
+    )]
+    pub split: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct DiagramError {
+    #[schemars(description = "1-based line in the markdown document")]
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct Diagram {
+    #[schemars(description = "The code block language, e.g. `mermaid`")]
+    pub lang: String,
+    #[schemars(description = "Text of the nearest heading before the diagram")]
+    pub title: Option<String>,
+    #[schemars(description = "1-based line of the opening fence")]
+    pub line: usize,
+    #[schemars(description = "The diagram source, without the fences")]
+    pub source: String,
+    #[schemars(
+        description = "Whether the diagram passed validation; null unless `validate` is set"
+    )]
+    pub valid: Option<bool>,
+    pub errors: Vec<DiagramError>,
+}
+
+/// Output of `extract_diagrams`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct DiagramsOutput {
+    #[schemars(description = "Diagrams in document order")]
+    pub diagrams: Vec<Diagram>,
+}
+
+/// A meaningful diagram line: its 0-based offset in the source and its
+/// text with any `%%` comment removed.
+struct Line<'a> {
+    index: usize,
+    text: &'a str,
+}
+
+/// Mermaid source lines after the front matter, without comments,
+/// directives and blank lines.
+fn mermaid_lines(source: &str) -> Vec<Line<'_>> {
+    let mut lines = source.lines().enumerate().peekable();
+    if lines.peek().is_some_and(|(_, line)| line.trim() == "---") {
+        lines.next();
+        for (_, line) in lines.by_ref() {
+            if line.trim() == "---" {
+                break;
+            }
+        }
+    }
+    lines
+        .filter_map(|(index, line)| {
+            let text = line.split("%%").next().unwrap_or_default().trim();
+            (!text.is_empty()).then_some(Line { index, text })
+        })
+        .collect()
+}
+
+/// Splits `text` into the parts outside double quotes.
+fn unquoted(text: &str) -> impl Iterator<Item = &str> {
+    text.split('"').step_by(2)
+}
+
+/// Checks that `(`, `[` and `{` are closed on the line that opens them.
+/// A stray `]` is allowed, as it closes the asymmetric `A>text]` shape.
+fn check_brackets(line: &Line<'_>, errors: &mut Vec<(usize, String)>) {
+    let mut stack = Vec::new();
+    for c in unquoted(line.text).flat_map(str::chars) {
+        match c {
+            '(' | '[' | '{' => stack.push(c),
+            ')' | ']' | '}' => {
+                let open = match c {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                match stack.pop() {
+                    Some(top) if top == open => {}
+                    None if c == ']' => {}
+                    _ => {
+                        errors.push((line.index, format!("Unbalanced `{c}`")));
+                        return;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(open) = stack.last() {
+        errors.push((line.index, format!("Unclosed `{open}`")));
+    }
+}
+
+/// Checks that every line starting with one of `openers` is matched by a
+/// line that is just `end`.
+fn check_end_blocks(lines: &[Line<'_>], openers: &[&str], errors: &mut Vec<(usize, String)>) {
+    let mut open = Vec::new();
+    for line in lines {
+        let keyword = line.text.split_whitespace().next().unwrap_or_default();
+        if openers.contains(&keyword) {
+            open.push((line.index, keyword));
+        } else if line.text == "end" && open.pop().is_none() {
+            errors.push((line.index, "`end` without an open block".to_string()));
+        }
+    }
+    for (index, keyword) in open {
+        errors.push((index, format!("Unclosed `{keyword}` block")));
+    }
+}
+
+/// Checks that `{` and `}` are balanced across lines.
+fn check_braces(lines: &[Line<'_>], errors: &mut Vec<(usize, String)>) {
+    let mut open = Vec::new();
+    for line in lines {
+        for c in unquoted(line.text).flat_map(str::chars) {
+            match c {
+                '{' => open.push(line.index),
+                '}' if open.pop().is_none() => {
+                    errors.push((line.index, "Unbalanced `}`".to_string()));
+                }
+                _ => {}
+            }
+        }
+    }
+    for index in open {
+        errors.push((index, "Unclosed `{`".to_string()));
+    }
+}
+
+/// Returns syntax errors in a mermaid diagram as (0-based source line,
+/// message) pairs. This is a structural check, not a full parse: it
+/// catches unknown diagram types, bad flowchart directions, unbalanced
+/// brackets and unclosed blocks.
+fn validate_mermaid(source: &str) -> Vec<(usize, String)> {
+    let lines = mermaid_lines(source);
+    let Some((header, body)) = lines.split_first() else {
+        return vec![(0, "Empty diagram".to_string())];
+    };
+    let mut words = header.text.split_whitespace();
+    let kind = words.next().unwrap_or_default();
+    if !MERMAID_TYPES.contains(&kind) {
+        return vec![(header.index, format!("Unknown diagram type `{kind}`"))];
+    }
+
+    let mut errors = Vec::new();
+    match kind {
+        "flowchart" | "graph" => {
+            if let Some(direction) = words.next().map(|d| d.trim_end_matches(';'))
+                && !FLOWCHART_DIRECTIONS.contains(&direction)
+            {
+                errors.push((header.index, format!("Unknown direction `{direction}`")));
+            }
+            for line in body {
+                check_brackets(line, &mut errors);
+            }
+            check_end_blocks(body, &["subgraph"], &mut errors);
+        }
+        "sequenceDiagram" => check_end_blocks(body, SEQUENCE_BLOCKS, &mut errors),
+        "classDiagram" | "classDiagram-v2" | "stateDiagram" | "stateDiagram-v2" | "erDiagram"
+        | "requirementDiagram" => check_braces(body, &mut errors),
+        _ => {}
+    }
+    errors.sort_by_key(|(index, _)| *index);
+    errors
+}
+
+/// Returns unmatched `@startX`/`@endX` tags in a PlantUML diagram. Blocks
+/// without any tags are accepted, as renderers add `@startuml` themselves.
+fn validate_plantuml(source: &str) -> Vec<(usize, String)> {
+    let mut errors = Vec::new();
+    let mut open: Option<(usize, &str)> = None;
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if let Some(kind) = line.strip_prefix("@start") {
+            let kind = kind.split_whitespace().next().unwrap_or_default();
+            if let Some((start, kind)) = open.replace((index, kind)) {
+                errors.push((start, format!("`@start{kind}` without `@end{kind}`")));
+            }
+        } else if let Some(kind) = line.strip_prefix("@end") {
+            match open.take() {
+                Some((_, start)) if start == kind.trim() => {}
+                Some((_, start)) => {
+                    errors.push((
+                        index,
+                        format!("`@end{}` closes `@start{start}`", kind.trim()),
+                    ));
+                }
+                None => errors.push((index, format!("`@end{}` without `@start`", kind.trim()))),
+            }
+        }
+    }
+    if let Some((start, kind)) = open {
+        errors.push((start, format!("`@start{kind}` without `@end{kind}`")));
+    }
+    errors
+}
+
+/// Collects fenced blocks in `languages` in document order, with the
+/// text of the nearest heading before each.
+fn collect(
+    nodes: &[Node],
+    languages: &[String],
+    title: &mut Option<String>,
+    blocks: &mut Vec<(Option<String>, mq_markdown::Code)>,
+) {
+    for node in nodes {
+        match node {
+            Node::Heading(_) => *title = Some(node.value().trim().to_string()),
+            Node::Code(code) if code.fence => {
+                let lang = code.lang.as_deref().unwrap_or_default();
+                if languages.iter().any(|l| l.eq_ignore_ascii_case(lang)) {
+                    blocks.push((title.clone(), code.clone()));
+                }
+            }
+            _ => collect(&node.children(), languages, title, blocks),
+        }
+    }
+}
+
+fn extract_diagrams(nodes: &[Node], languages: &[String], validate: bool) -> Vec<Diagram> {
+    let mut blocks = Vec::new();
+    collect(nodes, languages, &mut None, &mut blocks);
+    blocks
+        .into_iter()
+        .map(|(title, code)| {
+            let lang = code.lang.unwrap_or_default();
+            let line = code.position.map(|p| p.start.line).unwrap_or_default();
+            let errors = if !validate {
+                Vec::new()
+            } else if lang.eq_ignore_ascii_case("mermaid") {
+                validate_mermaid(&code.value)
+            } else if lang.eq_ignore_ascii_case("plantuml") || lang.eq_ignore_ascii_case("puml") {
+                validate_plantuml(&code.value)
+            } else {
+                Vec::new()
+            };
+            Diagram {
+                title,
+                line,
+                source: code.value,
+                valid: validate.then_some(errors.is_empty()),
+                // The source starts on the line after the opening fence.
+                errors: errors
+                    .into_iter()
+                    .map(|(index, message)| DiagramError {
+                        line: line + 1 + index,
+                        message,
+                    })
+                    .collect(),
+                lang,
+            }
+        })
+        .collect()
+}
+
+/// Renders a diagram as a markdown content item: its title as a heading,
+/// the fenced source and any validation errors.
+fn diagram_markdown(diagram: &Diagram) -> String {
+    let mut parts = Vec::new();
+    if let Some(title) = &diagram.title {
+        parts.push(format!("## {title}"));
+    }
+    parts.push(format!("```{}\n{}\n```", diagram.lang, diagram.source));
+    if !diagram.errors.is_empty() {
+        parts.push(
+            diagram
+                .errors
+                .iter()
+                .map(|e| format!("- line {}: {}", e.line, e.message))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+    }
+    parts.join("\n\n")
+}
+
+#[tool_router(router = diagrams_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Extract mermaid and PlantUML diagrams (fenced code blocks) from markdown, each with its title from the nearest preceding heading and its line number. Optionally checks mermaid syntax (diagram type, brackets, unclosed blocks) and PlantUML @start/@end tags, and can return each diagram as a separate content item.",
+        output_schema = output_schema::<DiagramsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn extract_diagrams(&self, Parameters(input): Parameters<ExtractDiagramsInput>) -> McpResult {
+        let nodes = parse_markdown_only(&input.markdown)?;
+        let languages = input
+            .languages
+            .unwrap_or_else(|| DEFAULT_LANGUAGES.iter().map(|l| l.to_string()).collect());
+        let output = DiagramsOutput {
+            diagrams: extract_diagrams(&nodes, &languages, input.validate.unwrap_or_default()),
+        };
+        if input.split.unwrap_or_default() {
+            let content = output
+                .diagrams
+                .iter()
+                .map(|diagram| ContentBlock::text(diagram_markdown(diagram)))
+                .collect();
+            structured_result(content, &output)
+        } else {
+            json_result(&output)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("flowchart LR\n  A[Start] --> B{Ok?}\n  B -->|yes| C>Done]", vec![])]
+    #[case("---\ntitle: Flow\n---\n%%{init: {\"theme\": \"dark\"}}%%\ngraph TD;\n  A --> B", vec![])]
+    #[case("flowchart XY\n  A --> B", vec![(0, "Unknown direction `XY`")])]
+    #[case("flowchart TD\n  A[Start --> B\n  B(\"(\") --> C", vec![(1, "Unclosed `[`")])]
+    #[case("flowchart TD\n  A[Start) --> B", vec![(1, "Unbalanced `)`")])]
+    #[case("flowchart TD\n  subgraph one\n    A --> B", vec![(1, "Unclosed `subgraph` block")])]
+    #[case("sequenceDiagram\n  loop Every minute\n    A->>B: ping :)\n  end\n  end", vec![(4, "`end` without an open block")])]
+    #[case("classDiagram\n  class Animal {\n    +name\n", vec![(1, "Unclosed `{`")])]
+    #[case("flowhcart TD\n  A --> B", vec![(0, "Unknown diagram type `flowhcart`")])]
+    #[case("%% nothing here\n", vec![(0, "Empty diagram")])]
+    fn test_validate_mermaid(#[case] source: &str, #[case] expected: Vec<(usize, &str)>) {
+        let errors = validate_mermaid(source);
+        assert_eq!(
+            errors
+                .iter()
+                .map(|(i, m)| (*i, m.as_str()))
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("Alice -> Bob", vec![])]
+    #[case("@startuml\nAlice -> Bob\n@enduml", vec![])]
+    #[case("@startuml\nAlice -> Bob", vec![(0, "`@startuml` without `@enduml`")])]
+    #[case("@startmindmap\n* root\n@enduml", vec![(2, "`@enduml` closes `@startmindmap`")])]
+    fn test_validate_plantuml(#[case] source: &str, #[case] expected: Vec<(usize, &str)>) {
+        let errors = validate_plantuml(source);
+        assert_eq!(
+            errors
+                .iter()
+                .map(|(i, m)| (*i, m.as_str()))
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_extract_diagrams() {
+        let markdown = "\
+# Design
+
+## Login flow
+
+```mermaid
+flowchart TD
+  A[Login --> B
+```
+
+```rust
+fn main() {}
+```
+
+- Sequence:
+
+  ```PlantUML
+  @startuml
+  A -> B
+  @enduml
+  ```
+";
+        let nodes = parse_markdown_only(markdown).unwrap();
+        let languages: Vec<String> = DEFAULT_LANGUAGES.iter().map(|l| l.to_string()).collect();
+        let diagrams = extract_diagrams(&nodes, &languages, true);
+
+        assert_eq!(diagrams.len(), 2);
+        assert_eq!(diagrams[0].title.as_deref(), Some("Login flow"));
+        assert_eq!(diagrams[0].line, 5);
+        assert_eq!(diagrams[0].valid, Some(false));
+        assert_eq!(
+            diagrams[0].errors,
+            vec![DiagramError {
+                line: 7,
+                message: "Unclosed `[`".to_string()
+            }]
+        );
+        assert_eq!(diagrams[1].lang, "PlantUML");
+        assert_eq!(diagrams[1].title.as_deref(), Some("Login flow"));
+        assert_eq!(diagrams[1].valid, Some(true));
+        assert_eq!(
+            diagram_markdown(&diagrams[0]),
+            "## Login flow\n\n```mermaid\nflowchart TD\n  A[Login --> B\n```\n\n- line 7: Unclosed `[`"
+        );
+    }
+}
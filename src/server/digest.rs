@@ -0,0 +1,194 @@
+//! Digests of watched documents: a pipeline scheduled with `"digest": true`
+//! publishes what changed in the headings and sections of its output since
+//! the previous run, rather than the output itself, so an agent asked what
+//! changed in some docs reads a short summary instead of comparing them.
+//!
+//! Sections are identified by their heading path (`Install > From source`),
+//! so a section whose heading is renamed shows as removed and added.
+
+use std::collections::HashMap;
+
+use rmcp::ErrorData;
+
+use super::sections;
+
+/// Path of the text before a document's first heading.
+const PREAMBLE: &str = "(before the first heading)";
+
+/// The sections of a document: heading path and body, in document order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct Outline(Vec<(String, String)>);
+
+impl Outline {
+    pub(super) fn new(markdown: &str) -> Result<Self, ErrorData> {
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut entries = Vec::new();
+        for section in sections::split(markdown)? {
+            let (path, body) = match &section.title {
+                Some(_) => {
+                    let path = section.path.join(" > ");
+                    let body = section
+                        .markdown
+                        .split_once('\n')
+                        .map_or("", |(_, body)| body);
+                    (path, body.trim().to_string())
+                }
+                None => (PREAMBLE.to_string(), section.markdown.trim().to_string()),
+            };
+            // Repeated paths are told apart by their position among them.
+            let count = seen.entry(path.clone()).or_default();
+            *count += 1;
+            let path = match *count {
+                1 => path,
+                n => format!("{path} ({n})"),
+            };
+            entries.push((path, body));
+        }
+        Ok(Self(entries))
+    }
+
+    fn body(&self, path: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(known, _)| known == path)
+            .map(|(_, body)| body.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) enum Change {
+    Added(String),
+    Removed(String),
+    /// A section whose body changed, with the number of lines added and
+    /// removed.
+    Changed {
+        path: String,
+        added: usize,
+        removed: usize,
+    },
+}
+
+/// The changes from `old` to `new`: sections added or changed in the order
+/// of `new`, then those removed in the order of `old`.
+pub(super) fn changes(old: &Outline, new: &Outline) -> Vec<Change> {
+    let mut changes: Vec<Change> = new
+        .0
+        .iter()
+        .filter_map(|(path, body)| match old.body(path) {
+            None => Some(Change::Added(path.clone())),
+            Some(previous) if previous != body => {
+                let (added, removed) = line_changes(previous, body);
+                Some(Change::Changed {
+                    path: path.clone(),
+                    added,
+                    removed,
+                })
+            }
+            Some(_) => None,
+        })
+        .collect();
+    changes.extend(
+        old.0
+            .iter()
+            .filter(|(path, _)| new.body(path).is_none())
+            .map(|(path, _)| Change::Removed(path.clone())),
+    );
+    changes
+}
+
+/// Non-blank lines only in `new` and only in `old`, counted as multisets.
+fn line_changes(old: &str, new: &str) -> (usize, usize) {
+    let mut counts: HashMap<&str, isize> = HashMap::new();
+    for line in old.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        *counts.entry(line).or_default() -= 1;
+    }
+    for line in new.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        *counts.entry(line).or_default() += 1;
+    }
+    counts.values().fold((0, 0), |(added, removed), &count| {
+        (
+            added + count.max(0).unsigned_abs(),
+            removed + count.min(0).unsigned_abs(),
+        )
+    })
+}
+
+/// The digest of `changes` to `source`, as a markdown section headed by the
+/// source and the time of the run.
+pub(super) fn render(source: &str, ran_at: &str, changes: &[Change]) -> String {
+    let mut digest = format!("## Changes to {source}\n\nAs of {ran_at}:\n");
+    for change in changes {
+        digest.push_str(&match change {
+            Change::Added(path) => format!("\n- Added: {path}"),
+            Change::Removed(path) => format!("\n- Removed: {path}"),
+            Change::Changed {
+                path,
+                added,
+                removed,
+            } => format!("\n- Changed: {path} (+{added} -{removed} lines)"),
+        });
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outline() {
+        let outline =
+            Outline::new("Intro.\n\n# Guide\n\n## Install\n\nRun it.\n\n# FAQ\n\n## Install\n")
+                .unwrap();
+        assert_eq!(
+            outline.0,
+            vec![
+                (PREAMBLE.to_string(), "Intro.".to_string()),
+                ("Guide".to_string(), String::new()),
+                ("Guide > Install".to_string(), "Run it.".to_string()),
+                ("FAQ".to_string(), String::new()),
+                ("FAQ > Install".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeated_headings_are_numbered() {
+        let outline = Outline::new("## Example\n\na\n\n## Example\n\nb\n").unwrap();
+        assert_eq!(outline.0[1].0, "Example (2)");
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = Outline::new("# Guide\n\n## Install\n\nRun it.\n\n## Legacy\n\nOld.\n").unwrap();
+        let new =
+            Outline::new("# Guide\n\n## Install\n\nRun it.\nThen check.\n\n## Usage\n\nUse it.\n")
+                .unwrap();
+        let changes = changes(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                Change::Changed {
+                    path: "Guide > Install".to_string(),
+                    added: 1,
+                    removed: 0,
+                },
+                Change::Added("Guide > Usage".to_string()),
+                Change::Removed("Guide > Legacy".to_string()),
+            ]
+        );
+        assert_eq!(
+            render("CHANGELOG.md", "2026-03-01T10:00:00+00:00", &changes),
+            "## Changes to CHANGELOG.md\n\nAs of 2026-03-01T10:00:00+00:00:\n\n\
+             - Changed: Guide > Install (+1 -0 lines)\n\
+             - Added: Guide > Usage\n\
+             - Removed: Guide > Legacy"
+        );
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let outline = Outline::new("# Guide\n\nText.\n").unwrap();
+        assert!(changes(&outline, &outline.clone()).is_empty());
+    }
+}
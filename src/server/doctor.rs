@@ -0,0 +1,467 @@
+//! `mq-mcp doctor`: checks that a server built from the given flags would
+//! work, and reports each check, so a failing setup can be diagnosed from
+//! the command line instead of from a client that only says the server
+//! failed to start.
+//!
+//! The checks cover the query engine and its builtins, the cargo features
+//! the binary was built with, a few sample queries, the configured files and
+//! the directories they are written to, and whether the hosts of the
+//! configured integrations can be reached. They never send credentials:
+//! reachability is a TCP connection to the host.
+
+use std::path::{Path, PathBuf};
+#[cfg(not(target_os = "wasi"))]
+use std::time::{Duration, Instant};
+
+use rmcp::serde::Serialize;
+
+use super::{
+    Server, ServerConfig,
+    engine::{Engine, QueryEngine},
+};
+#[cfg(feature = "fetch")]
+use super::{gdoc, github, notion, slack};
+
+/// How long to wait for an integration's host to accept a connection.
+#[cfg(not(target_os = "wasi"))]
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    /// Works, but may not behave as expected.
+    Warn,
+    Fail,
+    /// Not applicable to this configuration.
+    Skip,
+}
+
+impl CheckStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Fail => "FAIL",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// The result of [`doctor`].
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    /// Whether no check failed.
+    pub ok: bool,
+    pub version: &'static str,
+    pub checks: Vec<Check>,
+}
+
+/// A query run by the `queries` check.
+struct Sample {
+    name: &'static str,
+    query: &'static str,
+    markdown: &'static str,
+    aggregate: bool,
+    /// `None` accepts any non-empty result.
+    expected: Option<&'static [&'static str]>,
+}
+
+const SAMPLES: &[Sample] = &[
+    Sample {
+        name: "headings",
+        query: ".h",
+        markdown: "# Install\n\n## Usage",
+        aggregate: false,
+        expected: Some(&["# Install", "## Usage"]),
+    },
+    Sample {
+        name: "to_text",
+        query: ".h1 | to_text()",
+        markdown: "# Install\n\ntext",
+        aggregate: false,
+        expected: Some(&["Install"]),
+    },
+    Sample {
+        name: "section module",
+        query: r#"import "section" | section::sections() | section::toc()"#,
+        markdown: "# Install\n\n## Usage",
+        aggregate: true,
+        expected: None,
+    },
+];
+
+/// Runs every check for a server built from `config`.
+pub async fn doctor(config: ServerConfig) -> DoctorReport {
+    let mut checks = vec![check_engine(), check_features()];
+    let endpoints = endpoints(&config);
+    checks.extend(check_files(&config));
+    #[cfg(feature = "pandoc")]
+    checks.push(check_pandoc(&config.pandoc.path));
+    match Server::with_config(config) {
+        Ok(server) => checks.push(check_queries(&server)),
+        Err(e) => checks.push(Check::new("server", CheckStatus::Fail, e.to_string())),
+    }
+    if endpoints.is_empty() {
+        checks.push(Check::new(
+            "integrations",
+            CheckStatus::Skip,
+            "no integrations configured",
+        ));
+    }
+    for (name, url) in endpoints {
+        checks.push(check_reachable(name, &url).await);
+    }
+    DoctorReport {
+        ok: checks.iter().all(|check| check.status != CheckStatus::Fail),
+        version: env!("CARGO_PKG_VERSION"),
+        checks,
+    }
+}
+
+fn check_engine() -> Check {
+    let hir = mq_hir::Hir::default();
+    let functions = hir.builtin.functions.len() + hir.builtin.internal_functions.len();
+    if functions == 0 {
+        return Check::new("engine", CheckStatus::Fail, "no builtin functions loaded");
+    }
+    Check::new(
+        "engine",
+        CheckStatus::Ok,
+        format!("mq-lang {}, {functions} builtin functions", Engine::VERSION),
+    )
+}
+
+/// Lists the optional cargo features the binary was built with, which
+/// decide the tools it has.
+fn check_features() -> Check {
+    let features: Vec<&str> = [
+        ("db", cfg!(feature = "db")),
+        ("fetch", cfg!(feature = "fetch")),
+        ("index", cfg!(feature = "index")),
+        ("pandoc", cfg!(feature = "pandoc")),
+        ("embeddings", cfg!(feature = "embeddings")),
+        ("s3", cfg!(feature = "s3")),
+        ("otel", cfg!(feature = "otel")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect();
+    let detail = if features.is_empty() {
+        "none (minimal build)".to_string()
+    } else {
+        features.join(", ")
+    };
+    Check::new("features", CheckStatus::Ok, detail)
+}
+
+fn check_queries(server: &Server) -> Check {
+    for sample in SAMPLES {
+        let values = if sample.aggregate {
+            server.aggregate_values(sample.markdown, sample.query)
+        } else {
+            server.query_values(sample.markdown, sample.query)
+        };
+        let failure = match values {
+            Err(e) => Some(e.message.to_string()),
+            Ok(values) if values.is_empty() => Some("no results".to_string()),
+            Ok(values) => sample
+                .expected
+                .filter(|expected| *expected != values.as_slice())
+                .map(|expected| format!("expected {expected:?}, got {values:?}")),
+        };
+        if let Some(failure) = failure {
+            return Check::new(
+                "queries",
+                CheckStatus::Fail,
+                format!("{} (`{}`): {failure}", sample.name, sample.query),
+            );
+        }
+    }
+    Check::new(
+        "queries",
+        CheckStatus::Ok,
+        format!("{} sample queries passed", SAMPLES.len()),
+    )
+}
+
+/// Checks the working directory tools resolve paths against, and the
+/// configured database, index and state files.
+fn check_files(config: &ServerConfig) -> Vec<Check> {
+    let mut checks = vec![match std::env::current_dir() {
+        Ok(dir) => match std::fs::read_dir(&dir) {
+            Ok(_) => Check::new(
+                "workspace",
+                CheckStatus::Ok,
+                format!(
+                    "paths resolve against {} unless the client sends roots",
+                    dir.display()
+                ),
+            ),
+            Err(e) => Check::new(
+                "workspace",
+                CheckStatus::Fail,
+                format!("cannot read {}: {e}", dir.display()),
+            ),
+        },
+        Err(e) => Check::new("workspace", CheckStatus::Fail, e.to_string()),
+    }];
+    #[cfg(feature = "db")]
+    checks.push(check_configured_file(
+        "database",
+        config.db_path.as_deref(),
+        "db_index creates it",
+    ));
+    #[cfg(feature = "index")]
+    checks.push(check_configured_file(
+        "index",
+        config.index_path.as_deref(),
+        "index_directory creates it",
+    ));
+    checks.push(check_configured_file(
+        "state",
+        config.state_path.as_deref(),
+        "it is created on start",
+    ));
+    checks
+}
+
+fn check_configured_file(name: &str, path: Option<&Path>, creation: &str) -> Check {
+    match path {
+        None => Check::new(name, CheckStatus::Skip, "not configured"),
+        Some(path) => check_file(name, path, creation),
+    }
+}
+
+fn check_file(name: &str, path: &Path, creation: &str) -> Check {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    if let Err(e) = check_writable(&dir) {
+        return Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("cannot write to {}: {e}", dir.display()),
+        );
+    }
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.permissions().readonly() => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("{} is read-only", path.display()),
+        ),
+        Ok(metadata) => Check::new(
+            name,
+            CheckStatus::Ok,
+            format!("{} ({} bytes)", path.display(), metadata.len()),
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Check::new(
+            name,
+            CheckStatus::Warn,
+            format!("{} doesn't exist yet; {creation}", path.display()),
+        ),
+        Err(e) => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("cannot read {}: {e}", path.display()),
+        ),
+    }
+}
+
+/// Creates and removes a file in `dir`.
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    // Not the process id, which wasm32-wasip1 doesn't have.
+    let probe = dir.join(format!(
+        ".mq-mcp-doctor-{}",
+        chrono::Utc::now().timestamp_micros()
+    ));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
+#[cfg(feature = "pandoc")]
+fn check_pandoc(path: &Path) -> Check {
+    match std::process::Command::new(path).arg("--version").output() {
+        Ok(output) if output.status.success() => Check::new(
+            "pandoc",
+            CheckStatus::Ok,
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        ),
+        Ok(output) => Check::new(
+            "pandoc",
+            CheckStatus::Fail,
+            format!("{} --version exited with {}", path.display(), output.status),
+        ),
+        Err(e) => Check::new(
+            "pandoc",
+            CheckStatus::Fail,
+            format!("cannot run {}: {e}", path.display()),
+        ),
+    }
+}
+
+/// The URLs of the integrations `config` enables, by check name.
+#[cfg(not(feature = "fetch"))]
+fn endpoints(_config: &ServerConfig) -> Vec<(String, String)> {
+    Vec::new()
+}
+
+/// The URLs of the integrations `config` enables, by check name.
+#[cfg(feature = "fetch")]
+fn endpoints(config: &ServerConfig) -> Vec<(String, String)> {
+    let mut endpoints = Vec::new();
+    if config.github.token.is_some() || config.github.api_url.is_some() {
+        endpoints.push((
+            "github".to_string(),
+            config
+                .github
+                .api_url
+                .clone()
+                .unwrap_or_else(|| github::DEFAULT_API_URL.to_string()),
+        ));
+    }
+    if let Some(base_url) = &config.confluence.base_url {
+        endpoints.push(("confluence".to_string(), base_url.clone()));
+    }
+    if config.notion.token.is_some() {
+        endpoints.push(("notion".to_string(), notion::API_URL.to_string()));
+    }
+    if config.gdoc.token.is_some() {
+        endpoints.push(("gdoc".to_string(), gdoc::DRIVE_API_URL.to_string()));
+    }
+    if config.slack.token.is_some() {
+        endpoints.push(("slack".to_string(), slack::API_URL.to_string()));
+    }
+    for host in &config.webhook.allowed_hosts {
+        endpoints.push((format!("webhook {host}"), format!("https://{host}")));
+    }
+    #[cfg(feature = "embeddings")]
+    if let Some(url) = &config.embeddings.url {
+        endpoints.push(("embeddings".to_string(), url.clone()));
+    }
+    #[cfg(feature = "s3")]
+    if let Some(bucket) = &config.s3.bucket
+        && let Ok(url) = config.s3.object_url(bucket, "")
+    {
+        endpoints.push(("s3".to_string(), url.to_string()));
+    }
+    endpoints
+}
+
+/// Checks that the host of `url` accepts a TCP connection.
+#[cfg(not(target_os = "wasi"))]
+async fn check_reachable(name: String, url: &str) -> Check {
+    let parsed = match url::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return Check::new(name, CheckStatus::Fail, format!("invalid URL {url}: {e}")),
+    };
+    let (Some(host), Some(port)) = (parsed.host_str(), parsed.port_or_known_default()) else {
+        return Check::new(name, CheckStatus::Fail, format!("no host in {url}"));
+    };
+    let started = Instant::now();
+    match tokio::time::timeout(
+        CONNECT_TIMEOUT,
+        tokio::net::TcpStream::connect((host, port)),
+    )
+    .await
+    {
+        Ok(Ok(_)) => Check::new(
+            name,
+            CheckStatus::Ok,
+            format!(
+                "{host}:{port} reachable in {} ms",
+                started.elapsed().as_millis()
+            ),
+        ),
+        Ok(Err(e)) => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("cannot connect to {host}:{port}: {e}"),
+        ),
+        Err(_) => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!(
+                "{host}:{port} didn't answer within {} s",
+                CONNECT_TIMEOUT.as_secs()
+            ),
+        ),
+    }
+}
+
+/// wasm32-wasip1 has no sockets to probe the host with.
+#[cfg(target_os = "wasi")]
+async fn check_reachable(name: String, url: &str) -> Check {
+    Check::new(
+        name,
+        CheckStatus::Skip,
+        format!("{url} not checked: no sockets on wasm32-wasip1"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_doctor_default_config() {
+        let report = doctor(ServerConfig::default()).await;
+        // pandoc may not be installed where the tests run.
+        assert!(
+            report
+                .checks
+                .iter()
+                .all(|check| check.name == "pandoc" || check.status != CheckStatus::Fail),
+            "{:?}",
+            report.checks
+        );
+        let status = |name: &str| {
+            report
+                .checks
+                .iter()
+                .find(|check| check.name == name)
+                .map(|check| check.status)
+        };
+        assert_eq!(status("engine"), Some(CheckStatus::Ok));
+        assert_eq!(status("features"), Some(CheckStatus::Ok));
+        assert_eq!(status("queries"), Some(CheckStatus::Ok));
+        assert_eq!(status("state"), Some(CheckStatus::Skip));
+        assert_eq!(status("integrations"), Some(CheckStatus::Skip));
+    }
+
+    #[test]
+    fn test_check_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.db");
+        assert_eq!(check_file("state", &path, "").status, CheckStatus::Warn);
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(check_file("state", &path, "").status, CheckStatus::Ok);
+        assert_eq!(
+            check_file("state", &dir.path().join("missing/state.db"), "").status,
+            CheckStatus::Fail
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Dry runs of mutating tools: with the reserved `dry_run` argument, a tool
+//! that writes files or changes session state (its `readOnlyHint` is false)
+//! reports what it would change without changing it.
+//!
+//! [`Server::call_tool`](rmcp::ServerHandler::call_tool) strips the argument
+//! before the arguments reach the tool and carries it in a task-local for
+//! the duration of the call; tools check [`active`].
+
+use std::{future::Future, sync::Arc};
+
+use rmcp::{
+    ErrorData,
+    model::{JsonObject, Tool},
+};
+
+/// Name of the per-call argument accepted by every mutating tool.
+pub(crate) const ARGUMENT: &str = "dry_run";
+
+tokio::task_local! {
+    static DRY_RUN: bool;
+}
+
+/// Removes the [`ARGUMENT`] from a call's arguments and returns whether it
+/// requests a dry run.
+pub(crate) fn take_argument(arguments: &mut Option<JsonObject>) -> Result<bool, ErrorData> {
+    match arguments
+        .as_mut()
+        .and_then(|arguments| arguments.remove(ARGUMENT))
+    {
+        None => Ok(false),
+        Some(serde_json::Value::Bool(dry_run)) => Ok(dry_run),
+        Some(value) => Err(ErrorData::invalid_params(
+            "Invalid `dry_run` argument",
+            Some(serde_json::json!({ "expected": "boolean", "got": value })),
+        )),
+    }
+}
+
+/// Whether `tool` writes files or changes session state.
+pub(super) fn mutates(tool: &Tool) -> bool {
+    tool.annotations
+        .as_ref()
+        .and_then(|annotations| annotations.read_only_hint)
+        == Some(false)
+}
+
+/// Advertises the [`ARGUMENT`] in the input schema of mutating tools.
+pub(crate) fn describe_argument(mut tool: Tool) -> Tool {
+    if !mutates(&tool) {
+        return tool;
+    }
+    let mut schema = (*tool.input_schema).clone();
+    if let Some(serde_json::Value::Object(properties)) = schema.get_mut("properties") {
+        properties.insert(
+            ARGUMENT.to_string(),
+            serde_json::json!({
+                "type": "boolean",
+                "description": "Report what the call would change without writing files or changing session state (default: false)",
+            }),
+        );
+    }
+    tool.input_schema = Arc::new(schema);
+    tool
+}
+
+/// Runs a tool call, as a dry run if `dry_run`.
+pub(crate) async fn scope<F: Future>(dry_run: bool, f: F) -> F::Output {
+    DRY_RUN.scope(dry_run, f).await
+}
+
+/// Whether the current tool call is a dry run.
+pub(super) fn active() -> bool {
+    DRY_RUN.try_with(|dry_run| *dry_run).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Server;
+
+    fn tool(name: &str) -> Tool {
+        Server::saved_queries_router().get(name).cloned().unwrap()
+    }
+
+    #[test]
+    fn test_take_argument() {
+        let mut arguments = serde_json::json!({"path": "docs", "dry_run": true})
+            .as_object()
+            .cloned();
+        assert!(take_argument(&mut arguments).unwrap());
+        assert!(!arguments.as_ref().unwrap().contains_key(ARGUMENT));
+        assert!(!take_argument(&mut arguments).unwrap());
+        let mut arguments = serde_json::json!({"dry_run": 1}).as_object().cloned();
+        assert!(take_argument(&mut arguments).is_err());
+    }
+
+    #[test]
+    fn test_describe_argument() {
+        let described = describe_argument(tool("save_query"));
+        assert!(described.input_schema["properties"].get(ARGUMENT).is_some());
+        let described = describe_argument(tool("list_saved_queries"));
+        assert!(described.input_schema["properties"].get(ARGUMENT).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scope() {
+        assert!(!active());
+        assert!(scope(true, async { active() }).await);
+        assert!(!scope(false, async { active() }).await);
+    }
+}
@@ -0,0 +1,295 @@
+//! `find_duplicates`: repeated and near-repeated paragraphs or sections
+//! across markdown documents.
+
+use std::collections::{HashMap, HashSet};
+
+use mq_markdown::Node;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{
+    McpResult, Server, inline_runs,
+    keywords::prose,
+    output::{json_result, output_schema},
+    parse_markdown_only, sections,
+};
+
+const DEFAULT_THRESHOLD: f64 = 0.8;
+const DEFAULT_MIN_WORDS: usize = 5;
+/// Words per shingle when comparing near-duplicates.
+const SHINGLE: usize = 3;
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DuplicateUnit {
+    /// Paragraphs, list items, table rows and blockquotes.
+    #[default]
+    Paragraph,
+    /// Headings with their body, up to the next heading.
+    Section,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct FindDuplicatesInput {
+    #[schemars(description = "The markdown documents to compare, referred to by index")]
+    pub documents: Vec<String>,
+    #[schemars(description = "`paragraph` (default) or `section`")]
+    pub unit: Option<DuplicateUnit>,
+    #[schemars(
+        description = "Minimum similarity, from 0 to 1, for two units to be reported as near-duplicates; 1 reports exact duplicates only (default: 0.8)"
+    )]
+    pub threshold: Option<f64>,
+    #[schemars(description = "Ignore units with fewer words than this (default: 5)")]
+    pub min_words: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct Occurrence {
+    #[schemars(description = "Index of the document in `documents`")]
+    pub document: usize,
+    #[schemars(description = "1-based line where the unit starts")]
+    pub line: usize,
+    #[schemars(description = "Text of the nearest heading before the unit")]
+    pub section: Option<String>,
+    #[schemars(description = "The unit as markdown")]
+    pub markdown: String,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct DuplicateGroup {
+    #[schemars(
+        description = "Whether the units are identical after normalizing case, punctuation and whitespace"
+    )]
+    pub exact: bool,
+    #[schemars(
+        description = "Jaccard similarity of the units' word trigrams, from 0 to 1; 1 for exact duplicates"
+    )]
+    pub similarity: f64,
+    pub occurrences: Vec<Occurrence>,
+}
+
+/// Output of `find_duplicates`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct DuplicatesOutput {
+    #[schemars(
+        description = "Exact duplicate groups first, then near-duplicate pairs, most similar first"
+    )]
+    pub duplicates: Vec<DuplicateGroup>,
+}
+
+/// A paragraph or section with its normalized words.
+struct Unit {
+    occurrence: Occurrence,
+    words: Vec<String>,
+}
+
+/// The prose blocks of a document: each paragraph, list item, table row
+/// and blockquote, with the nearest heading. Headings themselves are too
+/// short to be worth reporting.
+fn paragraphs(document: usize, markdown: &str) -> Result<Vec<Occurrence>, ErrorData> {
+    let nodes = parse_markdown_only(markdown)?;
+    let mut section = None;
+    let mut occurrences = Vec::new();
+    for run in inline_runs(&nodes) {
+        match run {
+            [Node::Heading(_)] => section = Some(run[0].value().trim().to_string()),
+            [
+                Node::Code(_)
+                | Node::Math(_)
+                | Node::Html(_)
+                | Node::Yaml(_)
+                | Node::Toml(_)
+                | Node::Definition(_)
+                | Node::HorizontalRule(_)
+                | Node::TableAlign(_),
+            ] => {}
+            _ => occurrences.push(Occurrence {
+                document,
+                line: run[0].position().map(|p| p.start.line).unwrap_or_default(),
+                section: section.clone(),
+                markdown: run
+                    .iter()
+                    .map(Node::to_string)
+                    .collect::<String>()
+                    .trim()
+                    .to_string(),
+            }),
+        }
+    }
+    Ok(occurrences)
+}
+
+fn units(documents: &[String], unit: DuplicateUnit) -> Result<Vec<Unit>, ErrorData> {
+    let mut units = Vec::new();
+    for (document, markdown) in documents.iter().enumerate() {
+        let occurrences = match unit {
+            DuplicateUnit::Paragraph => paragraphs(document, markdown)?,
+            DuplicateUnit::Section => sections::split(markdown)?
+                .into_iter()
+                .map(|section| Occurrence {
+                    document,
+                    line: section.line,
+                    section: section.title,
+                    markdown: section.markdown,
+                })
+                .collect(),
+        };
+        units.extend(occurrences.into_iter().map(|occurrence| {
+            Unit {
+                words: prose(&occurrence.markdown)
+                    .unicode_words()
+                    .map(str::to_lowercase)
+                    .collect(),
+                occurrence,
+            }
+        }));
+    }
+    Ok(units)
+}
+
+fn shingles(words: &[String]) -> HashSet<&[String]> {
+    words.windows(SHINGLE.min(words.len()).max(1)).collect()
+}
+
+fn jaccard(a: &HashSet<&[String]>, b: &HashSet<&[String]>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+fn find_duplicates(units: Vec<Unit>, threshold: f64, min_words: usize) -> Vec<DuplicateGroup> {
+    // Exact duplicates share their normalized word sequence.
+    let mut groups: HashMap<Vec<String>, Vec<Occurrence>> = HashMap::new();
+    let mut order = Vec::new();
+    for unit in units
+        .into_iter()
+        .filter(|unit| unit.words.len() >= min_words)
+    {
+        let occurrences = groups.entry(unit.words.clone()).or_default();
+        if occurrences.is_empty() {
+            order.push(unit.words);
+        }
+        occurrences.push(unit.occurrence);
+    }
+
+    let mut duplicates: Vec<DuplicateGroup> = Vec::new();
+    let mut near: Vec<DuplicateGroup> = Vec::new();
+    if threshold < 1.0 {
+        let shingled: Vec<HashSet<&[String]>> = order.iter().map(|words| shingles(words)).collect();
+        for (i, a) in shingled.iter().enumerate() {
+            for (j, b) in shingled.iter().enumerate().skip(i + 1) {
+                let similarity = jaccard(a, b);
+                if similarity >= threshold {
+                    near.push(DuplicateGroup {
+                        exact: false,
+                        similarity,
+                        occurrences: vec![
+                            groups[&order[i]][0].clone(),
+                            groups[&order[j]][0].clone(),
+                        ],
+                    });
+                }
+            }
+        }
+        near.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    }
+    for words in &order {
+        if let Some(occurrences) = groups.remove(words)
+            && occurrences.len() > 1
+        {
+            duplicates.push(DuplicateGroup {
+                exact: true,
+                similarity: 1.0,
+                occurrences,
+            });
+        }
+    }
+    duplicates.extend(near);
+    duplicates
+}
+
+#[tool_router(router = duplicates_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Find duplicated and near-duplicated paragraphs or sections within and across markdown documents. Units are compared after normalizing case, punctuation and whitespace; near-duplicates are scored by the overlap of their word trigrams. Each finding lists the document index, line and nearest heading of every occurrence.",
+        output_schema = output_schema::<DuplicatesOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn find_duplicates(&self, Parameters(input): Parameters<FindDuplicatesInput>) -> McpResult {
+        let units = units(&input.documents, input.unit.unwrap_or_default())?;
+        json_result(&DuplicatesOutput {
+            duplicates: find_duplicates(
+                units,
+                input.threshold.unwrap_or(DEFAULT_THRESHOLD),
+                input.min_words.unwrap_or(DEFAULT_MIN_WORDS),
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn documents(documents: &[&str]) -> Vec<String> {
+        documents.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn test_exact_duplicates_across_documents() {
+        let documents = documents(&[
+            "# Install\n\nRun the installer and restart your shell.\n\nShort.",
+            "# Setup\n\nRun the **installer**, and restart your shell!\n\nShort.",
+        ]);
+        let units = units(&documents, DuplicateUnit::Paragraph).unwrap();
+        let duplicates = find_duplicates(units, 1.0, 5);
+        assert_eq!(duplicates.len(), 1);
+        assert!(duplicates[0].exact);
+        let occurrences: Vec<_> = duplicates[0]
+            .occurrences
+            .iter()
+            .map(|o| (o.document, o.line, o.section.as_deref()))
+            .collect();
+        assert_eq!(
+            occurrences,
+            vec![(0, 3, Some("Install")), (1, 3, Some("Setup"))]
+        );
+    }
+
+    #[test]
+    fn test_near_duplicates() {
+        let documents = documents(&[
+            "The server reads its configuration from the config file at startup time.",
+            "The server reads its configuration from the config file at boot time.",
+            "Something else entirely is described in this paragraph here.",
+        ]);
+        let units = units(&documents, DuplicateUnit::Paragraph).unwrap();
+        let duplicates = find_duplicates(units, 0.6, 5);
+        assert_eq!(duplicates.len(), 1);
+        assert!(!duplicates[0].exact);
+        assert!(duplicates[0].similarity >= 0.6 && duplicates[0].similarity < 1.0);
+        let documents: Vec<_> = duplicates[0]
+            .occurrences
+            .iter()
+            .map(|o| o.document)
+            .collect();
+        assert_eq!(documents, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_section_units() {
+        let section = "## FAQ\n\nAsk questions in the discussion forum, not in issues.";
+        let documents = documents(&[&format!("# A\n\n{section}"), section]);
+        let units = units(&documents, DuplicateUnit::Section).unwrap();
+        let duplicates = find_duplicates(units, 1.0, 5);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].occurrences[0].line, 3);
+        assert_eq!(duplicates[0].occurrences[0].markdown, section);
+    }
+}
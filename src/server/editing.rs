@@ -0,0 +1,370 @@
+//! `edit_section`: edits a document by section id rather than by position.
+//! Ids come from [`sections`](super::sections) and depend only on heading
+//! paths, so an agent can make several edits in a row with the ids it was
+//! given before the first one, even though each edit moves the lines after
+//! it.
+//!
+//! A section runs from its heading to the next heading of the same or a
+//! higher level, so it includes its subsections; the text before the first
+//! heading is a section of its own.
+
+use std::ops::Range;
+
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    output::{json_result, output_schema},
+    sections::{self, Section},
+    source_map::{Rewrite, RewriteOptions, RewriteOutput},
+};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, rmcp::serde::Deserialize, Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SectionAction {
+    /// Replace the section, heading and subsections included, with `text`.
+    Replace,
+    /// Keep the heading and replace everything under it with `text`.
+    ReplaceBody,
+    /// Add `text` at the end of the section's own body, before its
+    /// subsections.
+    Append,
+    /// Add `text` before the section's heading.
+    InsertBefore,
+    /// Add `text` after the section and its subsections.
+    InsertAfter,
+    /// Remove the section and its subsections.
+    Delete,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct EditSectionInput {
+    #[schemars(description = "The markdown document to edit")]
+    pub markdown: String,
+    #[schemars(
+        description = "Id of the section to edit (`s-...`), as returned by outline, rank_sections, semantic_search or an earlier edit_section"
+    )]
+    pub id: String,
+    #[schemars(
+        description = "`replace`, `replace_body`, `append`, `insert_before`, `insert_after` or `delete`"
+    )]
+    pub action: SectionAction,
+    #[schemars(description = "The markdown to put in; required except for `delete`")]
+    pub text: Option<String>,
+    #[serde(flatten)]
+    pub rewrite: RewriteOptions,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct SectionRef {
+    pub id: String,
+    #[schemars(description = "Heading text; null for the text before the first heading")]
+    pub title: Option<String>,
+    #[schemars(description = "1-based line of the heading")]
+    pub line: usize,
+}
+
+impl From<Section> for SectionRef {
+    fn from(section: Section) -> Self {
+        Self {
+            id: section.id,
+            title: section.title,
+            line: section.line,
+        }
+    }
+}
+
+/// Output of `edit_section`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct EditSectionOutput {
+    /// The edited document.
+    #[serde(flatten)]
+    pub rewrite: RewriteOutput,
+    #[schemars(description = "The sections of the edited document, to target further edits")]
+    pub sections: Vec<SectionRef>,
+}
+
+/// Byte offsets of the start of each line of `markdown`, and of its end.
+fn line_starts(markdown: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(markdown.match_indices('\n').map(|(i, _)| i + 1))
+        .filter(|&start| start < markdown.len())
+        .chain(std::iter::once(markdown.len()))
+        .collect()
+}
+
+/// `text` as a block placed after `before`, separated from what precedes
+/// and, unless `at_end`, from what follows by a blank line.
+fn block(before: &str, at_end: bool, text: &str) -> String {
+    let text = text.trim_matches('\n');
+    if text.is_empty() {
+        return String::new();
+    }
+    let separator = match before {
+        "" => "",
+        _ if before.ends_with("\n\n") => "",
+        _ if before.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+    let end = if at_end { "\n" } else { "\n\n" };
+    format!("{separator}{text}{end}")
+}
+
+/// The byte ranges of the section `index` of `sections`: the whole section
+/// with its subsections, its body after the heading, and its own body
+/// before the first subsection.
+fn ranges(
+    markdown: &str,
+    sections: &[Section],
+    index: usize,
+) -> (Range<usize>, Range<usize>, Range<usize>) {
+    let starts = line_starts(markdown);
+    let offset = |line: usize| starts[(line - 1).min(starts.len() - 1)];
+    let section = &sections[index];
+    let next = |own: bool| {
+        sections[index + 1..]
+            .iter()
+            .find(|next| own || section.title.is_none() || next.depth <= section.depth)
+            .map_or(markdown.len(), |next| offset(next.line))
+    };
+    let start = offset(section.line);
+    let heading_lines = match &section.title {
+        None => 0,
+        Some(_) if section.markdown.trim_start().starts_with('#') => 1,
+        // A setext heading and its underline.
+        Some(_) => 2,
+    };
+    let body = offset(section.line + heading_lines).max(start);
+    let (end, own_end) = (next(false), next(true));
+    (start..end, body.min(end)..end, body.min(own_end)..own_end)
+}
+
+fn edit_section(input: &EditSectionInput) -> Result<EditSectionOutput, ErrorData> {
+    let markdown = input.markdown.as_str();
+    let sections = sections::split(markdown)?;
+    let Some(index) = sections.iter().position(|section| section.id == input.id) else {
+        return Err(ErrorData::invalid_params(
+            "Section not found",
+            Some(serde_json::json!({
+                "id": input.id,
+                "sections": sections.into_iter().map(SectionRef::from).collect::<Vec<_>>(),
+            })),
+        ));
+    };
+    let text = match (input.action, input.text.as_deref()) {
+        (SectionAction::Delete, _) => "",
+        (_, Some(text)) => text,
+        (_, None) => {
+            return Err(ErrorData::invalid_params(
+                "`text` is required for this action",
+                Some(serde_json::json!({ "action": input.action })),
+            ));
+        }
+    };
+
+    let (section, body, own_body) = ranges(markdown, &sections, index);
+    let at_end = |range: &Range<usize>| range.end == markdown.len();
+    let (mut range, replacement) = match input.action {
+        SectionAction::Replace | SectionAction::Delete => (
+            section.clone(),
+            block(&markdown[..section.start], at_end(&section), text),
+        ),
+        SectionAction::ReplaceBody => (
+            body.clone(),
+            block(&markdown[..body.start], at_end(&body), text),
+        ),
+        SectionAction::Append => (
+            own_body.end..own_body.end,
+            block(&markdown[..own_body.end], at_end(&own_body), text),
+        ),
+        SectionAction::InsertBefore => (
+            section.start..section.start,
+            block(&markdown[..section.start], false, text),
+        ),
+        SectionAction::InsertAfter => (
+            section.end..section.end,
+            block(&markdown[..section.end], at_end(&section), text),
+        ),
+    };
+    if replacement.is_empty() && at_end(&range) && !range.is_empty() {
+        // Removing the end of the document also removes the blank lines
+        // before it.
+        let kept = markdown[..range.start].trim_end_matches('\n').len();
+        range.start = (kept + 1).min(range.start);
+    }
+    let mut rewrite = Rewrite::new(markdown);
+    rewrite.replace(range, &replacement);
+    let rewritten = rewrite.finish();
+    let sections = sections::split(&rewritten.output)?
+        .into_iter()
+        .map(SectionRef::from)
+        .collect();
+    Ok(EditSectionOutput {
+        rewrite: rewritten.render(&input.rewrite),
+        sections,
+    })
+}
+
+#[tool_router(router = editing_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Edit a markdown document by section id instead of by line: replace a section, replace or append to its body, insert before or after it, or delete it. Ids come from outline, rank_sections, semantic_search or an earlier edit_section, and stay valid while other parts of the document change. Returns the edited document, or a diff or edit list, and the ids of its sections.",
+        output_schema = output_schema::<EditSectionOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn edit_section(&self, Parameters(input): Parameters<EditSectionInput>) -> McpResult {
+        json_result(&edit_section(&input)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const MARKDOWN: &str = "\
+Intro.
+
+# Guide
+
+Read this.
+
+## Install
+
+Run it.
+
+## Usage
+
+Use it.
+
+# FAQ
+
+None yet.
+";
+
+    fn edit(title: Option<&str>, action: SectionAction, text: Option<&str>) -> String {
+        let id = sections::split(MARKDOWN)
+            .unwrap()
+            .into_iter()
+            .find(|section| section.title.as_deref() == title)
+            .unwrap()
+            .id;
+        edit_section(&EditSectionInput {
+            markdown: MARKDOWN.to_string(),
+            id,
+            action,
+            text: text.map(str::to_string),
+            rewrite: RewriteOptions::default(),
+        })
+        .unwrap()
+        .rewrite
+        .markdown
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case(
+        Some("Install"),
+        SectionAction::Replace,
+        "## Setup\n\nBuild it.",
+        "Intro.\n\n# Guide\n\nRead this.\n\n## Setup\n\nBuild it.\n\n## Usage\n\nUse it.\n\n# FAQ\n\nNone yet.\n"
+    )]
+    #[case(
+        Some("Guide"),
+        SectionAction::ReplaceBody,
+        "Gone.",
+        "Intro.\n\n# Guide\n\nGone.\n\n# FAQ\n\nNone yet.\n"
+    )]
+    #[case(
+        Some("Guide"),
+        SectionAction::Append,
+        "More.",
+        "Intro.\n\n# Guide\n\nRead this.\n\nMore.\n\n## Install\n\nRun it.\n\n## Usage\n\nUse it.\n\n# FAQ\n\nNone yet.\n"
+    )]
+    #[case(
+        Some("FAQ"),
+        SectionAction::Append,
+        "Ask.",
+        "Intro.\n\n# Guide\n\nRead this.\n\n## Install\n\nRun it.\n\n## Usage\n\nUse it.\n\n# FAQ\n\nNone yet.\n\nAsk.\n"
+    )]
+    #[case(
+        Some("Usage"),
+        SectionAction::InsertBefore,
+        "## Configure\n\nSet it.",
+        "Intro.\n\n# Guide\n\nRead this.\n\n## Install\n\nRun it.\n\n## Configure\n\nSet it.\n\n## Usage\n\nUse it.\n\n# FAQ\n\nNone yet.\n"
+    )]
+    #[case(
+        Some("Guide"),
+        SectionAction::InsertAfter,
+        "# API\n\nCall it.",
+        "Intro.\n\n# Guide\n\nRead this.\n\n## Install\n\nRun it.\n\n## Usage\n\nUse it.\n\n# API\n\nCall it.\n\n# FAQ\n\nNone yet.\n"
+    )]
+    #[case(
+        Some("FAQ"),
+        SectionAction::InsertAfter,
+        "# API",
+        "Intro.\n\n# Guide\n\nRead this.\n\n## Install\n\nRun it.\n\n## Usage\n\nUse it.\n\n# FAQ\n\nNone yet.\n\n# API\n"
+    )]
+    #[case(
+        Some("Guide"),
+        SectionAction::Delete,
+        "",
+        "Intro.\n\n# FAQ\n\nNone yet.\n"
+    )]
+    #[case(
+        None,
+        SectionAction::Replace,
+        "New intro.",
+        "New intro.\n\n# Guide\n\nRead this.\n\n## Install\n\nRun it.\n\n## Usage\n\nUse it.\n\n# FAQ\n\nNone yet.\n"
+    )]
+    fn test_edit_section(
+        #[case] title: Option<&str>,
+        #[case] action: SectionAction,
+        #[case] text: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(edit(title, action, Some(text)), expected);
+    }
+
+    #[test]
+    fn test_ids_stay_valid_across_edits() {
+        let sections = sections::split(MARKDOWN).unwrap();
+        let (install, faq) = (sections[2].id.clone(), sections[4].id.clone());
+        let first = edit_section(&EditSectionInput {
+            markdown: MARKDOWN.to_string(),
+            id: install,
+            action: SectionAction::Append,
+            text: Some("Then check.".to_string()),
+            rewrite: RewriteOptions::default(),
+        })
+        .unwrap();
+        assert!(first.sections.iter().any(|section| section.id == faq));
+        let second = edit_section(&EditSectionInput {
+            markdown: first.rewrite.markdown.unwrap(),
+            id: faq,
+            action: SectionAction::Delete,
+            text: None,
+            rewrite: RewriteOptions::default(),
+        })
+        .unwrap();
+        assert!(second.rewrite.markdown.unwrap().ends_with("Use it.\n"));
+    }
+
+    #[test]
+    fn test_unknown_section() {
+        let error = edit_section(&EditSectionInput {
+            markdown: MARKDOWN.to_string(),
+            id: "s-000000000000".to_string(),
+            action: SectionAction::Delete,
+            text: None,
+            rewrite: RewriteOptions::default(),
+        })
+        .unwrap_err();
+        assert_eq!(error.message, "Section not found");
+        assert_eq!(error.data.unwrap()["sections"][1]["title"], "Guide");
+    }
+}
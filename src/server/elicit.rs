@@ -0,0 +1,115 @@
+//! Asking the user to resolve ambiguous tool input through MCP elicitation
+//! (`elicitation/create`), for clients that advertise the capability.
+
+use rmcp::{
+    ErrorData, RoleServer,
+    model::{ElicitRequestParams, ElicitationAction, ElicitationSchema, JsonObject},
+    service::{ElicitationMode, Peer},
+};
+
+/// Whether the client can answer form elicitation requests.
+pub(crate) fn supports_elicitation(peer: &Peer<RoleServer>) -> bool {
+    peer.supported_elicitation_modes()
+        .contains(&ElicitationMode::Form)
+}
+
+/// `options` numbered from 1 (`2. Examples`), so that repeated options,
+/// such as two sections of the same title, stay distinct choices.
+fn numbered(options: &[String]) -> Vec<String> {
+    options
+        .iter()
+        .enumerate()
+        .map(|(i, option)| format!("{}. {option}", i + 1))
+        .collect()
+}
+
+/// A form with a single required string `field` restricted to `options`.
+fn choice_schema(field: &str, options: &[String]) -> JsonObject {
+    let schema = serde_json::json!({
+        "type": "object",
+        "properties": {
+            field: { "type": "string", "enum": options },
+        },
+        "required": [field],
+    });
+    match schema {
+        serde_json::Value::Object(object) => object,
+        _ => unreachable!(),
+    }
+}
+
+/// Asks the user to pick one of `options`, shown numbered. Returns the
+/// index of the chosen option, or `None` if the client can't elicit or the
+/// user declined.
+pub(crate) async fn choose(
+    peer: &Peer<RoleServer>,
+    message: impl Into<String>,
+    field: &str,
+    options: &[String],
+) -> Result<Option<usize>, ErrorData> {
+    if !supports_elicitation(peer) {
+        return Ok(None);
+    }
+    let options = numbered(options);
+    let requested_schema = ElicitationSchema::from_json_schema(choice_schema(field, &options))
+        .map_err(|e| {
+            ErrorData::internal_error(
+                "Failed to build elicitation schema",
+                Some(serde_json::Value::String(e.to_string())),
+            )
+        })?;
+    let result = peer
+        .create_elicitation(ElicitRequestParams::FormElicitationParams {
+            meta: None,
+            message: message.into(),
+            requested_schema,
+        })
+        .await
+        .map_err(|e| {
+            ErrorData::internal_error(
+                "Elicitation request failed",
+                Some(serde_json::Value::String(e.to_string())),
+            )
+        })?;
+    if result.action != ElicitationAction::Accept {
+        return Ok(None);
+    }
+    Ok(result
+        .content
+        .as_ref()
+        .and_then(|content| content.get(field))
+        .and_then(|value| value.as_str())
+        .and_then(|chosen| options.iter().position(|option| option == chosen)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choice_schema_is_a_valid_elicitation_schema() {
+        let options = vec!["Install".to_string(), "Install on Windows".to_string()];
+        let schema = choice_schema("section", &options);
+        assert_eq!(
+            schema["properties"]["section"]["enum"][1],
+            "Install on Windows"
+        );
+        assert_eq!(schema["required"][0], "section");
+        assert!(ElicitationSchema::from_json_schema(schema).is_ok());
+    }
+
+    #[test]
+    fn test_numbered_options_are_unique() {
+        let options = vec![
+            "Examples".to_string(),
+            "Usage".to_string(),
+            "Examples".to_string(),
+        ];
+        let numbered = numbered(&options);
+        assert_eq!(numbered, vec!["1. Examples", "2. Usage", "3. Examples"]);
+        assert_eq!(
+            numbered.iter().position(|option| option == "3. Examples"),
+            Some(2)
+        );
+    }
+}
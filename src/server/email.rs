@@ -0,0 +1,464 @@
+//! `email_to_markdown`: RFC 822 / MIME messages (`.eml`) converted to
+//! markdown, with the main headers as front matter.
+
+use std::sync::LazyLock;
+
+use base64::Engine;
+use encoding_rs::{Encoding, UTF_8};
+use regex::{Captures, Regex};
+use rmcp::{handler::server::wrapper::Parameters, schemars, tool, tool_router};
+
+use super::{
+    McpResult, Server, convert_html,
+    output::{QueryOutput, output_schema},
+    sanitize::SanitizeOptions,
+};
+
+/// Nested multiparts deeper than this are ignored.
+const MAX_DEPTH: usize = 8;
+/// Headers copied into the front matter, with their keys.
+const FRONT_MATTER_HEADERS: &[(&str, &str)] = &[
+    ("subject", "Subject"),
+    ("from", "From"),
+    ("to", "To"),
+    ("cc", "Cc"),
+    ("date", "Date"),
+    ("message_id", "Message-ID"),
+    ("in_reply_to", "In-Reply-To"),
+];
+
+static ENCODED_WORD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"=\?([^?\s]+)\?([bBqQ])\?([^?\s]*)\?=").unwrap());
+static BETWEEN_ENCODED_WORDS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\?=)\s+(=\?)").unwrap());
+static ATTRIBUTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^on\b.*\bwrote:\s*$").unwrap());
+static ORIGINAL_MESSAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^(?:-+\s*original message\s*-+|_{10,})$").unwrap());
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BodyPreference {
+    /// The HTML body, converted to markdown; falls back to plain text.
+    #[default]
+    Html,
+    /// The plain-text body; falls back to HTML.
+    Text,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct EmailToMarkdownInput {
+    #[schemars(description = "The raw message (RFC 822 / .eml), headers included")]
+    pub eml: String,
+    #[schemars(
+        description = "The mq query to execute (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+    #[schemars(
+        description = "Which body of a multipart/alternative message to use: `html` (default) or `text`"
+    )]
+    pub prefer: Option<BodyPreference>,
+    #[schemars(
+        description = "Remove quoted replies: quoted lines, \"On ... wrote:\" attributions and everything after an \"Original Message\" separator"
+    )]
+    pub strip_quotes: Option<bool>,
+    #[serde(flatten)]
+    pub sanitize: SanitizeOptions,
+}
+
+#[derive(Debug)]
+struct Attachment {
+    filename: String,
+    content_type: String,
+    size: usize,
+}
+
+/// The parts of a message that end up in the markdown.
+#[derive(Debug, Default)]
+struct Email {
+    headers: Vec<(String, String)>,
+    text: Option<String>,
+    html: Option<String>,
+    attachments: Vec<Attachment>,
+}
+
+/// Splits a message or part into unfolded headers and the body.
+fn split_headers(raw: &[u8]) -> (Vec<(String, String)>, &[u8]) {
+    let separator = find(raw, b"\r\n\r\n")
+        .map(|at| (at, 4))
+        .or_else(|| find(raw, b"\n\n").map(|at| (at, 2)));
+    let (head, body) = match separator {
+        Some((at, len)) => (&raw[..at], &raw[at + len..]),
+        None => (raw, &raw[raw.len()..]),
+    };
+    let head = String::from_utf8_lossy(head);
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in head.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    (headers, body)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Splits a structured header such as `Content-Type` into its lowercased
+/// value and its parameters.
+fn header_params(value: &str) -> (String, Vec<(String, String)>) {
+    let mut parts = value.split(';');
+    let main = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let params = parts
+        .filter_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            Some((
+                name.trim().to_ascii_lowercase(),
+                value.trim().trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+    (main, params)
+}
+
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn decode_charset(bytes: &[u8], charset: Option<&str>) -> String {
+    let encoding = charset
+        .and_then(|charset| Encoding::for_label(charset.as_bytes()))
+        .unwrap_or(UTF_8);
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+fn quoted_printable(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'=' if bytes[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if bytes[i + 1..].starts_with(b"\n") => i += 2,
+            b'=' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(b'=');
+                        i += 1;
+                    }
+                }
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Decodes RFC 2047 encoded words (`=?UTF-8?B?...?=`) in a header value.
+fn decode_words(value: &str) -> String {
+    let value = BETWEEN_ENCODED_WORDS.replace_all(value, "$1$2");
+    ENCODED_WORD
+        .replace_all(&value, |caps: &Captures| {
+            let bytes = match &caps[2] {
+                "B" | "b" => base64::engine::general_purpose::STANDARD
+                    .decode(&caps[3])
+                    .unwrap_or_default(),
+                _ => quoted_printable(caps[3].replace('_', " ").as_bytes()),
+            };
+            decode_charset(&bytes, Some(&caps[1]))
+        })
+        .into_owned()
+}
+
+/// The body of a part with its `Content-Transfer-Encoding` undone.
+fn transfer_decode(headers: &[(String, String)], body: &[u8]) -> Vec<u8> {
+    let encoding = header(headers, "Content-Transfer-Encoding")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match encoding.as_str() {
+        "base64" => {
+            let compact: Vec<u8> = body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(compact)
+                .unwrap_or_default()
+        }
+        "quoted-printable" => quoted_printable(body),
+        _ => body.to_vec(),
+    }
+}
+
+/// Splits a multipart body on `boundary`, dropping the preamble and
+/// epilogue.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut offset = 0;
+    for line in body.split_inclusive(|b| *b == b'\n') {
+        let trimmed = line.trim_ascii_end();
+        if trimmed.starts_with(delimiter.as_bytes()) {
+            let rest = &trimmed[delimiter.len()..];
+            if rest.is_empty() || rest == b"--" {
+                if let Some(start) = start {
+                    // The line break before a delimiter belongs to it.
+                    let mut end = offset;
+                    if body[..end].ends_with(b"\n") {
+                        end -= 1;
+                    }
+                    if body[..end].ends_with(b"\r") {
+                        end -= 1;
+                    }
+                    parts.push(&body[start..end.max(start)]);
+                }
+                if rest == b"--" {
+                    return parts;
+                }
+                start = Some(offset + line.len());
+            }
+        }
+        offset += line.len();
+    }
+    if let Some(start) = start {
+        parts.push(&body[start..]);
+    }
+    parts
+}
+
+fn walk(headers: &[(String, String)], body: &[u8], email: &mut Email, depth: usize) {
+    let (mime, params) = header_params(header(headers, "Content-Type").unwrap_or("text/plain"));
+    let (disposition, disposition_params) =
+        header_params(header(headers, "Content-Disposition").unwrap_or_default());
+    let filename = param(&disposition_params, "filename").or(param(&params, "name"));
+
+    if mime.starts_with("multipart/") {
+        if depth >= MAX_DEPTH {
+            return;
+        }
+        let Some(boundary) = param(&params, "boundary") else {
+            return;
+        };
+        for part in split_multipart(body, boundary) {
+            let (part_headers, part_body) = split_headers(part);
+            walk(&part_headers, part_body, email, depth + 1);
+        }
+        return;
+    }
+
+    let decoded = transfer_decode(headers, body);
+    let slot = match mime.as_str() {
+        "text/plain" => Some(&mut email.text),
+        "text/html" => Some(&mut email.html),
+        _ => None,
+    };
+    match slot {
+        Some(slot) if disposition != "attachment" && slot.is_none() => {
+            *slot = Some(decode_charset(&decoded, param(&params, "charset")));
+        }
+        _ if disposition == "attachment" || filename.is_some() => {
+            email.attachments.push(Attachment {
+                filename: filename
+                    .map(decode_words)
+                    .unwrap_or_else(|| "(unnamed)".to_string()),
+                content_type: mime,
+                size: decoded.len(),
+            })
+        }
+        _ => {}
+    }
+}
+
+fn parse_email(raw: &[u8]) -> Email {
+    let (headers, body) = split_headers(raw);
+    let mut email = Email::default();
+    walk(&headers, body, &mut email, 0);
+    email.headers = headers;
+    email
+}
+
+/// Removes quoted lines, reply attributions and forwarded originals.
+fn strip_quoted_replies(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if ORIGINAL_MESSAGE.is_match(trimmed) {
+            break;
+        }
+        if trimmed.starts_with('>') || ATTRIBUTION.is_match(trimmed) {
+            continue;
+        }
+        lines.push(line);
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+fn front_matter(headers: &[(String, String)]) -> String {
+    let mut lines = vec!["---".to_string()];
+    for (key, name) in FRONT_MATTER_HEADERS {
+        if let Some(value) = header(headers, name) {
+            // A JSON string is a valid YAML scalar.
+            let value = serde_json::Value::String(decode_words(value));
+            lines.push(format!("{key}: {value}"));
+        }
+    }
+    lines.push("---".to_string());
+    lines.join("\n")
+}
+
+#[tool_router(router = email_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Convert an email (RFC 822 / .eml, including multipart MIME) to markdown and run an mq query on it. The subject, sender, recipients and date become YAML front matter, the HTML or plain-text body becomes markdown, and attachments are listed at the end. Quoted replies can be stripped.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn email_to_markdown(&self, Parameters(input): Parameters<EmailToMarkdownInput>) -> McpResult {
+        let email = parse_email(input.eml.as_bytes());
+        let html = || {
+            email.html.as_deref().map(|html| {
+                convert_html(html, &input.sanitize)
+                    .map(|nodes| mq_markdown::Markdown::new(nodes).to_string())
+            })
+        };
+        let text = || email.text.clone().map(Ok);
+        let body = match input.prefer.unwrap_or_default() {
+            BodyPreference::Html => html().or_else(text),
+            BodyPreference::Text => text().or_else(html),
+        }
+        .transpose()?
+        .unwrap_or_default();
+        let body = if input.strip_quotes.unwrap_or_default() {
+            strip_quoted_replies(&body)
+        } else {
+            body.trim_end().to_string()
+        };
+
+        let mut markdown = front_matter(&email.headers);
+        if !body.is_empty() {
+            markdown.push_str("\n\n");
+            markdown.push_str(&body);
+        }
+        if !email.attachments.is_empty() {
+            markdown.push_str("\n\n## Attachments\n\n");
+            let list: Vec<String> = email
+                .attachments
+                .iter()
+                .map(|a| format!("- {} ({}, {} bytes)", a.filename, a.content_type, a.size))
+                .collect();
+            markdown.push_str(&list.join("\n"));
+        }
+        self.eval_query(&markdown, input.query.as_deref().unwrap_or("identity()"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const MULTIPART: &str = "\
+From: =?UTF-8?B?5bGx55Sw?= <yamada@example.com>\r
+To: team@example.com\r
+Subject: =?UTF-8?Q?Caf=C3=A9_plans?=\r
+ for Friday\r
+Content-Type: multipart/mixed; boundary=\"outer\"\r
+\r
+preamble\r
+--outer\r
+Content-Type: multipart/alternative; boundary=inner\r
+\r
+--inner\r
+Content-Type: text/plain; charset=utf-8\r
+Content-Transfer-Encoding: quoted-printable\r
+\r
+Caf=C3=A9 at noon.=\r
+\r
+--inner\r
+Content-Type: text/html; charset=utf-8\r
+Content-Transfer-Encoding: base64\r
+\r
+PHA+PGI+Q2Fmw6k8L2I+IGF0IG5vb24uPC9wPg==\r
+--inner--\r
+--outer\r
+Content-Type: application/pdf; name=\"menu.pdf\"\r
+Content-Disposition: attachment; filename=\"menu.pdf\"\r
+Content-Transfer-Encoding: base64\r
+\r
+JVBERi0=\r
+--outer--\r
+";
+
+    #[test]
+    fn test_parse_multipart_email() {
+        let email = parse_email(MULTIPART.as_bytes());
+        assert_eq!(email.text.as_deref(), Some("Café at noon."));
+        assert_eq!(email.html.as_deref(), Some("<p><b>Café</b> at noon.</p>"));
+        assert_eq!(email.attachments.len(), 1);
+        assert_eq!(email.attachments[0].filename, "menu.pdf");
+        assert_eq!(email.attachments[0].size, 5);
+        assert_eq!(
+            front_matter(&email.headers),
+            "---\nsubject: \"Café plans for Friday\"\nfrom: \"山田 <yamada@example.com>\"\nto: \"team@example.com\"\n---"
+        );
+    }
+
+    #[rstest]
+    #[case(b"a=3Db=\r\nc".as_slice(), b"a=bc".as_slice())]
+    #[case(b"x=\ny =E2=9C=93".as_slice(), "xy ✓".as_bytes())]
+    #[case(b"trailing =".as_slice(), b"trailing =".as_slice())]
+    fn test_quoted_printable(#[case] input: &[u8], #[case] expected: &[u8]) {
+        assert_eq!(quoted_printable(input), expected);
+    }
+
+    #[test]
+    fn test_strip_quoted_replies() {
+        let markdown = "\
+Sounds good, see you then.
+
+On Tue, 3 Mar 2026 at 10:00, Alice <alice@example.com> wrote:
+> Lunch on Friday?
+> - Alice
+
+Best,
+Bob
+
+-----Original Message-----
+From: Carol";
+        assert_eq!(
+            strip_quoted_replies(markdown),
+            "Sounds good, see you then.\n\n\nBest,\nBob"
+        );
+    }
+}
@@ -0,0 +1,499 @@
+//! `embed_sections` and `semantic_search`: embedding-based retrieval over
+//! the sections of documents loaded into a session. Embeddings come from
+//! an OpenAI-compatible `/embeddings` endpoint, which local model servers
+//! (Ollama, llama.cpp, text-embeddings-inference) also provide. Only built
+//! with the `embeddings` feature.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use serde_json::Value;
+
+use super::{
+    McpResult, Server,
+    dedup::{Seen, SharedSource, content_hash},
+    dry_run,
+    fetch::parse_url,
+    keywords::prose,
+    output::{json_result, output_schema},
+    sections,
+};
+
+const DEFAULT_LIMIT: usize = 5;
+/// Inputs sent per embeddings request.
+const BATCH_SIZE: usize = 64;
+/// Characters of a section that are embedded; the rest is cut, as most
+/// embedding models have a context of a few thousand tokens.
+const MAX_INPUT_CHARS: usize = 8000;
+
+/// Server-side settings for the embedding tools.
+#[derive(Clone, Default)]
+pub struct EmbeddingsConfig {
+    /// The OpenAI-compatible embeddings endpoint, e.g.
+    /// `http://localhost:11434/v1/embeddings`. `None` disables the tools.
+    pub url: Option<String>,
+    /// Model name sent with each request; omitted when `None`, for servers
+    /// that serve a single model.
+    pub model: Option<String>,
+    /// Key sent as `Authorization: Bearer`.
+    pub api_key: Option<String>,
+}
+
+impl fmt::Debug for EmbeddingsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmbeddingsConfig")
+            .field("url", &self.url)
+            .field("model", &self.model)
+            .field("api_key", &self.api_key.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl EmbeddingsConfig {
+    fn endpoint(&self) -> Result<(url::Url, HeaderMap), ErrorData> {
+        let Some(url) = &self.url else {
+            return Err(ErrorData::invalid_request(
+                "no embeddings endpoint configured — restart mq-mcp with --embeddings-url <url> to enable embed_sections and semantic_search",
+                None,
+            ));
+        };
+        let mut headers = HeaderMap::new();
+        if let Some(key) = &self.api_key {
+            let mut value = HeaderValue::from_str(&format!("Bearer {key}")).map_err(|_| {
+                ErrorData::internal_error(
+                    "Invalid embeddings API key in the server configuration",
+                    None,
+                )
+            })?;
+            value.set_sensitive(true);
+            headers.insert(header::AUTHORIZATION, value);
+        }
+        Ok((parse_url(url)?, headers))
+    }
+}
+
+/// A section with its unit-length embedding.
+#[derive(Debug)]
+struct Embedded {
+    document: String,
+    id: String,
+    title: Option<String>,
+    line: usize,
+    markdown: String,
+    vector: Vec<f32>,
+}
+
+/// Sections embedded by one session.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EmbeddingStore(Arc<Mutex<Vec<Embedded>>>);
+
+impl EmbeddingStore {
+    /// Replaces the sections of `document` and returns the number of
+    /// sections stored in the session.
+    fn replace(&self, document: &str, sections: Vec<Embedded>) -> usize {
+        let mut store = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        store.retain(|embedded| embedded.document != document);
+        store.extend(sections);
+        store.len()
+    }
+
+    /// The number of sections [`Self::replace`] would leave in the session
+    /// when replacing `document` with `sections` sections.
+    fn len_after(&self, document: &str, sections: usize) -> usize {
+        let store = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        store.iter().filter(|e| e.document != document).count() + sections
+    }
+
+    fn documents(&self) -> usize {
+        let store = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let mut names: Vec<&str> = store.iter().map(|e| e.document.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        names.len()
+    }
+
+    /// The `limit` sections most similar to `query`, among `documents`
+    /// when given. With `dedup`, sections with the same content count once.
+    fn search(
+        &self,
+        query: &[f32],
+        documents: Option<&[String]>,
+        limit: usize,
+        dedup: bool,
+    ) -> Result<Vec<SemanticMatch>, ErrorData> {
+        let store = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if store.is_empty() {
+            return Err(ErrorData::invalid_request(
+                "No sections embedded in this session; call embed_sections first",
+                None,
+            ));
+        }
+        let mut matches: Vec<SemanticMatch> = store
+            .iter()
+            .filter(|e| documents.is_none_or(|documents| documents.contains(&e.document)))
+            .filter(|e| e.vector.len() == query.len())
+            .map(|e| SemanticMatch {
+                document: e.document.clone(),
+                id: e.id.clone(),
+                title: e.title.clone(),
+                line: e.line,
+                score: dot(&e.vector, query),
+                hash: content_hash(std::slice::from_ref(&e.markdown)),
+                markdown: e.markdown.clone(),
+                shared_with: Vec::new(),
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        if dedup {
+            let mut seen = Seen::default();
+            let mut unique: Vec<SemanticMatch> = Vec::new();
+            for m in matches {
+                match seen.first(&m.hash, unique.len()) {
+                    Some(first) => unique[first].shared_with.push(SharedSource {
+                        source: m.document,
+                        line: m.line,
+                    }),
+                    None => unique.push(m),
+                }
+            }
+            matches = unique;
+        }
+        matches.truncate(limit);
+        Ok(matches)
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Scales `vector` to unit length, so cosine similarity is a dot product.
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = dot(&vector, &vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+fn invalid_response(detail: impl ToString) -> ErrorData {
+    ErrorData::internal_error(
+        "Invalid embeddings API response",
+        Some(serde_json::json!({ "error": detail.to_string() })),
+    )
+}
+
+/// Reads the vectors of an embeddings response, in input order.
+fn parse_embeddings(response: &str, expected: usize) -> Result<Vec<Vec<f32>>, ErrorData> {
+    let response: Value = serde_json::from_str(response).map_err(invalid_response)?;
+    let Some(data) = response["data"].as_array() else {
+        return Err(invalid_response("missing `data`"));
+    };
+    let mut vectors: Vec<(u64, Vec<f32>)> = data
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let vector = item["embedding"]
+                .as_array()
+                .ok_or_else(|| invalid_response("missing `embedding`"))?
+                .iter()
+                .map(|x| x.as_f64().map(|x| x as f32))
+                .collect::<Option<Vec<f32>>>()
+                .ok_or_else(|| invalid_response("non-numeric embedding"))?;
+            Ok((item["index"].as_u64().unwrap_or(i as u64), vector))
+        })
+        .collect::<Result<_, ErrorData>>()?;
+    if vectors.len() != expected {
+        return Err(invalid_response(format!(
+            "expected {expected} embeddings, got {}",
+            vectors.len()
+        )));
+    }
+    vectors.sort_by_key(|(index, _)| *index);
+    Ok(vectors
+        .into_iter()
+        .map(|(_, vector)| normalize(vector))
+        .collect())
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct EmbedSectionsInput {
+    #[schemars(description = "The markdown document to embed")]
+    pub markdown: String,
+    #[schemars(
+        description = "Name for the document in search results; embedding a document under the same name again replaces it (default: `document-<n>`)"
+    )]
+    pub document: Option<String>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SemanticSearchInput {
+    #[schemars(description = "The text to search for, e.g. a question")]
+    pub search: String,
+    #[schemars(description = "Maximum number of sections to return (default: 5)")]
+    pub limit: Option<usize>,
+    #[schemars(description = "Only search these documents, by name (default: all)")]
+    pub documents: Option<Vec<String>>,
+    #[schemars(
+        description = "Collapse sections with identical content into the first, listing the others in its `shared_with` (default: true)"
+    )]
+    pub dedup: Option<bool>,
+}
+
+/// Output of `embed_sections`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct EmbedSectionsOutput {
+    #[schemars(description = "Name of the embedded document")]
+    pub document: String,
+    #[schemars(description = "Number of sections embedded from the document")]
+    pub sections: usize,
+    #[schemars(description = "Length of the embedding vectors; 0 in a dry run")]
+    pub dimensions: usize,
+    #[schemars(description = "Number of sections embedded in this session, over all documents")]
+    pub total_sections: usize,
+    #[schemars(
+        description = "Whether this was a dry run that neither called the endpoint nor stored the sections"
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SemanticMatch {
+    pub document: String,
+    #[schemars(description = "The section's stable id, accepted by edit_section")]
+    pub id: String,
+    #[schemars(description = "Heading text; null for the text before the first heading")]
+    pub title: Option<String>,
+    #[schemars(description = "1-based line of the heading")]
+    pub line: usize,
+    #[schemars(description = "Cosine similarity to the search text, from -1 to 1")]
+    pub score: f32,
+    #[schemars(description = "The section (heading and body) as markdown")]
+    pub markdown: String,
+    #[schemars(description = "SHA-256 of `markdown`, ignoring trailing whitespace")]
+    pub hash: String,
+    #[schemars(description = "Other sections with the same content, collapsed into this one")]
+    pub shared_with: Vec<SharedSource>,
+}
+
+/// Output of `semantic_search`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SemanticSearchOutput {
+    #[schemars(description = "The most similar sections, best first")]
+    pub sections: Vec<SemanticMatch>,
+}
+
+impl Server {
+    /// Embeds `inputs` through the configured endpoint, in batches.
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, ErrorData> {
+        let config = &self.config.embeddings;
+        let (url, headers) = config.endpoint()?;
+        let mut vectors = Vec::with_capacity(inputs.len());
+        for batch in inputs.chunks(BATCH_SIZE) {
+            let mut body = serde_json::json!({ "input": batch });
+            if let Some(model) = &config.model {
+                body["model"] = Value::from(model.as_str());
+            }
+            let page = self
+                .fetcher
+                .post_api(url.clone(), headers.clone(), &body)
+                .await?;
+            vectors.extend(parse_embeddings(&page.text, batch.len())?);
+        }
+        Ok(vectors)
+    }
+}
+
+#[tool_router(router = embeddings_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Split a markdown document into sections (heading + body up to the next heading), compute an embedding for each through the configured embeddings endpoint and keep them for semantic_search in this session. Embedding a document under the same name again replaces it.",
+        output_schema = output_schema::<EmbedSectionsOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = true
+        )
+    )]
+    async fn embed_sections(&self, Parameters(input): Parameters<EmbedSectionsInput>) -> McpResult {
+        let document = input
+            .document
+            .unwrap_or_else(|| format!("document-{}", self.embeddings.documents() + 1));
+        let sections: Vec<sections::Section> = sections::split(&input.markdown)?
+            .into_iter()
+            .filter(|section| !prose(&section.markdown).trim().is_empty())
+            .collect();
+        let inputs: Vec<String> = sections
+            .iter()
+            .map(|section| {
+                prose(&section.markdown)
+                    .chars()
+                    .take(MAX_INPUT_CHARS)
+                    .collect()
+            })
+            .collect();
+        let count = sections.len();
+        if dry_run::active() {
+            // Check the configuration, but don't call the endpoint.
+            self.config.embeddings.endpoint()?;
+            return json_result(&EmbedSectionsOutput {
+                total_sections: self.embeddings.len_after(&document, count),
+                document,
+                sections: count,
+                dimensions: 0,
+                dry_run: true,
+            });
+        }
+        let vectors = self.embed(&inputs).await?;
+        let dimensions = vectors.first().map(Vec::len).unwrap_or_default();
+        let total_sections = self.embeddings.replace(
+            &document,
+            sections
+                .into_iter()
+                .zip(vectors)
+                .map(|(section, vector)| Embedded {
+                    document: document.clone(),
+                    id: section.id,
+                    title: section.title,
+                    line: section.line,
+                    markdown: section.markdown,
+                    vector,
+                })
+                .collect(),
+        );
+        json_result(&EmbedSectionsOutput {
+            document,
+            sections: count,
+            dimensions,
+            total_sections,
+            dry_run: false,
+        })
+    }
+
+    #[tool(
+        description = "Find the sections most similar in meaning to a search text among the documents embedded with embed_sections in this session, returning the top sections with their cosine similarity. Retrieval without sending whole documents to the model.",
+        output_schema = output_schema::<SemanticSearchOutput>(),
+        annotations(read_only_hint = true, open_world_hint = true)
+    )]
+    async fn semantic_search(
+        &self,
+        Parameters(input): Parameters<SemanticSearchInput>,
+    ) -> McpResult {
+        let query = self.embed(std::slice::from_ref(&input.search)).await?;
+        json_result(&SemanticSearchOutput {
+            sections: self.embeddings.search(
+                &query[0],
+                input.documents.as_deref(),
+                input.limit.unwrap_or(DEFAULT_LIMIT),
+                input.dedup.unwrap_or(true),
+            )?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedded(document: &str, title: &str, vector: Vec<f32>) -> Embedded {
+        Embedded {
+            document: document.to_string(),
+            id: sections::section_id(&[title.to_string()], 1),
+            title: Some(title.to_string()),
+            line: 1,
+            markdown: format!("# {title}"),
+            vector: normalize(vector),
+        }
+    }
+
+    #[test]
+    fn test_parse_embeddings_orders_by_index() {
+        let response = r#"{"data": [
+            {"index": 1, "embedding": [0.0, 2.0]},
+            {"index": 0, "embedding": [3.0, 4.0]}
+        ]}"#;
+        assert_eq!(
+            parse_embeddings(response, 2).unwrap(),
+            vec![vec![0.6, 0.8], vec![0.0, 1.0]]
+        );
+        assert!(parse_embeddings(response, 3).is_err());
+        assert!(parse_embeddings(r#"{"error": "bad model"}"#, 1).is_err());
+    }
+
+    #[test]
+    fn test_store_search() {
+        let store = EmbeddingStore::default();
+        assert!(store.search(&[1.0, 0.0], None, 5, true).is_err());
+        store.replace(
+            "a",
+            vec![
+                embedded("a", "Install", vec![1.0, 0.0]),
+                embedded("a", "Usage", vec![0.0, 1.0]),
+            ],
+        );
+        assert_eq!(
+            store.replace("b", vec![embedded("b", "Setup", vec![1.0, 0.1])]),
+            3
+        );
+        assert_eq!(store.documents(), 2);
+
+        let titles = |matches: Vec<SemanticMatch>| -> Vec<String> {
+            matches.into_iter().filter_map(|m| m.title).collect()
+        };
+        let query = normalize(vec![1.0, 0.1]);
+        assert_eq!(
+            titles(store.search(&query, None, 2, true).unwrap()),
+            vec!["Setup", "Install"]
+        );
+        assert_eq!(
+            titles(
+                store
+                    .search(&query, Some(&["a".to_string()]), 1, true)
+                    .unwrap()
+            ),
+            vec!["Install"]
+        );
+
+        // Embedding a document again replaces its sections.
+        assert_eq!(store.len_after("a", 1), 2);
+        assert_eq!(
+            store.replace("a", vec![embedded("a", "New", vec![0.0, 1.0])]),
+            2
+        );
+    }
+
+    #[test]
+    fn test_store_search_dedup() {
+        let store = EmbeddingStore::default();
+        store.replace("a", vec![embedded("a", "Install", vec![1.0, 0.0])]);
+        store.replace(
+            "mirror",
+            vec![embedded("mirror", "Install", vec![1.0, 0.0])],
+        );
+        store.replace("b", vec![embedded("b", "Usage", vec![0.5, 0.5])]);
+
+        let query = normalize(vec![1.0, 0.0]);
+        let matches = store.search(&query, None, 2, true).unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].document, "a");
+        assert_eq!(
+            matches[0].shared_with,
+            vec![SharedSource {
+                source: "mirror".to_string(),
+                line: 1,
+            }]
+        );
+        assert_eq!(matches[1].document, "b");
+        assert_eq!(
+            store.search(&query, None, 2, false).unwrap()[1].document,
+            "mirror"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! `:shortcode:` emoji normalization, used by the `emoji` post-processing
+//! step. Shortcodes are GitHub's (gemoji), plus the names Slack and
+//! Discord use where they differ.
+
+use rmcp::schemars;
+
+/// What the `emoji` step does with known shortcodes.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    rmcp::serde::Deserialize,
+    rmcp::serde::Serialize,
+    schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EmojiMode {
+    /// Replace them with the Unicode emoji.
+    Unicode,
+    /// Remove them.
+    Strip,
+}
+
+/// Whose shortcode names are recognized, besides GitHub's.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    rmcp::serde::Deserialize,
+    rmcp::serde::Serialize,
+    schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EmojiPlatform {
+    #[default]
+    Github,
+    Slack,
+    Discord,
+}
+
+/// Slack names that GitHub spells differently. Slack also writes skin
+/// tones as a separate `:skin-tone-N:` code after the emoji.
+const SLACK: &[(&str, &str)] = &[
+    ("simple_smile", "🙂"),
+    ("white_frowning_face", "☹️"),
+    ("face_with_rolling_eyes", "🙄"),
+    ("thinking_face", "🤔"),
+    ("hugging_face", "🤗"),
+    ("robot_face", "🤖"),
+    ("the_horns", "🤘"),
+    ("sign_of_the_horns", "🤘"),
+    ("spock-hand", "🖖"),
+    ("face_with_cowboy_hat", "🤠"),
+    ("man-woman-boy", "👨\u{200d}👩\u{200d}👦"),
+    ("male-technologist", "👨\u{200d}💻"),
+    ("female-technologist", "👩\u{200d}💻"),
+    ("skin-tone-2", "\u{1f3fb}"),
+    ("skin-tone-3", "\u{1f3fc}"),
+    ("skin-tone-4", "\u{1f3fd}"),
+    ("skin-tone-5", "\u{1f3fe}"),
+    ("skin-tone-6", "\u{1f3ff}"),
+];
+
+/// Discord names that GitHub spells differently. Discord also writes
+/// skin tones as a `_toneN` suffix and flags as `flag_xx`.
+const DISCORD: &[(&str, &str)] = &[
+    ("slight_smile", "🙂"),
+    ("slight_frown", "🙁"),
+    ("upside_down", "🙃"),
+    ("hugging", "🤗"),
+    ("rolling_eyes", "🙄"),
+    ("nerd", "🤓"),
+    ("head_bandage", "🤕"),
+    ("money_mouth", "🤑"),
+    ("zipper_mouth", "🤐"),
+    ("hand_splayed", "🖐️"),
+    ("vulcan", "🖖"),
+    ("writing_hand", "✍️"),
+];
+
+/// Skin tone modifiers, for Discord's `_tone1` to `_tone5` suffixes.
+const SKIN_TONES: [char; 5] = [
+    '\u{1f3fb}',
+    '\u{1f3fc}',
+    '\u{1f3fd}',
+    '\u{1f3fe}',
+    '\u{1f3ff}',
+];
+
+/// The emoji for shortcode `name` (without colons) on `platform`.
+fn lookup(name: &str, platform: EmojiPlatform) -> Option<String> {
+    let aliases = match platform {
+        EmojiPlatform::Github => &[][..],
+        EmojiPlatform::Slack => SLACK,
+        EmojiPlatform::Discord => DISCORD,
+    };
+    if let Some((_, emoji)) = aliases.iter().find(|(alias, _)| *alias == name) {
+        return Some(emoji.to_string());
+    }
+    if platform == EmojiPlatform::Discord {
+        if let Some(code) = name.strip_prefix("flag_")
+            && code.len() == 2
+            && code.bytes().all(|b| b.is_ascii_lowercase())
+        {
+            // Regional indicator symbols 🇦 to 🇿.
+            return code
+                .bytes()
+                .map(|b| char::from_u32(0x1f1e6 + u32::from(b - b'a')))
+                .collect();
+        }
+        if let Some((base, tone)) = name.rsplit_once("_tone")
+            && let Ok(tone @ 1..=5) = tone.parse::<usize>()
+        {
+            let mut emoji = lookup(base, platform)?;
+            emoji.push(SKIN_TONES[tone - 1]);
+            return Some(emoji);
+        }
+    }
+    emojis::get_by_shortcode(name).map(|emoji| emoji.as_str().to_string())
+}
+
+/// Replaces (or with [`EmojiMode::Strip`], removes) the known shortcodes
+/// in `text`. Unknown `:names:` and inline code are left as they are.
+pub(crate) fn normalize(text: &str, mode: EmojiMode, platform: EmojiPlatform) -> String {
+    let mut out = String::with_capacity(text.len());
+    // Inline code spans are the odd-numbered pieces between backticks.
+    for (i, piece) in text.split('`').enumerate() {
+        if i > 0 {
+            out.push('`');
+        }
+        if i % 2 == 1 {
+            out.push_str(piece);
+        } else {
+            replace_shortcodes(piece, mode, platform, &mut out);
+        }
+    }
+    out
+}
+
+fn replace_shortcodes(text: &str, mode: EmojiMode, platform: EmojiPlatform, out: &mut String) {
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name_len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+')))
+            .unwrap_or(after.len());
+        let emoji = (name_len > 0 && after[name_len..].starts_with(':'))
+            .then(|| lookup(&after[..name_len], platform))
+            .flatten();
+        match emoji {
+            Some(emoji) => {
+                if mode == EmojiMode::Unicode {
+                    out.push_str(&emoji);
+                } else if out.ends_with(' ') && after[name_len + 1..].starts_with([' ', '\n']) {
+                    // Keep a single space where a word-separated code was.
+                    out.pop();
+                }
+                rest = &after[name_len + 1..];
+            }
+            None => {
+                // The closing colon may open the next shortcode.
+                out.push(':');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("Ship it :rocket: :+1:", EmojiPlatform::Github, "Ship it 🚀 👍")]
+    #[case(
+        "at 10:30:00 :not_an_emoji:smile:",
+        EmojiPlatform::Github,
+        "at 10:30:00 :not_an_emoji😄"
+    )]
+    #[case("`:smile:` stays", EmojiPlatform::Github, "`:smile:` stays")]
+    #[case(":thumbsup::skin-tone-3:", EmojiPlatform::Slack, "👍\u{1f3fc}")]
+    #[case(":thinking_face:", EmojiPlatform::Github, ":thinking_face:")]
+    #[case(":thinking_face:", EmojiPlatform::Slack, "🤔")]
+    #[case(
+        ":slight_smile: :thumbsup_tone2: :flag_jp:",
+        EmojiPlatform::Discord,
+        "🙂 👍\u{1f3fc} 🇯🇵"
+    )]
+    fn test_normalize_unicode(
+        #[case] text: &str,
+        #[case] platform: EmojiPlatform,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(normalize(text, EmojiMode::Unicode, platform), expected);
+    }
+
+    #[rstest]
+    #[case("Great work :tada: team", "Great work team")]
+    #[case(":wave: Hello :unknown:", " Hello :unknown:")]
+    #[case("Done:white_check_mark:", "Done")]
+    fn test_normalize_strip(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(
+            normalize(text, EmojiMode::Strip, EmojiPlatform::Github),
+            expected
+        );
+    }
+}
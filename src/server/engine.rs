@@ -0,0 +1,107 @@
+//! The query runtime behind the tools, abstracted as [`QueryEngine`] and
+//! [`QueryValue`] so that mq-mcp can move to a new major version of
+//! mq-lang, or be built against either of two, without touching the tools.
+//! The tools create engines, compile and run queries and inspect results
+//! only through these traits; everything else about the runtime stays in
+//! the backend module of its version.
+//!
+//! mq-lang 0.7 is the only backend for now. Supporting another major
+//! version takes an optional, renamed dependency on it (`mq-lang-0_8 =
+//! {package = "mq-lang", ...}`), a feature enabling it, a backend module
+//! implementing the traits, and a `cfg` here picking that backend when the
+//! feature is on. Static analysis (`lint_query`, query suggestions, the
+//! function lists) uses mq-hir, which follows the default backend's
+//! version.
+
+mod mq_lang_0_7;
+
+use std::{fmt::Display, sync::Arc, time::Duration};
+
+use mq_markdown::Node;
+
+use super::policy::Policy;
+
+pub(crate) use mq_lang_0_7::{Engine, Range};
+
+/// A value queries take and return, in the backend selected at build time.
+pub(crate) type Value = <Engine as QueryEngine>::Value;
+
+/// How much the engine optimizes a query before running it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, rmcp::serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Optimization {
+    #[default]
+    None,
+    Basic,
+    Full,
+}
+
+/// Limits and options of the queries run on an engine, as an engine
+/// profile sets them. `None` keeps the backend's default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Limits {
+    /// Wall-clock time a query may run.
+    pub timeout: Option<Duration>,
+    pub max_call_stack_depth: Option<u32>,
+    pub optimization: Optimization,
+}
+
+/// An mq runtime.
+pub(crate) trait QueryEngine: Sized {
+    type Value: QueryValue;
+
+    /// The mq-lang version of the backend, as `doctor` reports it.
+    const VERSION: &'static str;
+
+    /// An engine with the builtin module loaded.
+    fn new() -> Self;
+
+    /// Applies `limits` to the queries run on this engine.
+    fn set_limits(&mut self, limits: &Limits);
+
+    /// Refuses to compile or run the queries `policy` denies.
+    fn set_policy(&mut self, policy: Arc<Policy>);
+
+    /// Defines a variable for the queries run on this engine.
+    fn define(&self, name: &str, value: Self::Value);
+
+    /// Defines the functions of `module`, mq source made of `def`s, for
+    /// the queries run on this engine.
+    fn load(&mut self, module: &str) -> Result<(), String>;
+
+    /// Checks that `query` compiles, without running it.
+    fn compile(&mut self, query: &str) -> Result<(), String>;
+
+    /// The syntax errors in `query` and where they are, parsing past each
+    /// one to find the rest.
+    fn parse_errors(query: &str) -> Vec<(String, Range)>;
+
+    /// Runs `query` once per input value and returns all results.
+    fn eval(
+        &mut self,
+        query: &str,
+        input: impl Iterator<Item = Self::Value>,
+    ) -> Result<Vec<Self::Value>, String>;
+}
+
+/// A runtime value.
+pub(crate) trait QueryValue: Clone + PartialOrd + Display + From<Node> {
+    fn string(text: String) -> Self;
+
+    fn array(values: Vec<Self>) -> Self;
+
+    /// A value read from JSON: objects become dicts, `null` none.
+    fn json(value: serde_json::Value) -> Self;
+
+    /// The elements of an array, or the value itself if it isn't one.
+    fn into_array(self) -> Result<Vec<Self>, Self>;
+
+    /// The markdown node the value holds, if it holds one.
+    fn node(&self) -> Option<&Node>;
+
+    /// Whether the value is none or empty. Tools drop such results.
+    fn is_blank(&self) -> bool;
+
+    /// Whether the value counts as true in a condition.
+    fn is_truthy(&self) -> bool;
+}
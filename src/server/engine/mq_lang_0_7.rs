@@ -0,0 +1,189 @@
+//! The mq-lang 0.7 backend.
+
+use std::sync::Arc;
+
+use mq_lang::{DefaultEngine, OptimizationLevel, RuntimeValue};
+use mq_markdown::Node;
+
+use super::{Limits, Optimization, Policy, QueryEngine, QueryValue};
+
+/// A span of query source, as the parser and mq-hir report it.
+pub(crate) use mq_lang::Range;
+
+pub(crate) struct Engine {
+    engine: DefaultEngine,
+    policy: Arc<Policy>,
+}
+
+impl QueryEngine for Engine {
+    type Value = RuntimeValue;
+
+    const VERSION: &'static str = "0.7";
+
+    fn new() -> Self {
+        let mut engine = DefaultEngine::default();
+        engine.load_builtin_module();
+        Self {
+            engine,
+            policy: Arc::default(),
+        }
+    }
+
+    fn set_limits(&mut self, limits: &Limits) {
+        if let Some(timeout) = limits.timeout {
+            self.engine.set_timeout(timeout);
+        }
+        if let Some(depth) = limits.max_call_stack_depth {
+            self.engine.set_max_call_stack_depth(depth);
+        }
+        self.engine
+            .set_optimization_level(match limits.optimization {
+                Optimization::None => OptimizationLevel::None,
+                Optimization::Basic => OptimizationLevel::Basic,
+                Optimization::Full => OptimizationLevel::Full,
+            });
+    }
+
+    fn set_policy(&mut self, policy: Arc<Policy>) {
+        self.policy = policy;
+    }
+
+    fn define(&self, name: &str, value: RuntimeValue) {
+        self.engine.define_value(name, value);
+    }
+
+    fn load(&mut self, module: &str) -> Result<(), String> {
+        // Definitions are registered in the engine's environment before the
+        // program runs, so evaluating the module on no input keeps them.
+        self.engine
+            .eval(module, std::iter::empty())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn compile(&mut self, query: &str) -> Result<(), String> {
+        self.policy.check(query)?;
+        self.engine
+            .compile(query)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn parse_errors(query: &str) -> Vec<(String, Range)> {
+        let (_, errors) = mq_lang::parse_recovery(query);
+        errors.error_ranges(query)
+    }
+
+    fn eval(
+        &mut self,
+        query: &str,
+        input: impl Iterator<Item = RuntimeValue>,
+    ) -> Result<Vec<RuntimeValue>, String> {
+        self.policy.check(query)?;
+        self.engine
+            .eval(query, input)
+            .map(|values| values.into_iter().collect())
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl QueryValue for RuntimeValue {
+    fn string(text: String) -> Self {
+        RuntimeValue::String(text)
+    }
+
+    fn array(values: Vec<Self>) -> Self {
+        RuntimeValue::Array(values)
+    }
+
+    fn json(value: serde_json::Value) -> Self {
+        RuntimeValue::from(value)
+    }
+
+    fn into_array(self) -> Result<Vec<Self>, Self> {
+        match self {
+            RuntimeValue::Array(values) => Ok(values),
+            value => Err(value),
+        }
+    }
+
+    fn node(&self) -> Option<&Node> {
+        match self {
+            RuntimeValue::Markdown(node, _) => Some(node.as_ref()),
+            _ => None,
+        }
+    }
+
+    fn is_blank(&self) -> bool {
+        RuntimeValue::is_none(self) || self.is_empty()
+    }
+
+    fn is_truthy(&self) -> bool {
+        RuntimeValue::is_truthy(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        let mut engine = Engine::new();
+        engine.define(
+            "names",
+            RuntimeValue::array(vec![RuntimeValue::string("a".to_string())]),
+        );
+        let values = engine
+            .eval(
+                "names",
+                std::iter::once(RuntimeValue::string(String::new())),
+            )
+            .unwrap();
+        assert_eq!(values.len(), 1);
+        assert!(engine.compile(".h | upcase(").is_err());
+
+        engine.load("def shout(): upcase() | add(\"!\");").unwrap();
+        let values = engine
+            .eval(
+                "shout()",
+                std::iter::once(RuntimeValue::string("hi".to_string())),
+            )
+            .unwrap();
+        assert_eq!(values[0].to_string(), "HI!");
+        assert!(RuntimeValue::string(String::new()).is_blank());
+    }
+
+    #[test]
+    fn test_set_limits() {
+        let mut engine = Engine::new();
+        engine.set_limits(&Limits {
+            max_call_stack_depth: Some(8),
+            ..Default::default()
+        });
+        engine
+            .load("def depth(n): if (n == 0): 0 else: 1 + depth(n - 1);")
+            .unwrap();
+        let input = || std::iter::once(RuntimeValue::string(String::new()));
+        assert!(engine.eval("depth(4)", input()).is_ok());
+        assert!(engine.eval("depth(20)", input()).is_err());
+    }
+
+    #[test]
+    fn test_set_policy() {
+        let mut engine = Engine::new();
+        engine.set_policy(Arc::new(Policy {
+            deny_functions: vec!["upcase".to_string()],
+            ..Default::default()
+        }));
+        // User modules aren't checked.
+        engine.load("def shout(): upcase();").unwrap();
+        let input = || std::iter::once(RuntimeValue::string("hi".to_string()));
+        assert_eq!(
+            engine.eval("shout()", input()).unwrap()[0].to_string(),
+            "HI"
+        );
+        assert!(engine.eval("upcase()", input()).is_err());
+        assert!(engine.compile("upcase()").is_err());
+    }
+}
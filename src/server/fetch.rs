@@ -0,0 +1,1050 @@
+//! Fetching remote pages for the URL-based tools, and `query_url`.
+//!
+//! Bodies are decoded to UTF-8 before parsing: the charset comes from a byte
+//! order mark, the `Content-Type` header or an HTML `<meta>` declaration, in
+//! that order, so pages served as Shift_JIS or ISO-8859-1 aren't garbled.
+//!
+//! Transient failures (connection errors, timeouts, `429` and `5xx`) are
+//! retried with exponential backoff, and a per-host circuit breaker stops
+//! sending requests to a host that keeps failing.
+//!
+//! Unless the server is configured to ignore them, robots.txt rules and
+//! `Crawl-delay` are honoured (see [`super::robots`]).
+//!
+//! Redirects are followed here rather than by the HTTP client, so each hop
+//! goes through the same checks as the first request and custom headers
+//! only follow a redirect to a host they may be sent to.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use regex::bytes::Regex;
+use reqwest::{
+    Method, StatusCode,
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    redirect,
+};
+use rmcp::{ErrorData, handler::server::wrapper::Parameters, schemars, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    circuit::CircuitBreaker,
+    output::{QueryOutput, output_schema},
+    page_cache::{CachedPage, Lookup, PageCache},
+    robots::{self, Politeness, Robots},
+    sanitize::SanitizeOptions,
+    stats::CacheCounts,
+};
+
+/// Responses larger than this are rejected rather than buffered.
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Redirects followed before a request fails, as in browsers.
+const MAX_REDIRECTS: usize = 10;
+/// Delay before the first retry; doubled for each further attempt.
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(10);
+/// A `Retry-After` longer than this fails the call instead of waiting.
+const RETRY_AFTER_MAX: Duration = Duration::from_secs(30);
+/// How far into an HTML document to look for a `<meta>` charset, as in the
+/// HTML encoding sniffing algorithm.
+const META_PRESCAN_BYTES: usize = 1024;
+
+static META_CHARSET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i-u)<meta\b[^>]*?charset\s*=\s*["']?\s*([a-z0-9_.:-]+)"#).unwrap()
+});
+
+/// Server-side settings for the URL-based tools.
+#[derive(Debug, Clone)]
+pub struct FetchConfig {
+    /// Request headers (case-insensitive names) that calls may set, e.g.
+    /// `Authorization` or `Cookie`. Empty rejects all custom headers.
+    pub allowed_headers: Vec<String>,
+    /// Hosts custom headers may be sent to. Empty allows any host.
+    pub header_hosts: Vec<String>,
+    /// How long a fetched page is reused without revalidation. `None`
+    /// disables the page cache.
+    pub cache_ttl: Option<Duration>,
+    /// Upper bound on the total size of cached pages, in bytes.
+    pub cache_max_bytes: usize,
+    /// How many times a transient failure is retried.
+    pub retries: u32,
+    /// Consecutive failures after which a host is skipped for
+    /// `breaker_cooldown`. 0 disables the circuit breaker.
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+    /// Skip robots.txt and `Crawl-delay` checks. Only for hosts you operate
+    /// or have permission to crawl; calls can't override this.
+    pub ignore_robots: bool,
+}
+
+/// Default for [`FetchConfig::cache_max_bytes`].
+pub const DEFAULT_CACHE_MAX_BYTES: usize = 64 * 1024 * 1024;
+/// Default for [`FetchConfig::retries`].
+pub const DEFAULT_FETCH_RETRIES: u32 = 2;
+/// Default for [`FetchConfig::breaker_threshold`].
+pub const DEFAULT_BREAKER_THRESHOLD: u32 = 5;
+/// Default for [`FetchConfig::breaker_cooldown`].
+pub const DEFAULT_BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            allowed_headers: Vec::new(),
+            header_hosts: Vec::new(),
+            cache_ttl: None,
+            cache_max_bytes: DEFAULT_CACHE_MAX_BYTES,
+            retries: DEFAULT_FETCH_RETRIES,
+            breaker_threshold: DEFAULT_BREAKER_THRESHOLD,
+            breaker_cooldown: DEFAULT_BREAKER_COOLDOWN,
+            ignore_robots: false,
+        }
+    }
+}
+
+impl FetchConfig {
+    /// Validates per-call `headers` for a request to `url` against the
+    /// allowlists.
+    pub(crate) fn request_headers(
+        &self,
+        url: &url::Url,
+        headers: &BTreeMap<String, String>,
+    ) -> Result<HeaderMap, ErrorData> {
+        let mut map = HeaderMap::new();
+        if headers.is_empty() {
+            return Ok(map);
+        }
+        let host = url.host_str().unwrap_or_default();
+        if !header_host_allowed(&self.header_hosts, host) {
+            return Err(ErrorData::invalid_params(
+                "Custom headers are not allowed for this host",
+                Some(serde_json::json!({ "host": host, "allowed_hosts": self.header_hosts })),
+            ));
+        }
+        for (name, value) in headers {
+            if !self
+                .allowed_headers
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(name))
+            {
+                return Err(ErrorData::invalid_params(
+                    "Header not allowed by the server configuration",
+                    Some(serde_json::json!({ "header": name, "allowed": self.allowed_headers })),
+                ));
+            }
+            let (Ok(header), Ok(mut value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) else {
+                return Err(ErrorData::invalid_params(
+                    "Invalid header",
+                    Some(serde_json::json!({ "header": name })),
+                ));
+            };
+            value.set_sensitive(true);
+            map.insert(header, value);
+        }
+        Ok(map)
+    }
+}
+
+/// Whether custom headers may be sent to `host`.
+fn header_host_allowed(header_hosts: &[String], host: &str) -> bool {
+    header_hosts.is_empty()
+        || header_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+}
+
+/// The `headers` of a request redirected from `from` to `to`. Credentials
+/// are dropped when the redirect leaves the host, and every header when it
+/// leads to a host custom headers may not be sent to.
+fn redirect_headers(
+    header_hosts: &[String],
+    from: &url::Url,
+    to: &url::Url,
+    mut headers: HeaderMap,
+) -> HeaderMap {
+    if from.host_str() == to.host_str()
+        && from.port_or_known_default() == to.port_or_known_default()
+    {
+        return headers;
+    }
+    if !header_host_allowed(header_hosts, to.host_str().unwrap_or_default()) {
+        return HeaderMap::new();
+    }
+    for name in [
+        header::AUTHORIZATION,
+        header::COOKIE,
+        header::PROXY_AUTHORIZATION,
+        header::WWW_AUTHENTICATE,
+    ] {
+        headers.remove(name);
+    }
+    headers
+}
+
+/// HTTP client, page cache, circuit breaker and robots.txt state for the
+/// URL-based tools. Cheap to clone; clones share all of them, so the HTTP
+/// transport creates one per process rather than per session.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Fetcher {
+    /// Doesn't follow redirects; see [`Self::send`].
+    client: reqwest::Client,
+    /// For robots.txt, whose redirects are followed.
+    robots_client: reqwest::Client,
+    header_hosts: Vec<String>,
+    cache: Option<Arc<Mutex<PageCache>>>,
+    breaker: Arc<Mutex<CircuitBreaker>>,
+    /// `None` when robots.txt is ignored.
+    politeness: Option<Arc<Mutex<Politeness>>>,
+    retries: u32,
+}
+
+impl Fetcher {
+    pub(crate) fn new(config: &FetchConfig) -> Self {
+        let client = |policy| {
+            reqwest::Client::builder()
+                .user_agent(concat!("mq-mcp/", env!("CARGO_PKG_VERSION")))
+                .timeout(TIMEOUT)
+                .redirect(policy)
+                .build()
+                .unwrap_or_default()
+        };
+        let cache = config
+            .cache_ttl
+            .map(|ttl| Arc::new(Mutex::new(PageCache::new(ttl, config.cache_max_bytes))));
+        let breaker = CircuitBreaker::new(config.breaker_threshold, config.breaker_cooldown);
+        Self {
+            client: client(redirect::Policy::none()),
+            robots_client: client(redirect::Policy::limited(5)),
+            header_hosts: config.header_hosts.clone(),
+            cache,
+            breaker: Arc::new(Mutex::new(breaker)),
+            politeness: (!config.ignore_robots).then(Default::default),
+            retries: config.retries,
+        }
+    }
+
+    /// Hits and misses of the page cache; `None` when it is disabled.
+    pub(crate) fn cache_counts(&self) -> Option<CacheCounts> {
+        self.cache
+            .as_ref()
+            .map(|cache| cache.lock().unwrap_or_else(|e| e.into_inner()).counts())
+    }
+
+    /// Fetches `url` with the extra request `headers`, going through the
+    /// page cache. Requests with custom headers (credentials, typically)
+    /// bypass the cache so their responses are never shared.
+    pub(crate) async fn fetch(&self, url: url::Url, headers: HeaderMap) -> Result<Page, ErrorData> {
+        self.fetch_with(url, headers, true).await
+    }
+
+    /// Like [`Self::fetch`], for documented API endpoints that are meant for
+    /// programs: robots.txt, which is for crawlers, is not consulted.
+    pub(crate) async fn fetch_api(
+        &self,
+        url: url::Url,
+        headers: HeaderMap,
+    ) -> Result<Page, ErrorData> {
+        self.fetch_with(url, headers, false).await
+    }
+
+    /// POSTs a JSON `body` to an API endpoint that is read-only despite the
+    /// method (search and query endpoints), so it is retried like a GET.
+    /// Responses are never cached.
+    pub(crate) async fn post_api(
+        &self,
+        url: url::Url,
+        headers: HeaderMap,
+        body: &serde_json::Value,
+    ) -> Result<Page, ErrorData> {
+        let body = serde_json::to_vec(body).map_err(|e| fetch_error(url.as_str(), e))?;
+        match self
+            .send(Method::POST, &url, headers, Some(&body), false)
+            .await?
+        {
+            Response::Fetched(fetched) => Ok(fetched.page),
+            Response::NotModified => Err(fetch_error(url.as_str(), "unexpected 304 Not Modified")),
+        }
+    }
+
+    /// POSTs a JSON `body` whose response doesn't matter, such as a webhook
+    /// delivery. Transient failures are retried.
+    pub(crate) async fn post(
+        &self,
+        url: url::Url,
+        headers: HeaderMap,
+        body: &[u8],
+    ) -> Result<(), ErrorData> {
+        self.send(Method::POST, &url, headers, Some(body), false)
+            .await
+            .map(|_| ())
+    }
+
+    /// PUTs `body`, such as an object to S3-compatible storage; a
+    /// `Content-Type` in `headers` overrides the default JSON one. Transient
+    /// failures are retried.
+    #[cfg(feature = "s3")]
+    pub(crate) async fn put(
+        &self,
+        url: url::Url,
+        headers: HeaderMap,
+        body: &[u8],
+    ) -> Result<(), ErrorData> {
+        self.send(Method::PUT, &url, headers, Some(body), false)
+            .await
+            .map(|_| ())
+    }
+
+    async fn fetch_with(
+        &self,
+        url: url::Url,
+        headers: HeaderMap,
+        honour_robots: bool,
+    ) -> Result<Page, ErrorData> {
+        let Some(cache) = self.cache.as_ref().filter(|_| headers.is_empty()) else {
+            return match self
+                .send(Method::GET, &url, headers, None, honour_robots)
+                .await?
+            {
+                Response::Fetched(fetched) => Ok(fetched.page),
+                Response::NotModified => {
+                    Err(fetch_error(url.as_str(), "unexpected 304 Not Modified"))
+                }
+            };
+        };
+
+        let key = url.to_string();
+        let lookup = cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .lookup(&key, Instant::now());
+        let stale = match lookup {
+            Lookup::Fresh(page) => return Ok(page),
+            Lookup::Stale(cached) => Some(cached),
+            Lookup::Miss => None,
+        };
+
+        let mut conditional = HeaderMap::new();
+        if let Some(stale) = &stale {
+            for (name, value) in [
+                (header::IF_NONE_MATCH, &stale.etag),
+                (header::IF_MODIFIED_SINCE, &stale.last_modified),
+            ] {
+                if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                    conditional.insert(name, value);
+                }
+            }
+        }
+
+        let response = self
+            .send(Method::GET, &url, conditional, None, honour_robots)
+            .await?;
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        match (response, stale) {
+            (Response::NotModified, Some(stale)) => {
+                cache.revalidated(&key, Instant::now());
+                Ok(stale.page)
+            }
+            (Response::NotModified, None) => {
+                Err(fetch_error(url.as_str(), "unexpected 304 Not Modified"))
+            }
+            (Response::Fetched(fetched), _) => {
+                if fetched.no_store {
+                    return Ok(fetched.page);
+                }
+                cache.insert(
+                    key,
+                    CachedPage {
+                        page: fetched.page.clone(),
+                        etag: fetched.etag,
+                        last_modified: fetched.last_modified,
+                    },
+                    Instant::now(),
+                );
+                Ok(fetched.page)
+            }
+        }
+    }
+
+    /// Sends the request, following up to [`MAX_REDIRECTS`] redirects. As
+    /// in browsers, `303 See Other`, and `301` or `302` after a POST,
+    /// continue with a GET without the body.
+    async fn send(
+        &self,
+        method: Method,
+        url: &url::Url,
+        headers: HeaderMap,
+        body: Option<&[u8]>,
+        honour_robots: bool,
+    ) -> Result<Response, ErrorData> {
+        let (mut method, mut url, mut headers, mut body) = (method, url.clone(), headers, body);
+        for _ in 0..=MAX_REDIRECTS {
+            let (status, location) = match self
+                .send_hop(method.clone(), &url, headers.clone(), body, honour_robots)
+                .await?
+            {
+                Sent::Response(response) => return Ok(response),
+                Sent::Redirect(status, location) => (status, location),
+            };
+            let next = url
+                .join(&location)
+                .map_err(|e| fetch_error(url.as_str(), format!("invalid redirect: {e}")))?;
+            if !matches!(next.scheme(), "http" | "https") {
+                return Err(fetch_error(url.as_str(), "redirect to a non-http URL"));
+            }
+            if status == StatusCode::SEE_OTHER
+                || (method == Method::POST
+                    && matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND))
+            {
+                method = Method::GET;
+                body = None;
+            }
+            headers = redirect_headers(&self.header_hosts, &url, &next, headers);
+            url = next;
+        }
+        Err(fetch_error(
+            url.as_str(),
+            format!("more than {MAX_REDIRECTS} redirects"),
+        ))
+    }
+
+    /// Sends one request, retrying transient failures with exponential
+    /// backoff (or the server's `Retry-After`) unless the host's circuit is
+    /// open.
+    async fn send_hop(
+        &self,
+        method: Method,
+        url: &url::Url,
+        headers: HeaderMap,
+        body: Option<&[u8]>,
+        honour_robots: bool,
+    ) -> Result<Sent, ErrorData> {
+        let host = url.host_str().unwrap_or_default();
+        let robots = match &self.politeness {
+            Some(politeness) if honour_robots => Some(self.robots(politeness, url).await),
+            _ => None,
+        };
+        if let Some(robots) = &robots {
+            let path = match url.query() {
+                Some(query) => format!("{}?{query}", url.path()),
+                None => url.path().to_string(),
+            };
+            if !robots.is_allowed(&path) {
+                return Err(ErrorData::invalid_params(
+                    "Disallowed by robots.txt",
+                    Some(serde_json::json!({ "url": url.as_str() })),
+                ));
+            }
+        }
+
+        let mut attempt = 0;
+        loop {
+            let check = self
+                .breaker
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .check(host, Instant::now());
+            if let Err(retry_after) = check {
+                return Err(ErrorData::internal_error(
+                    "Host temporarily unavailable",
+                    Some(serde_json::json!({
+                        "url": url.as_str(),
+                        "host": host,
+                        "retry_after_secs": retry_after.as_secs().max(1),
+                    })),
+                ));
+            }
+
+            if let (Some(politeness), Some(delay)) = (
+                &self.politeness,
+                robots.as_ref().and_then(|robots| robots.crawl_delay()),
+            ) {
+                let wait = politeness
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .reserve(host, delay, Instant::now());
+                if !wait.is_zero() {
+                    tracing::debug!("waiting {wait:?} for the crawl delay of {host}");
+                    tokio::time::sleep(wait).await;
+                }
+            }
+
+            let result = send(&self.client, method.clone(), url, headers.clone(), body).await;
+            let failure = {
+                let mut breaker = self.breaker.lock().unwrap_or_else(|e| e.into_inner());
+                match result {
+                    Ok(response) => {
+                        breaker.record_success(host);
+                        return Ok(response);
+                    }
+                    // The host answered; only the request was wrong.
+                    Err(failure) if !failure.transient => {
+                        breaker.record_success(host);
+                        return Err(failure.error);
+                    }
+                    Err(failure) => {
+                        breaker.record_failure(host, Instant::now());
+                        failure
+                    }
+                }
+            };
+
+            let delay = match failure.retry_after {
+                Some(retry_after) if retry_after > RETRY_AFTER_MAX => return Err(failure.error),
+                Some(retry_after) => retry_after,
+                None => backoff(attempt),
+            };
+            if attempt >= self.retries {
+                return Err(failure.error);
+            }
+            attempt += 1;
+            tracing::debug!(
+                "retrying {url} in {delay:?} (attempt {attempt}/{})",
+                self.retries
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// The robots.txt rules for the origin of `url`, fetched if not cached.
+    /// A missing robots.txt (any 4xx) allows everything; one that can't be
+    /// fetched (5xx or a network error) disallows everything for a while.
+    async fn robots(&self, politeness: &Mutex<Politeness>, url: &url::Url) -> Arc<Robots> {
+        let origin = url.origin().ascii_serialization();
+        let cached = politeness
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .cached(&origin, Instant::now());
+        if let Some(robots) = cached {
+            return robots;
+        }
+
+        let response = self
+            .robots_client
+            .get(format!("{origin}/robots.txt"))
+            .send()
+            .await;
+        let (robots, ttl) = match response {
+            Ok(response) if response.status().is_success() => match response.text().await {
+                Ok(text) => (
+                    Robots::parse(&text, robots::USER_AGENT_TOKEN),
+                    robots::ROBOTS_TTL,
+                ),
+                Err(_) => (Robots::disallow_all(), robots::UNREACHABLE_TTL),
+            },
+            Ok(response) if response.status().is_client_error() => {
+                (Robots::allow_all(), robots::ROBOTS_TTL)
+            }
+            Ok(_) | Err(_) => {
+                tracing::warn!("robots.txt for {origin} is unreachable; disallowing for now");
+                (Robots::disallow_all(), robots::UNREACHABLE_TTL)
+            }
+        };
+        politeness.lock().unwrap_or_else(|e| e.into_inner()).store(
+            origin,
+            robots,
+            ttl,
+            Instant::now(),
+        )
+    }
+}
+
+/// Delay before retry number `attempt + 1`.
+fn backoff(attempt: u32) -> Duration {
+    BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(BACKOFF_MAX)
+}
+
+/// A fetched page, decoded to UTF-8.
+#[derive(Debug, Clone)]
+pub(crate) struct Page {
+    /// Final URL, after redirects.
+    pub url: String,
+    pub content_type: Option<String>,
+    /// Name of the encoding the body was decoded from.
+    pub charset: &'static str,
+    pub text: String,
+}
+
+impl Page {
+    pub(crate) fn is_html(&self) -> bool {
+        match &self.content_type {
+            Some(content_type) => content_type.contains("html"),
+            None => self.text.trim_start().starts_with('<'),
+        }
+    }
+}
+
+fn fetch_error(url: &str, detail: impl ToString) -> ErrorData {
+    ErrorData::internal_error(
+        "Failed to fetch URL",
+        Some(serde_json::json!({ "url": url, "error": detail.to_string() })),
+    )
+}
+
+/// Parses a URL given to a URL-based tool; only http and https are
+/// accepted.
+pub(crate) fn parse_url(url: &str) -> Result<url::Url, ErrorData> {
+    let parsed = url::Url::parse(url).map_err(|e| {
+        ErrorData::invalid_params(
+            "Invalid URL",
+            Some(serde_json::Value::String(e.to_string())),
+        )
+    })?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(ErrorData::invalid_params(
+            "Only http and https URLs can be fetched",
+            Some(serde_json::json!({ "url": url })),
+        ));
+    }
+    Ok(parsed)
+}
+
+struct Fetched {
+    page: Page,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// `Cache-Control: no-store`: the response must not be cached.
+    no_store: bool,
+}
+
+enum Response {
+    Fetched(Fetched),
+    NotModified,
+}
+
+/// The outcome of one request: a response, or a redirect with the status
+/// and `Location` to follow.
+enum Sent {
+    Response(Response),
+    Redirect(StatusCode, String),
+}
+
+/// A failed request attempt.
+struct Failure {
+    error: ErrorData,
+    /// Worth retrying, and counts against the host's circuit breaker.
+    transient: bool,
+    retry_after: Option<Duration>,
+}
+
+impl Failure {
+    fn permanent(error: ErrorData) -> Self {
+        Self {
+            error,
+            transient: false,
+            retry_after: None,
+        }
+    }
+
+    fn from_reqwest(url: &url::Url, error: reqwest::Error) -> Self {
+        Self {
+            transient: error.is_connect()
+                || error.is_timeout()
+                || error.is_request()
+                || error.is_body(),
+            error: fetch_error(url.as_str(), error),
+            retry_after: None,
+        }
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// A `Retry-After` given in seconds. HTTP dates are ignored in favour of
+/// the regular backoff.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Sends a request for `url`, with a JSON `body` if given, and decodes the
+/// response body. Redirects are returned, not followed.
+async fn send(
+    client: &reqwest::Client,
+    method: Method,
+    url: &url::Url,
+    headers: HeaderMap,
+    body: Option<&[u8]>,
+) -> Result<Sent, Failure> {
+    let mut request = client.request(method, url.clone());
+    if let Some(body) = body {
+        request = request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+    }
+    let mut response = request
+        .headers(headers)
+        .send()
+        .await
+        .map_err(|e| Failure::from_reqwest(url, e))?;
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(Sent::Response(Response::NotModified));
+    }
+    if status.is_redirection()
+        && let Some(location) = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+    {
+        return Ok(Sent::Redirect(status, location.to_string()));
+    }
+    if !status.is_success() {
+        return Err(Failure {
+            error: fetch_error(url.as_str(), format!("HTTP {status}")),
+            transient: is_transient_status(status),
+            retry_after: retry_after(response.headers()),
+        });
+    }
+    let final_url = response.url().to_string();
+    let response_header = |name: HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let content_type = response_header(header::CONTENT_TYPE);
+    let etag = response_header(header::ETAG);
+    let last_modified = response_header(header::LAST_MODIFIED);
+    let no_store = response_header(header::CACHE_CONTROL)
+        .is_some_and(|cache_control| cache_control.to_ascii_lowercase().contains("no-store"));
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| Failure::from_reqwest(url, e))?
+    {
+        if body.len() + chunk.len() > MAX_BODY_BYTES {
+            return Err(Failure::permanent(fetch_error(
+                url.as_str(),
+                format!("response larger than {MAX_BODY_BYTES} bytes"),
+            )));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    let (text, encoding) = decode(&body, content_type.as_deref());
+    Ok(Sent::Response(Response::Fetched(Fetched {
+        page: Page {
+            url: final_url,
+            content_type,
+            charset: encoding.name(),
+            text,
+        },
+        etag,
+        last_modified,
+        no_store,
+    })))
+}
+
+/// The encoding named by the `charset` parameter of a `Content-Type`.
+fn content_type_charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| Encoding::for_label(value.trim().trim_matches(['"', '\'']).as_bytes()))?
+    })
+}
+
+/// The encoding declared by a `<meta charset>` or `<meta http-equiv>` tag
+/// near the start of an HTML document.
+fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let head = &body[..body.len().min(META_PRESCAN_BYTES)];
+    let label = META_CHARSET.captures(head)?.get(1)?.as_bytes();
+    // A document that could be read as ASCII can't really be UTF-16.
+    Encoding::for_label(label).map(Encoding::output_encoding)
+}
+
+/// Decodes `body` to a string, returning the encoding that was used.
+pub(crate) fn decode(body: &[u8], content_type: Option<&str>) -> (String, &'static Encoding) {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(body) {
+        let (text, _) = encoding.decode_without_bom_handling(&body[bom_len..]);
+        return (text.into_owned(), encoding);
+    }
+    let is_html = content_type.is_none_or(|content_type| content_type.contains("html"));
+    let encoding = content_type
+        .and_then(content_type_charset)
+        .or_else(|| is_html.then(|| meta_charset(body)).flatten())
+        .unwrap_or_else(|| {
+            if std::str::from_utf8(body).is_ok() {
+                UTF_8
+            } else {
+                WINDOWS_1252
+            }
+        });
+    let (text, _) = encoding.decode_without_bom_handling(body);
+    (text.into_owned(), encoding)
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct QueryUrlInput {
+    #[schemars(description = "The http(s) URL of an HTML or markdown page")]
+    pub url: String,
+    #[schemars(
+        description = "The mq query to execute (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+    #[schemars(
+        description = "Extra request headers, e.g. {\"Authorization\": \"Bearer ...\"}. Only headers allowed by the server configuration (--allow-fetch-header) may be set."
+    )]
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub sanitize: SanitizeOptions,
+}
+
+#[tool_router(router = fetch_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Fetch a web page (HTML or markdown) by URL, convert it to markdown and run an mq query on it. The page's character encoding is detected and converted to UTF-8. HTML is sanitized as in html_to_markdown.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = true)
+    )]
+    async fn query_url(&self, Parameters(input): Parameters<QueryUrlInput>) -> McpResult {
+        let url = parse_url(&input.url)?;
+        let headers = self.config.fetch.request_headers(&url, &input.headers)?;
+        let page = self.fetcher.fetch(url, headers).await?;
+        tracing::debug!(
+            "fetched {} ({}, decoded from {})",
+            page.url,
+            page.content_type.as_deref().unwrap_or("no content type"),
+            page.charset
+        );
+        let query = input.query.as_deref().unwrap_or("identity()");
+        if page.is_html() {
+            self.eval_html(&page.text, query, &input.sanitize)
+        } else {
+            self.eval_query(&page.text, query)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(b"caf\xc3\xa9".as_slice(), Some("text/plain"), "café", "UTF-8")]
+    #[case(b"caf\xe9".as_slice(), Some("text/html; charset=ISO-8859-1"), "café", "windows-1252")]
+    #[case(
+        b"<meta charset=\"Shift_JIS\"><p>\x93\xfa\x96\x7b</p>".as_slice(),
+        Some("text/html"),
+        "<meta charset=\"Shift_JIS\"><p>日本</p>",
+        "Shift_JIS"
+    )]
+    #[case(
+        b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=euc-jp\">\xc6\xfc".as_slice(),
+        None,
+        "<meta http-equiv=\"Content-Type\" content=\"text/html; charset=euc-jp\">日",
+        "EUC-JP"
+    )]
+    #[case(b"\xef\xbb\xbfhi".as_slice(), Some("text/html; charset=Shift_JIS"), "hi", "UTF-8")]
+    #[case(b"caf\xe9".as_slice(), Some("text/plain"), "café", "windows-1252")]
+    fn test_decode(
+        #[case] body: &[u8],
+        #[case] content_type: Option<&str>,
+        #[case] expected: &str,
+        #[case] charset: &str,
+    ) {
+        let (text, encoding) = decode(body, content_type);
+        assert_eq!(text, expected);
+        assert_eq!(encoding.name(), charset);
+    }
+
+    #[test]
+    fn test_content_type_charset_wins_over_meta() {
+        let body = b"<meta charset=\"utf-8\">caf\xe9";
+        let (_, encoding) = decode(body, Some("text/html; charset=\"latin1\""));
+        assert_eq!(encoding, WINDOWS_1252);
+    }
+
+    #[test]
+    fn test_parse_url_rejects_non_http_schemes() {
+        let error = parse_url("file:///etc/passwd").unwrap_err();
+        assert_eq!(error.message, "Only http and https URLs can be fetched");
+    }
+
+    #[rstest]
+    #[case(0, Duration::from_millis(500))]
+    #[case(2, Duration::from_secs(2))]
+    #[case(10, BACKOFF_MAX)]
+    #[case(u32::MAX, BACKOFF_MAX)]
+    fn test_backoff(#[case] attempt: u32, #[case] expected: Duration) {
+        assert_eq!(backoff(attempt), expected);
+    }
+
+    #[rstest]
+    #[case(StatusCode::TOO_MANY_REQUESTS, true)]
+    #[case(StatusCode::SERVICE_UNAVAILABLE, true)]
+    #[case(StatusCode::NOT_FOUND, false)]
+    #[case(StatusCode::FORBIDDEN, false)]
+    fn test_is_transient_status(#[case] status: StatusCode, #[case] expected: bool) {
+        assert_eq!(is_transient_status(status), expected);
+    }
+
+    #[rstest]
+    #[case("120", Some(Duration::from_secs(120)))]
+    #[case("Wed, 21 Oct 2015 07:28:00 GMT", None)]
+    fn test_retry_after(#[case] value: &str, #[case] expected: Option<Duration>) {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        assert_eq!(retry_after(&headers), expected);
+    }
+
+    #[rstest]
+    #[case(&[], &[], "Authorization", Err("Header not allowed by the server configuration"))]
+    #[case(&["authorization"], &[], "Authorization", Ok(()))]
+    #[case(&["Authorization"], &["wiki.example.com"], "authorization", Ok(()))]
+    #[case(&["Authorization"], &["other.example.com"], "Authorization", Err("Custom headers are not allowed for this host"))]
+    fn test_request_headers(
+        #[case] allowed_headers: &[&str],
+        #[case] header_hosts: &[&str],
+        #[case] header: &str,
+        #[case] expected: Result<(), &str>,
+    ) {
+        let config = FetchConfig {
+            allowed_headers: allowed_headers.iter().map(|h| h.to_string()).collect(),
+            header_hosts: header_hosts.iter().map(|h| h.to_string()).collect(),
+            ..Default::default()
+        };
+        let url = parse_url("https://wiki.example.com/page").unwrap();
+        let headers = BTreeMap::from([(header.to_string(), "Bearer t".to_string())]);
+        let result = config.request_headers(&url, &headers);
+        match expected {
+            Ok(()) => assert_eq!(result.unwrap().len(), 1),
+            Err(message) => assert_eq!(result.unwrap_err().message, message),
+        }
+    }
+
+    #[rstest]
+    #[case("https://wiki.example.com/b", &[], &["authorization", "x-api-key"])]
+    #[case("https://cdn.example.com/b", &[], &["x-api-key"])]
+    #[case("https://cdn.example.com/b", &["wiki.example.com", "cdn.example.com"], &["x-api-key"])]
+    #[case("https://cdn.example.com/b", &["wiki.example.com"], &[])]
+    #[case("https://wiki.example.com:8443/b", &["wiki.example.com"], &["x-api-key"])]
+    fn test_redirect_headers(
+        #[case] to: &str,
+        #[case] header_hosts: &[&str],
+        #[case] expected: &[&str],
+    ) {
+        let header_hosts: Vec<String> = header_hosts.iter().map(|h| h.to_string()).collect();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer t"));
+        headers.insert("x-api-key", HeaderValue::from_static("k"));
+        let from = parse_url("https://wiki.example.com/a").unwrap();
+        let headers = redirect_headers(&header_hosts, &from, &parse_url(to).unwrap(), headers);
+        let mut names: Vec<&str> = headers.keys().map(HeaderName::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(names, expected);
+    }
+
+    /// Serves the responses of `respond`, given the path and the server's
+    /// port, on 127.0.0.1, and returns the port and the requests received.
+    async fn serve(respond: fn(&str, u16) -> String) -> (u16, Arc<Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request).to_ascii_lowercase();
+                let path = request.split(' ').nth(1).unwrap_or_default().to_string();
+                received.lock().unwrap().push(request);
+                let _ = stream.write_all(respond(&path, port).as_bytes()).await;
+            }
+        });
+        (port, requests)
+    }
+
+    fn response(status: &str, location: Option<String>, body: &str) -> String {
+        let location = location.map_or(String::new(), |location| {
+            format!("Location: {location}\r\n")
+        });
+        format!(
+            "HTTP/1.1 {status}\r\n{location}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[tokio::test]
+    async fn test_redirect_drops_headers_off_header_hosts() {
+        let (port, requests) = serve(|path, port| match path {
+            "/start" => response("302 Found", Some("/same".to_string()), ""),
+            "/same" => response(
+                "301 Moved Permanently",
+                Some(format!("http://localhost:{port}/end")),
+                "",
+            ),
+            _ => response("200 OK", None, "# End"),
+        })
+        .await;
+        let fetcher = Fetcher::new(&FetchConfig {
+            header_hosts: vec!["127.0.0.1".to_string()],
+            ignore_robots: true,
+            ..Default::default()
+        });
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("secret"));
+        let url = parse_url(&format!("http://127.0.0.1:{port}/start")).unwrap();
+
+        let page = fetcher.fetch(url, headers).await.unwrap();
+        assert_eq!(page.url, format!("http://localhost:{port}/end"));
+        assert_eq!(page.text, "# End");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].contains("x-api-key: secret"));
+        assert!(!requests[2].contains("x-api-key"));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_checks_robots() {
+        let (port, requests) = serve(|path, _| match path {
+            "/robots.txt" => response("200 OK", None, "User-agent: *\nDisallow: /private\n"),
+            "/start" => response("302 Found", Some("/private".to_string()), ""),
+            _ => response("200 OK", None, "# Private"),
+        })
+        .await;
+        let fetcher = Fetcher::new(&FetchConfig::default());
+        let url = parse_url(&format!("http://127.0.0.1:{port}/start")).unwrap();
+
+        let error = fetcher.fetch(url, HeaderMap::new()).await.unwrap_err();
+        assert_eq!(error.message, "Disallowed by robots.txt");
+        assert!(
+            !requests
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|request| request.starts_with("get /private"))
+        );
+    }
+}
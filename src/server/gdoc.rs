@@ -0,0 +1,154 @@
+//! `query_gdoc`: Google Docs fetched with the Drive export API.
+
+use std::fmt;
+
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use rmcp::{ErrorData, handler::server::wrapper::Parameters, schemars, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    fetch::parse_url,
+    output::{QueryOutput, output_schema},
+    sanitize::SanitizeOptions,
+};
+
+pub(super) const DRIVE_API_URL: &str = "https://www.googleapis.com/drive/v3";
+
+/// Server-side settings for `query_gdoc`.
+#[derive(Clone, Default)]
+pub struct GdocConfig {
+    /// OAuth 2.0 access token with a Drive read scope (e.g.
+    /// `drive.readonly`). `None` disables the tool.
+    pub token: Option<String>,
+}
+
+impl fmt::Debug for GdocConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GdocConfig")
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl GdocConfig {
+    fn headers(&self) -> Result<HeaderMap, ErrorData> {
+        let Some(token) = &self.token else {
+            return Err(ErrorData::invalid_request(
+                "no Google OAuth token configured — restart mq-mcp with --google-token <token> to enable query_gdoc",
+                None,
+            ));
+        };
+        let mut value = HeaderValue::from_str(&format!("Bearer {token}")).map_err(|_| {
+            ErrorData::internal_error(
+                "Invalid Google OAuth token in the server configuration",
+                None,
+            )
+        })?;
+        value.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, value);
+        Ok(headers)
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum GdocFormat {
+    /// Google's own markdown export.
+    #[default]
+    Markdown,
+    /// The HTML export, converted like html_to_markdown.
+    Html,
+}
+
+impl GdocFormat {
+    fn mime_type(self) -> &'static str {
+        match self {
+            Self::Markdown => "text/markdown",
+            Self::Html => "text/html",
+        }
+    }
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct QueryGdocInput {
+    #[schemars(
+        description = "The document id, or its URL (https://docs.google.com/document/d/<id>/edit)"
+    )]
+    pub document: String,
+    #[schemars(
+        description = "Export format: `markdown` (default) or `html`, which keeps more of the layout of complex documents"
+    )]
+    pub format: Option<GdocFormat>,
+    #[schemars(
+        description = "The mq query to execute (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+    #[serde(flatten)]
+    pub sanitize: SanitizeOptions,
+}
+
+/// The file id in a document id or URL.
+fn parse_document_id(document: &str) -> Result<String, ErrorData> {
+    let document = document.trim();
+    let id = match document.split_once("/d/") {
+        Some((_, rest)) => rest.split(['/', '?', '#']).next().unwrap_or_default(),
+        None => document,
+    };
+    if !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(id.to_string())
+    } else {
+        Err(ErrorData::invalid_params(
+            "Invalid Google Docs document id",
+            Some(serde_json::json!({ "document": document })),
+        ))
+    }
+}
+
+#[tool_router(router = gdoc_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Export a Google Doc as markdown (or HTML) through the Google Drive API and run an mq query on it. Requires the server to be started with --google-token.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = true)
+    )]
+    async fn query_gdoc(&self, Parameters(input): Parameters<QueryGdocInput>) -> McpResult {
+        let headers = self.config.gdoc.headers()?;
+        let id = parse_document_id(&input.document)?;
+        let format = input.format.unwrap_or_default();
+        let mut url = parse_url(&format!("{DRIVE_API_URL}/files/{id}/export"))?;
+        url.query_pairs_mut()
+            .append_pair("mimeType", format.mime_type());
+
+        let page = self.fetcher.fetch_api(url, headers).await?;
+        let query = input.query.as_deref().unwrap_or("identity()");
+        match format {
+            GdocFormat::Markdown => self.eval_query(&page.text, query),
+            GdocFormat::Html => self.eval_html(&page.text, query, &input.sanitize),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("1AbC-d_E", Some("1AbC-d_E"))]
+    #[case(
+        "https://docs.google.com/document/d/1AbC-d_E/edit?tab=t.0",
+        Some("1AbC-d_E")
+    )]
+    #[case("https://docs.google.com/document/d/1AbC-d_E", Some("1AbC-d_E"))]
+    #[case("../secrets", None)]
+    fn test_parse_document_id(#[case] document: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_document_id(document).ok().as_deref(), expected);
+    }
+}
@@ -0,0 +1,195 @@
+//! `query_github`: files from GitHub repositories, fetched through the
+//! GitHub REST API.
+
+use std::fmt;
+
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use rmcp::{ErrorData, handler::server::wrapper::Parameters, schemars, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    fetch::parse_url,
+    output::{QueryOutput, output_schema},
+};
+
+pub(super) const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// Server-side settings for `query_github`.
+#[derive(Clone, Default)]
+pub struct GithubConfig {
+    /// Token sent as `Authorization: Bearer`, for private repositories and
+    /// higher rate limits.
+    pub token: Option<String>,
+    /// REST API root, for GitHub Enterprise Server (e.g.
+    /// `https://github.example.com/api/v3`). Defaults to api.github.com.
+    pub api_url: Option<String>,
+}
+
+impl fmt::Debug for GithubConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GithubConfig")
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("api_url", &self.api_url)
+            .finish()
+    }
+}
+
+impl GithubConfig {
+    fn headers(&self) -> Result<HeaderMap, ErrorData> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/vnd.github.raw+json"),
+        );
+        headers.insert(
+            "X-GitHub-Api-Version",
+            HeaderValue::from_static("2022-11-28"),
+        );
+        if let Some(token) = &self.token {
+            let mut value = HeaderValue::from_str(&format!("Bearer {token}")).map_err(|_| {
+                ErrorData::internal_error("Invalid GitHub token in the server configuration", None)
+            })?;
+            value.set_sensitive(true);
+            headers.insert(header::AUTHORIZATION, value);
+        }
+        Ok(headers)
+    }
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct QueryGithubInput {
+    #[schemars(
+        description = "The repository as `owner/name` or a https://github.com/owner/name URL"
+    )]
+    pub repo: String,
+    #[schemars(
+        description = "Path of the file in the repository, e.g. `docs/install.md` (default: the repository's README)"
+    )]
+    pub path: Option<String>,
+    #[schemars(description = "Branch, tag or commit to read from (default: the default branch)")]
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>,
+    #[schemars(
+        description = "The mq query to execute (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+}
+
+/// Splits `owner/name` out of a repository reference.
+fn parse_repo(repo: &str) -> Result<(String, String), ErrorData> {
+    let trimmed = repo
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("github.com/")
+        .trim_end_matches('/')
+        .trim_end_matches(".git");
+    let mut parts = trimmed.split('/');
+    match (parts.next(), parts.next()) {
+        (Some(owner), Some(name)) if !owner.is_empty() && !name.is_empty() => {
+            Ok((owner.to_string(), name.to_string()))
+        }
+        _ => Err(ErrorData::invalid_params(
+            "Invalid repository; expected `owner/name`",
+            Some(serde_json::json!({ "repo": repo })),
+        )),
+    }
+}
+
+/// The contents API URL for `path`, or the README endpoint.
+fn contents_url(
+    api_url: &str,
+    owner: &str,
+    name: &str,
+    path: Option<&str>,
+    git_ref: Option<&str>,
+) -> Result<url::Url, ErrorData> {
+    let mut url = parse_url(api_url)?;
+    {
+        let mut segments = url.path_segments_mut().map_err(|_| {
+            ErrorData::internal_error("Invalid GitHub API URL in the server configuration", None)
+        })?;
+        segments.pop_if_empty().extend(["repos", owner, name]);
+        match path
+            .map(|path| path.trim_matches('/'))
+            .filter(|path| !path.is_empty())
+        {
+            Some(path) => {
+                segments.push("contents").extend(path.split('/'));
+            }
+            None => {
+                segments.push("readme");
+            }
+        }
+    }
+    if let Some(git_ref) = git_ref {
+        url.query_pairs_mut().append_pair("ref", git_ref);
+    }
+    Ok(url)
+}
+
+#[tool_router(router = github_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Fetch a file from a GitHub repository (the README by default) at a branch, tag or commit through the GitHub API and run an mq query on it, e.g. to list the installation steps of a library.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = true)
+    )]
+    async fn query_github(&self, Parameters(input): Parameters<QueryGithubInput>) -> McpResult {
+        let (owner, name) = parse_repo(&input.repo)?;
+        let github = &self.config.github;
+        let url = contents_url(
+            github.api_url.as_deref().unwrap_or(DEFAULT_API_URL),
+            &owner,
+            &name,
+            input.path.as_deref(),
+            input.git_ref.as_deref(),
+        )?;
+        let page = self.fetcher.fetch_api(url, github.headers()?).await?;
+        self.eval_query(&page.text, input.query.as_deref().unwrap_or("identity()"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("harehare/mq", Some(("harehare", "mq")))]
+    #[case("https://github.com/harehare/mq-mcp.git", Some(("harehare", "mq-mcp")))]
+    #[case("github.com/harehare/mq/", Some(("harehare", "mq")))]
+    #[case("harehare", None)]
+    fn test_parse_repo(#[case] repo: &str, #[case] expected: Option<(&str, &str)>) {
+        let result = parse_repo(repo).ok();
+        assert_eq!(
+            result.as_ref().map(|(o, n)| (o.as_str(), n.as_str())),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(None, None, "https://api.github.com/repos/harehare/mq/readme")]
+    #[case(
+        Some("docs/install guide.md"),
+        Some("v0.5.0"),
+        "https://api.github.com/repos/harehare/mq/contents/docs/install%20guide.md?ref=v0.5.0"
+    )]
+    fn test_contents_url(
+        #[case] path: Option<&str>,
+        #[case] git_ref: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        let url = contents_url(DEFAULT_API_URL, "harehare", "mq", path, git_ref).unwrap();
+        assert_eq!(url.as_str(), expected);
+    }
+
+    #[test]
+    fn test_debug_redacts_token() {
+        let config = GithubConfig {
+            token: Some("ghp_secret".to_string()),
+            api_url: None,
+        };
+        assert!(!format!("{config:?}").contains("ghp_secret"));
+    }
+}
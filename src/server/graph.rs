@@ -0,0 +1,306 @@
+//! `link_graph`: the graph of wiki-links between the notes of a vault,
+//! for maintenance work such as finding orphaned notes, hubs and links to
+//! notes that don't exist. Returned as JSON, and optionally as Graphviz
+//! DOT or a Mermaid flowchart to render.
+
+use std::collections::{BTreeMap, HashMap};
+
+use rmcp::{handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    output::{json_result, output_schema},
+    vault::{self, Vault},
+    wikilinks,
+};
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum GraphFormat {
+    /// Nodes and edges only.
+    #[default]
+    Json,
+    /// Also a Graphviz `digraph`.
+    Dot,
+    /// Also a Mermaid flowchart.
+    Mermaid,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct LinkGraphInput {
+    #[schemars(description = "The directory of notes (the vault) to map")]
+    pub directory: String,
+    #[schemars(
+        description = "`json` (default), or `dot` or `mermaid` to also return the graph in that syntax in `graph`"
+    )]
+    pub format: Option<GraphFormat>,
+    #[schemars(
+        description = "Include the targets of links to notes that don't exist as nodes (default: true)"
+    )]
+    pub unresolved: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct GraphNode {
+    #[schemars(
+        description = "The note's path in the directory, or the link target for a note that doesn't exist"
+    )]
+    pub id: String,
+    pub name: String,
+    #[schemars(description = "Whether the note exists; false for the targets of unresolved links")]
+    pub exists: bool,
+    #[schemars(description = "Number of notes this note links to")]
+    pub outgoing: usize,
+    #[schemars(description = "Number of notes linking to this note")]
+    pub incoming: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct GraphEdge {
+    #[schemars(description = "Id of the linking note")]
+    pub source: String,
+    #[schemars(description = "Id of the linked note")]
+    pub target: String,
+    #[schemars(description = "Number of links and embeds from source to target")]
+    pub count: usize,
+}
+
+/// Output of `link_graph`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct LinkGraphOutput {
+    #[schemars(description = "The notes in path order, then the targets of unresolved links")]
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    #[schemars(description = "Paths of the notes with no links to or from other notes")]
+    pub orphans: Vec<String>,
+    #[schemars(description = "The graph in DOT or Mermaid syntax, per `format`")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graph: Option<String>,
+}
+
+/// The link graph of `vault`. A note's links to itself, and links within
+/// a note, aren't edges.
+fn graph(vault: &Vault, unresolved: bool) -> (Vec<GraphNode>, BTreeMap<(usize, usize), usize>) {
+    let mut nodes: Vec<GraphNode> = vault
+        .notes
+        .iter()
+        .map(|note| GraphNode {
+            id: note.path.clone(),
+            name: note.name().to_string(),
+            exists: true,
+            outgoing: 0,
+            incoming: 0,
+        })
+        .collect();
+    let index: HashMap<&str, usize> = vault
+        .notes
+        .iter()
+        .enumerate()
+        .map(|(i, note)| (note.path.as_str(), i))
+        .collect();
+    let mut missing: HashMap<String, usize> = HashMap::new();
+    let mut edges: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    for (source, note) in vault.notes.iter().enumerate() {
+        for link in wikilinks::parse(&note.markdown).links {
+            if link.target.is_empty() {
+                continue;
+            }
+            let target = match vault.resolve(&link.target) {
+                Some(target) => index[target.path.as_str()],
+                None if unresolved => {
+                    let id = vault::strip_extension(&link.target);
+                    *missing.entry(id.to_lowercase()).or_insert_with(|| {
+                        nodes.push(GraphNode {
+                            id: id.to_string(),
+                            name: id.rsplit('/').next().unwrap_or(id).to_string(),
+                            exists: false,
+                            outgoing: 0,
+                            incoming: 0,
+                        });
+                        nodes.len() - 1
+                    })
+                }
+                None => continue,
+            };
+            if target != source {
+                *edges.entry((source, target)).or_default() += 1;
+            }
+        }
+    }
+    for &(source, target) in edges.keys() {
+        nodes[source].outgoing += 1;
+        nodes[target].incoming += 1;
+    }
+    (nodes, edges)
+}
+
+/// `text` as a double-quoted DOT string.
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn dot(nodes: &[GraphNode], edges: &BTreeMap<(usize, usize), usize>) -> String {
+    let mut lines = vec!["digraph vault {".to_string()];
+    for node in nodes {
+        let style = if node.exists { "" } else { ", style=dashed" };
+        lines.push(format!(
+            "  {} [label={}{style}];",
+            dot_string(&node.id),
+            dot_string(&node.name)
+        ));
+    }
+    for &(source, target) in edges.keys() {
+        lines.push(format!(
+            "  {} -> {};",
+            dot_string(&nodes[source].id),
+            dot_string(&nodes[target].id)
+        ));
+    }
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+fn mermaid(nodes: &[GraphNode], edges: &BTreeMap<(usize, usize), usize>) -> String {
+    let mut lines = vec!["flowchart LR".to_string()];
+    for (i, node) in nodes.iter().enumerate() {
+        let label = node.name.replace('"', "#quot;");
+        lines.push(format!("  n{i}[\"{label}\"]"));
+    }
+    for &(source, target) in edges.keys() {
+        lines.push(format!("  n{source} --> n{target}"));
+    }
+    let missing: Vec<String> = nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| !node.exists)
+        .map(|(i, _)| format!("n{i}"))
+        .collect();
+    if !missing.is_empty() {
+        lines.push("  classDef missing stroke-dasharray: 5 5".to_string());
+        lines.push(format!("  class {} missing", missing.join(",")));
+    }
+    lines.join("\n")
+}
+
+fn link_graph(vault: &Vault, format: GraphFormat, unresolved: bool) -> LinkGraphOutput {
+    let (nodes, edges) = graph(vault, unresolved);
+    let graph = match format {
+        GraphFormat::Json => None,
+        GraphFormat::Dot => Some(dot(&nodes, &edges)),
+        GraphFormat::Mermaid => Some(mermaid(&nodes, &edges)),
+    };
+    LinkGraphOutput {
+        orphans: nodes
+            .iter()
+            .filter(|node| node.exists && node.incoming == 0 && node.outgoing == 0)
+            .map(|node| node.id.clone())
+            .collect(),
+        edges: edges
+            .into_iter()
+            .map(|((source, target), count)| GraphEdge {
+                source: nodes[source].id.clone(),
+                target: nodes[target].id.clone(),
+                count,
+            })
+            .collect(),
+        nodes,
+        graph,
+    }
+}
+
+#[tool_router(router = graph_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Map the wiki-links between the notes of a directory (an Obsidian or Foam vault): nodes with their incoming and outgoing link counts, edges, orphan notes with no links either way, and the targets of links to notes that don't exist. Optionally also as Graphviz DOT or a Mermaid flowchart.",
+        output_schema = output_schema::<LinkGraphOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn link_graph(&self, Parameters(input): Parameters<LinkGraphInput>) -> McpResult {
+        let vault = Vault::read(&self.scoped_path(&input.directory)?)?;
+        json_result(&link_graph(
+            &vault,
+            input.format.unwrap_or_default(),
+            input.unresolved.unwrap_or(true),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::vault::Note;
+
+    fn vault() -> Vault {
+        Vault::new(vec![
+            Note::new("Home.md", "[[Ada]], [[ada|again]], [[Ghost]] and [[#Top]]"),
+            Note::new("people/Ada.md", "Back [[Home]]. Me: [[Ada]]"),
+            Note::new("Lonely.md", "No links."),
+        ])
+    }
+
+    #[test]
+    fn test_link_graph() {
+        let output = link_graph(&vault(), GraphFormat::Json, true);
+        assert_eq!(
+            output
+                .nodes
+                .iter()
+                .map(|n| (n.id.as_str(), n.exists, n.outgoing, n.incoming))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Home.md", true, 2, 1),
+                ("Lonely.md", true, 0, 0),
+                ("people/Ada.md", true, 1, 1),
+                ("Ghost", false, 0, 1),
+            ]
+        );
+        assert_eq!(
+            output.edges[0],
+            GraphEdge {
+                source: "Home.md".to_string(),
+                target: "people/Ada.md".to_string(),
+                count: 2,
+            }
+        );
+        assert_eq!(output.edges.len(), 3);
+        assert_eq!(output.orphans, vec!["Lonely.md"]);
+        assert_eq!(output.graph, None);
+
+        let output = link_graph(&vault(), GraphFormat::Json, false);
+        assert_eq!(output.nodes.len(), 3);
+        assert_eq!(output.edges.len(), 2);
+    }
+
+    #[test]
+    fn test_exports() {
+        let output = link_graph(&vault(), GraphFormat::Dot, true);
+        assert_eq!(
+            output.graph.unwrap(),
+            "digraph vault {\n  \
+             \"Home.md\" [label=\"Home\"];\n  \
+             \"Lonely.md\" [label=\"Lonely\"];\n  \
+             \"people/Ada.md\" [label=\"Ada\"];\n  \
+             \"Ghost\" [label=\"Ghost\", style=dashed];\n  \
+             \"Home.md\" -> \"people/Ada.md\";\n  \
+             \"Home.md\" -> \"Ghost\";\n  \
+             \"people/Ada.md\" -> \"Home.md\";\n\
+             }"
+        );
+        let output = link_graph(&vault(), GraphFormat::Mermaid, true);
+        assert_eq!(
+            output.graph.unwrap(),
+            "flowchart LR\n  \
+             n0[\"Home\"]\n  \
+             n1[\"Lonely\"]\n  \
+             n2[\"Ada\"]\n  \
+             n3[\"Ghost\"]\n  \
+             n0 --> n2\n  \
+             n0 --> n3\n  \
+             n2 --> n0\n  \
+             classDef missing stroke-dasharray: 5 5\n  \
+             class n3 missing"
+        );
+    }
+}
@@ -0,0 +1,890 @@
+//! Localized tool descriptions, server instructions and error messages.
+//!
+//! English text lives next to the code it describes (the `#[tool]`
+//! attributes and `ErrorData` constructors) and is the fallback for every
+//! locale; this module only holds the translations.
+
+use std::{fmt, str::FromStr};
+
+use rmcp::{ErrorData, model::Tool};
+
+/// Language used for text the server sends to clients, selected with
+/// `--locale`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Ja,
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// Accepts bare language codes as well as POSIX/BCP 47 style tags such
+    /// as `ja_JP.UTF-8` or `en-US`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Ok(Self::En),
+            "ja" => Ok(Self::Ja),
+            _ => Err(format!("unsupported locale `{s}` (expected `en` or `ja`)")),
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::En => "en",
+            Self::Ja => "ja",
+        })
+    }
+}
+
+const INSTRUCTIONS_EN: &str = "mq is a tool for processing markdown content with a jq-like syntax.";
+const INSTRUCTIONS_JA: &str =
+    "mq は jq に似た構文で Markdown コンテンツを処理するためのツールです。";
+
+const TOOL_DESCRIPTIONS_JA: &[(&str, &str)] = &[
+    (
+        "html_to_markdown",
+        "指定した HTML コンテンツに mq クエリを実行し、結果を Markdown として返します。available_selectors と available_functions ツールで一覧できるセレクタと関数を使用できます。スクリプトは削除され、残った生の HTML からはアクティブなコンテンツが取り除かれます。信頼できないページでは `strict` を指定すると生の HTML をすべて削除します。",
+    ),
+    (
+        "extract_markdown",
+        "任意の mq クエリで Markdown コンテンツから内容を抽出します。available_selectors と available_functions ツールで一覧できるセレクタと関数を使用できます。",
+    ),
+    (
+        "extract_markdown_v2",
+        "任意の mq クエリで Markdown コンテンツから内容を抽出し、各結果を抽出元の入力の行番号とともに返します。available_selectors と available_functions ツールで一覧できるセレクタと関数を使用できます。",
+    ),
+    (
+        "extract_headings",
+        "Markdown コンテンツからすべての見出し（h1〜h6）を抽出します。",
+    ),
+    (
+        "extract_code_blocks",
+        "Markdown コンテンツからすべてのフェンス付きコードブロックを抽出します。",
+    ),
+    (
+        "extract_todos",
+        "Markdown コンテンツから未完了のタスクリスト項目（TODO）をすべて抽出します。",
+    ),
+    (
+        "extract_done_tasks",
+        "Markdown コンテンツから完了済みのタスクリスト項目をすべて抽出します。",
+    ),
+    (
+        "extract_links",
+        "Markdown コンテンツからすべてのリンクを抽出します。",
+    ),
+    (
+        "extract_images",
+        "Markdown コンテンツからすべての画像を抽出します。",
+    ),
+    (
+        "extract_tables",
+        "Markdown コンテンツからすべての表を抽出します。",
+    ),
+    (
+        "extract_text",
+        "Markdown コンテンツから段落のテキストノードをすべて抽出します。",
+    ),
+    (
+        "extract_blockquotes",
+        "Markdown コンテンツからすべての引用ブロックを抽出します。",
+    ),
+    (
+        "extract_sections",
+        "Markdown コンテンツをセクション（見出しと本文）に分割し、すべて Markdown として返します。",
+    ),
+    (
+        "extract_section",
+        "タイトルを指定して Markdown コンテンツから特定のセクション（見出しと本文）を抽出します。見出しテキストに対して部分一致（大文字小文字を区別）で検索します。",
+    ),
+    (
+        "extract_toc",
+        "Markdown コンテンツの見出しから目次を生成します。インデントされた項目の一覧を返します。",
+    ),
+    (
+        "available_functions",
+        "mq クエリで使用できる関数の一覧を取得します。",
+    ),
+    (
+        "available_selectors",
+        "mq クエリで使用できるセレクタの一覧を取得します。",
+    ),
+    (
+        "trace_query",
+        "mq クエリをデバッグします。クエリをトップレベルの `|` で段階ごとに評価し、各段階の後の値を（サイズを制限して）返します。クエリが何も返さないときに、どの段階ですべての値が失われるかを調べるのに使います。",
+    ),
+    (
+        "lint_query",
+        "mq クエリを実行せずに静的にチェックします。構文エラー、不明なセレクタ、未解決の関数・変数、引数の数の不一致、非推奨の関数、未使用の束縛、到達不能なコードを、重大度と位置付きで報告します。",
+    ),
+    (
+        "check_compatibility",
+        "mq クエリ（直接指定、または保存されたクエリの名前で指定）が、このサーバーのエンジンのバージョンに存在する関数とセレクタだけを使っているかをチェックし、非推奨のものを報告します。異なるバージョンのサーバー間で保存されたクエリを共有する前に使います。",
+    ),
+    (
+        "run_example",
+        "mq チュートリアル（mq://tutorial 以下のリソース）の例を、そのレッスンのドキュメントで実行します。同じドキュメントで独自のクエリを試すこともできます。ドキュメント、クエリ、その結果を返します。",
+    ),
+    (
+        "cookbook",
+        "このサーバーで成功したクエリのうち、よく使われた形（--cookbook で記録され、文字列リテラルは伏せられます）を一覧表示します。各エントリには使用しているセレクタと関数、使用回数、使用されたツールが含まれます。クエリを書き始めるときの出発点として使い、ツールで絞り込むと特定のソースで有効なクエリがわかります。",
+    ),
+    (
+        "append_to_scratchpad",
+        "このセッションのスクラッチパッド（mq://scratchpad リソース）に Markdown を追記し、レポートを毎回渡し直すことなく複数の呼び出しにわたって組み立てます。独自の Markdown、それに対する mq クエリの結果、セッション変数に保存された結果、またはオフロードされたツール結果を、必要なら見出しの下に追記できます。",
+    ),
+    (
+        "edit_section",
+        "行番号ではなくセクション ID で Markdown 文書を編集します。セクションの置換、本文の置換や追記、前後への挿入、削除ができます。ID は outline、rank_sections、semantic_search、または以前の edit_section から得られ、文書の他の部分が変わっても有効なままです。編集後の文書（または差分や編集リスト）と、そのセクションの ID を返します。",
+    ),
+    (
+        "outline",
+        "Markdown 文書の見出しツリーをコンパクトな JSON で返します。各セクションの安定した ID、行番号、段落・コードブロック・リスト・表・単語の数を含みます。大きな文書を概観してから必要なセクションだけを取得するのに使えます。`max_depth` で深い見出しを親にまとめ、`section` で一つのセクションを展開します。",
+    ),
+    (
+        "compose_report",
+        "以前の呼び出しの結果（オフロードされた結果 mq://results/...、セッション変数、スクラッチパッド、インラインの Markdown）から一つの Markdown レポートを組み立てます。レイアウトの各セクションには見出し、任意の導入文、内容（mq クエリで絞り込み可能、見出しはセクションの下に入れ子になります）と出典を示すフッターが付き、レポートの先頭には任意のタイトルと目次が付きます。",
+    ),
+    (
+        "clear_scratchpad",
+        "このセッションのスクラッチパッド（mq://scratchpad リソース）を空にします。",
+    ),
+    (
+        "infer_query",
+        "求める出力の例から mq クエリを推測します。ドキュメントとその一部（例: 見出し。`## Install` またはテキストだけでも可）を指定すると、それとそれに似たノードを選択するクエリの候補を、最も狭いものから最も一般的なものまで、説明とドキュメント上で返す値の数付きで返します。クエリを書き始めるときに使い、trace_query や lint_query で仕上げます。",
+    ),
+    (
+        "summarize_section",
+        "Markdown ドキュメントの一部を要約します。セクションのタイトルまたは mq クエリで内容を抽出し、MCP サンプリングでクライアントの LLM に要約を依頼します。抽出したテキストと要約の両方を返します。サンプリングに対応したクライアントが必要です。タイトルが複数のセクションに一致する場合（またはタイトルもクエリも指定されていない場合）、クライアントがエリシテーションに対応していれば、ユーザーにセクションを選んでもらいます。",
+    ),
+    (
+        "query_url",
+        "URL を指定して Web ページ（HTML または Markdown）を取得し、Markdown に変換して mq クエリを実行します。ページの文字エンコーディングを検出して UTF-8 に変換します。HTML は html_to_markdown と同様にサニタイズされます。",
+    ),
+    (
+        "query_wiki",
+        "MediaWiki API を使って Wikipedia などの MediaWiki のページを取得し、Markdown に変換して mq クエリを実行します。リダイレクトは自動的にたどり、解決後のタイトルを `_meta` で返します。`section` を指定すると 1 つのセクションだけを取得します。",
+    ),
+    (
+        "query_github",
+        "GitHub リポジトリのファイル（既定では README）を、ブランチ・タグ・コミットを指定して GitHub API で取得し、mq クエリを実行します。ライブラリのインストール手順を一覧するといった用途に使います。",
+    ),
+    (
+        "query_confluence",
+        "Confluence のページを ID、またはスペースキーとタイトルで指定して Confluence REST API で取得し、Markdown に変換して mq クエリを実行します。mq-mcp を --confluence-url 付きで起動している必要があります。",
+    ),
+    (
+        "query_notion",
+        "Notion API で Notion のページ（ブロックを Markdown に変換）またはデータベース（行を Markdown の表に変換）を取得し、mq クエリを実行します。mq-mcp を --notion-token 付きで起動し、ページをそのインテグレーションと共有している必要があります。",
+    ),
+    (
+        "query_gdoc",
+        "Google Drive API で Google ドキュメントを Markdown（または HTML）としてエクスポートし、mq クエリを実行します。mq-mcp を --google-token 付きで起動している必要があります。",
+    ),
+    (
+        "slack_to_markdown",
+        "Slack のメッセージを Markdown に変換して mq クエリを実行します。メッセージの JSON を渡すか、メッセージのパーマリンクを渡して Slack API でスレッドを取得します（--slack-token が必要）。Slack の mrkdwn（*太字*、_斜体_、~取り消し線~、<url|label> リンク、メンション）を標準の Markdown に変換し、各メッセージを投稿者と日時の見出し付きのセクションにします。",
+    ),
+    (
+        "email_to_markdown",
+        "メール（RFC 822 / .eml、マルチパート MIME を含む）を Markdown に変換して mq クエリを実行します。件名・差出人・宛先・日付は YAML フロントマターになり、HTML またはプレーンテキストの本文は Markdown に変換され、添付ファイルは末尾に一覧表示されます。引用返信を取り除くこともできます。",
+    ),
+    (
+        "openapi_to_markdown",
+        "OpenAPI 3.x または Swagger 2.0 の仕様（JSON または YAML）を Markdown の API ドキュメントに変換して mq クエリを実行します。各エンドポイントはパラメーター・リクエストボディ・レスポンスの表を持つ `### METHOD /path` セクションになり、スキーマはそれぞれプロパティの表として `## Schemas` の下に一覧表示されます。",
+    ),
+    (
+        "parse_changelog",
+        "変更履歴（Keep a Changelog や conventional-changelog などの CHANGELOG.md）を解析し、バージョン・日付・取り下げ（yanked）フラグ・リンク・カテゴリ別の項目（Added、Fixed など）を持つリリースの一覧を返します。`since`（そのバージョンを含まない）と `until`（含む）でバージョンを絞り込めます。",
+    ),
+    (
+        "generate_release_notes",
+        "Conventional Commits 形式（`feat(scope)!: ...`）のコミットメッセージから Markdown のリリースノートを生成します。コミットは Breaking Changes、Features、Bug Fixes、Performance Improvements、Reverts（`all_types` 指定時はその他の種類も）に分類され、任意の mq クエリをリリースノートに実行します。レイアウトは conventional-changelog と同じなので、parse_changelog で読み戻せます。",
+    ),
+    (
+        "markdown_to_text",
+        "Markdown を SMS・チャット・ターミナル向けのプレーンテキストに変換します。マークアップを取り除き、リストの記号と番号を描画し、表は 1 行ずつ平坦化するか ASCII の枠で描画し、リンクはインライン・番号付き脚注・テキストのみのいずれかで表示します。任意の mq クエリで変換対象を選べます。",
+    ),
+    (
+        "ast_json",
+        "Markdown の構文木を JSON で返します。mdast のルートノード（remark や markdown-rs が使う形式）か mq 自身のノード配列を選べます。任意の mq クエリで返す部分を選べます。",
+    ),
+    (
+        "from_ast_json",
+        "JSON の構文木を Markdown に戻します。mdast（ast_json、remark、markdown-rs が出力する形式）と mq のノード配列に対応します。ast_json と組み合わせてノード単位で文書を編集できます。任意の mq クエリを結果に実行できます。",
+    ),
+    (
+        "pandoc_to_markdown",
+        "pandoc が読める形式（reStructuredText、Org、LaTeX、MediaWiki、DOCX、EPUB、pandoc の JSON AST など）の文書を Markdown に変換し、mq クエリを実行します。バイナリ形式は base64 で渡します。",
+    ),
+    (
+        "markdown_to_pandoc",
+        "Markdown を pandoc が書き出せる形式（reStructuredText、Org、LaTeX、HTML、AsciiDoc、DOCX、pandoc の JSON AST など）に変換します。任意の mq クエリで変換する部分を選べます。バイナリ形式は base64 で返します。",
+    ),
+    (
+        "extract_diagrams",
+        "Markdown から mermaid と PlantUML の図（フェンス付きコードブロック）を、直前の見出しから取ったタイトルと行番号付きで抽出します。mermaid の構文（図の種類、括弧、閉じていないブロック）と PlantUML の @start/@end タグを任意で検証でき、図ごとに別のコンテンツとして返すこともできます。",
+    ),
+    (
+        "extract_math",
+        "Markdown 内の `$...$` と `$$...$$` の数式を一覧にします。各数式の TeX ソース、行番号、直前の見出し、前後の段落を返します。数式は HTML 変換を通さず、書かれたとおりに読み取ります。",
+    ),
+    (
+        "extract_definitions",
+        "Markdown 文書から用語と定義の組を抽出し、用語集の作成に使えるようにします。定義リスト（用語の行と `: 定義` の行）、`用語: 説明` の段落やリスト項目、太字の用語で始まる段落を読み取ります。各組には行番号と直前の見出しが付きます。",
+    ),
+    (
+        "extract_admonitions",
+        "Markdown 文書の GitHub（`> [!NOTE]`）、MkDocs（`!!! note`）、Docusaurus（`:::note`）形式の注記（コールアウト）を一覧にします。mq のクエリでは引用や段落として扱われるブロックです。各注記の形式、種類、タイトル、行番号、内容を返し、種類で絞り込んだり内容に mq クエリを実行したりできます。",
+    ),
+    (
+        "convert_admonitions",
+        "Markdown 文書の注記（コールアウト）を GitHub（`> [!NOTE]`）、MkDocs（`!!! note`）、Docusaurus（`:::note`）のいずれかの形式に変換します。種類は変換先が対応するものに対応付けられます。GitHub とドキュメントサイトの間で文書を移すときなどに使えます。",
+    ),
+    (
+        "extract_wikilinks",
+        "Obsidian や Foam のノートのウィキリンク（`[[Page]]`、`[[Page#Heading|alias]]`、埋め込み `![[Page]]`）とタグ（`#tag`、`#nested/tag`、フロントマターの `tags`）を一覧にします。mq のクエリではただのテキストとして扱われるものです。リンクはリンク先で、タグは名前で絞り込めます。",
+    ),
+    (
+        "backlinks",
+        "ディレクトリ（Obsidian や Foam の Vault）内で、ウィキリンクや埋め込みでノートにリンクしているノートを探し、各リンクの行番号と行のテキストを返します。リンクは Obsidian と同じく、ノート名、または指定があればパスで解決されます。",
+    ),
+    (
+        "link_graph",
+        "ディレクトリ（Obsidian や Foam の Vault）のノート間のウィキリンクをグラフにします。各ノードの入出力リンク数、エッジ、どことも繋がっていない孤立ノート、存在しないノートへのリンク先を返し、Graphviz の DOT や Mermaid のフローチャートとしても出力できます。",
+    ),
+    (
+        "vault_query",
+        "ディレクトリ（Obsidian や Foam の Vault）内のノートのフロントマターを、Dataview のように表として問い合わせます。1 ノートが 1 行、各フィールドが列になります。フィールドに対する mq の条件式で行を絞り込み、フィールドで並べ替え、列を選んで、JSON か Markdown の表で返します。",
+    ),
+    (
+        "aggregate_tasks",
+        "ディレクトリ（Obsidian や Foam の Vault）内の全ノートのタスクリスト項目（`- [ ]`、`- [x]`）を集め、ノート、タグ、期日ごとにまとめた TODO レポートにします。期日は Obsidian Tasks、Dataview、todo.txt、TaskPaper の書式から読み取ります。状態、タグ、期日で絞り込めます。",
+    ),
+    (
+        "daily_note",
+        "設定された Vault から指定日（既定は今日）のデイリーノートを取得します。存在しない場合は、Obsidian のデイリーノートプラグインと同じく、デイリーノートのテンプレートから作成します。",
+    ),
+    (
+        "append_to_daily_note",
+        "設定された Vault の指定日（既定は今日）のデイリーノートに、ノートの末尾または見出しの下のセクションの末尾へ項目を追記します。ノートが存在しない場合はテンプレートから作成します。エージェントが付ける日誌やログに使います。",
+    ),
+    (
+        "list_templates",
+        "設定されたテンプレートディレクトリのノートテンプレートを、それぞれのプレースホルダーとともに一覧表示します。",
+    ),
+    (
+        "render_template",
+        "設定されたテンプレートディレクトリのノートテンプレートを、ノートを作成せずに埋めます。`{{date}}`、`{{time}}`、`{{title}}` を埋め、`{{query:...}}` はソース文書に対する mq クエリの結果で、その他のプレースホルダーは指定したフィールドまたはテンプレートのフロントマターで埋めます。",
+    ),
+    (
+        "create_note",
+        "設定された Vault に、ノートテンプレートから render_template と同じくプレースホルダーを埋めてノートを作成します。`overwrite` を指定しない限り既存のノートは置き換えません。",
+    ),
+    (
+        "citations",
+        "Markdown 内の pandoc 形式の引用（`[@doe99, p. 33]`、`[-@doe99]`、本文中の `@doe99`）を BibTeX の参考文献と照合します。引用されたキーとその回数、エントリのないキー、一度も引用されていないエントリ、重複するキーを返します。引用したエントリの参考文献セクションも生成できます。学術文書の引用の整合性チェックに使います。",
+    ),
+    (
+        "detect_languages",
+        "Markdown の段落、見出し、リスト項目、表のセル、引用ブロック（または mq クエリの各結果）の自然言語を判定し、ISO 639-3 コード、文字体系、確信度と言語ごとの件数を返します。翻訳の前に多言語文書を振り分けるのに使います。",
+    ),
+    (
+        "extract_by_language",
+        "Markdown の段落、見出し、リスト項目、表のセル、引用ブロック（または mq クエリの結果）のうち、指定した言語で書かれたものを抽出します（例: `[\"jpn\"]`、`[\"German\"]`）。言語を判定できない値は除外されます。",
+    ),
+    (
+        "segment_for_translation",
+        "Markdown を翻訳単位に分割します。各見出し、段落、表のセル（または段落の各文）のテキストを、インラインの Markdown と安定した ID 付きで返します。コード、数式、HTML、フロントマターは含みません。翻訳した単位は reassemble_translation で文書に戻します。",
+    ),
+    (
+        "reassemble_translation",
+        "segment_for_translation の単位とその翻訳から Markdown 文書を再構成します。置き換えるのは単位のテキストだけなので、見出し、リスト、表、コード、リンクの構造は元のまま保たれます。翻訳のない単位は元のテキストのまま `untranslated` に列挙されます。",
+    ),
+    (
+        "keywords",
+        "1 つ以上の Markdown 文書のキーワードを、出現頻度または TF-IDF で順位付けします。ストップワードは言語ごと（自動判定または指定）に除外されます。全文書と文書ごとの上位キーワードを返します。mq クエリで文書の一部（見出しなど）に絞り込めます。",
+    ),
+    (
+        "find_duplicates",
+        "複数の Markdown 文書の内部および文書間で、重複またはほぼ重複している段落やセクションを検出します。大文字小文字、句読点、空白を正規化して比較し、ほぼ重複は単語トライグラムの重なりで評価します。各結果には出現ごとの文書番号、行、直前の見出しが含まれます。",
+    ),
+    (
+        "rank_sections",
+        "Markdown をセクション（見出しと次の見出しまでの本文）に分割し、検索テキストとの語彙的な関連度（BM25）で順位付けして、上位のセクションをスコアと一致した語とともに返します。ベクトルデータベースなしで大きな文書から安価に検索できます。",
+    ),
+    (
+        "section_summaries",
+        "Markdown 文書の各セクションを抽出型で要約します。最初の文と、セクション内で繰り返し使われる語を多く含む文を文書順に並べます。言語モデルを呼ばない決定的で安価な処理で、内容を完全に抽出する前のプレビューとして使えます。",
+    ),
+    (
+        "embed_sections",
+        "Markdown 文書をセクション（見出しと次の見出しまでの本文）に分割し、設定された埋め込みエンドポイントで各セクションの埋め込みを計算して、このセッションの semantic_search 用に保持します。同じ名前で再度埋め込むと置き換えられます。",
+    ),
+    (
+        "semantic_search",
+        "このセッションで embed_sections により埋め込んだ文書から、検索テキストと意味的に最も近いセクションを探し、コサイン類似度とともに上位のセクションを返します。文書全体をモデルに送らずに検索できます。",
+    ),
+    (
+        "index_directory",
+        "ディレクトリ内の Markdown ファイルで永続的な全文検索インデックスを作成・更新し、search_index で検索できるようにします。更新日時とサイズが変わっていないファイルはスキップし、存在しなくなったファイルは削除します。mq-mcp を --index <path> 付きで起動している必要があります。",
+    ),
+    (
+        "search_index",
+        "index_directory で作成した永続的な全文検索インデックスを検索し、BM25 で順位付けした上位のセクション（見出しと本文）をファイルから読み込んで返します。mq クエリで各結果を後処理できます（例: コードブロックだけを返す）。",
+    ),
+    (
+        "index_status",
+        "永続的な検索インデックスの状態を報告します。インデックス対象のディレクトリ、文書数とセクション数、ディレクトリを監視しているか、適用待ちの変更数、古くなったファイル数、最終更新日時を返します。",
+    ),
+    (
+        "server_stats",
+        "サーバーの稼働時間、開いているクライアントセッション数、ツールごとの呼び出し回数・エラー数・平均レイテンシ、キャッシュ (URL ツールが取得したページ、再送されたべき等キー) のヒット率を、サーバー起動以降の全セッション分集計して報告します。サーバーの負荷に合わせて、たとえばキャッシュ済みのページを優先するといった判断に使えます。",
+    ),
+    (
+        "set_variable",
+        "マークダウンに mq クエリを実行し、その結果を名前を付けてセッションに保存します。以降のクエリではどのツールでもその名前を保存された結果（文字列）の配列として参照できます。例えば `.h1 | to_text()` を `titles` として保存し、後で `join(titles, \", \")` を実行できるため、途中の結果をやり取りする必要がありません。変数はセッションの間保持されます。",
+    ),
+    (
+        "get_variable",
+        "set_variable でセッション変数に保存された結果を返します。",
+    ),
+    (
+        "run_pipeline",
+        "マークダウンに対して一連のステップをサーバー側で順に実行し、各ステップの出力を次のステップに渡します。ステップには mq クエリ（ノードごとまたは集約）、後処理の変換、形式の変換（markdown、text、html、json）があります。ステップごとにツールを呼び出して大きなデータをやり取りする代わりに、最終的な値と、必要に応じてステップごとの件数だけを返します。クエリではセッション変数を使用できます。",
+    ),
+    (
+        "schedule_pipeline",
+        "run_pipeline のステップからなるパイプラインを保存し、URL またはファイルに対して cron スケジュールで実行します（例: 変更履歴の監視）。保存時に一度実行され、最新の結果はリソース mq://pipelines/<name> として公開されます。このリソースを購読しているクライアントには、実行で結果が変わったときに通知されます。digest を指定すると、結果の代わりに前回の実行から追加・変更・削除されたセクションのダイジェストを公開し、リソース mq://digests にまとめます。webhook を指定すると、各実行の結果がその URL に POST されます。HTTP トランスポートでのみ使用できます。",
+    ),
+    (
+        "unschedule_pipeline",
+        "schedule_pipeline で保存したパイプラインを停止し、その結果のリソースとともに削除します。",
+    ),
+    (
+        "list_scheduled_pipelines",
+        "schedule_pipeline で保存したパイプラインを、スケジュール、ソース、次回と前回の実行日時、結果の件数、直前のエラーとともに一覧表示します。",
+    ),
+    (
+        "save_query",
+        "mq クエリに名前を付けて保存し、このセッションや他のセッションから run_saved_query で再実行できるようにします。永続的な状態ストア（--state）を使用している場合、保存したクエリは再起動後も残ります。",
+    ),
+    (
+        "run_saved_query",
+        "save_query で保存したクエリをマークダウンに対して実行し、extract_markdown と同様に空でない結果を返します。",
+    ),
+    (
+        "list_saved_queries",
+        "save_query で保存したクエリを説明とともに一覧表示します。",
+    ),
+    (
+        "delete_saved_query",
+        "save_query で保存したクエリを削除します。",
+    ),
+    (
+        "export_state",
+        "サーバーの保存されたクエリ、スケジュールされたパイプライン、設定を JSON バンドルとしてエクスポートします。バージョン管理などに利用できます。バンドルに認証情報は含まれません。import_state に渡すと、このサーバーや別のサーバーに復元できます。",
+    ),
+    (
+        "import_state",
+        "export_state で書き出したバンドルをインポートします。クエリとパイプラインを保存して同じ名前のものを置き換え、`replace` を指定するとバンドルにないものを削除します。すべてのクエリとパイプラインが有効な場合にのみインポートされます。設定はサーバーのコマンドラインフラグで決まるため、報告されるだけで変更されません。",
+    ),
+    (
+        "db_sql",
+        "読み込まれた mq-db データベースに読み取り専用の SQL クエリを実行し、一致する行を JSON で返します。mq-mcp を --db <path> 付きで起動している必要があります。",
+    ),
+    (
+        "db_mq",
+        "読み込まれた mq-db データベースのすべてのドキュメントに mq プログラムを実行し、結果を返します。mq-mcp を --db <path> 付きで起動している必要があります。",
+    ),
+    (
+        "db_list_documents",
+        "読み込まれた mq-db データベースに現在インデックスされているすべてのドキュメント（id、パス、タイトル、タグ、ブロック数）を一覧表示します。mq-mcp を --db <path> 付きで起動している必要があります。",
+    ),
+    (
+        "db_stats",
+        "読み込まれた mq-db データベースのブロック種別とコード言語の統計を返します。mq-mcp を --db <path> 付きで起動している必要があります。",
+    ),
+    (
+        "db_index",
+        "Markdown ファイル・ディレクトリを読み込まれた mq-db データベースに（再）インデックスし、設定された --db のパスに保存します。前回のインデックス以降に内容が変わっていないファイルはスキップします。`prune` を指定すると、ファイルが存在しなくなったドキュメントを削除します。mq-mcp を --db <path> 付きで起動している必要があります。",
+    ),
+];
+
+/// Error messages, matched as a prefix of the English message so that
+/// messages with appended detail (e.g. "did you mean" hints) still translate.
+const ERROR_MESSAGES_JA: &[(&str, &str)] = &[
+    ("Failed to query", "クエリの実行に失敗しました"),
+    ("Failed to parse markdown", "Markdown の解析に失敗しました"),
+    ("Failed to parse html", "HTML の解析に失敗しました"),
+    (
+        "no database configured — restart mq-mcp with --db <path> to enable db_* tools",
+        "データベースが設定されていません — db_* ツールを有効にするには mq-mcp を --db <path> 付きで再起動してください",
+    ),
+    (
+        "Failed to build SQL engine",
+        "SQL エンジンの構築に失敗しました",
+    ),
+    ("SQL query failed", "SQL クエリの実行に失敗しました"),
+    ("mq query failed", "mq クエリの実行に失敗しました"),
+    (
+        "No Markdown files found in the given paths",
+        "指定されたパスに Markdown ファイルが見つかりません",
+    ),
+    ("Reindex failed", "再インデックスに失敗しました"),
+    (
+        "Path is outside the client's workspace roots",
+        "パスがクライアントのワークスペースルートの外にあります",
+    ),
+    (
+        "Path names a reserved Windows device",
+        "パスが Windows の予約済みデバイス名を指しています",
+    ),
+    (
+        "Failed to save database",
+        "データベースの保存に失敗しました",
+    ),
+    (
+        "no search index configured — restart mq-mcp with --index <path> to enable index_directory, search_index and index_status",
+        "検索インデックスが設定されていません — index_directory、search_index、index_status を有効にするには mq-mcp を --index <path> 付きで再起動してください",
+    ),
+    (
+        "Failed to load search index",
+        "検索インデックスの読み込みに失敗しました",
+    ),
+    (
+        "Failed to save search index",
+        "検索インデックスの保存に失敗しました",
+    ),
+    ("Not a directory", "ディレクトリではありません"),
+    (
+        "The client does not support sampling",
+        "クライアントがサンプリングに対応していません",
+    ),
+    (
+        "Either `title` or `query` is required",
+        "`title` または `query` のいずれかが必要です",
+    ),
+    (
+        "Nothing to summarize: the query returned no content",
+        "要約する内容がありません: クエリが何も返しませんでした",
+    ),
+    (
+        "Sampling request failed",
+        "サンプリングのリクエストに失敗しました",
+    ),
+    (
+        "Failed to build elicitation schema",
+        "エリシテーションのスキーマの構築に失敗しました",
+    ),
+    (
+        "Elicitation request failed",
+        "エリシテーションのリクエストに失敗しました",
+    ),
+    (
+        "Session quota exceeded",
+        "セッションのクォータを超過しました",
+    ),
+    (
+        "Invalid `postprocess` argument",
+        "`postprocess` 引数が不正です",
+    ),
+    (
+        "Invalid `provenance` argument",
+        "`provenance` 引数が不正です",
+    ),
+    ("Invalid `dry_run` argument", "`dry_run` 引数が不正です"),
+    (
+        "`sort_by: expression` requires a `sort_key` expression",
+        "`sort_by: expression` には `sort_key` の式が必要です",
+    ),
+    (
+        "Invalid `idempotency_key` argument",
+        "`idempotency_key` 引数が不正です",
+    ),
+    (
+        "Invalid `correlation_id` argument",
+        "`correlation_id` 引数が不正です",
+    ),
+    ("Invalid `profile` argument", "`profile` 引数が不正です"),
+    ("Unknown profile", "不明なプロファイルです"),
+    (
+        "Idempotency key was already used with different arguments",
+        "この冪等性キーは別の引数で既に使用されています",
+    ),
+    (
+        "A call with this idempotency key is still in progress",
+        "この冪等性キーによる呼び出しはまだ実行中です",
+    ),
+    ("Invalid variable name", "変数名が不正です"),
+    ("Too many session variables", "セッション変数が多すぎます"),
+    ("Unknown session variable", "不明なセッション変数です"),
+    (
+        "Too many pipeline steps",
+        "パイプラインのステップが多すぎます",
+    ),
+    (
+        "Scheduled pipelines require the HTTP transport (--http)",
+        "スケジュールされたパイプラインには HTTP トランスポート（--http）が必要です",
+    ),
+    ("Invalid pipeline name", "パイプライン名が不正です"),
+    ("Invalid cron expression", "cron 式が不正です"),
+    (
+        "Give exactly one of `query` or `name`",
+        "`query` と `name` のどちらか一方だけを指定してください",
+    ),
+    (
+        "Example not found in the document",
+        "例がドキュメント内に見つかりません",
+    ),
+    (
+        "Tutorial example not found",
+        "チュートリアルの例が見つかりません",
+    ),
+    (
+        "The cookbook is disabled; start the server with --cookbook",
+        "クックブックは無効です。--cookbook を付けてサーバーを起動してください",
+    ),
+    (
+        "Failed to run an inferred query",
+        "推測したクエリの実行に失敗しました",
+    ),
+    ("The scratchpad is full", "スクラッチパッドがいっぱいです"),
+    ("Stored result not found", "保存された結果が見つかりません"),
+    (
+        "Too many report sections",
+        "レポートのセクションが多すぎます",
+    ),
+    ("Section not found", "セクションが見つかりません"),
+    (
+        "`text` is required for this action",
+        "この操作には `text` が必要です",
+    ),
+    (
+        "Failed to read the directory",
+        "ディレクトリの読み込みに失敗しました",
+    ),
+    (
+        "Too many notes in the directory",
+        "ディレクトリ内のノートが多すぎます",
+    ),
+    (
+        "Invalid date, expected YYYY-MM-DD",
+        "日付が不正です。YYYY-MM-DD 形式で指定してください",
+    ),
+    (
+        "no vault configured — restart mq-mcp with --vault <dir> to enable daily_note, append_to_daily_note and create_note",
+        "Vault が設定されていません — daily_note、append_to_daily_note、create_note を有効にするには mq-mcp を --vault <dir> 付きで再起動してください",
+    ),
+    (
+        "Invalid daily note format",
+        "デイリーノートのファイル名の書式が不正です",
+    ),
+    (
+        "Failed to read the daily note template",
+        "デイリーノートのテンプレートの読み込みに失敗しました",
+    ),
+    (
+        "Failed to read the daily note",
+        "デイリーノートの読み込みに失敗しました",
+    ),
+    (
+        "Failed to write the daily note",
+        "デイリーノートの書き込みに失敗しました",
+    ),
+    (
+        "no template directory configured — restart mq-mcp with --templates <dir> to enable list_templates, render_template and create_note",
+        "テンプレートディレクトリが設定されていません — list_templates、render_template、create_note を有効にするには mq-mcp を --templates <dir> 付きで再起動してください",
+    ),
+    ("Template not found", "テンプレートが見つかりません"),
+    (
+        "Invalid date format in the template",
+        "テンプレートの日付書式が不正です",
+    ),
+    ("Invalid note path", "ノートのパスが不正です"),
+    ("Note already exists", "ノートはすでに存在します"),
+    ("Failed to write the note", "ノートの書き込みに失敗しました"),
+    ("Invalid BibTeX", "BibTeX が不正です"),
+    (
+        "Give exactly one of `bibliography` or `bibtex`",
+        "`bibliography` と `bibtex` のいずれか一つだけを指定してください",
+    ),
+    (
+        "Give exactly one of `markdown`, `variable` or `result`",
+        "`markdown`、`variable`、`result` のいずれか一つだけを指定してください",
+    ),
+    (
+        "Give exactly one of `url` or `path`",
+        "`url` と `path` のどちらか一方だけを指定してください",
+    ),
+    (
+        "Too many scheduled pipelines",
+        "スケジュールされたパイプラインが多すぎます",
+    ),
+    (
+        "Scheduled pipeline not found",
+        "スケジュールされたパイプラインが見つかりません",
+    ),
+    ("Failed to read file", "ファイルの読み込みに失敗しました"),
+    (
+        "no webhook hosts configured — restart mq-mcp with --webhook-host <host> to enable webhooks",
+        "Webhook の送信先ホストが設定されていません — Webhook を有効にするには mq-mcp を --webhook-host <host> 付きで再起動してください",
+    ),
+    (
+        "Webhook host not allowed",
+        "Webhook の送信先ホストが許可されていません",
+    ),
+    ("Invalid query name", "クエリ名が不正です"),
+    ("Invalid mq query", "mq クエリが不正です"),
+    ("Saved query not found", "保存されたクエリが見つかりません"),
+    (
+        "Failed to access the state store",
+        "状態ストアへのアクセスに失敗しました",
+    ),
+    (
+        "Unsupported state bundle version",
+        "サポートされていない状態バンドルのバージョンです",
+    ),
+    (
+        "Scheduled pipelines aren't built in — install mq-mcp with the fetch feature to import them",
+        "スケジュールされたパイプラインはこのビルドに含まれていません — インポートするには fetch フィーチャー付きで mq-mcp をインストールしてください",
+    ),
+    ("Invalid URL", "URL が不正です"),
+    (
+        "Only http and https URLs can be fetched",
+        "取得できるのは http と https の URL のみです",
+    ),
+    ("Failed to fetch URL", "URL の取得に失敗しました"),
+    (
+        "Disallowed by robots.txt",
+        "robots.txt により禁止されています",
+    ),
+    ("Not a wiki page URL", "wiki ページの URL ではありません"),
+    ("Section not found", "セクションが見つかりません"),
+    ("MediaWiki API error", "MediaWiki API のエラーです"),
+    (
+        "Invalid repository; expected `owner/name`",
+        "リポジトリの指定が不正です。`owner/name` の形式で指定してください",
+    ),
+    (
+        "Invalid GitHub token in the server configuration",
+        "サーバー設定の GitHub トークンが不正です",
+    ),
+    (
+        "no Confluence site configured — restart mq-mcp with --confluence-url <url> to enable query_confluence",
+        "Confluence のサイトが設定されていません — query_confluence を有効にするには mq-mcp を --confluence-url <url> 付きで再起動してください",
+    ),
+    (
+        "Either `page_id` or `space` and `title` is required",
+        "`page_id`、または `space` と `title` のいずれかが必要です",
+    ),
+    (
+        "no Notion token configured — restart mq-mcp with --notion-token <token> to enable query_notion",
+        "Notion のトークンが設定されていません — query_notion を有効にするには mq-mcp を --notion-token <token> 付きで再起動してください",
+    ),
+    (
+        "no Google OAuth token configured — restart mq-mcp with --google-token <token> to enable query_gdoc",
+        "Google の OAuth トークンが設定されていません — query_gdoc を有効にするには mq-mcp を --google-token <token> 付きで再起動してください",
+    ),
+    (
+        "Invalid Google OAuth token in the server configuration",
+        "サーバー設定の Google OAuth トークンが不正です",
+    ),
+    (
+        "Invalid Google Docs document id",
+        "Google ドキュメントの ID が不正です",
+    ),
+    (
+        "no Slack token configured — restart mq-mcp with --slack-token <token> to fetch threads",
+        "Slack のトークンが設定されていません — スレッドを取得するには mq-mcp を --slack-token <token> 付きで再起動してください",
+    ),
+    (
+        "Invalid Slack token in the server configuration",
+        "サーバー設定の Slack トークンが不正です",
+    ),
+    ("Invalid Slack permalink", "Slack のパーマリンクが不正です"),
+    ("Invalid Slack API response", "Slack API の応答が不正です"),
+    ("Slack API error", "Slack API のエラーです"),
+    (
+        "Either `messages` or `thread` is required",
+        "`messages` または `thread` のいずれかが必要です",
+    ),
+    (
+        "Failed to parse the spec as JSON or YAML",
+        "仕様を JSON または YAML として解析できませんでした",
+    ),
+    (
+        "Not an OpenAPI or Swagger spec",
+        "OpenAPI または Swagger の仕様ではありません",
+    ),
+    (
+        "Failed to serialize the syntax tree",
+        "構文木をシリアライズできませんでした",
+    ),
+    ("Invalid mq node tree", "mq のノードツリーが不正です"),
+    ("Invalid pandoc format", "pandoc の形式名が不正です"),
+    ("Unknown language", "不明な言語です"),
+    (
+        "Unknown translation unit ids",
+        "不明な翻訳単位の ID があります",
+    ),
+    ("Failed to run pandoc", "pandoc を実行できませんでした"),
+    ("pandoc timed out", "pandoc がタイムアウトしました"),
+    ("pandoc failed", "pandoc がエラーで終了しました"),
+    ("Invalid base64 input", "base64 の入力が不正です"),
+    ("Invalid Notion id", "Notion の ID が不正です"),
+    (
+        "no embeddings endpoint configured — restart mq-mcp with --embeddings-url <url> to enable embed_sections and semantic_search",
+        "埋め込みエンドポイントが設定されていません — embed_sections と semantic_search を有効にするには mq-mcp を --embeddings-url <url> 付きで再起動してください",
+    ),
+    (
+        "Invalid embeddings API key in the server configuration",
+        "サーバー設定の埋め込み API キーが不正です",
+    ),
+    (
+        "Invalid embeddings API response",
+        "埋め込み API の応答が不正です",
+    ),
+    (
+        "No sections embedded in this session; call embed_sections first",
+        "このセッションで埋め込まれたセクションがありません。先に embed_sections を呼び出してください",
+    ),
+    ("Invalid Notion API response", "Notion API の応答が不正です"),
+    (
+        "Invalid Notion token in the server configuration",
+        "サーバー設定の Notion トークンが不正です",
+    ),
+    (
+        "Invalid Confluence page id",
+        "Confluence のページ ID が不正です",
+    ),
+    (
+        "Confluence page not found",
+        "Confluence のページが見つかりません",
+    ),
+    (
+        "Invalid Confluence API response",
+        "Confluence API の応答が不正です",
+    ),
+    (
+        "Invalid Confluence token in the server configuration",
+        "サーバー設定の Confluence トークンが不正です",
+    ),
+    (
+        "Invalid GitHub API URL in the server configuration",
+        "サーバー設定の GitHub API URL が不正です",
+    ),
+    (
+        "Invalid MediaWiki API response",
+        "MediaWiki API の応答が不正です",
+    ),
+    (
+        "Host temporarily unavailable",
+        "ホストが一時的に利用できません",
+    ),
+    (
+        "Custom headers are not allowed for this host",
+        "このホストにはカスタムヘッダーを送信できません",
+    ),
+    (
+        "Header not allowed by the server configuration",
+        "サーバーの設定で許可されていないヘッダーです",
+    ),
+    ("Invalid header", "ヘッダーが不正です"),
+    (
+        "Result not found (it may have been evicted)",
+        "結果が見つかりません（破棄された可能性があります）",
+    ),
+];
+
+impl Locale {
+    pub(crate) fn instructions(self) -> &'static str {
+        match self {
+            Self::En => INSTRUCTIONS_EN,
+            Self::Ja => INSTRUCTIONS_JA,
+        }
+    }
+
+    /// Replaces `tool`'s description with its translation, if there is one.
+    pub(crate) fn localize_tool(self, mut tool: Tool) -> Tool {
+        let translations = match self {
+            Self::En => return tool,
+            Self::Ja => TOOL_DESCRIPTIONS_JA,
+        };
+        if let Some((_, description)) = translations.iter().find(|(name, _)| *name == tool.name) {
+            tool.description = Some((*description).into());
+        }
+        tool
+    }
+
+    /// Translates the message of an error raised by this server. Unknown
+    /// messages (e.g. from rmcp itself) are left in English.
+    pub(crate) fn localize_error(self, mut error: ErrorData) -> ErrorData {
+        let translations = match self {
+            Self::En => return error,
+            Self::Ja => ERROR_MESSAGES_JA,
+        };
+        if let Some((english, translated)) = translations
+            .iter()
+            .find(|(english, _)| error.message.starts_with(english))
+        {
+            error.message = format!("{translated}{}", &error.message[english.len()..]).into();
+        }
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("en", Ok(Locale::En))]
+    #[case("ja", Ok(Locale::Ja))]
+    #[case("ja_JP.UTF-8", Ok(Locale::Ja))]
+    #[case("EN-us", Ok(Locale::En))]
+    #[case("fr", Err(()))]
+    fn test_parse_locale(#[case] input: &str, #[case] expected: Result<Locale, ()>) {
+        assert_eq!(input.parse::<Locale>().map_err(|_| ()), expected);
+    }
+
+    #[test]
+    fn test_localize_error_keeps_suffix() {
+        let error = ErrorData::invalid_request("Failed to query (did you mean `upcase`?)", None);
+        let localized = Locale::Ja.localize_error(error);
+        assert_eq!(
+            localized.message,
+            "クエリの実行に失敗しました (did you mean `upcase`?)"
+        );
+    }
+
+    #[test]
+    fn test_localize_error_passes_through_english() {
+        let error = ErrorData::invalid_request("Failed to query", None);
+        assert_eq!(Locale::En.localize_error(error).message, "Failed to query");
+    }
+
+    #[test]
+    fn test_every_tool_has_japanese_description() {
+        let server = super::super::Server::new(None).unwrap();
+        for tool in server.tool_router.list_all() {
+            assert!(
+                TOOL_DESCRIPTIONS_JA
+                    .iter()
+                    .any(|(name, _)| *name == tool.name),
+                "missing Japanese description for `{}`",
+                tool.name
+            );
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Idempotency keys for mutating tools: a call to a tool that writes files
+//! or changes session state may carry the reserved `idempotency_key`
+//! argument, and a repeated call with the same key (e.g. a retry after a
+//! transport error) gets the first call's result back instead of running
+//! again.
+//!
+//! [`Server::call_tool`](rmcp::ServerHandler::call_tool) strips the argument
+//! before the arguments reach the tool. Keys are remembered per session for
+//! [`ServerConfig::idempotency_ttl`](super::ServerConfig::idempotency_ttl).
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rmcp::{
+    ErrorData,
+    model::{CallToolResult, JsonObject, Tool},
+};
+
+use super::dry_run::mutates;
+
+/// Name of the per-call argument accepted by every mutating tool.
+pub(crate) const ARGUMENT: &str = "idempotency_key";
+
+/// Default for [`ServerConfig::idempotency_ttl`](super::ServerConfig::idempotency_ttl).
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Removes the [`ARGUMENT`] from a call's arguments and returns the key.
+pub(crate) fn take_argument(
+    arguments: &mut Option<JsonObject>,
+) -> Result<Option<String>, ErrorData> {
+    match arguments
+        .as_mut()
+        .and_then(|arguments| arguments.remove(ARGUMENT))
+    {
+        None => Ok(None),
+        Some(serde_json::Value::String(key)) if !key.is_empty() => Ok(Some(key)),
+        Some(value) => Err(ErrorData::invalid_params(
+            "Invalid `idempotency_key` argument",
+            Some(serde_json::json!({ "expected": "non-empty string", "got": value })),
+        )),
+    }
+}
+
+/// Advertises the [`ARGUMENT`] in the input schema of mutating tools.
+pub(crate) fn describe_argument(mut tool: Tool) -> Tool {
+    if !mutates(&tool) {
+        return tool;
+    }
+    let mut schema = (*tool.input_schema).clone();
+    if let Some(serde_json::Value::Object(properties)) = schema.get_mut("properties") {
+        properties.insert(
+            ARGUMENT.to_string(),
+            serde_json::json!({
+                "type": "string",
+                "description": "A unique key for this operation, e.g. a UUID. Retrying with the same key and arguments returns the first call's result instead of running the tool again",
+            }),
+        );
+    }
+    tool.input_schema = Arc::new(schema);
+    tool
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// The call's arguments, serialized; a key may only be reused with the
+    /// same arguments.
+    arguments: String,
+    started: Instant,
+    /// `None` while the first call is running.
+    result: Option<CallToolResult>,
+}
+
+/// What to do with a call carrying an idempotency key.
+#[derive(Debug)]
+pub(crate) enum Claim {
+    /// The key is new (or expired): run the tool and record its result on
+    /// the ticket.
+    Run(Ticket),
+    /// The key was used before: return this result.
+    Replay(CallToolResult),
+}
+
+/// A claimed key whose call is running. Dropping the ticket without a
+/// successful result (the call failed or was cancelled) releases the key,
+/// so the call can be retried.
+#[derive(Debug)]
+pub(crate) struct Ticket {
+    cache: IdempotencyCache,
+    id: (String, String),
+}
+
+impl Ticket {
+    /// Records the call's result for replay, unless it is an error.
+    pub(crate) fn complete(self, result: &CallToolResult) {
+        if result.is_error == Some(true) {
+            return;
+        }
+        let mut entries = self.cache.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.get_mut(&self.id) {
+            entry.result = Some(result.clone());
+        }
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let mut entries = self.cache.0.lock().unwrap_or_else(|e| e.into_inner());
+        if entries
+            .get(&self.id)
+            .is_some_and(|entry| entry.result.is_none())
+        {
+            entries.remove(&self.id);
+        }
+    }
+}
+
+/// Results of the calls made with each idempotency key in a session, keyed
+/// by tool name and key.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IdempotencyCache(Arc<Mutex<HashMap<(String, String), Entry>>>);
+
+impl IdempotencyCache {
+    /// Claims `key` for a call to `tool` with `arguments`, or finds the
+    /// result of an earlier call with it.
+    pub(crate) fn claim(
+        &self,
+        tool: &str,
+        key: &str,
+        arguments: Option<&JsonObject>,
+        ttl: Duration,
+        now: Instant,
+    ) -> Result<Claim, ErrorData> {
+        let arguments = arguments
+            .map(|arguments| serde_json::to_string(arguments).unwrap_or_default())
+            .unwrap_or_default();
+        let mut entries = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, entry| now.duration_since(entry.started) < ttl);
+        let id = (tool.to_string(), key.to_string());
+        let Some(entry) = entries.get(&id) else {
+            entries.insert(
+                id.clone(),
+                Entry {
+                    arguments,
+                    started: now,
+                    result: None,
+                },
+            );
+            return Ok(Claim::Run(Ticket {
+                cache: self.clone(),
+                id,
+            }));
+        };
+        if entry.arguments != arguments {
+            return Err(ErrorData::invalid_params(
+                "Idempotency key was already used with different arguments",
+                Some(serde_json::json!({ "tool": tool, "idempotency_key": key })),
+            ));
+        }
+        match &entry.result {
+            Some(result) => Ok(Claim::Replay(result.clone())),
+            None => Err(ErrorData::invalid_request(
+                "A call with this idempotency key is still in progress",
+                Some(serde_json::json!({ "tool": tool, "idempotency_key": key })),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::ContentBlock;
+
+    fn arguments(value: serde_json::Value) -> JsonObject {
+        value.as_object().cloned().unwrap()
+    }
+
+    fn text(result: &CallToolResult) -> &str {
+        &result.content[0].as_text().unwrap().text
+    }
+
+    #[test]
+    fn test_replay_within_ttl() {
+        let cache = IdempotencyCache::default();
+        let args = arguments(serde_json::json!({"path": "docs"}));
+        let ttl = Duration::from_secs(60);
+        let now = Instant::now();
+
+        let Ok(Claim::Run(ticket)) = cache.claim("index_directory", "k", Some(&args), ttl, now)
+        else {
+            panic!("expected a new key");
+        };
+        // A retry while the first call runs is rejected rather than run twice.
+        assert!(
+            cache
+                .claim("index_directory", "k", Some(&args), ttl, now)
+                .is_err()
+        );
+
+        ticket.complete(&CallToolResult::success(vec![ContentBlock::text("done")]));
+        match cache.claim(
+            "index_directory",
+            "k",
+            Some(&args),
+            ttl,
+            now + Duration::from_secs(1),
+        ) {
+            Ok(Claim::Replay(replayed)) => assert_eq!(text(&replayed), "done"),
+            other => panic!("expected a replay, got {other:?}"),
+        }
+
+        // The same key on another tool, or after the TTL, runs again.
+        let claim = cache.claim("db_index", "k", Some(&args), ttl, now);
+        assert!(matches!(claim, Ok(Claim::Run(_))));
+        let claim = cache.claim("index_directory", "k", Some(&args), ttl, now + ttl);
+        assert!(matches!(claim, Ok(Claim::Run(_))));
+    }
+
+    #[test]
+    fn test_key_reused_with_different_arguments() {
+        let cache = IdempotencyCache::default();
+        let ttl = Duration::from_secs(60);
+        let now = Instant::now();
+        let first = arguments(serde_json::json!({"path": "docs"}));
+        let second = arguments(serde_json::json!({"path": "notes"}));
+        let _ticket = cache.claim("index_directory", "k", Some(&first), ttl, now);
+        let error = cache
+            .claim("index_directory", "k", Some(&second), ttl, now)
+            .unwrap_err();
+        assert!(error.message.contains("different arguments"));
+    }
+
+    #[test]
+    fn test_failed_call_releases_key() {
+        let cache = IdempotencyCache::default();
+        let ttl = Duration::from_secs(60);
+        let now = Instant::now();
+        let Ok(Claim::Run(ticket)) = cache.claim("t", "k", None, ttl, now) else {
+            panic!("expected a new key");
+        };
+        ticket.complete(&CallToolResult::error(vec![ContentBlock::text("failed")]));
+        let running = cache.claim("t", "k", None, ttl, now);
+        assert!(matches!(running, Ok(Claim::Run(_))));
+        assert!(cache.claim("t", "k", None, ttl, now).is_err());
+
+        // A ticket dropped without a result, e.g. by a cancelled call.
+        drop(running);
+        assert!(matches!(
+            cache.claim("t", "k", None, ttl, now),
+            Ok(Claim::Run(_))
+        ));
+    }
+
+    #[test]
+    fn test_take_argument() {
+        let mut args = Some(arguments(
+            serde_json::json!({"idempotency_key": "abc", "path": "x"}),
+        ));
+        assert_eq!(take_argument(&mut args).unwrap().as_deref(), Some("abc"));
+        assert!(!args.as_ref().unwrap().contains_key(ARGUMENT));
+        assert_eq!(take_argument(&mut args).unwrap(), None);
+        let mut args = Some(arguments(serde_json::json!({"idempotency_key": ""})));
+        assert!(take_argument(&mut args).is_err());
+    }
+}
@@ -0,0 +1,892 @@
+//! `index_directory` and `search_index`: a persistent full-text index of
+//! markdown files on disk, ranked with BM25 at section granularity. The
+//! index keeps term counts only; hits are read back from the files, so
+//! they can be post-processed with an mq query.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::UNIX_EPOCH,
+};
+
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    dedup::{Seen, SharedSource, content_hash},
+    dry_run,
+    keywords::{Tokenizer, prose},
+    output::{IndexFailure, json_result, output_schema},
+    paths, sections,
+};
+
+/// Bumped when the on-disk format changes; older indexes are rebuilt.
+const FORMAT_VERSION: u32 = 1;
+const DEFAULT_LIMIT: usize = 10;
+/// BM25 term frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 length normalization.
+const B: f64 = 0.75;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct IndexedSection {
+    pub title: Option<String>,
+    /// 1-based lines of the heading and of the section's last line.
+    pub line: usize,
+    pub end_line: usize,
+    /// Number of indexed terms.
+    pub length: usize,
+    pub terms: HashMap<String, u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct IndexedFile {
+    /// Modification time in milliseconds since the epoch, and size; a file
+    /// is re-indexed when either changes.
+    pub modified: u64,
+    pub size: u64,
+    pub sections: Vec<IndexedSection>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub(crate) struct IndexData {
+    pub version: u32,
+    /// Indexed directories, with whether they were walked recursively.
+    pub directories: BTreeMap<PathBuf, bool>,
+    pub files: BTreeMap<PathBuf, IndexedFile>,
+    /// When the index was last saved, in seconds since the epoch.
+    #[serde(default)]
+    pub updated_at: Option<i64>,
+}
+
+/// What an update of the index changed.
+#[derive(Debug, Default)]
+pub(crate) struct IndexReport {
+    pub added: Vec<PathBuf>,
+    pub updated: Vec<PathBuf>,
+    pub unchanged: usize,
+    pub removed: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl IndexReport {
+    fn changed(&self) -> bool {
+        !(self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty())
+    }
+}
+
+/// A search hit: the file, its section, the score and the matched terms.
+type Hit<'a> = (&'a Path, &'a IndexedSection, f64, Vec<String>);
+
+/// The file's modification time (ms since the epoch) and size.
+fn stamp(path: &Path) -> std::io::Result<(u64, u64)> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    Ok((modified, metadata.len()))
+}
+
+/// Reads and tokenizes a markdown file, one entry per section.
+fn index_file(path: &Path) -> Result<IndexedFile, String> {
+    let (modified, size) = stamp(path).map_err(|e| e.to_string())?;
+    let markdown = paths::read_markdown(path).map_err(|e| e.to_string())?;
+    let tokenizer = Tokenizer::new(whatlang::detect_lang(&prose(&markdown)), &[]);
+    let sections = sections::split(&markdown)
+        .map_err(|e| e.message.to_string())?
+        .into_iter()
+        .map(|section| {
+            let mut terms: HashMap<String, u32> = HashMap::new();
+            for term in tokenizer.terms(&prose(&section.markdown)) {
+                *terms.entry(term).or_default() += 1;
+            }
+            IndexedSection {
+                title: section.title,
+                line: section.line,
+                end_line: section.line + section.markdown.lines().count().max(1) - 1,
+                length: terms.values().sum::<u32>() as usize,
+                terms,
+            }
+        })
+        .collect();
+    Ok(IndexedFile {
+        modified,
+        size,
+        sections,
+    })
+}
+
+impl IndexData {
+    /// (Re-)indexes the markdown files under `directory`, skipping files
+    /// whose modification time and size are unchanged, and drops indexed
+    /// files under it that are gone.
+    pub(crate) fn update_directory(
+        &mut self,
+        directory: &Path,
+        recursive: bool,
+        report: &mut IndexReport,
+    ) {
+        let files = mq_db::discover::collect_markdown_files(&[directory.to_path_buf()], recursive);
+        for path in &files {
+            self.update_file(path, report);
+        }
+        let found: HashSet<&PathBuf> = files.iter().collect();
+        let gone: Vec<PathBuf> = self
+            .files
+            .keys()
+            .filter(|path| {
+                path.parent().is_some_and(|parent| {
+                    parent == directory || (recursive && parent.starts_with(directory))
+                }) && !found.contains(path)
+            })
+            .cloned()
+            .collect();
+        for path in gone {
+            self.files.remove(&path);
+            report.removed.push(path);
+        }
+        self.directories.insert(directory.to_path_buf(), recursive);
+    }
+
+    /// (Re-)indexes one file if it changed, recording the outcome.
+    pub(crate) fn update_file(&mut self, path: &Path, report: &mut IndexReport) {
+        let current = self.files.get(path).map(|file| (file.modified, file.size));
+        if current.is_some() && stamp(path).ok() == current {
+            report.unchanged += 1;
+            return;
+        }
+        match index_file(path) {
+            Ok(file) => {
+                self.files.insert(path.to_path_buf(), file);
+                match current {
+                    Some(_) => report.updated.push(path.to_path_buf()),
+                    None => report.added.push(path.to_path_buf()),
+                }
+            }
+            Err(error) => report.failed.push((path.to_path_buf(), error)),
+        }
+    }
+
+    /// Whether an indexed directory covers `path`, and if so whether a
+    /// recursive one does.
+    fn covering(&self, path: &Path) -> Option<bool> {
+        self.directories
+            .iter()
+            .filter(|(directory, recursive)| {
+                (**recursive && path.starts_with(directory))
+                    || path.parent() == Some(directory.as_path())
+            })
+            .map(|(_, &recursive)| recursive)
+            .max()
+    }
+
+    /// Re-indexes what changed at `path`, as reported by the watcher.
+    fn apply_change(&mut self, path: &Path, report: &mut IndexReport) {
+        if let Some(&recursive) = self.directories.get(path) {
+            self.update_directory(path, recursive, report);
+        } else if !path.exists() {
+            let gone: Vec<PathBuf> = self
+                .files
+                .keys()
+                .filter(|file| file.starts_with(path))
+                .cloned()
+                .collect();
+            for file in gone {
+                self.files.remove(&file);
+                report.removed.push(file);
+            }
+        } else if path.is_dir() {
+            // A directory created or moved into a recursive one.
+            if self.covering(path) == Some(true) {
+                for file in mq_db::discover::collect_markdown_files(&[path.to_path_buf()], true) {
+                    self.update_file(&file, report);
+                }
+            }
+        } else if paths::is_markdown(path) && self.covering(path).is_some() {
+            self.update_file(path, report);
+        }
+    }
+
+    /// Indexed files that changed or were deleted since they were indexed.
+    fn stale(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|(path, file)| stamp(path).ok() != Some((file.modified, file.size)))
+            .count()
+    }
+
+    fn sections(&self) -> usize {
+        self.files.values().map(|file| file.sections.len()).sum()
+    }
+
+    /// The `limit` best sections for `terms` by BM25, among the files
+    /// `accept` allows.
+    fn search(
+        &self,
+        terms: &[String],
+        limit: usize,
+        accept: impl Fn(&Path) -> bool,
+    ) -> Vec<Hit<'_>> {
+        let sections: Vec<(&Path, &IndexedSection)> = self
+            .files
+            .iter()
+            .flat_map(|(path, file)| file.sections.iter().map(move |s| (path.as_path(), s)))
+            .collect();
+        let n = sections.len() as f64;
+        let average = sections.iter().map(|(_, s)| s.length).sum::<usize>() as f64 / n.max(1.0);
+        let idf: HashMap<&str, f64> = terms
+            .iter()
+            .map(|term| {
+                let df = sections
+                    .iter()
+                    .filter(|(_, s)| s.terms.contains_key(term))
+                    .count() as f64;
+                (term.as_str(), ((n - df + 0.5) / (df + 0.5) + 1.0).ln())
+            })
+            .collect();
+
+        let mut hits: Vec<Hit> = sections
+            .into_iter()
+            .filter(|(path, _)| accept(path))
+            .filter_map(|(path, section)| {
+                let mut score = 0.0;
+                let mut matched = Vec::new();
+                for term in terms {
+                    let Some(&tf) = section.terms.get(term) else {
+                        continue;
+                    };
+                    let tf = f64::from(tf);
+                    score += idf[term.as_str()] * tf * (K1 + 1.0)
+                        / (tf + K1 * (1.0 - B + B * section.length as f64 / average.max(1.0)));
+                    matched.push(term.clone());
+                }
+                matched.sort();
+                (!matched.is_empty()).then_some((path, section, score, matched))
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.2.total_cmp(&a.2)
+                .then(a.0.cmp(b.0))
+                .then(a.1.line.cmp(&b.1.line))
+        });
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// Paths the watcher reported changed, applied on the next use of the
+/// index.
+type Pending = Arc<Mutex<BTreeSet<PathBuf>>>;
+
+/// Keeps a filesystem watcher alive; dropping it stops watching.
+struct Watcher(notify::RecommendedWatcher);
+
+impl fmt::Debug for Watcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Watcher")
+    }
+}
+
+#[derive(Debug, Default)]
+struct IndexState {
+    data: Option<IndexData>,
+    watcher: Option<Watcher>,
+    /// Directories the watcher was told about.
+    watched: HashSet<PathBuf>,
+}
+
+/// Reads the index at `path`. A missing file, or one in an older format,
+/// is an empty index.
+fn load(path: &Path) -> Result<IndexData, ErrorData> {
+    let failed = |e: &dyn fmt::Display| {
+        ErrorData::internal_error(
+            "Failed to load search index",
+            Some(serde_json::json!({ "path": path, "error": e.to_string() })),
+        )
+    };
+    let data = match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice::<IndexData>(&bytes).map_err(|e| failed(&e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => IndexData::default(),
+        Err(e) => return Err(failed(&e)),
+    };
+    Ok(if data.version == FORMAT_VERSION {
+        data
+    } else {
+        IndexData {
+            version: FORMAT_VERSION,
+            ..Default::default()
+        }
+    })
+}
+
+/// The on-disk index, loaded on first use and shared by every session.
+/// Changes reported by the filesystem watcher are queued and applied file
+/// by file on the next use, instead of rebuilding the index.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SearchIndex {
+    state: Arc<Mutex<IndexState>>,
+    pending: Pending,
+}
+
+impl SearchIndex {
+    /// The index stored at `path`, loading it if needed. On load, every
+    /// indexed directory is queued for a refresh, to pick up changes made
+    /// while the server was not running.
+    pub(crate) fn lock(&self, path: &Path) -> Result<IndexGuard<'_>, ErrorData> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.data.is_none() {
+            let data = load(path)?;
+            self.pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .extend(data.directories.keys().cloned());
+            state.data = Some(data);
+        }
+        Ok(IndexGuard {
+            state,
+            pending: &self.pending,
+        })
+    }
+}
+
+pub(crate) struct IndexGuard<'a> {
+    state: MutexGuard<'a, IndexState>,
+    pending: &'a Pending,
+}
+
+impl IndexGuard<'_> {
+    pub(crate) fn data(&mut self) -> &mut IndexData {
+        self.state.data.get_or_insert_with(Default::default)
+    }
+
+    /// Number of changed paths not applied yet.
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub(crate) fn watching(&self) -> bool {
+        self.state.watcher.is_some()
+    }
+
+    /// Watches the indexed directories not watched yet. Failures (such as
+    /// the platform's limit on watches) are logged, and the index is then
+    /// only refreshed by `index_directory`.
+    pub(crate) fn watch(&mut self) {
+        if self.state.watcher.is_none() {
+            let pending = self.pending.clone();
+            let watcher = notify::recommended_watcher(
+                move |event: notify::Result<notify::Event>| match event {
+                    Ok(event) if !matches!(event.kind, notify::EventKind::Access(_)) => pending
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .extend(event.paths),
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("search index watcher error: {e}"),
+                },
+            );
+            match watcher {
+                Ok(watcher) => self.state.watcher = Some(Watcher(watcher)),
+                Err(e) => {
+                    tracing::warn!("failed to watch the search index directories: {e}");
+                    return;
+                }
+            }
+        }
+        let mut directories: Vec<(PathBuf, bool)> = self
+            .data()
+            .directories
+            .iter()
+            .map(|(directory, &recursive)| (directory.clone(), recursive))
+            .collect();
+        directories.retain(|(directory, _)| !self.state.watched.contains(directory));
+        let state = &mut *self.state;
+        let Some(Watcher(watcher)) = state.watcher.as_mut() else {
+            return;
+        };
+        for (directory, recursive) in directories {
+            let mode = if recursive {
+                notify::RecursiveMode::Recursive
+            } else {
+                notify::RecursiveMode::NonRecursive
+            };
+            match notify::Watcher::watch(watcher, &directory, mode) {
+                Ok(()) => {
+                    state.watched.insert(directory);
+                }
+                Err(e) => tracing::warn!("failed to watch {}: {e}", directory.display()),
+            }
+        }
+    }
+
+    /// Applies the queued changes, re-indexing only the affected files, and
+    /// saves the index to `path` if anything changed.
+    pub(crate) fn refresh(&mut self, path: &Path) -> Result<IndexReport, ErrorData> {
+        let changed = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        let mut report = IndexReport::default();
+        let data = self.data();
+        for changed in &changed {
+            data.apply_change(changed, &mut report);
+        }
+        if report.changed() {
+            self.save(path)?;
+        }
+        Ok(report)
+    }
+
+    /// Writes the index to `path`, through a temporary file so a crash
+    /// never leaves a truncated index.
+    pub(crate) fn save(&mut self, path: &Path) -> Result<(), ErrorData> {
+        let failed = |e: &dyn fmt::Display| {
+            ErrorData::internal_error(
+                "Failed to save search index",
+                Some(serde_json::json!({ "path": path, "error": e.to_string() })),
+            )
+        };
+        let data = self.data();
+        data.updated_at = Some(chrono::Utc::now().timestamp());
+        let bytes = serde_json::to_vec(data).map_err(|e| failed(&e))?;
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, bytes).map_err(|e| failed(&e))?;
+        std::fs::rename(&temp, path).map_err(|e| failed(&e))
+    }
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct IndexDirectoryInput {
+    #[schemars(description = "The directory of markdown files to index")]
+    pub path: String,
+    #[schemars(description = "Also index subdirectories (default: true)")]
+    pub recursive: Option<bool>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SearchIndexInput {
+    #[schemars(description = "The text to search for, e.g. a question or keywords")]
+    pub search: String,
+    #[schemars(
+        description = "An mq query run on each hit's section, e.g. `.code(\"sh\")`; hits without results are dropped (default: the whole section)"
+    )]
+    pub query: Option<String>,
+    #[schemars(description = "Only search files under this directory")]
+    pub path: Option<String>,
+    #[schemars(description = "Maximum number of hits (default: 10)")]
+    pub limit: Option<usize>,
+    #[schemars(
+        description = "Collapse hits with identical content into the first, listing the others in its `shared_with` (default: true)"
+    )]
+    pub dedup: Option<bool>,
+}
+
+/// Output of `index_directory`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct IndexDirectoryOutput {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: usize,
+    pub removed: Vec<String>,
+    pub failed: Vec<IndexFailure>,
+    #[schemars(description = "Number of files in the index, over all directories")]
+    pub documents: usize,
+    #[schemars(description = "Number of sections in the index")]
+    pub sections: usize,
+    #[schemars(description = "Whether this was a dry run that left the index unchanged")]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct IndexHit {
+    pub path: String,
+    #[schemars(description = "Heading text; null for the text before the first heading")]
+    pub title: Option<String>,
+    #[schemars(description = "1-based line of the heading")]
+    pub line: usize,
+    #[schemars(description = "BM25 score; higher is more relevant")]
+    pub score: f64,
+    #[schemars(description = "The search terms found in the section")]
+    pub matched: Vec<String>,
+    #[schemars(description = "The section as markdown, or the results of `query` on it")]
+    pub values: Vec<String>,
+    #[schemars(description = "SHA-256 of `values`, ignoring trailing whitespace")]
+    pub hash: String,
+    #[schemars(description = "Other sections with the same content, collapsed into this hit")]
+    pub shared_with: Vec<SharedSource>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct IndexedDirectory {
+    pub path: String,
+    pub recursive: bool,
+}
+
+/// Output of `index_status`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct IndexStatusOutput {
+    #[schemars(description = "Path of the index file")]
+    pub path: String,
+    pub directories: Vec<IndexedDirectory>,
+    #[schemars(description = "Number of indexed files")]
+    pub documents: usize,
+    #[schemars(description = "Number of indexed sections")]
+    pub sections: usize,
+    #[schemars(description = "Whether the directories are watched for changes")]
+    pub watching: bool,
+    #[schemars(description = "Changed paths reported by the watcher, applied on the next search")]
+    pub pending: usize,
+    #[schemars(description = "Indexed files that changed or were deleted since they were indexed")]
+    pub stale: usize,
+    #[schemars(description = "When the index was last updated (RFC 3339); null if never")]
+    pub updated_at: Option<String>,
+}
+
+/// Output of `search_index`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SearchIndexOutput {
+    #[schemars(description = "Matching sections, most relevant first")]
+    pub hits: Vec<IndexHit>,
+}
+
+fn lossy(paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Lines `line..=end_line` (1-based) of the file at `path`.
+fn read_section(path: &Path, line: usize, end_line: usize) -> Option<String> {
+    let markdown = paths::read_markdown(path).ok()?;
+    let lines: Vec<&str> = markdown.lines().collect();
+    let section = lines.get(line.checked_sub(1)?..end_line.min(lines.len()))?;
+    Some(section.join("\n"))
+}
+
+impl Server {
+    fn index_path(&self) -> Result<&Path, ErrorData> {
+        self.config.index_path.as_deref().ok_or_else(|| {
+            ErrorData::invalid_request(
+                "no search index configured — restart mq-mcp with --index <path> to enable index_directory, search_index and index_status",
+                None,
+            )
+        })
+    }
+
+    /// Locks the index, watching its directories when configured.
+    fn lock_index<'a>(&'a self, path: &Path) -> Result<IndexGuard<'a>, ErrorData> {
+        let mut index = self.index.lock(path)?;
+        if self.config.index_watch {
+            index.watch();
+        }
+        Ok(index)
+    }
+}
+
+#[tool_router(router = index_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Build or update the persistent full-text index with the markdown files of a directory, for search_index. Files whose modification time and size are unchanged are skipped, and indexed files that no longer exist are dropped. Requires mq-mcp to have been started with --index <path>.",
+        output_schema = output_schema::<IndexDirectoryOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    fn index_directory(&self, Parameters(input): Parameters<IndexDirectoryInput>) -> McpResult {
+        let index_path = self.index_path()?;
+        let directory = self.scoped_path(&input.path)?;
+        if !directory.is_dir() {
+            return Err(ErrorData::invalid_params(
+                "Not a directory",
+                Some(serde_json::json!({ "path": input.path })),
+            ));
+        }
+        let dry_run = dry_run::active();
+        let mut index = self.lock_index(index_path)?;
+        index.refresh(index_path)?;
+        let mut report = IndexReport::default();
+        // A dry run updates a copy of the index and discards it.
+        let mut scratch = dry_run.then(|| index.data().clone());
+        let data = match scratch.as_mut() {
+            Some(scratch) => scratch,
+            None => index.data(),
+        };
+        data.update_directory(&directory, input.recursive.unwrap_or(true), &mut report);
+        let (documents, sections) = (data.files.len(), data.sections());
+        if !dry_run {
+            index.save(index_path)?;
+            if self.config.index_watch {
+                index.watch();
+            }
+        }
+        json_result(&IndexDirectoryOutput {
+            added: lossy(&report.added),
+            updated: lossy(&report.updated),
+            unchanged: report.unchanged,
+            removed: lossy(&report.removed),
+            failed: report
+                .failed
+                .into_iter()
+                .map(|(path, error)| IndexFailure {
+                    path: path.to_string_lossy().into_owned(),
+                    error,
+                })
+                .collect(),
+            documents,
+            sections,
+            dry_run,
+        })
+    }
+
+    #[tool(
+        description = "Search the persistent full-text index built with index_directory and return the best-matching sections (heading + body), ranked by BM25, read from the files. An optional mq query post-processes each hit, e.g. to return only its code blocks.",
+        output_schema = output_schema::<SearchIndexOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn search_index(&self, Parameters(input): Parameters<SearchIndexInput>) -> McpResult {
+        let index_path = self.index_path()?;
+        let under = input
+            .path
+            .as_deref()
+            .map(|path| self.scoped_path(path))
+            .transpose()?;
+        let terms: Vec<String> = Tokenizer::new(None, &[])
+            .terms(&input.search)
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let limit = input.limit.unwrap_or(DEFAULT_LIMIT);
+
+        let mut index = self.lock_index(index_path)?;
+        index.refresh(index_path)?;
+        let dedup = input.dedup.unwrap_or(true);
+        let mut hits: Vec<IndexHit> = Vec::new();
+        let mut seen = Seen::default();
+        // Fetch extra candidates, as a query or deduplication may drop some
+        // hits.
+        let candidates = if input.query.is_some() || dedup {
+            limit * 4
+        } else {
+            limit
+        };
+        for (path, section, score, matched) in index.data().search(&terms, candidates, |path| {
+            under
+                .as_ref()
+                .is_none_or(|under| paths::starts_with(path, under))
+                && self.roots.scope(&path.to_string_lossy()).is_some()
+        }) {
+            let Some(markdown) = read_section(path, section.line, section.end_line) else {
+                continue;
+            };
+            let values = match input.query.as_deref() {
+                Some(query) => self.query_values(&markdown, query)?,
+                None => vec![markdown],
+            };
+            if values.is_empty() {
+                continue;
+            }
+            let hash = content_hash(&values);
+            if dedup && let Some(first) = seen.first(&hash, hits.len()) {
+                hits[first].shared_with.push(SharedSource {
+                    source: path.to_string_lossy().into_owned(),
+                    line: section.line,
+                });
+                continue;
+            }
+            hits.push(IndexHit {
+                path: path.to_string_lossy().into_owned(),
+                title: section.title.clone(),
+                line: section.line,
+                score,
+                matched,
+                values,
+                hash,
+                shared_with: Vec::new(),
+            });
+            if hits.len() == limit {
+                break;
+            }
+        }
+        json_result(&SearchIndexOutput { hits })
+    }
+
+    #[tool(
+        description = "Report the state of the persistent search index: the indexed directories, document and section counts, whether the directories are watched, how many changes are waiting to be applied, how many indexed files are stale and when the index was last updated.",
+        output_schema = output_schema::<IndexStatusOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn index_status(&self) -> McpResult {
+        let index_path = self.index_path()?;
+        let mut index = self.lock_index(index_path)?;
+        let watching = index.watching();
+        let pending = index.pending();
+        let data = index.data();
+        json_result(&IndexStatusOutput {
+            path: index_path.to_string_lossy().into_owned(),
+            directories: data
+                .directories
+                .iter()
+                .map(|(path, &recursive)| IndexedDirectory {
+                    path: path.to_string_lossy().into_owned(),
+                    recursive,
+                })
+                .collect(),
+            documents: data.files.len(),
+            sections: data.sections(),
+            watching,
+            pending,
+            stale: data.stale(),
+            updated_at: data
+                .updated_at
+                .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                .map(|at| at.to_rfc3339()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, markdown: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, markdown).unwrap();
+        path
+    }
+
+    fn update(data: &mut IndexData, directory: &Path, recursive: bool) -> IndexReport {
+        let mut report = IndexReport::default();
+        data.update_directory(directory, recursive, &mut report);
+        report
+    }
+
+    #[test]
+    fn test_update_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let install = write(
+            dir.path(),
+            "install.md",
+            "# Install\n\nRun cargo install.\n",
+        );
+        write(
+            dir.path(),
+            "docs/usage.md",
+            "# Usage\n\nPipe markdown into mq.\n",
+        );
+
+        let mut data = IndexData::default();
+        let report = update(&mut data, dir.path(), true);
+        assert_eq!(report.added.len(), 2);
+        assert_eq!(data.sections(), 2);
+
+        let report = update(&mut data, dir.path(), true);
+        assert_eq!((report.added.len(), report.unchanged), (0, 2));
+
+        std::fs::remove_file(&install).unwrap();
+        let report = update(&mut data, dir.path(), true);
+        assert_eq!(report.removed, vec![install]);
+        assert_eq!(data.files.len(), 1);
+    }
+
+    #[test]
+    fn test_apply_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let guide = write(dir.path(), "guide.md", "# Guide\n\nFirst version.\n");
+        let mut data = IndexData::default();
+        update(&mut data, dir.path(), false);
+
+        // A new file, a file in a subdirectory of a non-recursive
+        // directory, and a file that is not markdown.
+        let new = write(dir.path(), "new.md", "# New\n\nAdded later.\n");
+        let nested = write(dir.path(), "sub/nested.md", "# Nested\n");
+        let notes = write(dir.path(), "notes.txt", "plain text");
+        let mut report = IndexReport::default();
+        for path in [
+            &new,
+            &nested,
+            &nested.parent().unwrap().to_path_buf(),
+            &notes,
+        ] {
+            data.apply_change(path, &mut report);
+        }
+        assert_eq!(report.added, vec![new.clone()]);
+
+        write(
+            dir.path(),
+            "guide.md",
+            "# Guide\n\nSecond, longer version.\n",
+        );
+        std::fs::remove_file(&new).unwrap();
+        assert_eq!(data.stale(), 2);
+        let mut report = IndexReport::default();
+        data.apply_change(&guide, &mut report);
+        data.apply_change(&new, &mut report);
+        assert_eq!((report.updated, report.removed), (vec![guide], vec![new]));
+        assert_eq!(data.stale(), 0);
+    }
+
+    #[test]
+    fn test_search_and_read_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            dir.path(),
+            "guide.md",
+            "# Install\n\nRun cargo install mq.\n\n# Configure\n\nEdit the config file.\nThe config file is TOML.\n",
+        );
+        write(
+            dir.path(),
+            "other.md",
+            "# Notes\n\nNothing about settings.\n",
+        );
+        let mut data = IndexData::default();
+        update(&mut data, dir.path(), false);
+
+        let terms = vec!["config".to_string(), "file".to_string()];
+        let hits = data.search(&terms, 10, |_| true);
+        assert_eq!(hits.len(), 1);
+        let (hit_path, section, _, matched) = &hits[0];
+        assert_eq!(*hit_path, path.as_path());
+        assert_eq!(section.title.as_deref(), Some("Configure"));
+        assert_eq!(matched, &terms);
+        assert_eq!(
+            read_section(hit_path, section.line, section.end_line).unwrap(),
+            "# Configure\n\nEdit the config file.\nThe config file is TOML."
+        );
+        assert!(data.search(&terms, 10, |_| false).is_empty());
+    }
+
+    #[test]
+    fn test_save_load_and_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "a.md", "# A\n\nSome text here.\n");
+        let index_path = dir.path().join("index.json");
+
+        let index = SearchIndex::default();
+        let mut guard = index.lock(&index_path).unwrap();
+        update(guard.data(), dir.path(), false);
+        guard.save(&index_path).unwrap();
+        drop(guard);
+
+        // Files changed while the index was not loaded are picked up by the
+        // refresh queued on load.
+        write(dir.path(), "b.md", "# B\n\nMore text.\n");
+        let reloaded = SearchIndex::default();
+        let mut guard = reloaded.lock(&index_path).unwrap();
+        assert_eq!(guard.data().files.len(), 1);
+        assert_eq!(guard.pending(), 1);
+        assert!(guard.data().updated_at.is_some());
+
+        let report = guard.refresh(&index_path).unwrap();
+        assert_eq!(report.added, vec![dir.path().join("b.md")]);
+        assert_eq!(guard.pending(), 0);
+        assert_eq!(load(&index_path).unwrap().files.len(), 2);
+    }
+}
@@ -0,0 +1,301 @@
+//! `infer_query`: candidate mq queries built from an example of the output
+//! wanted, so that writing a query can start from "this heading" rather
+//! than from the selector reference. The example is located among the
+//! document's nodes (the values a query runs on), and the selector of its
+//! kind is generalized from the narrowest variant matching its attributes
+//! (heading level, code language) to every node of the kind. Each candidate
+//! is run on the document to report what it selects.
+
+use mq_markdown::Node;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    engine::{QueryEngine, QueryValue, Value},
+    new_engine,
+    output::{json_result, output_schema},
+    parse_markdown,
+};
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct InferQueryInput {
+    #[schemars(description = "The markdown (or HTML) document to query")]
+    pub markdown: String,
+    #[schemars(
+        description = "One piece of the output wanted, as it appears in the document: a single node such as a heading (`## Install`, or just `Install`), a code block, a list item or a link"
+    )]
+    pub example: String,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct Candidate {
+    pub query: String,
+    pub explanation: String,
+    #[schemars(description = "Number of values the query returns on the document")]
+    pub matches: usize,
+    #[schemars(description = "Whether the example is among them")]
+    pub includes_example: bool,
+}
+
+/// Output of `infer_query`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct InferQueryOutput {
+    #[schemars(description = "The suggested query: the narrowest candidate selecting the example")]
+    pub query: String,
+    pub explanation: String,
+    #[schemars(description = "The node the example was found as, rendered as markdown")]
+    pub example_node: String,
+    #[schemars(
+        description = "Whether the example matched the node exactly; if not, the first node containing its text was used"
+    )]
+    pub exact: bool,
+    #[schemars(description = "Every candidate, from the narrowest to the most general")]
+    pub candidates: Vec<Candidate>,
+}
+
+/// A selector and what it selects, in the words of an explanation.
+struct Selector {
+    query: String,
+    selects: String,
+}
+
+impl Selector {
+    fn new(query: impl Into<String>, selects: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            selects: selects.into(),
+        }
+    }
+}
+
+/// The selectors of `node`'s kind, from the narrowest to the most general;
+/// empty for kinds without a selector.
+fn selectors(node: &Node) -> Vec<Selector> {
+    let kind = |selector: &str, selects: &str| vec![Selector::new(selector, selects)];
+    match node {
+        Node::Heading(heading) => vec![
+            Selector::new(
+                format!(".h{}", heading.depth),
+                format!("every level-{} heading", heading.depth),
+            ),
+            Selector::new(".h", "every heading"),
+        ],
+        Node::Code(code) => match &code.lang {
+            Some(lang) => vec![
+                Selector::new(
+                    format!(".code | select(.code.lang == {})", string_literal(lang)),
+                    format!("every `{lang}` code block"),
+                ),
+                Selector::new(".code", "every code block"),
+            ],
+            None => kind(".code", "every code block"),
+        },
+        Node::List(list) if list.checked.is_some() => vec![
+            Selector::new(".task", "every task list item"),
+            Selector::new(".list", "every list item"),
+        ],
+        Node::List(_) => kind(".list", "every list item"),
+        Node::TableCell(cell) => vec![Selector::new(
+            format!(".[][{}]", cell.column),
+            format!("every table cell in column {}", cell.column),
+        )],
+        Node::Link(_) => kind(".link", "every link"),
+        Node::LinkRef(_) => kind(".link_ref", "every reference link"),
+        Node::Image(_) => kind(".image", "every image"),
+        Node::ImageRef(_) => kind(".image_ref", "every reference image"),
+        Node::CodeInline(_) => kind(".code_inline", "every inline code span"),
+        Node::MathInline(_) => kind(".math_inline", "every inline formula"),
+        Node::Math(_) => kind(".math", "every math block"),
+        Node::Blockquote(_) => kind(".blockquote", "every blockquote"),
+        Node::Strong(_) => kind(".strong", "every strong (bold) span"),
+        Node::Emphasis(_) => kind(".emphasis", "every emphasized span"),
+        Node::Delete(_) => kind(".delete", "every strikethrough span"),
+        Node::Footnote(_) => kind(".footnote", "every footnote definition"),
+        Node::FootnoteRef(_) => kind(".footnote_ref", "every footnote reference"),
+        Node::Definition(_) => kind(".definition", "every link definition"),
+        Node::Html(_) => kind(".html", "every raw HTML node"),
+        Node::Yaml(_) => kind(".yaml", "the YAML front matter"),
+        Node::Toml(_) => kind(".toml", "the TOML front matter"),
+        Node::HorizontalRule(_) => kind(".horizontal_rule", "every horizontal rule"),
+        Node::Text(_) => kind(".text", "every run of plain text"),
+        _ => Vec::new(),
+    }
+}
+
+/// `text` as an mq string literal.
+fn string_literal(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The node `example` designates: the first whose markdown or text is the
+/// example, or else the first whose text contains it (ignoring case).
+/// Returns whether the match is exact.
+fn locate<'a>(nodes: &'a [Node], example: &str) -> Option<(&'a Node, bool)> {
+    let example = example.trim();
+    let candidates = || nodes.iter().filter(|node| !selectors(node).is_empty());
+    if let Some(node) = candidates().find(|node| node.to_string().trim() == example) {
+        return Some((node, true));
+    }
+    if let Some(node) = candidates().find(|node| node.value().trim() == example) {
+        return Some((node, true));
+    }
+    let lowercase = example.to_lowercase();
+    candidates()
+        .find(|node| node.value().to_lowercase().contains(&lowercase))
+        .map(|node| (node, false))
+}
+
+/// Runs `query` on `nodes`, returning the number of values it returns and
+/// whether `example` is one of them.
+fn try_query(nodes: &[Node], query: &str, example: &str) -> Result<(usize, bool), ErrorData> {
+    let values = new_engine()
+        .eval(query, nodes.iter().cloned().map(Value::from))
+        .map_err(|e| {
+            ErrorData::internal_error(
+                "Failed to run an inferred query",
+                Some(serde_json::json!({ "query": query, "error": e })),
+            )
+        })?;
+    let values: Vec<Value> = values
+        .into_iter()
+        .filter(|value| !value.is_blank())
+        .collect();
+    let includes = values
+        .iter()
+        .any(|value| value.to_string().trim() == example.trim());
+    Ok((values.len(), includes))
+}
+
+pub(crate) fn infer(markdown: &str, example: &str) -> Result<InferQueryOutput, ErrorData> {
+    let nodes = parse_markdown(markdown)?;
+    let Some((node, exact)) = locate(&nodes, example) else {
+        return Err(ErrorData::invalid_params(
+            "Example not found in the document",
+            Some(serde_json::json!({ "example": example })),
+        ));
+    };
+    let example_node = node.to_string();
+    let text = node.value();
+    let text = text.trim();
+
+    let mut selectors = selectors(node);
+    // The narrowest selector, filtered down to the example itself.
+    if !text.is_empty() {
+        let narrowest = &selectors[0];
+        selectors.insert(
+            0,
+            Selector::new(
+                format!(
+                    "{} | select(to_text() == {})",
+                    narrowest.query,
+                    string_literal(text)
+                ),
+                format!("only the example among {}", narrowest.selects),
+            ),
+        );
+    }
+
+    let mut candidates = Vec::with_capacity(selectors.len());
+    for selector in selectors {
+        let (matches, includes_example) = try_query(&nodes, &selector.query, &example_node)?;
+        let explanation = format!(
+            "Selects {} ({matches} in the document){}.",
+            selector.selects,
+            if includes_example {
+                ""
+            } else {
+                "; the example is not among them"
+            }
+        );
+        candidates.push(Candidate {
+            query: selector.query,
+            explanation,
+            matches,
+            includes_example,
+        });
+    }
+
+    // The first candidate generalizing the example, or else any selecting it.
+    let suggested = candidates
+        .iter()
+        .skip(usize::from(!text.is_empty()))
+        .chain(&candidates)
+        .find(|candidate| candidate.includes_example)
+        .or(candidates.first())
+        .expect("located nodes have a selector");
+    Ok(InferQueryOutput {
+        query: suggested.query.clone(),
+        explanation: suggested.explanation.clone(),
+        example_node,
+        exact,
+        candidates,
+    })
+}
+
+#[tool_router(router = infer_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Infer an mq query from an example of the output wanted: give a document and one piece of it (e.g. a heading, as `## Install` or just its text) and get candidate queries selecting it and the nodes like it, from the narrowest to the most general, each with an explanation and the number of values it returns on the document. Use it to start a query, then refine it with trace_query or lint_query.",
+        output_schema = output_schema::<InferQueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn infer_query(&self, Parameters(input): Parameters<InferQueryInput>) -> McpResult {
+        json_result(&infer(&input.markdown, &input.example)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const DOCUMENT: &str = "# Guide\n\n## Install\n\nRun it.\n\n## Usage\n\n```rust\nfn main() {}\n```\n\n```sh\nmq --help\n```\n\n- [x] done\n- [ ] todo\n";
+
+    #[rstest]
+    #[case("## Install", ".h2", 2)]
+    #[case("Usage", ".h2", 2)]
+    #[case("mq --help", ".code | select(.code.lang == \"sh\")", 1)]
+    #[case("todo", ".task", 2)]
+    fn test_infer(#[case] example: &str, #[case] query: &str, #[case] matches: usize) {
+        let output = infer(DOCUMENT, example).unwrap();
+        assert_eq!(output.query, query);
+        let candidate = output
+            .candidates
+            .iter()
+            .find(|candidate| candidate.query == query)
+            .unwrap();
+        assert_eq!(candidate.matches, matches);
+        assert!(candidate.includes_example);
+    }
+
+    #[test]
+    fn test_infer_candidates() {
+        let output = infer(DOCUMENT, "## Install").unwrap();
+        assert!(output.exact);
+        let queries: Vec<_> = output
+            .candidates
+            .iter()
+            .map(|candidate| candidate.query.as_str())
+            .collect();
+        assert_eq!(
+            queries,
+            [".h2 | select(to_text() == \"Install\")", ".h2", ".h"]
+        );
+        assert_eq!(output.candidates[0].matches, 1);
+        assert_eq!(output.candidates[2].matches, 3);
+    }
+
+    #[test]
+    fn test_infer_approximate() {
+        let output = infer(DOCUMENT, "instal").unwrap();
+        assert!(!output.exact);
+        assert_eq!(output.example_node.trim(), "## Install");
+    }
+
+    #[test]
+    fn test_infer_example_not_found() {
+        assert!(infer(DOCUMENT, "Changelog").is_err());
+    }
+}
@@ -0,0 +1,155 @@
+//! The `instructions` string sent in `ServerInfo`, summarizing what this
+//! server instance can do so agents can write a sensible first query.
+
+use std::fmt::Write;
+
+use super::{Locale, Server};
+
+/// Example queries, shared with `available_functions`.
+pub(crate) const EXAMPLE_QUERIES: &[&str] = &[
+    r#"select(or(.[], .code, .h)) | upcase() | add(" Hello World")"#,
+    r#"select(not(.code))"#,
+    r#"select(.code.lang == "js")"#,
+    r#"import "section" | section::section("Install") | section::collect()"#,
+];
+
+struct Labels {
+    capabilities: &'static str,
+    functions: &'static str,
+    selectors: &'static str,
+    tools: &'static str,
+    #[cfg(feature = "db")]
+    database: &'static str,
+    #[cfg(feature = "db")]
+    no_database: &'static str,
+    roots: &'static str,
+    no_roots: &'static str,
+    examples: &'static str,
+    tips: &'static str,
+}
+
+const LABELS_EN: Labels = Labels {
+    capabilities: "Capabilities",
+    functions: "built-in functions",
+    selectors: "selectors",
+    tools: "Tools",
+    #[cfg(feature = "db")]
+    database: "Database",
+    #[cfg(feature = "db")]
+    no_database: "not configured (db_* tools return an error; restart with --db <path>)",
+    roots: "Workspace roots",
+    no_roots: "none reported yet (file paths are unrestricted until the client reports roots)",
+    examples: "Example queries",
+    tips: "Call available_functions / available_selectors for the full reference, lint_query to check a query before running it, and trace_query to find the stage of a pipeline that drops every value. New to mq? Read the tutorial at mq://tutorial and run its examples with run_example.",
+};
+
+const LABELS_JA: Labels = Labels {
+    capabilities: "機能",
+    functions: "個の組み込み関数",
+    selectors: "個のセレクタ",
+    tools: "ツール",
+    #[cfg(feature = "db")]
+    database: "データベース",
+    #[cfg(feature = "db")]
+    no_database: "未設定（db_* ツールはエラーになります。--db <path> 付きで再起動してください）",
+    roots: "ワークスペースルート",
+    no_roots: "未通知（クライアントがルートを通知するまでファイルパスは制限されません）",
+    examples: "クエリの例",
+    tips: "完全な一覧は available_functions / available_selectors で、実行前のクエリのチェックは lint_query で、パイプラインのどの段階で値がすべて失われるかは trace_query で確認できます。mq が初めての場合は、mq://tutorial のチュートリアルを読み、その例を run_example で実行してください。",
+};
+
+/// Builds the instructions for `server`, in its configured locale.
+pub(crate) fn build(server: &Server) -> String {
+    let locale = server.config.locale;
+    let labels = match locale {
+        Locale::En => &LABELS_EN,
+        Locale::Ja => &LABELS_JA,
+    };
+    let hir = mq_hir::Hir::default();
+    let functions = hir.builtin.functions.len() + hir.builtin.internal_functions.len();
+    let selectors = hir.builtin.selectors.len();
+    let mut tools = server
+        .tool_router
+        .list_all()
+        .into_iter()
+        .map(|tool| tool.name.to_string())
+        .collect::<Vec<_>>();
+    tools.sort();
+
+    let mut out = String::from(locale.instructions());
+    out.push_str("\n\n");
+    let _ = writeln!(
+        out,
+        "{}: {functions} {}, {selectors} {}.",
+        labels.capabilities, labels.functions, labels.selectors
+    );
+    let _ = writeln!(
+        out,
+        "{} ({}): {}",
+        labels.tools,
+        tools.len(),
+        tools.join(", ")
+    );
+    #[cfg(feature = "db")]
+    let _ = writeln!(
+        out,
+        "{}: {}",
+        labels.database,
+        server
+            .config
+            .db_path
+            .as_deref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|| labels.no_database.to_string())
+    );
+    let roots = server.roots.get();
+    let _ = writeln!(
+        out,
+        "{}: {}",
+        labels.roots,
+        if roots.is_empty() {
+            labels.no_roots.to_string()
+        } else {
+            roots
+                .iter()
+                .map(|root| root.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    );
+    let _ = writeln!(out, "\n{}:", labels.examples);
+    for query in EXAMPLE_QUERIES {
+        let _ = writeln!(out, "- `{query}`");
+    }
+    let _ = write!(out, "\n{}", labels.tips);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ServerConfig;
+
+    #[test]
+    fn test_build_summarizes_capabilities() {
+        let server = Server::new(None).unwrap();
+        let instructions = build(&server);
+        assert!(instructions.starts_with("mq is a tool for processing markdown content"));
+        assert!(instructions.contains("built-in functions"));
+        assert!(instructions.contains("extract_headings"));
+        #[cfg(feature = "db")]
+        assert!(instructions.contains("Database: not configured"));
+        assert!(instructions.contains(EXAMPLE_QUERIES[0]));
+    }
+
+    #[test]
+    fn test_build_localized() {
+        let server = Server::with_config(ServerConfig {
+            locale: Locale::Ja,
+            ..Default::default()
+        })
+        .unwrap();
+        let instructions = build(&server);
+        assert!(instructions.contains("クエリの例"));
+    }
+}
@@ -0,0 +1,164 @@
+//! Tool calls without an MCP client, behind [`Server::call`] and
+//! `mq-mcp run`.
+//!
+//! Tool handlers receive the calling client's peer (for sampling,
+//! elicitation and roots), which rmcp only creates for a served session. So
+//! [`Server::call`] serves the server over an in-memory pipe and plays a
+//! minimal client on the other end: it initializes the session without client
+//! capabilities and sends a single `tools/call`. The call goes through the
+//! same path as a client's, with quotas, dry runs, post-processing and
+//! localized errors; tools that need sampling report that the client
+//! doesn't support it.
+
+use miette::miette;
+use rmcp::{
+    ErrorData, ServiceExt,
+    model::{CallToolResult, JsonObject},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, ReadHalf, WriteHalf},
+    task::JoinHandle,
+};
+
+use super::{Server, ServerConfig, protocol};
+
+/// Size of the in-memory pipe's buffer in each direction.
+const PIPE_CAPACITY: usize = 1024 * 1024;
+
+/// Serves `server` over an in-memory pipe and returns the client's ends of
+/// it, and the session's task.
+pub(super) fn serve_in_memory(
+    server: Server,
+) -> (
+    ReadHalf<DuplexStream>,
+    WriteHalf<DuplexStream>,
+    JoinHandle<()>,
+) {
+    let (client, transport) = tokio::io::duplex(PIPE_CAPACITY);
+    let session = tokio::spawn(async move {
+        match server.serve(tokio::io::split(transport)).await {
+            Ok(service) => {
+                let _ = service.waiting().await;
+            }
+            Err(e) => tracing::warn!("in-memory session failed to start: {e}"),
+        }
+    });
+    let (reader, writer) = tokio::io::split(client);
+    (reader, writer, session)
+}
+
+/// Error for a session that failed before the tool's response arrived.
+fn session_error(e: impl std::fmt::Display) -> ErrorData {
+    ErrorData::internal_error(
+        "The in-memory session failed",
+        Some(serde_json::Value::String(e.to_string())),
+    )
+}
+
+impl Server {
+    /// Calls the tool `name` with `arguments` and returns its result, as
+    /// for a client without sampling, elicitation or roots. The call runs in
+    /// a session of its own, sharing this server's state (database, index,
+    /// state store and session variables).
+    pub async fn call(
+        &self,
+        name: &str,
+        arguments: JsonObject,
+    ) -> Result<CallToolResult, ErrorData> {
+        let (reader, mut writer, session) = serve_in_memory(self.clone());
+        let mut lines = BufReader::new(reader).lines();
+        let messages = [
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {
+                    "protocolVersion": protocol::SUPPORTED_PROTOCOL_VERSIONS[0],
+                    "capabilities": {},
+                    "clientInfo": { "name": "mq-mcp", "version": env!("CARGO_PKG_VERSION") },
+                },
+            }),
+            serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments },
+            }),
+        ];
+        for message in messages {
+            writer
+                .write_all(format!("{message}\n").as_bytes())
+                .await
+                .map_err(session_error)?;
+        }
+
+        let response = loop {
+            let line = lines
+                .next_line()
+                .await
+                .map_err(session_error)?
+                .ok_or_else(|| session_error("the server closed the session without responding"))?;
+            let message: serde_json::Value = serde_json::from_str(&line).map_err(session_error)?;
+            // Skip the initialize response and any notifications.
+            if message["id"] == 2 {
+                break message;
+            }
+        };
+        drop(writer);
+        session.abort();
+
+        if let Some(error) = response.get("error") {
+            return Err(serde_json::from_value(error.clone()).map_err(session_error)?);
+        }
+        serde_json::from_value(response["result"].clone()).map_err(session_error)
+    }
+}
+
+/// Calls the tool `name` with `arguments` on a server built from `config`
+/// and returns its result, as [`Server::call`] does. Errors returned by the
+/// tool are reported with their data.
+pub async fn invoke(
+    config: ServerConfig,
+    name: &str,
+    arguments: JsonObject,
+) -> miette::Result<CallToolResult> {
+    let server = Server::with_config(config).map_err(|e| miette!("{e}"))?;
+    server
+        .call(name, arguments)
+        .await
+        .map_err(|error| match error.data {
+            Some(data) => miette!("{}: {data}", error.message),
+            None => miette!("{}", error.message),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_invoke() {
+        let arguments = serde_json::json!({ "markdown": "# Install\n\ntext\n\n## Usage" });
+        let result = invoke(
+            ServerConfig::default(),
+            "extract_headings",
+            arguments.as_object().unwrap().clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            result.structured_content,
+            Some(serde_json::json!({ "results": ["# Install", "## Usage"] }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invoke_unknown_tool() {
+        assert!(
+            invoke(ServerConfig::default(), "no_such_tool", JsonObject::new())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,499 @@
+//! `keywords`: ranked terms of one or more markdown documents, by term
+//! frequency or TF-IDF. The tokenizer here is shared by the other
+//! lexical tools (`find_duplicates`, `rank_sections`).
+
+use std::collections::{HashMap, HashSet};
+
+use mq_markdown::Node;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use unicode_segmentation::UnicodeSegmentation;
+use whatlang::Lang;
+
+use super::{
+    McpResult, Server,
+    language::parse_language,
+    output::{json_result, output_schema},
+    parse_markdown_only,
+    plaintext::{LinkStyle, TableStyle, to_text},
+};
+
+const DEFAULT_LIMIT: usize = 20;
+
+const STOPWORDS_ENG: &[&str] = &[
+    "a",
+    "about",
+    "above",
+    "after",
+    "again",
+    "against",
+    "all",
+    "also",
+    "am",
+    "an",
+    "and",
+    "any",
+    "are",
+    "as",
+    "at",
+    "be",
+    "because",
+    "been",
+    "before",
+    "being",
+    "below",
+    "between",
+    "both",
+    "but",
+    "by",
+    "can",
+    "could",
+    "did",
+    "do",
+    "does",
+    "doing",
+    "down",
+    "during",
+    "each",
+    "few",
+    "for",
+    "from",
+    "further",
+    "had",
+    "has",
+    "have",
+    "having",
+    "he",
+    "her",
+    "here",
+    "hers",
+    "herself",
+    "him",
+    "himself",
+    "his",
+    "how",
+    "i",
+    "if",
+    "in",
+    "into",
+    "is",
+    "it",
+    "its",
+    "itself",
+    "just",
+    "may",
+    "me",
+    "might",
+    "more",
+    "most",
+    "must",
+    "my",
+    "myself",
+    "no",
+    "nor",
+    "not",
+    "now",
+    "of",
+    "off",
+    "on",
+    "once",
+    "only",
+    "or",
+    "other",
+    "our",
+    "ours",
+    "ourselves",
+    "out",
+    "over",
+    "own",
+    "same",
+    "shall",
+    "she",
+    "should",
+    "so",
+    "some",
+    "such",
+    "than",
+    "that",
+    "the",
+    "their",
+    "theirs",
+    "them",
+    "themselves",
+    "then",
+    "there",
+    "these",
+    "they",
+    "this",
+    "those",
+    "through",
+    "to",
+    "too",
+    "under",
+    "until",
+    "up",
+    "us",
+    "use",
+    "used",
+    "using",
+    "very",
+    "was",
+    "we",
+    "were",
+    "what",
+    "when",
+    "where",
+    "which",
+    "while",
+    "who",
+    "whom",
+    "why",
+    "will",
+    "with",
+    "would",
+    "you",
+    "your",
+    "yours",
+    "yourself",
+    "yourselves",
+];
+
+const STOPWORDS_DEU: &[&str] = &[
+    "aber", "alle", "als", "also", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "bist",
+    "da", "damit", "dann", "das", "dass", "dein", "dem", "den", "denn", "der", "des", "dich",
+    "die", "dir", "doch", "du", "durch", "ein", "eine", "einem", "einen", "einer", "eines", "er",
+    "es", "euer", "für", "hat", "hatte", "ich", "ihr", "ihre", "im", "in", "ist", "ja", "jede",
+    "kann", "kein", "keine", "man", "mein", "mich", "mir", "mit", "nach", "nicht", "noch", "nun",
+    "nur", "ob", "oder", "ohne", "sein", "seine", "sich", "sie", "sind", "so", "über", "um", "und",
+    "uns", "unser", "unter", "vom", "von", "vor", "war", "waren", "was", "weil", "wenn", "werden",
+    "wie", "wir", "wird", "wo", "zu", "zum", "zur",
+];
+
+const STOPWORDS_FRA: &[&str] = &[
+    "à", "au", "aux", "avec", "ce", "ces", "cet", "cette", "dans", "de", "des", "du", "elle",
+    "elles", "en", "est", "et", "eux", "il", "ils", "je", "la", "le", "les", "leur", "leurs",
+    "lui", "ma", "mais", "me", "même", "mes", "moi", "mon", "ne", "nos", "notre", "nous", "on",
+    "ou", "où", "par", "pas", "pour", "qu", "que", "qui", "sa", "se", "ses", "son", "sont", "sur",
+    "ta", "te", "tes", "toi", "ton", "tu", "un", "une", "vos", "votre", "vous", "être", "avoir",
+    "été", "fait", "plus", "comme", "si", "tout", "tous",
+];
+
+const STOPWORDS_SPA: &[&str] = &[
+    "a", "al", "algo", "como", "con", "cual", "de", "del", "donde", "el", "ella", "ellas", "ellos",
+    "en", "entre", "era", "es", "esa", "ese", "eso", "esta", "este", "esto", "fue", "ha", "hay",
+    "la", "las", "le", "les", "lo", "los", "más", "me", "mi", "muy", "no", "nos", "o", "para",
+    "pero", "por", "que", "qué", "se", "ser", "si", "sí", "sin", "sobre", "su", "sus", "también",
+    "te", "tiene", "todo", "tu", "un", "una", "uno", "unos", "y", "ya", "yo",
+];
+
+/// Stopwords for the languages that have a list.
+fn stopwords(lang: Lang) -> &'static [&'static str] {
+    match lang {
+        Lang::Eng => STOPWORDS_ENG,
+        Lang::Deu => STOPWORDS_DEU,
+        Lang::Fra => STOPWORDS_FRA,
+        Lang::Spa => STOPWORDS_SPA,
+        _ => &[],
+    }
+}
+
+/// The prose of `markdown` as plain text: code, math, raw HTML and front
+/// matter are left out, and links are reduced to their labels.
+pub(super) fn prose(markdown: &str) -> String {
+    let nodes: Vec<Node> = parse_markdown_only(markdown)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|node| {
+            !matches!(
+                node,
+                Node::Code(_) | Node::Math(_) | Node::Html(_) | Node::Yaml(_) | Node::Toml(_)
+            )
+        })
+        .collect();
+    to_text(&nodes, LinkStyle::Text, TableStyle::Flatten, "-")
+}
+
+/// Splits text into lowercase terms, dropping stopwords, numbers and
+/// single characters other than CJK ideographs.
+pub(super) struct Tokenizer {
+    stopwords: HashSet<String>,
+}
+
+impl Tokenizer {
+    pub(super) fn new(lang: Option<Lang>, extra: &[String]) -> Self {
+        let mut stopwords: HashSet<String> = lang
+            .map(stopwords)
+            .unwrap_or_default()
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+        stopwords.extend(extra.iter().map(|word| word.to_lowercase()));
+        Self { stopwords }
+    }
+
+    pub(super) fn terms(&self, text: &str) -> Vec<String> {
+        text.unicode_words()
+            .map(str::to_lowercase)
+            .filter(|term| {
+                let mut chars = term.chars();
+                let single =
+                    chars.next().is_some_and(|c| !is_ideograph(c)) && chars.next().is_none();
+                !single
+                    && !term.chars().all(|c| c.is_numeric() || c == '.' || c == ',')
+                    && !self.stopwords.contains(term)
+            })
+            .collect()
+    }
+}
+
+fn is_ideograph(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}')
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Scoring {
+    /// Raw term counts.
+    #[default]
+    Frequency,
+    /// Term frequency weighted by inverse document frequency, so terms
+    /// common to every document rank low.
+    TfIdf,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct KeywordsInput {
+    #[schemars(description = "The markdown documents to analyze")]
+    pub documents: Vec<String>,
+    #[schemars(
+        description = "An mq query selecting the part of each document to analyze, e.g. `.h` or `.text` (default: the whole document)"
+    )]
+    pub query: Option<String>,
+    #[schemars(
+        description = "`frequency` (default) for raw term counts or `tf_idf` to weight terms by how few documents use them"
+    )]
+    pub scoring: Option<Scoring>,
+    #[schemars(
+        description = "Language for the stopword list, as an ISO 639-3 code or English name (default: detected per document). Lists exist for English, German, French and Spanish"
+    )]
+    pub language: Option<String>,
+    #[schemars(description = "Extra words to ignore")]
+    pub stopwords: Option<Vec<String>>,
+    #[schemars(description = "Maximum number of keywords per list (default: 20)")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct Keyword {
+    pub term: String,
+    #[schemars(description = "Occurrences of the term")]
+    pub count: usize,
+    #[schemars(description = "Number of documents containing the term")]
+    pub documents: usize,
+    #[schemars(description = "The ranking score: the count, or the TF-IDF weight")]
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct DocumentKeywords {
+    #[schemars(description = "ISO 639-3 code of the language whose stopwords were used")]
+    pub language: Option<&'static str>,
+    pub keywords: Vec<Keyword>,
+}
+
+/// Output of `keywords`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct KeywordsOutput {
+    #[schemars(
+        description = "Keywords over all documents: summed counts, or for TF-IDF the highest weight in any document"
+    )]
+    pub keywords: Vec<Keyword>,
+    #[schemars(description = "Keywords of each document, in input order")]
+    pub documents: Vec<DocumentKeywords>,
+}
+
+fn top(mut keywords: Vec<Keyword>, limit: usize) -> Vec<Keyword> {
+    keywords.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.term.cmp(&b.term))
+    });
+    keywords.truncate(limit);
+    keywords
+}
+
+/// Ranks the terms of `texts`, each paired with its stopword language.
+fn keywords(
+    texts: &[(Option<Lang>, String)],
+    scoring: Scoring,
+    extra_stopwords: &[String],
+    limit: usize,
+) -> KeywordsOutput {
+    let counts: Vec<HashMap<String, usize>> = texts
+        .iter()
+        .map(|(lang, text)| {
+            let mut counts = HashMap::new();
+            for term in Tokenizer::new(*lang, extra_stopwords).terms(text) {
+                *counts.entry(term).or_default() += 1;
+            }
+            counts
+        })
+        .collect();
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for term in counts.iter().flat_map(HashMap::keys) {
+        *document_frequency.entry(term).or_default() += 1;
+    }
+    // Smoothed, so a term in every document still has a positive weight.
+    let idf = |term: &str| {
+        ((texts.len() as f64 + 1.0) / (document_frequency[term] as f64 + 1.0)).ln() + 1.0
+    };
+
+    let mut overall: HashMap<String, Keyword> = HashMap::new();
+    let documents = counts
+        .iter()
+        .zip(texts)
+        .map(|(counts, (lang, _))| {
+            let total: usize = counts.values().sum();
+            let keywords: Vec<Keyword> = counts
+                .iter()
+                .map(|(term, &count)| Keyword {
+                    term: term.clone(),
+                    count,
+                    documents: document_frequency[term.as_str()],
+                    score: match scoring {
+                        Scoring::Frequency => count as f64,
+                        Scoring::TfIdf => count as f64 / total as f64 * idf(term),
+                    },
+                })
+                .collect();
+            for keyword in &keywords {
+                let entry = overall
+                    .entry(keyword.term.clone())
+                    .or_insert_with(|| Keyword {
+                        term: keyword.term.clone(),
+                        count: 0,
+                        documents: keyword.documents,
+                        score: 0.0,
+                    });
+                entry.count += keyword.count;
+                entry.score = match scoring {
+                    Scoring::Frequency => entry.count as f64,
+                    Scoring::TfIdf => entry.score.max(keyword.score),
+                };
+            }
+            DocumentKeywords {
+                language: lang.map(|lang| lang.code()),
+                keywords: top(keywords, limit),
+            }
+        })
+        .collect();
+
+    KeywordsOutput {
+        keywords: top(overall.into_values().collect(), limit),
+        documents,
+    }
+}
+
+#[tool_router(router = keywords_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Rank the keywords of one or more markdown documents by term frequency or TF-IDF, with stopwords removed per language (detected, or given). Returns the top keywords over all documents and per document; an optional mq query limits the analysis to part of each document, e.g. its headings.",
+        output_schema = output_schema::<KeywordsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn keywords(&self, Parameters(input): Parameters<KeywordsInput>) -> McpResult {
+        let language = input.language.as_deref().map(parse_language).transpose()?;
+        let texts = input
+            .documents
+            .iter()
+            .map(|markdown| {
+                let text = match input.query.as_deref() {
+                    Some(query) => self
+                        .query_values(markdown, query)?
+                        .iter()
+                        .map(|value| prose(value))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    None => prose(markdown),
+                };
+                let lang = language.or_else(|| whatlang::detect_lang(&text));
+                Ok((lang, text))
+            })
+            .collect::<Result<Vec<_>, ErrorData>>()?;
+        json_result(&keywords(
+            &texts,
+            input.scoring.unwrap_or_default(),
+            input.stopwords.as_deref().unwrap_or_default(),
+            input.limit.unwrap_or(DEFAULT_LIMIT),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Some(Lang::Eng), "The parser and the Parser's tests, 2024.", vec!["parser", "parser's", "tests"])]
+    #[case(None, "the parser", vec!["the", "parser"])]
+    #[case(Some(Lang::Deu), "Der Parser und die Tests", vec!["parser", "tests"])]
+    fn test_terms(#[case] lang: Option<Lang>, #[case] text: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(Tokenizer::new(lang, &[]).terms(text), expected);
+    }
+
+    #[test]
+    fn test_prose_skips_code() {
+        assert_eq!(
+            prose("# Setup\n\nRun [the installer](https://x.dev).\n\n```sh\nnpm install\n```"),
+            "Setup\n\nRun the installer."
+        );
+    }
+
+    #[test]
+    fn test_keywords_frequency() {
+        let texts = [
+            (
+                Some(Lang::Eng),
+                "The parser parses tables. The parser is fast.".to_string(),
+            ),
+            (Some(Lang::Eng), "A parser for lists.".to_string()),
+        ];
+        let output = keywords(&texts, Scoring::Frequency, &["fast".to_string()], 2);
+        assert_eq!(
+            output.keywords,
+            vec![
+                Keyword {
+                    term: "parser".to_string(),
+                    count: 3,
+                    documents: 2,
+                    score: 3.0
+                },
+                Keyword {
+                    term: "lists".to_string(),
+                    count: 1,
+                    documents: 1,
+                    score: 1.0
+                },
+            ]
+        );
+        assert_eq!(output.documents[0].keywords[0].term, "parser");
+    }
+
+    #[test]
+    fn test_keywords_tf_idf_prefers_distinctive_terms() {
+        let texts = [
+            (
+                Some(Lang::Eng),
+                "markdown parser markdown tables".to_string(),
+            ),
+            (Some(Lang::Eng), "markdown lists".to_string()),
+        ];
+        let output = keywords(&texts, Scoring::TfIdf, &[], 10);
+        assert_eq!(output.documents[0].keywords[0].term, "markdown");
+        assert_eq!(output.documents[1].keywords[0].term, "lists");
+    }
+}
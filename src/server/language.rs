@@ -0,0 +1,272 @@
+//! `detect_languages` and `extract_by_language`: natural language
+//! detection for the text of markdown blocks, built on `whatlang`.
+
+use std::collections::HashMap;
+
+use mq_markdown::Node;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use whatlang::Lang;
+
+use super::{
+    McpResult, Server, inline_runs,
+    output::{QueryOutput, json_result, output_schema, query_result},
+    parse_markdown, parse_markdown_only,
+    plaintext::{LinkStyle, TableStyle, to_text},
+};
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct DetectLanguagesInput {
+    #[schemars(description = "The markdown content to process")]
+    pub markdown: String,
+    #[schemars(
+        description = "An mq query selecting the values to annotate, e.g. `.text` or `.h` (default: each paragraph, heading, list item, table cell and blockquote)"
+    )]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ExtractByLanguageInput {
+    #[schemars(description = "The markdown content to process")]
+    pub markdown: String,
+    #[schemars(
+        description = "Languages to keep, as ISO 639-3 codes (`eng`, `jpn`, `deu`) or English names (`Japanese`)"
+    )]
+    pub languages: Vec<String>,
+    #[schemars(
+        description = "An mq query selecting the values to filter (default: each paragraph, heading, list item, table cell and blockquote)"
+    )]
+    pub query: Option<String>,
+    #[schemars(
+        description = "Minimum detection confidence, from 0 to 1 (default: 0). Short values are detected with low confidence"
+    )]
+    pub min_confidence: Option<f64>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct LanguageAnnotation {
+    #[schemars(description = "The value, as markdown")]
+    pub markdown: String,
+    #[schemars(description = "ISO 639-3 code of the detected language; null if none was detected")]
+    pub lang: Option<&'static str>,
+    #[schemars(description = "English name of the detected language")]
+    pub language: Option<&'static str>,
+    #[schemars(description = "Writing system, e.g. Latin, Cyrillic, Hiragana")]
+    pub script: Option<String>,
+    #[schemars(description = "Detection confidence, from 0 to 1")]
+    pub confidence: f64,
+    #[schemars(description = "Whether the detection is reliable enough to route on")]
+    pub reliable: bool,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct LanguageCount {
+    pub lang: &'static str,
+    pub language: &'static str,
+    #[schemars(description = "Number of values detected as this language")]
+    pub count: usize,
+}
+
+/// Output of `detect_languages`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct LanguagesOutput {
+    #[schemars(description = "The values in document order, each with its detected language")]
+    pub values: Vec<LanguageAnnotation>,
+    #[schemars(description = "Detected languages, most frequent first")]
+    pub languages: Vec<LanguageCount>,
+}
+
+/// Detects the language of a markdown value from its plain text.
+fn annotate(markdown: String) -> LanguageAnnotation {
+    let text = parse_markdown_only(&markdown)
+        .map(|nodes| to_text(&nodes, LinkStyle::Text, TableStyle::Flatten, "-"))
+        .unwrap_or_default();
+    let info = whatlang::detect(&text);
+    LanguageAnnotation {
+        markdown,
+        lang: info.as_ref().map(|info| info.lang().code()),
+        language: info.as_ref().map(|info| info.lang().eng_name()),
+        script: info.as_ref().map(|info| info.script().name().to_string()),
+        confidence: info
+            .as_ref()
+            .map(|info| info.confidence())
+            .unwrap_or_default(),
+        reliable: info.as_ref().is_some_and(|info| info.is_reliable()),
+    }
+}
+
+/// The document's prose blocks as markdown: paragraphs, headings, list
+/// items, table cells and blockquotes. Code, math and raw HTML are
+/// skipped, as they are not natural language.
+fn blocks(nodes: &[Node]) -> Vec<String> {
+    inline_runs(nodes)
+        .filter(|run| {
+            !matches!(
+                run,
+                [Node::Code(_)
+                    | Node::Math(_)
+                    | Node::Html(_)
+                    | Node::Yaml(_)
+                    | Node::Toml(_)
+                    | Node::Definition(_)
+                    | Node::HorizontalRule(_)
+                    | Node::TableAlign(_)]
+            )
+        })
+        .map(|run| {
+            run.iter()
+                .map(Node::to_string)
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+        .filter(|block| !block.is_empty())
+        .collect()
+}
+
+/// Looks up a language by ISO 639-3 code or English name.
+pub(super) fn parse_language(name: &str) -> Result<Lang, ErrorData> {
+    let name = name.trim();
+    Lang::from_code(name.to_ascii_lowercase())
+        .or_else(|| {
+            Lang::all()
+                .iter()
+                .copied()
+                .find(|lang| lang.eng_name().eq_ignore_ascii_case(name))
+        })
+        .ok_or_else(|| {
+            ErrorData::invalid_params(
+                "Unknown language",
+                Some(serde_json::json!({ "language": name })),
+            )
+        })
+}
+
+impl Server {
+    /// The values to detect languages in: the query results, or the
+    /// document's prose blocks.
+    fn language_values(
+        &self,
+        markdown: &str,
+        query: Option<&str>,
+    ) -> Result<Vec<String>, ErrorData> {
+        match query {
+            Some(query) => self.query_values(markdown, query),
+            None => Ok(blocks(&parse_markdown(markdown)?)),
+        }
+    }
+}
+
+#[tool_router(router = language_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Detect the natural language of each paragraph, heading, list item, table cell and blockquote in markdown (or of each result of an mq query), with its ISO 639-3 code, script and confidence, plus a count per language. Use it to route multilingual documents before translation.",
+        output_schema = output_schema::<LanguagesOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn detect_languages(&self, Parameters(input): Parameters<DetectLanguagesInput>) -> McpResult {
+        let values: Vec<LanguageAnnotation> = self
+            .language_values(&input.markdown, input.query.as_deref())?
+            .into_iter()
+            .map(annotate)
+            .collect();
+
+        let mut counts: HashMap<Lang, usize> = HashMap::new();
+        for lang in values
+            .iter()
+            .filter_map(|value| value.lang.and_then(Lang::from_code))
+        {
+            *counts.entry(lang).or_default() += 1;
+        }
+        let mut languages: Vec<LanguageCount> = counts
+            .into_iter()
+            .map(|(lang, count)| LanguageCount {
+                lang: lang.code(),
+                language: lang.eng_name(),
+                count,
+            })
+            .collect();
+        languages.sort_by(|a, b| b.count.cmp(&a.count).then(a.lang.cmp(b.lang)));
+
+        json_result(&LanguagesOutput { values, languages })
+    }
+
+    #[tool(
+        description = "Extract the paragraphs, headings, list items, table cells and blockquotes of markdown (or the results of an mq query) written in the given languages, e.g. `[\"jpn\"]` or `[\"German\"]`. Values whose language cannot be detected are dropped.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn extract_by_language(
+        &self,
+        Parameters(input): Parameters<ExtractByLanguageInput>,
+    ) -> McpResult {
+        let languages = input
+            .languages
+            .iter()
+            .map(|name| parse_language(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let min_confidence = input.min_confidence.unwrap_or_default();
+        query_result(
+            self.language_values(&input.markdown, input.query.as_deref())?
+                .into_iter()
+                .map(annotate)
+                .filter(|value| {
+                    value.confidence >= min_confidence
+                        && value
+                            .lang
+                            .and_then(Lang::from_code)
+                            .is_some_and(|lang| languages.contains(&lang))
+                })
+                .map(|value| value.markdown)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("eng", Lang::Eng)]
+    #[case(" JPN ", Lang::Jpn)]
+    #[case("german", Lang::Deu)]
+    fn test_parse_language(#[case] name: &str, #[case] expected: Lang) {
+        assert_eq!(parse_language(name).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_language_rejects_unknown() {
+        assert!(parse_language("klingon").is_err());
+    }
+
+    #[test]
+    fn test_blocks_skip_code() {
+        let markdown = "# Title\n\nSome *emphasized* text.\n\n```rust\nfn main() {}\n```\n\n- item";
+        assert_eq!(
+            blocks(&parse_markdown_only(markdown).unwrap()),
+            vec!["# Title", "Some *emphasized* text.", "- item"]
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "The quick brown fox jumps over the lazy dog while the farmer watches.",
+        "eng"
+    )]
+    #[case(
+        "吾輩は猫である。名前はまだ無い。どこで生れたかとんと見当がつかぬ。",
+        "jpn"
+    )]
+    #[case(
+        "Der schnelle braune Fuchs springt über den faulen Hund, während der Bauer zusieht.",
+        "deu"
+    )]
+    fn test_annotate(#[case] text: &str, #[case] expected: &str) {
+        let annotation = annotate(text.to_string());
+        assert_eq!(annotation.lang, Some(expected));
+        assert_eq!(annotation.markdown, text);
+    }
+}
@@ -0,0 +1,279 @@
+//! `lint_query`: static diagnostics for an mq query, built on `mq_hir`.
+
+use mq_hir::{HirError, HirWarning, SymbolKind};
+use rmcp::{handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    engine::{self, Engine, QueryEngine},
+    output::{json_result, output_schema},
+};
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct LintQueryInput {
+    #[schemars(description = "The mq query to lint")]
+    query: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct LintDiagnostic {
+    #[schemars(
+        description = "`error` diagnostics make the query fail; `warning`s are likely mistakes"
+    )]
+    pub severity: Severity,
+    #[schemars(
+        description = "Diagnostic kind: syntax-error, unknown-selector, unresolved-symbol, module-not-found, arity-mismatch, deprecated-function, unused-function, unused-variable or unreachable-code"
+    )]
+    pub code: &'static str,
+    pub message: String,
+    #[schemars(description = "1-based start line")]
+    pub line: u32,
+    #[schemars(description = "1-based start column")]
+    pub column: usize,
+    pub end_line: u32,
+    pub end_column: usize,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct LintOutput {
+    pub errors: usize,
+    pub warnings: usize,
+    #[schemars(description = "Diagnostics ordered by position")]
+    pub diagnostics: Vec<LintDiagnostic>,
+}
+
+fn diagnostic(
+    severity: Severity,
+    code: &'static str,
+    message: String,
+    range: Option<engine::Range>,
+) -> LintDiagnostic {
+    let range = range.unwrap_or_default();
+    LintDiagnostic {
+        severity,
+        code,
+        message,
+        line: range.start.line,
+        column: range.start.column,
+        end_line: range.end.line,
+        end_column: range.end.column,
+    }
+}
+
+/// Whether `args` explicit arguments can satisfy `params`. mq passes the
+/// piped value as an implicit first argument, so one fewer is accepted.
+fn arity_matches(params: &[mq_hir::ParamInfo], args: usize) -> bool {
+    if params.iter().any(|p| p.is_variadic) {
+        return true;
+    }
+    let required = params.iter().filter(|p| !p.has_default).count();
+    args <= params.len() && args + 1 >= required
+}
+
+/// The symbols lowered from `source_id`. mq-hir's per-source index leaves
+/// out selectors and bindings, so this filters every symbol instead.
+pub(crate) fn source_symbols(
+    hir: &mq_hir::Hir,
+    source_id: mq_hir::SourceId,
+) -> impl Iterator<Item = (mq_hir::SymbolId, &mq_hir::Symbol)> {
+    hir.symbols()
+        .filter(move |(_, symbol)| symbol.source.source_id == Some(source_id))
+}
+
+pub(crate) fn lint(query: &str) -> Vec<LintDiagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for (message, range) in Engine::parse_errors(query) {
+        let code = if message.starts_with("Unknown selector") {
+            "unknown-selector"
+        } else {
+            "syntax-error"
+        };
+        diagnostics.push(diagnostic(Severity::Error, code, message, Some(range)));
+    }
+
+    let mut hir = mq_hir::Hir::default();
+    let (source_id, _) = hir.add_code(None, query);
+    let in_query = |symbol: &mq_hir::Symbol| symbol.source.source_id == Some(source_id);
+
+    for error in hir.errors() {
+        let (code, symbol) = match &error {
+            HirError::UnresolvedSymbol { symbol, .. } => ("unresolved-symbol", symbol),
+            HirError::ModuleNotFound { symbol, .. } => ("module-not-found", symbol),
+        };
+        if in_query(symbol) {
+            diagnostics.push(diagnostic(
+                Severity::Error,
+                code,
+                error.to_string(),
+                symbol.source.text_range,
+            ));
+        }
+    }
+
+    for warning in hir.warnings() {
+        let HirWarning::UnreachableCode { symbol } = &warning;
+        if in_query(symbol) {
+            diagnostics.push(diagnostic(
+                Severity::Warning,
+                "unreachable-code",
+                warning.to_string(),
+                symbol.source.text_range,
+            ));
+        }
+    }
+
+    for (_, symbol) in hir.unused_functions(source_id) {
+        diagnostics.push(diagnostic(
+            Severity::Warning,
+            "unused-function",
+            format!(
+                "Function `{}` is defined but never called",
+                symbol.value.as_deref().unwrap_or_default()
+            ),
+            symbol.source.text_range,
+        ));
+    }
+
+    for (symbol_id, symbol) in source_symbols(&hir, source_id) {
+        match &symbol.kind {
+            SymbolKind::Variable => {
+                // `${expr}` segments of interpolated strings are lowered as
+                // variables too, but they are uses rather than bindings.
+                let interpolated = symbol
+                    .parent
+                    .and_then(|parent| hir.symbol(parent))
+                    .is_some_and(|parent| parent.kind == SymbolKind::InterpolatedString);
+                let Some(name) = symbol.value.as_deref() else {
+                    continue;
+                };
+                if !interpolated && !name.starts_with('_') && hir.references(symbol_id).is_empty() {
+                    diagnostics.push(diagnostic(
+                        Severity::Warning,
+                        "unused-variable",
+                        format!("Variable `{name}` is bound but never used"),
+                        symbol.source.text_range,
+                    ));
+                }
+            }
+            SymbolKind::Call => {
+                let Some(definition) = hir
+                    .resolve_reference_symbol(symbol_id)
+                    .and_then(|id| hir.symbol(id))
+                else {
+                    continue;
+                };
+                let name = symbol.value.as_deref().unwrap_or_default();
+
+                if definition.is_deprecated() {
+                    diagnostics.push(diagnostic(
+                        Severity::Warning,
+                        "deprecated-function",
+                        format!("Function `{name}` is deprecated"),
+                        symbol.source.text_range,
+                    ));
+                }
+
+                if let SymbolKind::Function(params) = &definition.kind {
+                    let args = source_symbols(&hir, source_id)
+                        .filter(|(_, s)| s.parent == Some(symbol_id))
+                        .count();
+                    if !arity_matches(params, args) {
+                        diagnostics.push(diagnostic(
+                            Severity::Error,
+                            "arity-mismatch",
+                            format!(
+                                "`{name}` takes {} argument(s) ({}) but {args} were given",
+                                params.len(),
+                                params
+                                    .iter()
+                                    .map(|p| p.to_string())
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            ),
+                            symbol.source.text_range,
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    diagnostics.sort_by_key(|d| (d.line, d.column, d.severity));
+    diagnostics
+}
+
+#[tool_router(router = lint_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Statically check an mq query without running it. Reports syntax errors, unknown selectors, unresolved functions/variables, arity mismatches, deprecated functions, unused bindings and unreachable code, each with a severity and position.",
+        output_schema = output_schema::<LintOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn lint_query(
+        &self,
+        Parameters(LintQueryInput { query }): Parameters<LintQueryInput>,
+    ) -> McpResult {
+        let diagnostics = lint(&query);
+        let count = |severity| {
+            diagnostics
+                .iter()
+                .filter(|d| d.severity == severity)
+                .count()
+        };
+
+        json_result(&LintOutput {
+            errors: count(Severity::Error),
+            warnings: count(Severity::Warning),
+            diagnostics,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn codes(query: &str) -> Vec<&'static str> {
+        lint(query).into_iter().map(|d| d.code).collect()
+    }
+
+    #[rstest]
+    #[case(".h | upcase()", vec![])]
+    #[case(".h | upcse()", vec!["unresolved-symbol"])]
+    #[case("def unused(): 1; | .h", vec!["unused-function"])]
+    #[case("let x = 1 | .h", vec!["unused-variable"])]
+    #[case(".h | upcase(1, 2, 3)", vec!["arity-mismatch"])]
+    fn test_lint(#[case] query: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(codes(query), expected);
+    }
+
+    #[test]
+    fn test_lint_reports_syntax_errors() {
+        let diagnostics = lint(".h | upcase(");
+        assert!(!diagnostics.is_empty());
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_lint_query_counts_severities() {
+        let server = Server::new(None).unwrap();
+        let result = server
+            .lint_query(Parameters(LintQueryInput {
+                query: "let x = 1 | upcse()".to_string(),
+            }))
+            .unwrap();
+        let output = result.structured_content.unwrap();
+        assert_eq!(output["errors"], 1);
+        assert_eq!(output["warnings"], 1);
+    }
+}
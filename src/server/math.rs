@@ -0,0 +1,255 @@
+//! `$...$` and `$$...$$` math: the `math` parse option of `extract_markdown`
+//! and the `extract_math` tool.
+
+use mq_markdown::{Node, Position, Text};
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server, inline_runs,
+    output::{json_result, output_schema},
+    parse_markdown, parse_markdown_only,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MathMode {
+    /// Formulas are `math` and `math_inline` nodes, exactly as written.
+    Nodes,
+    /// Inline formulas stay in the text around them as `$...$`, so text
+    /// queries return whole sentences. Display formulas are `math` nodes.
+    Text,
+    /// Dollar signs are plain text, e.g. for documents with prices.
+    Off,
+}
+
+#[derive(Debug, Default, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct MathOptions {
+    #[schemars(
+        description = "How `$...$` and `$$...$$` math is parsed: `nodes` keeps formulas as math nodes exactly as written, `text` keeps inline formulas in the surrounding text so text queries return whole sentences, `off` treats dollar signs as plain text. When set, the input is parsed as markdown only, without the HTML conversion that can mangle `<` and `&` in formulas (default: unset)"
+    )]
+    pub math: Option<MathMode>,
+}
+
+impl MathOptions {
+    /// Parses `markdown` into top-level nodes per the math mode.
+    pub(crate) fn parse(&self, markdown: &str) -> Result<Vec<Node>, ErrorData> {
+        match self.math {
+            None => parse_markdown(markdown),
+            Some(MathMode::Nodes) => parse_markdown_only(markdown),
+            Some(mode) => Ok(inline_math(parse_markdown_only(markdown)?, mode)),
+        }
+    }
+}
+
+/// Rewrites math nodes as text per `mode` and merges each run of
+/// contiguous text into a single text node.
+fn inline_math(nodes: Vec<Node>, mode: MathMode) -> Vec<Node> {
+    let mut merged: Vec<Node> = Vec::with_capacity(nodes.len());
+    for mut node in nodes {
+        let children = node.children();
+        if !children.is_empty() {
+            node.set_children(inline_math(children, mode));
+        }
+        let node = match node {
+            Node::MathInline(math) => Node::Text(Text {
+                value: format!("${}$", math.value),
+                position: math.position,
+            }),
+            Node::Math(math) if mode == MathMode::Off => Node::Text(Text {
+                value: format!("$$\n{}\n$$", math.value),
+                position: math.position,
+            }),
+            node => node,
+        };
+        if let (Some(Node::Text(prev)), Node::Text(text)) = (merged.last_mut(), &node)
+            && let (Some(end), Some(position)) = (prev.position.as_mut(), &text.position)
+            && end.end == position.start
+        {
+            prev.value.push_str(&text.value);
+            end.end = position.end.clone();
+            continue;
+        }
+        merged.push(node);
+    }
+    merged
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ExtractMathInput {
+    #[schemars(description = "The markdown content to process")]
+    pub markdown: String,
+    #[schemars(description = "Only list display (`$$...$$`) formulas (default: false)")]
+    pub display_only: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct Formula {
+    #[schemars(description = "The TeX source, without the dollar signs")]
+    pub tex: String,
+    #[schemars(description = "Whether this is a display (`$$...$$`) formula")]
+    pub display: bool,
+    #[schemars(description = "1-based line where the formula starts")]
+    pub line: usize,
+    #[schemars(description = "Text of the nearest heading before the formula")]
+    pub section: Option<String>,
+    #[schemars(
+        description = "The paragraph containing an inline formula, or the paragraph just before a display formula, as markdown"
+    )]
+    pub context: Option<String>,
+}
+
+/// Output of `extract_math`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct MathOutput {
+    #[schemars(description = "Formulas in document order")]
+    pub formulas: Vec<Formula>,
+}
+
+fn formula(
+    tex: &str,
+    display: bool,
+    position: Option<Position>,
+    section: &Option<String>,
+) -> Formula {
+    Formula {
+        tex: tex.to_string(),
+        display,
+        line: position.map(|p| p.start.line).unwrap_or_default(),
+        section: section.clone(),
+        context: None,
+    }
+}
+
+/// Collects the formulas in `nodes`. `section` is the nearest heading so
+/// far and `previous` the last paragraph, for display formulas.
+fn collect(
+    nodes: &[Node],
+    section: &mut Option<String>,
+    previous: &mut Option<String>,
+    formulas: &mut Vec<Formula>,
+) {
+    for run in inline_runs(nodes) {
+        let context = run.iter().map(Node::to_string).collect::<String>();
+        let context = Some(context.trim().to_string()).filter(|c| !c.is_empty());
+        for node in run {
+            match node {
+                Node::MathInline(math) => formulas.push(Formula {
+                    context: context.clone(),
+                    ..formula(&math.value, false, math.position.clone(), section)
+                }),
+                Node::Math(math) => formulas.push(Formula {
+                    context: previous.clone(),
+                    ..formula(&math.value, true, math.position.clone(), section)
+                }),
+                Node::Heading(_) => {
+                    *section = Some(node.value().trim().to_string());
+                    collect(&node.children(), section, &mut None, formulas);
+                }
+                _ => collect(&node.children(), section, previous, formulas),
+            }
+        }
+        *previous = match run {
+            [Node::Math(_)] => previous.take(),
+            [Node::Heading(_)] => None,
+            _ => context,
+        };
+    }
+}
+
+fn extract_math(nodes: &[Node], display_only: bool) -> Vec<Formula> {
+    let mut formulas = Vec::new();
+    collect(nodes, &mut None, &mut None, &mut formulas);
+    formulas.retain(|f| f.display || !display_only);
+    formulas
+}
+
+#[tool_router(router = math_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "List the `$...$` and `$$...$$` math formulas in markdown, each with its TeX source, line, the nearest preceding heading and the surrounding paragraph as context. Formulas are read exactly as written, without the HTML conversion that can mangle `<` and `&`.",
+        output_schema = output_schema::<MathOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn extract_math(&self, Parameters(input): Parameters<ExtractMathInput>) -> McpResult {
+        let nodes = parse_markdown_only(&input.markdown)?;
+        json_result(&MathOutput {
+            formulas: extract_math(&nodes, input.display_only.unwrap_or_default()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn render(nodes: Vec<Node>) -> Vec<String> {
+        nodes.iter().map(Node::to_string).collect()
+    }
+
+    #[rstest]
+    #[case(MathMode::Nodes, vec!["Let ", "$a<b$", " hold."])]
+    #[case(MathMode::Text, vec!["Let $a<b$ hold."])]
+    #[case(MathMode::Off, vec!["Let $a<b$ hold."])]
+    fn test_inline_math(#[case] mode: MathMode, #[case] expected: Vec<&str>) {
+        let options = MathOptions { math: Some(mode) };
+        assert_eq!(render(options.parse("Let $a<b$ hold.").unwrap()), expected);
+    }
+
+    #[test]
+    fn test_off_keeps_display_math_as_text() {
+        let options = MathOptions {
+            math: Some(MathMode::Off),
+        };
+        let nodes = options.parse("It costs $5 or $10.\n\n$$\nx\n$$").unwrap();
+        assert!(nodes.iter().all(|node| matches!(node, Node::Text(_))));
+        assert_eq!(render(nodes), vec!["It costs $5 or $10.", "$$\nx\n$$"]);
+    }
+
+    #[test]
+    fn test_extract_math() {
+        let markdown = "\
+# Energy
+
+Mass and energy are related by $E = mc^2$ where $c$ is constant.
+
+The total is:
+
+$$
+\\sum_{i<n} x_i
+$$
+";
+        let nodes = parse_markdown_only(markdown).unwrap();
+        let formulas = extract_math(&nodes, false);
+        let context = "Mass and energy are related by $E = mc^2$ where $c$ is constant.";
+        assert_eq!(
+            formulas,
+            vec![
+                Formula {
+                    tex: "E = mc^2".to_string(),
+                    display: false,
+                    line: 3,
+                    section: Some("Energy".to_string()),
+                    context: Some(context.to_string()),
+                },
+                Formula {
+                    tex: "c".to_string(),
+                    display: false,
+                    line: 3,
+                    section: Some("Energy".to_string()),
+                    context: Some(context.to_string()),
+                },
+                Formula {
+                    tex: "\\sum_{i<n} x_i".to_string(),
+                    display: true,
+                    line: 7,
+                    section: Some("Energy".to_string()),
+                    context: Some("The total is:".to_string()),
+                },
+            ]
+        );
+        assert_eq!(extract_math(&nodes, true).len(), 1);
+    }
+}
@@ -0,0 +1,52 @@
+//! Tool call metrics, recorded through the global OpenTelemetry meter
+//! provider (the `otel` feature). The `mq-mcp` binary installs an OTLP
+//! exporter for them with `--otlp-endpoint`; embedders install their own
+//! provider before the first call, or the instruments stay no-ops.
+
+use std::{sync::LazyLock, time::Duration};
+
+use opentelemetry::{
+    KeyValue, global,
+    metrics::{Counter, Histogram},
+};
+
+struct Instruments {
+    calls: Counter<u64>,
+    duration: Histogram<f64>,
+    argument_bytes: Counter<u64>,
+}
+
+static INSTRUMENTS: LazyLock<Instruments> = LazyLock::new(|| {
+    let meter = global::meter("mq-mcp");
+    Instruments {
+        calls: meter
+            .u64_counter("mq_mcp.tool.calls")
+            .with_description("Tool calls, by tool and outcome")
+            .build(),
+        duration: meter
+            .f64_histogram("mq_mcp.tool.duration")
+            .with_description("Time spent running tool calls")
+            .with_unit("s")
+            .build(),
+        argument_bytes: meter
+            .u64_counter("mq_mcp.tool.argument_bytes")
+            .with_description("Bytes of tool arguments received")
+            .with_unit("By")
+            .build(),
+    }
+});
+
+/// Records a finished call of `tool`.
+pub(super) fn record_call(tool: &str, ok: bool, argument_bytes: usize, duration: Duration) {
+    let attributes = [
+        KeyValue::new("tool", tool.to_string()),
+        KeyValue::new("outcome", if ok { "ok" } else { "error" }),
+    ];
+    INSTRUMENTS.calls.add(1, &attributes);
+    INSTRUMENTS
+        .duration
+        .record(duration.as_secs_f64(), &attributes[..1]);
+    INSTRUMENTS
+        .argument_bytes
+        .add(argument_bytes as u64, &attributes[..1]);
+}
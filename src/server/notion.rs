@@ -0,0 +1,580 @@
+//! `query_notion`: Notion pages and databases fetched through the Notion
+//! API, with blocks mapped to the equivalent markdown.
+
+use std::{fmt, future::Future, pin::Pin};
+
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use rmcp::{ErrorData, handler::server::wrapper::Parameters, schemars, tool, tool_router};
+use serde_json::Value;
+
+use super::{
+    McpResult, Server,
+    fetch::parse_url,
+    openapi::markdown_table,
+    output::{QueryOutput, output_schema},
+};
+
+pub(super) const API_URL: &str = "https://api.notion.com/v1";
+const NOTION_VERSION: &str = "2022-06-28";
+/// Nested blocks below this depth are not fetched.
+const MAX_DEPTH: usize = 5;
+/// Stop fetching once a page has this many blocks (or a database this many
+/// rows).
+const MAX_BLOCKS: usize = 2000;
+
+/// Server-side settings for `query_notion`.
+#[derive(Clone, Default)]
+pub struct NotionConfig {
+    /// Internal integration token. The pages and databases to read must be
+    /// shared with the integration. `None` disables the tool.
+    pub token: Option<String>,
+}
+
+impl fmt::Debug for NotionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NotionConfig")
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl NotionConfig {
+    fn headers(&self) -> Result<HeaderMap, ErrorData> {
+        let Some(token) = &self.token else {
+            return Err(ErrorData::invalid_request(
+                "no Notion token configured — restart mq-mcp with --notion-token <token> to enable query_notion",
+                None,
+            ));
+        };
+        let mut headers = HeaderMap::new();
+        let mut value = HeaderValue::from_str(&format!("Bearer {token}")).map_err(|_| {
+            ErrorData::internal_error("Invalid Notion token in the server configuration", None)
+        })?;
+        value.set_sensitive(true);
+        headers.insert(header::AUTHORIZATION, value);
+        headers.insert("Notion-Version", HeaderValue::from_static(NOTION_VERSION));
+        Ok(headers)
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum NotionKind {
+    #[default]
+    Page,
+    /// Rows of a database, as a markdown table.
+    Database,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct QueryNotionInput {
+    #[schemars(description = "The page or database id, or its Notion URL")]
+    pub id: String,
+    #[schemars(
+        description = "`page` (default): the page's blocks as markdown. `database`: the database's rows as a markdown table."
+    )]
+    pub kind: Option<NotionKind>,
+    #[schemars(
+        description = "The mq query to execute (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+}
+
+/// A block and its fetched children.
+#[derive(Debug)]
+struct Block {
+    value: Value,
+    children: Vec<Block>,
+}
+
+/// The 32-digit id in a Notion id, UUID or page URL.
+fn parse_notion_id(id: &str) -> Result<String, ErrorData> {
+    let last = id
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .replace('-', "");
+    let hex = &last[last.len().saturating_sub(32)..];
+    if hex.len() == 32 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(hex.to_ascii_lowercase())
+    } else {
+        Err(ErrorData::invalid_params(
+            "Invalid Notion id",
+            Some(serde_json::json!({ "id": id })),
+        ))
+    }
+}
+
+fn rich_text(value: &Value) -> String {
+    let Some(segments) = value.as_array() else {
+        return String::new();
+    };
+    segments
+        .iter()
+        .map(|segment| {
+            let mut text = segment["plain_text"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            if segment["type"] == "equation" {
+                return format!("${text}$");
+            }
+            if text.trim().is_empty() {
+                return text;
+            }
+            let annotations = &segment["annotations"];
+            if annotations["code"] == true {
+                text = format!("`{text}`");
+            }
+            if annotations["bold"] == true {
+                text = format!("**{text}**");
+            }
+            if annotations["italic"] == true {
+                text = format!("*{text}*");
+            }
+            if annotations["strikethrough"] == true {
+                text = format!("~~{text}~~");
+            }
+            match segment["href"].as_str() {
+                Some(href) => format!("[{text}]({href})"),
+                None => text,
+            }
+        })
+        .collect()
+}
+
+fn page_link(id: &str) -> String {
+    format!("https://www.notion.so/{}", id.replace('-', ""))
+}
+
+/// Renders `blocks` as markdown, indenting every line by `indent`.
+fn render_blocks(blocks: &[Block], indent: &str) -> String {
+    let mut out = String::new();
+    let mut previous_was_item = false;
+    for block in blocks {
+        let kind = block.value["type"].as_str().unwrap_or_default();
+        let data = &block.value[kind];
+        let text = rich_text(&data["rich_text"]);
+        let is_item = matches!(
+            kind,
+            "bulleted_list_item" | "numbered_list_item" | "to_do" | "toggle"
+        );
+        let (rendered, child_indent) = match kind {
+            "paragraph" => (text, indent.to_string()),
+            "heading_1" | "heading_2" | "heading_3" => {
+                let level = kind[kind.len() - 1..].parse().unwrap_or(1);
+                (format!("{} {text}", "#".repeat(level)), indent.to_string())
+            }
+            "bulleted_list_item" | "toggle" => (format!("- {text}"), format!("{indent}  ")),
+            "numbered_list_item" => (format!("1. {text}"), format!("{indent}   ")),
+            "to_do" => {
+                let mark = if data["checked"] == true { "x" } else { " " };
+                (format!("- [{mark}] {text}"), format!("{indent}  "))
+            }
+            "quote" => (format!("> {text}"), format!("{indent}> ")),
+            "callout" => {
+                let icon = data["icon"]["emoji"]
+                    .as_str()
+                    .map(|emoji| format!("{emoji} "))
+                    .unwrap_or_default();
+                (format!("> {icon}{text}"), format!("{indent}> "))
+            }
+            "code" => {
+                let code = data["rich_text"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|segment| segment["plain_text"].as_str())
+                    .collect::<String>();
+                let language = data["language"].as_str().unwrap_or_default();
+                let language = if language == "plain text" {
+                    ""
+                } else {
+                    language
+                };
+                (format!("```{language}\n{code}\n```"), indent.to_string())
+            }
+            "equation" => (
+                format!(
+                    "$$\n{}\n$$",
+                    data["expression"].as_str().unwrap_or_default()
+                ),
+                indent.to_string(),
+            ),
+            "divider" => ("---".to_string(), indent.to_string()),
+            "image" => {
+                let url = data[data["type"].as_str().unwrap_or("external")]["url"]
+                    .as_str()
+                    .unwrap_or_default();
+                (
+                    format!("![{}]({url})", rich_text(&data["caption"])),
+                    indent.to_string(),
+                )
+            }
+            "bookmark" | "embed" | "link_preview" => {
+                let url = data["url"].as_str().unwrap_or_default();
+                let caption = rich_text(&data["caption"]);
+                let label = if caption.is_empty() {
+                    url
+                } else {
+                    caption.as_str()
+                };
+                (format!("[{label}]({url})"), indent.to_string())
+            }
+            "child_page" | "child_database" => {
+                let id = block.value["id"].as_str().unwrap_or_default();
+                let title = data["title"].as_str().unwrap_or_default();
+                (format!("[{title}]({})", page_link(id)), indent.to_string())
+            }
+            "table" => (render_table(block), indent.to_string()),
+            _ => continue,
+        };
+
+        if !out.is_empty() {
+            out.push_str(if is_item && previous_was_item {
+                "\n"
+            } else {
+                "\n\n"
+            });
+        }
+        previous_was_item = is_item;
+        for (i, line) in rendered.lines().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.push_str(indent);
+            out.push_str(line);
+        }
+        if kind != "table" && !block.children.is_empty() {
+            match kind {
+                _ if is_item => out.push('\n'),
+                // Keep the children inside the quote.
+                "quote" | "callout" => out.push_str(&format!("\n{indent}>\n")),
+                _ => out.push_str("\n\n"),
+            }
+            out.push_str(&render_blocks(&block.children, &child_indent));
+        }
+    }
+    out
+}
+
+fn render_table(block: &Block) -> String {
+    let rows: Vec<Vec<String>> = block
+        .children
+        .iter()
+        .map(|row| {
+            row.value["table_row"]["cells"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|cell| rich_text(cell).replace('|', r"\|"))
+                .collect()
+        })
+        .collect();
+    markdown_table(&rows)
+}
+
+/// A database property value as plain text.
+fn property_text(property: &Value) -> String {
+    let kind = property["type"].as_str().unwrap_or_default();
+    let value = &property[kind];
+    match kind {
+        "title" | "rich_text" => rich_text(value),
+        "number" => value.as_f64().map(|n| n.to_string()).unwrap_or_default(),
+        "checkbox" => value.as_bool().unwrap_or_default().to_string(),
+        "select" | "status" => value["name"].as_str().unwrap_or_default().to_string(),
+        "multi_select" | "people" | "relation" => value
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|item| item["name"].as_str().or(item["id"].as_str()))
+            .collect::<Vec<_>>()
+            .join(", "),
+        "date" => match (value["start"].as_str(), value["end"].as_str()) {
+            (Some(start), Some(end)) => format!("{start} → {end}"),
+            (Some(start), None) => start.to_string(),
+            _ => String::new(),
+        },
+        "url" | "email" | "phone_number" | "created_time" | "last_edited_time" => {
+            value.as_str().unwrap_or_default().to_string()
+        }
+        "formula" => {
+            let formula_kind = value["type"].as_str().unwrap_or_default();
+            match &value[formula_kind] {
+                Value::String(s) => s.clone(),
+                Value::Null => String::new(),
+                other => other.to_string(),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// Database rows as a table; the title column comes first, the others in
+/// name order.
+fn render_database(rows: &[Value]) -> String {
+    let Some(first) = rows.first().and_then(|row| row["properties"].as_object()) else {
+        return String::new();
+    };
+    let mut columns: Vec<&String> = first.keys().collect();
+    columns.sort_by_key(|name| (first[name.as_str()]["type"] != "title", name.to_string()));
+
+    let mut table: Vec<Vec<String>> = vec![columns.iter().map(|name| name.to_string()).collect()];
+    table.extend(rows.iter().map(|row| {
+        columns
+            .iter()
+            .map(|name| {
+                property_text(&row["properties"][name.as_str()])
+                    .replace('|', r"\|")
+                    .replace('\n', " ")
+            })
+            .collect()
+    }));
+    markdown_table(&table)
+}
+
+impl Server {
+    async fn notion_json(
+        &self,
+        url: url::Url,
+        headers: &HeaderMap,
+        body: Option<&Value>,
+    ) -> Result<Value, ErrorData> {
+        let page = match body {
+            Some(body) => self.fetcher.post_api(url, headers.clone(), body).await?,
+            None => self.fetcher.fetch_api(url, headers.clone()).await?,
+        };
+        serde_json::from_str(&page.text).map_err(|e| {
+            ErrorData::internal_error(
+                "Invalid Notion API response",
+                Some(serde_json::json!({ "url": page.url, "error": e.to_string() })),
+            )
+        })
+    }
+
+    /// Fetches the children of block (or page) `id`, recursively, following
+    /// pagination. `budget` is the number of blocks still allowed.
+    fn notion_children<'a>(
+        &'a self,
+        id: String,
+        headers: &'a HeaderMap,
+        depth: usize,
+        budget: &'a mut usize,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<Block>, ErrorData>> + Send + 'a>> {
+        Box::pin(async move {
+            let mut blocks = Vec::new();
+            let mut cursor: Option<String> = None;
+            loop {
+                let mut url = parse_url(&format!("{API_URL}/blocks/{id}/children"))?;
+                url.query_pairs_mut().append_pair("page_size", "100");
+                if let Some(cursor) = &cursor {
+                    url.query_pairs_mut().append_pair("start_cursor", cursor);
+                }
+                let response = self.notion_json(url, headers, None).await?;
+                for value in response["results"].as_array().into_iter().flatten() {
+                    if *budget == 0 {
+                        return Ok(blocks);
+                    }
+                    *budget -= 1;
+                    let children = match (value["has_children"] == true, value["id"].as_str()) {
+                        (true, Some(child)) if depth < MAX_DEPTH => {
+                            self.notion_children(child.to_string(), headers, depth + 1, budget)
+                                .await?
+                        }
+                        _ => Vec::new(),
+                    };
+                    blocks.push(Block {
+                        value: value.clone(),
+                        children,
+                    });
+                }
+                match response["next_cursor"].as_str() {
+                    Some(next) if response["has_more"] == true => cursor = Some(next.to_string()),
+                    _ => return Ok(blocks),
+                }
+            }
+        })
+    }
+
+    async fn notion_database_rows(
+        &self,
+        id: &str,
+        headers: &HeaderMap,
+    ) -> Result<Vec<Value>, ErrorData> {
+        let url = parse_url(&format!("{API_URL}/databases/{id}/query"))?;
+        let mut rows = Vec::new();
+        let mut body = serde_json::json!({ "page_size": 100 });
+        loop {
+            let response = self.notion_json(url.clone(), headers, Some(&body)).await?;
+            rows.extend(
+                response["results"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .cloned(),
+            );
+            match response["next_cursor"].as_str() {
+                Some(next) if response["has_more"] == true && rows.len() < MAX_BLOCKS => {
+                    body["start_cursor"] = Value::String(next.to_string());
+                }
+                _ => break,
+            }
+        }
+        rows.truncate(MAX_BLOCKS);
+        Ok(rows)
+    }
+}
+
+#[tool_router(router = notion_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Fetch a Notion page (its blocks as markdown) or database (its rows as a markdown table) through the Notion API and run an mq query on it. Requires the server to be started with --notion-token, and the page to be shared with that integration.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = true)
+    )]
+    async fn query_notion(&self, Parameters(input): Parameters<QueryNotionInput>) -> McpResult {
+        let headers = self.config.notion.headers()?;
+        let id = parse_notion_id(&input.id)?;
+        let markdown = match input.kind.unwrap_or_default() {
+            NotionKind::Page => {
+                let mut budget = MAX_BLOCKS;
+                let blocks = self.notion_children(id, &headers, 0, &mut budget).await?;
+                render_blocks(&blocks, "")
+            }
+            NotionKind::Database => {
+                render_database(&self.notion_database_rows(&id, &headers).await?)
+            }
+        };
+        self.eval_query(&markdown, input.query.as_deref().unwrap_or("identity()"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn text(content: &str) -> Value {
+        json!([{ "type": "text", "plain_text": content, "href": null, "annotations": {} }])
+    }
+
+    fn block(kind: &str, data: Value, children: Vec<Block>) -> Block {
+        Block {
+            value: json!({ "id": "0123", "type": kind, kind: data }),
+            children,
+        }
+    }
+
+    #[rstest]
+    #[case(
+        "0123456789abcdef0123456789abcdef",
+        Some("0123456789abcdef0123456789abcdef")
+    )]
+    #[case(
+        "01234567-89ab-cdef-0123-456789abcdef",
+        Some("0123456789abcdef0123456789abcdef")
+    )]
+    #[case(
+        "https://www.notion.so/acme/Release-Plan-0123456789ABCDEF0123456789abcdef?v=1",
+        Some("0123456789abcdef0123456789abcdef")
+    )]
+    #[case("Release Plan", None)]
+    fn test_parse_notion_id(#[case] id: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_notion_id(id).ok().as_deref(), expected);
+    }
+
+    #[test]
+    fn test_rich_text_annotations() {
+        let value = json!([
+            { "type": "text", "plain_text": "Read ", "href": null, "annotations": {} },
+            { "type": "text", "plain_text": "the docs", "href": "https://mqlang.org", "annotations": { "bold": true } },
+            { "type": "equation", "plain_text": "x^2", "annotations": {} },
+        ]);
+        assert_eq!(
+            rich_text(&value),
+            "Read [**the docs**](https://mqlang.org)$x^2$"
+        );
+    }
+
+    #[test]
+    fn test_render_blocks() {
+        let blocks = vec![
+            block("heading_1", json!({ "rich_text": text("Plan") }), vec![]),
+            block("paragraph", json!({ "rich_text": text("Intro") }), vec![]),
+            block(
+                "bulleted_list_item",
+                json!({ "rich_text": text("Parent") }),
+                vec![block(
+                    "to_do",
+                    json!({ "rich_text": text("Child"), "checked": true }),
+                    vec![],
+                )],
+            ),
+            block(
+                "bulleted_list_item",
+                json!({ "rich_text": text("Sibling") }),
+                vec![],
+            ),
+            block(
+                "code",
+                json!({ "rich_text": text("fn main() {}"), "language": "rust" }),
+                vec![],
+            ),
+            block(
+                "table",
+                json!({ "table_width": 2 }),
+                vec![
+                    block(
+                        "table_row",
+                        json!({ "cells": [text("a"), text("b")] }),
+                        vec![],
+                    ),
+                    block(
+                        "table_row",
+                        json!({ "cells": [text("1"), text("2")] }),
+                        vec![],
+                    ),
+                ],
+            ),
+        ];
+        assert_eq!(
+            render_blocks(&blocks, ""),
+            "\
+# Plan
+
+Intro
+
+- Parent
+  - [x] Child
+- Sibling
+
+```rust
+fn main() {}
+```
+
+| a | b |
+| --- | --- |
+| 1 | 2 |"
+        );
+    }
+
+    #[test]
+    fn test_render_database() {
+        let rows = vec![json!({ "properties": {
+            "Status": { "type": "status", "status": { "name": "Done" } },
+            "Name": { "type": "title", "title": text("Ship it") },
+            "Due": { "type": "date", "date": { "start": "2026-01-02", "end": null } },
+        }})];
+        assert_eq!(
+            render_database(&rows),
+            "| Name | Due | Status |\n| --- | --- | --- |\n| Ship it | 2026-01-02 | Done |"
+        );
+    }
+}
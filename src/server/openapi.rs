@@ -0,0 +1,480 @@
+//! `openapi_to_markdown`: OpenAPI 3 and Swagger 2 specs rendered as
+//! markdown reference documentation.
+
+use rmcp::{ErrorData, handler::server::wrapper::Parameters, schemars, tool, tool_router};
+use serde_json::Value;
+
+use super::{
+    McpResult, Server,
+    output::{QueryOutput, output_schema},
+};
+
+/// Operations in the order they are listed under each path.
+const METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// A markdown table whose first row is the header.
+pub(super) fn markdown_table(rows: &[Vec<String>]) -> String {
+    let Some(header) = rows.first() else {
+        return String::new();
+    };
+    let line = |cells: &[String]| format!("| {} |", cells.join(" | "));
+    let separator = vec!["---".to_string(); header.len()];
+    let mut lines = vec![line(header), line(&separator)];
+    lines.extend(rows[1..].iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct OpenapiToMarkdownInput {
+    #[schemars(description = "The OpenAPI 3.x or Swagger 2.0 spec, as JSON or YAML")]
+    pub spec: String,
+    #[schemars(
+        description = "The mq query to execute (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+}
+
+fn parse_spec(spec: &str) -> Result<Value, ErrorData> {
+    let value = match serde_json::from_str::<Value>(spec) {
+        Ok(value) => value,
+        Err(_) => serde_yaml::from_str::<Value>(spec).map_err(|e| {
+            ErrorData::invalid_params(
+                "Failed to parse the spec as JSON or YAML",
+                Some(serde_json::Value::String(e.to_string())),
+            )
+        })?,
+    };
+    if value.get("openapi").is_none() && value.get("swagger").is_none() {
+        return Err(ErrorData::invalid_params(
+            "Not an OpenAPI or Swagger spec: no `openapi` or `swagger` field",
+            None,
+        ));
+    }
+    Ok(value)
+}
+
+/// Makes `text` safe inside a table cell.
+fn cell(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+/// A short type description of a schema, e.g. `Pet[]` or `integer (int64)`.
+fn schema_type(schema: &Value) -> String {
+    if let Some(reference) = schema["$ref"].as_str() {
+        return reference
+            .rsplit('/')
+            .next()
+            .unwrap_or(reference)
+            .to_string();
+    }
+    for (key, separator) in [("oneOf", " | "), ("anyOf", " | "), ("allOf", " & ")] {
+        if let Some(variants) = schema[key].as_array() {
+            return variants
+                .iter()
+                .map(schema_type)
+                .collect::<Vec<_>>()
+                .join(separator);
+        }
+    }
+    let kind = match &schema["type"] {
+        Value::String(kind) => kind.clone(),
+        // OpenAPI 3.1 allows `type: [string, "null"]`.
+        Value::Array(kinds) => kinds
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" | "),
+        _ if schema.get("properties").is_some() => "object".to_string(),
+        _ => return "any".to_string(),
+    };
+    if kind == "array" {
+        return format!("{}[]", schema_type(&schema["items"]));
+    }
+    let mut text = match schema["format"].as_str() {
+        Some(format) => format!("{kind} ({format})"),
+        None => kind,
+    };
+    if let Some(values) = schema["enum"].as_array() {
+        let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        text.push_str(&format!(": {}", values.join(", ")));
+    }
+    text
+}
+
+/// Resolves a local `$ref` (`#/components/parameters/Limit`) one level.
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    match value["$ref"].as_str().and_then(|r| r.strip_prefix('#')) {
+        Some(pointer) => spec.pointer(pointer).unwrap_or(value),
+        None => value,
+    }
+}
+
+fn parameters_table(spec: &Value, parameters: &[&Value]) -> Option<String> {
+    let mut rows = vec![
+        ["Name", "In", "Type", "Required", "Description"]
+            .map(String::from)
+            .to_vec(),
+    ];
+    for parameter in parameters {
+        let parameter = resolve(spec, parameter);
+        // Swagger 2 body parameters are shown as the request body.
+        if parameter["in"] == "body" {
+            continue;
+        }
+        // Swagger 2 puts the type on the parameter itself.
+        let schema = parameter.get("schema").unwrap_or(parameter);
+        rows.push(vec![
+            format!("`{}`", parameter["name"].as_str().unwrap_or_default()),
+            parameter["in"].as_str().unwrap_or_default().to_string(),
+            cell(&schema_type(schema)),
+            if parameter["required"].as_bool().unwrap_or_default() {
+                "yes"
+            } else {
+                "no"
+            }
+            .to_string(),
+            cell(parameter["description"].as_str().unwrap_or_default()),
+        ]);
+    }
+    (rows.len() > 1).then(|| markdown_table(&rows))
+}
+
+/// `application/json`: `Pet` lines for an OpenAPI 3 `content` map.
+fn content_lines(content: &Value) -> Vec<String> {
+    content
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(media_type, media)| format!("- `{media_type}`: `{}`", schema_type(&media["schema"])))
+        .collect()
+}
+
+fn render_operation(
+    spec: &Value,
+    path: &str,
+    method: &str,
+    operation: &Value,
+    path_parameters: &[Value],
+) -> String {
+    let mut sections = vec![format!("### {} {path}", method.to_ascii_uppercase())];
+    if operation["deprecated"].as_bool().unwrap_or_default() {
+        sections.push("**Deprecated**".to_string());
+    }
+    for key in ["summary", "description"] {
+        if let Some(text) = operation[key].as_str().filter(|t| !t.trim().is_empty()) {
+            sections.push(text.trim().to_string());
+        }
+    }
+    if let Some(id) = operation["operationId"].as_str() {
+        sections.push(format!("Operation ID: `{id}`"));
+    }
+
+    let parameters: Vec<&Value> = path_parameters
+        .iter()
+        .chain(operation["parameters"].as_array().into_iter().flatten())
+        .collect();
+    if let Some(table) = parameters_table(spec, &parameters) {
+        sections.push(format!("#### Parameters\n\n{table}"));
+    }
+
+    let body = match operation.get("requestBody") {
+        Some(body) => {
+            let body = resolve(spec, body);
+            let mut lines = Vec::new();
+            if let Some(description) = body["description"].as_str() {
+                lines.push(description.trim().to_string());
+            }
+            lines.extend(content_lines(&body["content"]));
+            lines
+        }
+        None => parameters
+            .iter()
+            .map(|p| resolve(spec, p))
+            .filter(|p| p["in"] == "body")
+            .map(|p| format!("- `{}`", schema_type(&p["schema"])))
+            .collect(),
+    };
+    if !body.is_empty() {
+        sections.push(format!("#### Request body\n\n{}", body.join("\n")));
+    }
+
+    if let Some(responses) = operation["responses"].as_object() {
+        let mut rows = vec![["Status", "Description", "Type"].map(String::from).to_vec()];
+        for (status, response) in responses {
+            let response = resolve(spec, response);
+            let types: Vec<String> = match response.get("schema") {
+                Some(schema) => vec![schema_type(schema)],
+                None => response["content"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(_, media)| schema_type(&media["schema"]))
+                    .collect(),
+            };
+            let mut types: Vec<String> = types.into_iter().filter(|t| t != "any").collect();
+            types.dedup();
+            rows.push(vec![
+                format!("`{status}`"),
+                cell(response["description"].as_str().unwrap_or_default()),
+                cell(&types.join(", ")),
+            ]);
+        }
+        if rows.len() > 1 {
+            sections.push(format!("#### Responses\n\n{}", markdown_table(&rows)));
+        }
+    }
+    sections.join("\n\n")
+}
+
+fn render_schema(name: &str, schema: &Value) -> String {
+    let mut sections = vec![format!("### {name}")];
+    if let Some(description) = schema["description"].as_str() {
+        sections.push(description.trim().to_string());
+    }
+    match schema["properties"].as_object() {
+        Some(properties) => {
+            let required: Vec<&str> = schema["required"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            let mut rows = vec![
+                ["Property", "Type", "Required", "Description"]
+                    .map(String::from)
+                    .to_vec(),
+            ];
+            for (property, value) in properties {
+                rows.push(vec![
+                    format!("`{property}`"),
+                    cell(&schema_type(value)),
+                    if required.contains(&property.as_str()) {
+                        "yes"
+                    } else {
+                        "no"
+                    }
+                    .to_string(),
+                    cell(value["description"].as_str().unwrap_or_default()),
+                ]);
+            }
+            sections.push(markdown_table(&rows));
+        }
+        None => sections.push(format!("Type: `{}`", schema_type(schema))),
+    }
+    sections.join("\n\n")
+}
+
+/// Renders a parsed spec: overview, endpoints by path, then schemas.
+fn spec_to_markdown(spec: &Value) -> String {
+    let info = &spec["info"];
+    let title = info["title"].as_str().unwrap_or("API");
+    let mut sections = vec![match info["version"].as_str() {
+        Some(version) => format!("# {title} {version}"),
+        None => format!("# {title}"),
+    }];
+    if let Some(description) = info["description"].as_str() {
+        sections.push(description.trim().to_string());
+    }
+
+    let servers: Vec<String> = match spec["servers"].as_array() {
+        Some(servers) => servers
+            .iter()
+            .filter_map(|s| s["url"].as_str())
+            .map(|url| format!("- `{url}`"))
+            .collect(),
+        None => spec["host"]
+            .as_str()
+            .map(|host| {
+                let scheme = spec["schemes"][0].as_str().unwrap_or("https");
+                let base = spec["basePath"].as_str().unwrap_or_default();
+                vec![format!("- `{scheme}://{host}{base}`")]
+            })
+            .unwrap_or_default(),
+    };
+    if !servers.is_empty() {
+        sections.push(format!("Servers:\n\n{}", servers.join("\n")));
+    }
+
+    if let Some(paths) = spec["paths"].as_object().filter(|p| !p.is_empty()) {
+        sections.push("## Endpoints".to_string());
+        for (path, item) in paths {
+            let item = resolve(spec, item);
+            let path_parameters = item["parameters"].as_array().cloned().unwrap_or_default();
+            for method in METHODS {
+                if let Some(operation) = item.get(*method) {
+                    sections.push(render_operation(
+                        spec,
+                        path,
+                        method,
+                        operation,
+                        &path_parameters,
+                    ));
+                }
+            }
+        }
+    }
+
+    let schemas = spec["components"]["schemas"]
+        .as_object()
+        .or(spec["definitions"].as_object())
+        .filter(|s| !s.is_empty());
+    if let Some(schemas) = schemas {
+        sections.push("## Schemas".to_string());
+        sections.extend(
+            schemas
+                .iter()
+                .map(|(name, schema)| render_schema(name, schema)),
+        );
+    }
+    sections.join("\n\n")
+}
+
+#[tool_router(router = openapi_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Convert an OpenAPI 3.x or Swagger 2.0 spec (JSON or YAML) to markdown API documentation and run an mq query on it. Endpoints become `### METHOD /path` sections with parameter, request body and response tables; schemas are listed under `## Schemas` with a property table each.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn openapi_to_markdown(
+        &self,
+        Parameters(input): Parameters<OpenapiToMarkdownInput>,
+    ) -> McpResult {
+        let spec = parse_spec(&input.spec)?;
+        self.eval_query(
+            &spec_to_markdown(&spec),
+            input.query.as_deref().unwrap_or("identity()"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    const PETSTORE: &str = r##"
+openapi: 3.0.3
+info:
+  title: Petstore
+  version: 1.0.0
+servers:
+  - url: https://petstore.example.com/v1
+paths:
+  /pets/{petId}:
+    parameters:
+      - $ref: "#/components/parameters/PetId"
+    get:
+      summary: Get a pet
+      operationId: getPet
+      responses:
+        "200":
+          description: The pet
+          content:
+            application/json:
+              schema:
+                $ref: "#/components/schemas/Pet"
+        "404":
+          description: Not found
+components:
+  parameters:
+    PetId:
+      name: petId
+      in: path
+      required: true
+      description: The pet id
+      schema:
+        type: integer
+        format: int64
+  schemas:
+    Pet:
+      required: [name]
+      properties:
+        name:
+          type: string
+        tags:
+          type: array
+          items:
+            type: string
+"##;
+
+    #[test]
+    fn test_spec_to_markdown() {
+        let spec = parse_spec(PETSTORE).unwrap();
+        assert_eq!(
+            spec_to_markdown(&spec),
+            "\
+# Petstore 1.0.0
+
+Servers:
+
+- `https://petstore.example.com/v1`
+
+## Endpoints
+
+### GET /pets/{petId}
+
+Get a pet
+
+Operation ID: `getPet`
+
+#### Parameters
+
+| Name | In | Type | Required | Description |
+| --- | --- | --- | --- | --- |
+| `petId` | path | integer (int64) | yes | The pet id |
+
+#### Responses
+
+| Status | Description | Type |
+| --- | --- | --- |
+| `200` | The pet | Pet |
+| `404` | Not found |  |
+
+## Schemas
+
+### Pet
+
+| Property | Type | Required | Description |
+| --- | --- | --- | --- |
+| `name` | string | yes |  |
+| `tags` | string[] | no |  |"
+        );
+    }
+
+    #[rstest]
+    #[case(json!({"$ref": "#/definitions/Pet"}), "Pet")]
+    #[case(json!({"type": "array", "items": {"$ref": "#/components/schemas/Pet"}}), "Pet[]")]
+    #[case(json!({"oneOf": [{"type": "string"}, {"type": "integer"}]}), "string | integer")]
+    #[case(json!({"type": ["string", "null"]}), "string | null")]
+    #[case(json!({"type": "string", "enum": ["a", "b"]}), "string: \"a\", \"b\"")]
+    #[case(json!({}), "any")]
+    fn test_schema_type(#[case] schema: Value, #[case] expected: &str) {
+        assert_eq!(schema_type(&schema), expected);
+    }
+
+    #[test]
+    fn test_swagger_body_parameter() {
+        let spec = parse_spec(
+            r##"{"swagger": "2.0", "info": {"title": "T"}, "host": "api.example.com", "basePath": "/v2",
+                "paths": {"/pets": {"post": {"parameters": [{"in": "body", "name": "pet", "schema": {"$ref": "#/definitions/Pet"}}],
+                "responses": {"201": {"description": "Created"}}}}}}"##,
+        )
+        .unwrap();
+        let markdown = spec_to_markdown(&spec);
+        assert!(markdown.contains("- `https://api.example.com/v2`"));
+        assert!(markdown.contains("#### Request body\n\n- `Pet`"));
+        assert!(!markdown.contains("#### Parameters"));
+    }
+
+    #[test]
+    fn test_parse_spec_rejects_other_documents() {
+        assert!(parse_spec("name: not a spec").is_err());
+    }
+}
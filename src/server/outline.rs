@@ -0,0 +1,311 @@
+//! `outline`: the heading tree of a document with the size of each
+//! section, so an agent can look over a large document cheaply and fetch
+//! only the sections it needs. Deep headings can be collapsed into their
+//! parents, and a single section can be expanded on its own.
+
+use markdown::mdast;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{
+    McpResult, Server,
+    ast::parse_mdast,
+    keywords::prose,
+    output::{json_result, output_schema},
+    sections::{self, Section},
+};
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct OutlineInput {
+    #[schemars(description = "The markdown document to outline")]
+    pub markdown: String,
+    #[schemars(
+        description = "Deepest heading level to list, 1 to 6 (default: 6); deeper sections are collapsed into their parent, whose counts include them"
+    )]
+    pub max_depth: Option<u8>,
+    #[schemars(
+        description = "Id of a section (`s-...`) to outline alone, with its subsections, e.g. to expand a collapsed one"
+    )]
+    pub section: Option<String>,
+}
+
+/// The blocks of a section, not counting its heading.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct BlockCounts {
+    pub paragraphs: usize,
+    pub code_blocks: usize,
+    pub lists: usize,
+    pub tables: usize,
+    #[schemars(description = "Words of prose, leaving out code")]
+    pub words: usize,
+}
+
+impl BlockCounts {
+    /// Counts the top-level blocks of `markdown` after its heading, if it
+    /// starts with one.
+    fn new(markdown: &str) -> Result<Self, ErrorData> {
+        let root = parse_mdast(markdown)?;
+        let mut counts = Self::default();
+        let mut heading_words = 0;
+        for (i, node) in root.children().into_iter().flatten().enumerate() {
+            match node {
+                mdast::Node::Heading(_) if i == 0 => {
+                    heading_words = node.to_string().unicode_words().count();
+                }
+                mdast::Node::Paragraph(_) => counts.paragraphs += 1,
+                mdast::Node::Code(_) => counts.code_blocks += 1,
+                mdast::Node::List(_) => counts.lists += 1,
+                mdast::Node::Table(_) => counts.tables += 1,
+                _ => {}
+            }
+        }
+        counts.words = prose(markdown)
+            .unicode_words()
+            .count()
+            .saturating_sub(heading_words);
+        Ok(counts)
+    }
+
+    fn add(&mut self, other: Self) {
+        self.paragraphs += other.paragraphs;
+        self.code_blocks += other.code_blocks;
+        self.lists += other.lists;
+        self.tables += other.tables;
+        self.words += other.words;
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct OutlineEntry {
+    #[schemars(description = "The section's stable id, accepted by edit_section")]
+    pub id: String,
+    pub title: String,
+    #[schemars(description = "Heading level, 1 to 6")]
+    pub depth: u8,
+    #[schemars(description = "1-based line of the heading")]
+    pub line: usize,
+    #[serde(flatten)]
+    pub counts: BlockCounts,
+    #[schemars(description = "Number of subsections collapsed into this one by `max_depth`")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collapsed: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<OutlineEntry>,
+}
+
+impl OutlineEntry {
+    fn new(section: &Section, counts: BlockCounts) -> Self {
+        Self {
+            id: section.id.clone(),
+            title: section.title.clone().unwrap_or_default(),
+            depth: section.depth,
+            line: section.line,
+            counts,
+            collapsed: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Output of `outline`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct OutlineOutput {
+    #[schemars(description = "The blocks before the first heading, if any")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preamble: Option<BlockCounts>,
+    #[schemars(description = "The top-level sections, each with its subsections as `children`")]
+    pub sections: Vec<OutlineEntry>,
+}
+
+/// Moves the innermost open entry of `open` into its parent, or into
+/// `roots` if it has none.
+fn close(open: &mut Vec<OutlineEntry>, roots: &mut Vec<OutlineEntry>) {
+    if let Some(entry) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.children.push(entry),
+            None => roots.push(entry),
+        }
+    }
+}
+
+fn outline(input: &OutlineInput) -> Result<OutlineOutput, ErrorData> {
+    let max_depth = input.max_depth.unwrap_or(6).clamp(1, 6);
+    let mut sections = sections::split(&input.markdown)?;
+    let mut preamble = None;
+    if sections
+        .first()
+        .is_some_and(|section| section.title.is_none())
+    {
+        preamble = Some(BlockCounts::new(&sections.remove(0).markdown)?);
+    }
+    if let Some(id) = &input.section {
+        let Some(start) = sections.iter().position(|section| &section.id == id) else {
+            return Err(ErrorData::invalid_params(
+                "Section not found",
+                Some(serde_json::json!({ "id": id })),
+            ));
+        };
+        let depth = sections[start].depth;
+        let end = sections[start + 1..]
+            .iter()
+            .position(|section| section.depth <= depth)
+            .map_or(sections.len(), |offset| start + 1 + offset);
+        sections.truncate(end);
+        sections.drain(..start);
+        preamble = None;
+    }
+
+    let mut roots = Vec::new();
+    let mut open: Vec<OutlineEntry> = Vec::new();
+    for section in &sections {
+        let counts = BlockCounts::new(&section.markdown)?;
+        if section.depth > max_depth
+            && let Some(parent) = open.last_mut()
+        {
+            parent.counts.add(counts);
+            *parent.collapsed.get_or_insert(0) += 1;
+            continue;
+        }
+        while open
+            .last()
+            .is_some_and(|entry| entry.depth >= section.depth)
+        {
+            close(&mut open, &mut roots);
+        }
+        open.push(OutlineEntry::new(section, counts));
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut roots);
+    }
+    Ok(OutlineOutput {
+        preamble,
+        sections: roots,
+    })
+}
+
+#[tool_router(router = outline_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Outline a markdown document: its heading tree as compact JSON, with each section's stable id, line and counts of paragraphs, code blocks, lists, tables and words. A cheap overview of a large document before fetching the sections needed; `max_depth` collapses deep headings into their parents and `section` expands one section.",
+        output_schema = output_schema::<OutlineOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn outline(&self, Parameters(input): Parameters<OutlineInput>) -> McpResult {
+        json_result(&outline(&input)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const MARKDOWN: &str = "\
+Intro text.
+
+# Guide
+
+Read this first.
+
+## Install
+
+Run the installer.
+
+```sh
+make install
+```
+
+### From source
+
+Clone it, then build it.
+
+## Usage
+
+- run
+- stop
+
+# FAQ
+
+| Q | A |
+|---|---|
+| a | b |
+";
+
+    fn input(max_depth: Option<u8>, section: Option<String>) -> OutlineInput {
+        OutlineInput {
+            markdown: MARKDOWN.to_string(),
+            max_depth,
+            section,
+        }
+    }
+
+    fn titles(entries: &[OutlineEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| {
+                if entry.children.is_empty() {
+                    entry.title.clone()
+                } else {
+                    format!("{} ({})", entry.title, titles(&entry.children).join(", "))
+                }
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(None, vec!["Guide (Install (From source), Usage)", "FAQ"])]
+    #[case(Some(2), vec!["Guide (Install, Usage)", "FAQ"])]
+    #[case(Some(1), vec!["Guide", "FAQ"])]
+    fn test_outline(#[case] max_depth: Option<u8>, #[case] expected: Vec<&str>) {
+        let output = outline(&input(max_depth, None)).unwrap();
+        assert_eq!(titles(&output.sections), expected);
+    }
+
+    #[test]
+    fn test_counts() {
+        let output = outline(&input(None, None)).unwrap();
+        assert_eq!(
+            output.preamble,
+            Some(BlockCounts {
+                paragraphs: 1,
+                words: 2,
+                ..Default::default()
+            })
+        );
+        let install = &output.sections[0].children[0];
+        assert_eq!(install.line, 7);
+        assert_eq!(
+            install.counts,
+            BlockCounts {
+                paragraphs: 1,
+                code_blocks: 1,
+                words: 3,
+                ..Default::default()
+            }
+        );
+        assert_eq!(output.sections[1].counts.tables, 1);
+    }
+
+    #[test]
+    fn test_collapsed_sections_are_counted_in_their_parent() {
+        let output = outline(&input(Some(1), None)).unwrap();
+        let guide = &output.sections[0];
+        assert_eq!(guide.collapsed, Some(3));
+        assert_eq!(guide.counts.paragraphs, 3);
+        assert_eq!(guide.counts.lists, 1);
+        assert_eq!(output.sections[1].collapsed, None);
+    }
+
+    #[test]
+    fn test_expand_a_section() {
+        let install = outline(&input(None, None)).unwrap().sections[0].children[0]
+            .id
+            .clone();
+        let output = outline(&input(None, Some(install))).unwrap();
+        assert_eq!(output.preamble, None);
+        assert_eq!(titles(&output.sections), vec!["Install (From source)"]);
+        assert!(outline(&input(None, Some("s-000000000000".to_string()))).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Typed tool outputs, returned to clients as `structuredContent` alongside
+//! the plain text blocks.
+
+use std::sync::Arc;
+
+use rmcp::{
+    ErrorData,
+    handler::server::tool::schema_for_output,
+    model::{CallToolResult, ContentBlock, JsonObject},
+    schemars,
+    serde::Serialize,
+};
+
+use super::McpResult;
+
+#[derive(Debug, Serialize, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct FunctionInfo {
+    #[schemars(description = "The function name")]
+    pub name: String,
+    #[schemars(description = "The function description")]
+    pub description: String,
+    #[schemars(description = "The function parameters")]
+    pub params: Vec<String>,
+    #[schemars(description = "Whether this is a built-in function")]
+    pub is_builtin: bool,
+}
+
+#[derive(Debug, Serialize, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SelectorInfo {
+    #[schemars(description = "The function name")]
+    pub name: String,
+    #[schemars(description = "The function description")]
+    pub description: String,
+    #[schemars(description = "The function parameters")]
+    pub params: Vec<String>,
+}
+
+/// Output of the query, selector and section tools.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct QueryOutput {
+    #[schemars(description = "Non-empty result values rendered as markdown, in evaluation order")]
+    pub results: Vec<String>,
+}
+
+/// One result of `extract_markdown_v2`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct LocatedValue {
+    #[schemars(description = "The result rendered as markdown")]
+    pub value: String,
+    #[schemars(
+        description = "First line of the input the result was extracted from (1-based); null if it has no place in the input, e.g. a count over the whole document"
+    )]
+    pub line: Option<usize>,
+    #[schemars(description = "Last line of the input the result was extracted from")]
+    pub end_line: Option<usize>,
+}
+
+/// Output of `extract_markdown_v2`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct LocatedOutput {
+    #[schemars(description = "Non-empty result values, in evaluation order")]
+    pub values: Vec<LocatedValue>,
+}
+
+/// Output of `available_functions`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct FunctionsOutput {
+    #[schemars(description = "Functions that can be called from an mq query")]
+    pub functions: Vec<FunctionInfo>,
+    #[schemars(description = "Example queries")]
+    pub examples: Vec<String>,
+}
+
+/// Output of `available_selectors`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SelectorsOutput {
+    #[schemars(description = "Selectors that can be used in an mq query")]
+    pub selectors: Vec<SelectorInfo>,
+}
+
+/// Output of `db_sql`.
+#[cfg(feature = "db")]
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct DbSqlOutput {
+    #[schemars(description = "Column names, in select order")]
+    pub columns: Vec<String>,
+    #[schemars(description = "Result rows; each row has one value per column")]
+    pub rows: Vec<Vec<String>>,
+}
+
+#[cfg(feature = "db")]
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct DbDocument {
+    pub id: u32,
+    pub path: Option<String>,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub block_count: u32,
+}
+
+/// Output of `db_list_documents`.
+#[cfg(feature = "db")]
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct DbDocumentsOutput {
+    pub documents: Vec<DbDocument>,
+}
+
+#[cfg(feature = "db")]
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct BlockTypeCount {
+    pub block_type: String,
+    pub count: usize,
+}
+
+#[cfg(feature = "db")]
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct CodeLangCount {
+    pub lang: String,
+    pub count: usize,
+}
+
+/// Output of `db_stats`.
+#[cfg(feature = "db")]
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct DbStatsOutput {
+    pub documents: usize,
+    pub blocks: usize,
+    pub block_type_counts: Vec<BlockTypeCount>,
+    pub code_lang_counts: Vec<CodeLangCount>,
+}
+
+#[cfg(any(feature = "db", feature = "index"))]
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct IndexFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Output of `db_index`.
+#[cfg(feature = "db")]
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct DbIndexOutput {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: usize,
+    pub removed: Vec<String>,
+    pub failed: Vec<IndexFailure>,
+    #[schemars(description = "Whether this was a dry run that left the database unchanged")]
+    pub dry_run: bool,
+}
+
+/// JSON schema advertised as a tool's `outputSchema` — used as
+/// `#[tool(output_schema = output_schema::<T>())]`.
+pub(crate) fn output_schema<T: schemars::JsonSchema + 'static>() -> Arc<JsonObject> {
+    schema_for_output::<T>().expect("tool output types are JSON objects")
+}
+
+/// Builds a successful result carrying `output` as `structuredContent`, with
+/// `content` as the text fallback for clients without structured output
+/// support.
+pub(crate) fn structured_result<T: Serialize>(content: Vec<ContentBlock>, output: &T) -> McpResult {
+    let value = serde_json::to_value(output).map_err(|e| {
+        ErrorData::internal_error(
+            "Failed to serialize structured content",
+            Some(serde_json::Value::String(e.to_string())),
+        )
+    })?;
+    let mut result = CallToolResult::success(content);
+    result.structured_content = Some(value);
+    Ok(result)
+}
+
+/// Builds a structured result whose text fallback is `output` serialized as
+/// a single JSON text block.
+pub(crate) fn json_result<T: Serialize>(output: &T) -> McpResult {
+    let text = serde_json::to_string(output).map_err(|e| {
+        ErrorData::internal_error(
+            "Failed to serialize output",
+            Some(serde_json::Value::String(e.to_string())),
+        )
+    })?;
+    structured_result(vec![ContentBlock::text(text)], output)
+}
+
+/// Builds the result of a query tool: one text block per value, plus the
+/// same values as [`QueryOutput`].
+pub(crate) fn query_result(results: Vec<String>) -> McpResult {
+    let content = results.iter().cloned().map(ContentBlock::text).collect();
+    structured_result(content, &QueryOutput { results })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_result_carries_structured_content() {
+        let result = query_result(vec!["# A".to_string(), "# B".to_string()]).unwrap();
+        assert_eq!(result.content.len(), 2);
+        assert_eq!(
+            result.structured_content,
+            Some(serde_json::json!({ "results": ["# A", "# B"] }))
+        );
+    }
+
+    #[test]
+    fn test_output_schema_describes_object() {
+        let schema = output_schema::<QueryOutput>();
+        assert_eq!(schema.get("type"), Some(&serde_json::json!("object")));
+        assert!(schema["properties"].get("results").is_some());
+    }
+
+    #[test]
+    fn test_json_result_text_matches_structured_content() {
+        let result = json_result(&SelectorsOutput { selectors: vec![] }).unwrap();
+        let text = result.content[0].as_text().unwrap().text.clone();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+            result.structured_content.unwrap()
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! In-memory cache of fetched pages, keyed by URL, shared by every session
+//! of a server process. Entries are served without a request while younger
+//! than the TTL, then revalidated with `If-None-Match` /
+//! `If-Modified-Since`.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use super::{fetch::Page, stats::CacheCounts};
+
+#[derive(Debug, Clone)]
+pub(crate) struct CachedPage {
+    pub page: Page,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    cached: CachedPage,
+    validated_at: Instant,
+    last_used: Instant,
+}
+
+/// What the cache knows about a URL.
+#[derive(Debug)]
+pub(crate) enum Lookup {
+    Miss,
+    /// Young enough to use without asking the server.
+    Fresh(Page),
+    /// Needs revalidation before use.
+    Stale(CachedPage),
+}
+
+#[derive(Debug)]
+pub(crate) struct PageCache {
+    ttl: Duration,
+    max_bytes: usize,
+    bytes: usize,
+    entries: HashMap<String, Entry>,
+    counts: CacheCounts,
+}
+
+impl PageCache {
+    pub(crate) fn new(ttl: Duration, max_bytes: usize) -> Self {
+        Self {
+            ttl,
+            max_bytes,
+            bytes: 0,
+            entries: HashMap::new(),
+            counts: CacheCounts::default(),
+        }
+    }
+
+    pub(crate) fn lookup(&mut self, url: &str, now: Instant) -> Lookup {
+        let Some(entry) = self.entries.get_mut(url) else {
+            self.counts.misses += 1;
+            return Lookup::Miss;
+        };
+        entry.last_used = now;
+        if now.duration_since(entry.validated_at) < self.ttl {
+            self.counts.hits += 1;
+            Lookup::Fresh(entry.cached.page.clone())
+        } else {
+            self.counts.misses += 1;
+            Lookup::Stale(entry.cached.clone())
+        }
+    }
+
+    /// Marks a stale entry as valid again after a `304 Not Modified`. Its
+    /// lookup counts as a hit after all.
+    pub(crate) fn revalidated(&mut self, url: &str, now: Instant) {
+        if let Some(entry) = self.entries.get_mut(url) {
+            entry.validated_at = now;
+            self.counts.misses = self.counts.misses.saturating_sub(1);
+            self.counts.hits += 1;
+        }
+    }
+
+    /// Hits and misses of the lookups so far.
+    pub(crate) fn counts(&self) -> CacheCounts {
+        self.counts
+    }
+
+    /// Stores `cached`, evicting least recently used pages to stay within
+    /// the size limit. Pages larger than the whole cache are not stored.
+    pub(crate) fn insert(&mut self, url: String, cached: CachedPage, now: Instant) {
+        self.remove(&url);
+        let size = cached.page.text.len();
+        if size > self.max_bytes {
+            return;
+        }
+        while self.bytes + size > self.max_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(url, _)| url.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+        self.bytes += size;
+        self.entries.insert(
+            url,
+            Entry {
+                cached,
+                validated_at: now,
+                last_used: now,
+            },
+        );
+    }
+
+    fn remove(&mut self, url: &str) {
+        if let Some(entry) = self.entries.remove(url) {
+            self.bytes -= entry.cached.page.text.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cached(text: &str) -> CachedPage {
+        CachedPage {
+            page: Page {
+                url: "https://example.com/".to_string(),
+                content_type: Some("text/html".to_string()),
+                charset: "UTF-8",
+                text: text.to_string(),
+            },
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn test_entries_go_stale_after_ttl() {
+        let mut cache = PageCache::new(Duration::from_secs(60), 1024);
+        let now = Instant::now();
+        cache.insert("a".to_string(), cached("<p>a</p>"), now);
+
+        assert!(matches!(cache.lookup("a", now), Lookup::Fresh(_)));
+        let later = now + Duration::from_secs(60);
+        assert!(matches!(cache.lookup("a", later), Lookup::Stale(_)));
+        cache.revalidated("a", later);
+        assert!(matches!(cache.lookup("a", later), Lookup::Fresh(_)));
+        assert!(matches!(cache.lookup("b", now), Lookup::Miss));
+        assert_eq!(cache.counts(), CacheCounts { hits: 3, misses: 1 });
+    }
+
+    #[test]
+    fn test_insert_evicts_least_recently_used() {
+        let mut cache = PageCache::new(Duration::from_secs(60), 10);
+        let now = Instant::now();
+        cache.insert("a".to_string(), cached("aaaa"), now);
+        cache.insert(
+            "b".to_string(),
+            cached("bbbb"),
+            now + Duration::from_secs(1),
+        );
+        cache.lookup("a", now + Duration::from_secs(2));
+        cache.insert(
+            "c".to_string(),
+            cached("cccc"),
+            now + Duration::from_secs(3),
+        );
+
+        assert!(matches!(cache.lookup("b", now), Lookup::Miss));
+        assert!(!matches!(cache.lookup("a", now), Lookup::Miss));
+        assert_eq!(cache.bytes, 8);
+
+        cache.insert("huge".to_string(), cached("x".repeat(11).as_str()), now);
+        assert!(matches!(cache.lookup("huge", now), Lookup::Miss));
+    }
+}
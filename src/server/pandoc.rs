@@ -0,0 +1,251 @@
+//! `pandoc_to_markdown` and `markdown_to_pandoc`: conversions through a
+//! local `pandoc` binary, for formats mq does not read or write itself.
+//! Only built with the `pandoc` feature.
+
+use std::{path::PathBuf, process::Stdio, time::Duration};
+
+use base64::Engine;
+use rmcp::{ErrorData, handler::server::wrapper::Parameters, schemars, tool, tool_router};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use super::{
+    McpResult, Server,
+    output::{QueryOutput, output_schema, query_result},
+};
+
+/// How long a single pandoc run may take.
+const PANDOC_TIMEOUT: Duration = Duration::from_secs(30);
+/// The markdown flavour exchanged with pandoc: the GFM mq parses, plus
+/// the math and front matter mq understands.
+const MARKDOWN_FORMAT: &str = "gfm+tex_math_dollars+yaml_metadata_block";
+/// Formats pandoc reads or writes as binary (zip or PDF) data, which are
+/// exchanged base64-encoded.
+const BINARY_FORMATS: &[&str] = &[
+    "docx", "odt", "epub", "epub2", "epub3", "pptx", "xlsx", "pdf",
+];
+
+/// Server-side settings for the pandoc tools.
+#[derive(Debug, Clone)]
+pub struct PandocConfig {
+    /// The pandoc executable, looked up on `PATH` unless it is a path.
+    pub path: PathBuf,
+}
+
+impl Default for PandocConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("pandoc"),
+        }
+    }
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct PandocToMarkdownInput {
+    #[schemars(
+        description = "The document to convert; base64-encoded for binary formats (docx, odt, epub, pptx, xlsx)"
+    )]
+    pub input: String,
+    #[schemars(
+        description = "The pandoc input format, e.g. `rst`, `org`, `latex`, `mediawiki`, `docx` or `json` (pandoc's JSON AST), optionally with extensions (`markdown+smart`)"
+    )]
+    pub from: String,
+    #[schemars(
+        description = "The mq query to execute (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct MarkdownToPandocInput {
+    #[schemars(description = "The markdown to convert")]
+    pub markdown: String,
+    #[schemars(
+        description = "The pandoc output format, e.g. `rst`, `org`, `latex`, `html5`, `asciidoc`, `docx` or `json` (pandoc's JSON AST). Binary formats are returned base64-encoded"
+    )]
+    pub to: String,
+    #[schemars(description = "An mq query to run first; only its results are converted")]
+    pub query: Option<String>,
+    #[schemars(
+        description = "Produce a standalone document with header and footer (default: false)"
+    )]
+    pub standalone: Option<bool>,
+}
+
+/// Checks a pandoc format name. Paths (custom Lua readers and writers)
+/// are rejected, so a format can only name a built-in one.
+fn check_format(format: &str) -> Result<&str, ErrorData> {
+    let format = format.trim();
+    if !format.is_empty()
+        && format
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-'))
+        && !format.starts_with(['-', '+'])
+    {
+        Ok(format)
+    } else {
+        Err(ErrorData::invalid_params(
+            "Invalid pandoc format",
+            Some(serde_json::json!({ "format": format })),
+        ))
+    }
+}
+
+fn is_binary(format: &str) -> bool {
+    let base = format.split(['+', '-']).next().unwrap_or_default();
+    BINARY_FORMATS.contains(&base)
+}
+
+impl PandocConfig {
+    /// Runs pandoc with `args` on `input` and returns its output. pandoc
+    /// runs with `--sandbox`, so documents cannot read files or fetch URLs.
+    async fn run(&self, args: &[String], input: Vec<u8>) -> Result<Vec<u8>, ErrorData> {
+        let mut child = Command::new(&self.path)
+            .arg("--sandbox")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                ErrorData::internal_error(
+                    "Failed to run pandoc",
+                    Some(serde_json::json!({
+                        "path": self.path.display().to_string(),
+                        "error": e.to_string(),
+                    })),
+                )
+            })?;
+        // Written concurrently so a large output cannot fill the stdout
+        // pipe while we are still writing.
+        let writer = child.stdin.take().map(|mut stdin| {
+            tokio::spawn(async move {
+                let _ = stdin.write_all(&input).await;
+            })
+        });
+        let output = tokio::time::timeout(PANDOC_TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| {
+                ErrorData::internal_error(
+                    "pandoc timed out",
+                    Some(serde_json::json!({ "timeout_secs": PANDOC_TIMEOUT.as_secs() })),
+                )
+            })?
+            .map_err(|e| {
+                ErrorData::internal_error(
+                    "Failed to run pandoc",
+                    Some(serde_json::Value::String(e.to_string())),
+                )
+            })?;
+        if let Some(writer) = writer {
+            let _ = writer.await;
+        }
+        if !output.status.success() {
+            return Err(ErrorData::invalid_params(
+                "pandoc failed",
+                Some(serde_json::json!({
+                    "status": output.status.code(),
+                    "stderr": String::from_utf8_lossy(&output.stderr).trim(),
+                })),
+            ));
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[tool_router(router = pandoc_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Convert a document from any format pandoc reads (reStructuredText, Org, LaTeX, MediaWiki, DOCX, EPUB, pandoc's JSON AST, ...) to markdown and run an mq query on it. Binary inputs are passed base64-encoded.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    async fn pandoc_to_markdown(
+        &self,
+        Parameters(input): Parameters<PandocToMarkdownInput>,
+    ) -> McpResult {
+        let from = check_format(&input.from)?;
+        let bytes = if is_binary(from) {
+            base64::engine::general_purpose::STANDARD
+                .decode(input.input.trim())
+                .map_err(|e| {
+                    ErrorData::invalid_params(
+                        "Invalid base64 input",
+                        Some(serde_json::Value::String(e.to_string())),
+                    )
+                })?
+        } else {
+            input.input.into_bytes()
+        };
+        let args = [
+            format!("--from={from}"),
+            format!("--to={MARKDOWN_FORMAT}"),
+            "--wrap=none".to_string(),
+        ];
+        let output = self.config.pandoc.run(&args, bytes).await?;
+        self.eval_query(
+            &String::from_utf8_lossy(&output),
+            input.query.as_deref().unwrap_or("identity()"),
+        )
+    }
+
+    #[tool(
+        description = "Convert markdown to any format pandoc writes (reStructuredText, Org, LaTeX, HTML, AsciiDoc, DOCX, pandoc's JSON AST, ...), optionally after selecting part of it with an mq query. Binary outputs are returned base64-encoded.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    async fn markdown_to_pandoc(
+        &self,
+        Parameters(input): Parameters<MarkdownToPandocInput>,
+    ) -> McpResult {
+        let to = check_format(&input.to)?;
+        let markdown = match input.query.as_deref() {
+            Some(query) => self.query_values(&input.markdown, query)?.join("\n\n"),
+            None => input.markdown,
+        };
+        let mut args = vec![format!("--from={MARKDOWN_FORMAT}"), format!("--to={to}")];
+        if input.standalone.unwrap_or_default() {
+            args.push("--standalone".to_string());
+        }
+        let binary = is_binary(to);
+        if binary {
+            // pandoc refuses to write binary formats to a terminal unless
+            // stdout is named explicitly.
+            args.push("--output=-".to_string());
+        }
+        let output = self.config.pandoc.run(&args, markdown.into_bytes()).await?;
+        let text = if binary {
+            base64::engine::general_purpose::STANDARD.encode(output)
+        } else {
+            String::from_utf8_lossy(&output).into_owned()
+        };
+        query_result(vec![text])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("rst", Some("rst"))]
+    #[case(" markdown+smart-raw_html ", Some("markdown+smart-raw_html"))]
+    #[case("custom.lua", None)]
+    #[case("../writer.lua", None)]
+    #[case("--lua-filter", None)]
+    #[case("", None)]
+    fn test_check_format(#[case] format: &str, #[case] expected: Option<&str>) {
+        assert_eq!(check_format(format).ok(), expected);
+    }
+
+    #[rstest]
+    #[case("docx", true)]
+    #[case("docx+styles", true)]
+    #[case("epub3", true)]
+    #[case("html5", false)]
+    #[case("json", false)]
+    fn test_is_binary(#[case] format: &str, #[case] expected: bool) {
+        assert_eq!(is_binary(format), expected);
+    }
+}
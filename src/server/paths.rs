@@ -0,0 +1,171 @@
+//! Filesystem paths and files as the file-based tools see them, with the
+//! Windows rules applied on Windows: canonical paths without the `\\?\`
+//! prefix `canonicalize` adds there, case-insensitive containment checks,
+//! refusal of reserved device names (`CON`, `NUL`, `COM1`, ...) and
+//! markdown files saved with a BOM (Notepad's UTF-8 and UTF-16).
+
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use encoding_rs::Encoding;
+
+/// Device names Windows reserves in every directory, with any extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM0", "COM1", "COM2", "COM3", "COM4",
+    "COM5", "COM6", "COM7", "COM8", "COM9", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6",
+    "LPT7", "LPT8", "LPT9",
+];
+
+/// `path` made absolute with symlinks resolved, as [`Path::canonicalize`],
+/// but in the form users write: `C:\docs` rather than `\\?\C:\docs`, and
+/// `\\server\share` rather than `\\?\UNC\server\share`.
+pub(crate) fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    let path = path.canonicalize()?;
+    if cfg!(windows)
+        && let Some(simplified) = path.to_str().and_then(strip_verbatim_prefix)
+    {
+        return Ok(PathBuf::from(simplified));
+    }
+    Ok(path)
+}
+
+/// A verbatim (`\\?\`) drive or UNC path without its prefix. `None` for
+/// other paths, and for verbatim paths that don't mean the same thing
+/// without the prefix (reserved names, `.` and `..` components, trailing
+/// dots or spaces, or too long for the legacy path limit).
+fn strip_verbatim_prefix(path: &str) -> Option<String> {
+    const MAX_PATH: usize = 260;
+    let rest = path.strip_prefix(r"\\?\")?;
+    let simplified = match rest.strip_prefix(r"UNC\") {
+        Some(unc) => format!(r"\\{unc}"),
+        None => {
+            let bytes = rest.as_bytes();
+            if !(bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && &bytes[1..3] == br":\") {
+                return None;
+            }
+            rest.to_string()
+        }
+    };
+    let plain = simplified.len() < MAX_PATH
+        && simplified.split('\\').skip(1).all(|name| {
+            name != "." && name != ".." && !name.ends_with(['.', ' ']) && !is_reserved_name(name)
+        });
+    plain.then_some(simplified)
+}
+
+/// Whether `name` is one of Windows' reserved device names, which refer to
+/// the device whatever the directory or extension (`nul.md`, `Con .txt`).
+pub(crate) fn is_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ');
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+/// The first component of `path` naming a reserved device, on Windows.
+/// Reading one blocks on the console or a port instead of failing.
+pub(crate) fn reserved_component(path: &Path) -> Option<&str> {
+    if !cfg!(windows) {
+        return None;
+    }
+    path.components().find_map(|component| match component {
+        Component::Normal(name) => name.to_str().filter(|name| is_reserved_name(name)),
+        _ => None,
+    })
+}
+
+/// Whether `path` is `root` or inside it. Windows filesystems ignore case,
+/// so there the components are compared case-insensitively.
+pub(crate) fn starts_with(path: &Path, root: &Path) -> bool {
+    if !cfg!(windows) {
+        return path.starts_with(root);
+    }
+    let mut components = path.components();
+    root.components().all(|expected| {
+        components.next().is_some_and(|component| {
+            component
+                .as_os_str()
+                .to_string_lossy()
+                .eq_ignore_ascii_case(&expected.as_os_str().to_string_lossy())
+        })
+    })
+}
+
+/// Whether `path` has a markdown extension, in any case (`README.MD`).
+pub(crate) fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown")
+        })
+}
+
+/// Reads the markdown file at `path`: UTF-8, or UTF-8 or UTF-16 behind a
+/// BOM, which is dropped so the first line parses as usual.
+pub(crate) fn read_markdown(path: &Path) -> io::Result<String> {
+    decode_markdown(std::fs::read(path)?)
+}
+
+fn decode_markdown(bytes: Vec<u8>) -> io::Result<String> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(&bytes) {
+        let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        if had_errors {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("file is not valid {}", encoding.name()),
+            ));
+        }
+        return Ok(text.into_owned());
+    }
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(r"\\?\C:\docs\a.md", Some(r"C:\docs\a.md"))]
+    #[case(r"\\?\UNC\server\share\a.md", Some(r"\\server\share\a.md"))]
+    #[case(r"\\?\C:\docs\nul.md", None)]
+    #[case(r"\\?\C:\docs\a.", None)]
+    #[case(r"\\?\Volume{0b1c}\docs", None)]
+    #[case(r"C:\docs", None)]
+    fn test_strip_verbatim_prefix(#[case] path: &str, #[case] expected: Option<&str>) {
+        assert_eq!(strip_verbatim_prefix(path).as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case("CON", true)]
+    #[case("nul.md", true)]
+    #[case("Com1 .txt", true)]
+    #[case("conin$", true)]
+    #[case("console.md", false)]
+    #[case("COM10", false)]
+    #[case("notes.md", false)]
+    fn test_is_reserved_name(#[case] name: &str, #[case] expected: bool) {
+        assert_eq!(is_reserved_name(name), expected);
+    }
+
+    #[test]
+    fn test_is_markdown() {
+        assert!(is_markdown(Path::new("docs/README.MD")));
+        assert!(is_markdown(Path::new("notes.markdown")));
+        assert!(!is_markdown(Path::new("notes.txt")));
+    }
+
+    #[test]
+    fn test_decode_markdown_drops_bom() {
+        let utf8 = [b"\xEF\xBB\xBF".as_slice(), "# Title".as_bytes()].concat();
+        assert_eq!(decode_markdown(utf8).unwrap(), "# Title");
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("# Title".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(decode_markdown(utf16).unwrap(), "# Title");
+        assert!(decode_markdown(vec![0xC3, 0x28]).is_err());
+    }
+}
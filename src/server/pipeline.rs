@@ -0,0 +1,268 @@
+//! `run_pipeline`: an ordered list of steps run server-side, each step's
+//! output feeding the next, so a multi-step extraction is one tool call
+//! and its intermediate results never reach the client.
+
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, model::ContentBlock, schemars,
+    serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    output::{output_schema, structured_result},
+    parse_markdown,
+    plaintext::{self, LinkStyle, TableStyle},
+    postprocess::PostProcess,
+    provenance,
+};
+
+/// Maximum number of steps in one pipeline.
+const MAX_STEPS: usize = 32;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, rmcp::serde::Deserialize, Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Format {
+    /// The values unchanged.
+    Markdown,
+    /// Each value rendered as plain text, as by `markdown_to_text`.
+    Text,
+    /// Each value rendered as HTML.
+    Html,
+    /// A single JSON array of the values.
+    Json,
+}
+
+#[derive(Debug, Clone, rmcp::serde::Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Step {
+    /// Runs an mq query on the previous step's output.
+    Query {
+        #[schemars(description = "The mq query to run")]
+        query: String,
+        #[schemars(
+            description = "Run the query once over all top-level nodes as a single array, as the section module expects, instead of once per node (default: false)"
+        )]
+        #[serde(default)]
+        aggregate: bool,
+    },
+    /// Normalizes each value with the steps of the `postprocess` argument.
+    Transform(PostProcess),
+    /// Converts each value to another format.
+    Format {
+        #[schemars(description = "`markdown`, `text`, `html` or `json`")]
+        format: Format,
+    },
+}
+
+impl Step {
+    fn kind(&self) -> &'static str {
+        match self {
+            Step::Query { .. } => "query",
+            Step::Transform(_) => "transform",
+            Step::Format { .. } => "format",
+        }
+    }
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct RunPipelineInput {
+    #[schemars(description = "The markdown content the first step runs on")]
+    pub markdown: String,
+    #[schemars(
+        description = "Steps to run in order. Each step runs on the previous step's values joined by blank lines: `{\"type\": \"query\", \"query\": \"...\", \"aggregate\": false}`, `{\"type\": \"transform\", \"trim\": true, ...}` with the options of the `postprocess` argument, or `{\"type\": \"format\", \"format\": \"text\"}`"
+    )]
+    pub steps: Vec<Step>,
+    #[schemars(
+        description = "Also return the number and size of the values after each step (default: false)"
+    )]
+    pub summaries: Option<bool>,
+}
+
+/// What one step of a pipeline produced.
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct StepSummary {
+    #[schemars(description = "1-based position of the step")]
+    pub step: usize,
+    #[schemars(description = "`query`, `transform` or `format`")]
+    pub kind: String,
+    #[schemars(description = "Number of values the step produced")]
+    pub count: usize,
+    #[schemars(description = "Total size of the values in bytes")]
+    pub bytes: usize,
+}
+
+/// Output of `run_pipeline`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct RunPipelineOutput {
+    #[schemars(description = "The values produced by the last step")]
+    pub results: Vec<String>,
+    #[schemars(description = "Per-step summaries, when requested with `summaries`")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub steps: Option<Vec<StepSummary>>,
+}
+
+/// Adds the 1-based position of the failing step to a step's error.
+fn at_step(step: usize, mut error: ErrorData) -> ErrorData {
+    error.data = Some(match error.data.take() {
+        Some(serde_json::Value::Object(mut data)) => {
+            data.insert("step".to_string(), step.into());
+            serde_json::Value::Object(data)
+        }
+        Some(detail) => serde_json::json!({ "step": step, "detail": detail }),
+        None => serde_json::json!({ "step": step }),
+    });
+    error
+}
+
+impl Server {
+    /// Runs `steps` on `markdown`, returning the last step's values and a
+    /// summary of every step.
+    pub(super) fn run_steps(
+        &self,
+        markdown: String,
+        steps: &[Step],
+    ) -> Result<(Vec<String>, Vec<StepSummary>), ErrorData> {
+        if steps.len() > MAX_STEPS {
+            return Err(ErrorData::invalid_params(
+                "Too many pipeline steps",
+                Some(serde_json::json!({ "steps": steps.len(), "limit": MAX_STEPS })),
+            ));
+        }
+        let mut values = vec![markdown];
+        let mut summaries = Vec::with_capacity(steps.len());
+        for (index, step) in steps.iter().enumerate() {
+            values = self
+                .run_step(step, values)
+                .map_err(|e| at_step(index + 1, e))?;
+            summaries.push(StepSummary {
+                step: index + 1,
+                kind: step.kind().to_string(),
+                count: values.len(),
+                bytes: values.iter().map(String::len).sum(),
+            });
+        }
+        Ok((values, summaries))
+    }
+
+    fn run_step(&self, step: &Step, values: Vec<String>) -> Result<Vec<String>, ErrorData> {
+        Ok(match step {
+            Step::Query { query, aggregate } => {
+                let input = values.join("\n\n");
+                if *aggregate {
+                    self.aggregate_values(&input, query)?
+                } else {
+                    self.query_values(&input, query)?
+                }
+            }
+            Step::Transform(steps) => values.iter().map(|value| steps.apply(value)).collect(),
+            Step::Format { format } => match format {
+                Format::Markdown => values,
+                Format::Text => values
+                    .iter()
+                    .map(|value| {
+                        Ok(plaintext::to_text(
+                            &parse_markdown(value)?,
+                            LinkStyle::default(),
+                            TableStyle::default(),
+                            "•",
+                        ))
+                    })
+                    .collect::<Result<_, ErrorData>>()?,
+                Format::Html => values
+                    .iter()
+                    .map(|value| mq_markdown::to_html(value))
+                    .collect(),
+                Format::Json => vec![serde_json::to_string(&values).unwrap_or_default()],
+            },
+        })
+    }
+}
+
+#[tool_router(router = pipeline_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Run an ordered list of steps on markdown server-side, each step's output feeding the next: mq queries (per node or aggregated), post-processing transforms and format conversions (markdown, text, html, json). Returns only the final values, plus optional per-step counts, instead of one tool call and one large payload per step. Queries can use session variables.",
+        output_schema = output_schema::<RunPipelineOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    async fn run_pipeline(&self, Parameters(input): Parameters<RunPipelineInput>) -> McpResult {
+        // Line numbers of intermediate values don't refer to the input, so
+        // the steps run without provenance footers.
+        let (results, summaries) =
+            provenance::scope(None, async { self.run_steps(input.markdown, &input.steps) }).await?;
+        let content = results.iter().cloned().map(ContentBlock::text).collect();
+        structured_result(
+            content,
+            &RunPipelineOutput {
+                results,
+                steps: input.summaries.unwrap_or(false).then_some(summaries),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARKDOWN: &str = "# Guide\n\n## Install\n\nRun `make`.\n\n## Usage\n\nCall it.\n";
+
+    fn steps(value: serde_json::Value) -> Vec<Step> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_steps_feed_each_other() {
+        let server = Server::new(None).unwrap();
+        let (results, summaries) = server
+            .run_steps(
+                MARKDOWN.to_string(),
+                &steps(serde_json::json!([
+                    {"type": "query", "query": ".h2"},
+                    {"type": "query", "query": "to_text()"},
+                    {"type": "format", "format": "json"},
+                ])),
+            )
+            .unwrap();
+        assert_eq!(results, vec![r#"["Install","Usage"]"#]);
+        assert_eq!(
+            summaries
+                .iter()
+                .map(|s| (s.kind.as_str(), s.count))
+                .collect::<Vec<_>>(),
+            vec![("query", 2), ("query", 2), ("format", 1)]
+        );
+    }
+
+    #[test]
+    fn test_transform_and_text_format() {
+        let server = Server::new(None).unwrap();
+        let (results, _) = server
+            .run_steps(
+                "Some **bold**  \n\n\n\ntext <!-- note -->".to_string(),
+                &steps(serde_json::json!([
+                    {"type": "transform", "strip_html_comments": true, "trim": true},
+                    {"type": "format", "format": "text"},
+                ])),
+            )
+            .unwrap();
+        assert_eq!(results, vec!["Some bold\n\ntext"]);
+    }
+
+    #[test]
+    fn test_failing_step_is_reported() {
+        let server = Server::new(None).unwrap();
+        let error = server
+            .run_steps(
+                MARKDOWN.to_string(),
+                &steps(serde_json::json!([
+                    {"type": "query", "query": ".h2"},
+                    {"type": "query", "query": "nosuchfunction()"},
+                ])),
+            )
+            .unwrap_err();
+        assert_eq!(error.data.unwrap()["step"], 2);
+    }
+}
@@ -0,0 +1,433 @@
+//! `markdown_to_text`: deterministic plain-text rendering of markdown.
+
+use std::collections::HashMap;
+
+use mq_markdown::Node;
+use rmcp::{handler::server::wrapper::Parameters, schemars, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    output::{QueryOutput, output_schema, query_result},
+    parse_markdown_only,
+};
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LinkStyle {
+    /// `label (https://...)`; the URL alone when it is also the label.
+    #[default]
+    Inline,
+    /// `label [1]`, with the numbered URLs listed at the end; bare URLs
+    /// stay inline.
+    Footnotes,
+    /// The label only.
+    Text,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TableStyle {
+    /// One line per row: `Header: value, Header: value`.
+    #[default]
+    Flatten,
+    /// A box drawn with `+`, `-` and `|`.
+    Ascii,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct MarkdownToTextInput {
+    #[schemars(description = "The markdown to render as plain text")]
+    pub markdown: String,
+    #[schemars(
+        description = "An mq query to run first; its results are rendered instead of the whole document"
+    )]
+    pub query: Option<String>,
+    #[schemars(
+        description = "How links are shown: `inline` (default, `label (url)`), `footnotes` (`label [1]` with the URLs listed at the end) or `text` (label only)"
+    )]
+    pub links: Option<LinkStyle>,
+    #[schemars(
+        description = "How tables are shown: `flatten` (default, one `Header: value, ...` line per row) or `ascii` (a drawn box)"
+    )]
+    pub tables: Option<TableStyle>,
+    #[schemars(description = "Bullet for unordered list items (default: `•`)")]
+    pub bullet: Option<String>,
+}
+
+struct Renderer<'a> {
+    links: LinkStyle,
+    tables: TableStyle,
+    bullet: &'a str,
+    /// Link reference definitions, for `[label][ref]` links.
+    definitions: HashMap<String, String>,
+    footnotes: Vec<String>,
+}
+
+impl Renderer<'_> {
+    fn link(&mut self, label: String, url: &str) -> String {
+        if url.is_empty() {
+            return label;
+        }
+        match self.links {
+            LinkStyle::Text => label,
+            _ if label.is_empty() || label == url => url.to_string(),
+            LinkStyle::Inline => format!("{label} ({url})"),
+            LinkStyle::Footnotes => {
+                let number = match self.footnotes.iter().position(|u| u == url) {
+                    Some(index) => index + 1,
+                    None => {
+                        self.footnotes.push(url.to_string());
+                        self.footnotes.len()
+                    }
+                };
+                format!("{label} [{number}]")
+            }
+        }
+    }
+
+    fn inline(&mut self, nodes: &[Node]) -> String {
+        nodes.iter().map(|node| self.node(node)).collect()
+    }
+
+    fn node(&mut self, node: &Node) -> String {
+        match node {
+            Node::Link(link) => {
+                let label = self.inline(&link.values);
+                self.link(label, link.url.as_str())
+            }
+            Node::LinkRef(link) => {
+                let label = self.inline(&link.values);
+                match self.definitions.get(&link.ident.to_lowercase()).cloned() {
+                    Some(url) => self.link(label, &url),
+                    None => label,
+                }
+            }
+            Node::Image(image) => image.alt.clone(),
+            Node::ImageRef(image) => image.alt.clone(),
+            Node::FootnoteRef(footnote) => format!("[{}]", footnote.ident),
+            Node::Footnote(footnote) => {
+                format!("[{}] {}", footnote.ident, self.inline(&footnote.values))
+            }
+            Node::Break(_) => "\n".to_string(),
+            Node::Blockquote(quote) => quote_lines(&self.inline(&quote.values)),
+            Node::Callout(callout) => {
+                let title = callout
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| callout.kind.clone());
+                quote_lines(&format!("{title}\n{}", self.inline(&callout.values)))
+            }
+            Node::Heading(heading) => self.inline(&heading.values),
+            Node::Emphasis(v) => self.inline(&v.values),
+            Node::Strong(v) => self.inline(&v.values),
+            Node::Delete(v) => self.inline(&v.values),
+            Node::Fragment(v) => self.inline(&v.values),
+            Node::HorizontalRule(_) => "----------".to_string(),
+            Node::Code(code) => code.value.clone(),
+            // Front matter, raw HTML, link definitions and MDX have no text.
+            Node::Html(_)
+            | Node::Yaml(_)
+            | Node::Toml(_)
+            | Node::Definition(_)
+            | Node::TableAlign(_)
+            | Node::MdxFlowExpression(_)
+            | Node::MdxJsEsm(_)
+            | Node::MdxTextExpression(_)
+            | Node::Empty => String::new(),
+            Node::MdxJsxFlowElement(mdx) => self.inline(&mdx.children),
+            Node::MdxJsxTextElement(mdx) => self.inline(&mdx.children),
+            node => node.value(),
+        }
+    }
+
+    fn table(&mut self, cells: &[&Node]) -> String {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut current_row = None;
+        for cell in cells {
+            let Node::TableCell(cell) = cell else {
+                continue;
+            };
+            if current_row != Some(cell.row) {
+                rows.push(Vec::new());
+                current_row = Some(cell.row);
+            }
+            let text = self.inline(&cell.values).replace('\n', " ");
+            if let Some(row) = rows.last_mut() {
+                row.push(text.trim().to_string());
+            }
+        }
+        match self.tables {
+            TableStyle::Flatten => flatten_table(&rows),
+            TableStyle::Ascii => ascii_table(&rows),
+        }
+    }
+
+    /// Renders top-level nodes, separating blocks by blank lines as in the
+    /// source and numbering ordered lists.
+    fn render(&mut self, nodes: &[Node]) -> String {
+        for node in nodes {
+            if let Node::Definition(definition) = node {
+                self.definitions.insert(
+                    definition.ident.to_lowercase(),
+                    definition.url.as_str().to_string(),
+                );
+            }
+        }
+
+        let mut out = String::new();
+        let mut previous_end: Option<usize> = None;
+        // Next number for ordered lists, per nesting level.
+        let mut counters: Vec<usize> = Vec::new();
+        let mut i = 0;
+        while i < nodes.len() {
+            let node = &nodes[i];
+            let (text, end) = if matches!(node, Node::TableCell(_) | Node::TableAlign(_)) {
+                let start = i;
+                while i < nodes.len()
+                    && matches!(nodes[i], Node::TableCell(_) | Node::TableAlign(_))
+                {
+                    i += 1;
+                }
+                let table: Vec<&Node> = nodes[start..i].iter().collect();
+                let end = table
+                    .iter()
+                    .rev()
+                    .find_map(|n| n.position())
+                    .map(|p| p.end.line);
+                (self.table(&table), end)
+            } else {
+                i += 1;
+                let text = match node {
+                    Node::List(list) => {
+                        counters.truncate(list.level as usize + 1);
+                        counters.resize(list.level as usize + 1, 1);
+                        let marker = if list.ordered {
+                            let number = counters[list.level as usize];
+                            counters[list.level as usize] += 1;
+                            format!("{number}.")
+                        } else {
+                            self.bullet.to_string()
+                        };
+                        let checkbox = match list.checked {
+                            Some(true) => "[x] ",
+                            Some(false) => "[ ] ",
+                            None => "",
+                        };
+                        let indent = "  ".repeat(list.level as usize);
+                        let body = self.inline(&list.values);
+                        let continuation =
+                            format!("\n{indent}{}", " ".repeat(marker.chars().count() + 1));
+                        format!(
+                            "{indent}{marker} {checkbox}{}",
+                            body.trim().replace('\n', &continuation)
+                        )
+                    }
+                    node => self.node(node),
+                };
+                (text, node.position().map(|p| p.end.line))
+            };
+            if !matches!(node, Node::List(_)) {
+                counters.clear();
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            let start = node.position().map(|p| p.start.line);
+            if !out.is_empty() {
+                let newlines = match (previous_end, start) {
+                    (Some(previous), Some(start)) => start.saturating_sub(previous).min(2),
+                    _ => 1,
+                };
+                out.push_str(&"\n".repeat(newlines));
+            }
+            out.push_str(&text);
+            previous_end = end;
+        }
+
+        if !self.footnotes.is_empty() {
+            out.push_str("\n\n");
+            let list: Vec<String> = self
+                .footnotes
+                .iter()
+                .enumerate()
+                .map(|(i, url)| format!("[{}] {url}", i + 1))
+                .collect();
+            out.push_str(&list.join("\n"));
+        }
+        out
+    }
+}
+
+fn quote_lines(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("> {line}").trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn flatten_table(rows: &[Vec<String>]) -> String {
+    let Some((header, body)) = rows.split_first() else {
+        return String::new();
+    };
+    if body.is_empty() {
+        return header.join(", ");
+    }
+    body.iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, value)| !value.is_empty())
+                .map(
+                    |(column, value)| match header.get(column).filter(|h| !h.is_empty()) {
+                        Some(name) => format!("{name}: {value}"),
+                        None => value.clone(),
+                    },
+                )
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn ascii_table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let border = format!(
+        "+{}+",
+        widths
+            .iter()
+            .map(|width| "-".repeat(width + 2))
+            .collect::<Vec<_>>()
+            .join("+")
+    );
+    let mut lines = vec![border.clone()];
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(column, width)| {
+                let cell = row.get(column).map(String::as_str).unwrap_or_default();
+                let padding = width - cell.chars().count();
+                format!(" {cell}{} ", " ".repeat(padding))
+            })
+            .collect();
+        lines.push(format!("|{}|", cells.join("|")));
+        if i == 0 {
+            lines.push(border.clone());
+        }
+    }
+    if rows.len() > 1 {
+        lines.push(border);
+    }
+    lines.join("\n")
+}
+
+/// Renders markdown nodes as plain text.
+pub(super) fn to_text(
+    nodes: &[Node],
+    links: LinkStyle,
+    tables: TableStyle,
+    bullet: &str,
+) -> String {
+    Renderer {
+        links,
+        tables,
+        bullet,
+        definitions: HashMap::new(),
+        footnotes: Vec::new(),
+    }
+    .render(nodes)
+}
+
+#[tool_router(router = plaintext_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Render markdown as clean plain text for SMS, chat or terminal output: markup is removed, list bullets and numbers are rendered, tables are flattened or drawn as ASCII boxes, and links are shown inline, as numbered footnotes or as text only. An optional mq query selects what to render.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn markdown_to_text(&self, Parameters(input): Parameters<MarkdownToTextInput>) -> McpResult {
+        let markdown = match input.query.as_deref() {
+            Some(query) => self.query_values(&input.markdown, query)?.join("\n\n"),
+            None => input.markdown,
+        };
+        let text = to_text(
+            &parse_markdown_only(&markdown)?,
+            input.links.unwrap_or_default(),
+            input.tables.unwrap_or_default(),
+            input.bullet.as_deref().unwrap_or("•"),
+        );
+        query_result(vec![text])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn render(markdown: &str, links: LinkStyle, tables: TableStyle) -> String {
+        to_text(&parse_markdown_only(markdown).unwrap(), links, tables, "•")
+    }
+
+    #[test]
+    fn test_lists_and_inline_markup() {
+        assert_eq!(
+            render(
+                "# Title\n\nSome **bold** and `code`.\n\n- one\n  - nested\n- [x] done\n\n1. first\n2. second\n",
+                LinkStyle::Inline,
+                TableStyle::Flatten,
+            ),
+            "Title\n\nSome bold and code.\n\n• one\n  • nested\n• [x] done\n\n1. first\n2. second"
+        );
+    }
+
+    #[rstest]
+    #[case(LinkStyle::Inline, "See docs (https://example.com) and https://x.dev.")]
+    #[case(LinkStyle::Text, "See docs and https://x.dev.")]
+    #[case(
+        LinkStyle::Footnotes,
+        "See docs [1] and https://x.dev.\n\n[1] https://example.com"
+    )]
+    fn test_links(#[case] links: LinkStyle, #[case] expected: &str) {
+        assert_eq!(
+            render(
+                "See [docs](https://example.com) and <https://x.dev>.",
+                links,
+                TableStyle::Flatten
+            ),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(TableStyle::Flatten, "Name: Alice, Age: 30\nName: Bob")]
+    #[case(
+        TableStyle::Ascii,
+        "+-------+-----+\n| Name  | Age |\n+-------+-----+\n| Alice | 30  |\n| Bob   |     |\n+-------+-----+"
+    )]
+    fn test_tables(#[case] tables: TableStyle, #[case] expected: &str) {
+        assert_eq!(
+            render(
+                "| Name | Age |\n| --- | --- |\n| Alice | 30 |\n| Bob | |\n",
+                LinkStyle::Inline,
+                tables
+            ),
+            expected
+        );
+    }
+}
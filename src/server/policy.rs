@@ -0,0 +1,135 @@
+//! The query policy: limits on the query language itself, for deployments
+//! whose security review needs to constrain what a query can do. The config
+//! file's `policy` can deny functions by name (e.g. builtins doing IO) and
+//! cap the length of queries and of their string literals.
+//!
+//! The policy applies to every profile. Engines check each query against it
+//! before compiling or running it, as `db_mq` does before handing its
+//! program to mq-db, so a denied query never starts; user modules are the
+//! operator's own code and aren't checked.
+
+use mq_hir::SymbolKind;
+
+use super::lint;
+
+/// The `policy` of the config file. The default allows everything.
+#[derive(Debug, Default, Clone, PartialEq, Eq, rmcp::serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Policy {
+    /// Functions queries may not call or refer to. A function the query
+    /// defines itself under the same name is allowed.
+    #[serde(default)]
+    pub deny_functions: Vec<String>,
+    /// Characters a query may have.
+    pub max_query_length: Option<usize>,
+    /// Characters a string literal of a query may have.
+    pub max_string_length: Option<usize>,
+}
+
+impl Policy {
+    fn is_empty(&self) -> bool {
+        self.deny_functions.is_empty()
+            && self.max_query_length.is_none()
+            && self.max_string_length.is_none()
+    }
+
+    /// Checks that the policy allows `query`, describing the first
+    /// violation if not. Queries that don't parse are left to the engine
+    /// to report.
+    pub(crate) fn check(&self, query: &str) -> Result<(), String> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        if let Some(max) = self.max_query_length {
+            let length = query.chars().count();
+            if length > max {
+                return Err(format!(
+                    "the query policy allows queries of up to {max} characters; this one has {length}"
+                ));
+            }
+        }
+
+        let mut hir = mq_hir::Hir::default();
+        let (source_id, _) = hir.add_code(None, query);
+        for (symbol_id, symbol) in lint::source_symbols(&hir, source_id) {
+            let value = symbol.value.as_deref().unwrap_or_default();
+            let at = symbol
+                .source
+                .text_range
+                .map(|range| format!(" at {}:{}", range.start.line, range.start.column))
+                .unwrap_or_default();
+            match symbol.kind {
+                SymbolKind::Call | SymbolKind::Ref
+                    if self.deny_functions.iter().any(|name| name == value) =>
+                {
+                    let defined_by_query = hir
+                        .resolve_reference_symbol(symbol_id)
+                        .and_then(|id| hir.symbol(id))
+                        .is_some_and(|definition| definition.source.source_id == Some(source_id));
+                    if !defined_by_query {
+                        return Err(format!(
+                            "the query policy denies the function `{value}`{at}"
+                        ));
+                    }
+                }
+                SymbolKind::String | SymbolKind::InterpolatedString => {
+                    let length = value.chars().count();
+                    if let Some(max) = self.max_string_length
+                        && length > max
+                    {
+                        return Err(format!(
+                            "the query policy allows strings of up to {max} characters; the string{at} has {length}"
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn policy() -> Policy {
+        Policy {
+            deny_functions: vec!["upcase".to_string()],
+            max_query_length: Some(60),
+            max_string_length: Some(5),
+        }
+    }
+
+    #[rstest]
+    #[case(".h | to_text()")]
+    #[case(".h | select(contains(\"Intro\"))")]
+    #[case("def upcase(): downcase(); | .h | upcase()")]
+    #[case("# a comment mentioning upcase()\n.h")]
+    fn test_allowed(#[case] query: &str) {
+        assert_eq!(policy().check(query), Ok(()));
+    }
+
+    #[rstest]
+    #[case(".h | upcase()", "denies the function `upcase`")]
+    #[case(".h | map(upcase)", "denies the function `upcase`")]
+    #[case("def shout(): upcase(); | shout()", "denies the function `upcase`")]
+    #[case(
+        ".h | select(contains(\"Introduction\"))",
+        "strings of up to 5 characters"
+    )]
+    #[case(
+        ".h | to_text() | to_text() | to_text() | to_text() | to_text()",
+        "queries of up to 60 characters"
+    )]
+    fn test_denied(#[case] query: &str, #[case] reason: &str) {
+        let error = policy().check(query).unwrap_err();
+        assert!(error.contains(reason), "{error}");
+    }
+
+    #[test]
+    fn test_default_policy_allows_everything() {
+        assert_eq!(Policy::default().check(".h | upcase()"), Ok(()));
+    }
+}
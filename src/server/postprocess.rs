@@ -0,0 +1,565 @@
+//! Normalization of the markdown returned by query tools: trimming,
+//! blank-line collapsing, HTML comment stripping, line wrapping, tab
+//! expansion, emoji shortcode replacement, strict CommonMark output, final
+//! newlines and line endings.
+//!
+//! Steps are configured server-wide with `--postprocess` and can be
+//! overridden per call through the reserved `postprocess` argument, which
+//! [`Server::call_tool`](rmcp::ServerHandler::call_tool) strips before the
+//! arguments reach the tool.
+
+use std::{
+    str::FromStr,
+    sync::{Arc, LazyLock},
+};
+
+use regex::Regex;
+use rmcp::{
+    ErrorData,
+    model::{CallToolResult, ContentBlock, JsonObject, Tool},
+    schemars,
+};
+
+use super::{
+    commonmark,
+    emoji::{self, EmojiMode, EmojiPlatform},
+};
+
+/// Name of the per-call argument accepted by every markdown-returning tool.
+pub(crate) const ARGUMENT: &str = "postprocess";
+
+static HTML_COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+
+/// The line endings of post-processed markdown.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    rmcp::serde::Deserialize,
+    rmcp::serde::Serialize,
+    schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEnding {
+    /// `\n`, as the markdown is rendered.
+    Lf,
+    /// `\r\n`, for Windows tools.
+    Crlf,
+}
+
+/// Post-processing steps. `None` fields fall back to the server-wide
+/// setting.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    rmcp::serde::Deserialize,
+    rmcp::serde::Serialize,
+    schemars::JsonSchema,
+)]
+pub struct PostProcess {
+    #[schemars(
+        description = "Strip trailing whitespace from lines and blank lines from both ends"
+    )]
+    pub trim: Option<bool>,
+    #[schemars(description = "Collapse runs of blank lines into one")]
+    pub collapse_blank_lines: Option<bool>,
+    #[schemars(description = "Remove HTML comments (`<!-- ... -->`)")]
+    pub strip_html_comments: Option<bool>,
+    #[schemars(
+        description = "Wrap prose lines longer than this many characters at word boundaries"
+    )]
+    pub max_line_width: Option<usize>,
+    #[schemars(
+        description = "Replace `:shortcode:` emojis with Unicode (`unicode`) or remove them (`strip`)"
+    )]
+    pub emoji: Option<EmojiMode>,
+    #[schemars(
+        description = "Also recognize the shortcode names of `slack` or `discord` (default: `github`)"
+    )]
+    pub emoji_platform: Option<EmojiPlatform>,
+    #[schemars(
+        description = "Write the markdown so that it re-parses identically: one spelling per construct and every markup character escaped"
+    )]
+    pub strict_commonmark: Option<bool>,
+    #[schemars(
+        description = "Replace tabs outside code blocks with spaces, up to tab stops this many columns apart"
+    )]
+    pub expand_tabs: Option<usize>,
+    #[schemars(description = "End each value with a newline (`true`) or with none (`false`)")]
+    pub final_newline: Option<bool>,
+    #[schemars(description = "Line endings: `lf` or `crlf` (default: `lf`, as rendered)")]
+    pub line_endings: Option<LineEnding>,
+}
+
+impl FromStr for PostProcess {
+    type Err = String;
+
+    /// Parses a comma-separated list of steps, e.g.
+    /// `trim,collapse-blank-lines,strip-html-comments,max-line-width=100,emoji=unicode,final-newline,line-endings=crlf`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = Self::default();
+        for step in s.split(',').map(str::trim).filter(|step| !step.is_empty()) {
+            match step.split_once('=') {
+                None if step == "trim" => steps.trim = Some(true),
+                None if step == "collapse-blank-lines" => steps.collapse_blank_lines = Some(true),
+                None if step == "strip-html-comments" => steps.strip_html_comments = Some(true),
+                None if step == "strict-commonmark" => steps.strict_commonmark = Some(true),
+                None if step == "final-newline" => steps.final_newline = Some(true),
+                Some(("max-line-width", width)) => {
+                    steps.max_line_width = Some(
+                        width
+                            .parse()
+                            .map_err(|_| format!("invalid max-line-width `{width}`"))?,
+                    );
+                }
+                Some(("expand-tabs", width)) => {
+                    steps.expand_tabs = Some(
+                        width
+                            .parse()
+                            .ok()
+                            .filter(|width| *width > 0)
+                            .ok_or_else(|| format!("invalid expand-tabs `{width}`"))?,
+                    );
+                }
+                Some(("line-endings", endings)) => {
+                    steps.line_endings = Some(match endings {
+                        "lf" => LineEnding::Lf,
+                        "crlf" => LineEnding::Crlf,
+                        _ => {
+                            return Err(format!(
+                                "invalid line endings `{endings}` (expected lf or crlf)"
+                            ));
+                        }
+                    });
+                }
+                Some(("emoji", mode)) => {
+                    steps.emoji = Some(match mode {
+                        "unicode" => EmojiMode::Unicode,
+                        "strip" => EmojiMode::Strip,
+                        _ => {
+                            return Err(format!(
+                                "invalid emoji mode `{mode}` (expected unicode or strip)"
+                            ));
+                        }
+                    });
+                }
+                Some(("emoji-platform", platform)) => {
+                    steps.emoji_platform = Some(match platform {
+                        "github" => EmojiPlatform::Github,
+                        "slack" => EmojiPlatform::Slack,
+                        "discord" => EmojiPlatform::Discord,
+                        _ => {
+                            return Err(format!(
+                                "invalid emoji platform `{platform}` (expected github, slack or discord)"
+                            ));
+                        }
+                    });
+                }
+                _ => {
+                    return Err(format!(
+                        "unknown post-processing step `{step}` (expected trim, collapse-blank-lines, strip-html-comments, max-line-width=N, emoji=MODE, emoji-platform=NAME, strict-commonmark, expand-tabs=N, final-newline or line-endings=lf|crlf)"
+                    ));
+                }
+            }
+        }
+        Ok(steps)
+    }
+}
+
+impl PostProcess {
+    /// `self`, with unset fields taken from `defaults`.
+    fn or(self, defaults: Self) -> Self {
+        Self {
+            trim: self.trim.or(defaults.trim),
+            collapse_blank_lines: self.collapse_blank_lines.or(defaults.collapse_blank_lines),
+            strip_html_comments: self.strip_html_comments.or(defaults.strip_html_comments),
+            max_line_width: self.max_line_width.or(defaults.max_line_width),
+            emoji: self.emoji.or(defaults.emoji),
+            emoji_platform: self.emoji_platform.or(defaults.emoji_platform),
+            strict_commonmark: self.strict_commonmark.or(defaults.strict_commonmark),
+            expand_tabs: self.expand_tabs.or(defaults.expand_tabs),
+            final_newline: self.final_newline.or(defaults.final_newline),
+            line_endings: self.line_endings.or(defaults.line_endings),
+        }
+    }
+
+    fn is_noop(&self) -> bool {
+        !self.trim.unwrap_or_default()
+            && !self.collapse_blank_lines.unwrap_or_default()
+            && !self.strip_html_comments.unwrap_or_default()
+            && self.max_line_width.is_none()
+            && self.emoji.is_none()
+            && !self.strict_commonmark.unwrap_or_default()
+            && self.expand_tabs.is_none()
+            && self.final_newline.is_none()
+            && self.line_endings.is_none()
+    }
+
+    /// Applies the enabled steps to one markdown value. Fenced code blocks
+    /// are left untouched except for trimming at the ends of the value.
+    /// Strict CommonMark output comes after the other rewriting steps, so
+    /// none can undo it, and line endings last, as the markdown is
+    /// rendered and processed with `\n`.
+    pub(crate) fn apply(&self, markdown: &str) -> String {
+        let mut out: Vec<String> = Vec::new();
+        let mut prose = String::new();
+        let mut fence: Option<String> = None;
+
+        for line in markdown.split('\n') {
+            let marker = fence_marker(line);
+            match (&fence, marker) {
+                (None, Some(marker)) => {
+                    self.flush_prose(&mut prose, &mut out);
+                    out.push(line.to_string());
+                    fence = Some(marker);
+                }
+                (None, None) => {
+                    prose.push_str(line);
+                    prose.push('\n');
+                }
+                (Some(open), Some(marker)) if marker.starts_with(open.as_str()) => {
+                    out.push(line.to_string());
+                    fence = None;
+                }
+                (Some(_), _) => out.push(line.to_string()),
+            }
+        }
+        self.flush_prose(&mut prose, &mut out);
+
+        let mut text = out.join("\n");
+        if self.trim.unwrap_or_default() {
+            text = text.trim_matches('\n').to_string();
+        }
+        if self.strict_commonmark.unwrap_or_default() {
+            text = commonmark::strict(&text);
+        }
+        match self.final_newline {
+            Some(true) if !text.is_empty() && !text.ends_with('\n') => text.push('\n'),
+            Some(false) => text.truncate(text.trim_end_matches(['\r', '\n']).len()),
+            _ => {}
+        }
+        match self.line_endings {
+            Some(LineEnding::Lf) => text = text.replace("\r\n", "\n"),
+            Some(LineEnding::Crlf) => text = text.replace("\r\n", "\n").replace('\n', "\r\n"),
+            None => {}
+        }
+        text
+    }
+
+    fn flush_prose(&self, prose: &mut String, out: &mut Vec<String>) {
+        if prose.is_empty() {
+            return;
+        }
+        let mut text = std::mem::take(prose);
+        if text.ends_with('\n') {
+            text.pop();
+        }
+        if self.strip_html_comments.unwrap_or_default() {
+            text = HTML_COMMENT.replace_all(&text, "").into_owned();
+        }
+        if let Some(mode) = self.emoji {
+            text = emoji::normalize(&text, mode, self.emoji_platform.unwrap_or_default());
+        }
+        if let Some(width) = self.expand_tabs {
+            text = text
+                .split('\n')
+                .map(|line| expand_tabs(line, width))
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        let mut blank_run = false;
+        for line in text.split('\n') {
+            let line = if self.trim.unwrap_or_default() {
+                line.trim_end()
+            } else {
+                line
+            };
+            if self.collapse_blank_lines.unwrap_or_default() && line.trim().is_empty() {
+                if blank_run {
+                    continue;
+                }
+                blank_run = true;
+            } else {
+                blank_run = false;
+            }
+            match self.max_line_width {
+                Some(width) => out.extend(wrap_line(line, width)),
+                None => out.push(line.to_string()),
+            }
+        }
+    }
+}
+
+/// The fence (```` ``` ```` or `~~~`, with its full length) opened or
+/// closed by `line`, if any.
+fn fence_marker(line: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    let ch = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|c| *c == ch).count();
+    (len >= 3).then(|| ch.to_string().repeat(len))
+}
+
+/// `line` with each tab replaced by the spaces up to the next tab stop,
+/// every `width` columns.
+fn expand_tabs(line: &str, width: usize) -> String {
+    let width = width.max(1);
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = width - column % width;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+/// Wraps a prose line at word boundaries. Headings and table rows are never
+/// wrapped; continuation lines keep blockquote prefixes and align with list
+/// item content.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let content = line.trim_start();
+    if line.chars().count() <= width || content.starts_with('#') || content.starts_with('|') {
+        return vec![line.to_string()];
+    }
+
+    let indent = &line[..line.len() - content.len()];
+    let quote_len = content
+        .find(|c: char| c != '>' && c != ' ')
+        .unwrap_or(content.len());
+    let (quote, rest) = content.split_at(quote_len);
+    let marker_len = list_marker_len(rest);
+    let first_prefix = format!("{indent}{quote}{}", &rest[..marker_len]);
+    let next_prefix = format!("{indent}{quote}{}", " ".repeat(marker_len));
+
+    let mut lines = Vec::new();
+    let mut current = first_prefix;
+    let mut has_word = false;
+    for word in rest[marker_len..]
+        .split(' ')
+        .filter(|word| !word.is_empty())
+    {
+        if has_word && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::replace(&mut current, next_prefix.clone()));
+            has_word = false;
+        }
+        if has_word {
+            current.push(' ');
+        }
+        current.push_str(word);
+        has_word = true;
+    }
+    lines.push(current);
+    lines
+}
+
+/// Length of a leading `- `, `* `, `+ ` or `1. ` list marker.
+fn list_marker_len(s: &str) -> usize {
+    if ["- ", "* ", "+ "]
+        .iter()
+        .any(|marker| s.starts_with(marker))
+    {
+        return 2;
+    }
+    let digits = s.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && (s[digits..].starts_with(". ") || s[digits..].starts_with(") ")) {
+        digits + 2
+    } else {
+        0
+    }
+}
+
+/// Removes the reserved [`ARGUMENT`] from a call's arguments and parses it.
+pub(crate) fn take_argument(arguments: &mut Option<JsonObject>) -> Result<PostProcess, ErrorData> {
+    let Some(value) = arguments
+        .as_mut()
+        .and_then(|arguments| arguments.remove(ARGUMENT))
+    else {
+        return Ok(PostProcess::default());
+    };
+    serde_json::from_value(value).map_err(|e| {
+        ErrorData::invalid_params(
+            "Invalid `postprocess` argument",
+            Some(serde_json::Value::String(e.to_string())),
+        )
+    })
+}
+
+/// Whether `tool` returns markdown values (its output is a `QueryOutput`).
+pub(super) fn returns_markdown(tool: &Tool) -> bool {
+    tool.output_schema
+        .as_ref()
+        .and_then(|schema| schema.get("properties"))
+        .and_then(|properties| properties.get("results"))
+        .is_some()
+}
+
+/// Advertises the [`ARGUMENT`] in the input schema of markdown-returning
+/// tools.
+pub(crate) fn describe_argument(mut tool: Tool) -> Tool {
+    if !returns_markdown(&tool) {
+        return tool;
+    }
+    let mut schema = (*tool.input_schema).clone();
+    if let Some(serde_json::Value::Object(properties)) = schema.get_mut("properties") {
+        let mut argument =
+            serde_json::to_value(schemars::schema_for!(PostProcess)).unwrap_or_default();
+        if let Some(argument) = argument.as_object_mut() {
+            argument.remove("$schema");
+            argument.insert(
+                "description".to_string(),
+                "Optional post-processing of the returned markdown, overriding the server defaults"
+                    .into(),
+            );
+        }
+        properties.insert(ARGUMENT.to_string(), argument);
+    }
+    tool.input_schema = Arc::new(schema);
+    tool
+}
+
+/// Post-processes the markdown values of a query tool result (one whose
+/// structured content is a `QueryOutput`); other results pass through.
+pub(crate) fn process_result(
+    steps: PostProcess,
+    defaults: PostProcess,
+    mut result: CallToolResult,
+) -> CallToolResult {
+    let steps = steps.or(defaults);
+    if steps.is_noop() {
+        return result;
+    }
+    let Some(serde_json::Value::Array(values)) = result
+        .structured_content
+        .as_mut()
+        .and_then(|structured| structured.get_mut("results"))
+    else {
+        return result;
+    };
+    let mut results = Vec::with_capacity(values.len());
+    for value in values.iter_mut() {
+        if let serde_json::Value::String(markdown) = value {
+            *markdown = steps.apply(markdown);
+            results.push(markdown.clone());
+        }
+    }
+    result.content = results.into_iter().map(ContentBlock::text).collect();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("trim", "  \n# A  \n\ntext \n\n", "# A\n\ntext")]
+    #[case("collapse-blank-lines", "a\n\n\n\nb", "a\n\nb")]
+    #[case("strip-html-comments", "a <!-- note -->b\n<!--\nmulti\n-->", "a b\n")]
+    #[case(
+        "max-line-width=10",
+        "- one two three four",
+        "- one two\n  three\n  four"
+    )]
+    #[case(
+        "max-line-width=10",
+        "## a very long heading",
+        "## a very long heading"
+    )]
+    #[case(
+        "emoji=unicode",
+        "# Done :tada:\n\n```\n:tada:\n```",
+        "# Done 🎉\n\n```\n:tada:\n```"
+    )]
+    #[case(
+        "emoji=strip,emoji-platform=slack",
+        "Nice :thinking_face: idea",
+        "Nice idea"
+    )]
+    #[case(
+        "strict-commonmark",
+        "* _a_ and 1. b\n* 5 * 3",
+        "- *a* and 1. b\n- 5 \\* 3"
+    )]
+    #[case("emoji=unicode,strict-commonmark", "__Done__ :tada:", "**Done** 🎉")]
+    #[case(
+        "expand-tabs=4",
+        "a\tb\tc\n```\n\tcode\n```",
+        "a   b   c\n```\n\tcode\n```"
+    )]
+    #[case("final-newline", "# A\n\ntext", "# A\n\ntext\n")]
+    #[case("line-endings=crlf", "# A\r\n\ntext", "# A\r\n\r\ntext")]
+    #[case("line-endings=lf", "a\r\nb", "a\nb")]
+    #[case("trim,final-newline,line-endings=crlf", "a\nb\n\n", "a\r\nb\r\n")]
+    #[case(
+        "trim,collapse-blank-lines,strip-html-comments,max-line-width=5",
+        "```\nkeep   \n\n\n<!-- x --> long code line\n```",
+        "```\nkeep   \n\n\n<!-- x --> long code line\n```"
+    )]
+    fn test_apply(#[case] steps: &str, #[case] input: &str, #[case] expected: &str) {
+        let steps = steps.parse::<PostProcess>().unwrap();
+        assert_eq!(steps.apply(input), expected);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_step() {
+        assert!("uppercase".parse::<PostProcess>().is_err());
+        assert!("emoji=shout".parse::<PostProcess>().is_err());
+        assert!("line-endings=cr".parse::<PostProcess>().is_err());
+        assert!("expand-tabs=0".parse::<PostProcess>().is_err());
+    }
+
+    #[test]
+    fn test_final_newline_can_be_removed() {
+        let steps = PostProcess {
+            final_newline: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(steps.apply("a\r\n\n"), "a");
+    }
+
+    #[test]
+    fn test_per_call_steps_override_defaults() {
+        let defaults = "trim,max-line-width=80".parse::<PostProcess>().unwrap();
+        let steps = PostProcess {
+            trim: Some(false),
+            ..Default::default()
+        };
+        let merged = steps.or(defaults);
+        assert_eq!(merged.trim, Some(false));
+        assert_eq!(merged.max_line_width, Some(80));
+    }
+
+    #[test]
+    fn test_process_result_rewrites_query_output() {
+        let result = super::super::output::query_result(vec!["a\n\n\nb".to_string()]).unwrap();
+        let steps = "collapse-blank-lines".parse::<PostProcess>().unwrap();
+        let result = process_result(steps, PostProcess::default(), result);
+        assert_eq!(result.content[0].as_text().unwrap().text, "a\n\nb");
+        assert_eq!(
+            result.structured_content,
+            Some(serde_json::json!({ "results": ["a\n\nb"] }))
+        );
+    }
+
+    #[test]
+    fn test_take_argument_removes_it() {
+        let mut arguments =
+            serde_json::json!({ "markdown": "# A", "postprocess": { "trim": true } })
+                .as_object()
+                .cloned();
+        let steps = take_argument(&mut arguments).unwrap();
+        assert_eq!(steps.trim, Some(true));
+        assert!(!arguments.unwrap().contains_key(ARGUMENT));
+    }
+}
@@ -0,0 +1,328 @@
+//! Engine profiles: named environments the config file defines, each with
+//! its own user modules and limits, so that agents with different needs
+//! (strict extraction, permissive transformation) share one server. A call
+//! picks one with the reserved `profile` argument; calls without it use the
+//! profile named `default` if the config file defines one, and otherwise
+//! the user modules and the engine's default limits.
+//!
+//! [`Server::call_tool`](rmcp::ServerHandler::call_tool) strips the argument
+//! before the arguments reach the tool and carries the profile in a
+//! task-local for the duration of the call; [`setup`] applies it to the
+//! engines the tool creates.
+//!
+//! Profiles, and the config file itself, can also set the query a tool runs
+//! when a call gives none, in place of `identity()`: per tool, and for tools
+//! taking a `url`, per host pattern, e.g. a site-specific query stripping
+//! the navigation of pages fetched from a documentation site.
+
+use std::{future::Future, path::PathBuf, sync::Arc, time::Duration};
+
+use rmcp::{
+    ErrorData,
+    model::{JsonObject, Tool},
+};
+
+use super::{
+    engine::{Engine, Limits, Optimization, QueryEngine},
+    policy::Policy,
+    reload::Module,
+};
+
+/// Name of the per-call argument accepted by every tool.
+pub(crate) const ARGUMENT: &str = "profile";
+
+/// The profile used by calls that don't name one.
+pub(crate) const DEFAULT: &str = "default";
+
+tokio::task_local! {
+    static PROFILE: Arc<Profile>;
+}
+
+/// A profile as the config file's `profiles` define it.
+#[derive(Debug, Default, Clone, PartialEq, Eq, rmcp::serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ProfileConfig {
+    /// Directory of the profile's user modules, relative to the config
+    /// file, loaded instead of the server's.
+    pub modules: Option<PathBuf>,
+    /// Milliseconds a query may run.
+    pub timeout_ms: Option<u64>,
+    /// How deep user-defined functions may recurse.
+    pub max_call_stack_depth: Option<u32>,
+    #[serde(default)]
+    pub optimization: Optimization,
+    /// Checked before the config file's own `default_queries`.
+    #[serde(default)]
+    pub default_queries: Vec<DefaultQuery>,
+}
+
+/// A query a tool runs when a call gives none.
+#[derive(Debug, Clone, PartialEq, Eq, rmcp::serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DefaultQuery {
+    pub tool: String,
+    /// Host of the call's `url`: `docs.example.com`, or `*.example.com`
+    /// for its subdomains. Without it, the query applies to every call of
+    /// the tool.
+    pub host: Option<String>,
+    pub query: String,
+}
+
+impl DefaultQuery {
+    fn matches(&self, tool: &str, host: Option<&str>) -> bool {
+        if self.tool != tool {
+            return false;
+        }
+        let Some(pattern) = &self.host else {
+            return true;
+        };
+        let Some(host) = host else {
+            return false;
+        };
+        match pattern.strip_prefix("*.") {
+            Some(domain) => host.len().checked_sub(domain.len() + 1).is_some_and(|dot| {
+                host.as_bytes()[dot] == b'.' && host[dot + 1..].eq_ignore_ascii_case(domain)
+            }),
+            None => host.eq_ignore_ascii_case(pattern),
+        }
+    }
+}
+
+impl ProfileConfig {
+    pub(crate) fn limits(&self) -> Limits {
+        Limits {
+            timeout: self.timeout_ms.map(Duration::from_millis),
+            max_call_stack_depth: self.max_call_stack_depth,
+            optimization: self.optimization,
+        }
+    }
+}
+
+/// A loaded profile.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Profile {
+    pub modules: Arc<[Module]>,
+    pub limits: Limits,
+    pub default_queries: Arc<[DefaultQuery]>,
+    /// The config file's query policy, the same for every profile.
+    pub policy: Arc<Policy>,
+}
+
+impl Profile {
+    /// The first default query of `tool` matching `host`.
+    fn default_query(&self, tool: &str, host: Option<&str>) -> Option<&str> {
+        self.default_queries
+            .iter()
+            .find(|rule| rule.matches(tool, host))
+            .map(|rule| rule.query.as_str())
+    }
+
+    /// Sets the `query` argument of a call to `tool` that gives none, if a
+    /// default query matches the tool and the host of its `url` argument.
+    pub(crate) fn fill_default_query(&self, tool: &Tool, arguments: &mut Option<JsonObject>) {
+        let given = |name: &str| {
+            arguments
+                .as_ref()
+                .and_then(|arguments| arguments.get(name))
+                .filter(|value| !value.is_null())
+        };
+        if self.default_queries.is_empty()
+            || given("query").is_some()
+            || tool
+                .input_schema
+                .get("properties")
+                .and_then(|properties| properties.get("query"))
+                .is_none()
+        {
+            return;
+        }
+        let host = given("url")
+            .and_then(|url| url.as_str())
+            .and_then(|url| url::Url::parse(url).ok())
+            .and_then(|url| url.host_str().map(str::to_string));
+        if let Some(query) = self.default_query(&tool.name, host.as_deref()) {
+            arguments
+                .get_or_insert_with(JsonObject::new)
+                .insert("query".to_string(), query.into());
+        }
+    }
+}
+
+/// Removes the [`ARGUMENT`] from a call's arguments and returns the profile
+/// it names.
+pub(crate) fn take_argument(
+    arguments: &mut Option<JsonObject>,
+) -> Result<Option<String>, ErrorData> {
+    match arguments
+        .as_mut()
+        .and_then(|arguments| arguments.remove(ARGUMENT))
+    {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::String(name)) => Ok(Some(name)),
+        Some(value) => Err(ErrorData::invalid_params(
+            "Invalid `profile` argument",
+            Some(serde_json::json!({ "expected": "string", "got": value })),
+        )),
+    }
+}
+
+/// Advertises the [`ARGUMENT`], with the profiles `names`, in `tool`'s input
+/// schema. Servers without profiles don't advertise it.
+pub(crate) fn describe_argument(mut tool: Tool, names: &[String]) -> Tool {
+    if names.is_empty() {
+        return tool;
+    }
+    let mut schema = (*tool.input_schema).clone();
+    if let Some(serde_json::Value::Object(properties)) = schema.get_mut("properties") {
+        properties.insert(
+            ARGUMENT.to_string(),
+            serde_json::json!({
+                "type": "string",
+                "enum": names,
+                "description": "Engine profile to run the call's queries with: its user modules and limits (default: the `default` profile)",
+            }),
+        );
+    }
+    tool.input_schema = Arc::new(schema);
+    tool
+}
+
+/// Runs a tool call with `profile`. A reload during the call applies from
+/// the next one.
+pub(crate) async fn scope<F: Future>(profile: Arc<Profile>, f: F) -> F::Output {
+    PROFILE.scope(profile, f).await
+}
+
+/// Applies the current call's profile to `engine`: sets its limits and
+/// query policy and loads its user modules.
+pub(super) fn setup(engine: &mut Engine) {
+    let _ = PROFILE.try_with(|profile| {
+        engine.set_limits(&profile.limits);
+        engine.set_policy(profile.policy.clone());
+        for module in profile.modules.iter() {
+            if let Err(e) = engine.load(&module.source) {
+                tracing::warn!("user module {} doesn't load: {e}", module.name);
+            }
+        }
+    });
+}
+
+/// Checks `query` against the current call's query policy, or `default`'s
+/// outside a call, for queries run by other engines than [`Engine`], such
+/// as mq-db's.
+pub(super) fn check_policy(query: &str, default: &Profile) -> Result<(), String> {
+    PROFILE
+        .try_with(|profile| profile.policy.check(query))
+        .unwrap_or_else(|_| default.policy.check(query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::engine::{QueryValue, Value};
+    use rstest::rstest;
+
+    #[test]
+    fn test_take_argument() {
+        let mut arguments = serde_json::json!({ "markdown": "# a", "profile": "strict" })
+            .as_object()
+            .cloned();
+        assert_eq!(
+            take_argument(&mut arguments).unwrap(),
+            Some("strict".to_string())
+        );
+        assert!(!arguments.unwrap().contains_key(ARGUMENT));
+
+        let mut arguments = serde_json::json!({ "profile": 1 }).as_object().cloned();
+        assert!(take_argument(&mut arguments).is_err());
+    }
+
+    #[rstest]
+    #[case("docs.example.com", Some("docs.example.com"), true)]
+    #[case("docs.example.com", Some("DOCS.example.com"), true)]
+    #[case("*.example.com", Some("docs.example.com"), true)]
+    #[case("*.example.com", Some("example.com"), false)]
+    #[case("*.example.com", Some("badexample.com"), false)]
+    #[case("*.example.com", None, false)]
+    fn test_host_patterns(
+        #[case] pattern: &str,
+        #[case] host: Option<&str>,
+        #[case] expected: bool,
+    ) {
+        let rule = DefaultQuery {
+            tool: "query_url".to_string(),
+            host: Some(pattern.to_string()),
+            query: "identity()".to_string(),
+        };
+        assert_eq!(rule.matches("query_url", host), expected);
+        assert!(!rule.matches("html_to_markdown", host));
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn test_fill_default_query() {
+        let rule = |host: Option<&str>, query: &str| DefaultQuery {
+            tool: "query_url".to_string(),
+            host: host.map(str::to_string),
+            query: query.to_string(),
+        };
+        let profile = Profile {
+            default_queries: Arc::new([
+                rule(Some("*.example.com"), "select(!.h)"),
+                rule(None, ".h1"),
+            ]),
+            ..Default::default()
+        };
+        let tool = crate::server::Server::new(None)
+            .unwrap()
+            .tool_router
+            .get("query_url")
+            .cloned()
+            .unwrap();
+        let fill = |arguments: serde_json::Value| {
+            let mut arguments = arguments.as_object().cloned();
+            profile.fill_default_query(&tool, &mut arguments);
+            arguments.unwrap().get("query").cloned()
+        };
+        assert_eq!(
+            fill(serde_json::json!({ "url": "https://docs.example.com/a" })),
+            Some("select(!.h)".into())
+        );
+        assert_eq!(
+            fill(serde_json::json!({ "url": "https://other.org/" })),
+            Some(".h1".into())
+        );
+        assert_eq!(
+            fill(serde_json::json!({ "url": "https://other.org/", "query": ".h2" })),
+            Some(".h2".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scope_sets_up_engines() {
+        let profile = Arc::new(Profile {
+            modules: Arc::new([Module {
+                name: "shout".to_string(),
+                source: "def shout(): upcase();".to_string(),
+            }]),
+            limits: Limits::default(),
+            default_queries: Arc::new([]),
+            policy: Arc::new(Policy {
+                deny_functions: vec!["downcase".to_string()],
+                ..Default::default()
+            }),
+        });
+        let (values, denied) = scope(profile, async {
+            let mut engine = Engine::new();
+            setup(&mut engine);
+            let input = || std::iter::once(Value::string("hi".to_string()));
+            (
+                engine.eval("shout()", input()),
+                engine.eval("downcase()", input()),
+            )
+        })
+        .await;
+        assert_eq!(values.unwrap()[0].to_string(), "HI");
+        assert!(denied.is_err());
+    }
+}
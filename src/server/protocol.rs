@@ -0,0 +1,54 @@
+//! MCP protocol revision negotiation.
+//!
+//! The server speaks the latest revision it knows, but answers older
+//! clients in the revision they asked for and avoids content types those
+//! revisions don't define.
+
+use rmcp::model::ProtocolVersion;
+
+/// Protocol revisions this server can speak, newest first.
+pub(crate) const SUPPORTED_PROTOCOL_VERSIONS: &[ProtocolVersion] = &[
+    ProtocolVersion::V_2025_06_18,
+    ProtocolVersion::V_2025_03_26,
+    ProtocolVersion::V_2024_11_05,
+];
+
+/// Picks the revision to use with a client that requested `requested`: the
+/// same revision if supported, otherwise our latest (the client then decides
+/// whether it can proceed, as the spec prescribes).
+pub(crate) fn negotiate(requested: &ProtocolVersion) -> ProtocolVersion {
+    SUPPORTED_PROTOCOL_VERSIONS
+        .iter()
+        .find(|version| *version == requested)
+        .unwrap_or(&SUPPORTED_PROTOCOL_VERSIONS[0])
+        .clone()
+}
+
+/// `resource_link` content blocks were introduced in 2025-06-18.
+pub(crate) fn supports_resource_links(version: &ProtocolVersion) -> bool {
+    *version != ProtocolVersion::V_2025_03_26 && *version != ProtocolVersion::V_2024_11_05
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(ProtocolVersion::V_2024_11_05, ProtocolVersion::V_2024_11_05)]
+    #[case(ProtocolVersion::V_2025_03_26, ProtocolVersion::V_2025_03_26)]
+    #[case(ProtocolVersion::V_2025_06_18, ProtocolVersion::V_2025_06_18)]
+    #[case(
+        serde_json::from_value(serde_json::json!("1999-01-01")).unwrap(),
+        ProtocolVersion::V_2025_06_18
+    )]
+    fn test_negotiate(#[case] requested: ProtocolVersion, #[case] expected: ProtocolVersion) {
+        assert_eq!(negotiate(&requested), expected);
+    }
+
+    #[test]
+    fn test_supports_resource_links() {
+        assert!(supports_resource_links(&ProtocolVersion::V_2025_06_18));
+        assert!(!supports_resource_links(&ProtocolVersion::V_2024_11_05));
+    }
+}
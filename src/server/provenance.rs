@@ -0,0 +1,306 @@
+//! Provenance footers: an opt-in citation mode in which each markdown value
+//! a query tool returns ends with the source document, heading path and
+//! line range it was extracted from, so agents can cite their claims.
+//!
+//! Enabled server-wide with `--provenance` or per call through the reserved
+//! `provenance` argument, which [`Server::call_tool`](rmcp::ServerHandler::call_tool)
+//! strips before the arguments reach the tool. The setting is carried to
+//! [`Server::query_nodes`](super::Server) in a task-local for the duration of
+//! the call.
+
+use std::{future::Future, sync::Arc};
+
+use mq_markdown::{Node, Position};
+use rmcp::{
+    ErrorData,
+    model::{JsonObject, Tool},
+};
+
+use super::{
+    engine::{QueryValue, Value},
+    postprocess::returns_markdown,
+};
+
+/// Name of the per-call argument accepted by every markdown-returning tool.
+pub(crate) const ARGUMENT: &str = "provenance";
+
+tokio::task_local! {
+    static PROVENANCE: Option<Provenance>;
+}
+
+/// The citation settings of one tool call.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Provenance {
+    /// The document the tool read, taken from its `url`, `repo`/`path` or
+    /// `path` argument; `None` for inline markdown.
+    source: Option<String>,
+}
+
+/// Removes the [`ARGUMENT`] from a call's arguments. Returns the citation
+/// settings when it (or, if absent, `default`) enables provenance.
+pub(crate) fn take_argument(
+    arguments: &mut Option<JsonObject>,
+    default: bool,
+) -> Result<Option<Provenance>, ErrorData> {
+    let enabled = match arguments
+        .as_mut()
+        .and_then(|arguments| arguments.remove(ARGUMENT))
+    {
+        None => default,
+        Some(serde_json::Value::Bool(enabled)) => enabled,
+        Some(value) => {
+            return Err(ErrorData::invalid_params(
+                "Invalid `provenance` argument",
+                Some(serde_json::json!({ "expected": "boolean", "got": value })),
+            ));
+        }
+    };
+    Ok(enabled.then(|| Provenance {
+        source: arguments.as_ref().and_then(source),
+    }))
+}
+
+/// The document named by a call's arguments.
+fn source(arguments: &JsonObject) -> Option<String> {
+    let string = |key: &str| arguments.get(key).and_then(serde_json::Value::as_str);
+    match (string("url"), string("repo"), string("path")) {
+        (Some(url), ..) => Some(url.to_string()),
+        (None, Some(repo), Some(path)) => Some(format!("{repo}/{path}")),
+        (None, Some(repo), None) => Some(repo.to_string()),
+        (None, None, path) => path.map(str::to_string),
+    }
+}
+
+/// Advertises the [`ARGUMENT`] in the input schema of markdown-returning
+/// tools.
+pub(crate) fn describe_argument(mut tool: Tool) -> Tool {
+    if !returns_markdown(&tool) {
+        return tool;
+    }
+    let mut schema = (*tool.input_schema).clone();
+    if let Some(serde_json::Value::Object(properties)) = schema.get_mut("properties") {
+        properties.insert(
+            ARGUMENT.to_string(),
+            serde_json::json!({
+                "type": "boolean",
+                "description": "Append a footer to each returned value citing its source document, heading path and line range, overriding the server default",
+            }),
+        );
+    }
+    tool.input_schema = Arc::new(schema);
+    tool
+}
+
+/// Runs a tool call with the given citation settings.
+pub(crate) async fn scope<F: Future>(provenance: Option<Provenance>, f: F) -> F::Output {
+    PROVENANCE.scope(provenance, f).await
+}
+
+/// Locates query results within the parsed input and formats their
+/// footers.
+pub(super) struct Citations {
+    source: Option<String>,
+    /// Positions of the top-level input nodes, one per evaluated input.
+    positions: Vec<Option<Position>>,
+    /// Line, depth and text of each heading in the input.
+    headings: Vec<(usize, u8, String)>,
+}
+
+impl Citations {
+    /// Prepares citations for `nodes` if the current call enabled
+    /// provenance.
+    pub(super) fn current(nodes: &[Node]) -> Option<Self> {
+        let provenance = PROVENANCE.try_with(Clone::clone).ok().flatten()?;
+        Some(Self::new(provenance, nodes))
+    }
+
+    fn new(provenance: Provenance, nodes: &[Node]) -> Self {
+        Self {
+            source: provenance.source,
+            positions: nodes.iter().map(Node::position).collect(),
+            headings: nodes
+                .iter()
+                .filter_map(|node| match node {
+                    Node::Heading(heading) => heading.position.as_ref().map(|position| {
+                        (
+                            position.start.line,
+                            heading.depth,
+                            node.value().trim().to_string(),
+                        )
+                    }),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether each result corresponds to the input node at the same index,
+    /// as it does unless the query aggregates with `nodes`.
+    pub(super) fn aligned(&self, results: usize) -> bool {
+        results == self.positions.len()
+    }
+
+    /// Appends the footer to `text`, the rendering of `value`. The value's
+    /// own position is preferred; computed values (strings, numbers) fall
+    /// back to that of the input node at `index`, if results are aligned.
+    pub(super) fn cite(&self, text: String, value: &Value, index: Option<usize>) -> String {
+        let position = value
+            .node()
+            .and_then(Node::position)
+            .or_else(|| index.and_then(|index| self.positions.get(index).cloned().flatten()));
+        match self.footer(position.as_ref()) {
+            Some(footer) => format!("{}\n\n{footer}", text.trim_end()),
+            None => text,
+        }
+    }
+
+    /// `[Source: ... | Section: A > B | Lines: 3-7]`, omitting the parts
+    /// that are unknown.
+    fn footer(&self, position: Option<&Position>) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(source) = &self.source {
+            parts.push(format!("Source: {source}"));
+        }
+        if let Some(position) = position {
+            let path = self.heading_path(position.start.line);
+            if !path.is_empty() {
+                parts.push(format!("Section: {}", path.join(" > ")));
+            }
+            let (start, end) = (
+                position.start.line,
+                position.end.line.max(position.start.line),
+            );
+            parts.push(if start == end {
+                format!("Line: {start}")
+            } else {
+                format!("Lines: {start}-{end}")
+            });
+        }
+        (!parts.is_empty()).then(|| format!("[{}]", parts.join(" | ")))
+    }
+
+    /// The headings enclosing `line`, outermost first.
+    fn heading_path(&self, line: usize) -> Vec<&str> {
+        let mut path: Vec<(u8, &str)> = Vec::new();
+        for (_, depth, text) in self
+            .headings
+            .iter()
+            .take_while(|(start, ..)| *start <= line)
+        {
+            while path.last().is_some_and(|(d, _)| d >= depth) {
+                path.pop();
+            }
+            path.push((*depth, text));
+        }
+        path.into_iter().map(|(_, text)| text).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::parse_markdown_only;
+    use rstest::rstest;
+
+    const MARKDOWN: &str = "\
+# Guide
+
+Intro.
+
+## Install
+
+Run the installer.
+
+## Usage
+
+### Flags
+
+```sh
+mq --verbose file.md
+```
+";
+
+    fn citations(source: Option<&str>) -> (Vec<Node>, Citations) {
+        let nodes = parse_markdown_only(MARKDOWN).unwrap();
+        let citations = Citations::new(
+            Provenance {
+                source: source.map(str::to_string),
+            },
+            &nodes,
+        );
+        (nodes, citations)
+    }
+
+    #[test]
+    fn test_cite_markdown_node() {
+        let (nodes, citations) = citations(Some("https://example.com/guide.md"));
+        let code = nodes
+            .iter()
+            .find(|node| node.value().starts_with("mq --verbose"))
+            .unwrap()
+            .clone();
+        let value = Value::from(code);
+        assert_eq!(
+            citations.cite(value.to_string(), &value, None),
+            "```sh\nmq --verbose file.md\n```\n\n[Source: https://example.com/guide.md | Section: Guide > Usage > Flags | Lines: 13-15]"
+        );
+    }
+
+    #[test]
+    fn test_cite_computed_value_uses_input_position() {
+        let (nodes, citations) = citations(None);
+        let index = nodes
+            .iter()
+            .position(|node| node.value().starts_with("Run"))
+            .unwrap();
+        let value = Value::string("Run the installer.".to_string());
+        assert_eq!(
+            citations.cite(value.to_string(), &value, Some(index)),
+            "Run the installer.\n\n[Section: Guide > Install | Line: 7]"
+        );
+        assert_eq!(citations.cite("x".to_string(), &value, None), "x");
+    }
+
+    #[rstest]
+    #[case(serde_json::json!({"url": "https://a.b/c", "path": "x"}), Some("https://a.b/c"))]
+    #[case(serde_json::json!({"repo": "o/r", "path": "docs/a.md"}), Some("o/r/docs/a.md"))]
+    #[case(serde_json::json!({"path": "notes.md"}), Some("notes.md"))]
+    #[case(serde_json::json!({"markdown": "# A"}), None)]
+    fn test_source(#[case] arguments: serde_json::Value, #[case] expected: Option<&str>) {
+        assert_eq!(source(arguments.as_object().unwrap()).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_take_argument() {
+        let mut arguments = serde_json::json!({"url": "https://a.b", "provenance": true})
+            .as_object()
+            .cloned();
+        let provenance = take_argument(&mut arguments, false).unwrap();
+        assert_eq!(provenance.unwrap().source.as_deref(), Some("https://a.b"));
+        assert!(!arguments.unwrap().contains_key(ARGUMENT));
+
+        let mut arguments = serde_json::json!({"provenance": false})
+            .as_object()
+            .cloned();
+        assert_eq!(take_argument(&mut arguments, true).unwrap(), None);
+        assert_eq!(
+            take_argument(&mut None, true).unwrap(),
+            Some(Provenance::default())
+        );
+        let mut arguments = serde_json::json!({"provenance": "yes"})
+            .as_object()
+            .cloned();
+        assert!(take_argument(&mut arguments, false).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scope() {
+        let nodes = parse_markdown_only(MARKDOWN).unwrap();
+        assert!(Citations::current(&nodes).is_none());
+        let cited = scope(Some(Provenance::default()), async {
+            Citations::current(&nodes).is_some()
+        })
+        .await;
+        assert!(cited);
+    }
+}
@@ -0,0 +1,146 @@
+//! Per-session accounting of the bytes processed by tool calls, and the
+//! optional hourly/daily quotas enforced on it.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rmcp::{ErrorData, model::CallToolResult};
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Byte limits per rolling window; `None` is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quotas {
+    pub hourly: Option<u64>,
+    pub daily: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct UsageLog {
+    /// Bytes processed, by time; entries older than a day are dropped.
+    events: VecDeque<(Instant, u64)>,
+}
+
+impl UsageLog {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .events
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= DAY)
+        {
+            self.events.pop_front();
+        }
+    }
+
+    /// Bytes recorded within `window` of `now`, and when the oldest of them
+    /// leaves the window.
+    fn used(&self, now: Instant, window: Duration) -> (u64, Duration) {
+        let mut in_window = self
+            .events
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) < window)
+            .peekable();
+        let resets_in = in_window
+            .peek()
+            .map(|(at, _)| window - now.duration_since(*at))
+            .unwrap_or_default();
+        (in_window.map(|(_, bytes)| bytes).sum(), resets_in)
+    }
+}
+
+/// Bytes processed by one session (request arguments plus returned text).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Usage(Arc<Mutex<UsageLog>>);
+
+impl Usage {
+    /// Admits a call carrying `incoming` bytes of arguments, recording them,
+    /// or explains which quota it would exceed.
+    pub(crate) fn admit(&self, quotas: Quotas, incoming: u64) -> Result<(), ErrorData> {
+        self.admit_at(quotas, incoming, Instant::now())
+    }
+
+    fn admit_at(&self, quotas: Quotas, incoming: u64, now: Instant) -> Result<(), ErrorData> {
+        let mut log = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        log.prune(now);
+        for (window, duration, limit) in [
+            ("hourly", HOUR, quotas.hourly),
+            ("daily", DAY, quotas.daily),
+        ] {
+            let Some(limit) = limit else {
+                continue;
+            };
+            let (used, resets_in) = log.used(now, duration);
+            if used + incoming > limit {
+                return Err(ErrorData::invalid_request(
+                    format!("Session quota exceeded ({window} limit of {limit} bytes)"),
+                    Some(serde_json::json!({
+                        "window": window,
+                        "limit": limit,
+                        "used": used,
+                        "requested": incoming,
+                        "retry_after_secs": resets_in.as_secs().max(1),
+                    })),
+                ));
+            }
+        }
+        log.events.push_back((now, incoming));
+        Ok(())
+    }
+
+    /// Records the text returned by a call.
+    pub(crate) fn record_result(&self, result: &CallToolResult) {
+        let bytes = result
+            .content
+            .iter()
+            .filter_map(|content| content.as_text())
+            .map(|text| text.text.len() as u64)
+            .sum();
+        self.record_at(bytes, Instant::now());
+    }
+
+    fn record_at(&self, bytes: u64, now: Instant) {
+        let mut log = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        log.prune(now);
+        log.events.push_back((now, bytes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admit_enforces_hourly_quota() {
+        let usage = Usage::default();
+        let quotas = Quotas {
+            hourly: Some(100),
+            daily: None,
+        };
+        let start = Instant::now();
+        assert!(usage.admit_at(quotas, 60, start).is_ok());
+        usage.record_at(30, start);
+
+        let error = usage.admit_at(quotas, 20, start).unwrap_err();
+        assert!(error.message.starts_with("Session quota exceeded (hourly"));
+        let data = error.data.unwrap();
+        assert_eq!(data["used"], 90);
+        assert_eq!(data["retry_after_secs"], 3600);
+
+        assert!(usage.admit_at(quotas, 20, start + HOUR).is_ok());
+        assert_eq!(usage.0.lock().unwrap().used(start + HOUR, DAY).0, 110);
+    }
+
+    #[test]
+    fn test_admit_without_quotas_is_unlimited() {
+        let usage = Usage::default();
+        assert!(
+            usage
+                .admit_at(Quotas::default(), u64::MAX / 2, Instant::now())
+                .is_ok()
+        );
+    }
+}
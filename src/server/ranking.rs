@@ -0,0 +1,199 @@
+//! `rank_sections`: lexical (BM25) retrieval over the sections of a single
+//! document.
+
+use std::collections::{HashMap, HashSet};
+
+use rmcp::{handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    keywords::{Tokenizer, prose},
+    language::parse_language,
+    output::{json_result, output_schema},
+    sections::{self, Section},
+};
+
+const DEFAULT_LIMIT: usize = 5;
+/// BM25 term frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 length normalization.
+const B: f64 = 0.75;
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct RankSectionsInput {
+    #[schemars(description = "The markdown content to search")]
+    pub markdown: String,
+    #[schemars(description = "The text to rank sections against, e.g. a question or keywords")]
+    pub search: String,
+    #[schemars(description = "Maximum number of sections to return (default: 5)")]
+    pub limit: Option<usize>,
+    #[schemars(
+        description = "Language for the stopword list, as an ISO 639-3 code or English name (default: detected from the document)"
+    )]
+    pub language: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct RankedSection {
+    #[schemars(description = "The section's stable id, accepted by edit_section")]
+    pub id: String,
+    #[schemars(description = "Heading text; null for the text before the first heading")]
+    pub title: Option<String>,
+    #[schemars(description = "1-based line of the heading")]
+    pub line: usize,
+    #[schemars(description = "BM25 score; higher is more relevant")]
+    pub score: f64,
+    #[schemars(description = "The search terms found in the section")]
+    pub matched: Vec<String>,
+    #[schemars(description = "The section (heading and body) as markdown")]
+    pub markdown: String,
+}
+
+/// Output of `rank_sections`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct RankSectionsOutput {
+    #[schemars(description = "Matching sections, most relevant first")]
+    pub sections: Vec<RankedSection>,
+}
+
+/// Scores each section against `search` with BM25 and returns the `limit`
+/// best sections that match at least one term.
+fn rank(
+    sections: Vec<Section>,
+    search: &str,
+    tokenizer: &Tokenizer,
+    limit: usize,
+) -> Vec<RankedSection> {
+    let terms: Vec<String> = tokenizer
+        .terms(search)
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let documents: Vec<HashMap<String, usize>> = sections
+        .iter()
+        .map(|section| {
+            let mut counts = HashMap::new();
+            for term in tokenizer.terms(&prose(&section.markdown)) {
+                *counts.entry(term).or_default() += 1;
+            }
+            counts
+        })
+        .collect();
+    let lengths: Vec<f64> = documents
+        .iter()
+        .map(|counts| counts.values().sum::<usize>() as f64)
+        .collect();
+    let average = lengths.iter().sum::<f64>() / lengths.len().max(1) as f64;
+    let n = documents.len() as f64;
+    let idf: HashMap<&str, f64> = terms
+        .iter()
+        .map(|term| {
+            let df = documents.iter().filter(|d| d.contains_key(term)).count() as f64;
+            (term.as_str(), ((n - df + 0.5) / (df + 0.5) + 1.0).ln())
+        })
+        .collect();
+
+    let mut ranked: Vec<RankedSection> = sections
+        .into_iter()
+        .zip(documents.iter().zip(&lengths))
+        .filter_map(|(section, (counts, &length))| {
+            let mut matched = Vec::new();
+            let mut score = 0.0;
+            for term in &terms {
+                let Some(&tf) = counts.get(term) else {
+                    continue;
+                };
+                let tf = tf as f64;
+                score += idf[term.as_str()] * tf * (K1 + 1.0)
+                    / (tf + K1 * (1.0 - B + B * length / average.max(1.0)));
+                matched.push(term.clone());
+            }
+            matched.sort();
+            (!matched.is_empty()).then_some(RankedSection {
+                id: section.id,
+                title: section.title,
+                line: section.line,
+                score,
+                matched,
+                markdown: section.markdown,
+            })
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.line.cmp(&b.line)));
+    ranked.truncate(limit);
+    ranked
+}
+
+#[tool_router(router = ranking_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Split markdown into sections (heading + body up to the next heading) and rank them by lexical relevance (BM25) to a search text, returning the top sections with their scores and matched terms. Cheap retrieval over one large document without a vector database.",
+        output_schema = output_schema::<RankSectionsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn rank_sections(&self, Parameters(input): Parameters<RankSectionsInput>) -> McpResult {
+        let language = match input.language.as_deref() {
+            Some(name) => Some(parse_language(name)?),
+            None => whatlang::detect_lang(&prose(&input.markdown)),
+        };
+        json_result(&RankSectionsOutput {
+            sections: rank(
+                sections::split(&input.markdown)?,
+                &input.search,
+                &Tokenizer::new(language, &[]),
+                input.limit.unwrap_or(DEFAULT_LIMIT),
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use whatlang::Lang;
+
+    const MARKDOWN: &str = "\
+# Installation
+
+Install the binary with cargo, or download a release.
+
+# Configuration
+
+The server reads its configuration file at startup. The configuration
+file lists the allowed roots.
+
+# Troubleshooting
+
+If the server fails to start, check the configuration file for typos.
+";
+
+    fn titles(ranked: &[RankedSection]) -> Vec<Option<&str>> {
+        ranked.iter().map(|s| s.title.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_rank() {
+        let ranked = rank(
+            sections::split(MARKDOWN).unwrap(),
+            "configuration file",
+            &Tokenizer::new(Some(Lang::Eng), &[]),
+            5,
+        );
+        assert_eq!(
+            titles(&ranked),
+            vec![Some("Configuration"), Some("Troubleshooting")]
+        );
+        assert_eq!(ranked[0].line, 5);
+        assert_eq!(ranked[0].matched, vec!["configuration", "file"]);
+        assert!(ranked[0].score > ranked[1].score);
+    }
+
+    #[test]
+    fn test_rank_limit_and_no_match() {
+        let tokenizer = Tokenizer::new(Some(Lang::Eng), &[]);
+        let split = || sections::split(MARKDOWN).unwrap();
+        assert_eq!(rank(split(), "server", &tokenizer, 1).len(), 1);
+        assert!(rank(split(), "the kubernetes", &tokenizer, 5).is_empty());
+    }
+}
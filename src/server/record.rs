@@ -0,0 +1,405 @@
+//! Recording and replay of stdio sessions, for reproducing bugs reported
+//! with a particular client.
+//!
+//! With `--record <dir>`, every JSON-RPC message the client and the server
+//! exchange is appended to a JSON Lines file in `dir`, one file per
+//! session. `--replay <file>` feeds the client's messages of such a file to
+//! a fresh server, in the recorded interleaving, and compares the server's
+//! responses with the recorded ones.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use chrono::Utc;
+use miette::miette;
+use rmcp::{ServiceExt, serde::Serialize};
+use serde_json::Value;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream, Lines, ReadBuf,
+    ReadHalf, stdin, stdout,
+};
+
+use super::{Server, ServerConfig, correlation, invoke::serve_in_memory};
+
+/// How long a replay waits for the server to send what it sent at that point
+/// of the recording before going on without it.
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Which side sent a recorded message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, rmcp::serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    Client,
+    Server,
+}
+
+/// One line of a recording.
+#[derive(Debug, Serialize, rmcp::serde::Deserialize)]
+struct Entry {
+    /// When the message was sent (RFC 3339).
+    at: String,
+    direction: Direction,
+    /// The JSON-RPC message; a string if it wasn't valid JSON.
+    message: Value,
+}
+
+/// The recording file of a session, shared by both directions.
+#[derive(Clone)]
+struct Recorder(Arc<Mutex<File>>);
+
+impl Recorder {
+    /// Creates a new recording in `dir`, creating `dir` if needed.
+    fn create(dir: &Path) -> Result<(Self, PathBuf), String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+        let path = dir.join(format!(
+            "session-{}-{}.jsonl",
+            Utc::now().format("%Y%m%dT%H%M%S"),
+            std::process::id()
+        ));
+        let file = File::create_new(&path)
+            .map_err(|e| format!("failed to create {}: {e}", path.display()))?;
+        Ok((Self(Arc::new(Mutex::new(file))), path))
+    }
+
+    /// Appends a message. Failures are logged, so a full disk doesn't end
+    /// the session.
+    fn record(&self, direction: Direction, line: &[u8]) {
+        let message = serde_json::from_slice(line)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(line).into_owned()));
+        let entry = Entry {
+            at: Utc::now().to_rfc3339(),
+            direction,
+            message,
+        };
+        let Ok(json) = serde_json::to_string(&entry) else {
+            return;
+        };
+        let mut file = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{json}") {
+            tracing::warn!("failed to record a message: {e}");
+        }
+    }
+}
+
+/// A transport half that records every line passing through it.
+struct Recording<T> {
+    inner: T,
+    recorder: Recorder,
+    direction: Direction,
+    /// Bytes of a line not yet complete.
+    pending: Vec<u8>,
+}
+
+impl<T> Recording<T> {
+    fn new(inner: T, recorder: Recorder, direction: Direction) -> Self {
+        Self {
+            inner,
+            recorder,
+            direction,
+            pending: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        while let Some(end) = self.pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = line.trim_ascii();
+            if !line.is_empty() {
+                self.recorder.record(self.direction, line);
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Recording<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            this.push(&buf.filled()[before..]);
+        }
+        poll
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Recording<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_write(cx, data);
+        if let Poll::Ready(Ok(written)) = poll {
+            this.push(&data[..written]);
+        }
+        poll
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Serves over stdio, as [`super::start`] does, recording the session in a
+/// new file in `dir`.
+pub async fn start_recorded(config: ServerConfig, dir: &Path) -> miette::Result<()> {
+    let (recorder, path) = Recorder::create(dir).map_err(|e| miette!(e))?;
+    tracing::info!("recording the session to {}", path.display());
+    let transport = (
+        Recording::new(stdin(), recorder.clone(), Direction::Client),
+        Recording::new(stdout(), recorder, Direction::Server),
+    );
+    let server = Server::with_config(config).map_err(|e| miette!("{e}"))?;
+    let service = server.serve(transport).await.map_err(|e| miette!(e))?;
+    service.waiting().await.map_err(|e| miette!(e))?;
+    Ok(())
+}
+
+/// The result of [`replay`].
+#[derive(Debug, Serialize)]
+pub struct ReplayReport {
+    /// The messages the server sent, in order.
+    pub messages: Vec<Value>,
+    /// Number of responses in the recording.
+    pub responses: usize,
+    /// Ids of the recorded responses the server answered differently, or
+    /// not at all.
+    pub differing: Vec<Value>,
+}
+
+/// The responses among `messages`, by id.
+fn responses<'a>(messages: impl Iterator<Item = &'a Value>) -> HashMap<String, &'a Value> {
+    messages
+        .filter(|message| message.get("method").is_none())
+        .filter_map(|message| Some((message.get("id")?.to_string(), message)))
+        .collect()
+}
+
+/// `message` without the request id of an error response, which is
+/// generated anew on every run.
+fn without_request_id(message: &Value) -> Value {
+    let mut message = message.clone();
+    for pointer in ["/error/data", "/result/_meta"] {
+        if let Some(Value::Object(object)) = message.pointer_mut(pointer) {
+            object.remove(correlation::REQUEST_ID_KEY);
+        }
+    }
+    message
+}
+
+/// Reads the server's messages into `messages` until `done` holds, the
+/// session ends, or nothing arrives for [`REPLAY_TIMEOUT`].
+async fn read_until(
+    lines: &mut Lines<BufReader<ReadHalf<DuplexStream>>>,
+    messages: &mut Vec<Value>,
+    done: impl Fn(&[Value]) -> bool,
+) -> miette::Result<()> {
+    while !done(messages) {
+        match tokio::time::timeout(REPLAY_TIMEOUT, lines.next_line()).await {
+            Err(_) => {
+                tracing::warn!("the server sent fewer messages than recorded");
+                return Ok(());
+            }
+            Ok(Err(e)) => return Err(miette!("failed to read from the server: {e}")),
+            Ok(Ok(None)) => return Ok(()),
+            Ok(Ok(Some(line))) => {
+                messages.push(serde_json::from_str(&line).unwrap_or_else(|_| Value::String(line)))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replays the client's messages recorded in `file` to a server built from
+/// `config`. Each message is sent once the server has sent as many messages
+/// as it had at that point of the recording, so requests the server makes
+/// of the client get their recorded answers.
+pub async fn replay(config: ServerConfig, file: &Path) -> miette::Result<ReplayReport> {
+    let text = std::fs::read_to_string(file)
+        .map_err(|e| miette!("failed to read {}: {e}", file.display()))?;
+    let entries = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str::<Entry>(line).map_err(|e| {
+                miette!(
+                    "invalid recording {} at line {}: {e}",
+                    file.display(),
+                    index + 1
+                )
+            })
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+
+    let server = Server::with_config(config).map_err(|e| miette!("{e}"))?;
+    let (reader, mut writer, session) = serve_in_memory(server);
+    let mut lines = BufReader::new(reader).lines();
+    let mut messages = Vec::new();
+    let mut expected = 0;
+    let mut requests = HashSet::new();
+    for entry in &entries {
+        if entry.direction == Direction::Server {
+            expected += 1;
+            continue;
+        }
+        read_until(&mut lines, &mut messages, |messages| {
+            messages.len() >= expected
+        })
+        .await?;
+        if let (Some(_), Some(id)) = (entry.message.get("method"), entry.message.get("id")) {
+            requests.insert(id.to_string());
+        }
+        writer
+            .write_all(format!("{}\n", entry.message).as_bytes())
+            .await
+            .map_err(|e| miette!("failed to send a message to the server: {e}"))?;
+    }
+    read_until(&mut lines, &mut messages, |messages| {
+        let answered = responses(messages.iter());
+        messages.len() >= expected && requests.iter().all(|id| answered.contains_key(id))
+    })
+    .await?;
+    drop(writer);
+    session.abort();
+
+    let recorded = responses(
+        entries
+            .iter()
+            .filter(|entry| entry.direction == Direction::Server)
+            .map(|entry| &entry.message),
+    );
+    let replayed = responses(messages.iter());
+    let mut differing: Vec<Value> = recorded
+        .iter()
+        .filter(|(id, message)| {
+            replayed
+                .get(*id)
+                .map(|replayed| without_request_id(replayed))
+                != Some(without_request_id(message))
+        })
+        .map(|(_, message)| message["id"].clone())
+        .collect();
+    differing.sort_by_key(|id| id.to_string());
+    Ok(ReplayReport {
+        responses: recorded.len(),
+        differing,
+        messages,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_recording(path: &Path, entries: &[(Direction, Value)]) {
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|(direction, message)| {
+                serde_json::to_string(&Entry {
+                    at: Utc::now().to_rfc3339(),
+                    direction: *direction,
+                    message: message.clone(),
+                })
+                .unwrap()
+            })
+            .collect();
+        std::fs::write(path, lines.join("\n")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_recording_splits_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let (recorder, path) = Recorder::create(dir.path()).unwrap();
+        let mut recording = Recording::new(tokio::io::sink(), recorder, Direction::Server);
+        recording.write_all(b"{\"id\":1}\n{\"id\"").await.unwrap();
+        recording.write_all(b":2}\nnot json\n").await.unwrap();
+
+        let entries: Vec<Entry> = std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let messages: Vec<_> = entries.into_iter().map(|entry| entry.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                serde_json::json!({ "id": 1 }),
+                serde_json::json!({ "id": 2 }),
+                serde_json::json!("not json"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_is_deterministic() {
+        let initialize = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-06-18",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0" },
+            },
+        });
+        let initialized =
+            serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        let call = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "extract_headings", "arguments": { "markdown": "# Install" } },
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+
+        // Only the client's side: the server's answers are what it sends.
+        write_recording(
+            &path,
+            &[
+                (Direction::Client, initialize.clone()),
+                (Direction::Client, initialized.clone()),
+                (Direction::Client, call.clone()),
+            ],
+        );
+        let first = replay(ServerConfig::default(), &path).await.unwrap();
+        let answered = responses(first.messages.iter());
+        assert!(answered.contains_key("1") && answered.contains_key("2"));
+
+        // Replaying a full recording of that session reproduces it.
+        write_recording(
+            &path,
+            &[
+                (Direction::Client, initialize),
+                (Direction::Server, answered["1"].clone()),
+                (Direction::Client, initialized),
+                (Direction::Client, call),
+                (Direction::Server, answered["2"].clone()),
+            ],
+        );
+        let second = replay(ServerConfig::default(), &path).await.unwrap();
+        assert_eq!(second.responses, 2);
+        assert!(second.differing.is_empty(), "{:?}", second.differing);
+    }
+}
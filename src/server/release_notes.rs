@@ -0,0 +1,278 @@
+//! `generate_release_notes`: markdown release notes from Conventional
+//! Commits messages.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use rmcp::{handler::server::wrapper::Parameters, schemars, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    output::{QueryOutput, output_schema},
+};
+
+/// Section title for each commit type, in output order. Types with
+/// `false` are only listed when `all_types` is set.
+const SECTIONS: &[(&str, &str, bool)] = &[
+    ("feat", "Features", true),
+    ("fix", "Bug Fixes", true),
+    ("perf", "Performance Improvements", true),
+    ("revert", "Reverts", true),
+    ("docs", "Documentation", false),
+    ("refactor", "Code Refactoring", false),
+    ("style", "Styles", false),
+    ("test", "Tests", false),
+    ("build", "Build System", false),
+    ("ci", "Continuous Integration", false),
+    ("chore", "Chores", false),
+];
+const BREAKING_SECTION: &str = "⚠ BREAKING CHANGES";
+const OTHER_SECTION: &str = "Other Changes";
+
+static CONVENTIONAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\w+)(?:\(([^)]*)\))?(!)?:\s*(.+)$").unwrap());
+static HASH_PREFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([0-9a-f]{7,40})\s+").unwrap());
+static BREAKING_FOOTER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^BREAKING[ -]CHANGE:\s*(.+)$").unwrap());
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct GenerateReleaseNotesInput {
+    #[schemars(
+        description = "Commit messages, newest first, e.g. from `git log --format=%H%n%B`. A message may start with its commit hash (`abc1234 feat: ...`); the body may hold a `BREAKING CHANGE:` footer"
+    )]
+    pub commits: Vec<String>,
+    #[schemars(description = "Version for the heading (default: `Unreleased`)")]
+    pub version: Option<String>,
+    #[schemars(description = "Release date for the heading, e.g. 2024-01-31")]
+    pub date: Option<String>,
+    #[schemars(
+        description = "Base URL of the repository (e.g. https://github.com/owner/repo); commit hashes link to `<url>/commit/<hash>`"
+    )]
+    pub repository_url: Option<String>,
+    #[schemars(
+        description = "Also list docs, refactor, style, test, build, ci and chore commits, and commits that do not follow Conventional Commits (default: false)"
+    )]
+    pub all_types: Option<bool>,
+    #[schemars(
+        description = "The mq query to execute on the notes (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Commit {
+    hash: Option<String>,
+    kind: Option<String>,
+    scope: Option<String>,
+    description: String,
+    breaking: Option<String>,
+}
+
+fn parse_commit(message: &str) -> Option<Commit> {
+    let message = message.trim();
+    let (hash, message) = match HASH_PREFIX.captures(message) {
+        Some(caps) => (Some(caps[1].to_string()), &message[caps[0].len()..]),
+        None => (None, message),
+    };
+    let subject = message.lines().next()?.trim();
+    if subject.is_empty() {
+        return None;
+    }
+    let footer = BREAKING_FOOTER
+        .captures(message)
+        .map(|caps| caps[1].trim().to_string());
+
+    Some(match CONVENTIONAL.captures(subject) {
+        Some(caps) => {
+            let description = caps[4].trim().to_string();
+            let breaking = footer.or_else(|| caps.get(3).map(|_| description.clone()));
+            Commit {
+                hash,
+                kind: Some(caps[1].to_ascii_lowercase()),
+                scope: caps
+                    .get(2)
+                    .map(|m| m.as_str().trim().to_string())
+                    .filter(|scope| !scope.is_empty()),
+                description,
+                breaking,
+            }
+        }
+        None => Commit {
+            hash,
+            kind: None,
+            scope: None,
+            description: subject.to_string(),
+            breaking: footer,
+        },
+    })
+}
+
+fn item(commit: &Commit, text: &str, repository_url: Option<&str>) -> String {
+    let mut line = match &commit.scope {
+        Some(scope) => format!("- **{scope}:** {text}"),
+        None => format!("- {text}"),
+    };
+    if let Some(hash) = &commit.hash {
+        let short = &hash[..hash.len().min(7)];
+        match repository_url {
+            Some(url) => line.push_str(&format!(
+                " ([{short}]({}/commit/{hash}))",
+                url.trim_end_matches('/')
+            )),
+            None => line.push_str(&format!(" ({short})")),
+        }
+    }
+    line
+}
+
+/// Renders release notes for `commits`, grouped by commit type.
+fn release_notes(
+    commits: &[String],
+    version: &str,
+    date: Option<&str>,
+    repository_url: Option<&str>,
+    all_types: bool,
+) -> String {
+    let commits: Vec<Commit> = commits.iter().filter_map(|m| parse_commit(m)).collect();
+    let mut sections = vec![match date {
+        Some(date) => format!("## {version} ({date})"),
+        None => format!("## {version}"),
+    }];
+    let mut push_section = |title: &str, items: Vec<String>| {
+        if !items.is_empty() {
+            sections.push(format!("### {title}\n\n{}", items.join("\n")));
+        }
+    };
+
+    push_section(
+        BREAKING_SECTION,
+        commits
+            .iter()
+            .filter_map(|c| Some(item(c, c.breaking.as_deref()?, repository_url)))
+            .collect(),
+    );
+    for (kind, title, default) in SECTIONS {
+        if !default && !all_types {
+            continue;
+        }
+        push_section(
+            title,
+            commits
+                .iter()
+                .filter(|c| c.kind.as_deref() == Some(*kind))
+                .map(|c| item(c, &c.description, repository_url))
+                .collect(),
+        );
+    }
+    if all_types {
+        let known = |kind: &str| SECTIONS.iter().any(|(k, _, _)| *k == kind);
+        push_section(
+            OTHER_SECTION,
+            commits
+                .iter()
+                .filter(|c| c.kind.as_deref().is_none_or(|kind| !known(kind)))
+                .map(|c| item(c, &c.description, repository_url))
+                .collect(),
+        );
+    }
+    sections.join("\n\n")
+}
+
+#[tool_router(router = release_notes_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Generate markdown release notes from commit messages following Conventional Commits (`feat(scope)!: ...`). Commits are grouped into Breaking Changes, Features, Bug Fixes, Performance Improvements and Reverts (and other types with `all_types`), then an optional mq query is run on the notes. The layout matches conventional-changelog, so parse_changelog reads it back.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn generate_release_notes(
+        &self,
+        Parameters(input): Parameters<GenerateReleaseNotesInput>,
+    ) -> McpResult {
+        let markdown = release_notes(
+            &input.commits,
+            input.version.as_deref().unwrap_or("Unreleased"),
+            input.date.as_deref(),
+            input.repository_url.as_deref(),
+            input.all_types.unwrap_or_default(),
+        );
+        self.eval_query(&markdown, input.query.as_deref().unwrap_or("identity()"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::changelog::parse_changelog;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        "feat(parser): support tables",
+        Commit { hash: None, kind: Some("feat".into()), scope: Some("parser".into()), description: "support tables".into(), breaking: None }
+    )]
+    #[case(
+        "0123abcd fix!: drop v1 API",
+        Commit { hash: Some("0123abcd".into()), kind: Some("fix".into()), scope: None, description: "drop v1 API".into(), breaking: Some("drop v1 API".into()) }
+    )]
+    #[case(
+        "refactor: split module\n\nBREAKING CHANGE: `Engine::new` takes options",
+        Commit { hash: None, kind: Some("refactor".into()), scope: None, description: "split module".into(), breaking: Some("`Engine::new` takes options".into()) }
+    )]
+    #[case(
+        "Merge pull request #12 from fork/main",
+        Commit { hash: None, kind: None, scope: None, description: "Merge pull request #12 from fork/main".into(), breaking: None }
+    )]
+    fn test_parse_commit(#[case] message: &str, #[case] expected: Commit) {
+        assert_eq!(parse_commit(message), Some(expected));
+    }
+
+    #[test]
+    fn test_release_notes() {
+        let commits = [
+            "abcdef0123 feat(api)!: remove the v1 endpoints".to_string(),
+            "1234567 fix: handle empty input".to_string(),
+            "docs: update README".to_string(),
+            "feat: add --json flag".to_string(),
+        ];
+        let notes = release_notes(
+            &commits,
+            "2.0.0",
+            Some("2024-01-31"),
+            Some("https://github.com/o/r/"),
+            false,
+        );
+        assert_eq!(
+            notes,
+            "\
+## 2.0.0 (2024-01-31)
+
+### ⚠ BREAKING CHANGES
+
+- **api:** remove the v1 endpoints ([abcdef0](https://github.com/o/r/commit/abcdef0123))
+
+### Features
+
+- **api:** remove the v1 endpoints ([abcdef0](https://github.com/o/r/commit/abcdef0123))
+- add --json flag
+
+### Bug Fixes
+
+- handle empty input ([1234567](https://github.com/o/r/commit/1234567))"
+        );
+
+        let releases = parse_changelog(&notes);
+        assert_eq!(releases[0].version, "2.0.0");
+        assert_eq!(releases[0].categories.len(), 3);
+    }
+
+    #[test]
+    fn test_release_notes_all_types() {
+        let commits = ["docs: update README".to_string(), "WIP".to_string()];
+        assert_eq!(
+            release_notes(&commits, "Unreleased", None, None, true),
+            "## Unreleased\n\n### Documentation\n\n- update README\n\n### Other Changes\n\n- WIP"
+        );
+    }
+}
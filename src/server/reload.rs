@@ -0,0 +1,585 @@
+//! `--config` and `--modules`: settings and user-defined mq functions that
+//! can change while the server runs, so long-lived deployments pick up an
+//! edited config file or module without a restart.
+//!
+//! The config file is YAML; its keys override the flags of the same name,
+//! and a key removed from it falls back to the flag again. User modules are
+//! the `.mq` files of the module directory, and the functions they define
+//! can be called from every query. Both are polled for changes and reloaded
+//! for every session at once. When a reload changes the tools listed,
+//! sessions are sent `notifications/tools/list_changed`. A file that doesn't
+//! parse, or a module that doesn't load, is logged and the previous version
+//! kept, so a half-saved edit doesn't take the server down.
+//!
+//! The config file's `profiles` define engine profiles (see
+//! [`profile`](super::profile)), each loading the modules of its own
+//! directory or, without one, the server's.
+//!
+//! The files are polled rather than watched: editors and configuration
+//! management tools replace files by renaming over them, which loses a
+//! watch on the file itself.
+
+#[cfg(not(target_os = "wasi"))]
+use std::time::Duration;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+use rmcp::{ErrorData, RoleServer, service::Peer};
+
+use super::{
+    ServerConfig,
+    engine::{Engine, QueryEngine},
+    policy::Policy,
+    profile::{self, DefaultQuery, Profile, ProfileConfig},
+};
+
+/// How often the config file and the module directory are checked for
+/// changes.
+#[cfg(not(target_os = "wasi"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Extension of user module files.
+const MODULE_EXTENSION: &str = "mq";
+
+/// The settings of the `--config` file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, rmcp::serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConfigFile {
+    /// Directory of user modules, relative to the config file. Overrides
+    /// `--modules`.
+    pub modules: Option<PathBuf>,
+    pub hide_deprecated_tools: Option<bool>,
+    /// Queries tools run when a call gives none, for every profile.
+    #[serde(default)]
+    pub default_queries: Vec<DefaultQuery>,
+    /// Engine profiles by name.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// What queries may do, in every profile.
+    #[serde(default)]
+    pub policy: Policy,
+}
+
+/// A user module: the mq source of one `.mq` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Module {
+    /// File name without the extension.
+    pub name: String,
+    pub source: String,
+}
+
+/// Modification times of the files something was loaded from.
+type Stamps = Vec<(PathBuf, Option<SystemTime>)>;
+
+#[derive(Debug, Default)]
+struct Loaded {
+    config: ConfigFile,
+    /// The profile of calls that don't name one.
+    default: Arc<Profile>,
+    profiles: BTreeMap<String, Arc<Profile>>,
+    stamps: Stamps,
+}
+
+#[derive(Default)]
+struct State {
+    config_path: Option<PathBuf>,
+    /// `--modules`, used when the config file names no directory.
+    modules_dir: Option<PathBuf>,
+    /// `--hide-deprecated-tools`, used when the config file doesn't set it.
+    hide_deprecated_tools: bool,
+    loaded: Loaded,
+    /// Sessions to notify when the tool list changes.
+    peers: Vec<Peer<RoleServer>>,
+    polling: bool,
+}
+
+/// The reloadable settings and modules of a server, shared by its sessions.
+/// Cheap to clone; clones share the state.
+#[derive(Clone, Default)]
+pub(crate) struct Live(Arc<Mutex<State>>);
+
+impl Live {
+    /// Loads the config file and user modules of `config`. Unlike a reload,
+    /// fails if either is invalid.
+    pub(crate) fn open(config: &ServerConfig) -> Result<Self, String> {
+        let state = State {
+            config_path: config.config_path.clone(),
+            modules_dir: config.modules_dir.clone(),
+            hide_deprecated_tools: config.hide_deprecated_tools,
+            ..Default::default()
+        };
+        let loaded = state.load()?;
+        Ok(Self(Arc::new(Mutex::new(State { loaded, ..state }))))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn hide_deprecated_tools(&self) -> bool {
+        self.lock().hides_deprecated_tools()
+    }
+
+    /// The profile `name`, or the default profile.
+    pub(crate) fn profile(&self, name: Option<&str>) -> Result<Arc<Profile>, ErrorData> {
+        let state = self.lock();
+        match name {
+            None | Some(profile::DEFAULT) => Ok(state.loaded.default.clone()),
+            Some(name) => state.loaded.profiles.get(name).cloned().ok_or_else(|| {
+                ErrorData::invalid_params(
+                    "Unknown profile",
+                    Some(serde_json::json!({
+                        "profile": name,
+                        "profiles": state.profile_names(),
+                    })),
+                )
+            }),
+        }
+    }
+
+    /// The names of the profiles a call can pick; none if the config file
+    /// defines no profiles.
+    pub(crate) fn profile_names(&self) -> Vec<String> {
+        self.lock().profile_names()
+    }
+
+    /// Reloads the files if any changed since they were loaded. Returns
+    /// whether the tools listed changed.
+    #[cfg(not(target_os = "wasi"))]
+    fn reload(&self) -> bool {
+        let mut state = self.lock();
+        let stamps = state.stamps(&state.loaded.config);
+        if stamps == state.loaded.stamps {
+            return false;
+        }
+        let hidden = state.hides_deprecated_tools();
+        let names = state.profile_names();
+        match state.load() {
+            Ok(loaded) => {
+                tracing::info!(
+                    "reloaded the config file, {} user modules and {} profiles",
+                    loaded.default.modules.len(),
+                    loaded.profiles.len()
+                );
+                state.loaded = loaded;
+            }
+            Err(e) => {
+                tracing::warn!("keeping the previous config file and user modules: {e}");
+                // Not retried until the files change again.
+                state.loaded.stamps = stamps;
+                return false;
+            }
+        }
+        // The profile names are listed in every tool's input schema.
+        state.hides_deprecated_tools() != hidden || state.profile_names() != names
+    }
+
+    /// Registers the session at `peer` for tool list notifications and, on
+    /// the first call, starts polling the files for changes.
+    #[cfg(not(target_os = "wasi"))]
+    pub(crate) fn watch(&self, peer: Peer<RoleServer>) {
+        let mut state = self.lock();
+        if state.config_path.is_none() && state.modules_dir.is_none() {
+            return;
+        }
+        state.peers.push(peer);
+        if std::mem::replace(&mut state.polling, true) {
+            return;
+        }
+        let live = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                if live.reload() {
+                    live.notify().await;
+                }
+            }
+        });
+    }
+
+    /// Sends `notifications/tools/list_changed` to every session,
+    /// forgetting sessions that are gone.
+    #[cfg(not(target_os = "wasi"))]
+    async fn notify(&self) {
+        let peers = std::mem::take(&mut self.lock().peers);
+        let mut open = Vec::with_capacity(peers.len());
+        for peer in peers {
+            match peer.notify_tool_list_changed().await {
+                Ok(()) => open.push(peer),
+                Err(e) => tracing::debug!("dropping tool list subscriber: {e}"),
+            }
+        }
+        self.lock().peers.extend(open);
+    }
+}
+
+impl State {
+    fn hides_deprecated_tools(&self) -> bool {
+        self.loaded
+            .config
+            .hide_deprecated_tools
+            .unwrap_or(self.hide_deprecated_tools)
+    }
+
+    fn profile_names(&self) -> Vec<String> {
+        let profiles = &self.loaded.config.profiles;
+        if profiles.is_empty() {
+            return Vec::new();
+        }
+        let mut names: Vec<String> = profiles.keys().cloned().collect();
+        if !profiles.contains_key(profile::DEFAULT) {
+            names.insert(0, profile::DEFAULT.to_string());
+        }
+        names
+    }
+
+    /// `dir` from the config file, relative to it.
+    fn config_relative(&self, dir: &Path) -> PathBuf {
+        match &self.config_path {
+            Some(path) => path.parent().unwrap_or(Path::new("")).join(dir),
+            None => dir.to_path_buf(),
+        }
+    }
+
+    /// The module directory `config` selects.
+    fn modules_dir(&self, config: &ConfigFile) -> Option<PathBuf> {
+        match &config.modules {
+            Some(dir) => Some(self.config_relative(dir)),
+            None => self.modules_dir.clone(),
+        }
+    }
+
+    /// The module directory of `profile`.
+    fn profile_modules_dir(&self, config: &ConfigFile, profile: &ProfileConfig) -> Option<PathBuf> {
+        match &profile.modules {
+            Some(dir) => Some(self.config_relative(dir)),
+            None => self.modules_dir(config),
+        }
+    }
+
+    /// Every module directory `config` selects.
+    fn modules_dirs(&self, config: &ConfigFile) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self.modules_dir(config).into_iter().collect();
+        dirs.extend(
+            config
+                .profiles
+                .values()
+                .filter_map(|profile| self.profile_modules_dir(config, profile)),
+        );
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// The files loaded with `config`, with their modification times.
+    fn stamps(&self, config: &ConfigFile) -> Stamps {
+        let mut files: Vec<PathBuf> = self.config_path.iter().cloned().collect();
+        for dir in self.modules_dirs(config) {
+            files.extend(module_files(&dir).unwrap_or_default());
+        }
+        files
+            .into_iter()
+            .map(|path| {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (path, modified)
+            })
+            .collect()
+    }
+
+    fn load(&self) -> Result<Loaded, String> {
+        let config = match &self.config_path {
+            Some(path) => read_config(path)?,
+            None => ConfigFile::default(),
+        };
+        // Taken before the modules are read, so an edit made while reading
+        // them is noticed by the next poll.
+        let stamps = self.stamps(&config);
+        let mut modules = BTreeMap::new();
+        for dir in self.modules_dirs(&config) {
+            let read: Arc<[Module]> = read_modules(&dir)?.into();
+            modules.insert(dir, read);
+        }
+        let policy = Arc::new(config.policy.clone());
+        let load = |profile: &ProfileConfig| {
+            let profile = Profile {
+                modules: self
+                    .profile_modules_dir(&config, profile)
+                    .and_then(|dir| modules.get(&dir).cloned())
+                    .unwrap_or_default(),
+                limits: profile.limits(),
+                // The profile's own rules take precedence.
+                default_queries: profile
+                    .default_queries
+                    .iter()
+                    .chain(&config.default_queries)
+                    .cloned()
+                    .collect(),
+                policy: policy.clone(),
+            };
+            check_default_queries(&profile)?;
+            Ok::<_, String>(Arc::new(profile))
+        };
+        let default = load(
+            config
+                .profiles
+                .get(profile::DEFAULT)
+                .unwrap_or(&ProfileConfig::default()),
+        )?;
+        let profiles = config
+            .profiles
+            .iter()
+            .map(|(name, profile)| {
+                load(profile)
+                    .map(|loaded| (name.clone(), loaded))
+                    .map_err(|e| format!("profile {name}: {e}"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Loaded {
+            config,
+            default,
+            profiles,
+            stamps,
+        })
+    }
+}
+
+fn read_config(path: &Path) -> Result<ConfigFile, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read config file {}: {e}", path.display()))?;
+    if text.trim().is_empty() {
+        return Ok(ConfigFile::default());
+    }
+    serde_yaml::from_str(&text).map_err(|e| format!("invalid config file {}: {e}", path.display()))
+}
+
+/// Checks that the default queries of `profile` compile and that its
+/// policy allows them.
+fn check_default_queries(profile: &Profile) -> Result<(), String> {
+    let mut engine = Engine::new();
+    engine.set_policy(profile.policy.clone());
+    for rule in profile.default_queries.iter() {
+        engine
+            .compile(&rule.query)
+            .map_err(|e| format!("default query for {} doesn't compile: {e}", rule.tool))?;
+    }
+    Ok(())
+}
+
+/// The module files in `dir`, in name order.
+fn module_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == MODULE_EXTENSION) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Reads the modules in `dir`, checking that each loads.
+fn read_modules(dir: &Path) -> Result<Vec<Module>, String> {
+    let files = module_files(dir)
+        .map_err(|e| format!("failed to read module directory {}: {e}", dir.display()))?;
+    files
+        .into_iter()
+        .map(|path| {
+            let source = fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read module {}: {e}", path.display()))?;
+            Engine::new()
+                .load(&source)
+                .map_err(|e| format!("module {} doesn't load: {e}", path.display()))?;
+            Ok(Module {
+                name: path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                source,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::engine::Optimization;
+
+    /// Rewrites `path`, dating it `secs` seconds from now so the change is
+    /// seen even on file systems with coarse modification times.
+    fn touch(path: &Path, contents: &str, secs: u64) {
+        fs::write(path, contents).unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(secs);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+    }
+
+    fn open(dir: &Path) -> Live {
+        Live::open(&ServerConfig {
+            config_path: Some(dir.join("mq-mcp.yaml")),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    /// The modules of calls that don't name a profile.
+    fn modules(live: &Live) -> Arc<[Module]> {
+        live.profile(None).unwrap().modules.clone()
+    }
+
+    #[test]
+    fn test_open_reads_config_and_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("modules")).unwrap();
+        fs::write(
+            dir.path().join("mq-mcp.yaml"),
+            "modules: modules\nhide_deprecated_tools: true\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("modules/shout.mq"),
+            "def shout(): upcase() | add(\"!\");",
+        )
+        .unwrap();
+        fs::write(dir.path().join("modules/notes.txt"), "not a module").unwrap();
+
+        let live = open(dir.path());
+        assert!(live.hide_deprecated_tools());
+        assert!(live.profile_names().is_empty());
+        let modules = modules(&live);
+        assert_eq!(modules.len(), 1);
+        assert_eq!(modules[0].name, "shout");
+    }
+
+    #[test]
+    fn test_open_rejects_invalid_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("mq-mcp.yaml"), "unknown_key: 1\n").unwrap();
+        assert!(
+            Live::open(&ServerConfig {
+                config_path: Some(dir.path().join("mq-mcp.yaml")),
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[cfg(not(target_os = "wasi"))]
+    #[test]
+    fn test_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("mq-mcp.yaml");
+        let module = dir.path().join("shout.mq");
+        fs::write(&config, "modules: .\n").unwrap();
+        fs::write(&module, "def shout(): upcase();").unwrap();
+        let live = open(dir.path());
+        assert!(!live.reload());
+
+        touch(&config, "modules: .\nhide_deprecated_tools: true\n", 10);
+        assert!(live.reload());
+        assert!(live.hide_deprecated_tools());
+
+        // A module that doesn't load leaves the previous version in place.
+        touch(&module, "def shout(: upcase();", 10);
+        assert!(!live.reload());
+        assert_eq!(modules(&live)[0].source, "def shout(): upcase();");
+
+        touch(&module, "def shout(): upcase() | add(\"!\");", 20);
+        live.reload();
+        assert!(modules(&live)[0].source.contains("add"));
+    }
+
+    #[test]
+    fn test_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("strict")).unwrap();
+        fs::write(
+            dir.path().join("mq-mcp.yaml"),
+            "modules: .\nprofiles:\n  strict:\n    modules: strict\n    timeout_ms: 500\n  loose:\n    optimization: full\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("shout.mq"), "def shout(): upcase();").unwrap();
+        fs::write(
+            dir.path().join("strict/quiet.mq"),
+            "def quiet(): downcase();",
+        )
+        .unwrap();
+
+        let live = open(dir.path());
+        assert_eq!(live.profile_names(), ["default", "loose", "strict"]);
+        let strict = live.profile(Some("strict")).unwrap();
+        assert_eq!(strict.modules[0].name, "quiet");
+        assert_eq!(
+            strict.limits.timeout,
+            Some(std::time::Duration::from_millis(500))
+        );
+        let loose = live.profile(Some("loose")).unwrap();
+        assert_eq!(loose.modules[0].name, "shout");
+        assert_eq!(loose.limits.optimization, Optimization::Full);
+        assert_eq!(
+            live.profile(Some("default")).unwrap().modules[0].name,
+            "shout"
+        );
+        assert!(live.profile(Some("unknown")).is_err());
+    }
+
+    #[test]
+    fn test_default_queries() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("mq-mcp.yaml");
+        fs::write(
+            &config,
+            "default_queries:\n  - tool: html_to_markdown\n    query: .h\nprofiles:\n  strict:\n    default_queries:\n      - tool: html_to_markdown\n        query: .h1\n",
+        )
+        .unwrap();
+        let live = open(dir.path());
+        let queries = |profile: Option<&str>| -> Vec<String> {
+            let profile = live.profile(profile).unwrap();
+            profile
+                .default_queries
+                .iter()
+                .map(|rule| rule.query.clone())
+                .collect()
+        };
+        assert_eq!(queries(None), [".h"]);
+        assert_eq!(queries(Some("strict")), [".h1", ".h"]);
+
+        // Default queries must be allowed by the policy.
+        fs::write(
+            &config,
+            "default_queries:\n  - tool: html_to_markdown\n    query: .h | upcase()\npolicy:\n  deny_functions: [upcase]\n",
+        )
+        .unwrap();
+        assert!(
+            Live::open(&ServerConfig {
+                config_path: Some(config.clone()),
+                ..Default::default()
+            })
+            .is_err()
+        );
+
+        fs::write(
+            &config,
+            "default_queries:\n  - tool: html_to_markdown\n    query: .h | upcase(\n",
+        )
+        .unwrap();
+        assert!(
+            Live::open(&ServerConfig {
+                config_path: Some(config),
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! `compose_report`: assembles a markdown report from the results of
+//! earlier calls (offloaded results, session variables, the scratchpad or
+//! inline markdown) following a layout of titled sections, with a table of
+//! contents and a footer citing where each section came from. Report-writing
+//! agents get the whole document back from one call instead of stitching it
+//! together in the prompt.
+//!
+//! Headings inside a section's content are nested under the section's
+//! heading, and the table of contents links to the anchors GitHub generates
+//! for the headings.
+
+use std::collections::HashMap;
+
+use mq_markdown::Node;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    output::{json_result, output_schema},
+    parse_markdown_only,
+};
+
+/// Maximum number of sections in a report.
+const MAX_SECTIONS: usize = 64;
+
+/// Depth of the section headings, under the report's title.
+const SECTION_DEPTH: u8 = 2;
+
+const TOC_HEADING: &str = "Contents";
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ReportSection {
+    #[schemars(description = "The section's heading")]
+    pub heading: String,
+    #[schemars(description = "Markdown to put under the heading, before the content")]
+    pub intro: Option<String>,
+    #[schemars(description = "The content as markdown; use either this, `variable` or `result`")]
+    pub markdown: Option<String>,
+    #[schemars(description = "Name of a session variable whose stored results are the content")]
+    pub variable: Option<String>,
+    #[schemars(
+        description = "URI of an offloaded tool result (`mq://results/...`) or `mq://scratchpad` whose text is the content"
+    )]
+    pub result: Option<String>,
+    #[schemars(
+        description = "An mq query run on the content: only its non-empty results go in the section"
+    )]
+    pub query: Option<String>,
+    #[schemars(
+        description = "Where the content came from, cited in the footer (e.g. a URL). Defaults to the result URI or the variable"
+    )]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ComposeReportInput {
+    #[schemars(description = "The report's level-1 title")]
+    pub title: Option<String>,
+    #[schemars(description = "Markdown to put after the title, before the contents")]
+    pub intro: Option<String>,
+    #[schemars(description = "The report's sections in order (at most 64)")]
+    pub sections: Vec<ReportSection>,
+    #[schemars(description = "Include a table of contents linking the sections (default: true)")]
+    pub toc: Option<bool>,
+    #[schemars(
+        description = "End each section with a footer citing its source and query (default: true)"
+    )]
+    pub footers: Option<bool>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ComposedSection {
+    pub heading: String,
+    #[schemars(description = "The heading's anchor, e.g. `#usage`")]
+    pub anchor: String,
+    #[schemars(description = "The cited source, if any")]
+    pub source: Option<String>,
+    #[schemars(description = "Number of values the section's content was made of")]
+    pub values: usize,
+}
+
+/// Output of `compose_report`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ComposeReportOutput {
+    #[schemars(description = "The report")]
+    pub markdown: String,
+    pub sections: Vec<ComposedSection>,
+}
+
+/// Generates heading anchors the way GitHub does: lowercased, punctuation
+/// dropped, spaces as `-`, and `-1`, `-2`, ... appended to repeated ones.
+#[derive(Debug, Default)]
+struct Anchors(HashMap<String, usize>);
+
+impl Anchors {
+    fn next(&mut self, heading: &str) -> String {
+        let slug: String = heading
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter_map(|c| match c {
+                ' ' => Some('-'),
+                c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+                _ => None,
+            })
+            .collect();
+        let count = self.0.entry(slug.clone()).or_default();
+        let anchor = match *count {
+            0 => format!("#{slug}"),
+            n => format!("#{slug}-{n}"),
+        };
+        *count += 1;
+        anchor
+    }
+}
+
+/// Nests the headings of `markdown` under a heading of `depth`: they are
+/// moved down so that the shallowest is one level deeper (at most 6), and
+/// setext headings become ATX ones. Returns the markdown and the text of its
+/// headings in order.
+fn nest(markdown: &str, depth: u8) -> Result<(String, Vec<String>), ErrorData> {
+    let headings: Vec<(usize, usize, u8, String)> = parse_markdown_only(markdown)?
+        .iter()
+        .filter_map(|node| match node {
+            Node::Heading(heading) => heading.position.as_ref().map(|position| {
+                (
+                    position.start.line,
+                    position.end.line,
+                    heading.depth,
+                    node.value().trim().to_string(),
+                )
+            }),
+            _ => None,
+        })
+        .collect();
+    let Some(shallowest) = headings.iter().map(|(_, _, depth, _)| *depth).min() else {
+        return Ok((markdown.to_string(), Vec::new()));
+    };
+    let shift = (depth + 1).saturating_sub(shallowest);
+
+    let lines: Vec<&str> = markdown.lines().collect();
+    let mut nested = Vec::with_capacity(lines.len());
+    let mut next = 0;
+    for (start, end, heading_depth, _) in &headings {
+        let (start, end) = (start - 1, (end - 1).min(lines.len() - 1));
+        nested.extend(lines[next..start].iter().map(|line| line.to_string()));
+        let marker = "#".repeat((heading_depth + shift).min(6) as usize);
+        let setext = end > start
+            && !lines[end].trim().is_empty()
+            && lines[end].trim().chars().all(|c| c == '=' || c == '-');
+        let text = if setext {
+            lines[start..end]
+                .iter()
+                .map(|line| line.trim())
+                .collect::<Vec<_>>()
+                .join(" ")
+        } else {
+            lines[start..=end]
+                .join("\n")
+                .trim_start()
+                .trim_start_matches('#')
+                .trim()
+                .to_string()
+        };
+        nested.push(format!("{marker} {text}").trim_end().to_string());
+        next = end + 1;
+    }
+    nested.extend(lines[next..].iter().map(|line| line.to_string()));
+    Ok((
+        nested.join("\n"),
+        headings.into_iter().map(|(.., text)| text).collect(),
+    ))
+}
+
+impl Server {
+    fn compose_report_markdown(
+        &self,
+        input: ComposeReportInput,
+    ) -> Result<ComposeReportOutput, ErrorData> {
+        if input.sections.len() > MAX_SECTIONS {
+            return Err(ErrorData::invalid_params(
+                "Too many report sections",
+                Some(serde_json::json!({
+                    "count": input.sections.len(),
+                    "limit": MAX_SECTIONS,
+                })),
+            ));
+        }
+        let toc = input.toc.unwrap_or(true) && !input.sections.is_empty();
+        let footers = input.footers.unwrap_or(true);
+        let mut anchors = Anchors::default();
+        let mut blocks = Vec::new();
+        if let Some(title) = &input.title {
+            anchors.next(title);
+            blocks.push(format!("# {}", title.trim()));
+        }
+        if let Some(intro) = &input.intro {
+            blocks.push(intro.trim_matches('\n').to_string());
+        }
+        if toc {
+            anchors.next(TOC_HEADING);
+        }
+
+        let mut sections = Vec::with_capacity(input.sections.len());
+        let mut body = Vec::new();
+        for (i, section) in input.sections.into_iter().enumerate() {
+            let source = section
+                .source
+                .or_else(|| match (&section.variable, &section.result) {
+                    (Some(name), None) => Some(format!("session variable `{name}`")),
+                    (None, Some(uri)) => Some(uri.clone()),
+                    _ => None,
+                });
+            let values = self
+                .content_values(
+                    section.markdown,
+                    section.query.as_deref(),
+                    section.variable.as_deref(),
+                    section.result.as_deref(),
+                )
+                .map_err(|mut e| {
+                    let mut data = serde_json::json!({ "section": i });
+                    if let (Some(data), Some(serde_json::Value::Object(details))) =
+                        (data.as_object_mut(), e.data.take())
+                    {
+                        data.extend(details);
+                    }
+                    e.data = Some(data);
+                    e
+                })?;
+            let heading = section.heading.trim().to_string();
+            let anchor = anchors.next(&heading);
+            body.push(format!("{} {heading}", "#".repeat(SECTION_DEPTH as usize)));
+            if let Some(intro) = &section.intro {
+                body.push(intro.trim_matches('\n').to_string());
+            }
+            for value in &values {
+                let value = value.trim_matches('\n');
+                if value.is_empty() {
+                    continue;
+                }
+                let (value, headings) = nest(value, SECTION_DEPTH)?;
+                for heading in &headings {
+                    anchors.next(heading);
+                }
+                body.push(value);
+            }
+            if footers && let Some(source) = &source {
+                body.push(match &section.query {
+                    Some(query) => format!("[Source: {source} | Query: `{query}`]"),
+                    None => format!("[Source: {source}]"),
+                });
+            }
+            sections.push(ComposedSection {
+                heading,
+                anchor,
+                source,
+                values: values.len(),
+            });
+        }
+
+        if toc {
+            let mut contents = format!("{} {TOC_HEADING}\n", "#".repeat(SECTION_DEPTH as usize));
+            for section in &sections {
+                contents.push_str(&format!("\n- [{}]({})", section.heading, section.anchor));
+            }
+            blocks.push(contents);
+        }
+        blocks.extend(body);
+        let mut markdown = blocks.join("\n\n");
+        markdown.push('\n');
+        Ok(ComposeReportOutput { markdown, sections })
+    }
+}
+
+#[tool_router(router = report_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Assemble one markdown report from the results of earlier calls: offloaded results (mq://results/...), session variables, the scratchpad or inline markdown. Each section of the layout gets a heading, optional intro text, its content (optionally narrowed with an mq query, headings nested under the section) and a footer citing its source; the report starts with an optional title and a table of contents.",
+        output_schema = output_schema::<ComposeReportOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn compose_report(&self, Parameters(input): Parameters<ComposeReportInput>) -> McpResult {
+        json_result(&self.compose_report_markdown(input)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn compose(server: &Server, input: serde_json::Value) -> Result<serde_json::Value, ErrorData> {
+        server
+            .compose_report(Parameters(serde_json::from_value(input).unwrap()))
+            .map(|result| result.structured_content.unwrap())
+    }
+
+    #[test]
+    fn test_compose_report() {
+        let server = Server::new(None).unwrap();
+        let uri = server
+            .results
+            .insert("# Install\n\nRun `make`.\n\n## From source\n\nClone it.".to_string());
+        let output = compose(
+            &server,
+            serde_json::json!({
+                "title": "Report",
+                "sections": [
+                    { "heading": "Install", "result": uri },
+                    {
+                        "heading": "Usage",
+                        "intro": "The commands:",
+                        "markdown": "# Usage\n\n- run\n\n# Install\n\nignored",
+                        "query": ".list",
+                        "source": "https://example.com/guide.md",
+                    },
+                    { "heading": "Notes", "markdown": "Nothing else." },
+                ],
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            output["markdown"],
+            format!(
+                "# Report\n\n## Contents\n\n- [Install](#install)\n- [Usage](#usage)\n- [Notes](#notes)\n\n\
+                 ## Install\n\n### Install\n\nRun `make`.\n\n#### From source\n\nClone it.\n\n[Source: {uri}]\n\n\
+                 ## Usage\n\nThe commands:\n\n- run\n\n[Source: https://example.com/guide.md | Query: `.list`]\n\n\
+                 ## Notes\n\nNothing else.\n"
+            )
+        );
+        assert_eq!(output["sections"][0]["anchor"], "#install");
+        assert_eq!(output["sections"][1]["values"], 1);
+    }
+
+    #[test]
+    fn test_compose_report_without_toc_and_footers() {
+        let server = Server::new(None).unwrap();
+        let output = compose(
+            &server,
+            serde_json::json!({
+                "sections": [{ "heading": "Only", "markdown": "Text.", "source": "notes.md" }],
+                "toc": false,
+                "footers": false,
+            }),
+        )
+        .unwrap();
+        assert_eq!(output["markdown"], "## Only\n\nText.\n");
+        assert_eq!(output["sections"][0]["source"], "notes.md");
+    }
+
+    #[test]
+    fn test_compose_report_names_the_failing_section() {
+        let server = Server::new(None).unwrap();
+        let error = compose(
+            &server,
+            serde_json::json!({
+                "sections": [
+                    { "heading": "Ok", "markdown": "Text." },
+                    { "heading": "Gone", "result": "mq://results/9" },
+                ],
+            }),
+        )
+        .unwrap_err();
+        assert_eq!(error.message, "Stored result not found");
+        assert_eq!(error.data.unwrap()["section"], 1);
+    }
+
+    #[rstest]
+    #[case("Text.", "Text.")]
+    #[case("# A\n\ntext\n\n## B", "### A\n\ntext\n\n#### B")]
+    #[case("### A\n\n#### B", "### A\n\n#### B")]
+    #[case("Title\n=====\n\nSub\n---", "### Title\n\n#### Sub")]
+    #[case(
+        "# A\n\n```md\n# not a heading\n```",
+        "### A\n\n```md\n# not a heading\n```"
+    )]
+    #[case("# A\n\n###### F", "### A\n\n###### F")]
+    fn test_nest(#[case] markdown: &str, #[case] expected: &str) {
+        assert_eq!(nest(markdown, SECTION_DEPTH).unwrap().0, expected);
+    }
+
+    #[test]
+    fn test_anchors() {
+        let mut anchors = Anchors::default();
+        assert_eq!(anchors.next("Hello, World!"), "#hello-world");
+        assert_eq!(anchors.next("hello world"), "#hello-world-1");
+        assert_eq!(anchors.next("Set up `mq`"), "#set-up-mq");
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use rmcp::model::{ContentBlock, Resource};
+
+/// URI prefix for tool results that were offloaded to the result store.
+pub(crate) const RESULT_URI_PREFIX: &str = "mq://results/";
+
+/// Maximum number of offloaded results retained per session. The oldest are
+/// evicted first, after which reading their URI reports "not found".
+const MAX_STORED_RESULTS: usize = 64;
+
+/// Maximum number of strings of one result's structured content that are
+/// offloaded, well under [`MAX_STORED_RESULTS`] so a result never evicts its
+/// own links. Oversized strings past it stay inline.
+const MAX_OFFLOADED_STRINGS: usize = 16;
+
+#[derive(Debug, Default)]
+struct StoredResults {
+    next_id: u64,
+    entries: VecDeque<(u64, String)>,
+}
+
+/// Per-session store of large tool results, exposed to clients as MCP
+/// resources so they can be read lazily instead of inlined into the
+/// `tools/call` response.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ResultStore(Arc<Mutex<StoredResults>>);
+
+impl ResultStore {
+    /// Stores `text` and returns the URI it can be read back from. Text that
+    /// is already stored keeps its URI, and becomes the newest entry again.
+    pub(crate) fn insert(&self, text: String) -> String {
+        let mut inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = inner.entries.iter().position(|(_, stored)| *stored == text) {
+            let entry = inner.entries.remove(index).expect("index is in bounds");
+            let id = entry.0;
+            inner.entries.push_back(entry);
+            return format!("{RESULT_URI_PREFIX}{id}");
+        }
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.entries.push_back((id, text));
+        while inner.entries.len() > MAX_STORED_RESULTS {
+            inner.entries.pop_front();
+        }
+        format!("{RESULT_URI_PREFIX}{id}")
+    }
+
+    pub(crate) fn get(&self, uri: &str) -> Option<String> {
+        let id: u64 = uri.strip_prefix(RESULT_URI_PREFIX)?.parse().ok()?;
+        let inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        inner
+            .entries
+            .iter()
+            .find(|(entry_id, _)| *entry_id == id)
+            .map(|(_, text)| text.clone())
+    }
+
+    pub(crate) fn list(&self) -> Vec<Resource> {
+        let inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        inner
+            .entries
+            .iter()
+            .map(|(id, text)| result_resource(&format!("{RESULT_URI_PREFIX}{id}"), text.len()))
+            .collect()
+    }
+
+    /// Replaces every text block larger than `threshold` bytes with a link to
+    /// a stored copy of it. Smaller blocks and non-text content pass through.
+    ///
+    /// Clients on protocol revisions without `resource_link` content
+    /// (`links == false`) get a text block naming the URI instead.
+    pub(crate) fn offload(
+        &self,
+        content: Vec<ContentBlock>,
+        threshold: usize,
+        links: bool,
+    ) -> Vec<ContentBlock> {
+        content
+            .into_iter()
+            .map(|block| match block.as_text() {
+                Some(text) if text.text.len() > threshold => {
+                    let len = text.text.len();
+                    let uri = self.insert(text.text.clone());
+                    if links {
+                        ContentBlock::resource_link(result_resource(&uri, len))
+                    } else {
+                        ContentBlock::text(format!(
+                            "Result too large to inline ({len} bytes); read {uri} with resources/read"
+                        ))
+                    }
+                }
+                _ => block,
+            })
+            .collect()
+    }
+
+    /// Replaces strings in `value` longer than `threshold` bytes with an
+    /// [`offloaded_marker`] naming a stored copy, so structured content keeps
+    /// to the tool's output schema without carrying offloaded text inline.
+    /// At most [`MAX_OFFLOADED_STRINGS`] strings are replaced; text also in
+    /// an offloaded content block shares its stored copy.
+    pub(crate) fn offload_strings(&self, value: &mut serde_json::Value, threshold: usize) {
+        let mut remaining = MAX_OFFLOADED_STRINGS;
+        self.offload_strings_up_to(value, threshold, &mut remaining);
+    }
+
+    fn offload_strings_up_to(
+        &self,
+        value: &mut serde_json::Value,
+        threshold: usize,
+        remaining: &mut usize,
+    ) {
+        match value {
+            serde_json::Value::String(text) if text.len() > threshold && *remaining > 0 => {
+                *remaining -= 1;
+                let len = text.len();
+                let uri = self.insert(std::mem::take(text));
+                *text = offloaded_marker(&uri, len);
+            }
+            serde_json::Value::Array(values) => {
+                for value in values {
+                    self.offload_strings_up_to(value, threshold, remaining);
+                }
+            }
+            serde_json::Value::Object(fields) => {
+                for value in fields.values_mut() {
+                    self.offload_strings_up_to(value, threshold, remaining);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// What an offloaded string of structured content is replaced with, telling
+/// it apart from data that happens to be an `mq://results/` URI.
+pub(crate) fn offloaded_marker(uri: &str, len: usize) -> String {
+    format!("[offloaded {len} bytes: {uri}]")
+}
+
+fn result_resource(uri: &str, len: usize) -> Resource {
+    Resource::new(uri, uri.trim_start_matches(RESULT_URI_PREFIX))
+        .with_description(format!(
+            "Tool result too large to inline ({len} bytes); read it with resources/read"
+        ))
+        .with_mime_type("text/markdown")
+        .with_size(len as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offload_replaces_only_large_text() {
+        let store = ResultStore::default();
+        let content = store.offload(
+            vec![
+                ContentBlock::text("small"),
+                ContentBlock::text("x".repeat(100)),
+            ],
+            10,
+            true,
+        );
+
+        assert_eq!(content[0].as_text().map(|t| t.text.as_str()), Some("small"));
+        let link = content[1]
+            .as_resource_link()
+            .expect("expected a resource link");
+        assert_eq!(link.size, Some(100));
+        assert_eq!(store.get(&link.uri), Some("x".repeat(100)));
+    }
+
+    #[test]
+    fn test_offload_strings() {
+        let store = ResultStore::default();
+        let mut value = serde_json::json!({
+            "results": ["small", "x".repeat(100)],
+            "count": 2,
+        });
+        store.offload_strings(&mut value, 10);
+
+        assert_eq!(value["results"][0], "small");
+        assert_eq!(value["results"][1], offloaded_marker("mq://results/1", 100));
+        assert_eq!(store.get("mq://results/1"), Some("x".repeat(100)));
+        assert_eq!(value["count"], 2);
+    }
+
+    #[test]
+    fn test_offload_strings_keeps_own_links() {
+        let store = ResultStore::default();
+        let strings: Vec<String> = (0..MAX_STORED_RESULTS + 1)
+            .map(|i| format!("{i:0>100}"))
+            .collect();
+        let mut value = serde_json::json!({ "results": strings });
+        store.offload_strings(&mut value, 10);
+
+        let results = value["results"].as_array().unwrap();
+        let offloaded = &results[..MAX_OFFLOADED_STRINGS];
+        for (i, marker) in offloaded.iter().enumerate() {
+            let uri = format!("{RESULT_URI_PREFIX}{}", i + 1);
+            assert_eq!(marker, &offloaded_marker(&uri, 100));
+            assert_eq!(store.get(&uri).as_ref(), Some(&strings[i]));
+        }
+        assert_eq!(
+            results[MAX_OFFLOADED_STRINGS],
+            strings[MAX_OFFLOADED_STRINGS]
+        );
+    }
+
+    #[test]
+    fn test_insert_stores_text_once() {
+        let store = ResultStore::default();
+        let first = store.insert("text".to_string());
+        store.insert("other".to_string());
+
+        assert_eq!(store.insert("text".to_string()), first);
+        assert_eq!(store.list().len(), 2);
+    }
+
+    #[test]
+    fn test_offload_without_links_names_uri() {
+        let store = ResultStore::default();
+        let content = store.offload(vec![ContentBlock::text("x".repeat(100))], 10, false);
+
+        let text = content[0].as_text().expect("expected a text block");
+        assert!(text.text.contains("mq://results/1"));
+        assert_eq!(store.get("mq://results/1"), Some("x".repeat(100)));
+    }
+
+    #[test]
+    fn test_store_evicts_oldest() {
+        let store = ResultStore::default();
+        let first = store.insert("first".to_string());
+        for i in 0..MAX_STORED_RESULTS {
+            store.insert(format!("later {i}"));
+        }
+
+        assert_eq!(store.get(&first), None);
+        assert_eq!(store.list().len(), MAX_STORED_RESULTS);
+    }
+
+    #[test]
+    fn test_get_rejects_foreign_uri() {
+        let store = ResultStore::default();
+        store.insert("text".to_string());
+        assert_eq!(store.get("file:///etc/passwd"), None);
+    }
+}
@@ -0,0 +1,265 @@
+//! robots.txt (RFC 9309) and `Crawl-delay` compliance for the URL-based
+//! tools. Rules are fetched once per origin and cached; requests to a host
+//! are spaced out by its crawl delay.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The product token matched against `User-agent` lines.
+pub(crate) const USER_AGENT_TOKEN: &str = "mq-mcp";
+/// Parsing stops after this much of a robots.txt, as RFC 9309 allows.
+const MAX_ROBOTS_BYTES: usize = 500 * 1024;
+/// Crawl delays longer than this are clamped.
+const MAX_CRAWL_DELAY: Duration = Duration::from_secs(30);
+/// How long fetched rules are reused.
+pub(crate) const ROBOTS_TTL: Duration = Duration::from_secs(60 * 60);
+/// How long an unreachable robots.txt (which disallows everything) is
+/// remembered before it is tried again.
+pub(crate) const UNREACHABLE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+/// The rules of a robots.txt that apply to this server.
+#[derive(Debug, Default)]
+pub(crate) struct Robots {
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    pub(crate) fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Used when robots.txt could not be fetched because of a server or
+    /// network error.
+    pub(crate) fn disallow_all() -> Self {
+        Self {
+            rules: vec![Rule {
+                allow: false,
+                pattern: "/".to_string(),
+            }],
+            crawl_delay: None,
+        }
+    }
+
+    /// Parses `text`, keeping the group for `agent` or, if there is none,
+    /// the `*` group.
+    pub(crate) fn parse(text: &str, agent: &str) -> Self {
+        let mut end = text.len().min(MAX_ROBOTS_BYTES);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let text = &text[..end];
+        let mut specific = Robots::default();
+        let mut wildcard = Robots::default();
+        let mut found_specific = false;
+        // Agents of the group being read, and whether its rules started.
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_ascii_lowercase());
+                }
+                key @ ("allow" | "disallow" | "crawl-delay") => {
+                    in_rules = true;
+                    let is_specific = agents.iter().any(|a| a == agent);
+                    found_specific |= is_specific;
+                    let targets = [
+                        is_specific.then_some(&mut specific),
+                        agents.iter().any(|a| a == "*").then_some(&mut wildcard),
+                    ];
+                    for robots in targets.into_iter().flatten() {
+                        match key {
+                            "crawl-delay" => {
+                                robots.crawl_delay = parse_crawl_delay(value);
+                            }
+                            // An empty `Disallow:` allows everything.
+                            _ if value.is_empty() => {}
+                            _ => robots.rules.push(Rule {
+                                allow: key == "allow",
+                                pattern: value.to_string(),
+                            }),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if found_specific { specific } else { wildcard }
+    }
+
+    /// Whether `path` (path and query of a URL) may be fetched: the longest
+    /// matching rule wins, and `Allow` wins a tie.
+    pub(crate) fn is_allowed(&self, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+        self.rules
+            .iter()
+            .filter(|rule| matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    pub(crate) fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+}
+
+fn parse_crawl_delay(value: &str) -> Option<Duration> {
+    let secs = value.parse::<f64>().ok()?;
+    (secs.is_finite() && secs >= 0.0).then(|| Duration::from_secs_f64(secs).min(MAX_CRAWL_DELAY))
+}
+
+/// Matches a robots.txt path pattern, where `*` matches any sequence and a
+/// trailing `$` anchors the end.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    if parts.is_empty() {
+        return !anchored || rest.is_empty();
+    }
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Cached rules per origin and the next permitted request time per host,
+/// shared by every session of a server process.
+#[derive(Debug, Default)]
+pub(crate) struct Politeness {
+    robots: HashMap<String, (Arc<Robots>, Instant)>,
+    next_request: HashMap<String, Instant>,
+}
+
+impl Politeness {
+    pub(crate) fn cached(&self, origin: &str, now: Instant) -> Option<Arc<Robots>> {
+        self.robots
+            .get(origin)
+            .filter(|(_, expires)| now < *expires)
+            .map(|(robots, _)| robots.clone())
+    }
+
+    pub(crate) fn store(
+        &mut self,
+        origin: String,
+        robots: Robots,
+        ttl: Duration,
+        now: Instant,
+    ) -> Arc<Robots> {
+        let robots = Arc::new(robots);
+        self.robots.insert(origin, (robots.clone(), now + ttl));
+        robots
+    }
+
+    /// Reserves the next request slot for `host`, returning how long to wait
+    /// before sending it.
+    pub(crate) fn reserve(&mut self, host: &str, delay: Duration, now: Instant) -> Duration {
+        let slot = self
+            .next_request
+            .get(host)
+            .map_or(now, |next| (*next).max(now));
+        self.next_request.insert(host.to_string(), slot + delay);
+        slot - now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const ROBOTS: &str = "\
+# comment
+User-agent: *
+Disallow: /private/
+Allow: /private/public$
+Crawl-delay: 2
+
+User-agent: GoogleBot
+User-agent: mq-mcp
+Disallow: /search
+Allow: /search/about
+Disallow: /*.pdf$
+";
+
+    #[rstest]
+    #[case("/", true)]
+    #[case("/search?q=mq", false)]
+    #[case("/search/about", true)]
+    #[case("/docs/manual.pdf", false)]
+    #[case("/docs/manual.pdf?download", true)]
+    #[case("/private/x", true)]
+    #[case("/robots.txt", true)]
+    fn test_specific_group(#[case] path: &str, #[case] expected: bool) {
+        let robots = Robots::parse(ROBOTS, USER_AGENT_TOKEN);
+        assert_eq!(robots.is_allowed(path), expected);
+        assert_eq!(robots.crawl_delay(), None);
+    }
+
+    #[rstest]
+    #[case("/private/x", false)]
+    #[case("/private/public", true)]
+    #[case("/private/public/x", false)]
+    #[case("/search", true)]
+    fn test_wildcard_group(#[case] path: &str, #[case] expected: bool) {
+        let robots = Robots::parse(ROBOTS, "otherbot");
+        assert_eq!(robots.is_allowed(path), expected);
+        assert_eq!(robots.crawl_delay(), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_empty_disallow_and_disallow_all() {
+        assert!(Robots::parse("User-agent: *\nDisallow:\n", USER_AGENT_TOKEN).is_allowed("/a"));
+        assert!(!Robots::disallow_all().is_allowed("/a"));
+        assert!(Robots::allow_all().is_allowed("/a"));
+    }
+
+    #[test]
+    fn test_reserve_spaces_out_requests() {
+        let mut politeness = Politeness::default();
+        let now = Instant::now();
+        let delay = Duration::from_secs(2);
+        assert_eq!(politeness.reserve("a.test", delay, now), Duration::ZERO);
+        assert_eq!(politeness.reserve("a.test", delay, now), delay);
+        assert_eq!(politeness.reserve("a.test", delay, now), delay * 2);
+        assert_eq!(politeness.reserve("b.test", delay, now), Duration::ZERO);
+        assert_eq!(
+            politeness.reserve("a.test", delay, now + Duration::from_secs(10)),
+            Duration::ZERO
+        );
+    }
+}
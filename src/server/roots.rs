@@ -0,0 +1,196 @@
+//! Workspace roots advertised by the client through the MCP `roots`
+//! capability. File-based tools resolve their paths against these and
+//! refuse paths outside them.
+
+// rmcp deprecates roots after SEP-2577 without a replacement yet; clients
+// still report their workspaces this way, so scoping keeps using them.
+#![allow(deprecated)]
+
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use rmcp::{ErrorData, RoleServer, model::Root, service::Peer};
+
+use super::{Server, paths};
+
+/// The current workspace roots of a session. Empty until the client has
+/// reported roots; sessions without any roots are not restricted.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Roots(Arc<Mutex<Vec<PathBuf>>>);
+
+impl Roots {
+    pub(crate) fn get(&self) -> Vec<PathBuf> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub(crate) fn set(&self, roots: Vec<PathBuf>) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = roots;
+    }
+
+    /// Re-reads the roots from the client (`roots/list`). On failure the
+    /// previous roots are kept.
+    pub(crate) async fn refresh(&self, peer: &Peer<RoleServer>) {
+        match peer.list_roots().await {
+            Ok(result) => {
+                let roots = root_paths(&result.roots);
+                tracing::info!("workspace roots: {roots:?}");
+                self.set(roots);
+            }
+            Err(e) => tracing::warn!("failed to list client roots: {e}"),
+        }
+    }
+
+    /// Resolves a tool-supplied `path` against the current roots: relative
+    /// paths are joined to the first root, and the result must lie inside
+    /// one of them. Without roots, paths are returned unchanged.
+    pub(crate) fn scope(&self, path: &str) -> Option<PathBuf> {
+        scope_path(&self.get(), Path::new(path))
+    }
+}
+
+impl Server {
+    /// `path` scoped to the session's roots (see [`Roots::scope`]), or an
+    /// error naming the roots. On Windows, paths naming a reserved device
+    /// (`CON`, `NUL`, ...) are refused.
+    pub(super) fn scoped_path(&self, path: &str) -> Result<PathBuf, ErrorData> {
+        if let Some(name) = paths::reserved_component(Path::new(path)) {
+            return Err(ErrorData::invalid_params(
+                "Path names a reserved Windows device",
+                Some(serde_json::json!({ "path": path, "name": name })),
+            ));
+        }
+        let path = self.roots.scope(path).ok_or_else(|| {
+            ErrorData::invalid_request(
+                "Path is outside the client's workspace roots",
+                Some(serde_json::json!({ "path": path, "roots": self.roots.get() })),
+            )
+        })?;
+        Ok(paths::canonicalize(&path).unwrap_or(path))
+    }
+}
+
+/// Local directories for the `file://` roots; other schemes can't scope
+/// filesystem access and are ignored.
+fn root_paths(roots: &[Root]) -> Vec<PathBuf> {
+    roots
+        .iter()
+        .filter_map(|root| url::Url::parse(&root.uri).ok()?.to_file_path().ok())
+        .map(|path| paths::canonicalize(&path).unwrap_or(path))
+        .collect()
+}
+
+fn scope_path(roots: &[PathBuf], path: &Path) -> Option<PathBuf> {
+    let Some(first) = roots.first() else {
+        return Some(path.to_path_buf());
+    };
+    let path = if path.is_relative() {
+        first.join(path)
+    } else {
+        path.to_path_buf()
+    };
+    let path = resolve(&path)?;
+    roots
+        .iter()
+        .any(|root| paths::starts_with(&path, root))
+        .then_some(path)
+}
+
+/// `path` with symlinks resolved, even when it doesn't exist yet: its
+/// nearest existing ancestor is canonicalized and the missing components
+/// appended to it, so a new file under a symlinked directory is scoped by
+/// where the link points. `None` when a missing component is `..`, which
+/// can't be resolved without the directory it leaves.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let mut existing = path.to_path_buf();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(mut resolved) = paths::canonicalize(&existing) {
+            resolved.extend(missing.iter().rev());
+            return Some(resolved);
+        }
+        match existing.components().next_back()? {
+            Component::Normal(name) => missing.push(name.to_os_string()),
+            _ => return None,
+        }
+        existing.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_path_without_roots_is_unrestricted() {
+        assert_eq!(
+            scope_path(&[], Path::new("/etc/passwd")),
+            Some(PathBuf::from("/etc/passwd"))
+        );
+    }
+
+    #[test]
+    fn test_scope_path_within_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = paths::canonicalize(dir.path()).unwrap();
+        std::fs::write(root.join("a.md"), "# A").unwrap();
+        let roots = vec![root.clone()];
+
+        assert_eq!(
+            scope_path(&roots, Path::new("a.md")),
+            Some(root.join("a.md"))
+        );
+        assert_eq!(
+            scope_path(&roots, &root.join("a.md")),
+            Some(root.join("a.md"))
+        );
+        assert_eq!(scope_path(&roots, Path::new("../outside.md")), None);
+        assert_eq!(scope_path(&roots, Path::new("/")), None);
+        assert_eq!(
+            scope_path(&roots, Path::new("new/dir/b.md")),
+            Some(root.join("new/dir/b.md"))
+        );
+        assert_eq!(scope_path(&roots, Path::new("new/../../b.md")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scope_path_resolves_symlinked_ancestor() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let root = paths::canonicalize(dir.path()).unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.join("link")).unwrap();
+
+        assert_eq!(scope_path(&[root], Path::new("link/new.md")), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = paths::canonicalize(dir.path()).unwrap();
+        assert!(!root.to_string_lossy().starts_with(r"\\?\"));
+        let upper = PathBuf::from(root.to_string_lossy().to_uppercase());
+        assert!(scope_path(&[root], &upper.join("new.md")).is_some());
+
+        let server = Server::new(None).unwrap();
+        assert!(server.scoped_path(r"docs\NUL.md").is_err());
+        assert!(server.scoped_path(r"docs\notes.md").is_ok());
+    }
+
+    #[test]
+    fn test_root_paths_keeps_file_uris() {
+        let dir = tempfile::tempdir().unwrap();
+        let uri = url::Url::from_directory_path(dir.path()).unwrap();
+        let roots: Vec<Root> = serde_json::from_value(serde_json::json!([
+            { "uri": uri.as_str(), "name": "tmp" },
+            { "uri": "https://example.com/repo" }
+        ]))
+        .unwrap();
+        assert_eq!(
+            root_paths(&roots),
+            vec![paths::canonicalize(dir.path()).unwrap()]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! `summarize_section`: extracts content with mq, then asks the client's LLM
+//! for a summary through MCP sampling (`sampling/createMessage`).
+
+// rmcp deprecates sampling after SEP-2577 without a replacement yet; clients
+// still implement it, so the tool keeps using it until rmcp drops it.
+#![allow(deprecated)]
+
+use rmcp::{
+    ErrorData, RoleServer,
+    handler::server::wrapper::Parameters,
+    model::{ContentBlock, CreateMessageRequestParams, SamplingMessage},
+    schemars,
+    serde::Serialize,
+    service::Peer,
+    tool, tool_router,
+};
+
+use super::{
+    McpResult, Server, elicit,
+    output::{output_schema, structured_result},
+    section_filter, section_query,
+};
+
+/// Default `maxTokens` for the sampling request.
+const DEFAULT_MAX_TOKENS: u32 = 512;
+
+const SYSTEM_PROMPT: &str = "You summarize Markdown documents. Reply with the summary only, in the language of the document.";
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SummarizeSectionInput {
+    #[schemars(description = "The markdown content to process")]
+    pub markdown: String,
+    #[schemars(
+        description = "Title of the section to summarize (partial, case-sensitive match on the heading). Use either this or `query`; if both are omitted, the user is asked to pick a section (requires elicitation support)."
+    )]
+    pub title: Option<String>,
+    #[schemars(
+        description = "mq query selecting the content to summarize, e.g. `.h2 | select(contains(\"API\"))`. Use either this or `title`."
+    )]
+    pub query: Option<String>,
+    #[schemars(description = "Extra guidance for the summary, e.g. \"three bullet points\"")]
+    pub instructions: Option<String>,
+    #[schemars(
+        description = "Maximum number of tokens the client's model may generate (default: 512)"
+    )]
+    pub max_tokens: Option<u32>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SummaryOutput {
+    #[schemars(description = "The content extracted by the query, as markdown")]
+    pub extracted: String,
+    #[schemars(description = "Summary written by the client's model")]
+    pub summary: String,
+    #[schemars(description = "Model that wrote the summary, as reported by the client")]
+    pub model: String,
+}
+
+fn summary_prompt(extracted: &str, instructions: Option<&str>) -> String {
+    let mut prompt = String::from("Summarize the following Markdown content.");
+    if let Some(instructions) = instructions {
+        prompt.push(' ');
+        prompt.push_str(instructions);
+    }
+    prompt.push_str("\n\n---\n\n");
+    prompt.push_str(extracted);
+    prompt
+}
+
+impl Server {
+    /// Extracts the content to summarize. When `title` matches several
+    /// sections, or neither `title` nor `query` is given, the user is asked
+    /// which section they mean if the client supports elicitation;
+    /// otherwise every matching section is used (or, with neither, an error
+    /// is returned).
+    async fn summary_source(
+        &self,
+        peer: &Peer<RoleServer>,
+        input: &SummarizeSectionInput,
+    ) -> Result<Vec<String>, ErrorData> {
+        let sections = match (&input.title, &input.query) {
+            (None, Some(query)) => return self.query_values(&input.markdown, query),
+            (Some(title), _) => section_filter(title),
+            (None, None) => "section::sections()".to_string(),
+        };
+        let titles = self.aggregate_values(
+            &input.markdown,
+            &format!(r#"import "section" | {sections} | section::titles()"#),
+        )?;
+
+        let ambiguous = match &input.title {
+            Some(_) => titles.len() > 1,
+            None => !titles.is_empty(),
+        };
+        let chosen = if ambiguous {
+            let message = match &input.title {
+                Some(title) => {
+                    format!("Several sections match \"{title}\". Which one should be summarized?")
+                }
+                None => "Which section should be summarized?".to_string(),
+            };
+            elicit::choose(peer, message, "section", &titles).await?
+        } else {
+            None
+        };
+
+        match (chosen, &input.title) {
+            (Some(index), _) => self.aggregate_values(
+                &input.markdown,
+                &format!(
+                    r#"import "section" | {sections} | section::nth({index}) | section::all_nodes()"#
+                ),
+            ),
+            (None, Some(title)) => self.aggregate_values(&input.markdown, &section_query(title)),
+            (None, None) => Err(ErrorData::invalid_params(
+                "Either `title` or `query` is required",
+                None,
+            )),
+        }
+    }
+}
+
+#[tool_router(router = sampling_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Summarize part of a markdown document: extract it by section title or mq query, then ask the client's LLM (via MCP sampling) for a summary. Returns both the extracted text and the summary. Requires a client that supports sampling. If the title matches several sections (or neither title nor query is given) and the client supports elicitation, the user is asked to pick the section.",
+        output_schema = output_schema::<SummaryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    async fn summarize_section(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(input): Parameters<SummarizeSectionInput>,
+    ) -> McpResult {
+        let supports_sampling = peer
+            .peer_info()
+            .is_some_and(|info| info.capabilities.sampling.is_some());
+        if !supports_sampling {
+            return Err(ErrorData::invalid_request(
+                "The client does not support sampling",
+                None,
+            ));
+        }
+
+        let values = self.summary_source(&peer, &input).await?;
+        if values.is_empty() {
+            return Err(ErrorData::invalid_request(
+                "Nothing to summarize: the query returned no content",
+                None,
+            ));
+        }
+        let extracted = values.join("\n\n");
+
+        let request = CreateMessageRequestParams::new(
+            vec![SamplingMessage::user_text(summary_prompt(
+                &extracted,
+                input.instructions.as_deref(),
+            ))],
+            input.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        )
+        .with_system_prompt(SYSTEM_PROMPT);
+        let response = peer.create_message(request).await.map_err(|e| {
+            ErrorData::internal_error(
+                "Sampling request failed",
+                Some(serde_json::Value::String(e.to_string())),
+            )
+        })?;
+        let summary = response
+            .message
+            .content
+            .into_vec()
+            .into_iter()
+            .filter_map(|content| content.as_text().map(|text| text.text.clone()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        structured_result(
+            vec![
+                ContentBlock::text(summary.clone()),
+                ContentBlock::text(extracted.clone()),
+            ],
+            &SummaryOutput {
+                extracted,
+                summary,
+                model: response.model,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_prompt_includes_instructions_and_content() {
+        let prompt = summary_prompt("# Install\n\nRun it.", Some("One sentence."));
+        assert!(prompt.starts_with("Summarize the following Markdown content. One sentence."));
+        assert!(prompt.ends_with("# Install\n\nRun it."));
+    }
+}
@@ -0,0 +1,183 @@
+//! Sanitization of untrusted HTML for `html_to_markdown`: how scripts and
+//! embedded frames are converted, and what raw HTML may survive into the
+//! resulting markdown.
+
+use std::sync::LazyLock;
+
+use mq_markdown::{ConversionOptions, Node};
+use regex::Regex;
+use rmcp::schemars;
+
+/// Embedded-content elements controlled by [`SanitizeOptions::iframes`].
+const EMBED_TAGS: &[&str] = &["iframe", "object", "embed"];
+
+/// Elements removed from raw HTML nodes that survive conversion.
+const ACTIVE_TAGS: &[&str] = &["script", "style", "iframe", "object", "embed"];
+
+/// URL schemes that execute code when followed.
+const DANGEROUS_SCHEMES: &[&str] = &["javascript:", "vbscript:", "data:text/html"];
+
+static EMBEDS: LazyLock<Vec<Regex>> =
+    LazyLock::new(|| EMBED_TAGS.iter().map(|tag| element_regex(tag)).collect());
+static ACTIVE_ELEMENTS: LazyLock<Vec<Regex>> =
+    LazyLock::new(|| ACTIVE_TAGS.iter().map(|tag| element_regex(tag)).collect());
+static EVENT_HANDLERS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\s+on[a-z]+\s*=\s*(?:"[^"]*"|'[^']*'|[^\s>]+)"#).unwrap());
+static SCRIPT_URLS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\b(href|src|action)\s*=\s*(["']?)\s*(?:javascript|vbscript):[^"'\s>]*"#)
+        .unwrap()
+});
+
+/// Matches a `tag` element with its content, a self-closing one, or an
+/// unclosed/void opening tag.
+fn element_regex(tag: &str) -> Regex {
+    Regex::new(&format!(
+        r"(?is)<{tag}\b[^>]*>.*?</{tag}\s*>|<{tag}\b[^>]*/?>"
+    ))
+    .unwrap()
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ScriptHandling {
+    /// Drop `<script>` elements.
+    #[default]
+    Drop,
+    /// Keep scripts as fenced code blocks (inert, but visible).
+    CodeBlock,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EmbedHandling {
+    /// Replace embeds with a link to their source.
+    #[default]
+    Link,
+    /// Drop embeds entirely.
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, Default, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SanitizeOptions {
+    #[schemars(description = "How to handle <script> elements: `drop` (default) or `code_block`")]
+    pub scripts: Option<ScriptHandling>,
+    #[schemars(
+        description = "How to handle <iframe>, <object> and <embed> elements: `link` (default, a link to the embedded URL) or `drop`"
+    )]
+    pub iframes: Option<EmbedHandling>,
+    #[schemars(
+        description = "Drop all raw HTML from the result. By default, raw HTML is kept with scripts, styles, frames, inline event handlers and javascript: URLs removed."
+    )]
+    pub strict: Option<bool>,
+}
+
+impl SanitizeOptions {
+    pub(crate) fn conversion_options(&self) -> ConversionOptions {
+        ConversionOptions {
+            extract_scripts_as_code_blocks: self.scripts.unwrap_or_default()
+                == ScriptHandling::CodeBlock,
+            ..Default::default()
+        }
+    }
+
+    /// Removes what the converter would otherwise turn into content.
+    pub(crate) fn preprocess(&self, html: &str) -> String {
+        let mut html = html.to_string();
+        if self.iframes.unwrap_or_default() == EmbedHandling::Drop {
+            for embed in EMBEDS.iter() {
+                html = embed.replace_all(&html, "").into_owned();
+            }
+        }
+        html
+    }
+
+    /// Sanitizes the converted markdown nodes, recursively.
+    pub(crate) fn sanitize(&self, nodes: Vec<Node>) -> Vec<Node> {
+        nodes
+            .into_iter()
+            .filter_map(|node| self.sanitize_node(node))
+            .collect()
+    }
+
+    fn sanitize_node(&self, mut node: Node) -> Option<Node> {
+        if node.is_html() {
+            if self.strict.unwrap_or_default() {
+                return None;
+            }
+            let html = sanitize_raw_html(&node.value());
+            return (!html.trim().is_empty()).then(|| node.with_value(&html));
+        }
+        if node.is_link() || node.is_image() || node.is_definition() {
+            let url = node
+                .attr("url")
+                .map(|url| url.as_string())
+                .unwrap_or_default();
+            if is_dangerous_url(&url) {
+                node.set_attr("url", "#");
+            }
+        }
+        let children = node.children();
+        if !children.is_empty() {
+            node.set_children(self.sanitize(children));
+        }
+        Some(node)
+    }
+}
+
+fn is_dangerous_url(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    DANGEROUS_SCHEMES
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+/// Strips active content from a raw HTML fragment.
+fn sanitize_raw_html(html: &str) -> String {
+    let mut html = html.to_string();
+    for element in ACTIVE_ELEMENTS.iter() {
+        html = element.replace_all(&html, "").into_owned();
+    }
+    html = EVENT_HANDLERS.replace_all(&html, "").into_owned();
+    SCRIPT_URLS.replace_all(&html, "$1=$2#").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(r#"<div onclick="steal()">hi</div>"#, "<div>hi</div>")]
+    #[case("<b>a</b><script>alert(1)</script>", "<b>a</b>")]
+    #[case("<style>p{}</style><i>b</i>", "<i>b</i>")]
+    #[case(r#"<a href="javascript:alert(1)">x</a>"#, r##"<a href="#">x</a>"##)]
+    fn test_sanitize_raw_html(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(sanitize_raw_html(input), expected);
+    }
+
+    #[test]
+    fn test_preprocess_drops_iframes() {
+        let options = SanitizeOptions {
+            iframes: Some(EmbedHandling::Drop),
+            ..Default::default()
+        };
+        assert_eq!(
+            options
+                .preprocess(r#"<p>a</p><iframe src="https://x.test"></iframe><embed src="y.swf">"#),
+            "<p>a</p>"
+        );
+    }
+
+    #[rstest]
+    #[case("javascript:alert(1)", true)]
+    #[case(" JavaScript:alert(1)", true)]
+    #[case("data:text/html;base64,AAAA", true)]
+    #[case("https://example.com", false)]
+    fn test_is_dangerous_url(#[case] url: &str, #[case] expected: bool) {
+        assert_eq!(is_dangerous_url(url), expected);
+    }
+}
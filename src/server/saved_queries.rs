@@ -0,0 +1,257 @@
+//! Saved queries: `save_query` stores an mq query under a name in the
+//! server's state store, so agents and users can reuse a tested query with
+//! `run_saved_query` instead of writing it again. With `--state`, saved
+//! queries survive restarts and are shared by every session.
+
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server, dry_run,
+    lint::{self, Severity},
+    output::{QueryOutput, json_result, output_schema, query_result},
+    storage::SavedQuery,
+};
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SaveQueryInput {
+    #[schemars(
+        description = "Query name (letters, digits, `-` and `_`). Saving an existing name replaces it"
+    )]
+    pub name: String,
+    #[schemars(description = "The mq query to save")]
+    pub query: String,
+    #[schemars(description = "What the query extracts, shown by list_saved_queries")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct RunSavedQueryInput {
+    #[schemars(description = "Name of a query saved with save_query")]
+    pub name: String,
+    #[schemars(description = "The markdown content to query")]
+    pub markdown: String,
+    #[schemars(
+        description = "Run the query once over all top-level nodes as a single array, as the section module expects, instead of once per node (default: false)"
+    )]
+    pub aggregate: Option<bool>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SavedQueryNameInput {
+    #[schemars(description = "Name of a query saved with save_query")]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SavedQueryInfo {
+    pub name: String,
+    pub query: String,
+    pub description: Option<String>,
+    #[schemars(description = "When the query was last saved (RFC 3339)")]
+    pub updated_at: String,
+}
+
+impl From<SavedQuery> for SavedQueryInfo {
+    fn from(saved: SavedQuery) -> Self {
+        Self {
+            name: saved.name,
+            query: saved.query,
+            description: saved.description,
+            updated_at: saved.updated_at,
+        }
+    }
+}
+
+/// Output of `save_query` and `delete_saved_query`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SavedQueryOutput {
+    #[serde(flatten)]
+    pub query: SavedQueryInfo,
+    #[schemars(description = "Whether this was a dry run that left the saved queries unchanged")]
+    pub dry_run: bool,
+}
+
+/// Output of `list_saved_queries`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SavedQueriesOutput {
+    #[schemars(description = "The saved queries, in name order")]
+    pub queries: Vec<SavedQueryInfo>,
+}
+
+pub(super) fn check_name(name: &str) -> Result<(), ErrorData> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ErrorData::invalid_params(
+            "Invalid query name",
+            Some(serde_json::json!({
+                "name": name,
+                "expected": "letters, digits, `-` and `_`",
+            })),
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects queries that don't parse. Unresolved names are allowed, as they
+/// may be session variables.
+pub(super) fn check_query(query: &str) -> Result<(), ErrorData> {
+    let errors: Vec<_> = lint::lint(query)
+        .into_iter()
+        .filter(|d| {
+            matches!(d.severity, Severity::Error)
+                && matches!(d.code, "syntax-error" | "unknown-selector")
+        })
+        .collect();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(ErrorData::invalid_params(
+        "Invalid mq query",
+        Some(serde_json::json!({ "diagnostics": errors })),
+    ))
+}
+
+impl Server {
+    pub(super) fn saved_query(&self, name: &str) -> Result<SavedQuery, ErrorData> {
+        self.storage.saved_query(name)?.ok_or_else(|| {
+            ErrorData::invalid_params(
+                "Saved query not found",
+                Some(serde_json::json!({ "name": name })),
+            )
+        })
+    }
+}
+
+#[tool_router(router = saved_queries_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Save an mq query under a name so it can be rerun later with run_saved_query, in this or any other session. With a persistent state store (--state), saved queries survive restarts.",
+        output_schema = output_schema::<SavedQueryOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    fn save_query(&self, Parameters(input): Parameters<SaveQueryInput>) -> McpResult {
+        check_name(&input.name)?;
+        check_query(&input.query)?;
+        let dry_run = dry_run::active();
+        if !dry_run {
+            self.storage
+                .save_query(&input.name, &input.query, input.description.as_deref())?;
+        }
+        json_result(&SavedQueryOutput {
+            query: SavedQueryInfo {
+                name: input.name,
+                query: input.query,
+                description: input.description,
+                updated_at: chrono::Utc::now().to_rfc3339(),
+            },
+            dry_run,
+        })
+    }
+
+    #[tool(
+        description = "Run a query saved with save_query on markdown and return the non-empty results, as extract_markdown does.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn run_saved_query(&self, Parameters(input): Parameters<RunSavedQueryInput>) -> McpResult {
+        let saved = self.saved_query(&input.name)?;
+        let values = if input.aggregate.unwrap_or(false) {
+            self.aggregate_values(&input.markdown, &saved.query)?
+        } else {
+            self.query_values(&input.markdown, &saved.query)?
+        };
+        query_result(values)
+    }
+
+    #[tool(
+        description = "List the queries saved with save_query, with their descriptions.",
+        output_schema = output_schema::<SavedQueriesOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn list_saved_queries(&self) -> McpResult {
+        json_result(&SavedQueriesOutput {
+            queries: self
+                .storage
+                .saved_queries()?
+                .into_iter()
+                .map(SavedQueryInfo::from)
+                .collect(),
+        })
+    }
+
+    #[tool(
+        description = "Delete a query saved with save_query.",
+        output_schema = output_schema::<SavedQueryOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    fn delete_saved_query(&self, Parameters(input): Parameters<SavedQueryNameInput>) -> McpResult {
+        let saved = self.saved_query(&input.name)?;
+        let dry_run = dry_run::active();
+        if !dry_run {
+            self.storage.delete_query(&input.name)?;
+        }
+        json_result(&SavedQueryOutput {
+            query: saved.into(),
+            dry_run,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(".h1 | to_text()", true)]
+    #[case("join(titles, \", \")", true)]
+    #[case(".h1 |", false)]
+    fn test_check_query(#[case] query: &str, #[case] valid: bool) {
+        assert_eq!(check_query(query).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_save_and_run() {
+        let server = Server::new(None).unwrap();
+        server
+            .save_query(Parameters(SaveQueryInput {
+                name: "titles".to_string(),
+                query: ".h1 | to_text()".to_string(),
+                description: None,
+            }))
+            .unwrap();
+        let result = server
+            .run_saved_query(Parameters(RunSavedQueryInput {
+                name: "titles".to_string(),
+                markdown: "# Install\n\ntext\n\n# Usage".to_string(),
+                aggregate: None,
+            }))
+            .unwrap();
+        assert_eq!(
+            result.structured_content,
+            Some(serde_json::json!({ "results": ["Install", "Usage"] }))
+        );
+
+        server
+            .delete_saved_query(Parameters(SavedQueryNameInput {
+                name: "titles".to_string(),
+            }))
+            .unwrap();
+        assert!(server.saved_query("titles").is_err());
+    }
+}
@@ -0,0 +1,852 @@
+//! Scheduled pipelines: with the HTTP transport, `schedule_pipeline` saves
+//! a pipeline (the steps of `run_pipeline`) that runs on a cron schedule
+//! against a URL or file. Each pipeline's latest results are exposed as the
+//! resource `mq://pipelines/<name>`, and sessions subscribed to it with
+//! `resources/subscribe` are notified when a run updates it, so an agent
+//! can watch a changelog without polling.
+//!
+//! A pipeline scheduled as a digest publishes the changes to the structure
+//! of its output instead (see [`digest`](super::digest)), and the resource
+//! `mq://digests` gathers the latest digest of each.
+//!
+//! Pipelines are shared by every session of the server and run one at a
+//! time, in UTC. Their definitions are saved in the state store, so with
+//! `--state` they are rescheduled when the server restarts.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use chrono::{DateTime, Timelike, Utc};
+use rmcp::{
+    ErrorData, RoleServer,
+    handler::server::wrapper::Parameters,
+    model::{Resource, ResourceUpdatedNotificationParam},
+    schemars,
+    serde::Serialize,
+    service::Peer,
+    tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    cron::Cron,
+    digest::{self, Outline},
+    dry_run,
+    fetch::parse_url,
+    output::{json_result, output_schema},
+    paths,
+    pipeline::Step,
+    profile, provenance, redact,
+    webhook::Delivery,
+};
+
+/// URI prefix of the resources holding scheduled pipelines' results.
+pub(crate) const PIPELINE_URI_PREFIX: &str = "mq://pipelines/";
+
+/// URI of the resource gathering the latest digest of every digest
+/// pipeline.
+pub(crate) const DIGESTS_URI: &str = "mq://digests";
+
+/// Maximum number of scheduled pipelines per server.
+pub(super) const MAX_PIPELINES: usize = 32;
+
+#[derive(Debug, Clone, rmcp::serde::Deserialize, Serialize, schemars::JsonSchema)]
+pub(crate) struct SchedulePipelineInput {
+    #[schemars(
+        description = "Pipeline name (letters, digits, `-` and `_`); its results are the resource mq://pipelines/<name>. Scheduling an existing name replaces it"
+    )]
+    pub name: String,
+    #[schemars(
+        description = "Five-field cron expression in UTC (`minute hour day-of-month month day-of-week`), e.g. `0 * * * *` for hourly, or `@hourly`, `@daily`, `@weekly`, `@monthly`"
+    )]
+    pub cron: String,
+    #[schemars(description = "http(s) URL of the HTML or markdown page to process")]
+    pub url: Option<String>,
+    #[schemars(description = "Path of the markdown file to process; use either this or `url`")]
+    pub path: Option<String>,
+    #[schemars(description = "The steps to run, as for run_pipeline")]
+    pub steps: Vec<Step>,
+    #[schemars(
+        description = "Publish a digest of the sections added, changed and removed in the steps' output since the previous run, instead of the output itself (default: false). With no steps, the whole document is compared"
+    )]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub digest: bool,
+    #[schemars(
+        description = "URL to POST a JSON report of every run to; its host must be allowed by the server configuration (--webhook-host)"
+    )]
+    pub webhook: Option<String>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct PipelineNameInput {
+    #[schemars(description = "Name of a scheduled pipeline")]
+    pub name: String,
+}
+
+/// What a scheduled pipeline runs on.
+#[derive(Debug, Clone)]
+enum Source {
+    Url(url::Url),
+    Path(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Url(url) => write!(f, "{url}"),
+            Source::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+struct Scheduled {
+    expression: String,
+    cron: Cron,
+    source: Source,
+    steps: Vec<Step>,
+    webhook: Option<url::Url>,
+    /// `None` when the expression never matches again.
+    next_run: Option<DateTime<Utc>>,
+    last_run: Option<DateTime<Utc>>,
+    /// Values of the last successful run; for a digest, the last changes
+    /// found.
+    results: Vec<String>,
+    /// Error of the last run, if it failed.
+    error: Option<String>,
+    /// For a digest, the outline of the last successful run's output.
+    digest: Option<Option<Outline>>,
+}
+
+impl Scheduled {
+    /// Takes the values of a successful run made at `now`. Returns whether
+    /// the results changed: for a digest, whether the run found changes
+    /// since the previous one. The first run of a digest only takes its
+    /// outline.
+    fn update(&mut self, values: Vec<String>, now: DateTime<Utc>) -> Result<bool, ErrorData> {
+        let Some(previous) = &mut self.digest else {
+            let changed = self.results != values;
+            self.results = values;
+            return Ok(changed);
+        };
+        let outline = Outline::new(&values.join("\n\n"))?;
+        let changes = previous
+            .as_ref()
+            .map(|previous| digest::changes(previous, &outline))
+            .unwrap_or_default();
+        *previous = Some(outline);
+        if changes.is_empty() {
+            return Ok(false);
+        }
+        self.results = vec![digest::render(
+            &self.source.to_string(),
+            &now.to_rfc3339(),
+            &changes,
+        )];
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ScheduledPipelineInfo {
+    pub name: String,
+    #[schemars(description = "Resource holding the latest results")]
+    pub uri: String,
+    pub cron: String,
+    #[schemars(description = "The URL or file the pipeline runs on")]
+    pub source: String,
+    #[schemars(description = "When the pipeline runs next (RFC 3339); null if never")]
+    pub next_run: Option<String>,
+    #[schemars(description = "When the pipeline last ran (RFC 3339)")]
+    pub last_run: Option<String>,
+    #[schemars(description = "Number of values of the last successful run")]
+    pub results: usize,
+    #[schemars(description = "Why the last run failed; its previous results are kept")]
+    pub last_error: Option<String>,
+    #[schemars(description = "URL each run is reported to")]
+    pub webhook: Option<String>,
+    #[schemars(description = "Whether the results are a digest of the changes")]
+    pub digest: bool,
+}
+
+/// Output of `schedule_pipeline` and `unschedule_pipeline`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ScheduledPipelineOutput {
+    #[serde(flatten)]
+    pub pipeline: ScheduledPipelineInfo,
+    #[schemars(description = "Whether this was a dry run that left the schedule unchanged")]
+    pub dry_run: bool,
+}
+
+/// Output of `list_scheduled_pipelines`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ScheduledPipelinesOutput {
+    pub pipelines: Vec<ScheduledPipelineInfo>,
+}
+
+fn pipeline_uri(name: &str) -> String {
+    format!("{PIPELINE_URI_PREFIX}{name}")
+}
+
+fn info(name: &str, scheduled: &Scheduled) -> ScheduledPipelineInfo {
+    ScheduledPipelineInfo {
+        name: name.to_string(),
+        uri: pipeline_uri(name),
+        cron: scheduled.expression.clone(),
+        source: scheduled.source.to_string(),
+        next_run: scheduled.next_run.map(|at| at.to_rfc3339()),
+        last_run: scheduled.last_run.map(|at| at.to_rfc3339()),
+        results: scheduled.results.len(),
+        last_error: scheduled.error.clone(),
+        webhook: scheduled.webhook.as_ref().map(url::Url::to_string),
+        digest: scheduled.digest.is_some(),
+    }
+}
+
+/// The JSON definition of `input` saved in the state store. Paths are saved
+/// resolved against the roots of the session that scheduled the pipeline,
+/// as restoring it has no roots.
+fn definition(input: &SchedulePipelineInput, source: &Source) -> Result<String, ErrorData> {
+    let mut input = input.clone();
+    if let Source::Path(path) = source {
+        input.path = Some(path.display().to_string());
+    }
+    serde_json::to_string(&input).map_err(|e| {
+        ErrorData::internal_error(
+            "Failed to serialize output",
+            Some(serde_json::Value::String(e.to_string())),
+        )
+    })
+}
+
+fn pipeline_not_found(name: &str) -> ErrorData {
+    ErrorData::invalid_params(
+        "Scheduled pipeline not found",
+        Some(serde_json::json!({ "name": name })),
+    )
+}
+
+/// The resource URIs a session subscribed to.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Subscriptions(Arc<Mutex<HashSet<String>>>);
+
+impl Subscriptions {
+    fn contains(&self, uri: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(uri)
+    }
+
+    pub(crate) fn remove(&self, uri: &str) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).remove(uri);
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Whether pipelines are run, i.e. the server uses the HTTP transport.
+    running: bool,
+    pipelines: BTreeMap<String, Scheduled>,
+    /// Sessions that subscribed to a pipeline resource.
+    subscribers: Vec<(Subscriptions, Peer<RoleServer>)>,
+}
+
+/// The scheduled pipelines of a server, shared by its sessions.
+#[derive(Clone, Default)]
+pub(crate) struct Scheduler(Arc<Mutex<State>>);
+
+impl Scheduler {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Resources for the pipelines' latest results, and for their digests
+    /// if any pipeline is a digest.
+    pub(crate) fn resources(&self) -> Vec<Resource> {
+        let state = self.lock();
+        let mut resources: Vec<Resource> = state
+            .pipelines
+            .iter()
+            .map(|(name, scheduled)| {
+                Resource::new(pipeline_uri(name), name)
+                    .with_description(format!(
+                        "Latest {} of the pipeline scheduled `{}` on {}",
+                        match scheduled.digest {
+                            Some(_) => "changes",
+                            None => "results",
+                        },
+                        scheduled.expression,
+                        scheduled.source
+                    ))
+                    .with_mime_type("text/markdown")
+            })
+            .collect();
+        if state
+            .pipelines
+            .values()
+            .any(|scheduled| scheduled.digest.is_some())
+        {
+            resources.push(
+                Resource::new(DIGESTS_URI, "Digests")
+                    .with_description("Latest changes found by every digest pipeline")
+                    .with_mime_type("text/markdown"),
+            );
+        }
+        resources
+    }
+
+    /// The latest results of the pipeline at `uri`, joined by blank lines,
+    /// or the latest digests of all pipelines for [`DIGESTS_URI`].
+    pub(crate) fn read(&self, uri: &str) -> Option<String> {
+        let state = self.lock();
+        if uri == DIGESTS_URI {
+            let digests: Vec<&str> = state
+                .pipelines
+                .values()
+                .filter(|scheduled| scheduled.digest.is_some())
+                .flat_map(|scheduled| scheduled.results.iter().map(String::as_str))
+                .collect();
+            if digests.is_empty() {
+                return Some("No changes found yet.".to_string());
+            }
+            return Some(digests.join("\n\n"));
+        }
+        let name = uri.strip_prefix(PIPELINE_URI_PREFIX)?;
+        Some(state.pipelines.get(name)?.results.join("\n\n"))
+    }
+
+    /// Records that the session with `subscriptions` wants updates of
+    /// `uri`.
+    pub(crate) fn subscribe(
+        &self,
+        subscriptions: &Subscriptions,
+        uri: String,
+        peer: Peer<RoleServer>,
+    ) {
+        subscriptions
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(uri);
+        let mut state = self.lock();
+        if !state
+            .subscribers
+            .iter()
+            .any(|(known, _)| Arc::ptr_eq(&known.0, &subscriptions.0))
+        {
+            state.subscribers.push((subscriptions.clone(), peer));
+        }
+    }
+
+    /// Sends `notifications/resources/updated` for `uri` to the sessions
+    /// subscribed to it, forgetting sessions that are gone.
+    async fn notify(&self, uri: &str) {
+        let subscribers: Vec<_> = self
+            .lock()
+            .subscribers
+            .iter()
+            .filter(|(subscriptions, _)| subscriptions.contains(uri))
+            .cloned()
+            .collect();
+        for (subscriptions, peer) in subscribers {
+            if let Err(e) = peer
+                .notify_resource_updated(ResourceUpdatedNotificationParam::new(uri))
+                .await
+            {
+                tracing::debug!("dropping resource subscriber: {e}");
+                self.lock()
+                    .subscribers
+                    .retain(|(known, _)| !Arc::ptr_eq(&known.0, &subscriptions.0));
+            }
+        }
+    }
+
+    /// Notifies the subscribers of the results of `name`, and of
+    /// [`DIGESTS_URI`] if it is a digest.
+    async fn notify_changed(&self, name: &str) {
+        self.notify(&pipeline_uri(name)).await;
+        let digest = self
+            .lock()
+            .pipelines
+            .get(name)
+            .is_some_and(|scheduled| scheduled.digest.is_some());
+        if digest {
+            self.notify(DIGESTS_URI).await;
+        }
+    }
+
+    /// Names of the pipelines due at `now`, advancing their next run.
+    fn due(&self, now: DateTime<Utc>) -> Vec<String> {
+        let mut state = self.lock();
+        state
+            .pipelines
+            .iter_mut()
+            .filter(|(_, scheduled)| scheduled.next_run.is_some_and(|at| at <= now))
+            .map(|(name, scheduled)| {
+                scheduled.next_run = scheduled.cron.next_after(now);
+                name.clone()
+            })
+            .collect()
+    }
+
+    /// Records the outcome of a run of `name`. Returns whether its results
+    /// changed.
+    fn record(
+        &self,
+        name: &str,
+        outcome: Result<Vec<String>, ErrorData>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let mut state = self.lock();
+        let Some(scheduled) = state.pipelines.get_mut(name) else {
+            return false;
+        };
+        scheduled.last_run = Some(now);
+        match outcome.and_then(|values| scheduled.update(values, now)) {
+            Ok(changed) => {
+                scheduled.error = None;
+                changed
+            }
+            Err(e) => {
+                tracing::warn!("scheduled pipeline {name} failed: {}", e.message);
+                scheduled.error = Some(e.message.to_string());
+                false
+            }
+        }
+    }
+}
+
+/// Checks a pipeline name, which becomes part of a resource URI.
+fn check_name(name: &str) -> Result<(), ErrorData> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ErrorData::invalid_params(
+            "Invalid pipeline name",
+            Some(serde_json::json!({
+                "name": name,
+                "expected": "letters, digits, `-` and `_`",
+            })),
+        ));
+    }
+    Ok(())
+}
+
+impl Server {
+    /// Runs the scheduled pipelines as they come due, for as long as the
+    /// process lives. Called once by the HTTP transport.
+    pub(super) fn start_scheduler(self) {
+        self.scheduler.lock().running = true;
+        tokio::spawn(async move {
+            self.restore_pipelines().await;
+            loop {
+                let second = u64::from(Utc::now().second());
+                tokio::time::sleep(Duration::from_secs(60 - second.min(59))).await;
+                for name in self.scheduler.due(Utc::now()) {
+                    let job = self
+                        .scheduler
+                        .lock()
+                        .pipelines
+                        .get(&name)
+                        .map(|scheduled| (scheduled.source.clone(), scheduled.steps.clone()));
+                    let Some((source, steps)) = job else {
+                        continue;
+                    };
+                    let outcome = self.run_on(&source, &steps).await;
+                    let changed = self.scheduler.record(&name, outcome, Utc::now());
+                    if changed {
+                        self.scheduler.notify_changed(&name).await;
+                    }
+                    self.deliver_webhook(&name, changed).await;
+                }
+            }
+        });
+    }
+
+    /// Reschedules the pipelines saved in the state store, running each
+    /// once. A pipeline whose definition no longer validates is logged and
+    /// left in the store.
+    async fn restore_pipelines(&self) {
+        let saved = match self.storage.pipelines() {
+            Ok(saved) => saved,
+            Err(e) => {
+                tracing::warn!("failed to load saved pipelines: {:?}", e.data);
+                return;
+            }
+        };
+        for (name, definition) in saved {
+            if let Err(e) = self.reschedule(&name, &definition).await {
+                tracing::warn!("not restoring scheduled pipeline {name}: {e}");
+            }
+        }
+    }
+
+    /// Schedules the pipeline saved as `definition` under `name`, replacing
+    /// any pipeline of that name, and runs it once. Fails only if the
+    /// definition doesn't validate; a failed run is recorded.
+    pub(super) async fn reschedule(&self, name: &str, definition: &str) -> Result<(), String> {
+        let scheduled = serde_json::from_str::<SchedulePipelineInput>(definition)
+            .map_err(|e| e.to_string())
+            .and_then(|input| self.validate(&input).map_err(|e| e.message.to_string()))?;
+        let (source, steps) = (scheduled.source.clone(), scheduled.steps.clone());
+        self.scheduler
+            .lock()
+            .pipelines
+            .insert(name.to_string(), scheduled);
+        let outcome = self.run_on(&source, &steps).await;
+        self.scheduler.record(name, outcome, Utc::now());
+        Ok(())
+    }
+
+    /// Whether pipelines run in this process, i.e. with the HTTP transport.
+    pub(super) fn scheduler_running(&self) -> bool {
+        self.scheduler.lock().running
+    }
+
+    /// Stops the pipeline scheduled as `name`, leaving the state store as is.
+    pub(super) fn stop_pipeline(&self, name: &str) {
+        self.scheduler.lock().pipelines.remove(name);
+    }
+
+    /// Checks `input` and returns the definition to save in the state store.
+    pub(super) fn pipeline_definition(
+        &self,
+        input: &SchedulePipelineInput,
+    ) -> Result<String, ErrorData> {
+        let scheduled = self.validate(input)?;
+        definition(input, &scheduled.source)
+    }
+
+    /// Checks `input` and builds the pipeline it describes, not yet run.
+    fn validate(&self, input: &SchedulePipelineInput) -> Result<Scheduled, ErrorData> {
+        check_name(&input.name)?;
+        let cron: Cron = input.cron.parse().map_err(|e: String| {
+            ErrorData::invalid_params(
+                "Invalid cron expression",
+                Some(serde_json::json!({ "cron": input.cron, "error": e })),
+            )
+        })?;
+        let source = self.source(input)?;
+        let webhook = input
+            .webhook
+            .as_deref()
+            .map(|url| self.config.webhook.check(url))
+            .transpose()?;
+        Ok(Scheduled {
+            expression: input.cron.clone(),
+            next_run: cron.next_after(Utc::now()),
+            cron,
+            source,
+            steps: input.steps.clone(),
+            webhook,
+            last_run: None,
+            results: Vec::new(),
+            error: None,
+            digest: input.digest.then_some(None),
+        })
+    }
+
+    /// Fetches or reads `source` and runs `steps` on it.
+    async fn run_on(&self, source: &Source, steps: &[Step]) -> Result<Vec<String>, ErrorData> {
+        let markdown = match source {
+            Source::Url(url) => {
+                self.fetcher
+                    .fetch(url.clone(), reqwest::header::HeaderMap::new())
+                    .await?
+                    .text
+            }
+            Source::Path(path) => paths::read_markdown(path).map_err(|e| {
+                ErrorData::invalid_params(
+                    "Failed to read file",
+                    Some(serde_json::json!({ "path": path, "error": e.to_string() })),
+                )
+            })?,
+        };
+        let run = provenance::scope(None, async { self.run_steps(markdown, steps) });
+        let (mut results, _) = profile::scope(self.live.profile(None)?, run).await?;
+        // The results are read as resources, which finish_result doesn't
+        // redact.
+        redact::redact_texts(&self.config.redactions, &mut results);
+        Ok(results)
+    }
+
+    /// Reports the last run of `name` to its webhook, if it has one.
+    /// Failed deliveries are logged.
+    async fn deliver_webhook(&self, name: &str, changed: bool) {
+        let (url, body) = {
+            let state = self.scheduler.lock();
+            let Some(scheduled) = state.pipelines.get(name) else {
+                return;
+            };
+            let Some(url) = scheduled.webhook.clone() else {
+                return;
+            };
+            let delivery = Delivery {
+                pipeline: name,
+                uri: pipeline_uri(name),
+                source: scheduled.source.to_string(),
+                ran_at: scheduled
+                    .last_run
+                    .map(|at| at.to_rfc3339())
+                    .unwrap_or_default(),
+                changed,
+                results: &scheduled.results,
+                error: scheduled.error.as_deref(),
+            };
+            (url, delivery.body(&self.config.redactions))
+        };
+        let result = match body {
+            Ok(body) => {
+                self.config
+                    .webhook
+                    .deliver(&self.fetcher, url.clone(), &body)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::warn!("webhook of pipeline {name} to {url} failed: {}", e.message);
+        }
+    }
+
+    fn source(&self, input: &SchedulePipelineInput) -> Result<Source, ErrorData> {
+        match (&input.url, &input.path) {
+            (Some(url), None) => Ok(Source::Url(parse_url(url)?)),
+            (None, Some(path)) => Ok(Source::Path(self.scoped_path(path)?)),
+            _ => Err(ErrorData::invalid_params(
+                "Give exactly one of `url` or `path`",
+                None,
+            )),
+        }
+    }
+}
+
+#[tool_router(router = schedule_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Save a pipeline (the steps of run_pipeline) that runs on a cron schedule against a URL or file, e.g. to watch a changelog. It runs once immediately; its latest results are the resource mq://pipelines/<name>, and clients subscribed to that resource are notified when a run changes them. With `digest`, it publishes the sections added, changed and removed since the previous run instead, gathered with other digests in mq://digests. With a `webhook`, each run's results are also POSTed to that URL. Only available with the HTTP transport.",
+        output_schema = output_schema::<ScheduledPipelineOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = true
+        )
+    )]
+    async fn schedule_pipeline(
+        &self,
+        Parameters(input): Parameters<SchedulePipelineInput>,
+    ) -> McpResult {
+        if !self.scheduler_running() {
+            return Err(ErrorData::invalid_request(
+                "Scheduled pipelines require the HTTP transport (--http)",
+                None,
+            ));
+        }
+        let mut scheduled = self.validate(&input)?;
+        {
+            let state = self.scheduler.lock();
+            if !state.pipelines.contains_key(&input.name) && state.pipelines.len() >= MAX_PIPELINES
+            {
+                return Err(ErrorData::invalid_request(
+                    "Too many scheduled pipelines",
+                    Some(serde_json::json!({ "limit": MAX_PIPELINES })),
+                ));
+            }
+        }
+
+        // The first run checks the source and steps before anything is saved.
+        let values = self.run_on(&scheduled.source, &scheduled.steps).await?;
+        let now = Utc::now();
+        scheduled.update(values, now)?;
+        scheduled.last_run = Some(now);
+        let pipeline = info(&input.name, &scheduled);
+        let dry_run = dry_run::active();
+        if !dry_run {
+            let definition = definition(&input, &scheduled.source)?;
+            self.storage.save_pipeline(&input.name, &definition)?;
+            self.scheduler
+                .lock()
+                .pipelines
+                .insert(input.name.clone(), scheduled);
+            self.scheduler.notify_changed(&input.name).await;
+            self.deliver_webhook(&input.name, true).await;
+        }
+        json_result(&ScheduledPipelineOutput { pipeline, dry_run })
+    }
+
+    #[tool(
+        description = "Stop and remove a pipeline saved with schedule_pipeline, with its results resource.",
+        output_schema = output_schema::<ScheduledPipelineOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    fn unschedule_pipeline(&self, Parameters(input): Parameters<PipelineNameInput>) -> McpResult {
+        let dry_run = dry_run::active();
+        let mut state = self.scheduler.lock();
+        let pipeline = state
+            .pipelines
+            .get(&input.name)
+            .map(|scheduled| info(&input.name, scheduled))
+            .ok_or_else(|| pipeline_not_found(&input.name))?;
+        if !dry_run {
+            self.storage.delete_pipeline(&input.name)?;
+            state.pipelines.remove(&input.name);
+        }
+        json_result(&ScheduledPipelineOutput { pipeline, dry_run })
+    }
+
+    #[tool(
+        description = "List the pipelines saved with schedule_pipeline, with their schedule, source, next and last run, result count and last error.",
+        output_schema = output_schema::<ScheduledPipelinesOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn list_scheduled_pipelines(&self) -> McpResult {
+        let state = self.scheduler.lock();
+        json_result(&ScheduledPipelinesOutput {
+            pipelines: state
+                .pipelines
+                .iter()
+                .map(|(name, scheduled)| info(name, scheduled))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ServerConfig;
+
+    fn scheduled(expression: &str, next_run: Option<DateTime<Utc>>) -> Scheduled {
+        Scheduled {
+            expression: expression.to_string(),
+            cron: expression.parse().unwrap(),
+            source: Source::Path(PathBuf::from("CHANGELOG.md")),
+            steps: Vec::new(),
+            webhook: None,
+            next_run,
+            last_run: None,
+            results: Vec::new(),
+            error: None,
+            digest: None,
+        }
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_due_advances_next_run() {
+        let scheduler = Scheduler::default();
+        let now = at("2026-03-01T10:00:00Z");
+        scheduler.lock().pipelines.extend([
+            ("hourly".to_string(), scheduled("0 * * * *", Some(now))),
+            (
+                "later".to_string(),
+                scheduled("30 * * * *", Some(at("2026-03-01T10:30:00Z"))),
+            ),
+        ]);
+        assert_eq!(scheduler.due(now), vec!["hourly"]);
+        assert_eq!(
+            scheduler.lock().pipelines["hourly"].next_run,
+            Some(at("2026-03-01T11:00:00Z"))
+        );
+        assert!(scheduler.due(now).is_empty());
+    }
+
+    #[test]
+    fn test_record_keeps_results_of_failed_runs() {
+        let scheduler = Scheduler::default();
+        scheduler
+            .lock()
+            .pipelines
+            .insert("watch".to_string(), scheduled("@daily", None));
+        let now = at("2026-03-01T00:00:00Z");
+        assert!(scheduler.record("watch", Ok(vec!["## 1.0".to_string()]), now));
+        assert!(!scheduler.record("watch", Ok(vec!["## 1.0".to_string()]), now));
+        let failed = ErrorData::internal_error("Failed to fetch URL", None);
+        assert!(!scheduler.record("watch", Err(failed), now));
+
+        let uri = pipeline_uri("watch");
+        assert_eq!(scheduler.read(&uri).as_deref(), Some("## 1.0"));
+        let state = scheduler.lock();
+        let info = info("watch", &state.pipelines["watch"]);
+        assert_eq!(info.last_error.as_deref(), Some("Failed to fetch URL"));
+        assert_eq!(info.results, 1);
+    }
+
+    #[test]
+    fn test_record_digest() {
+        let scheduler = Scheduler::default();
+        let mut digest = scheduled("@daily", None);
+        digest.digest = Some(None);
+        scheduler
+            .lock()
+            .pipelines
+            .insert("guide".to_string(), digest);
+        let now = at("2026-03-01T00:00:00Z");
+        let run = |markdown: &str| scheduler.record("guide", Ok(vec![markdown.to_string()]), now);
+        assert!(!run("# Guide\n\n## Install\n"));
+        assert!(!run("# Guide\n\n## Install\n"));
+        assert!(run("# Guide\n\n## Install\n\n## Usage\n"));
+        assert!(!run("# Guide\n\n## Install\n\n## Usage\n"));
+
+        let digest = scheduler.read(&pipeline_uri("guide")).unwrap();
+        assert!(digest.contains("- Added: Guide > Usage"), "{digest}");
+        assert_eq!(scheduler.read(DIGESTS_URI), Some(digest));
+        assert_eq!(scheduler.resources().len(), 2);
+    }
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("watch-changelog_2").is_ok());
+        assert!(check_name("").is_err());
+        assert!(check_name("a/b").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_on_redacts_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contacts.md");
+        std::fs::write(&path, "Mail alice@example.com\n").unwrap();
+        let server = Server::with_config(ServerConfig {
+            redactions: vec!["email".parse().unwrap()],
+            ..Default::default()
+        })
+        .unwrap();
+        let results = server.run_on(&Source::Path(path), &[]).await.unwrap();
+        assert_eq!(results, ["Mail [REDACTED:email]\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_requires_http() {
+        let server = Server::new(None).unwrap();
+        let input = SchedulePipelineInput {
+            name: "watch".to_string(),
+            cron: "@hourly".to_string(),
+            url: Some("https://example.com/CHANGELOG.md".to_string()),
+            path: None,
+            steps: Vec::new(),
+            digest: false,
+            webhook: None,
+        };
+        let error = server
+            .schedule_pipeline(Parameters(input))
+            .await
+            .unwrap_err();
+        assert!(error.message.contains("HTTP transport"));
+    }
+}
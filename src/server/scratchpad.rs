@@ -0,0 +1,336 @@
+//! The scratchpad: a markdown document of the session that
+//! `append_to_scratchpad` adds to and clients read as the `mq://scratchpad`
+//! resource. An agent building a report over many calls appends each
+//! piece as it goes (text it writes, the results of a query, a session
+//! variable or an offloaded result) instead of carrying the report so far
+//! through every call.
+//!
+//! A session subscribed to the resource with `resources/subscribe` is sent
+//! `notifications/resources/updated` when it changes.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rmcp::{
+    ErrorData, RoleServer,
+    handler::server::wrapper::Parameters,
+    model::{Resource, ResourceUpdatedNotificationParam},
+    schemars,
+    serde::Serialize,
+    service::Peer,
+    tool, tool_router,
+};
+
+use super::{
+    McpResult, Server, dry_run,
+    output::{json_result, output_schema},
+};
+
+/// URI of the scratchpad resource.
+pub(crate) const SCRATCHPAD_URI: &str = "mq://scratchpad";
+
+/// Maximum size of a session's scratchpad.
+const MAX_SCRATCHPAD_BYTES: usize = 1024 * 1024;
+
+#[derive(Default)]
+struct State {
+    text: String,
+    /// The session, if it subscribed to the resource.
+    subscriber: Option<Peer<RoleServer>>,
+}
+
+/// A session's scratchpad. Cheap to clone; clones share the document.
+#[derive(Clone, Default)]
+pub(crate) struct Scratchpad(Arc<Mutex<State>>);
+
+impl Scratchpad {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn text(&self) -> String {
+        self.lock().text.clone()
+    }
+
+    fn len(&self) -> usize {
+        self.lock().text.len()
+    }
+
+    /// Appends `block` after a blank line, returning the new size. A dry
+    /// run only returns the size the scratchpad would have.
+    fn append(&self, block: &str, dry_run: bool) -> Result<usize, ErrorData> {
+        let mut state = self.lock();
+        let separator = match state.text.len() {
+            0 => "",
+            _ if state.text.ends_with("\n\n") => "",
+            _ if state.text.ends_with('\n') => "\n",
+            _ => "\n\n",
+        };
+        let size = state.text.len() + separator.len() + block.len() + 1;
+        if size > MAX_SCRATCHPAD_BYTES {
+            return Err(ErrorData::invalid_request(
+                "The scratchpad is full",
+                Some(serde_json::json!({
+                    "size": state.text.len(),
+                    "limit": MAX_SCRATCHPAD_BYTES,
+                })),
+            ));
+        }
+        if dry_run {
+            return Ok(size);
+        }
+        state.text.push_str(separator);
+        state.text.push_str(block.trim_end_matches('\n'));
+        state.text.push('\n');
+        let size = state.text.len();
+        notify(&state);
+        Ok(size)
+    }
+
+    fn clear(&self) {
+        let mut state = self.lock();
+        state.text.clear();
+        notify(&state);
+    }
+
+    pub(crate) fn subscribe(&self, peer: Peer<RoleServer>) {
+        self.lock().subscriber = Some(peer);
+    }
+
+    pub(crate) fn unsubscribe(&self) {
+        self.lock().subscriber = None;
+    }
+
+    pub(crate) fn resource(&self) -> Resource {
+        Resource::new(SCRATCHPAD_URI, "Scratchpad")
+            .with_description("This session's scratchpad, written with append_to_scratchpad")
+            .with_mime_type("text/markdown")
+            .with_size(self.len() as u64)
+    }
+}
+
+/// Tells the subscribed session, if any, that the scratchpad changed.
+fn notify(state: &State) {
+    let Some(peer) = state.subscriber.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        if let Err(e) = peer
+            .notify_resource_updated(ResourceUpdatedNotificationParam::new(SCRATCHPAD_URI))
+            .await
+        {
+            tracing::debug!("failed to notify a scratchpad update: {e}");
+        }
+    });
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct AppendToScratchpadInput {
+    #[schemars(description = "Markdown to append; use either this, `variable` or `result`")]
+    pub markdown: Option<String>,
+    #[schemars(
+        description = "An mq query run on the content: its non-empty results are appended instead of the whole content"
+    )]
+    pub query: Option<String>,
+    #[schemars(description = "Name of a session variable whose stored results are appended")]
+    pub variable: Option<String>,
+    #[schemars(description = "URI of an offloaded tool result (`mq://results/...`) to append")]
+    pub result: Option<String>,
+    #[schemars(description = "A level-2 heading to put before the appended content")]
+    pub heading: Option<String>,
+}
+
+/// Output of `append_to_scratchpad` and `clear_scratchpad`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ScratchpadOutput {
+    #[schemars(description = "The resource to read the scratchpad from")]
+    pub uri: String,
+    #[schemars(description = "Bytes appended")]
+    pub appended: usize,
+    #[schemars(description = "The scratchpad's size in bytes")]
+    pub size: usize,
+    #[schemars(description = "Whether this was a dry run that changed nothing")]
+    pub dry_run: bool,
+}
+
+impl Server {
+    /// The values of exactly one of `markdown`, the session variable
+    /// `variable` and the stored `result` (an offloaded result or the
+    /// scratchpad itself), narrowed to the non-empty results of `query` if
+    /// given.
+    pub(super) fn content_values(
+        &self,
+        markdown: Option<String>,
+        query: Option<&str>,
+        variable: Option<&str>,
+        result: Option<&str>,
+    ) -> Result<Vec<String>, ErrorData> {
+        let values = match (markdown, variable, result) {
+            (Some(markdown), None, None) => vec![markdown],
+            (None, Some(name), None) => self.variables.get(name)?,
+            (None, None, Some(SCRATCHPAD_URI)) => vec![self.scratchpad.text()],
+            (None, None, Some(uri)) => vec![self.results.get(uri).ok_or_else(|| {
+                ErrorData::invalid_params(
+                    "Stored result not found",
+                    Some(serde_json::json!({ "result": uri })),
+                )
+            })?],
+            _ => {
+                return Err(ErrorData::invalid_params(
+                    "Give exactly one of `markdown`, `variable` or `result`",
+                    None,
+                ));
+            }
+        };
+        match query {
+            Some(query) => self.query_values(&values.join("\n\n"), query),
+            None => Ok(values),
+        }
+    }
+
+    /// The content `input` appends.
+    fn scratchpad_content(&self, input: AppendToScratchpadInput) -> Result<String, ErrorData> {
+        let values = self.content_values(
+            input.markdown,
+            input.query.as_deref(),
+            input.variable.as_deref(),
+            input.result.as_deref(),
+        )?;
+        let mut blocks: Vec<String> = input
+            .heading
+            .map(|heading| format!("## {}", heading.trim()))
+            .into_iter()
+            .collect();
+        blocks.extend(
+            values
+                .iter()
+                .map(|value| value.trim_matches('\n').to_string())
+                .filter(|value| !value.is_empty()),
+        );
+        Ok(blocks.join("\n\n"))
+    }
+}
+
+#[tool_router(router = scratchpad_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Append markdown to this session's scratchpad, the mq://scratchpad resource, to build a report over many calls without passing it back each time. Append your own markdown, the results of an mq query on it, the results stored in a session variable, or an offloaded tool result, optionally under a heading.",
+        output_schema = output_schema::<ScratchpadOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    fn append_to_scratchpad(
+        &self,
+        Parameters(input): Parameters<AppendToScratchpadInput>,
+    ) -> McpResult {
+        let content = self.scratchpad_content(input)?;
+        let dry_run = dry_run::active();
+        let before = self.scratchpad.len();
+        let size = if content.is_empty() {
+            before
+        } else {
+            self.scratchpad.append(&content, dry_run)?
+        };
+        json_result(&ScratchpadOutput {
+            uri: SCRATCHPAD_URI.to_string(),
+            appended: size.saturating_sub(before),
+            size,
+            dry_run,
+        })
+    }
+
+    #[tool(
+        description = "Empty this session's scratchpad (the mq://scratchpad resource).",
+        output_schema = output_schema::<ScratchpadOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    fn clear_scratchpad(&self) -> McpResult {
+        let dry_run = dry_run::active();
+        if !dry_run {
+            self.scratchpad.clear();
+        }
+        json_result(&ScratchpadOutput {
+            uri: SCRATCHPAD_URI.to_string(),
+            appended: 0,
+            size: self.scratchpad.len(),
+            dry_run,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn append(server: &Server, input: serde_json::Value) -> Result<serde_json::Value, ErrorData> {
+        server
+            .append_to_scratchpad(Parameters(serde_json::from_value(input).unwrap()))
+            .map(|result| result.structured_content.unwrap())
+    }
+
+    #[test]
+    fn test_append() {
+        let server = Server::new(None).unwrap();
+        append(
+            &server,
+            serde_json::json!({ "markdown": "# Report\n", "heading": null }),
+        )
+        .unwrap();
+        let output = append(
+            &server,
+            serde_json::json!({
+                "markdown": "# Install\n\ntext\n\n# Usage",
+                "query": ".h1 | to_text()",
+                "heading": "Sections",
+            }),
+        )
+        .unwrap();
+        let text = server.scratchpad.text();
+        assert_eq!(text, "# Report\n\n## Sections\n\nInstall\n\nUsage\n");
+        assert_eq!(output["size"], text.len());
+        assert_eq!(server.scratchpad.resource().size, Some(text.len() as u64));
+
+        server.clear_scratchpad().unwrap();
+        assert_eq!(server.scratchpad.text(), "");
+    }
+
+    #[test]
+    fn test_append_a_stored_result() {
+        let server = Server::new(None).unwrap();
+        let uri = server.results.insert("A long result".to_string());
+        append(&server, serde_json::json!({ "result": uri })).unwrap();
+        assert_eq!(server.scratchpad.text(), "A long result\n");
+        assert!(append(&server, serde_json::json!({ "result": "mq://results/99" })).is_err());
+    }
+
+    #[test]
+    fn test_append_needs_one_source() {
+        let server = Server::new(None).unwrap();
+        assert!(append(&server, serde_json::json!({})).is_err());
+        assert!(
+            append(
+                &server,
+                serde_json::json!({ "markdown": "a", "variable": "titles" })
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_scratchpad_is_bounded() {
+        let scratchpad = Scratchpad::default();
+        scratchpad
+            .append(&"a".repeat(MAX_SCRATCHPAD_BYTES - 1), false)
+            .unwrap();
+        assert!(scratchpad.append("b", true).is_err());
+        assert!(scratchpad.append("b", false).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Splitting a document into heading-delimited sections, for the tools
+//! that compare or rank sections (`find_duplicates`, `rank_sections`).
+//!
+//! Each section has a stable id derived from its heading path, which tools
+//! return with the sections they find and editing tools accept in place of
+//! a position: an edit earlier in the document moves the lines after it,
+//! but leaves the ids of the sections after it as they were.
+
+use std::collections::HashMap;
+
+use mq_markdown::Node;
+use rmcp::ErrorData;
+use sha2::{Digest, Sha256};
+
+use super::parse_markdown_only;
+
+/// A heading and the body up to the next heading of any depth. Text
+/// before the first heading is a section without a title.
+#[derive(Debug, PartialEq)]
+pub(super) struct Section {
+    /// See [`section_id`].
+    pub id: String,
+    pub title: Option<String>,
+    /// The titles of the enclosing headings and this one; empty before the
+    /// first heading.
+    pub path: Vec<String>,
+    pub depth: u8,
+    /// 1-based line of the heading.
+    pub line: usize,
+    /// The heading and body as written in the source.
+    pub markdown: String,
+}
+
+/// The id of the `occurrence`-th (from 1) section with the heading path
+/// `path`: `s-` and 12 hex digits of a hash of both. It changes only when
+/// the section's heading or an enclosing one is renamed, or a section with
+/// the same path is added before it.
+pub(super) fn section_id(path: &[String], occurrence: usize) -> String {
+    let mut hasher = Sha256::new();
+    for title in path {
+        hasher.update(title.as_bytes());
+        hasher.update(b"\0");
+    }
+    hasher.update(occurrence.to_string().as_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+    format!("s-{}", &hash[..12])
+}
+
+/// Splits `markdown` at its top-level headings. Empty sections (a blank
+/// preamble) are dropped.
+pub(super) fn split(markdown: &str) -> Result<Vec<Section>, ErrorData> {
+    let headings: Vec<(usize, Option<String>, u8)> = parse_markdown_only(markdown)?
+        .iter()
+        .filter_map(|node| match node {
+            Node::Heading(heading) => heading.position.as_ref().map(|position| {
+                (
+                    position.start.line,
+                    Some(node.value().trim().to_string()),
+                    heading.depth,
+                )
+            }),
+            _ => None,
+        })
+        .collect();
+
+    let lines: Vec<&str> = markdown.lines().collect();
+    let starts = std::iter::once((1, None, 0)).chain(headings);
+    let mut sections: Vec<Section> = Vec::new();
+    for (line, title, depth) in starts {
+        if let Some(previous) = sections.last_mut() {
+            previous.markdown = lines[previous.line - 1..line - 1].join("\n");
+        }
+        sections.push(Section {
+            id: String::new(),
+            title,
+            path: Vec::new(),
+            depth,
+            line,
+            markdown: String::new(),
+        });
+    }
+    if let Some(last) = sections.last_mut() {
+        last.markdown = lines[(last.line - 1).min(lines.len())..].join("\n");
+    }
+    let mut parents: Vec<(u8, String)> = Vec::new();
+    let mut occurrences: HashMap<Vec<String>, usize> = HashMap::new();
+    for section in &mut sections {
+        section.markdown = section.markdown.trim_end().to_string();
+        if let Some(title) = &section.title {
+            while parents
+                .last()
+                .is_some_and(|(depth, _)| *depth >= section.depth)
+            {
+                parents.pop();
+            }
+            parents.push((section.depth, title.clone()));
+        }
+        section.path = parents.iter().map(|(_, title)| title.clone()).collect();
+        let occurrence = occurrences.entry(section.path.clone()).or_default();
+        *occurrence += 1;
+        section.id = section_id(&section.path, *occurrence);
+    }
+    sections.retain(|section| section.title.is_some() || !section.markdown.trim().is_empty());
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let markdown = "Intro text.\n\n# One\n\nBody.\n\n## Two\n\n```md\n# not a heading\n```\n";
+        assert_eq!(
+            split(markdown).unwrap(),
+            vec![
+                Section {
+                    id: section_id(&[], 1),
+                    title: None,
+                    path: Vec::new(),
+                    depth: 0,
+                    line: 1,
+                    markdown: "Intro text.".to_string(),
+                },
+                Section {
+                    id: section_id(&["One".to_string()], 1),
+                    title: Some("One".to_string()),
+                    path: vec!["One".to_string()],
+                    depth: 1,
+                    line: 3,
+                    markdown: "# One\n\nBody.".to_string(),
+                },
+                Section {
+                    id: section_id(&["One".to_string(), "Two".to_string()], 1),
+                    title: Some("Two".to_string()),
+                    path: vec!["One".to_string(), "Two".to_string()],
+                    depth: 2,
+                    line: 7,
+                    markdown: "## Two\n\n```md\n# not a heading\n```".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ids_survive_edits_elsewhere() {
+        let ids = |markdown: &str| -> Vec<String> {
+            split(markdown)
+                .unwrap()
+                .into_iter()
+                .map(|section| section.id)
+                .collect()
+        };
+        let before = ids("# Guide\n\n## Install\n\nRun it.\n\n## Usage\n");
+        let after = ids("# Guide\n\nNew intro.\n\n## Install\n\nRun it.\nTwice.\n\n## Usage\n");
+        assert_eq!(before, after);
+        assert!(before[2].starts_with("s-") && before[2].len() == 14);
+
+        let repeated = ids("## Example\n\n## Example\n");
+        assert_ne!(repeated[0], repeated[1]);
+        let renamed = ids("# Guide\n\n## Setup\n\n## Usage\n");
+        assert_ne!(before[1], renamed[1]);
+        assert_eq!(before[2], renamed[2]);
+    }
+
+    #[test]
+    fn test_split_drops_empty_preamble() {
+        let sections = split("\n# Only\n").unwrap();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].markdown, "# Only");
+    }
+}
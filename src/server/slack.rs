@@ -0,0 +1,375 @@
+//! `slack_to_markdown`: Slack messages (given as JSON, or a thread fetched
+//! with the Web API) converted from Slack's mrkdwn to markdown.
+
+use std::{fmt, sync::LazyLock};
+
+use regex::{Captures, Regex};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use rmcp::{ErrorData, handler::server::wrapper::Parameters, schemars, tool, tool_router};
+use serde_json::Value;
+
+use super::{
+    McpResult, Server,
+    fetch::parse_url,
+    output::{QueryOutput, output_schema},
+};
+
+pub(super) const API_URL: &str = "https://slack.com/api";
+/// Thread replies beyond this are not fetched.
+const MAX_MESSAGES: usize = 1000;
+
+static BOLD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(^|[^\w*])\*(\S(?:[^*\n]*\S)?)\*").unwrap());
+static ITALIC: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(^|[^\w_])_(\S(?:[^_\n]*\S)?)_").unwrap());
+static STRIKE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(^|[^\w~])~(\S(?:[^~\n]*\S)?)~").unwrap());
+static ANGLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<([^<>|\s]+)(?:\|([^<>]*))?>").unwrap());
+static BULLET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^(\s*)[•◦▪] ").unwrap());
+
+/// Server-side settings for fetching threads in `slack_to_markdown`.
+#[derive(Clone, Default)]
+pub struct SlackConfig {
+    /// Bot or user token with the `channels:history` (and, for private
+    /// channels, `groups:history`) scope.
+    pub token: Option<String>,
+}
+
+impl fmt::Debug for SlackConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlackConfig")
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl SlackConfig {
+    fn headers(&self) -> Result<HeaderMap, ErrorData> {
+        let Some(token) = &self.token else {
+            return Err(ErrorData::invalid_request(
+                "no Slack token configured — restart mq-mcp with --slack-token <token> to fetch threads",
+                None,
+            ));
+        };
+        let mut value = HeaderValue::from_str(&format!("Bearer {token}")).map_err(|_| {
+            ErrorData::internal_error("Invalid Slack token in the server configuration", None)
+        })?;
+        value.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, value);
+        Ok(headers)
+    }
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SlackToMarkdownInput {
+    #[schemars(
+        description = "Slack message JSON: a message object, an array of messages, or an API response with a `messages` array. Omit to fetch `thread` instead."
+    )]
+    pub messages: Option<Value>,
+    #[schemars(
+        description = "Permalink of a message whose thread to fetch, e.g. https://acme.slack.com/archives/C0123/p1700000000123456"
+    )]
+    pub thread: Option<String>,
+    #[schemars(
+        description = "The mq query to execute (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+}
+
+/// The channel id and message timestamp of a Slack permalink.
+fn parse_permalink(permalink: &str) -> Result<(String, String), ErrorData> {
+    let url = parse_url(permalink)?;
+    let segments: Vec<&str> = url
+        .path_segments()
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    // A reply's permalink names its thread in `thread_ts`.
+    let thread_ts = url
+        .query_pairs()
+        .find(|(key, _)| key == "thread_ts")
+        .map(|(_, ts)| ts.into_owned());
+    match segments.as_slice() {
+        ["archives", channel, message, ..] if message.len() > 7 && message.starts_with('p') => {
+            let digits = &message[1..];
+            let ts = thread_ts.unwrap_or_else(|| {
+                format!(
+                    "{}.{}",
+                    &digits[..digits.len() - 6],
+                    &digits[digits.len() - 6..]
+                )
+            });
+            Ok((channel.to_string(), ts))
+        }
+        _ => Err(ErrorData::invalid_params(
+            "Invalid Slack permalink",
+            Some(serde_json::json!({ "thread": permalink })),
+        )),
+    }
+}
+
+/// Converts the mrkdwn outside code spans and blocks to markdown.
+fn mrkdwn_to_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, block) in text.split("```").enumerate() {
+        if i % 2 == 1 {
+            // Slack allows text right after the fence; markdown needs a
+            // line break.
+            let code = block.trim_matches('\n');
+            out.push_str(&format!("\n```\n{}\n```\n", unescape(code)));
+            continue;
+        }
+        for (j, span) in block.split('`').enumerate() {
+            if j % 2 == 1 {
+                out.push_str(&format!("`{}`", unescape(span)));
+            } else {
+                out.push_str(&convert_inline(span));
+            }
+        }
+    }
+    out.trim().to_string()
+}
+
+fn convert_inline(text: &str) -> String {
+    let text = ANGLE.replace_all(text, |caps: &Captures| {
+        let target = &caps[1];
+        let label = caps.get(2).map(|label| label.as_str());
+        match (target.chars().next(), label) {
+            (Some('@'), Some(label)) => format!("@{}", label.trim_start_matches('@')),
+            (Some('@'), None) => target.to_string(),
+            (Some('#'), Some(label)) => format!("#{label}"),
+            (Some('#'), None) => target.to_string(),
+            // `<!here>`, `<!subteam^ID|@team>`, `<!date^...|fallback>`
+            (Some('!'), Some(label)) => label.to_string(),
+            (Some('!'), None) => format!("@{}", &target[1..]),
+            (_, Some(label)) if !label.is_empty() => format!("[{label}]({target})"),
+            _ => format!("<{target}>"),
+        }
+    });
+    let text = BOLD.replace_all(&text, "$1**$2**");
+    let text = ITALIC.replace_all(&text, "$1*$2*");
+    let text = STRIKE.replace_all(&text, "$1~~$2~~");
+    let text = BULLET.replace_all(&text, "$1- ");
+    unescape(&text)
+}
+
+/// Slack escapes only these three characters.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn author(message: &Value) -> &str {
+    [
+        &message["user_profile"]["real_name"],
+        &message["user_profile"]["display_name"],
+        &message["username"],
+        &message["bot_profile"]["name"],
+        &message["user"],
+    ]
+    .into_iter()
+    .find_map(|name| name.as_str().filter(|name| !name.is_empty()))
+    .unwrap_or("unknown")
+}
+
+fn timestamp(ts: &str) -> Option<String> {
+    let seconds = ts.split('.').next()?.parse().ok()?;
+    chrono::DateTime::from_timestamp(seconds, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+}
+
+/// Renders messages as a section each: a heading with the author and time,
+/// the converted text, and links to attached files.
+fn messages_to_markdown(messages: &[Value]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let mut heading = format!("### {}", author(message));
+            if let Some(time) = message["ts"].as_str().and_then(timestamp) {
+                heading.push_str(&format!(" — {time}"));
+            }
+            let mut parts = vec![heading];
+            let text = mrkdwn_to_markdown(message["text"].as_str().unwrap_or_default());
+            if !text.is_empty() {
+                parts.push(text);
+            }
+            let files: Vec<String> = message["files"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|file| {
+                    let name = file["name"].as_str().or(file["title"].as_str())?;
+                    let url = file["permalink"]
+                        .as_str()
+                        .or(file["url_private"].as_str())?;
+                    Some(format!("- [{name}]({url})"))
+                })
+                .collect();
+            if !files.is_empty() {
+                parts.push(files.join("\n"));
+            }
+            parts.join("\n\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The messages in a JSON value given to the tool.
+fn messages_from_json(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(messages) => messages,
+        Value::Object(mut object) => match object.remove("messages") {
+            Some(Value::Array(messages)) => messages,
+            _ => vec![Value::Object(object)],
+        },
+        Value::String(json) => serde_json::from_str(&json)
+            .map(messages_from_json)
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+impl Server {
+    /// Fetches a thread with `conversations.replies`, following pagination.
+    async fn slack_thread(&self, channel: &str, ts: &str) -> Result<Vec<Value>, ErrorData> {
+        let headers = self.config.slack.headers()?;
+        let mut messages = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut url = parse_url(&format!("{API_URL}/conversations.replies"))?;
+            url.query_pairs_mut()
+                .append_pair("channel", channel)
+                .append_pair("ts", ts)
+                .append_pair("limit", "200");
+            if let Some(cursor) = &cursor {
+                url.query_pairs_mut().append_pair("cursor", cursor);
+            }
+            let page = self.fetcher.fetch_api(url, headers.clone()).await?;
+            let response: Value = serde_json::from_str(&page.text).map_err(|e| {
+                ErrorData::internal_error(
+                    "Invalid Slack API response",
+                    Some(serde_json::json!({ "error": e.to_string() })),
+                )
+            })?;
+            if response["ok"] != true {
+                return Err(ErrorData::invalid_params(
+                    "Slack API error",
+                    Some(serde_json::json!({ "error": response["error"] })),
+                ));
+            }
+            messages.extend(
+                response["messages"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .cloned(),
+            );
+            match response["response_metadata"]["next_cursor"].as_str() {
+                Some(next) if !next.is_empty() && messages.len() < MAX_MESSAGES => {
+                    cursor = Some(next.to_string());
+                }
+                _ => break,
+            }
+        }
+        messages.truncate(MAX_MESSAGES);
+        Ok(messages)
+    }
+}
+
+#[tool_router(router = slack_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Convert Slack messages to markdown and run an mq query on them. Pass the message JSON, or a message permalink to fetch its thread through the Slack API (requires --slack-token). Slack's mrkdwn (*bold*, _italic_, ~strike~, <url|label> links, mentions) becomes standard markdown; each message is a section headed by its author and time.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = true)
+    )]
+    async fn slack_to_markdown(
+        &self,
+        Parameters(input): Parameters<SlackToMarkdownInput>,
+    ) -> McpResult {
+        let messages = match (input.messages, input.thread.as_deref()) {
+            (Some(messages), _) => messages_from_json(messages),
+            (None, Some(thread)) => {
+                let (channel, ts) = parse_permalink(thread)?;
+                self.slack_thread(&channel, &ts).await?
+            }
+            (None, None) => {
+                return Err(ErrorData::invalid_params(
+                    "Either `messages` or `thread` is required",
+                    None,
+                ));
+            }
+        };
+        self.eval_query(
+            &messages_to_markdown(&messages),
+            input.query.as_deref().unwrap_or("identity()"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("*Deploy* is _done_ ~maybe~", "**Deploy** is *done* ~~maybe~~")]
+    #[case(
+        "see <https://mqlang.org|the docs> and <https://x.test>",
+        "see [the docs](https://mqlang.org) and <https://x.test>"
+    )]
+    #[case(
+        "<@U123|alice> in <#C456|general>, <!here>",
+        "@alice in #general, @here"
+    )]
+    #[case(
+        "run `a*b*c` then```\nmake *all*```",
+        "run `a*b*c` then\n```\nmake *all*\n```"
+    )]
+    #[case("&gt; quoted &amp; escaped", "> quoted & escaped")]
+    #[case("• one\n• two", "- one\n- two")]
+    #[case("snake_case_name and 2*3*4", "snake_case_name and 2*3*4")]
+    fn test_mrkdwn_to_markdown(#[case] mrkdwn: &str, #[case] expected: &str) {
+        assert_eq!(mrkdwn_to_markdown(mrkdwn), expected);
+    }
+
+    #[rstest]
+    #[case("https://acme.slack.com/archives/C0123/p1700000000123456", Some(("C0123", "1700000000.123456")))]
+    #[case(
+        "https://acme.slack.com/archives/C0123/p1700000001000000?thread_ts=1700000000.123456&cid=C0123",
+        Some(("C0123", "1700000000.123456"))
+    )]
+    #[case("https://acme.slack.com/client/T1/C0123", None)]
+    fn test_parse_permalink(#[case] permalink: &str, #[case] expected: Option<(&str, &str)>) {
+        let parsed = parse_permalink(permalink).ok();
+        assert_eq!(
+            parsed.as_ref().map(|(c, ts)| (c.as_str(), ts.as_str())),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_messages_to_markdown() {
+        let messages = messages_from_json(serde_json::json!({
+            "ok": true,
+            "messages": [
+                { "user": "U1", "user_profile": { "real_name": "Alice" }, "ts": "1700000000.000100", "text": "Ship *today*?" },
+                { "username": "deploybot", "ts": "1700000060.000200", "text": "",
+                  "files": [{ "name": "log.txt", "permalink": "https://acme.slack.com/files/log" }] },
+            ]
+        }));
+        assert_eq!(
+            messages_to_markdown(&messages),
+            "\
+### Alice — 2023-11-14 22:13 UTC
+
+Ship **today**?
+
+### deploybot — 2023-11-14 22:14 UTC
+
+- [log.txt](https://acme.slack.com/files/log)"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Ordering of query results. Results come back in document order (the
+//! order of the input nodes, and within a node the order the query yields
+//! them) unless an extraction tool is asked to sort them with `sort_by`.
+//! Sorting is stable, so results with equal keys keep document order.
+
+use std::cmp::Ordering;
+
+use mq_markdown::Node;
+use rmcp::{ErrorData, schemars};
+
+use super::{
+    engine::{QueryEngine, QueryValue, Value},
+    new_engine,
+    suggest::query_error,
+};
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SortBy {
+    /// The order the results appear in the document.
+    #[default]
+    Document,
+    /// Heading level (`#` first); results that are not headings last.
+    Level,
+    /// Plain text, case-insensitively.
+    Text,
+    /// The value of the mq expression in `sort_key`, run on each result.
+    Expression,
+}
+
+#[derive(Debug, Default, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SortOptions {
+    #[schemars(
+        description = "Order of the results: `document` (default), `level` (heading level, non-headings last), `text` (plain text, case-insensitive) or `expression` (the value of `sort_key`). Sorting is stable: ties keep document order"
+    )]
+    pub sort_by: Option<SortBy>,
+    #[schemars(
+        description = "mq expression computing each result's sort key when `sort_by` is `expression`, e.g. `to_text() | len()`; results without a key go last"
+    )]
+    pub sort_key: Option<String>,
+    #[schemars(description = "Reverse the order (default: false)")]
+    pub descending: Option<bool>,
+}
+
+/// A result's sort key. Keys of different variants compare equal.
+enum Key {
+    Level(u8),
+    Number(f64),
+    Text(String),
+    Value(Value),
+}
+
+impl Key {
+    fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Key::Level(a), Key::Level(b)) => a.cmp(b),
+            (Key::Number(a), Key::Number(b)) => a.total_cmp(b),
+            (Key::Text(a), Key::Text(b)) => a.cmp(b),
+            (Key::Value(a), Key::Value(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+impl SortOptions {
+    /// Stably reorders `values`, each paired with its index among the
+    /// query's results. Results without a key go last in either direction.
+    pub(super) fn sort(&self, values: &mut Vec<(usize, Value)>) -> Result<(), ErrorData> {
+        let sort_by = self.sort_by.unwrap_or_default();
+        let descending = self.descending.unwrap_or(false);
+        if sort_by == SortBy::Document {
+            if descending {
+                values.reverse();
+            }
+            return Ok(());
+        }
+        let mut keyed = values
+            .drain(..)
+            .map(|(index, value)| Ok((self.key(sort_by, &value)?, index, value)))
+            .collect::<Result<Vec<_>, ErrorData>>()?;
+        keyed.sort_by(|(a, ..), (b, ..)| match (a, b) {
+            (Some(a), Some(b)) if descending => b.compare(a),
+            (Some(a), Some(b)) => a.compare(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        values.extend(keyed.into_iter().map(|(_, index, value)| (index, value)));
+        Ok(())
+    }
+
+    fn key(&self, sort_by: SortBy, value: &Value) -> Result<Option<Key>, ErrorData> {
+        Ok(match sort_by {
+            SortBy::Document => None,
+            SortBy::Level => match value.node() {
+                Some(Node::Heading(heading)) => Some(Key::Level(heading.depth)),
+                _ => None,
+            },
+            SortBy::Text => Some(Key::Text(
+                match value.node() {
+                    Some(node) => node.value(),
+                    None => value.to_string(),
+                }
+                .trim()
+                .to_lowercase(),
+            )),
+            SortBy::Expression => {
+                let Some(expression) = self.sort_key.as_deref() else {
+                    return Err(ErrorData::invalid_params(
+                        "`sort_by: expression` requires a `sort_key` expression",
+                        None,
+                    ));
+                };
+                new_engine()
+                    .eval(expression, std::iter::once(value.clone()))
+                    .map_err(|e| query_error(expression, e))?
+                    .into_iter()
+                    .next()
+                    .filter(|key| !key.is_none())
+                    .map(|key| match key.node() {
+                        // Run on a markdown node, the expression returns
+                        // numbers and strings as text nodes.
+                        Some(node) => {
+                            let text = node.value();
+                            match text.trim().parse() {
+                                Ok(number) => Key::Number(number),
+                                Err(_) => Key::Text(text),
+                            }
+                        }
+                        None => Key::Value(key),
+                    })
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::parse_markdown_only;
+    use rstest::rstest;
+
+    fn sorted(markdown: &str, options: SortOptions) -> Vec<String> {
+        let mut values: Vec<(usize, Value)> = parse_markdown_only(markdown)
+            .unwrap()
+            .into_iter()
+            .map(Value::from)
+            .enumerate()
+            .collect();
+        options.sort(&mut values).unwrap();
+        values
+            .into_iter()
+            .map(|(_, value)| value.to_string().trim().to_string())
+            .collect()
+    }
+
+    #[rstest]
+    #[case(SortBy::Document, false, vec!["## b", "# C", "### a", "text", "# A"])]
+    #[case(SortBy::Document, true, vec!["# A", "text", "### a", "# C", "## b"])]
+    #[case(SortBy::Level, false, vec!["# C", "# A", "## b", "### a", "text"])]
+    #[case(SortBy::Level, true, vec!["### a", "## b", "# C", "# A", "text"])]
+    #[case(SortBy::Text, false, vec!["### a", "# A", "## b", "# C", "text"])]
+    fn test_sort(#[case] sort_by: SortBy, #[case] descending: bool, #[case] expected: Vec<&str>) {
+        let options = SortOptions {
+            sort_by: Some(sort_by),
+            descending: Some(descending),
+            ..Default::default()
+        };
+        assert_eq!(
+            sorted("## b\n\n# C\n\n### a\n\ntext\n\n# A", options),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_sort_by_expression() {
+        let options = SortOptions {
+            sort_by: Some(SortBy::Expression),
+            sort_key: Some("to_text() | len()".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            sorted("# Three\n\n# A\n\n# Twelve chars", options),
+            vec!["# A", "# Three", "# Twelve chars"]
+        );
+
+        let options = SortOptions {
+            sort_by: Some(SortBy::Expression),
+            ..Default::default()
+        };
+        let mut values = vec![(0, Value::string("a".to_string()))];
+        assert!(options.sort(&mut values).is_err());
+    }
+}
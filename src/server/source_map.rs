@@ -0,0 +1,457 @@
+//! Rewrites of a document by span, and the ways transform tools return
+//! them.
+//!
+//! Transform tools that replace spans of their input build their output
+//! with a [`Rewrite`], which records where each replacement went. Per
+//! [`RewriteOptions`], they return the whole rewritten document, a unified
+//! diff against the input or the list of replacements, so that reviewing or
+//! applying a small change to a large file doesn't take the whole file.
+//! The optional source map lists the output as consecutive ranges, each
+//! with the input range it came from and whether it was copied or
+//! replaced, so an editor can apply only the replaced ranges to its buffer.
+
+use std::ops::Range;
+
+use rmcp::{schemars, serde::Serialize};
+
+/// Lines of context around the changes of a diff.
+const DIFF_CONTEXT: usize = 3;
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RewriteFormat {
+    /// The whole rewritten document.
+    #[default]
+    Document,
+    /// A unified diff against the input.
+    Diff,
+    /// The replacements to make to the input.
+    Edits,
+}
+
+#[derive(Debug, Default, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct RewriteOptions {
+    #[schemars(
+        description = "`document` (default) for the whole rewritten document in `markdown`, `diff` for a unified diff against the input in `diff`, or `edits` for the list of replacements to make to the input in `edits`"
+    )]
+    pub output: Option<RewriteFormat>,
+    #[schemars(
+        description = "Also return a source map linking ranges of the output to the ranges of the input they copy or replace, to apply the result as edits (default: false)"
+    )]
+    pub source_map: Option<bool>,
+}
+
+/// A position in a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+pub(crate) struct Location {
+    #[schemars(description = "Byte offset")]
+    pub offset: usize,
+    #[schemars(description = "1-based line")]
+    pub line: usize,
+    #[schemars(description = "1-based column, in characters")]
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+pub(crate) struct SourceRange {
+    pub start: Location,
+    #[schemars(description = "Exclusive")]
+    pub end: Location,
+}
+
+/// A range of the output and the range of the input it came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+pub(crate) struct Mapping {
+    pub output: SourceRange,
+    pub input: SourceRange,
+    #[schemars(
+        description = "Whether the output range replaces the input range; if not, it is a copy of it"
+    )]
+    pub changed: bool,
+}
+
+/// A replacement of a range of the input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+pub(crate) struct Edit {
+    pub range: SourceRange,
+    #[schemars(description = "The text replacing the range")]
+    pub text: String,
+}
+
+/// The rewritten document, as requested with [`RewriteOptions`].
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct RewriteOutput {
+    #[schemars(description = "The rewritten document, with `output: document`")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markdown: Option<String>,
+    #[schemars(
+        description = "A unified diff from the input to the rewritten document, with `output: diff`; empty if nothing changed"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    #[schemars(
+        description = "The replacements turning the input into the rewritten document, in order and without overlaps, with `output: edits`"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edits: Option<Vec<Edit>>,
+    #[schemars(
+        description = "The rewritten document's ranges and the input ranges they come from, when requested"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_map: Option<Vec<Mapping>>,
+}
+
+/// Byte offsets of the starts of a text's lines.
+struct Lines<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, starts }
+    }
+
+    fn location(&self, offset: usize) -> Location {
+        let line = self.starts.partition_point(|&start| start <= offset);
+        let start = self.starts[line - 1];
+        Location {
+            offset,
+            line,
+            column: self.text[start..offset].chars().count() + 1,
+        }
+    }
+
+    fn range(&self, range: &Range<usize>) -> SourceRange {
+        SourceRange {
+            start: self.location(range.start),
+            end: self.location(range.end),
+        }
+    }
+}
+
+/// A document being rebuilt from its input, left to right: spans of the
+/// input are replaced, and what lies between them is copied.
+pub(crate) struct Rewrite<'a> {
+    input: &'a str,
+    output: String,
+    copied: usize,
+    /// Replaced input ranges and their output ranges.
+    edits: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl<'a> Rewrite<'a> {
+    pub(crate) fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            output: String::with_capacity(input.len()),
+            copied: 0,
+            edits: Vec::new(),
+        }
+    }
+
+    /// Replaces `range` of the input, which must start after the ranges
+    /// replaced so far, with `text`. Replacing a span with its own text
+    /// leaves it a copy.
+    pub(crate) fn replace(&mut self, range: Range<usize>, text: &str) {
+        debug_assert!(range.start >= self.copied, "replacements must be in order");
+        if self.input[range.clone()] == *text {
+            return;
+        }
+        self.output.push_str(&self.input[self.copied..range.start]);
+        let start = self.output.len();
+        self.output.push_str(text);
+        self.edits.push((range.clone(), start..self.output.len()));
+        self.copied = range.end;
+    }
+
+    /// Copies the rest of the input.
+    pub(crate) fn finish(mut self) -> Rewritten<'a> {
+        self.output.push_str(&self.input[self.copied..]);
+        Rewritten {
+            input: self.input,
+            output: self.output,
+            edits: self.edits,
+        }
+    }
+}
+
+/// The result of a [`Rewrite`].
+pub(crate) struct Rewritten<'a> {
+    input: &'a str,
+    pub output: String,
+    edits: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl Rewritten<'_> {
+    /// The output as consecutive ranges, each mapped to the input range it
+    /// was copied from or replaces. Empty copies are left out.
+    pub(crate) fn source_map(&self) -> Vec<Mapping> {
+        let input = Lines::new(self.input);
+        let output = Lines::new(&self.output);
+        let mut mappings = Vec::with_capacity(self.edits.len() * 2 + 1);
+        let copy = |from: Range<usize>, to: Range<usize>, mappings: &mut Vec<Mapping>| {
+            if !from.is_empty() {
+                mappings.push(Mapping {
+                    output: output.range(&to),
+                    input: input.range(&from),
+                    changed: false,
+                });
+            }
+        };
+        let (mut input_end, mut output_end) = (0, 0);
+        for (from, to) in &self.edits {
+            copy(input_end..from.start, output_end..to.start, &mut mappings);
+            mappings.push(Mapping {
+                output: output.range(to),
+                input: input.range(from),
+                changed: true,
+            });
+            (input_end, output_end) = (from.end, to.end);
+        }
+        copy(
+            input_end..self.input.len(),
+            output_end..self.output.len(),
+            &mut mappings,
+        );
+        mappings
+    }
+
+    /// The replacements of the rewrite, with their ranges in the input.
+    pub(crate) fn edits(&self) -> Vec<Edit> {
+        let input = Lines::new(self.input);
+        self.edits
+            .iter()
+            .map(|(from, to)| Edit {
+                range: input.range(from),
+                text: self.output[to.clone()].to_string(),
+            })
+            .collect()
+    }
+
+    /// The byte ranges of whole lines of the input and the output that the
+    /// edits change, edits on the same lines grouped together.
+    fn changed_lines(&self) -> Vec<(Range<usize>, Range<usize>)> {
+        let whole = |text: &str| text.is_empty() || text.ends_with('\n');
+        let mut blocks: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+        for (from, to) in &self.edits {
+            let old_start = line_start(self.input, from.start);
+            let new_start = line_start(&self.output, to.start);
+            // Edits of whole lines end there; others take the rest of the
+            // line they end in.
+            let (old_end, new_end) = if whole(&self.input[old_start..from.end])
+                && whole(&self.output[new_start..to.end])
+            {
+                (from.end, to.end)
+            } else {
+                (
+                    line_end(self.input, from.end),
+                    line_end(&self.output, to.end),
+                )
+            };
+            match blocks.last_mut() {
+                Some((old, new)) if old_start < old.end => {
+                    old.end = old.end.max(old_end);
+                    new.end = new.end.max(new_end);
+                }
+                _ => blocks.push((old_start..old_end, new_start..new_end)),
+            }
+        }
+        blocks
+    }
+
+    /// A unified diff from the input to the output, with [`DIFF_CONTEXT`]
+    /// lines of context. Empty if the rewrite changed nothing.
+    pub(crate) fn diff(&self) -> String {
+        let line_range = |text: &str, range: &Range<usize>| {
+            let start = text[..range.start].matches('\n').count();
+            start..start + text[range.clone()].split_inclusive('\n').count()
+        };
+        let changes: Vec<_> = self
+            .changed_lines()
+            .iter()
+            .map(|(old, new)| (line_range(self.input, old), line_range(&self.output, new)))
+            .collect();
+        if changes.is_empty() {
+            return String::new();
+        }
+        let input: Vec<&str> = self.input.split_inclusive('\n').collect();
+        let output: Vec<&str> = self.output.split_inclusive('\n').collect();
+
+        let mut diff = String::from("--- input\n+++ output\n");
+        let mut rest = changes.as_slice();
+        while let Some(first) = rest.first() {
+            // Changes whose contexts touch make one hunk.
+            let count = rest
+                .windows(2)
+                .take_while(|pair| pair[1].0.start - pair[0].0.end <= 2 * DIFF_CONTEXT)
+                .count()
+                + 1;
+            let (hunk, next) = rest.split_at(count);
+            rest = next;
+            let last = &hunk[count - 1];
+
+            let old_start = first.0.start.saturating_sub(DIFF_CONTEXT);
+            let old_end = (last.0.end + DIFF_CONTEXT).min(input.len());
+            let new_start = first.1.start - (first.0.start - old_start);
+            let new_end = last.1.end + (old_end - last.0.end);
+            diff.push_str(&format!(
+                "@@ -{} +{} @@\n",
+                hunk_range(old_start, old_end),
+                hunk_range(new_start, new_end)
+            ));
+            let mut line = old_start;
+            for (old, new) in hunk {
+                push_lines(&mut diff, ' ', &input[line..old.start]);
+                push_lines(&mut diff, '-', &input[old.clone()]);
+                push_lines(&mut diff, '+', &output[new.clone()]);
+                line = old.end;
+            }
+            push_lines(&mut diff, ' ', &input[line..old_end]);
+        }
+        diff
+    }
+
+    /// The output in the format `options` asks for.
+    pub(crate) fn render(self, options: &RewriteOptions) -> RewriteOutput {
+        let format = options.output.unwrap_or_default();
+        RewriteOutput {
+            diff: (format == RewriteFormat::Diff).then(|| self.diff()),
+            edits: (format == RewriteFormat::Edits).then(|| self.edits()),
+            source_map: options
+                .source_map
+                .unwrap_or(false)
+                .then(|| self.source_map()),
+            markdown: (format == RewriteFormat::Document).then_some(self.output),
+        }
+    }
+}
+
+fn line_start(text: &str, offset: usize) -> usize {
+    text[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// The offset past the end of the line `offset` is in, or starts if it is
+/// at the start of one.
+fn line_end(text: &str, offset: usize) -> usize {
+    text[offset..]
+        .find('\n')
+        .map_or(text.len(), |i| offset + i + 1)
+}
+
+/// A hunk header's `start,count` for the 0-based lines `start..end`.
+fn hunk_range(start: usize, end: usize) -> String {
+    // An empty range is given by the line before it.
+    let first = if start == end { start } else { start + 1 };
+    format!("{first},{}", end - start)
+}
+
+fn push_lines(diff: &mut String, prefix: char, lines: &[&str]) {
+    for line in lines {
+        diff.push(prefix);
+        diff.push_str(line);
+        if !line.ends_with('\n') {
+            diff.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn rewrite<'a>(input: &'a str, replacements: &[(Range<usize>, &str)]) -> Rewritten<'a> {
+        let mut rewrite = Rewrite::new(input);
+        for (range, text) in replacements {
+            rewrite.replace(range.clone(), text);
+        }
+        rewrite.finish()
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let rewritten = rewrite("# Title\n\nSome text.\n", &[(2..7, "Titre"), (9..13, "Du")]);
+        assert_eq!(rewritten.output, "# Titre\n\nDu text.\n");
+        let map = rewritten.source_map();
+        let ranges: Vec<_> = map
+            .iter()
+            .map(|mapping| {
+                (
+                    mapping.input.start.offset..mapping.input.end.offset,
+                    mapping.output.start.offset..mapping.output.end.offset,
+                    mapping.changed,
+                )
+            })
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                (0..2, 0..2, false),
+                (2..7, 2..7, true),
+                (7..9, 7..9, false),
+                (9..13, 9..11, true),
+                (13..20, 11..18, false),
+            ]
+        );
+        assert_eq!(
+            map[3].output,
+            SourceRange {
+                start: Location {
+                    offset: 9,
+                    line: 3,
+                    column: 1
+                },
+                end: Location {
+                    offset: 11,
+                    line: 3,
+                    column: 3
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_unchanged_replacements_are_copies() {
+        let rewritten = rewrite("é and é", &[(0..2, "é"), (7..9, "e")]);
+        assert_eq!(rewritten.output, "é and e");
+        let map = rewritten.source_map();
+        assert_eq!(map.len(), 2);
+        assert!(!map[0].changed);
+        assert_eq!(map[1].input.start.column, 7);
+        assert_eq!(map[1].output.end.offset, 8);
+    }
+
+    #[rstest]
+    #[case("a\nb\nc\n", &[(2..3, "B")], "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n")]
+    #[case("a\nb\nc\n", &[(2..4, "")], "@@ -1,3 +1,2 @@\n a\n-b\n c\n")]
+    #[case("a\n", &[(0..0, "x\n")], "@@ -1,1 +1,2 @@\n+x\n a\n")]
+    #[case(
+        "a\nb",
+        &[(2..3, "c")],
+        "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n\\ No newline at end of file\n"
+    )]
+    #[case(
+        "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n",
+        &[(0..1, "one"), (18..20, "ten")],
+        "@@ -1,4 +1,4 @@\n-1\n+one\n 2\n 3\n 4\n@@ -7,4 +7,4 @@\n 7\n 8\n 9\n-10\n+ten\n"
+    )]
+    fn test_diff(
+        #[case] input: &str,
+        #[case] replacements: &[(Range<usize>, &str)],
+        #[case] hunks: &str,
+    ) {
+        let diff = rewrite(input, replacements).diff();
+        assert_eq!(diff, format!("--- input\n+++ output\n{hunks}"));
+    }
+
+    #[test]
+    fn test_diff_without_changes_is_empty() {
+        assert_eq!(rewrite("a\n", &[(0..1, "a")]).diff(), "");
+    }
+}
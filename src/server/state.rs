@@ -0,0 +1,429 @@
+//! Export and import of the server's state: `export_state` dumps the saved
+//! queries, scheduled pipelines and settings as a JSON bundle, and
+//! `import_state` loads one, so a team can keep its mq-mcp setup under
+//! version control and share it. `mq-mcp export-state` and `mq-mcp
+//! import-state` call them on the `--state` file without starting a server.
+//!
+//! Settings come from command-line flags, so importing a bundle doesn't
+//! change them; the import reports which differ from the server's. Bundles
+//! never contain credentials.
+
+#[cfg(feature = "fetch")]
+use std::collections::BTreeSet;
+
+use chrono::Utc;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+#[cfg(feature = "fetch")]
+use super::schedule::{MAX_PIPELINES, SchedulePipelineInput};
+use super::{
+    McpResult, PostProcess, Server, ServerConfig, dry_run,
+    output::{json_result, output_schema},
+    saved_queries::{check_name, check_query},
+    storage::SavedQuery,
+};
+
+/// Builds without the `fetch` feature can't schedule pipelines; they export
+/// the stored ones as they are.
+#[cfg(not(feature = "fetch"))]
+type SchedulePipelineInput = serde_json::Value;
+
+/// Version of the bundle format written by `export_state`.
+const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, rmcp::serde::Deserialize, Serialize, schemars::JsonSchema)]
+pub(crate) struct ExportedQuery {
+    pub name: String,
+    pub query: String,
+    pub description: Option<String>,
+}
+
+/// The server settings recorded in a bundle, named after the flags that
+/// set them.
+#[derive(Debug, Clone, PartialEq, rmcp::serde::Deserialize, Serialize, schemars::JsonSchema)]
+pub(crate) struct StateSettings {
+    pub locale: String,
+    pub resource_threshold: Option<usize>,
+    pub provenance: bool,
+    pub postprocess: PostProcess,
+    pub hourly_quota: Option<u64>,
+    pub daily_quota: Option<u64>,
+    #[schemars(description = "In seconds")]
+    pub idempotency_ttl: Option<u64>,
+    #[serde(default)]
+    pub hide_deprecated_tools: bool,
+    #[serde(default)]
+    pub cookbook: bool,
+    #[cfg(feature = "fetch")]
+    pub allowed_fetch_headers: Vec<String>,
+    #[cfg(feature = "fetch")]
+    #[schemars(description = "In seconds")]
+    pub fetch_cache_ttl: Option<u64>,
+    #[cfg(feature = "fetch")]
+    pub ignore_robots_txt: bool,
+    #[cfg(feature = "fetch")]
+    pub webhook_hosts: Vec<String>,
+}
+
+impl From<&ServerConfig> for StateSettings {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            locale: config.locale.to_string(),
+            resource_threshold: config.resource_threshold,
+            provenance: config.provenance,
+            postprocess: config.postprocess,
+            hourly_quota: config.quotas.hourly,
+            daily_quota: config.quotas.daily,
+            idempotency_ttl: config.idempotency_ttl.map(|ttl| ttl.as_secs()),
+            hide_deprecated_tools: config.hide_deprecated_tools,
+            cookbook: config.cookbook,
+            #[cfg(feature = "fetch")]
+            allowed_fetch_headers: config.fetch.allowed_headers.clone(),
+            #[cfg(feature = "fetch")]
+            fetch_cache_ttl: config.fetch.cache_ttl.map(|ttl| ttl.as_secs()),
+            #[cfg(feature = "fetch")]
+            ignore_robots_txt: config.fetch.ignore_robots,
+            #[cfg(feature = "fetch")]
+            webhook_hosts: config.webhook.allowed_hosts.clone(),
+        }
+    }
+}
+
+impl StateSettings {
+    /// Names of the settings whose values differ from `other`'s.
+    fn differing(&self, other: &Self) -> Vec<String> {
+        let (Ok(serde_json::Value::Object(ours)), Ok(serde_json::Value::Object(theirs))) =
+            (serde_json::to_value(self), serde_json::to_value(other))
+        else {
+            return Vec::new();
+        };
+        ours.into_iter()
+            .filter(|(name, value)| theirs.get(name) != Some(value))
+            .map(|(name, _)| name)
+            .collect()
+    }
+}
+
+/// The JSON bundle written by `export_state` and read by `import_state`.
+#[derive(Debug, Clone, rmcp::serde::Deserialize, Serialize, schemars::JsonSchema)]
+pub(crate) struct StateBundle {
+    #[schemars(description = "Bundle format version; currently 1")]
+    pub version: u32,
+    #[schemars(description = "When the bundle was exported (RFC 3339)")]
+    #[serde(default)]
+    pub exported_at: Option<String>,
+    #[serde(default)]
+    pub saved_queries: Vec<ExportedQuery>,
+    #[schemars(description = "Scheduled pipelines, as given to schedule_pipeline")]
+    #[serde(default)]
+    pub pipelines: Vec<SchedulePipelineInput>,
+    #[schemars(
+        description = "Settings of the exporting server; informational, as settings come from command-line flags"
+    )]
+    #[serde(default)]
+    pub settings: Option<StateSettings>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ImportStateInput {
+    #[schemars(description = "A bundle written by export_state")]
+    pub bundle: StateBundle,
+    #[schemars(
+        description = "Delete the saved queries and pipelines that aren't in the bundle (default: false, which keeps them and replaces those of the same name)"
+    )]
+    pub replace: Option<bool>,
+}
+
+/// Output of `import_state`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ImportStateOutput {
+    #[schemars(description = "Number of saved queries imported")]
+    pub saved_queries: usize,
+    #[schemars(description = "Number of pipelines imported")]
+    pub pipelines: usize,
+    #[schemars(
+        description = "Whether the pipelines were scheduled now; otherwise they are the next time the server starts with --http"
+    )]
+    pub scheduled: bool,
+    #[schemars(
+        description = "Settings of the bundle that differ from this server's, which the import doesn't change"
+    )]
+    pub differing_settings: Vec<String>,
+    #[schemars(description = "Whether this was a dry run that left the state unchanged")]
+    pub dry_run: bool,
+}
+
+impl Server {
+    /// The server's saved queries, pipelines and settings as a bundle.
+    fn state_bundle(&self) -> Result<StateBundle, ErrorData> {
+        let saved_queries = self
+            .storage
+            .saved_queries()?
+            .into_iter()
+            .map(|saved| ExportedQuery {
+                name: saved.name,
+                query: saved.query,
+                description: saved.description,
+            })
+            .collect();
+        let pipelines = self
+            .storage
+            .pipelines()?
+            .into_iter()
+            .filter_map(|(name, definition)| {
+                serde_json::from_str(&definition)
+                    .inspect_err(|e| tracing::warn!("not exporting scheduled pipeline {name}: {e}"))
+                    .ok()
+            })
+            .collect();
+        Ok(StateBundle {
+            version: BUNDLE_VERSION,
+            exported_at: Some(Utc::now().to_rfc3339()),
+            saved_queries,
+            pipelines,
+            settings: Some(StateSettings::from(self.config.as_ref())),
+        })
+    }
+
+    /// Checks every query and pipeline of `bundle`, then saves them all in
+    /// the state store, scheduling the pipelines if the scheduler runs in
+    /// this process.
+    async fn import_bundle(
+        &self,
+        bundle: StateBundle,
+        replace: bool,
+    ) -> Result<ImportStateOutput, ErrorData> {
+        if bundle.version != BUNDLE_VERSION {
+            return Err(ErrorData::invalid_params(
+                "Unsupported state bundle version",
+                Some(serde_json::json!({
+                    "version": bundle.version,
+                    "supported": BUNDLE_VERSION,
+                })),
+            ));
+        }
+        for saved in &bundle.saved_queries {
+            check_name(&saved.name)?;
+            check_query(&saved.query)?;
+        }
+        let pipelines = self.bundle_pipelines(&bundle.pipelines, replace)?;
+
+        let dry_run = dry_run::active();
+        #[cfg(feature = "fetch")]
+        let scheduled = self.scheduler_running();
+        #[cfg(not(feature = "fetch"))]
+        let scheduled = false;
+        if !dry_run {
+            let queries: Vec<SavedQuery> = bundle
+                .saved_queries
+                .iter()
+                .map(|saved| SavedQuery {
+                    name: saved.name.clone(),
+                    query: saved.query.clone(),
+                    description: saved.description.clone(),
+                    updated_at: String::new(),
+                })
+                .collect();
+            #[cfg(feature = "fetch")]
+            let existing = self.stored_pipelines()?;
+            self.storage.import(&queries, &pipelines, replace)?;
+            #[cfg(feature = "fetch")]
+            if scheduled {
+                if replace {
+                    for name in existing
+                        .iter()
+                        .filter(|name| !pipelines.iter().any(|(imported, _)| imported == *name))
+                    {
+                        self.stop_pipeline(name);
+                    }
+                }
+                for (name, definition) in &pipelines {
+                    if let Err(e) = self.reschedule(name, definition).await {
+                        tracing::warn!("failed to schedule imported pipeline {name}: {e}");
+                    }
+                }
+            }
+        }
+        Ok(ImportStateOutput {
+            saved_queries: bundle.saved_queries.len(),
+            pipelines: pipelines.len(),
+            scheduled,
+            differing_settings: bundle
+                .settings
+                .map(|settings| settings.differing(&StateSettings::from(self.config.as_ref())))
+                .unwrap_or_default(),
+            dry_run,
+        })
+    }
+}
+
+impl Server {
+    /// Names of the pipelines in the state store.
+    #[cfg(feature = "fetch")]
+    fn stored_pipelines(&self) -> Result<BTreeSet<String>, ErrorData> {
+        Ok(self
+            .storage
+            .pipelines()?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    /// Checks the pipelines of a bundle and returns their definitions as
+    /// stored, by name.
+    #[cfg(feature = "fetch")]
+    fn bundle_pipelines(
+        &self,
+        pipelines: &[SchedulePipelineInput],
+        replace: bool,
+    ) -> Result<Vec<(String, String)>, ErrorData> {
+        let pipelines = pipelines
+            .iter()
+            .map(|input| Ok((input.name.clone(), self.pipeline_definition(input)?)))
+            .collect::<Result<Vec<_>, ErrorData>>()?;
+        let imported: BTreeSet<&str> = pipelines.iter().map(|(name, _)| name.as_str()).collect();
+        let total = if replace {
+            imported.len()
+        } else {
+            self.stored_pipelines()?
+                .iter()
+                .filter(|name| !imported.contains(name.as_str()))
+                .count()
+                + imported.len()
+        };
+        if total > MAX_PIPELINES {
+            return Err(ErrorData::invalid_request(
+                "Too many scheduled pipelines",
+                Some(serde_json::json!({ "limit": MAX_PIPELINES })),
+            ));
+        }
+        Ok(pipelines)
+    }
+
+    #[cfg(not(feature = "fetch"))]
+    fn bundle_pipelines(
+        &self,
+        pipelines: &[SchedulePipelineInput],
+        _replace: bool,
+    ) -> Result<Vec<(String, String)>, ErrorData> {
+        if pipelines.is_empty() {
+            return Ok(Vec::new());
+        }
+        Err(ErrorData::invalid_request(
+            "Scheduled pipelines aren't built in — install mq-mcp with the fetch feature to import them",
+            None,
+        ))
+    }
+}
+
+#[tool_router(router = state_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Export the server's saved queries, scheduled pipelines and settings as a JSON bundle, e.g. to keep them under version control. The bundle contains no credentials; pass it to import_state to restore it here or on another server.",
+        output_schema = output_schema::<StateBundle>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn export_state(&self) -> McpResult {
+        json_result(&self.state_bundle()?)
+    }
+
+    #[tool(
+        description = "Import a bundle written by export_state: save its queries and pipelines, replacing those of the same name, and with `replace` delete those not in the bundle. Nothing is imported unless every query and pipeline is valid. Settings are reported but not changed, as they come from the server's command-line flags.",
+        output_schema = output_schema::<ImportStateOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = true
+        )
+    )]
+    async fn import_state(&self, Parameters(input): Parameters<ImportStateInput>) -> McpResult {
+        json_result(
+            &self
+                .import_bundle(input.bundle, input.replace.unwrap_or(false))
+                .await?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle() -> StateBundle {
+        serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "saved_queries": [{ "name": "titles", "query": ".h1 | to_text()" }],
+            "pipelines": [{
+                "name": "watch",
+                "cron": "@hourly",
+                "url": "https://example.com/CHANGELOG.md",
+                "steps": [{ "type": "query", "query": ".h2" }],
+            }],
+            "settings": StateSettings {
+                provenance: true,
+                ..StateSettings::from(&ServerConfig::default())
+            },
+        }))
+        .unwrap()
+    }
+
+    #[cfg(feature = "fetch")]
+    #[tokio::test]
+    async fn test_import_and_export() {
+        let server = Server::new(None).unwrap();
+        server.storage.save_query("old", ".code", None).unwrap();
+        let output = server.import_bundle(bundle(), true).await.unwrap();
+        assert_eq!(output.saved_queries, 1);
+        assert_eq!(output.pipelines, 1);
+        assert!(!output.scheduled);
+        assert_eq!(output.differing_settings, vec!["provenance"]);
+
+        let exported = server.state_bundle().unwrap();
+        assert_eq!(exported.version, BUNDLE_VERSION);
+        assert_eq!(
+            exported
+                .saved_queries
+                .iter()
+                .map(|q| q.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["titles"]
+        );
+        assert_eq!(exported.pipelines[0].name, "watch");
+
+        // The exported bundle imports into a fresh server unchanged.
+        let other = Server::new(None).unwrap();
+        let output = other.import_bundle(exported, false).await.unwrap();
+        assert!(output.differing_settings.is_empty());
+        assert!(other.storage.saved_query("titles").unwrap().is_some());
+    }
+
+    #[cfg(feature = "fetch")]
+    #[tokio::test]
+    async fn test_invalid_bundle_imports_nothing() {
+        let server = Server::new(None).unwrap();
+        let mut invalid = bundle();
+        invalid.pipelines[0].cron = "every hour".to_string();
+        assert!(server.import_bundle(invalid, false).await.is_err());
+        assert!(server.storage.saved_queries().unwrap().is_empty());
+
+        let mut newer = bundle();
+        newer.version = BUNDLE_VERSION + 1;
+        assert!(server.import_bundle(newer, false).await.is_err());
+    }
+
+    #[cfg(not(feature = "fetch"))]
+    #[tokio::test]
+    async fn test_pipelines_need_fetch() {
+        let server = Server::new(None).unwrap();
+        assert!(server.import_bundle(bundle(), false).await.is_err());
+        assert!(server.storage.saved_queries().unwrap().is_empty());
+
+        let mut queries_only = bundle();
+        queries_only.pipelines.clear();
+        let output = server.import_bundle(queries_only, false).await.unwrap();
+        assert_eq!(output.saved_queries, 1);
+    }
+}
@@ -0,0 +1,251 @@
+//! `server_stats`: uptime, open sessions, per-tool call counts and latencies
+//! and cache hit rates, counted across every session since the server
+//! started. Agents can use them to adapt to the server's load, e.g. by
+//! preferring pages already in the page cache, and operators to see what
+//! the server is used for without a metrics stack.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use rmcp::{schemars, serde::Serialize, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    output::{json_result, output_schema},
+};
+
+/// Hits and misses of a cache.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CacheCounts {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Default)]
+struct ToolCounts {
+    calls: u64,
+    errors: u64,
+    total: Duration,
+}
+
+#[derive(Debug)]
+struct Counts {
+    started: Instant,
+    started_at: DateTime<Utc>,
+    sessions: usize,
+    tools: HashMap<String, ToolCounts>,
+    idempotency: CacheCounts,
+}
+
+/// Counters shared by every session of a server. Cheap to clone; clones
+/// share the counts.
+#[derive(Debug, Clone)]
+pub(crate) struct Stats(Arc<Mutex<Counts>>);
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(Counts {
+            started: Instant::now(),
+            started_at: Utc::now(),
+            sessions: 0,
+            tools: HashMap::new(),
+            idempotency: CacheCounts::default(),
+        })))
+    }
+}
+
+impl Stats {
+    fn lock(&self) -> MutexGuard<'_, Counts> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records a finished call of `tool`.
+    pub(crate) fn record_call(&self, tool: &str, ok: bool, duration: Duration) {
+        let mut counts = self.lock();
+        let tool = counts.tools.entry(tool.to_string()).or_default();
+        tool.calls += 1;
+        tool.errors += u64::from(!ok);
+        tool.total += duration;
+    }
+
+    /// Records a call made with an idempotency key, `replayed` if the key's
+    /// result was returned instead of running the tool.
+    pub(crate) fn record_idempotency(&self, replayed: bool) {
+        let mut counts = self.lock();
+        if replayed {
+            counts.idempotency.hits += 1;
+        } else {
+            counts.idempotency.misses += 1;
+        }
+    }
+}
+
+/// Counts a server as an open session from [`Session::open`] until the
+/// server and all its clones are dropped.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Session(Arc<OnceLock<SessionGuard>>);
+
+#[derive(Debug)]
+struct SessionGuard(Stats);
+
+impl Session {
+    /// Counts the session in `stats`, once.
+    pub(crate) fn open(&self, stats: &Stats) {
+        self.0.get_or_init(|| {
+            stats.lock().sessions += 1;
+            SessionGuard(stats.clone())
+        });
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.0.lock().sessions -= 1;
+    }
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ToolStats {
+    pub name: String,
+    pub calls: u64,
+    #[schemars(description = "Calls that failed or returned an error result")]
+    pub errors: u64,
+    #[schemars(description = "Average time a call took, in milliseconds")]
+    pub average_ms: f64,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct CacheStats {
+    #[schemars(
+        description = "`page_cache` (pages fetched by the URL tools) or `idempotency` (replayed idempotency keys)"
+    )]
+    pub name: String,
+    pub hits: u64,
+    pub misses: u64,
+    #[schemars(
+        description = "Share of lookups that were hits, from 0 to 1; null before the first lookup"
+    )]
+    pub hit_rate: Option<f64>,
+}
+
+/// Output of `server_stats`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ServerStatsOutput {
+    #[schemars(description = "When the server started (RFC 3339)")]
+    pub started_at: String,
+    #[schemars(description = "Seconds since the server started")]
+    pub uptime_secs: u64,
+    #[schemars(description = "Client sessions currently open")]
+    pub sessions: usize,
+    #[schemars(description = "Tool calls since the server started")]
+    pub calls: u64,
+    #[schemars(description = "The tools called so far, most called first")]
+    pub tools: Vec<ToolStats>,
+    #[schemars(
+        description = "The server's caches; the page cache only when enabled with --fetch-cache-ttl"
+    )]
+    pub caches: Vec<CacheStats>,
+}
+
+impl CacheStats {
+    fn new(name: &str, counts: CacheCounts) -> Self {
+        let lookups = counts.hits + counts.misses;
+        Self {
+            name: name.to_string(),
+            hits: counts.hits,
+            misses: counts.misses,
+            hit_rate: (lookups > 0).then(|| counts.hits as f64 / lookups as f64),
+        }
+    }
+}
+
+impl Stats {
+    /// The counts so far, with the counts of the other caches.
+    fn report(&self, other_caches: Vec<CacheStats>) -> ServerStatsOutput {
+        let counts = self.lock();
+        let mut tools: Vec<ToolStats> = counts
+            .tools
+            .iter()
+            .map(|(name, tool)| ToolStats {
+                name: name.clone(),
+                calls: tool.calls,
+                errors: tool.errors,
+                average_ms: tool.total.as_secs_f64() * 1000.0 / tool.calls.max(1) as f64,
+            })
+            .collect();
+        tools.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.name.cmp(&b.name)));
+        let mut caches = other_caches;
+        caches.push(CacheStats::new("idempotency", counts.idempotency));
+        ServerStatsOutput {
+            started_at: counts.started_at.to_rfc3339(),
+            uptime_secs: counts.started.elapsed().as_secs(),
+            sessions: counts.sessions,
+            calls: tools.iter().map(|tool| tool.calls).sum(),
+            tools,
+            caches,
+        }
+    }
+}
+
+#[tool_router(router = stats_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Report the server's uptime, the number of open client sessions, per-tool call counts, error counts and average latencies, and the hit rates of its caches (pages fetched by the URL tools, replayed idempotency keys), counted across all sessions since the server started. Use it to adapt to the server's load, e.g. by preferring pages that are already cached.",
+        output_schema = output_schema::<ServerStatsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn server_stats(&self) -> McpResult {
+        #[cfg(feature = "fetch")]
+        let caches = self
+            .fetcher
+            .cache_counts()
+            .map(|counts| CacheStats::new("page_cache", counts))
+            .into_iter()
+            .collect();
+        #[cfg(not(feature = "fetch"))]
+        let caches = Vec::new();
+        json_result(&self.stats.report(caches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let stats = Stats::default();
+        stats.record_call("lint_markdown", true, Duration::from_millis(10));
+        stats.record_call("lint_markdown", false, Duration::from_millis(30));
+        stats.record_call("html_to_markdown", true, Duration::from_millis(5));
+        stats.record_idempotency(false);
+        stats.record_idempotency(true);
+
+        let report = stats.report(Vec::new());
+        assert_eq!(report.calls, 3);
+        assert_eq!(report.tools[0].name, "lint_markdown");
+        assert_eq!(report.tools[0].errors, 1);
+        assert!((report.tools[0].average_ms - 20.0).abs() < 1e-9);
+        assert_eq!(report.caches[0].name, "idempotency");
+        assert_eq!(report.caches[0].hit_rate, Some(0.5));
+        assert_eq!(
+            CacheStats::new("page_cache", CacheCounts::default()).hit_rate,
+            None
+        );
+    }
+
+    #[test]
+    fn test_sessions_close_when_dropped() {
+        let stats = Stats::default();
+        let session = Session::default();
+        session.open(&stats);
+        session.clone().open(&stats);
+        assert_eq!(stats.report(Vec::new()).sessions, 1);
+        drop(session);
+        assert_eq!(stats.report(Vec::new()).sessions, 0);
+    }
+}
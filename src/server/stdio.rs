@@ -0,0 +1,85 @@
+//! The stdio transport.
+//!
+//! Native builds hand tokio's stdin and stdout to rmcp. tokio has no stdio
+//! (nor a blocking pool) on `wasm32-wasip1`, so there the server runs over
+//! the same in-memory pipe as [`super::Server::call`], and a message loop on
+//! the single runtime thread relays between it and the blocking std handles:
+//! it reads a message from stdin, passes it on, then forwards what the
+//! server sends until every request the client made is answered or the
+//! server asks the client something. Notifications the server sends while
+//! the client is idle go out after the client's next message.
+
+use miette::miette;
+#[cfg(not(target_os = "wasi"))]
+use rmcp::ServiceExt;
+
+use super::{Server, ServerConfig};
+
+#[cfg(not(target_os = "wasi"))]
+pub async fn start(config: ServerConfig) -> miette::Result<()> {
+    let transport = (tokio::io::stdin(), tokio::io::stdout());
+    let server = Server::with_config(config).expect("Failed to create server");
+
+    let service = server.serve(transport).await.map_err(|e| miette!(e))?;
+    service.waiting().await.map_err(|e| miette!(e))?;
+
+    Ok(())
+}
+
+#[cfg(target_os = "wasi")]
+pub async fn start(config: ServerConfig) -> miette::Result<()> {
+    use std::{collections::HashSet, io::Write};
+
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let server = Server::with_config(config).map_err(|e| miette!("{e}"))?;
+    let (reader, mut writer, session) = super::invoke::serve_in_memory(server);
+    let mut lines = BufReader::new(reader).lines();
+    let mut stdout = std::io::stdout();
+    // Ids of the client's requests the server hasn't answered yet.
+    let mut pending = HashSet::new();
+
+    for line in std::io::stdin().lines() {
+        let line = line.map_err(|e| miette!("failed to read from stdin: {e}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Ok(message) = serde_json::from_str::<serde_json::Value>(&line)
+            && let (Some(_), Some(id)) = (message.get("method"), message.get("id"))
+        {
+            pending.insert(id.to_string());
+        }
+        writer
+            .write_all(format!("{line}\n").as_bytes())
+            .await
+            .map_err(|e| miette!("failed to send a message to the server: {e}"))?;
+
+        while !pending.is_empty() {
+            let Some(line) = lines
+                .next_line()
+                .await
+                .map_err(|e| miette!("failed to read from the server: {e}"))?
+            else {
+                return Ok(());
+            };
+            writeln!(stdout, "{line}")
+                .and_then(|()| stdout.flush())
+                .map_err(|e| miette!("failed to write to stdout: {e}"))?;
+            let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) else {
+                continue;
+            };
+            match (message.get("method"), message.get("id")) {
+                // A request of the server's: the client answers it next.
+                (Some(_), Some(_)) => break,
+                (None, Some(id)) => {
+                    pending.remove(&id.to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    drop(writer);
+    session.abort();
+    Ok(())
+}
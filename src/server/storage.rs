@@ -0,0 +1,574 @@
+//! The durable store behind the server's state: saved queries, scheduled
+//! pipelines, the audit log of tool calls and the cookbook of query shapes,
+//! in one SQLite database.
+//!
+//! With `--state <path>` the database is a file and survives restarts;
+//! without it, an in-memory database gives the same behaviour for the life of
+//! the process. The schema is versioned with `PRAGMA user_version` and
+//! upgraded by [`MIGRATIONS`] when the file is opened.
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use chrono::Utc;
+use rmcp::{ErrorData, model::CallToolResult};
+use rusqlite::{Connection, OptionalExtension, params};
+
+use super::Server;
+
+/// Schema changes, in order. A database at `user_version` N has had the
+/// first N applied; never edit an entry once released, only append.
+const MIGRATIONS: &[&str] = &[
+    // 1: saved queries, scheduled pipelines and the audit log.
+    "CREATE TABLE saved_queries (
+        name TEXT PRIMARY KEY,
+        query TEXT NOT NULL,
+        description TEXT,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE pipelines (
+        name TEXT PRIMARY KEY,
+        definition TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE audit (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at TEXT NOT NULL,
+        tool TEXT NOT NULL,
+        ok INTEGER NOT NULL,
+        dry_run INTEGER NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        argument_bytes INTEGER NOT NULL,
+        result_bytes INTEGER NOT NULL
+    );",
+    // 2: the cookbook.
+    "CREATE TABLE cookbook (
+        tool TEXT NOT NULL,
+        shape TEXT NOT NULL,
+        uses INTEGER NOT NULL,
+        last_used TEXT NOT NULL,
+        PRIMARY KEY (tool, shape)
+    );",
+];
+
+const SAVE_QUERY: &str = "INSERT INTO saved_queries (name, query, description, updated_at)
+     VALUES (?1, ?2, ?3, ?4)
+     ON CONFLICT (name) DO UPDATE SET
+        query = excluded.query,
+        description = excluded.description,
+        updated_at = excluded.updated_at";
+
+const SAVE_PIPELINE: &str =
+    "INSERT INTO pipelines (name, definition, updated_at) VALUES (?1, ?2, ?3)
+     ON CONFLICT (name) DO UPDATE SET
+        definition = excluded.definition,
+        updated_at = excluded.updated_at";
+
+/// Audit records kept; older ones are deleted as new calls are recorded.
+const MAX_AUDIT_RECORDS: i64 = 100_000;
+
+/// Cookbook rows kept; the least used are deleted as new shapes are
+/// recorded.
+const MAX_COOKBOOK_SHAPES: i64 = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SavedQuery {
+    pub name: String,
+    pub query: String,
+    pub description: Option<String>,
+    /// When the query was last saved (RFC 3339).
+    pub updated_at: String,
+}
+
+/// A query shape in the cookbook, over every tool it was used with.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct QueryShape {
+    pub shape: String,
+    pub uses: u64,
+    /// The tools it was used with, in name order.
+    pub tools: Vec<String>,
+    /// When it was last used (RFC 3339).
+    pub last_used: String,
+}
+
+/// One tool call in the audit log. Arguments and results are not stored,
+/// only their sizes.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AuditRecord {
+    pub tool: String,
+    pub ok: bool,
+    pub dry_run: bool,
+    pub duration_ms: u64,
+    pub argument_bytes: usize,
+    pub result_bytes: usize,
+}
+
+fn storage_error(e: rusqlite::Error) -> ErrorData {
+    ErrorData::internal_error(
+        "Failed to access the state store",
+        Some(serde_json::Value::String(e.to_string())),
+    )
+}
+
+/// Brings the schema of `connection` up to date.
+fn migrate(connection: &mut Connection) -> Result<(), String> {
+    let version: usize = connection
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if version > MIGRATIONS.len() {
+        return Err(format!(
+            "schema version {version} is newer than this mq-mcp supports ({})",
+            MIGRATIONS.len()
+        ));
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        apply(connection, migration, index + 1)
+            .map_err(|e| format!("migration {} failed: {e}", index + 1))?;
+    }
+    Ok(())
+}
+
+/// Runs `migration` and records `version` in one transaction.
+fn apply(connection: &mut Connection, migration: &str, version: usize) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(migration)?;
+    transaction.pragma_update(None, "user_version", version)?;
+    transaction.commit()
+}
+
+/// A handle to the state database, shared by every session of the server.
+#[derive(Debug, Clone)]
+pub(crate) struct Storage(Arc<Mutex<Connection>>);
+
+impl Default for Storage {
+    /// An in-memory store.
+    fn default() -> Self {
+        Self::open(None).expect("an in-memory database can always be opened")
+    }
+}
+
+impl Storage {
+    /// Opens (creating if needed) and migrates the database at `path`, or an
+    /// in-memory one.
+    pub(crate) fn open(path: Option<&Path>) -> Result<Self, String> {
+        let location = path.map_or_else(|| "memory".to_string(), |p| p.display().to_string());
+        let connection = match path {
+            Some(path) => Connection::open(path).and_then(|connection| {
+                connection.pragma_update(None, "journal_mode", "WAL")?;
+                connection.pragma_update(None, "synchronous", "NORMAL")?;
+                Ok(connection)
+            }),
+            None => Connection::open_in_memory(),
+        };
+        let mut connection =
+            connection.map_err(|e| format!("failed to open the state store at {location}: {e}"))?;
+        migrate(&mut connection)
+            .map_err(|e| format!("failed to migrate the state store at {location}: {e}"))?;
+        Ok(Self(Arc::new(Mutex::new(connection))))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Saves `query` under `name`, replacing any query of that name.
+    pub(crate) fn save_query(
+        &self,
+        name: &str,
+        query: &str,
+        description: Option<&str>,
+    ) -> Result<(), ErrorData> {
+        self.lock()
+            .execute(
+                SAVE_QUERY,
+                params![name, query, description, Utc::now().to_rfc3339()],
+            )
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    pub(crate) fn saved_query(&self, name: &str) -> Result<Option<SavedQuery>, ErrorData> {
+        self.lock()
+            .query_row(
+                "SELECT name, query, description, updated_at FROM saved_queries WHERE name = ?1",
+                [name],
+                |row| {
+                    Ok(SavedQuery {
+                        name: row.get(0)?,
+                        query: row.get(1)?,
+                        description: row.get(2)?,
+                        updated_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(storage_error)
+    }
+
+    /// All saved queries, in name order.
+    pub(crate) fn saved_queries(&self) -> Result<Vec<SavedQuery>, ErrorData> {
+        let connection = self.lock();
+        let mut statement = connection
+            .prepare("SELECT name, query, description, updated_at FROM saved_queries ORDER BY name")
+            .map_err(storage_error)?;
+        statement
+            .query_map([], |row| {
+                Ok(SavedQuery {
+                    name: row.get(0)?,
+                    query: row.get(1)?,
+                    description: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            })
+            .and_then(Iterator::collect)
+            .map_err(storage_error)
+    }
+
+    /// Deletes the query saved under `name`. Returns whether there was one.
+    pub(crate) fn delete_query(&self, name: &str) -> Result<bool, ErrorData> {
+        self.lock()
+            .execute("DELETE FROM saved_queries WHERE name = ?1", [name])
+            .map(|deleted| deleted > 0)
+            .map_err(storage_error)
+    }
+
+    /// Saves the JSON `definition` of the pipeline scheduled as `name`.
+    #[cfg(any(feature = "fetch", test))]
+    pub(crate) fn save_pipeline(&self, name: &str, definition: &str) -> Result<(), ErrorData> {
+        self.lock()
+            .execute(
+                SAVE_PIPELINE,
+                params![name, definition, Utc::now().to_rfc3339()],
+            )
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "fetch", test))]
+    pub(crate) fn delete_pipeline(&self, name: &str) -> Result<(), ErrorData> {
+        self.lock()
+            .execute("DELETE FROM pipelines WHERE name = ?1", [name])
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    /// The names and JSON definitions of the saved pipelines.
+    pub(crate) fn pipelines(&self) -> Result<Vec<(String, String)>, ErrorData> {
+        let connection = self.lock();
+        let mut statement = connection
+            .prepare("SELECT name, definition FROM pipelines ORDER BY name")
+            .map_err(storage_error)?;
+        statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .and_then(Iterator::collect)
+            .map_err(storage_error)
+    }
+
+    /// Saves `queries` and the `pipelines` (names and JSON definitions) in
+    /// one transaction, after deleting every saved query and pipeline if
+    /// `replace`. The queries' `updated_at` is set to now.
+    pub(crate) fn import(
+        &self,
+        queries: &[SavedQuery],
+        pipelines: &[(String, String)],
+        replace: bool,
+    ) -> Result<(), ErrorData> {
+        let mut connection = self.lock();
+        let transaction = connection.transaction().map_err(storage_error)?;
+        if replace {
+            transaction
+                .execute_batch("DELETE FROM saved_queries; DELETE FROM pipelines;")
+                .map_err(storage_error)?;
+        }
+        let now = Utc::now().to_rfc3339();
+        for query in queries {
+            transaction
+                .execute(
+                    SAVE_QUERY,
+                    params![query.name, query.query, query.description, now],
+                )
+                .map_err(storage_error)?;
+        }
+        for (name, definition) in pipelines {
+            transaction
+                .execute(SAVE_PIPELINE, params![name, definition, now])
+                .map_err(storage_error)?;
+        }
+        transaction.commit().map_err(storage_error)
+    }
+
+    /// Appends `record` to the audit log, dropping the oldest records past
+    /// [`MAX_AUDIT_RECORDS`].
+    pub(crate) fn audit(&self, record: &AuditRecord) -> Result<(), ErrorData> {
+        let connection = self.lock();
+        connection
+            .execute(
+                "INSERT INTO audit
+                    (at, tool, ok, dry_run, duration_ms, argument_bytes, result_bytes)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    Utc::now().to_rfc3339(),
+                    record.tool,
+                    record.ok,
+                    record.dry_run,
+                    record.duration_ms,
+                    record.argument_bytes,
+                    record.result_bytes,
+                ],
+            )
+            .map_err(storage_error)?;
+        connection
+            .execute(
+                "DELETE FROM audit WHERE id <= last_insert_rowid() - ?1",
+                [MAX_AUDIT_RECORDS],
+            )
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    /// Counts a successful use of the query `shape` by `tool`, dropping the
+    /// least used shapes past [`MAX_COOKBOOK_SHAPES`].
+    pub(crate) fn record_shape(&self, tool: &str, shape: &str) -> Result<(), ErrorData> {
+        let connection = self.lock();
+        connection
+            .execute(
+                "INSERT INTO cookbook (tool, shape, uses, last_used) VALUES (?1, ?2, 1, ?3)
+                 ON CONFLICT (tool, shape) DO UPDATE SET
+                    uses = uses + 1,
+                    last_used = excluded.last_used",
+                params![tool, shape, Utc::now().to_rfc3339()],
+            )
+            .map_err(storage_error)?;
+        connection
+            .execute(
+                "DELETE FROM cookbook WHERE rowid IN (
+                    SELECT rowid FROM cookbook ORDER BY uses, last_used
+                    LIMIT max(0, (SELECT COUNT(*) FROM cookbook) - ?1)
+                 )",
+                [MAX_COOKBOOK_SHAPES],
+            )
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    /// The `limit` most used query shapes, optionally only those used with
+    /// `tool`.
+    pub(crate) fn query_shapes(
+        &self,
+        tool: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<QueryShape>, ErrorData> {
+        let connection = self.lock();
+        let mut statement = connection
+            .prepare(
+                "SELECT shape, SUM(uses), GROUP_CONCAT(tool, ' '), MAX(last_used)
+                 FROM cookbook
+                 WHERE shape IN (SELECT shape FROM cookbook WHERE ?1 IS NULL OR tool = ?1)
+                 GROUP BY shape
+                 ORDER BY SUM(uses) DESC, shape
+                 LIMIT ?2",
+            )
+            .map_err(storage_error)?;
+        statement
+            .query_map(params![tool, limit], |row| {
+                let tools: String = row.get(2)?;
+                let mut tools: Vec<String> = tools.split(' ').map(str::to_string).collect();
+                tools.sort();
+                Ok(QueryShape {
+                    shape: row.get(0)?,
+                    uses: row.get(1)?,
+                    tools,
+                    last_used: row.get(3)?,
+                })
+            })
+            .and_then(Iterator::collect)
+            .map_err(storage_error)
+    }
+
+    /// The most recent `limit` audit records, newest first.
+    #[cfg(test)]
+    fn recent_audit(&self, limit: usize) -> Vec<AuditRecord> {
+        let connection = self.lock();
+        let mut statement = connection
+            .prepare(
+                "SELECT tool, ok, dry_run, duration_ms, argument_bytes, result_bytes
+                 FROM audit ORDER BY id DESC LIMIT ?1",
+            )
+            .unwrap();
+        statement
+            .query_map([limit], |row| {
+                Ok(AuditRecord {
+                    tool: row.get(0)?,
+                    ok: row.get(1)?,
+                    dry_run: row.get(2)?,
+                    duration_ms: row.get(3)?,
+                    argument_bytes: row.get(4)?,
+                    result_bytes: row.get(5)?,
+                })
+            })
+            .and_then(Iterator::collect)
+            .unwrap()
+    }
+}
+
+impl Server {
+    /// Records a finished tool call in the audit log. `result` is `None`
+    /// when the call failed. Failures to record are logged.
+    pub(super) fn audit(
+        &self,
+        tool: String,
+        dry_run: bool,
+        argument_bytes: usize,
+        duration: Duration,
+        result: Option<&CallToolResult>,
+    ) {
+        let record = AuditRecord {
+            tool,
+            ok: result.is_some_and(|result| result.is_error != Some(true)),
+            dry_run,
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            argument_bytes,
+            result_bytes: result.map_or(0, |result| {
+                result
+                    .content
+                    .iter()
+                    .filter_map(|content| content.as_text())
+                    .map(|text| text.text.len())
+                    .sum()
+            }),
+        };
+        if let Err(e) = self.storage.audit(&record) {
+            tracing::warn!("failed to record a call of {}: {:?}", record.tool, e.data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_queries() {
+        let storage = Storage::open(None).unwrap();
+        storage.save_query("titles", ".h1", None).unwrap();
+        storage
+            .save_query("titles", ".h1 | to_text()", Some("Document titles"))
+            .unwrap();
+        storage.save_query("code", ".code", None).unwrap();
+
+        let titles = storage.saved_query("titles").unwrap().unwrap();
+        assert_eq!(titles.query, ".h1 | to_text()");
+        assert_eq!(titles.description.as_deref(), Some("Document titles"));
+        assert_eq!(
+            storage
+                .saved_queries()
+                .unwrap()
+                .iter()
+                .map(|q| q.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["code", "titles"]
+        );
+        assert!(storage.delete_query("code").unwrap());
+        assert!(!storage.delete_query("code").unwrap());
+        assert_eq!(storage.saved_query("code").unwrap(), None);
+    }
+
+    #[test]
+    fn test_state_survives_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.db");
+        {
+            let storage = Storage::open(Some(&path)).unwrap();
+            storage.save_query("titles", ".h1", None).unwrap();
+            storage
+                .save_pipeline("watch", r#"{"name":"watch"}"#)
+                .unwrap();
+        }
+        let storage = Storage::open(Some(&path)).unwrap();
+        assert!(storage.saved_query("titles").unwrap().is_some());
+        assert_eq!(
+            storage.pipelines().unwrap(),
+            vec![("watch".to_string(), r#"{"name":"watch"}"#.to_string())]
+        );
+        storage.delete_pipeline("watch").unwrap();
+        assert!(storage.pipelines().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_import_replace() {
+        let storage = Storage::open(None).unwrap();
+        storage.save_query("titles", ".h1", None).unwrap();
+        storage.save_pipeline("watch", "{}").unwrap();
+        let code = SavedQuery {
+            name: "code".to_string(),
+            query: ".code".to_string(),
+            description: None,
+            updated_at: String::new(),
+        };
+
+        storage
+            .import(std::slice::from_ref(&code), &[], false)
+            .unwrap();
+        assert_eq!(storage.saved_queries().unwrap().len(), 2);
+        storage.import(&[code], &[], true).unwrap();
+        assert_eq!(
+            storage
+                .saved_queries()
+                .unwrap()
+                .iter()
+                .map(|q| q.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["code"]
+        );
+        assert!(storage.pipelines().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_newer_schema_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.db");
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(Storage::open(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn test_audit() {
+        let storage = Storage::open(None).unwrap();
+        let record = AuditRecord {
+            tool: "extract_headings".to_string(),
+            ok: true,
+            dry_run: false,
+            duration_ms: 3,
+            argument_bytes: 40,
+            result_bytes: 12,
+        };
+        storage.audit(&record).unwrap();
+        assert_eq!(storage.recent_audit(10), vec![record]);
+    }
+
+    #[test]
+    fn test_cookbook() {
+        let storage = Storage::open(None).unwrap();
+        storage.record_shape("extract_markdown_v2", ".h2").unwrap();
+        storage.record_shape("query_url", ".h2").unwrap();
+        storage.record_shape("query_url", ".code").unwrap();
+
+        let shapes = storage.query_shapes(None, 10).unwrap();
+        assert_eq!(shapes[0].shape, ".h2");
+        assert_eq!(shapes[0].uses, 2);
+        assert_eq!(shapes[0].tools, vec!["extract_markdown_v2", "query_url"]);
+        assert_eq!(shapes[1].shape, ".code");
+
+        let shapes = storage
+            .query_shapes(Some("extract_markdown_v2"), 10)
+            .unwrap();
+        assert_eq!(shapes.len(), 1);
+        assert_eq!(storage.query_shapes(None, 1).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,137 @@
+//! "Did you mean" suggestions for misspelled functions and selectors,
+//! attached to query evaluation errors.
+
+use std::collections::BTreeMap;
+
+use mq_hir::{HirError, SymbolKind};
+use rmcp::ErrorData;
+
+use super::engine::{Engine, QueryEngine};
+
+/// Maximum number of candidates suggested per unknown name.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns the closest known names for every unknown function, variable or
+/// selector in `query`, ranked by edit distance against the HIR symbol
+/// table (builtins included).
+pub(crate) fn did_you_mean(query: &str) -> BTreeMap<String, Vec<String>> {
+    let mut suggestions = BTreeMap::new();
+    let mut hir = mq_hir::Hir::default();
+    let (source_id, _) = hir.add_code(None, query);
+
+    let known_names = hir
+        .symbols()
+        .filter(|(_, symbol)| {
+            matches!(symbol.kind, SymbolKind::Function(_) | SymbolKind::Variable)
+                && !symbol.is_internal_function()
+        })
+        .filter_map(|(_, symbol)| symbol.value.as_deref())
+        .collect::<Vec<_>>();
+    for error in hir.errors() {
+        if let HirError::UnresolvedSymbol { symbol, .. } = error
+            && symbol.source.source_id == Some(source_id)
+            && let Some(name) = symbol.value.as_deref()
+        {
+            let candidates = closest(name, known_names.iter().copied());
+            if !candidates.is_empty() {
+                suggestions.insert(name.to_string(), candidates);
+            }
+        }
+    }
+
+    for (message, _) in Engine::parse_errors(query) {
+        if let Some(name) = message
+            .strip_prefix("Unknown selector `")
+            .and_then(|rest| rest.strip_suffix('`'))
+        {
+            let candidates = closest(name, hir.builtin.selectors.keys().map(|s| s.as_str()));
+            if !candidates.is_empty() {
+                suggestions.insert(name.to_string(), candidates);
+            }
+        }
+    }
+
+    suggestions
+}
+
+/// Ranks `candidates` by Damerau–Levenshtein distance to `name`, keeping
+/// those within roughly a third of its length (at least one edit).
+fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut ranked = candidates
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (strsim::damerau_levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect::<Vec<_>>();
+    ranked.sort();
+    ranked.dedup_by_key(|(_, candidate)| *candidate);
+    ranked
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Builds the "Failed to query" error for `query`. When the failure involves
+/// misspelled names, `data` becomes `{"error", "did_you_mean"}` (a map from
+/// each unknown name to its suggestions) and the message names the best
+/// candidates; otherwise `data` is the error string as before.
+pub(crate) fn query_error(query: &str, error: impl ToString) -> ErrorData {
+    let error = error.to_string();
+    let suggestions = did_you_mean(query);
+    if suggestions.is_empty() {
+        return ErrorData::invalid_request(
+            "Failed to query",
+            Some(serde_json::Value::String(error)),
+        );
+    }
+
+    let hint = suggestions
+        .iter()
+        .map(|(name, candidates)| format!("`{}` for `{name}`", candidates[0]))
+        .collect::<Vec<_>>()
+        .join(", ");
+    ErrorData::invalid_request(
+        format!("Failed to query (did you mean {hint}?)"),
+        Some(serde_json::json!({
+            "error": error,
+            "did_you_mean": suggestions,
+        })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(".h | upcse()", "upcse", "upcase")]
+    #[case(r#".h | selct(contians("a"))"#, "contians", "contains")]
+    #[case(r#".h | selct(contains("a"))"#, "selct", "select")]
+    fn test_did_you_mean(#[case] query: &str, #[case] unknown: &str, #[case] expected: &str) {
+        let suggestions = did_you_mean(query);
+        assert_eq!(
+            suggestions
+                .get(unknown)
+                .and_then(|c| c.first())
+                .map(String::as_str),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn test_did_you_mean_ignores_valid_query() {
+        assert!(did_you_mean(".h | upcase()").is_empty());
+    }
+
+    #[test]
+    fn test_query_error_includes_did_you_mean() {
+        let err = query_error(".h | upcse()", "\"upcse\" is not defined");
+        assert!(err.message.contains("Failed to query"));
+        assert!(err.message.contains("`upcase`"));
+        let data = err.data.unwrap();
+        assert_eq!(data["error"], "\"upcse\" is not defined");
+        assert_eq!(data["did_you_mean"]["upcse"][0], "upcase");
+    }
+}
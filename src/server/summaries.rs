@@ -0,0 +1,238 @@
+//! `section_summaries`: extractive summaries of each section of a document,
+//! picked from its own sentences without a language model. A summary is the
+//! section's first sentence followed by the sentences whose words recur
+//! most in the section, in document order, so the same document always
+//! gives the same summaries.
+
+use std::collections::{HashMap, HashSet};
+
+use markdown::mdast;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{
+    McpResult, Server,
+    ast::parse_mdast,
+    keywords::{Tokenizer, prose},
+    language::parse_language,
+    output::{json_result, output_schema},
+    sections::{self, Section},
+};
+
+const DEFAULT_SENTENCES: usize = 3;
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SectionSummariesInput {
+    #[schemars(description = "The markdown document to summarize")]
+    pub markdown: String,
+    #[schemars(
+        description = "Maximum number of sentences per summary, including the first (default: 3)"
+    )]
+    pub sentences: Option<usize>,
+    #[schemars(
+        description = "Language for the stopword list, as an ISO 639-3 code or English name (default: detected from the document)"
+    )]
+    pub language: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct SectionSummary {
+    #[schemars(description = "The section's stable id, accepted by edit_section")]
+    pub id: String,
+    #[schemars(description = "Heading text; null for the text before the first heading")]
+    pub title: Option<String>,
+    #[schemars(description = "1-based line of the heading")]
+    pub line: usize,
+    #[schemars(
+        description = "Sentences taken from the section, in document order; empty if it has no prose"
+    )]
+    pub summary: String,
+    #[schemars(description = "Number of sentences in the section")]
+    pub sentences: usize,
+}
+
+/// Output of `section_summaries`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SectionSummariesOutput {
+    pub sections: Vec<SectionSummary>,
+}
+
+/// Appends the text of the paragraphs under `node`, including those in
+/// lists and block quotes, to `texts`.
+fn paragraphs(node: &mdast::Node, texts: &mut Vec<String>) {
+    match node {
+        mdast::Node::Paragraph(_) => texts.push(node.to_string()),
+        mdast::Node::Heading(_) | mdast::Node::Table(_) => {}
+        _ => {
+            for child in node.children().into_iter().flatten() {
+                paragraphs(child, texts);
+            }
+        }
+    }
+}
+
+/// The sentences of the prose of `markdown`, leaving out headings, tables
+/// and code.
+fn sentences(markdown: &str) -> Result<Vec<String>, ErrorData> {
+    let mut texts = Vec::new();
+    paragraphs(&parse_mdast(markdown)?, &mut texts);
+    Ok(texts
+        .iter()
+        .flat_map(|text| {
+            // Soft line breaks don't end a sentence.
+            text.replace(['\r', '\n'], " ")
+                .unicode_sentences()
+                .map(|sentence| sentence.trim().to_string())
+                .filter(|sentence| !sentence.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .collect())
+}
+
+/// Picks up to `limit` of `sentences`: the first, then the others by how
+/// often the section uses their words. Words used only once in the section
+/// don't count, and sentences with none of the recurring words aren't
+/// picked.
+fn pick(sentences: &[String], tokenizer: &Tokenizer, limit: usize) -> Vec<usize> {
+    let terms: Vec<Vec<String>> = sentences
+        .iter()
+        .map(|sentence| tokenizer.terms(sentence))
+        .collect();
+    let mut frequencies: HashMap<&str, usize> = HashMap::new();
+    for term in terms.iter().flatten() {
+        *frequencies.entry(term).or_default() += 1;
+    }
+    let mut scored: Vec<(usize, f64)> = terms
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter_map(|(index, terms)| {
+            let distinct: HashSet<&str> = terms.iter().map(String::as_str).collect();
+            let score: usize = distinct
+                .iter()
+                .map(|term| frequencies[term])
+                .filter(|&count| count > 1)
+                .sum();
+            (score > 0).then(|| (index, score as f64 / distinct.len() as f64))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mut picked = if sentences.is_empty() {
+        vec![]
+    } else {
+        vec![0]
+    };
+    picked.extend(
+        scored
+            .into_iter()
+            .map(|(index, _)| index)
+            .take(limit.saturating_sub(1)),
+    );
+    picked.sort();
+    picked
+}
+
+fn summarize(
+    section: Section,
+    tokenizer: &Tokenizer,
+    limit: usize,
+) -> Result<SectionSummary, ErrorData> {
+    let sentences = sentences(&section.markdown)?;
+    let summary = pick(&sentences, tokenizer, limit)
+        .into_iter()
+        .map(|index| sentences[index].as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok(SectionSummary {
+        id: section.id,
+        title: section.title,
+        line: section.line,
+        summary,
+        sentences: sentences.len(),
+    })
+}
+
+#[tool_router(router = summaries_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Summarize each section of a markdown document by extraction: its first sentence plus the sentences whose words recur most in the section, in document order. Deterministic and cheap, with no language model; a preview of a document before extracting its content in full.",
+        output_schema = output_schema::<SectionSummariesOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn section_summaries(&self, Parameters(input): Parameters<SectionSummariesInput>) -> McpResult {
+        let language = match input.language.as_deref() {
+            Some(name) => Some(parse_language(name)?),
+            None => whatlang::detect_lang(&prose(&input.markdown)),
+        };
+        let tokenizer = Tokenizer::new(language, &[]);
+        let limit = input.sentences.unwrap_or(DEFAULT_SENTENCES).max(1);
+        json_result(&SectionSummariesOutput {
+            sections: sections::split(&input.markdown)?
+                .into_iter()
+                .map(|section| summarize(section, &tokenizer, limit))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use whatlang::Lang;
+
+    const MARKDOWN: &str = "\
+# Caching
+
+The server keeps a cache of fetched pages. Pages are fetched once a day.
+The weather was nice when this was written.
+
+- The cache is stored on disk, and the cache survives restarts.
+
+```sh
+rm -rf cache
+```
+
+# Empty
+
+| a | b |
+|---|---|
+| 1 | 2 |
+";
+
+    fn summaries(limit: usize) -> Vec<SectionSummary> {
+        let tokenizer = Tokenizer::new(Some(Lang::Eng), &[]);
+        sections::split(MARKDOWN)
+            .unwrap()
+            .into_iter()
+            .map(|section| summarize(section, &tokenizer, limit))
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[rstest]
+    #[case(1, "The server keeps a cache of fetched pages.")]
+    #[case(
+        2,
+        "The server keeps a cache of fetched pages. Pages are fetched once a day."
+    )]
+    #[case(
+        3,
+        "The server keeps a cache of fetched pages. Pages are fetched once a day. The cache is stored on disk, and the cache survives restarts."
+    )]
+    fn test_summary(#[case] limit: usize, #[case] expected: &str) {
+        let summaries = summaries(limit);
+        assert_eq!(summaries[0].summary, expected);
+        assert_eq!(summaries[0].sentences, 4);
+    }
+
+    #[test]
+    fn test_section_without_prose() {
+        let summaries = summaries(3);
+        assert_eq!(summaries[1].title.as_deref(), Some("Empty"));
+        assert_eq!(summaries[1].summary, "");
+        assert_eq!(summaries[1].sentences, 0);
+    }
+}
@@ -0,0 +1,428 @@
+//! `aggregate_tasks`: the task list items (`- [ ]`, `- [x]`) of every note
+//! in a vault, with their tags and due dates, grouped by note, tag or due
+//! date into one TODO report.
+
+use std::{collections::BTreeMap, sync::LazyLock};
+
+use chrono::NaiveDate;
+use markdown::mdast;
+use regex::Regex;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    ast::parse_mdast,
+    daily,
+    output::{json_result, output_schema},
+    vault::{Note, Vault},
+    wikilinks,
+};
+
+/// Due dates as the Obsidian Tasks plugin (`📅 2026-10-20`), Dataview
+/// (`[due:: 2026-10-20]`), todo.txt (`due:2026-10-20`) and TaskPaper
+/// (`@due(2026-10-20)`) write them.
+static DUE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:📅|🗓️?|\bdue::?|@due\()\s*(\d{4}-\d{2}-\d{2})").unwrap());
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TaskStatus {
+    /// Unchecked tasks.
+    #[default]
+    Open,
+    /// Checked tasks.
+    Done,
+    All,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TaskGrouping {
+    /// By note, in path order.
+    #[default]
+    File,
+    /// By tag, alphabetically; a task is in the group of each of its tags.
+    Tag,
+    /// By due date, soonest first.
+    Due,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TaskReportFormat {
+    /// Groups only.
+    #[default]
+    Json,
+    /// Also a markdown report.
+    Markdown,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct AggregateTasksInput {
+    #[schemars(description = "The directory of notes (the vault) to collect tasks from")]
+    pub directory: String,
+    #[schemars(description = "`open` (default), `done` or `all` tasks")]
+    pub status: Option<TaskStatus>,
+    #[schemars(
+        description = "Group tasks by `file` (default), `tag` or `due` date; tasks without a tag or due date are grouped last"
+    )]
+    pub group_by: Option<TaskGrouping>,
+    #[schemars(
+        description = "Only return tasks with this tag or a tag nested under it, e.g. `work` for `work/urgent`"
+    )]
+    pub tag: Option<String>,
+    #[schemars(description = "Only return tasks due on or before this date, as YYYY-MM-DD")]
+    pub due_by: Option<String>,
+    #[schemars(
+        description = "`json` (default), or `markdown` to also return the groups as a markdown TODO report in `report`"
+    )]
+    pub format: Option<TaskReportFormat>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct Task {
+    #[schemars(description = "The task's markdown, without the checkbox")]
+    pub text: String,
+    pub done: bool,
+    #[schemars(description = "Path of the note in the directory")]
+    pub path: String,
+    #[schemars(description = "1-based line of the task")]
+    pub line: usize,
+    #[schemars(description = "Tags on the task's line, without `#`")]
+    pub tags: Vec<String>,
+    #[schemars(description = "Due date, YYYY-MM-DD")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct TaskGroup {
+    #[schemars(
+        description = "The note path, tag or due date of the group; null for tasks without a tag or due date"
+    )]
+    pub key: Option<String>,
+    pub tasks: Vec<Task>,
+}
+
+/// Output of `aggregate_tasks`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct AggregateTasksOutput {
+    pub groups: Vec<TaskGroup>,
+    #[schemars(description = "Number of tasks returned, each counted once")]
+    pub total: usize,
+    #[schemars(description = "The groups as a markdown TODO report, when `format` is `markdown`")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<String>,
+}
+
+fn collect(node: &mdast::Node, note: &Note, tasks: &mut Vec<Task>) {
+    if let mdast::Node::ListItem(item) = node
+        && let Some(done) = item.checked
+        && let Some(position) = item
+            .children
+            .first()
+            .filter(|child| matches!(child, mdast::Node::Paragraph(_)))
+            .and_then(mdast::Node::position)
+    {
+        let text = &note.markdown[position.start.offset..position.end.offset];
+        tasks.push(Task {
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            done,
+            path: note.path.clone(),
+            line: position.start.line,
+            tags: wikilinks::parse(text)
+                .tags
+                .into_iter()
+                .map(|tag| tag.name)
+                .collect(),
+            due: DUE.captures(text).map(|due| due[1].to_string()),
+        });
+    }
+    for child in node.children().into_iter().flatten() {
+        collect(child, note, tasks);
+    }
+}
+
+/// The task list items of `note`, in document order, nested ones included.
+fn tasks(note: &Note) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    match parse_mdast(&note.markdown) {
+        Ok(root) => collect(&root, note, &mut tasks),
+        Err(e) => tracing::debug!("skipping {}: {}", note.path, e.message),
+    }
+    tasks
+}
+
+fn group(tasks: Vec<Task>, grouping: TaskGrouping) -> Vec<TaskGroup> {
+    let mut groups: BTreeMap<String, Vec<Task>> = BTreeMap::new();
+    let mut rest = Vec::new();
+    for task in tasks {
+        let keys = match grouping {
+            TaskGrouping::File => vec![task.path.clone()],
+            TaskGrouping::Tag => task.tags.clone(),
+            TaskGrouping::Due => task.due.iter().cloned().collect(),
+        };
+        if keys.is_empty() {
+            rest.push(task);
+            continue;
+        }
+        for key in keys {
+            let group = groups.entry(key).or_default();
+            // A tag repeated on one line.
+            if group.last() != Some(&task) {
+                group.push(task.clone());
+            }
+        }
+    }
+    let mut groups: Vec<TaskGroup> = groups
+        .into_iter()
+        .map(|(key, tasks)| TaskGroup {
+            key: Some(key),
+            tasks,
+        })
+        .collect();
+    if !rest.is_empty() {
+        groups.push(TaskGroup {
+            key: None,
+            tasks: rest,
+        });
+    }
+    groups
+}
+
+fn report(groups: &[TaskGroup], grouping: TaskGrouping) -> String {
+    let mut sections = Vec::new();
+    for group in groups {
+        let heading = match (&group.key, grouping) {
+            (Some(key), TaskGrouping::Tag) => format!("#{key}"),
+            (Some(key), _) => key.clone(),
+            (None, TaskGrouping::Due) => "No due date".to_string(),
+            (None, _) => "No tag".to_string(),
+        };
+        let items: Vec<String> = group
+            .tasks
+            .iter()
+            .map(|task| {
+                let mark = if task.done { 'x' } else { ' ' };
+                format!("- [{mark}] {} ({}:{})", task.text, task.path, task.line)
+            })
+            .collect();
+        sections.push(format!("## {heading}\n\n{}", items.join("\n")));
+    }
+    sections.join("\n\n")
+}
+
+fn aggregate_tasks(
+    vault: &Vault,
+    input: &AggregateTasksInput,
+) -> Result<AggregateTasksOutput, ErrorData> {
+    let due_by = input.due_by.as_deref().map(daily::parse_date).transpose()?;
+    let status = input.status.unwrap_or_default();
+    let grouping = input.group_by.unwrap_or_default();
+    let tasks: Vec<Task> = vault
+        .notes
+        .iter()
+        .flat_map(tasks)
+        .filter(|task| match status {
+            TaskStatus::Open => !task.done,
+            TaskStatus::Done => task.done,
+            TaskStatus::All => true,
+        })
+        .filter(|task| {
+            input.tag.as_ref().is_none_or(|filter| {
+                task.tags
+                    .iter()
+                    .any(|tag| wikilinks::tag_matches(tag, filter))
+            })
+        })
+        .filter(|task| {
+            due_by.is_none_or(|due_by| {
+                task.due
+                    .as_deref()
+                    .and_then(|due| NaiveDate::parse_from_str(due, "%Y-%m-%d").ok())
+                    .is_some_and(|due| due <= due_by)
+            })
+        })
+        .collect();
+    let total = tasks.len();
+    let groups = group(tasks, grouping);
+    let report = (input.format.unwrap_or_default() == TaskReportFormat::Markdown)
+        .then(|| report(&groups, grouping));
+    Ok(AggregateTasksOutput {
+        groups,
+        total,
+        report,
+    })
+}
+
+#[tool_router(router = tasks_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Collect the task list items (`- [ ]`, `- [x]`) of every note in a directory (an Obsidian or Foam vault) into one TODO report, grouped by note, tag or due date. Due dates are read from the Obsidian Tasks, Dataview, todo.txt and TaskPaper annotations. Filter by status, tag and due date.",
+        output_schema = output_schema::<AggregateTasksOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn aggregate_tasks(&self, Parameters(input): Parameters<AggregateTasksInput>) -> McpResult {
+        let vault = Vault::read(&self.scoped_path(&input.directory)?)?;
+        json_result(&aggregate_tasks(&vault, &input)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn vault() -> Vault {
+        Vault::new(vec![
+            Note::new(
+                "Work.md",
+                "---\ntags: [work]\n---\n# Work\n\n\
+                 - [ ] Ship the release #work/urgent 📅 2026-10-20\n\
+                 - [x] Write the changelog #work\n\
+                 - [ ] Review the PR\n  - [ ] Check the tests [due:: 2026-10-18]\n\
+                 - Not a task #work\n",
+            ),
+            Note::new(
+                "Home.md",
+                "- [ ] Call the plumber @due(2026-10-18) #home\n\n```\n- [ ] Not a task\n```\n",
+            ),
+        ])
+    }
+
+    fn input() -> AggregateTasksInput {
+        AggregateTasksInput {
+            directory: String::new(),
+            status: None,
+            group_by: None,
+            tag: None,
+            due_by: None,
+            format: None,
+        }
+    }
+
+    fn keys(output: &AggregateTasksOutput) -> Vec<(Option<&str>, Vec<usize>)> {
+        output
+            .groups
+            .iter()
+            .map(|group| {
+                (
+                    group.key.as_deref(),
+                    group.tasks.iter().map(|task| task.line).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tasks() {
+        let tasks = tasks(&vault().notes[1]);
+        assert_eq!(
+            tasks,
+            vec![
+                Task {
+                    text: "Ship the release #work/urgent 📅 2026-10-20".to_string(),
+                    done: false,
+                    path: "Work.md".to_string(),
+                    line: 6,
+                    tags: vec!["work/urgent".to_string()],
+                    due: Some("2026-10-20".to_string()),
+                },
+                Task {
+                    text: "Write the changelog #work".to_string(),
+                    done: true,
+                    path: "Work.md".to_string(),
+                    line: 7,
+                    tags: vec!["work".to_string()],
+                    due: None,
+                },
+                Task {
+                    text: "Review the PR".to_string(),
+                    done: false,
+                    path: "Work.md".to_string(),
+                    line: 8,
+                    tags: Vec::new(),
+                    due: None,
+                },
+                Task {
+                    text: "Check the tests [due:: 2026-10-18]".to_string(),
+                    done: false,
+                    path: "Work.md".to_string(),
+                    line: 9,
+                    tags: Vec::new(),
+                    due: Some("2026-10-18".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case(TaskGrouping::File, vec![(Some("Home.md"), vec![1]), (Some("Work.md"), vec![6, 8, 9])])]
+    #[case(TaskGrouping::Tag, vec![(Some("home"), vec![1]), (Some("work/urgent"), vec![6]), (None, vec![8, 9])])]
+    #[case(TaskGrouping::Due, vec![(Some("2026-10-18"), vec![1, 9]), (Some("2026-10-20"), vec![6]), (None, vec![8])])]
+    fn test_group_by(
+        #[case] grouping: TaskGrouping,
+        #[case] expected: Vec<(Option<&str>, Vec<usize>)>,
+    ) {
+        let output = aggregate_tasks(
+            &vault(),
+            &AggregateTasksInput {
+                group_by: Some(grouping),
+                ..input()
+            },
+        )
+        .unwrap();
+        assert_eq!(keys(&output), expected);
+        assert_eq!(output.total, 4);
+    }
+
+    #[test]
+    fn test_filters_and_report() {
+        let output = aggregate_tasks(
+            &vault(),
+            &AggregateTasksInput {
+                status: Some(TaskStatus::All),
+                tag: Some("work".to_string()),
+                format: Some(TaskReportFormat::Markdown),
+                ..input()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            output.report.unwrap(),
+            "## Work.md\n\n\
+             - [ ] Ship the release #work/urgent 📅 2026-10-20 (Work.md:6)\n\
+             - [x] Write the changelog #work (Work.md:7)"
+        );
+
+        let output = aggregate_tasks(
+            &vault(),
+            &AggregateTasksInput {
+                due_by: Some("2026-10-19".to_string()),
+                ..input()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            keys(&output),
+            vec![(Some("Home.md"), vec![1]), (Some("Work.md"), vec![9])]
+        );
+
+        let result = aggregate_tasks(
+            &vault(),
+            &AggregateTasksInput {
+                due_by: Some("next week".to_string()),
+                ..input()
+            },
+        );
+        assert!(result.is_err());
+    }
+}
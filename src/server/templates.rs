@@ -0,0 +1,534 @@
+//! Note templates: markdown files in the configured template directory
+//! with `{{...}}` placeholders, filled in when a note is created from
+//! them. `create_note` and `daily_note` use them; `list_templates` and
+//! `render_template` show them and what they expand to.
+//!
+//! Placeholders are `{{date}}` and `{{time}}` (optionally with a strftime
+//! format, `{{date:%d %B %Y}}`), `{{title}}`, `{{query:<mq query>}}`, run
+//! on the source document of the call, and any other name, looked up in
+//! the fields of the call and then in the template's own front matter.
+//! Placeholders that can't be filled in are left as written.
+
+use std::{
+    fmt::Write,
+    fs,
+    io::{self, Write as _},
+    path::Path,
+    sync::LazyLock,
+};
+
+use chrono::{DateTime, Local, NaiveDate};
+use regex::Regex;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use serde_json::{Map, Value};
+
+use super::{
+    McpResult, Server, daily, dry_run, eval_nodes_sorted,
+    output::{json_result, output_schema},
+    parse_markdown, paths, sort,
+    vault::{self, Vault},
+    vault_query,
+};
+
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*([^{}\n]+?)\s*\}\}").unwrap());
+
+/// What a template is filled in with.
+pub(super) struct Context<'a> {
+    pub title: &'a str,
+    /// The date of `{{date}}`.
+    pub date: NaiveDate,
+    /// The time of `{{time}}`.
+    pub now: DateTime<Local>,
+    pub fields: &'a Map<String, Value>,
+    /// The markdown `{{query:...}}` placeholders run on; they are left
+    /// unfilled without it.
+    pub source: Option<&'a str>,
+}
+
+/// A filled in template.
+#[derive(Debug, PartialEq)]
+pub(super) struct Rendered {
+    pub markdown: String,
+    /// The placeholders left as written, each once.
+    pub unresolved: Vec<String>,
+}
+
+/// `formatted` (a date or time formatted with `format`) as a string.
+fn format(formatted: impl std::fmt::Display, format: &str) -> Result<String, ErrorData> {
+    let mut text = String::new();
+    write!(text, "{formatted}").map_err(|_| {
+        ErrorData::invalid_params(
+            "Invalid date format in the template",
+            Some(serde_json::json!({ "format": format })),
+        )
+    })?;
+    Ok(text)
+}
+
+/// The results of `query` run on `source`, one per line.
+fn query(source: &str, query: &str) -> Result<String, ErrorData> {
+    let (values, _) = eval_nodes_sorted(
+        parse_markdown(source)?,
+        query,
+        &sort::SortOptions::default(),
+    )?;
+    Ok(values
+        .into_iter()
+        .map(|(_, value)| value.to_string())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// The text `placeholder` (between the braces) stands for, if any.
+fn value(
+    placeholder: &str,
+    context: &Context,
+    defaults: &Map<String, Value>,
+) -> Result<Option<String>, ErrorData> {
+    let (name, argument) = match placeholder.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument.trim())),
+        None => (placeholder, None),
+    };
+    Ok(match (name, argument) {
+        ("date", argument) => {
+            let pattern = argument.unwrap_or("%Y-%m-%d");
+            Some(format(context.date.format(pattern), pattern)?)
+        }
+        ("time", argument) => {
+            let pattern = argument.unwrap_or("%H:%M");
+            Some(format(context.now.format(pattern), pattern)?)
+        }
+        ("title", None) => Some(context.title.to_string()),
+        ("query", Some(argument)) => context
+            .source
+            .map(|source| query(source, argument))
+            .transpose()?,
+        _ => context
+            .fields
+            .get(placeholder)
+            .map(vault_query::text)
+            .or_else(|| {
+                defaults
+                    .get(placeholder)
+                    .map(vault_query::text)
+                    .filter(|text| !text.contains("{{"))
+            }),
+    })
+}
+
+/// Fills in the placeholders of `template`.
+pub(super) fn render(template: &str, context: &Context) -> Result<Rendered, ErrorData> {
+    let defaults = vault::front_matter_fields(template).unwrap_or_default();
+    let mut markdown = String::with_capacity(template.len());
+    let mut unresolved: Vec<String> = Vec::new();
+    let mut end = 0;
+    for captures in PLACEHOLDER.captures_iter(template) {
+        let (whole, placeholder) = (captures.get(0).unwrap(), &captures[1]);
+        markdown.push_str(&template[end..whole.start()]);
+        match value(placeholder, context, &defaults)? {
+            Some(value) => markdown.push_str(&value),
+            None => {
+                markdown.push_str(whole.as_str());
+                if !unresolved.iter().any(|p| p == placeholder) {
+                    unresolved.push(placeholder.to_string());
+                }
+            }
+        }
+        end = whole.end();
+    }
+    markdown.push_str(&template[end..]);
+    Ok(Rendered {
+        markdown,
+        unresolved,
+    })
+}
+
+/// The placeholders of `template`, each once, in order.
+fn placeholders(template: &str) -> Vec<String> {
+    let mut placeholders: Vec<String> = Vec::new();
+    for captures in PLACEHOLDER.captures_iter(template) {
+        if !placeholders.iter().any(|p| p == &captures[1]) {
+            placeholders.push(captures[1].to_string());
+        }
+    }
+    placeholders
+}
+
+impl Server {
+    pub(super) fn templates(&self) -> Result<Vault, ErrorData> {
+        let dir = self.config.templates_dir.as_deref().ok_or_else(|| {
+            ErrorData::invalid_request(
+                "no template directory configured — restart mq-mcp with --templates <dir> to enable list_templates, render_template and create_note",
+                None,
+            )
+        })?;
+        Vault::read(dir)
+    }
+
+    /// The template named `name` (its path in the template directory,
+    /// with or without the extension, or its file name).
+    pub(super) fn template(&self, name: &str) -> Result<String, ErrorData> {
+        let templates = self.templates()?;
+        templates
+            .resolve(name)
+            .map(|template| template.markdown.clone())
+            .ok_or_else(|| {
+                ErrorData::invalid_params(
+                    "Template not found",
+                    Some(serde_json::json!({
+                        "template": name,
+                        "templates": templates
+                            .notes
+                            .iter()
+                            .map(|note| vault::strip_extension(&note.path))
+                            .collect::<Vec<_>>(),
+                    })),
+                )
+            })
+    }
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct TemplateInfo {
+    #[schemars(description = "The template's path in the template directory, without `.md`")]
+    pub name: String,
+    #[schemars(description = "The placeholders of the template, between `{{` and `}}`")]
+    pub placeholders: Vec<String>,
+}
+
+/// Output of `list_templates`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ListTemplatesOutput {
+    pub templates: Vec<TemplateInfo>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct RenderTemplateInput {
+    #[schemars(description = "The template's name, as listed by list_templates")]
+    pub template: String,
+    #[schemars(description = "The value of `{{title}}`")]
+    pub title: Option<String>,
+    #[schemars(description = "The date of `{{date}}`, as YYYY-MM-DD (default: today)")]
+    pub date: Option<String>,
+    #[schemars(
+        description = "Values of the other placeholders by name, e.g. `{\"project\": \"Engine\"}`; these override the template's front matter fields"
+    )]
+    pub fields: Option<Map<String, Value>>,
+    #[schemars(description = "The markdown `{{query:...}}` placeholders run on")]
+    pub source: Option<String>,
+}
+
+/// Output of `render_template`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct RenderTemplateOutput {
+    pub markdown: String,
+    #[schemars(description = "Placeholders left as written, with no value to fill in")]
+    pub unresolved: Vec<String>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct CreateNoteInput {
+    #[schemars(
+        description = "Path of the note in the vault, e.g. `Meetings/2026-10-16 Standup.md`; `.md` is added when missing"
+    )]
+    pub path: String,
+    #[schemars(description = "The template's name, as listed by list_templates")]
+    pub template: String,
+    #[schemars(description = "The value of `{{title}}` (default: the note's file name)")]
+    pub title: Option<String>,
+    #[schemars(description = "The date of `{{date}}`, as YYYY-MM-DD (default: today)")]
+    pub date: Option<String>,
+    #[schemars(
+        description = "Values of the other placeholders by name; these override the template's front matter fields"
+    )]
+    pub fields: Option<Map<String, Value>>,
+    #[schemars(description = "The markdown `{{query:...}}` placeholders run on")]
+    pub source: Option<String>,
+    #[schemars(description = "Replace the note if it exists (default: false)")]
+    pub overwrite: Option<bool>,
+}
+
+/// Output of `create_note`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct CreateNoteOutput {
+    #[schemars(description = "Path of the note in the vault")]
+    pub path: String,
+    pub markdown: String,
+    #[schemars(description = "Placeholders left as written, with no value to fill in")]
+    pub unresolved: Vec<String>,
+    #[schemars(description = "Whether this was a dry run that wrote no note")]
+    pub dry_run: bool,
+}
+
+/// `path` as a note path in the vault, with `/` separators and `.md`.
+fn note_path(path: &str) -> Result<String, ErrorData> {
+    let parts: Vec<&str> = path
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    if parts.is_empty()
+        || Path::new(path).is_absolute()
+        || parts
+            .iter()
+            .any(|part| *part == ".." || paths::is_reserved_name(part))
+    {
+        return Err(ErrorData::invalid_params(
+            "Invalid note path",
+            Some(serde_json::json!({ "path": path })),
+        ));
+    }
+    let path = parts.join("/");
+    Ok(if paths::is_markdown(Path::new(&path)) {
+        path
+    } else {
+        format!("{path}.md")
+    })
+}
+
+fn date(date: Option<&str>) -> Result<NaiveDate, ErrorData> {
+    date.map_or_else(|| Ok(Local::now().date_naive()), daily::parse_date)
+}
+
+#[tool_router(router = templates_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "List the note templates of the configured template directory, with the placeholders of each.",
+        output_schema = output_schema::<ListTemplatesOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn list_templates(&self) -> McpResult {
+        json_result(&ListTemplatesOutput {
+            templates: self
+                .templates()?
+                .notes
+                .iter()
+                .map(|note| TemplateInfo {
+                    name: vault::strip_extension(&note.path).to_string(),
+                    placeholders: placeholders(&note.markdown),
+                })
+                .collect(),
+        })
+    }
+
+    #[tool(
+        description = "Fill in a note template from the configured template directory without creating a note: `{{date}}`, `{{time}}` and `{{title}}`, `{{query:...}}` with the results of an mq query on a source document, and other placeholders with the given fields or the template's front matter.",
+        output_schema = output_schema::<RenderTemplateOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn render_template(&self, Parameters(input): Parameters<RenderTemplateInput>) -> McpResult {
+        let template = self.template(&input.template)?;
+        let rendered = render(
+            &template,
+            &Context {
+                title: input.title.as_deref().unwrap_or_default(),
+                date: date(input.date.as_deref())?,
+                now: Local::now(),
+                fields: &input.fields.unwrap_or_default(),
+                source: input.source.as_deref(),
+            },
+        )?;
+        json_result(&RenderTemplateOutput {
+            markdown: rendered.markdown,
+            unresolved: rendered.unresolved,
+        })
+    }
+
+    #[tool(
+        description = "Create a note in the configured vault from a note template, filling in its placeholders as render_template does. Refuses to replace an existing note unless `overwrite` is set.",
+        output_schema = output_schema::<CreateNoteOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    fn create_note(&self, Parameters(input): Parameters<CreateNoteInput>) -> McpResult {
+        let vault = self.vault_dir()?;
+        let path = note_path(&input.path)?;
+        let file = vault.join(&path);
+        let overwrite = input.overwrite.unwrap_or(false);
+        if file.exists() && !overwrite {
+            return Err(already_exists(&path));
+        }
+        let template = self.template(&input.template)?;
+        let name = vault::strip_extension(path.rsplit('/').next().unwrap_or(&path));
+        let rendered = render(
+            &template,
+            &Context {
+                title: input.title.as_deref().unwrap_or(name),
+                date: date(input.date.as_deref())?,
+                now: Local::now(),
+                fields: &input.fields.unwrap_or_default(),
+                source: input.source.as_deref(),
+            },
+        )?;
+        let dry_run = dry_run::active();
+        if !dry_run {
+            write(&file, &rendered.markdown, overwrite).map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => already_exists(&path),
+                _ => write_error(&path, e),
+            })?;
+        }
+        json_result(&CreateNoteOutput {
+            path,
+            markdown: rendered.markdown,
+            unresolved: rendered.unresolved,
+            dry_run,
+        })
+    }
+}
+
+/// Writes `markdown` to `file`, creating its folders. Without `overwrite`
+/// the file must not exist when it is opened, so a note created since the
+/// call checked is left alone.
+pub(super) fn write(file: &Path, markdown: &str, overwrite: bool) -> io::Result<()> {
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if overwrite {
+        return fs::write(file, markdown);
+    }
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(file)?
+        .write_all(markdown.as_bytes())
+}
+
+fn already_exists(path: &str) -> ErrorData {
+    ErrorData::invalid_params(
+        "Note already exists",
+        Some(serde_json::json!({ "path": path })),
+    )
+}
+
+fn write_error(path: &str, e: io::Error) -> ErrorData {
+    ErrorData::internal_error(
+        "Failed to write the note",
+        Some(serde_json::json!({ "path": path, "error": e.to_string() })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rstest::rstest;
+
+    fn context<'a>(fields: &'a Map<String, Value>, source: Option<&'a str>) -> Context<'a> {
+        Context {
+            title: "Standup",
+            date: daily::parse_date("2026-10-16").unwrap(),
+            now: Local.with_ymd_and_hms(2026, 10, 16, 9, 30, 0).unwrap(),
+            fields,
+            source,
+        }
+    }
+
+    #[rstest]
+    #[case("# {{title}}", "# Standup")]
+    #[case("{{date}} {{ time }}", "2026-10-16 09:30")]
+    #[case("{{date:%d %B %Y}}, {{time:%H}}h", "16 October 2026, 09h")]
+    #[case("Project: {{project}}", "Project: Engine")]
+    #[case("Tags: {{tags}}", "Tags: daily, work")]
+    #[case("{{query:.h2 | to_text()}}", "Goals\nNotes")]
+    #[case(
+        "---\nstatus: draft\nowner: \"{{owner}}\"\n---\n{{status}} by {{owner}}",
+        "---\nstatus: draft\nowner: \"{{owner}}\"\n---\ndraft by {{owner}}"
+    )]
+    fn test_render(#[case] template: &str, #[case] expected: &str) {
+        let fields = serde_json::json!({ "project": "Engine", "tags": ["daily", "work"] });
+        let fields = fields.as_object().unwrap();
+        let rendered = render(template, &context(fields, Some("## Goals\n\n## Notes\n"))).unwrap();
+        assert_eq!(rendered.markdown, expected);
+    }
+
+    #[test]
+    fn test_unresolved() {
+        let rendered = render(
+            "{{query:.h1}} {{missing}} {{missing}}",
+            &context(&Map::new(), None),
+        )
+        .unwrap();
+        assert_eq!(rendered.markdown, "{{query:.h1}} {{missing}} {{missing}}");
+        assert_eq!(rendered.unresolved, vec!["query:.h1", "missing"]);
+        assert!(render("{{date:%H}}", &context(&Map::new(), None)).is_err());
+        assert_eq!(
+            placeholders("{{title}} {{ date:%Y }} {{title}}"),
+            vec!["title", "date:%Y"]
+        );
+    }
+
+    #[rstest]
+    #[case("Meetings/Standup", Ok("Meetings/Standup.md"))]
+    #[case("./notes\\Idea.markdown", Ok("notes/Idea.markdown"))]
+    #[case("../outside.md", Err(()))]
+    #[case("/etc/notes.md", Err(()))]
+    #[case("", Err(()))]
+    fn test_note_path(#[case] path: &str, #[case] expected: Result<&str, ()>) {
+        assert_eq!(
+            note_path(path).map_err(|_| ()),
+            expected.map(str::to_string)
+        );
+    }
+
+    #[test]
+    fn test_create_note() {
+        let vault = tempfile::tempdir().unwrap();
+        let templates = tempfile::tempdir().unwrap();
+        fs::write(
+            templates.path().join("Meeting.md"),
+            "# {{title}}\n\nAttendees: {{attendees}}\n",
+        )
+        .unwrap();
+        let server = Server::with_config(crate::server::ServerConfig {
+            vault: Some(vault.path().to_path_buf()),
+            templates_dir: Some(templates.path().to_path_buf()),
+            ..Default::default()
+        })
+        .unwrap();
+        let input = || CreateNoteInput {
+            path: "Meetings/Standup".to_string(),
+            template: "meeting".to_string(),
+            title: None,
+            date: None,
+            fields: serde_json::json!({ "attendees": "Ada, Grace" })
+                .as_object()
+                .cloned(),
+            source: None,
+            overwrite: None,
+        };
+
+        server.create_note(Parameters(input())).unwrap();
+        assert_eq!(
+            fs::read_to_string(vault.path().join("Meetings/Standup.md")).unwrap(),
+            "# Standup\n\nAttendees: Ada, Grace\n"
+        );
+        assert!(server.create_note(Parameters(input())).is_err());
+        assert!(
+            server
+                .create_note(Parameters(CreateNoteInput {
+                    template: "Missing".to_string(),
+                    overwrite: Some(true),
+                    ..input()
+                }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_write_keeps_existing_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("Notes/Idea.md");
+        write(&file, "first", false).unwrap();
+        let e = write(&file, "second", false).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "first");
+        write(&file, "second", true).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "second");
+    }
+}
@@ -0,0 +1,270 @@
+//! `trace_query`: evaluates a query stage by stage so agents can see where a
+//! pipeline stops producing values.
+
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    engine::{QueryEngine, QueryValue},
+    new_engine,
+    output::{json_result, output_schema},
+    parse_input,
+    suggest::query_error,
+};
+
+/// Default number of values shown per stage.
+const DEFAULT_MAX_VALUES: usize = 10;
+/// Rendered values longer than this many characters are truncated.
+const MAX_VALUE_CHARS: usize = 200;
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct TraceQueryInput {
+    #[schemars(description = "The markdown content to process")]
+    markdown: String,
+    #[schemars(
+        description = "The mq query to trace, e.g. `.h | select(contains(\"API\")) | upcase()`"
+    )]
+    query: String,
+    #[schemars(description = "Maximum number of values shown per stage (default: 10)")]
+    max_values: Option<usize>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct TraceStage {
+    #[schemars(description = "Source text of this pipeline stage")]
+    stage: String,
+    #[schemars(description = "Number of non-empty values after this stage")]
+    count: usize,
+    #[schemars(
+        description = "Number of None/empty values after this stage (dropped from final results)"
+    )]
+    empty: usize,
+    #[schemars(description = "The first values after this stage, rendered as markdown")]
+    values: Vec<String>,
+    #[schemars(description = "Whether `values` or any value in it was cut short")]
+    truncated: bool,
+    #[schemars(description = "Evaluation error raised by this stage; later stages are not run")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct TraceOutput {
+    stages: Vec<TraceStage>,
+}
+
+/// Splits `query` at top-level `|` pipes, ignoring pipes inside brackets,
+/// string literals and comments, as well as `||` and `|=`.
+fn split_pipes(query: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut chars = query.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '#' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '|' => match chars.peek() {
+                Some((_, '|' | '=')) => {
+                    chars.next();
+                }
+                _ if depth == 0 => {
+                    segments.push(&query[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    segments.push(&query[start..]);
+    segments
+}
+
+/// Groups the raw pipe segments into stages whose cumulative prefix
+/// compiles — a `|` inside e.g. a `def ...;` body splits the text but not
+/// the pipeline, so such segments are merged with the next one.
+fn pipeline_stages(query: &str) -> Result<Vec<(String, String)>, ErrorData> {
+    let mut stages = Vec::new();
+    let mut prefix = String::new();
+    let mut pending = String::new();
+
+    for segment in split_pipes(query) {
+        if !pending.is_empty() {
+            pending.push('|');
+        }
+        pending.push_str(segment);
+
+        let candidate = if prefix.is_empty() {
+            pending.clone()
+        } else {
+            format!("{prefix}|{pending}")
+        };
+        if new_engine().compile(&candidate).is_ok() {
+            stages.push((pending.trim().to_string(), candidate.clone()));
+            prefix = candidate;
+            pending.clear();
+        }
+    }
+
+    if !pending.is_empty() {
+        // The full query doesn't compile; surface the same error as eval.
+        let err = new_engine().compile(query).err().unwrap_or_default();
+        return Err(query_error(query, err));
+    }
+    Ok(stages)
+}
+
+fn truncate_value(mut value: String) -> (String, bool) {
+    match value.char_indices().nth(MAX_VALUE_CHARS) {
+        Some((idx, _)) => {
+            value.truncate(idx);
+            value.push('…');
+            (value, true)
+        }
+        None => (value, false),
+    }
+}
+
+#[tool_router(router = trace_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Debug an mq query: evaluate it stage by stage (split at top-level `|`) and return the values after each stage, capped in size. Use this when a query returns nothing to find the stage that drops everything.",
+        output_schema = output_schema::<TraceOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn trace_query(
+        &self,
+        Parameters(TraceQueryInput {
+            markdown,
+            query,
+            max_values,
+        }): Parameters<TraceQueryInput>,
+    ) -> McpResult {
+        let input = parse_input(&markdown)?;
+        let max_values = max_values.unwrap_or(DEFAULT_MAX_VALUES);
+        let mut stages = Vec::new();
+
+        for (stage, prefix) in pipeline_stages(&query)? {
+            let mut engine = new_engine();
+            match engine.eval(&prefix, input.clone().into_iter()) {
+                Ok(values) => {
+                    let (non_empty, empty): (Vec<_>, Vec<_>) =
+                        values.into_iter().partition(|v| !v.is_blank());
+                    let mut truncated = non_empty.len() > max_values;
+                    let values = non_empty
+                        .iter()
+                        .take(max_values)
+                        .map(|v| {
+                            let (value, cut) = truncate_value(v.to_string());
+                            truncated |= cut;
+                            value
+                        })
+                        .collect();
+                    stages.push(TraceStage {
+                        stage,
+                        count: non_empty.len(),
+                        empty: empty.len(),
+                        values,
+                        truncated,
+                        error: None,
+                    });
+                }
+                Err(e) => {
+                    stages.push(TraceStage {
+                        stage,
+                        count: 0,
+                        empty: 0,
+                        values: vec![],
+                        truncated: false,
+                        error: Some(e),
+                    });
+                    break;
+                }
+            }
+        }
+
+        json_result(&TraceOutput { stages })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(".h | upcase()", vec![".h ", " upcase()"])]
+    #[case(r#"select(or(.h1, .h2)) | add("|")"#, vec!["select(or(.h1, .h2)) ", r#" add("|")"#])]
+    #[case(".h || .code", vec![".h || .code"])]
+    #[case(".h # a | comment\n| upcase()", vec![".h # a | comment\n", " upcase()"])]
+    fn test_split_pipes(#[case] query: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(split_pipes(query), expected);
+    }
+
+    #[test]
+    fn test_trace_query_reports_each_stage() {
+        let server = Server::new(None).unwrap();
+        let result = server
+            .trace_query(Parameters(TraceQueryInput {
+                markdown: "# Hello\n\n## World".to_string(),
+                query: ".h | select(contains(\"Nope\"))".to_string(),
+                max_values: None,
+            }))
+            .unwrap();
+        let output = result.structured_content.unwrap();
+        let stages = output["stages"].as_array().unwrap();
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0]["stage"], ".h");
+        assert_eq!(stages[0]["count"], 2);
+        assert_eq!(stages[1]["count"], 0);
+    }
+
+    #[test]
+    fn test_trace_query_caps_values() {
+        let server = Server::new(None).unwrap();
+        let result = server
+            .trace_query(Parameters(TraceQueryInput {
+                markdown: "# A\n\n# B\n\n# C".to_string(),
+                query: ".h".to_string(),
+                max_values: Some(1),
+            }))
+            .unwrap();
+        let output = result.structured_content.unwrap();
+        assert_eq!(output["stages"][0]["values"].as_array().unwrap().len(), 1);
+        assert_eq!(output["stages"][0]["truncated"], true);
+    }
+
+    #[test]
+    fn test_trace_query_invalid_query() {
+        let server = Server::new(None).unwrap();
+        let err = server
+            .trace_query(Parameters(TraceQueryInput {
+                markdown: "# A".to_string(),
+                query: "not_a_function(".to_string(),
+                max_values: None,
+            }))
+            .expect_err("expected an error");
+        assert!(err.message.contains("Failed to query"));
+    }
+}
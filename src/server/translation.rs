@@ -0,0 +1,530 @@
+//! `segment_for_translation` and `reassemble_translation`: translation
+//! units cut from the markdown source, and the document rebuilt with
+//! their translations.
+//!
+//! Units are spans of the source (the inline content of headings,
+//! paragraphs and table cells, optionally split into sentences), so
+//! reassembly replaces exactly those spans and leaves the markup around
+//! them untouched. Ids are positional (`b3`, `b3.s2`), so segmenting the
+//! same document again yields the same ids.
+
+use std::collections::HashMap;
+
+use markdown::mdast;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{
+    McpResult, Server,
+    ast::parse_mdast,
+    output::{json_result, output_schema},
+    source_map::{Rewrite, RewriteOptions, RewriteOutput},
+};
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Granularity {
+    /// One unit per heading, paragraph and table cell.
+    #[default]
+    Paragraph,
+    /// Paragraphs split into sentences.
+    Sentence,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UnitKind {
+    Heading,
+    Paragraph,
+    TableCell,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SegmentForTranslationInput {
+    #[schemars(description = "The markdown to segment")]
+    pub markdown: String,
+    #[schemars(
+        description = "`paragraph` (default) for one unit per heading, paragraph and table cell, or `sentence` to split paragraphs into sentences"
+    )]
+    pub granularity: Option<Granularity>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct TranslatedUnit {
+    #[schemars(description = "The unit id from segment_for_translation")]
+    pub id: String,
+    #[schemars(description = "The translated text, keeping the unit's inline markdown")]
+    pub text: String,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ReassembleTranslationInput {
+    #[schemars(description = "The original markdown passed to segment_for_translation")]
+    pub markdown: String,
+    #[schemars(description = "The granularity used to segment it (default: `paragraph`)")]
+    pub granularity: Option<Granularity>,
+    #[schemars(description = "Translated units; units left out keep their original text")]
+    pub units: Vec<TranslatedUnit>,
+    #[serde(flatten)]
+    pub rewrite: RewriteOptions,
+}
+
+#[derive(Debug, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct TranslationUnit {
+    #[schemars(description = "Stable id: `b<block>` or `b<block>.s<sentence>`")]
+    pub id: String,
+    pub kind: UnitKind,
+    #[schemars(description = "The text to translate, with its inline markdown")]
+    pub text: String,
+}
+
+/// Output of `segment_for_translation`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SegmentsOutput {
+    #[schemars(description = "Units in document order")]
+    pub units: Vec<TranslationUnit>,
+}
+
+/// Output of `reassemble_translation`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ReassembleOutput {
+    /// The document with the translated units in place.
+    #[serde(flatten)]
+    pub rewrite: RewriteOutput,
+    #[schemars(description = "Ids of units that kept their original text")]
+    pub untranslated: Vec<String>,
+}
+
+/// A unit's span of the source.
+#[derive(Debug)]
+struct Segment {
+    id: String,
+    kind: UnitKind,
+    start: usize,
+    end: usize,
+    /// Blockquote markers continuation lines of the block need, e.g. `> `.
+    quote: String,
+}
+
+fn span(node: &mdast::Node) -> Option<(usize, usize)> {
+    node.position().map(|p| (p.start.offset, p.end.offset))
+}
+
+/// Spans inside which a sentence never ends: links, images, code and math.
+fn protected(nodes: &[mdast::Node], spans: &mut Vec<(usize, usize)>) {
+    for node in nodes {
+        match node {
+            mdast::Node::Link(_)
+            | mdast::Node::LinkReference(_)
+            | mdast::Node::Image(_)
+            | mdast::Node::ImageReference(_)
+            | mdast::Node::InlineCode(_)
+            | mdast::Node::InlineMath(_)
+            | mdast::Node::Html(_) => spans.extend(span(node)),
+            _ => protected(
+                node.children().map(Vec::as_slice).unwrap_or_default(),
+                spans,
+            ),
+        }
+    }
+}
+
+/// Splits `start..end` into trimmed sentence spans.
+fn sentences(
+    markdown: &str,
+    start: usize,
+    end: usize,
+    children: &[mdast::Node],
+) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    protected(children, &mut spans);
+    // Soft line breaks would end a sentence, so they are read as spaces;
+    // both are one byte, so the offsets still match the source.
+    let mut bounds: Vec<usize> = markdown[start..end]
+        .replace(['\r', '\n'], " ")
+        .split_sentence_bound_indices()
+        .map(|(offset, _)| start + offset)
+        .filter(|&bound| !spans.iter().any(|&(s, e)| s < bound && bound < e))
+        .collect();
+    bounds.push(end);
+    bounds
+        .windows(2)
+        .filter_map(|pair| {
+            let text = &markdown[pair[0]..pair[1]];
+            let lead = text.len() - text.trim_start().len();
+            let trimmed = text.trim();
+            (!trimmed.is_empty()).then(|| (pair[0] + lead, pair[0] + lead + trimmed.len()))
+        })
+        .collect()
+}
+
+fn collect(
+    markdown: &str,
+    nodes: &[mdast::Node],
+    granularity: Granularity,
+    blocks: &mut usize,
+    segments: &mut Vec<Segment>,
+) {
+    for node in nodes {
+        let (kind, children) = match node {
+            mdast::Node::Heading(heading) => (UnitKind::Heading, &heading.children),
+            mdast::Node::Paragraph(paragraph) => (UnitKind::Paragraph, &paragraph.children),
+            mdast::Node::TableCell(cell) => (UnitKind::TableCell, &cell.children),
+            mdast::Node::Code(_)
+            | mdast::Node::Math(_)
+            | mdast::Node::Html(_)
+            | mdast::Node::Yaml(_)
+            | mdast::Node::Toml(_)
+            | mdast::Node::Definition(_) => continue,
+            _ => {
+                if let Some(children) = node.children() {
+                    collect(markdown, children, granularity, blocks, segments);
+                }
+                continue;
+            }
+        };
+        let (Some((start, _)), Some((_, end))) = (
+            children.first().and_then(span),
+            children.last().and_then(span),
+        ) else {
+            continue;
+        };
+        *blocks += 1;
+        let line_start = markdown[..start].rfind('\n').map_or(0, |i| i + 1);
+        let quote = "> ".repeat(markdown[line_start..start].matches('>').count());
+        let segment = |id: String, (start, end): (usize, usize)| Segment {
+            id,
+            kind,
+            start,
+            end,
+            quote: quote.clone(),
+        };
+        if granularity == Granularity::Sentence && kind == UnitKind::Paragraph {
+            for (i, span) in sentences(markdown, start, end, children)
+                .into_iter()
+                .enumerate()
+            {
+                segments.push(segment(format!("b{blocks}.s{}", i + 1), span));
+            }
+        } else {
+            segments.push(segment(format!("b{blocks}"), (start, end)));
+        }
+    }
+}
+
+fn segments(markdown: &str, granularity: Granularity) -> Result<Vec<Segment>, ErrorData> {
+    let root = parse_mdast(markdown)?;
+    let mut segments = Vec::new();
+    collect(
+        markdown,
+        root.children().map(Vec::as_slice).unwrap_or_default(),
+        granularity,
+        &mut 0,
+        &mut segments,
+    );
+    Ok(segments)
+}
+
+/// The text of a segment, without the blockquote markers and indentation
+/// of its continuation lines.
+fn unit_text(markdown: &str, segment: &Segment) -> String {
+    markdown[segment.start..segment.end]
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                line
+            } else {
+                line.trim_start_matches([' ', '\t', '>'])
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The source text for a translation of `segment`: single-line units are
+/// kept on one line, and continuation lines of quoted paragraphs get
+/// their blockquote markers back.
+fn replacement(segment: &Segment, text: &str) -> String {
+    let text = text.trim();
+    match segment.kind {
+        UnitKind::Heading => text.split_whitespace().collect::<Vec<_>>().join(" "),
+        UnitKind::TableCell => {
+            let mut cell = String::with_capacity(text.len());
+            let mut escaped = false;
+            for c in text.chars() {
+                if c == '\n' {
+                    cell.push(' ');
+                } else {
+                    if c == '|' && !escaped {
+                        cell.push('\\');
+                    }
+                    cell.push(c);
+                }
+                escaped = c == '\\' && !escaped;
+            }
+            cell
+        }
+        UnitKind::Paragraph => text.replace('\n', &format!("\n{}", segment.quote)),
+    }
+}
+
+fn reassemble(
+    markdown: &str,
+    granularity: Granularity,
+    units: &[TranslatedUnit],
+    options: &RewriteOptions,
+) -> Result<ReassembleOutput, ErrorData> {
+    let segments = segments(markdown, granularity)?;
+    let translations: HashMap<&str, &str> = units
+        .iter()
+        .map(|unit| (unit.id.as_str(), unit.text.as_str()))
+        .collect();
+    let unknown: Vec<&str> = translations
+        .keys()
+        .copied()
+        .filter(|id| !segments.iter().any(|segment| segment.id == *id))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(ErrorData::invalid_params(
+            "Unknown translation unit ids",
+            Some(serde_json::json!({ "ids": unknown })),
+        ));
+    }
+
+    let mut rewrite = Rewrite::new(markdown);
+    let mut untranslated = Vec::new();
+    for segment in &segments {
+        match translations.get(segment.id.as_str()) {
+            Some(text) => rewrite.replace(segment.start..segment.end, &replacement(segment, text)),
+            None => untranslated.push(segment.id.clone()),
+        }
+    }
+    Ok(ReassembleOutput {
+        rewrite: rewrite.finish().render(options),
+        untranslated,
+    })
+}
+
+#[tool_router(router = translation_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Split markdown into translation units: the text of each heading, paragraph and table cell (or each sentence of a paragraph), with its inline markdown and a stable id. Code, math, HTML and front matter are left out. Translate the units, then rebuild the document with reassemble_translation.",
+        output_schema = output_schema::<SegmentsOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn segment_for_translation(
+        &self,
+        Parameters(input): Parameters<SegmentForTranslationInput>,
+    ) -> McpResult {
+        let segments = segments(&input.markdown, input.granularity.unwrap_or_default())?;
+        json_result(&SegmentsOutput {
+            units: segments
+                .iter()
+                .map(|segment| TranslationUnit {
+                    id: segment.id.clone(),
+                    kind: segment.kind,
+                    text: unit_text(&input.markdown, segment),
+                })
+                .collect(),
+        })
+    }
+
+    #[tool(
+        description = "Rebuild a markdown document from the units of segment_for_translation and their translations. Only the units' text is replaced, so headings, lists, tables, code and links keep their original structure. Units without a translation keep their original text and are listed in `untranslated`.",
+        output_schema = output_schema::<ReassembleOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn reassemble_translation(
+        &self,
+        Parameters(input): Parameters<ReassembleTranslationInput>,
+    ) -> McpResult {
+        json_result(&reassemble(
+            &input.markdown,
+            input.granularity.unwrap_or_default(),
+            &input.units,
+            &input.rewrite,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::source_map::RewriteFormat;
+    use rstest::rstest;
+
+    const MARKDOWN: &str = "\
+# Getting started
+
+Install the tool. See [the guide. It helps](https://example.com) first.
+
+> Quoted text that
+> spans two lines.
+
+```sh
+echo not translated
+```
+
+| Name | Note |
+| ---- | ---- |
+| a    | Short note |
+";
+
+    fn units(granularity: Granularity) -> Vec<(String, String)> {
+        segments(MARKDOWN, granularity)
+            .unwrap()
+            .iter()
+            .map(|segment| (segment.id.clone(), unit_text(MARKDOWN, segment)))
+            .collect()
+    }
+
+    #[test]
+    fn test_segments_paragraphs() {
+        assert_eq!(
+            units(Granularity::Paragraph),
+            vec![
+                ("b1".to_string(), "Getting started".to_string()),
+                (
+                    "b2".to_string(),
+                    "Install the tool. See [the guide. It helps](https://example.com) first."
+                        .to_string()
+                ),
+                (
+                    "b3".to_string(),
+                    "Quoted text that\nspans two lines.".to_string()
+                ),
+                ("b4".to_string(), "Name".to_string()),
+                ("b5".to_string(), "Note".to_string()),
+                ("b6".to_string(), "a".to_string()),
+                ("b7".to_string(), "Short note".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_segments_sentences() {
+        let units = units(Granularity::Sentence);
+        assert_eq!(
+            units[1..3],
+            [
+                ("b2.s1".to_string(), "Install the tool.".to_string()),
+                (
+                    "b2.s2".to_string(),
+                    "See [the guide. It helps](https://example.com) first.".to_string()
+                ),
+            ]
+        );
+        assert_eq!(units[3].0, "b3.s1");
+    }
+
+    #[rstest]
+    #[case(Granularity::Paragraph)]
+    #[case(Granularity::Sentence)]
+    fn test_reassemble_without_translations_is_identity(#[case] granularity: Granularity) {
+        let options = RewriteOptions {
+            output: None,
+            source_map: Some(true),
+        };
+        let output = reassemble(MARKDOWN, granularity, &[], &options).unwrap();
+        assert_eq!(output.rewrite.markdown.as_deref(), Some(MARKDOWN));
+        assert_eq!(
+            output.untranslated.len(),
+            segments(MARKDOWN, granularity).unwrap().len()
+        );
+        assert_eq!(output.rewrite.source_map.unwrap().len(), 1);
+    }
+
+    fn translate(output: Option<RewriteFormat>) -> ReassembleOutput {
+        let unit = |id: &str, text: &str| TranslatedUnit {
+            id: id.to_string(),
+            text: text.to_string(),
+        };
+        reassemble(
+            MARKDOWN,
+            Granularity::Paragraph,
+            &[
+                unit("b1", "はじめに\n"),
+                unit("b3", "二行に\nまたがる引用。"),
+                unit("b7", "短い | メモ"),
+            ],
+            &RewriteOptions {
+                output,
+                source_map: Some(true),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let output = translate(None);
+        let markdown = output.rewrite.markdown.unwrap();
+        assert!(markdown.starts_with("# はじめに\n\nInstall the tool."));
+        assert!(markdown.contains("> 二行に\n> またがる引用。\n"));
+        assert!(markdown.contains("| a    | 短い \\| メモ |"));
+        assert!(markdown.contains("echo not translated"));
+        assert_eq!(output.untranslated, vec!["b2", "b4", "b5", "b6"]);
+
+        // The changed ranges of the source map turn the input into the output.
+        let mut edited = MARKDOWN.to_string();
+        for mapping in output.rewrite.source_map.unwrap().iter().rev() {
+            if mapping.changed {
+                edited.replace_range(
+                    mapping.input.start.offset..mapping.input.end.offset,
+                    &markdown[mapping.output.start.offset..mapping.output.end.offset],
+                );
+            }
+        }
+        assert_eq!(edited, markdown);
+    }
+
+    #[test]
+    fn test_reassemble_as_edits() {
+        let output = translate(Some(RewriteFormat::Edits));
+        assert!(output.rewrite.markdown.is_none());
+        let edits = output.rewrite.edits.unwrap();
+        let texts: Vec<_> = edits.iter().map(|edit| edit.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["はじめに", "二行に\n> またがる引用。", "短い \\| メモ"]
+        );
+        assert_eq!(edits[0].range.start.line, 1);
+        assert_eq!(edits[0].range.start.column, 3);
+    }
+
+    #[test]
+    fn test_reassemble_as_diff() {
+        let diff = translate(Some(RewriteFormat::Diff)).rewrite.diff.unwrap();
+        assert!(diff.starts_with(
+            "--- input\n+++ output\n@@ -1,9 +1,9 @@\n-# Getting started\n+# はじめに\n"
+        ));
+        assert!(
+            diff.contains(
+                "-> Quoted text that\n-> spans two lines.\n+> 二行に\n+> またがる引用。\n"
+            )
+        );
+        assert!(diff.ends_with("-| a    | Short note |\n+| a    | 短い \\| メモ |\n"));
+    }
+
+    #[test]
+    fn test_reassemble_rejects_unknown_ids() {
+        let units = [TranslatedUnit {
+            id: "b99".to_string(),
+            text: "x".to_string(),
+        }];
+        assert!(
+            reassemble(
+                MARKDOWN,
+                Granularity::Paragraph,
+                &units,
+                &RewriteOptions::default()
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! A progressive mq tutorial inside the client: each lesson is a resource
+//! (`mq://tutorial/<lesson>`, indexed at `mq://tutorial`) explaining one
+//! idea with runnable examples, and `run_example` runs an example, or a
+//! variation of its query, on the lesson's document. New users can learn mq
+//! without leaving the client, and agents can check how a construct behaves
+//! before using it.
+
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, model::Resource, schemars, serde::Serialize,
+    tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    output::{json_result, output_schema},
+};
+
+/// URI of the tutorial's index; lessons are below it.
+pub(crate) const TUTORIAL_URI: &str = "mq://tutorial";
+
+struct Example {
+    id: &'static str,
+    description: &'static str,
+    query: &'static str,
+}
+
+struct Lesson {
+    slug: &'static str,
+    title: &'static str,
+    text: &'static str,
+    /// The document the lesson's examples run on.
+    markdown: &'static str,
+    examples: &'static [Example],
+}
+
+const GUIDE: &str = "# Guide\n\n## Install\n\n```sh\ncargo install mq-run\n```\n\n## Usage\n\nRead [the docs](https://mqlang.org/book/) first.\n\n```rust\nfn main() {}\n```\n";
+
+const LESSONS: &[Lesson] = &[
+    Lesson {
+        slug: "selectors",
+        title: "Selecting nodes",
+        text: "A query runs once per node of the document: every heading, paragraph text, code block, list item and so on. A selector such as `.h` keeps the nodes of one kind and drops the others, so the query `.h` returns the document's headings.",
+        markdown: GUIDE,
+        examples: &[
+            Example {
+                id: "selectors-1",
+                description: "Every heading, whatever its level.",
+                query: ".h",
+            },
+            Example {
+                id: "selectors-2",
+                description: "Only level-2 headings; `.h1` to `.h6` select one level.",
+                query: ".h2",
+            },
+            Example {
+                id: "selectors-3",
+                description: "Every code block.",
+                query: ".code",
+            },
+            Example {
+                id: "selectors-4",
+                description: "Every link.",
+                query: ".link",
+            },
+        ],
+    },
+    Lesson {
+        slug: "pipes",
+        title: "Pipes and functions",
+        text: "`|` passes what its left side returns to its right side. Functions called in a pipe take that value as their first argument: `.h | to_text()` is `to_text` of each heading, its text without the `#` markers.",
+        markdown: GUIDE,
+        examples: &[
+            Example {
+                id: "pipes-1",
+                description: "The text of every heading.",
+                query: ".h | to_text()",
+            },
+            Example {
+                id: "pipes-2",
+                description: "Functions chain: the text of level-2 headings, upper-cased.",
+                query: ".h2 | to_text() | upcase()",
+            },
+        ],
+    },
+    Lesson {
+        slug: "filtering",
+        title: "Filtering",
+        text: "`select(condition)` keeps the values for which the condition holds and drops the others. Some selectors take arguments that filter as well: `.code(\"sh\")` selects only shell code blocks.",
+        markdown: GUIDE,
+        examples: &[
+            Example {
+                id: "filtering-1",
+                description: "The headings containing `Install`.",
+                query: ".h | select(contains(\"Install\"))",
+            },
+            Example {
+                id: "filtering-2",
+                description: "The code blocks in `sh`, by selector argument.",
+                query: ".code(\"sh\")",
+            },
+            Example {
+                id: "filtering-3",
+                description: "The code blocks in any language but `sh`, with `select` and an attribute (see the next lesson).",
+                query: ".code | select(.code.lang != \"sh\")",
+            },
+        ],
+    },
+    Lesson {
+        slug: "attributes",
+        title: "Attributes",
+        text: "After a selector, `.name` reads an attribute of the node instead of returning the node: the URL of a link, the language of a code block, the level of a heading.",
+        markdown: GUIDE,
+        examples: &[
+            Example {
+                id: "attributes-1",
+                description: "The URL of every link.",
+                query: ".link.url",
+            },
+            Example {
+                id: "attributes-2",
+                description: "The language of every code block.",
+                query: ".code.lang",
+            },
+            Example {
+                id: "attributes-3",
+                description: "The level of every heading.",
+                query: ".h.depth",
+            },
+        ],
+    },
+    Lesson {
+        slug: "lists",
+        title: "Lists and tasks",
+        text: "`.[]` (or `.list`) selects list items. Task list items have their own selectors: `.task` for all of them, `.todo` for the open ones and `.done` for the checked ones.",
+        markdown: "# Release\n\n- [x] Write the changelog\n- [ ] Tag the release\n- [ ] Announce it\n\n1. Build\n2. Test\n",
+        examples: &[
+            Example {
+                id: "lists-1",
+                description: "Every list item, ordered or not.",
+                query: ".[]",
+            },
+            Example {
+                id: "lists-2",
+                description: "The open tasks.",
+                query: ".todo",
+            },
+            Example {
+                id: "lists-3",
+                description: "The text of the checked tasks.",
+                query: ".done | to_text()",
+            },
+        ],
+    },
+    Lesson {
+        slug: "variables",
+        title: "Variables and conditions",
+        text: "`let name = expression` binds a value for the rest of the pipe, and `self` is the value the pipe is at. `if (condition): a else: b` picks one of two values.",
+        markdown: GUIDE,
+        examples: &[Example {
+            id: "variables-1",
+            description: "Headings longer than five characters upper-cased, the others as they are.",
+            query: ".h | let title = to_text(self) | if (len(title) > 5): upcase(title) else: title",
+        }],
+    },
+    Lesson {
+        slug: "functions",
+        title: "Defining functions",
+        text: "`def name(): body;` defines a function for the rest of the query. Called in a pipe, it runs its body on the piped value. The server's user modules (see `--modules`) define functions the same way for every query.",
+        markdown: GUIDE,
+        examples: &[Example {
+            id: "functions-1",
+            description: "A `shout` function, applied to the text of level-2 headings.",
+            query: "def shout(): upcase() | add(\"!\"); | .h2 | to_text() | shout()",
+        }],
+    },
+];
+
+fn lesson_uri(lesson: &Lesson) -> String {
+    format!("{TUTORIAL_URI}/{}", lesson.slug)
+}
+
+fn find_example(id: &str) -> Option<(&'static Lesson, &'static Example)> {
+    LESSONS.iter().find_map(|lesson| {
+        lesson
+            .examples
+            .iter()
+            .find(|example| example.id == id)
+            .map(|example| (lesson, example))
+    })
+}
+
+/// The tutorial's resources: the index, then the lessons in order.
+pub(crate) fn resources() -> Vec<Resource> {
+    let index = Resource::new(TUTORIAL_URI, "mq tutorial")
+        .with_description("Lessons teaching mq, with examples to run with run_example")
+        .with_mime_type("text/markdown");
+    let lessons = LESSONS.iter().enumerate().map(|(i, lesson)| {
+        Resource::new(
+            lesson_uri(lesson),
+            format!("mq tutorial {}: {}", i + 1, lesson.title),
+        )
+        .with_mime_type("text/markdown")
+    });
+    std::iter::once(index).chain(lessons).collect()
+}
+
+/// The text of the tutorial resource `uri`, if it is one.
+pub(crate) fn read(uri: &str) -> Option<String> {
+    if uri == TUTORIAL_URI {
+        return Some(index());
+    }
+    let position = LESSONS
+        .iter()
+        .position(|lesson| lesson_uri(lesson) == uri)?;
+    Some(render(position))
+}
+
+fn index() -> String {
+    let mut text = String::from(
+        "# mq tutorial\n\nmq queries markdown the way jq queries JSON. Read the lessons in order; each one runs its examples on a small document, with the run_example tool.\n\n",
+    );
+    for (i, lesson) in LESSONS.iter().enumerate() {
+        text.push_str(&format!(
+            "{}. [{}]({})\n",
+            i + 1,
+            lesson.title,
+            lesson_uri(lesson)
+        ));
+    }
+    text
+}
+
+fn render(position: usize) -> String {
+    let lesson = &LESSONS[position];
+    let mut text = format!(
+        "# {}. {}\n\n{}\n\nThe examples run on this document:\n\n````markdown\n{}````\n",
+        position + 1,
+        lesson.title,
+        lesson.text,
+        lesson.markdown
+    );
+    for example in lesson.examples {
+        text.push_str(&format!(
+            "\n## Example `{}`\n\n{}\n\n```mq\n{}\n```\n",
+            example.id, example.description, example.query
+        ));
+    }
+    text.push_str(&format!(
+        "\nRun an example with run_example, e.g. `{{\"id\": \"{}\"}}`, and pass your own `query` to try a variation on the same document.\n",
+        lesson.examples[0].id
+    ));
+    if let Some(next) = LESSONS.get(position + 1) {
+        text.push_str(&format!("\nNext: [{}]({})\n", next.title, lesson_uri(next)));
+    }
+    text
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct RunExampleInput {
+    #[schemars(description = "Id of a tutorial example, e.g. `selectors-1`")]
+    pub id: String,
+    #[schemars(
+        description = "A query to run on the example's document instead of the example's own, to try a variation"
+    )]
+    pub query: Option<String>,
+}
+
+/// Output of `run_example`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct RunExampleOutput {
+    #[schemars(description = "The lesson the example is from")]
+    pub lesson: String,
+    #[schemars(description = "The document the query ran on")]
+    pub markdown: String,
+    #[schemars(description = "The query that ran")]
+    pub query: String,
+    #[schemars(description = "Non-empty result values rendered as markdown, in evaluation order")]
+    pub results: Vec<String>,
+}
+
+#[tool_router(router = tutorial_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Run an example of the mq tutorial (the resources under mq://tutorial) on its lesson's document, or try your own query on that document. Returns the document, the query and its results.",
+        output_schema = output_schema::<RunExampleOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn run_example(&self, Parameters(input): Parameters<RunExampleInput>) -> McpResult {
+        let Some((lesson, example)) = find_example(&input.id) else {
+            let ids: Vec<&str> = LESSONS
+                .iter()
+                .flat_map(|lesson| lesson.examples.iter().map(|example| example.id))
+                .collect();
+            return Err(ErrorData::invalid_params(
+                "Tutorial example not found",
+                Some(serde_json::json!({ "id": input.id, "examples": ids })),
+            ));
+        };
+        let query = input.query.unwrap_or_else(|| example.query.to_string());
+        let results = self.query_values(lesson.markdown, &query)?;
+        json_result(&RunExampleOutput {
+            lesson: lesson_uri(lesson),
+            markdown: lesson.markdown.to_string(),
+            query,
+            results,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(id: &str, query: Option<&str>) -> Result<serde_json::Value, ErrorData> {
+        Server::new(None)
+            .unwrap()
+            .run_example(Parameters(RunExampleInput {
+                id: id.to_string(),
+                query: query.map(str::to_string),
+            }))
+            .map(|result| result.structured_content.unwrap())
+    }
+
+    #[test]
+    fn test_every_example_returns_results() {
+        for lesson in LESSONS {
+            for example in lesson.examples {
+                let output = run(example.id, None).unwrap();
+                assert!(
+                    !output["results"].as_array().unwrap().is_empty(),
+                    "{} returned nothing",
+                    example.id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_example() {
+        let output = run("pipes-2", None).unwrap();
+        assert_eq!(output["results"], serde_json::json!(["INSTALL", "USAGE"]));
+        let output = run("pipes-2", Some(".h1 | to_text()")).unwrap();
+        assert_eq!(output["results"], serde_json::json!(["Guide"]));
+        assert!(run("pipes-9", None).is_err());
+    }
+
+    #[test]
+    fn test_resources() {
+        let resources = resources();
+        assert_eq!(resources.len(), LESSONS.len() + 1);
+        for resource in &resources {
+            assert!(read(&resource.uri).is_some());
+        }
+        let lesson = read("mq://tutorial/selectors").unwrap();
+        assert!(lesson.starts_with("# 1. Selecting nodes"));
+        assert!(lesson.contains("## Example `selectors-1`"));
+        assert!(lesson.contains("Next: [Pipes and functions](mq://tutorial/pipes)"));
+        assert!(read("mq://tutorial/unknown").is_none());
+    }
+}
@@ -0,0 +1,228 @@
+//! Session variables: `set_variable` stores the results of a query under a
+//! name, and later queries in the session can refer to them by that name,
+//! so the steps of a multi-step extraction don't have to pass their
+//! intermediate results back through the model.
+//!
+//! A variable is an array of the stored results as strings.
+//! [`Server::call_tool`](rmcp::ServerHandler::call_tool) carries the
+//! session's variables in a task-local for the duration of each call, and
+//! [`new_engine`](super::new_engine) defines them in every engine it creates.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server, dry_run,
+    engine::{Engine, QueryEngine, QueryValue, Value},
+    output::{QueryOutput, json_result, output_schema, query_result},
+};
+
+/// Maximum number of variables per session. Overwriting an existing
+/// variable is always allowed.
+const MAX_VARIABLES: usize = 64;
+
+tokio::task_local! {
+    static VARIABLES: Variables;
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct SetVariableInput {
+    #[schemars(
+        description = "Variable name: letters, digits and underscores, not starting with a digit. Setting an existing variable replaces it"
+    )]
+    pub name: String,
+    #[schemars(description = "The markdown content to query")]
+    pub markdown: String,
+    #[schemars(
+        description = "The mq query whose non-empty results are stored. It may refer to variables set earlier"
+    )]
+    pub query: String,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct GetVariableInput {
+    #[schemars(description = "Name of a variable set with set_variable")]
+    pub name: String,
+}
+
+/// Output of `set_variable`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct SetVariableOutput {
+    pub name: String,
+    #[schemars(description = "Number of results stored")]
+    pub count: usize,
+    #[schemars(description = "Whether this was a dry run that stored nothing")]
+    pub dry_run: bool,
+}
+
+/// The variables set in a session, in name order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Variables(Arc<Mutex<BTreeMap<String, Vec<String>>>>);
+
+impl Variables {
+    /// Checks that `name` can be set: that it is a valid identifier and the
+    /// session has room for it.
+    fn check(&self, name: &str) -> Result<(), ErrorData> {
+        let mut chars = name.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(ErrorData::invalid_params(
+                "Invalid variable name",
+                Some(serde_json::json!({
+                    "name": name,
+                    "expected": "letters, digits and underscores, not starting with a digit",
+                })),
+            ));
+        }
+        let variables = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if !variables.contains_key(name) && variables.len() >= MAX_VARIABLES {
+            return Err(ErrorData::invalid_request(
+                "Too many session variables",
+                Some(serde_json::json!({ "limit": MAX_VARIABLES })),
+            ));
+        }
+        Ok(())
+    }
+
+    fn set(&self, name: &str, values: Vec<String>) -> Result<(), ErrorData> {
+        self.check(name)?;
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string(), values);
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Result<Vec<String>, ErrorData> {
+        let variables = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        variables.get(name).cloned().ok_or_else(|| {
+            ErrorData::invalid_params(
+                "Unknown session variable",
+                Some(serde_json::json!({
+                    "name": name,
+                    "defined": variables.keys().collect::<Vec<_>>(),
+                })),
+            )
+        })
+    }
+}
+
+/// Runs a tool call with the session's variables.
+pub(crate) async fn scope<F: Future>(variables: Variables, f: F) -> F::Output {
+    VARIABLES.scope(variables, f).await
+}
+
+/// Defines the current call's session variables in `engine`.
+pub(super) fn define(engine: &Engine) {
+    let _ = VARIABLES.try_with(|variables| {
+        let variables = variables.0.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, values) in variables.iter() {
+            engine.define(
+                name,
+                Value::array(values.iter().cloned().map(Value::string).collect()),
+            );
+        }
+    });
+}
+
+#[tool_router(router = variables_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Run an mq query on markdown and store its results in the session under a name. Later queries in any tool can refer to the name as an array of the stored results (strings), e.g. store `.h1 | to_text()` as `titles` and later run `join(titles, \", \")`, without passing the intermediate results back. Variables last for the session.",
+        output_schema = output_schema::<SetVariableOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    fn set_variable(&self, Parameters(input): Parameters<SetVariableInput>) -> McpResult {
+        self.variables.check(&input.name)?;
+        let values = self.query_values(&input.markdown, &input.query)?;
+        let count = values.len();
+        let dry_run = dry_run::active();
+        if !dry_run {
+            self.variables.set(&input.name, values)?;
+        }
+        json_result(&SetVariableOutput {
+            name: input.name,
+            count,
+            dry_run,
+        })
+    }
+
+    #[tool(
+        description = "Return the results stored in a session variable by set_variable.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn get_variable(&self, Parameters(input): Parameters<GetVariableInput>) -> McpResult {
+        query_result(self.variables.get(&input.name)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("titles", true)]
+    #[case("_step2", true)]
+    #[case("2nd", false)]
+    #[case("my-var", false)]
+    #[case("", false)]
+    fn test_check_name(#[case] name: &str, #[case] valid: bool) {
+        assert_eq!(Variables::default().check(name).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_limit() {
+        let variables = Variables::default();
+        for i in 0..MAX_VARIABLES {
+            variables.set(&format!("v{i}"), vec![]).unwrap();
+        }
+        assert!(variables.set("one_more", vec![]).is_err());
+        assert!(variables.set("v0", vec!["x".to_string()]).is_ok());
+        assert_eq!(variables.get("v0").unwrap(), vec!["x"]);
+        assert!(variables.get("one_more").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_variables_are_defined_in_queries() {
+        let server = Server::new(None).unwrap();
+        let input = SetVariableInput {
+            name: "titles".to_string(),
+            markdown: "# Install\n\n# Usage\n\ntext".to_string(),
+            query: ".h1 | to_text()".to_string(),
+        };
+        scope(server.variables.clone(), async {
+            server.set_variable(Parameters(input)).unwrap();
+        })
+        .await;
+        assert_eq!(
+            server.variables.get("titles").unwrap(),
+            vec!["Install", "Usage"]
+        );
+
+        let values = scope(server.variables.clone(), async {
+            server.query_values("text", r#"join(titles, ",")"#)
+        })
+        .await
+        .unwrap();
+        assert_eq!(values, vec!["Install,Usage"]);
+        // Outside a call's scope, the name is not defined.
+        assert!(server.query_values("text", "titles").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Vaults: directories of markdown notes as Obsidian and Foam keep them,
+//! read whole for the tools that look across notes (backlinks, the link
+//! graph, front matter queries, tasks). Notes are named by their file
+//! stem and linked to by it, or by their path when the stem is ambiguous.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rmcp::ErrorData;
+use serde_json::{Map, Value};
+
+use super::{Server, paths};
+
+/// Most notes read from one vault.
+const MAX_NOTES: usize = 10_000;
+
+/// A note of a vault.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Note {
+    /// Relative to the vault, with `/` separators.
+    pub path: String,
+    pub markdown: String,
+}
+
+impl Note {
+    /// A note at `path` with `markdown`, for tests.
+    #[cfg(test)]
+    pub(super) fn new(path: &str, markdown: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            markdown: markdown.to_string(),
+        }
+    }
+
+    /// The note's name, its file stem.
+    pub(super) fn name(&self) -> &str {
+        let file = self.path.rsplit('/').next().unwrap_or(&self.path);
+        file.rsplit_once('.').map_or(file, |(stem, _)| stem)
+    }
+
+    /// The note's path without its extension.
+    fn path_stem(&self) -> &str {
+        self.path
+            .rsplit_once('.')
+            .map_or(self.path.as_str(), |(stem, _)| stem)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Vault {
+    /// In path order.
+    pub notes: Vec<Note>,
+    /// The index in `notes` of the first note with each lowercase name.
+    names: HashMap<String, usize>,
+}
+
+impl Vault {
+    pub(super) fn new(mut notes: Vec<Note>) -> Self {
+        notes.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut names = HashMap::new();
+        for (index, note) in notes.iter().enumerate() {
+            names.entry(note.name().to_lowercase()).or_insert(index);
+        }
+        Self { notes, names }
+    }
+
+    /// Reads the markdown files under `directory`. Hidden files and
+    /// directories (`.obsidian`, `.git`, `.trash`) are skipped, and so are
+    /// files that can't be read as text.
+    pub(super) fn read(directory: &Path) -> Result<Self, ErrorData> {
+        if !directory.is_dir() {
+            return Err(ErrorData::invalid_params(
+                "Not a directory",
+                Some(serde_json::json!({ "path": directory })),
+            ));
+        }
+        let mut files = Vec::new();
+        collect(directory, &mut files).map_err(|e| {
+            ErrorData::internal_error(
+                "Failed to read the directory",
+                Some(serde_json::json!({ "path": directory, "error": e.to_string() })),
+            )
+        })?;
+        if files.len() > MAX_NOTES {
+            return Err(ErrorData::invalid_params(
+                "Too many notes in the directory",
+                Some(serde_json::json!({ "path": directory, "limit": MAX_NOTES })),
+            ));
+        }
+        let notes = files
+            .into_iter()
+            .filter_map(|file| match paths::read_markdown(&file) {
+                Ok(markdown) => Some(Note {
+                    path: relative(directory, &file),
+                    markdown,
+                }),
+                Err(e) => {
+                    tracing::debug!("skipping {}: {e}", file.display());
+                    None
+                }
+            })
+            .collect();
+        Ok(Self::new(notes))
+    }
+
+    /// The note a link to `target` (a name or a path, with or without the
+    /// extension) leads to: the note with that path, or else the first
+    /// with that name, ignoring case.
+    pub(super) fn resolve(&self, target: &str) -> Option<&Note> {
+        let target = target.trim().trim_start_matches('/').replace('\\', "/");
+        let target = strip_extension(&target);
+        if target.is_empty() {
+            return None;
+        }
+        if target.contains('/') {
+            let suffix = format!("/{target}");
+            return self.notes.iter().find(|note| {
+                let stem = note.path_stem();
+                stem.eq_ignore_ascii_case(target)
+                    || stem.to_lowercase().ends_with(&suffix.to_lowercase())
+            });
+        }
+        self.names
+            .get(&target.to_lowercase())
+            .map(|&index| &self.notes[index])
+    }
+}
+
+impl Server {
+    /// The vault set with `--vault`, which the tools creating notes write
+    /// to.
+    pub(super) fn vault_dir(&self) -> Result<&Path, ErrorData> {
+        self.config.vault.as_deref().ok_or_else(|| {
+            ErrorData::invalid_request(
+                "no vault configured — restart mq-mcp with --vault <dir> to enable daily_note, append_to_daily_note and create_note",
+                None,
+            )
+        })
+    }
+}
+
+/// `target` without a markdown extension.
+pub(super) fn strip_extension(target: &str) -> &str {
+    let lower = target.to_ascii_lowercase();
+    [".md", ".markdown"]
+        .iter()
+        .find(|extension| lower.ends_with(*extension))
+        .map_or(target, |extension| {
+            &target[..target.len() - extension.len()]
+        })
+}
+
+fn collect(directory: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect(&path, files)?;
+        } else if file_type.is_file() && paths::is_markdown(&path) {
+            files.push(path);
+        }
+        if files.len() > MAX_NOTES {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// `path` relative to `directory`, with `/` separators.
+fn relative(directory: &Path, path: &Path) -> String {
+    path.strip_prefix(directory)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The YAML front matter of `markdown`, between a `---` first line and the
+/// next `---` or `...` line, and the offset of the text after it.
+pub(super) fn front_matter(markdown: &str) -> Option<(&str, usize)> {
+    let rest = markdown
+        .strip_prefix("---\n")
+        .or_else(|| markdown.strip_prefix("---\r\n"))?;
+    let start = markdown.len() - rest.len();
+    let mut offset = start;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return Some((&markdown[start..offset], offset + line.len()));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// The fields of the YAML front matter of `markdown`; empty without front
+/// matter, and `None` if it isn't a mapping.
+pub(super) fn front_matter_fields(markdown: &str) -> Option<Map<String, Value>> {
+    match front_matter(markdown) {
+        Some((yaml, _)) if !yaml.trim().is_empty() => {
+            match serde_yaml::from_str::<serde_yaml::Value>(yaml)
+                .ok()
+                .map(serde_json::to_value)
+            {
+                Some(Ok(Value::Object(fields))) => Some(fields),
+                _ => None,
+            }
+        }
+        _ => Some(Map::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn note(path: &str) -> Note {
+        Note::new(path, "")
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("people")).unwrap();
+        fs::create_dir_all(dir.path().join(".obsidian")).unwrap();
+        fs::write(dir.path().join("Home.md"), "# Home").unwrap();
+        fs::write(dir.path().join("people/Ada.md"), "# Ada").unwrap();
+        fs::write(dir.path().join(".obsidian/notes.md"), "# Settings").unwrap();
+        fs::write(dir.path().join("image.png"), [0u8, 1, 2]).unwrap();
+
+        let vault = Vault::read(dir.path()).unwrap();
+        assert_eq!(
+            vault
+                .notes
+                .iter()
+                .map(|note| note.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Home.md", "people/Ada.md"]
+        );
+        assert!(Vault::read(&dir.path().join("Home.md")).is_err());
+    }
+
+    #[rstest]
+    #[case("Ada", Some("people/Ada.md"))]
+    #[case("ada.md", Some("people/Ada.md"))]
+    #[case("people/Ada", Some("people/Ada.md"))]
+    #[case("work/people/Ada", None)]
+    #[case("Grace", None)]
+    #[case("", None)]
+    fn test_resolve(#[case] target: &str, #[case] expected: Option<&str>) {
+        let vault = Vault::new(vec![note("people/Ada.md"), note("Home.md")]);
+        assert_eq!(
+            vault.resolve(target).map(|note| note.path.as_str()),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("---\ntags: [a]\n---\n# Title\n", Some(("tags: [a]\n", "# Title\n")))]
+    #[case("---\ntitle: x\n...\nText", Some(("title: x\n", "Text")))]
+    #[case("# Title\n---\n", None)]
+    #[case("---\nnever closed\n", None)]
+    fn test_front_matter(#[case] markdown: &str, #[case] expected: Option<(&str, &str)>) {
+        assert_eq!(
+            front_matter(markdown).map(|(yaml, end)| (yaml, &markdown[end..])),
+            expected
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! `vault_query`: the front matter of a vault's notes as a table, one row
+//! per note and one column per field, filtered with an mq condition,
+//! sorted and projected, as Obsidian's Dataview does for `TABLE` queries.
+
+use std::cmp::Ordering;
+
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router,
+};
+use serde_json::{Map, Value};
+
+use super::{
+    McpResult, Server,
+    engine::{self, QueryEngine, QueryValue},
+    new_engine,
+    openapi::markdown_table,
+    output::{json_result, output_schema},
+    query_error,
+    vault::{self, Note, Vault},
+};
+
+/// Columns every row has, whatever its front matter.
+const PATH: &str = "path";
+const NAME: &str = "name";
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum VaultQueryFormat {
+    /// Rows only.
+    #[default]
+    Json,
+    /// Also a markdown table.
+    Table,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct VaultQueryInput {
+    #[schemars(description = "The directory of notes (the vault) to query")]
+    pub directory: String,
+    #[schemars(
+        description = "mq condition a note must meet to be a row, e.g. `status == \"active\" && priority > 1`. Front matter fields are variables, along with `path` and `name`; the row is also the input, so any field can be read with `get(self, \"due-date\")`. Missing fields are none"
+    )]
+    pub filter: Option<String>,
+    #[schemars(
+        description = "Columns to return, in order (default: `path`, then every front matter field in the order first seen)"
+    )]
+    pub columns: Option<Vec<String>>,
+    #[schemars(
+        description = "Field to sort the rows by: numbers by value, anything else as text ignoring case; rows without it go last (default: path order)"
+    )]
+    pub sort_by: Option<String>,
+    #[schemars(description = "Reverse the order (default: false)")]
+    pub descending: Option<bool>,
+    #[schemars(description = "Most rows to return, after sorting")]
+    pub limit: Option<usize>,
+    #[schemars(
+        description = "`json` (default), or `table` to also return the rows as a markdown table in `table`"
+    )]
+    pub format: Option<VaultQueryFormat>,
+}
+
+/// Output of `vault_query`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct VaultQueryOutput {
+    pub columns: Vec<String>,
+    #[schemars(
+        description = "One row per note, one value per column; null where a field is missing"
+    )]
+    pub rows: Vec<Vec<Value>>,
+    #[schemars(description = "Number of notes matching the filter, before `limit`")]
+    pub matched: usize,
+    #[schemars(description = "Paths of the notes whose front matter isn't a YAML mapping")]
+    pub invalid: Vec<String>,
+    #[schemars(description = "The rows as a markdown table, when `format` is `table`")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+}
+
+/// The fields of `note`: its front matter, then `path` and `name`, which
+/// win over front matter fields of the same name. `None` if the front
+/// matter isn't a mapping.
+fn row(note: &Note) -> Option<Map<String, Value>> {
+    let mut fields = vault::front_matter_fields(&note.markdown)?;
+    fields.insert(PATH.to_string(), Value::String(note.path.clone()));
+    fields.insert(NAME.to_string(), Value::String(note.name().to_string()));
+    Some(fields)
+}
+
+/// Whether `name` can be used as an mq variable.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Keeps the rows meeting `filter`. Each row's fields are defined as
+/// variables, and those of the other rows reset to none, before the filter
+/// runs on it.
+fn filter(
+    rows: Vec<Map<String, Value>>,
+    fields: &[String],
+    filter: &str,
+) -> Result<Vec<Map<String, Value>>, ErrorData> {
+    let mut engine = new_engine();
+    engine.compile(filter).map_err(|e| query_error(filter, e))?;
+    let variables: Vec<&String> = fields.iter().filter(|f| is_identifier(f)).collect();
+    let mut kept = Vec::new();
+    for row in rows {
+        for &field in &variables {
+            let value = row.get(field).cloned().unwrap_or(Value::Null);
+            engine.define(field, engine::Value::json(value));
+        }
+        let values = engine
+            .eval(
+                filter,
+                std::iter::once(engine::Value::json(Value::Object(row.clone()))),
+            )
+            .map_err(|e| query_error(filter, e))?;
+        if values.iter().any(QueryValue::is_truthy) {
+            kept.push(row);
+        }
+    }
+    Ok(kept)
+}
+
+/// Orders two field values: numbers by value, anything else as text
+/// ignoring case.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => text(a).to_lowercase().cmp(&text(b).to_lowercase()),
+    }
+}
+
+/// Sorts `rows` by `field`, rows without it last either way.
+fn sort(rows: &mut [Map<String, Value>], field: &str, descending: bool) {
+    let key = |row: &Map<String, Value>| row.get(field).filter(|value| !value.is_null()).cloned();
+    rows.sort_by(|a, b| match (key(a), key(b)) {
+        (Some(a), Some(b)) if descending => compare(&b, &a),
+        (Some(a), Some(b)) => compare(&a, &b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+}
+
+/// `value` as text: lists joined with commas, null empty.
+pub(super) fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(values) => values.iter().map(text).collect::<Vec<_>>().join(", "),
+        value => value.to_string(),
+    }
+}
+
+fn vault_query(vault: &Vault, input: &VaultQueryInput) -> Result<VaultQueryOutput, ErrorData> {
+    let mut rows = Vec::new();
+    let mut invalid = Vec::new();
+    let mut fields = vec![PATH.to_string(), NAME.to_string()];
+    for note in &vault.notes {
+        let Some(row) = row(note) else {
+            invalid.push(note.path.clone());
+            continue;
+        };
+        for field in row.keys() {
+            if !fields.contains(field) {
+                fields.push(field.clone());
+            }
+        }
+        rows.push(row);
+    }
+    if let Some(condition) = &input.filter {
+        rows = filter(rows, &fields, condition)?;
+    }
+    if let Some(field) = &input.sort_by {
+        sort(&mut rows, field, input.descending.unwrap_or(false));
+    } else if input.descending.unwrap_or(false) {
+        rows.reverse();
+    }
+    let matched = rows.len();
+    rows.truncate(input.limit.unwrap_or(usize::MAX));
+
+    let columns = input
+        .columns
+        .clone()
+        .unwrap_or_else(|| fields.into_iter().filter(|field| field != NAME).collect());
+    let rows: Vec<Vec<Value>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| row.get(column).cloned().unwrap_or(Value::Null))
+                .collect()
+        })
+        .collect();
+    let table = (input.format.unwrap_or_default() == VaultQueryFormat::Table).then(|| {
+        let mut table = vec![columns.clone()];
+        table.extend(rows.iter().map(|row| {
+            row.iter()
+                .map(|value| {
+                    text(value)
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                        .replace('|', "\\|")
+                })
+                .collect()
+        }));
+        markdown_table(&table)
+    });
+    Ok(VaultQueryOutput {
+        columns,
+        rows,
+        matched,
+        invalid,
+        table,
+    })
+}
+
+#[tool_router(router = vault_query_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Query the front matter of the notes in a directory (an Obsidian or Foam vault) as a table, as Dataview does: one row per note, one column per field. Filter rows with an mq condition on the fields, sort by a field, pick the columns, and get the rows as JSON or a markdown table.",
+        output_schema = output_schema::<VaultQueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn vault_query(&self, Parameters(input): Parameters<VaultQueryInput>) -> McpResult {
+        let vault = Vault::read(&self.scoped_path(&input.directory)?)?;
+        json_result(&vault_query(&vault, &input)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn vault() -> Vault {
+        Vault::new(vec![
+            Note::new(
+                "projects/Engine.md",
+                "---\nstatus: active\npriority: 2\ntags: [rust, mq]\n---\n# Engine\n",
+            ),
+            Note::new(
+                "projects/Site.md",
+                "---\nstatus: done\npriority: 10\ndue-date: 2026-01-31\n---\n",
+            ),
+            Note::new("projects/Docs.md", "---\nstatus: active\n---\n"),
+            Note::new("Inbox.md", "No front matter."),
+            Note::new("Broken.md", "---\n- a list\n---\n"),
+        ])
+    }
+
+    fn input() -> VaultQueryInput {
+        VaultQueryInput {
+            directory: String::new(),
+            filter: None,
+            columns: None,
+            sort_by: None,
+            descending: None,
+            limit: None,
+            format: None,
+        }
+    }
+
+    #[test]
+    fn test_vault_query() {
+        let output = vault_query(&vault(), &input()).unwrap();
+        assert_eq!(
+            output.columns,
+            vec!["path", "status", "priority", "tags", "due-date"]
+        );
+        assert_eq!(
+            output.rows[0],
+            vec![
+                Value::from("Inbox.md"),
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+            ]
+        );
+        assert_eq!(output.matched, 4);
+        assert_eq!(output.invalid, vec!["Broken.md"]);
+        assert_eq!(output.table, None);
+    }
+
+    #[rstest]
+    #[case("status == \"active\"", vec!["Docs", "Engine"])]
+    #[case("priority > 1", vec!["Engine", "Site"])]
+    #[case("starts_with(path, \"projects/\") && status != \"done\"", vec!["Docs", "Engine"])]
+    #[case("get(self, \"due-date\")", vec!["Site"])]
+    fn test_filter(#[case] filter: &str, #[case] expected: Vec<&str>) {
+        let output = vault_query(
+            &vault(),
+            &VaultQueryInput {
+                filter: Some(filter.to_string()),
+                columns: Some(vec!["name".to_string()]),
+                ..input()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            output.rows,
+            expected
+                .into_iter()
+                .map(|name| vec![Value::from(name)])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_sort_and_table() {
+        let output = vault_query(
+            &vault(),
+            &VaultQueryInput {
+                columns: Some(vec![
+                    "name".to_string(),
+                    "priority".to_string(),
+                    "tags".to_string(),
+                ]),
+                sort_by: Some("priority".to_string()),
+                descending: Some(true),
+                limit: Some(3),
+                format: Some(VaultQueryFormat::Table),
+                ..input()
+            },
+        )
+        .unwrap();
+        assert_eq!(output.matched, 4);
+        assert_eq!(
+            output.table.unwrap(),
+            "| name | priority | tags |\n\
+             | --- | --- | --- |\n\
+             | Site | 10 |  |\n\
+             | Engine | 2 | rust, mq |\n\
+             | Inbox |  |  |"
+        );
+    }
+
+    #[test]
+    fn test_invalid_filter() {
+        let result = vault_query(
+            &vault(),
+            &VaultQueryInput {
+                filter: Some("status ==".to_string()),
+                ..input()
+            },
+        );
+        assert!(result.is_err());
+    }
+}
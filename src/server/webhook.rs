@@ -0,0 +1,182 @@
+//! Webhooks for scheduled pipelines: a pipeline saved with a `webhook` URL
+//! POSTs a JSON report of every run to it, so systems outside MCP can
+//! consume its results.
+//!
+//! Webhooks may only be sent to the hosts allowed with `--webhook-host`.
+//! With a `--webhook-secret`, each delivery carries an HMAC-SHA256 of the
+//! request body in the `X-Mq-Signature-256` header (`sha256=<hex>`, as in
+//! GitHub webhooks), so receivers can check it came from this server.
+
+use std::fmt;
+
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use rmcp::{ErrorData, serde::Serialize};
+use sha2::Sha256;
+
+use super::{
+    fetch::{Fetcher, parse_url},
+    redact::{self, RedactionRule},
+};
+
+/// Header carrying the signature of a delivery.
+pub(crate) const SIGNATURE_HEADER: &str = "x-mq-signature-256";
+
+/// Server-side settings for pipeline webhooks.
+#[derive(Clone, Default)]
+pub struct WebhookConfig {
+    /// Hosts webhooks may be sent to. Empty disables webhooks.
+    pub allowed_hosts: Vec<String>,
+    /// Key for signing deliveries. `None` sends them unsigned.
+    pub secret: Option<String>,
+}
+
+impl fmt::Debug for WebhookConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookConfig")
+            .field("allowed_hosts", &self.allowed_hosts)
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// The body of a webhook delivery: the report of one pipeline run.
+#[derive(Debug, Serialize)]
+pub(crate) struct Delivery<'a> {
+    pub pipeline: &'a str,
+    /// The resource holding the pipeline's latest results.
+    pub uri: String,
+    pub source: String,
+    /// When the run finished (RFC 3339).
+    pub ran_at: String,
+    /// Whether the results differ from the previous run's.
+    pub changed: bool,
+    /// The latest results; those of the previous run if this one failed.
+    pub results: &'a [String],
+    pub error: Option<&'a str>,
+}
+
+impl Delivery<'_> {
+    /// The JSON request body, with `rules` applied to every string in it
+    /// so the signature covers what is actually sent.
+    pub(crate) fn body(&self, rules: &[RedactionRule]) -> Result<Vec<u8>, ErrorData> {
+        let failed = |e: serde_json::Error| {
+            ErrorData::internal_error(
+                "Failed to serialize output",
+                Some(serde_json::Value::String(e.to_string())),
+            )
+        };
+        let mut body = serde_json::to_value(self).map_err(failed)?;
+        redact::redact_json(rules, &mut body);
+        serde_json::to_vec(&body).map_err(failed)
+    }
+}
+
+/// `sha256=<hex>`: the HMAC-SHA256 of `body` keyed with `secret`.
+pub(crate) fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+impl WebhookConfig {
+    /// Parses a pipeline's webhook URL and checks its host is allowed.
+    pub(crate) fn check(&self, url: &str) -> Result<url::Url, ErrorData> {
+        let parsed = parse_url(url)?;
+        if self.allowed_hosts.is_empty() {
+            return Err(ErrorData::invalid_request(
+                "no webhook hosts configured — restart mq-mcp with --webhook-host <host> to enable webhooks",
+                None,
+            ));
+        }
+        let host = parsed.host_str().unwrap_or_default();
+        if !self
+            .allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+        {
+            return Err(ErrorData::invalid_params(
+                "Webhook host not allowed",
+                Some(serde_json::json!({ "host": host, "allowed_hosts": self.allowed_hosts })),
+            ));
+        }
+        Ok(parsed)
+    }
+
+    /// POSTs the body of a [`Delivery`] to `url`. Failed deliveries are
+    /// retried like other requests, so a receiver may see a delivery more
+    /// than once.
+    pub(crate) async fn deliver(
+        &self,
+        fetcher: &Fetcher,
+        url: url::Url,
+        body: &[u8],
+    ) -> Result<(), ErrorData> {
+        let mut headers = HeaderMap::new();
+        if let Some(secret) = &self.secret {
+            let mut value = HeaderValue::from_str(&sign(secret, body))
+                .expect("hex digits are a valid header value");
+            value.set_sensitive(true);
+            headers.insert(HeaderName::from_static(SIGNATURE_HEADER), value);
+        }
+        fetcher.post(url, headers, body).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_body_is_redacted() {
+        let rules = vec!["token=tok_[a-z]+".parse::<RedactionRule>().unwrap()];
+        let results = vec!["key: tok_abc".to_string()];
+        let delivery = Delivery {
+            pipeline: "watch",
+            uri: "mq://pipelines/watch".to_string(),
+            source: "https://example.com/?t=tok_def".to_string(),
+            ran_at: String::new(),
+            changed: true,
+            results: &results,
+            error: None,
+        };
+        let body = String::from_utf8(delivery.body(&rules).unwrap()).unwrap();
+        assert!(!body.contains("tok_"));
+        assert!(body.contains("key: [REDACTED:token]"));
+    }
+
+    #[test]
+    fn test_check() {
+        let config = WebhookConfig {
+            allowed_hosts: vec!["hooks.example.com".to_string()],
+            secret: None,
+        };
+        assert!(config.check("https://HOOKS.example.com/mq").is_ok());
+        assert!(config.check("https://evil.example.com/mq").is_err());
+        assert!(config.check("ftp://hooks.example.com/mq").is_err());
+        assert!(
+            WebhookConfig::default()
+                .check("https://hooks.example.com")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_debug_redacts_secret() {
+        let config = WebhookConfig {
+            allowed_hosts: Vec::new(),
+            secret: Some("hunter2".to_string()),
+        };
+        assert!(!format!("{config:?}").contains("hunter2"));
+    }
+}
@@ -0,0 +1,435 @@
+//! `query_wiki`: pages from Wikipedia and other MediaWiki sites, fetched
+//! through the MediaWiki action API instead of being scraped.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use rmcp::{
+    ErrorData, handler::server::wrapper::Parameters, model::Meta, schemars, tool, tool_router,
+};
+
+use super::{
+    McpResult, Server,
+    fetch::parse_url,
+    output::{QueryOutput, output_schema},
+    sanitize::SanitizeOptions,
+};
+
+/// `_meta` key under which `query_wiki` reports the page it resolved to.
+pub(crate) const WIKI_META_KEY: &str = "mq/wiki";
+const DEFAULT_SITE: &str = "en.wikipedia.org";
+
+static COMMENTS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+static REFS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<ref\b[^>]*/>|<ref\b[^>]*>.*?</ref\s*>").unwrap());
+static HEADING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(={1,6})\s*(.+?)\s*={1,6}\s*$").unwrap());
+static BOLD_ITALIC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"'''''(.+?)'''''").unwrap());
+static BOLD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"'''(.+?)'''").unwrap());
+static ITALIC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"''(.+?)''").unwrap());
+static INTERNAL_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[\[([^\]|]+)(?:\|([^\]]*))?\]\]").unwrap());
+static EXTERNAL_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[(https?://[^\s\]]+)(?:\s+([^\]]+))?\]").unwrap());
+static TAGS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]+>").unwrap());
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, rmcp::serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WikiFormat {
+    /// The rendered page, converted like html_to_markdown.
+    #[default]
+    Html,
+    /// The page source, converted with a basic wikitext converter.
+    Wikitext,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct QueryWikiInput {
+    #[schemars(
+        description = "A page title, e.g. `Rust (programming language)`, or a page URL such as https://en.wikipedia.org/wiki/Markdown"
+    )]
+    pub page: String,
+    #[schemars(
+        description = "The wiki's host (default: en.wikipedia.org), or the URL of its api.php for wikis not served under /w/. Ignored when `page` is a URL."
+    )]
+    pub site: Option<String>,
+    #[schemars(description = "Only return this section: its heading, or its index in the page")]
+    pub section: Option<String>,
+    #[schemars(
+        description = "`html` (default): convert the rendered page, with templates expanded. `wikitext`: convert the page source; faster, but templates and tables are dropped."
+    )]
+    pub format: Option<WikiFormat>,
+    #[schemars(
+        description = "The mq query to execute (default: `identity()`). Selectors and functions listed in the available_selectors and available_functions tools can be used."
+    )]
+    pub query: Option<String>,
+    #[serde(flatten)]
+    pub sanitize: SanitizeOptions,
+}
+
+/// The API endpoint and page title for a `page` / `site` pair.
+fn resolve_page(page: &str, site: Option<&str>) -> Result<(url::Url, String), ErrorData> {
+    if page.starts_with("http://") || page.starts_with("https://") {
+        let url = parse_url(page)?;
+        let title = url
+            .path()
+            .strip_prefix("/wiki/")
+            .map(|title| {
+                // Decodes percent-escapes in the path segment.
+                url::form_urlencoded::parse(format!("t={title}").as_bytes())
+                    .map(|(_, title)| title.into_owned())
+                    .next()
+                    .unwrap_or_default()
+            })
+            .or_else(|| {
+                url.query_pairs()
+                    .find(|(key, _)| key == "title")
+                    .map(|(_, title)| title.into_owned())
+            })
+            .filter(|title| !title.is_empty())
+            .ok_or_else(|| {
+                ErrorData::invalid_params(
+                    "Not a wiki page URL",
+                    Some(serde_json::json!({ "page": page })),
+                )
+            })?;
+        // `/index.php?title=...` URLs sit next to api.php; `/wiki/...` ones
+        // use the Wikipedia layout.
+        let mut api = url.clone();
+        match url.path().strip_suffix("index.php") {
+            Some(dir) => api.set_path(&format!("{dir}api.php")),
+            None => api.set_path("/w/api.php"),
+        }
+        api.set_query(None);
+        api.set_fragment(None);
+        return Ok((api, title));
+    }
+
+    let api = match site {
+        Some(site) if site.contains("api.php") => parse_url(site)?,
+        Some(site) => parse_url(&format!("https://{}/w/api.php", site.trim_end_matches('/')))?,
+        None => parse_url(&format!("https://{DEFAULT_SITE}/w/api.php"))?,
+    };
+    Ok((api, page.to_string()))
+}
+
+fn api_url(api: &url::Url, params: &[(&str, &str)]) -> url::Url {
+    let mut url = api.clone();
+    url.query_pairs_mut()
+        .extend_pairs(params)
+        .append_pair("format", "json")
+        .append_pair("formatversion", "2");
+    url
+}
+
+/// The index of `section` among the page's `sections`, matched by index or
+/// case-insensitively by heading.
+fn section_index(sections: &[serde_json::Value], section: &str) -> Result<String, ErrorData> {
+    let heading = |s: &serde_json::Value| {
+        TAGS.replace_all(s["line"].as_str().unwrap_or_default(), "")
+            .into_owned()
+    };
+    sections
+        .iter()
+        .find(|s| {
+            s["index"].as_str() == Some(section) || heading(s).eq_ignore_ascii_case(section.trim())
+        })
+        .and_then(|s| s["index"].as_str().map(str::to_string))
+        .ok_or_else(|| {
+            ErrorData::invalid_params(
+                "Section not found",
+                Some(serde_json::json!({
+                    "section": section,
+                    "available": sections.iter().map(heading).collect::<Vec<_>>(),
+                })),
+            )
+        })
+}
+
+/// Removes `open` ... `close` blocks that begin with one of `starts`,
+/// including nested ones.
+fn remove_blocks(text: &str, starts: &[&str], open: &str, close: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = starts.iter().filter_map(|s| rest.find(s)).min() {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + open.len()..];
+        let mut depth = 1;
+        while depth > 0 {
+            let next_open = rest.find(open);
+            let Some(next_close) = rest.find(close) else {
+                rest = "";
+                break;
+            };
+            match next_open {
+                Some(at) if at < next_close => {
+                    depth += 1;
+                    rest = &rest[at + open.len()..];
+                }
+                _ => {
+                    depth -= 1;
+                    rest = &rest[next_close + close.len()..];
+                }
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Converts the common subset of wikitext to markdown: headings, emphasis,
+/// links, lists and rules. Templates, tables, references, files and
+/// categories are dropped.
+fn wikitext_to_markdown(wikitext: &str, article_base: &str) -> String {
+    let text = COMMENTS.replace_all(wikitext, "");
+    let text = REFS.replace_all(&text, "");
+    let text = remove_blocks(&text, &["{{"], "{{", "}}");
+    let text = remove_blocks(&text, &["{|"], "{|", "|}");
+    let text = remove_blocks(&text, &["[[File:", "[[Image:", "[[Category:"], "[[", "]]");
+
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end();
+        if let Some(caps) = HEADING.captures(line) {
+            lines.push(format!("{} {}", "#".repeat(caps[1].len()), &caps[2]));
+            continue;
+        }
+        if line.starts_with("----") {
+            lines.push("---".to_string());
+            continue;
+        }
+        let markers = line.len() - line.trim_start_matches(['*', '#', ':', ';']).len();
+        let (prefix, body) = line.split_at(markers);
+        let body = body.trim();
+        let line = match prefix.chars().last() {
+            Some('*') => format!("{}- {body}", "  ".repeat(markers - 1)),
+            Some('#') => format!("{}1. {body}", "   ".repeat(markers - 1)),
+            Some(';') => format!("**{body}**"),
+            Some(_) => body.to_string(),
+            None => line.to_string(),
+        };
+        lines.push(line);
+    }
+
+    let text = lines.join("\n");
+    let text = BOLD_ITALIC.replace_all(&text, "***$1***");
+    let text = BOLD.replace_all(&text, "**$1**");
+    let text = ITALIC.replace_all(&text, "*$1*");
+    let text = INTERNAL_LINK.replace_all(&text, |caps: &regex::Captures| {
+        let target = caps[1].trim();
+        let label = caps.get(2).map_or(target, |label| label.as_str().trim());
+        format!("[{label}]({article_base}{})", target.replace(' ', "_"))
+    });
+    let text = EXTERNAL_LINK.replace_all(&text, |caps: &regex::Captures| match caps.get(2) {
+        Some(label) => format!("[{}]({})", label.as_str(), &caps[1]),
+        None => format!("<{}>", &caps[1]),
+    });
+
+    let mut markdown = String::new();
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if !markdown.is_empty() {
+            markdown.push_str("\n\n");
+        }
+        markdown.push_str(paragraph);
+    }
+    markdown
+}
+
+impl Server {
+    /// Calls the MediaWiki API, turning API errors into tool errors.
+    async fn call_wiki_api(
+        &self,
+        api: &url::Url,
+        params: &[(&str, &str)],
+    ) -> Result<serde_json::Value, ErrorData> {
+        let page = self
+            .fetcher
+            .fetch_api(api_url(api, params), HeaderMap::new())
+            .await?;
+        let response: serde_json::Value = serde_json::from_str(&page.text).map_err(|e| {
+            ErrorData::internal_error(
+                "Invalid MediaWiki API response",
+                Some(serde_json::json!({ "url": page.url, "error": e.to_string() })),
+            )
+        })?;
+        if let Some(error) = response.get("error") {
+            return Err(ErrorData::invalid_params(
+                "MediaWiki API error",
+                Some(serde_json::json!({ "code": error["code"], "info": error["info"] })),
+            ));
+        }
+        Ok(response)
+    }
+}
+
+#[tool_router(router = wiki_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "Fetch a Wikipedia or other MediaWiki page through the MediaWiki API, convert it to markdown and run an mq query on it. Redirects are followed; `_meta` reports the resolved title. Use `section` to fetch a single section.",
+        output_schema = output_schema::<QueryOutput>(),
+        annotations(read_only_hint = true, open_world_hint = true)
+    )]
+    async fn query_wiki(&self, Parameters(input): Parameters<QueryWikiInput>) -> McpResult {
+        let (api, title) = resolve_page(&input.page, input.site.as_deref())?;
+        let format = input.format.unwrap_or_default();
+
+        let section = match input.section.as_deref().map(str::trim) {
+            Some(section) if section.chars().all(|c| c.is_ascii_digit()) => {
+                Some(section.to_string())
+            }
+            Some(section) => {
+                let response = self
+                    .call_wiki_api(
+                        &api,
+                        &[
+                            ("action", "parse"),
+                            ("page", &title),
+                            ("redirects", "1"),
+                            ("prop", "sections"),
+                        ],
+                    )
+                    .await?;
+                let sections = response["parse"]["sections"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                Some(section_index(&sections, section)?)
+            }
+            None => None,
+        };
+
+        let prop = match format {
+            WikiFormat::Html => "text",
+            WikiFormat::Wikitext => "wikitext",
+        };
+        let mut params = vec![
+            ("action", "parse"),
+            ("page", title.as_str()),
+            ("redirects", "1"),
+            ("prop", prop),
+            ("disableeditsection", "1"),
+            ("disabletoc", "1"),
+        ];
+        if let Some(section) = &section {
+            params.push(("section", section));
+        }
+        let response = self.call_wiki_api(&api, &params).await?;
+        let parse = &response["parse"];
+        let content = parse[prop].as_str().unwrap_or_default();
+
+        let query = input.query.as_deref().unwrap_or("identity()");
+        let mut result = match format {
+            WikiFormat::Html => self.eval_html(content, query, &input.sanitize)?,
+            WikiFormat::Wikitext => {
+                let host = api.host_str().unwrap_or(DEFAULT_SITE);
+                let article_base = format!("{}://{host}/wiki/", api.scheme());
+                self.eval_query(&wikitext_to_markdown(content, &article_base), query)?
+            }
+        };
+        result.meta.get_or_insert_with(Meta::new).0.insert(
+            WIKI_META_KEY.to_string(),
+            serde_json::json!({
+                "title": parse["title"],
+                "pageid": parse["pageid"],
+                "redirected_from": parse["redirects"]
+                    .as_array()
+                    .and_then(|redirects| redirects.first())
+                    .map(|redirect| redirect["from"].clone()),
+                "section": section,
+            }),
+        );
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        "Rust (programming language)",
+        None,
+        "https://en.wikipedia.org/w/api.php",
+        "Rust (programming language)"
+    )]
+    #[case(
+        "Markdown",
+        Some("de.wikipedia.org"),
+        "https://de.wikipedia.org/w/api.php",
+        "Markdown"
+    )]
+    #[case(
+        "Main Page",
+        Some("https://wiki.example.com/api.php"),
+        "https://wiki.example.com/api.php",
+        "Main Page"
+    )]
+    #[case(
+        "https://ja.wikipedia.org/wiki/C%2B%2B",
+        None,
+        "https://ja.wikipedia.org/w/api.php",
+        "C++"
+    )]
+    #[case(
+        "https://wiki.example.com/index.php?title=Help:Links",
+        None,
+        "https://wiki.example.com/api.php",
+        "Help:Links"
+    )]
+    fn test_resolve_page(
+        #[case] page: &str,
+        #[case] site: Option<&str>,
+        #[case] api: &str,
+        #[case] title: &str,
+    ) {
+        let (resolved_api, resolved_title) = resolve_page(page, site).unwrap();
+        assert_eq!(resolved_api.as_str(), api);
+        assert_eq!(resolved_title, title);
+    }
+
+    #[test]
+    fn test_section_index() {
+        let sections = vec![
+            serde_json::json!({ "line": "History", "index": "1" }),
+            serde_json::json!({ "line": "<i>Syntax</i> rules", "index": "2" }),
+        ];
+        assert_eq!(section_index(&sections, "syntax rules").unwrap(), "2");
+        assert_eq!(section_index(&sections, "1").unwrap(), "1");
+        assert_eq!(
+            section_index(&sections, "Usage").unwrap_err().message,
+            "Section not found"
+        );
+    }
+
+    #[test]
+    fn test_wikitext_to_markdown() {
+        let wikitext = "\
+{{Infobox language|name=Rust|paradigm={{hlist|a|b}}}}
+'''Rust''' is a ''general-purpose'' [[programming language]]<ref>{{cite web|url=x}}</ref>.
+[[File:Rust logo.svg|thumb|The [[logo]]]]
+== History ==
+* Started at [[Mozilla|Mozilla Research]]
+** See [https://www.rust-lang.org the website]
+# First
+<!-- hidden -->
+{| class=\"wikitable\"
+| a || b
+|}
+[[Category:Languages]]";
+        assert_eq!(
+            wikitext_to_markdown(wikitext, "https://en.wikipedia.org/wiki/"),
+            "\
+**Rust** is a *general-purpose* [programming language](https://en.wikipedia.org/wiki/programming_language).
+
+## History
+- Started at [Mozilla Research](https://en.wikipedia.org/wiki/Mozilla)
+  - See [the website](https://www.rust-lang.org)
+1. First"
+        );
+    }
+}
@@ -0,0 +1,432 @@
+//! Wiki-links (`[[Page]]`, `[[Page#Heading|alias]]`, embeds `![[Page]]`)
+//! and tags (`#tag`, `#nested/tag`, front matter `tags`) as Obsidian and
+//! Foam write them. Markdown parsers, mq's included, read them as plain
+//! text, so they are found by scanning the source, skipping code.
+//! `extract_wikilinks` lists them in one note; `backlinks` finds the notes
+//! of a directory linking to one.
+
+use rmcp::{handler::server::wrapper::Parameters, schemars, serde::Serialize, tool, tool_router};
+
+use super::{
+    McpResult, Server,
+    admonitions::Fences,
+    output::{json_result, output_schema},
+    vault::{self, Vault},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct WikiLink {
+    #[schemars(description = "The linked note's name or path; empty for a link within the note")]
+    pub target: String,
+    #[schemars(description = "The heading linked to, after `#`")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading: Option<String>,
+    #[schemars(description = "The block id linked to, after `#^`")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block: Option<String>,
+    #[schemars(description = "The text shown instead of the target, after `|`")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[schemars(description = "Whether this is an embed, `![[...]]`")]
+    pub embed: bool,
+    #[schemars(description = "1-based line of the link")]
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, schemars::JsonSchema)]
+pub(crate) struct Tag {
+    #[schemars(description = "The tag without `#`, e.g. `project/active`")]
+    pub name: String,
+    #[schemars(description = "1-based line of the tag; null for tags in the front matter")]
+    pub line: Option<usize>,
+}
+
+/// The wiki-links and tags of a note, in document order.
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct Parsed {
+    pub links: Vec<WikiLink>,
+    pub tags: Vec<Tag>,
+}
+
+/// `line` with its code spans blanked out, so nothing in them is read as
+/// a link or a tag. Offsets are kept.
+pub(super) fn blank_code(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('`') {
+        let run = rest[start..].chars().take_while(|&c| c == '`').count();
+        let fence = &rest[start..start + run];
+        let Some(end) = rest[start + run..]
+            .match_indices(fence)
+            .map(|(i, _)| start + run + i)
+            .find(|&i| !rest[i + run..].starts_with('`'))
+        else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&" ".repeat(end + run - start));
+        rest = &rest[end + run..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The wiki-link `inner` (between `[[` and `]]`) stands for.
+fn link(inner: &str, embed: bool, line: usize) -> Option<WikiLink> {
+    let (reference, alias) = match inner.split_once('|') {
+        Some((reference, alias)) => (reference, Some(alias.trim().to_string())),
+        None => (inner, None),
+    };
+    let (target, fragment) = reference.split_once('#').unwrap_or((reference, ""));
+    let fragment = fragment.trim();
+    let (heading, block) = match fragment.strip_prefix('^') {
+        Some(block) => (None, Some(block.to_string())),
+        None => ((!fragment.is_empty()).then(|| fragment.to_string()), None),
+    };
+    let target = target.trim().to_string();
+    (!target.is_empty() || heading.is_some() || block.is_some()).then_some(WikiLink {
+        target,
+        heading,
+        block,
+        alias: alias.filter(|alias| !alias.is_empty()),
+        embed,
+        line,
+    })
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
+
+/// Reads the links and tags of one line, with code blanked out.
+fn scan_line(line: &str, number: usize, parsed: &mut Parsed) {
+    let mut text = line.to_string();
+    let mut from = 0;
+    while let Some(start) = text[from..].find("[[").map(|i| from + i) {
+        let Some(end) = text[start + 2..].find("]]").map(|i| start + 2 + i) else {
+            break;
+        };
+        let inner = &text[start + 2..end];
+        if inner.contains("[[") {
+            from = start + 2;
+            continue;
+        }
+        let embed = text[..start].ends_with('!');
+        parsed.links.extend(link(inner, embed, number));
+        // Headings in links aren't tags.
+        text.replace_range(start..end + 2, &" ".repeat(end + 2 - start));
+        from = end + 2;
+    }
+    for (i, _) in text.match_indices('#') {
+        if text[..i]
+            .chars()
+            .next_back()
+            .is_some_and(|c| !c.is_whitespace())
+        {
+            continue;
+        }
+        let name: String = text[i + 1..]
+            .chars()
+            .take_while(|&c| is_tag_char(c))
+            .collect();
+        let name = name.trim_end_matches('/');
+        if name.chars().any(|c| !c.is_numeric() && c != '/') {
+            parsed.tags.push(Tag {
+                name: name.to_string(),
+                line: Some(number),
+            });
+        }
+    }
+}
+
+/// The tags listed in the `tags` field of YAML front matter: a list, or a
+/// string of tags separated by commas or spaces.
+fn front_matter_tags(yaml: &str) -> Vec<Tag> {
+    let Ok(serde_yaml::Value::Mapping(mapping)) = serde_yaml::from_str(yaml) else {
+        return Vec::new();
+    };
+    let names: Vec<String> = match mapping.get("tags") {
+        Some(serde_yaml::Value::Sequence(tags)) => tags
+            .iter()
+            .filter_map(|tag| tag.as_str().map(str::to_string))
+            .collect(),
+        Some(serde_yaml::Value::String(tags)) => {
+            tags.split([',', ' ']).map(str::to_string).collect()
+        }
+        _ => Vec::new(),
+    };
+    names
+        .iter()
+        .map(|name| name.trim().trim_start_matches('#'))
+        .filter(|name| !name.is_empty())
+        .map(|name| Tag {
+            name: name.to_string(),
+            line: None,
+        })
+        .collect()
+}
+
+/// The wiki-links and tags of `markdown`. Fenced code blocks and code
+/// spans are skipped.
+pub(super) fn parse(markdown: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    let mut body = 0;
+    if let Some((yaml, end)) = vault::front_matter(markdown) {
+        parsed.tags = front_matter_tags(yaml);
+        body = end;
+    }
+    let first_line = markdown[..body].matches('\n').count() + 1;
+    let mut fences = Fences::default();
+    for (number, line) in (first_line..).zip(markdown[body..].lines()) {
+        if fences.code(line) {
+            continue;
+        }
+        scan_line(&blank_code(line), number, &mut parsed);
+    }
+    parsed
+}
+
+/// Whether `tag` is `filter` or nested under it, ignoring case.
+pub(super) fn tag_matches(tag: &str, filter: &str) -> bool {
+    let (tag, filter) = (
+        tag.to_lowercase(),
+        filter.trim_start_matches('#').to_lowercase(),
+    );
+    tag == filter || tag.starts_with(&format!("{filter}/"))
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct ExtractWikilinksInput {
+    #[schemars(description = "The markdown note to read")]
+    pub markdown: String,
+    #[schemars(description = "Only return links to this note name or path, ignoring case")]
+    pub target: Option<String>,
+    #[schemars(
+        description = "Only return this tag and the tags nested under it, e.g. `project` for `project/active`"
+    )]
+    pub tag: Option<String>,
+}
+
+/// Output of `extract_wikilinks`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ExtractWikilinksOutput {
+    pub links: Vec<WikiLink>,
+    pub tags: Vec<Tag>,
+}
+
+#[derive(Debug, rmcp::serde::Deserialize, schemars::JsonSchema)]
+pub(crate) struct BacklinksInput {
+    #[schemars(description = "The directory of notes (the vault) to search")]
+    pub directory: String,
+    #[schemars(
+        description = "The note to find links to: its name (`Ada`) or its path in the directory (`people/Ada.md`)"
+    )]
+    pub note: String,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct Backlink {
+    #[schemars(description = "Path of the linking note in the directory")]
+    pub path: String,
+    #[serde(flatten)]
+    pub link: WikiLink,
+    #[schemars(description = "The line of the link, trimmed")]
+    pub context: String,
+}
+
+/// Output of `backlinks`.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct BacklinksOutput {
+    #[schemars(description = "Path of the note in the directory; null if no note has that name")]
+    pub note: Option<String>,
+    #[schemars(description = "The links to the note, by linking note, then line")]
+    pub backlinks: Vec<Backlink>,
+}
+
+fn backlinks(vault: &Vault, note: &str) -> BacklinksOutput {
+    let resolved = vault.resolve(note).map(|note| note.path.clone());
+    let name = vault::strip_extension(note.trim()).to_lowercase();
+    let mut backlinks = Vec::new();
+    for source in &vault.notes {
+        if Some(&source.path) == resolved.as_ref() {
+            continue;
+        }
+        for link in parse(&source.markdown).links {
+            let links_here = match &resolved {
+                Some(path) => vault
+                    .resolve(&link.target)
+                    .is_some_and(|target| &target.path == path),
+                None => vault::strip_extension(&link.target).to_lowercase() == name,
+            };
+            if links_here {
+                backlinks.push(Backlink {
+                    path: source.path.clone(),
+                    context: source
+                        .markdown
+                        .lines()
+                        .nth(link.line - 1)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                    link,
+                });
+            }
+        }
+    }
+    BacklinksOutput {
+        note: resolved,
+        backlinks,
+    }
+}
+
+#[tool_router(router = wikilinks_router, vis = "pub(crate)")]
+impl Server {
+    #[tool(
+        description = "List the wiki-links (`[[Page]]`, `[[Page#Heading|alias]]`, embeds `![[Page]]`) and tags (`#tag`, `#nested/tag`, front matter `tags`) of an Obsidian or Foam note, which mq queries otherwise see as plain text. Select links by target and tags by name.",
+        output_schema = output_schema::<ExtractWikilinksOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn extract_wikilinks(&self, Parameters(input): Parameters<ExtractWikilinksInput>) -> McpResult {
+        let mut parsed = parse(&input.markdown);
+        if let Some(target) = &input.target {
+            let target = vault::strip_extension(target.trim()).to_lowercase();
+            parsed
+                .links
+                .retain(|link| vault::strip_extension(&link.target).to_lowercase() == target);
+        }
+        if let Some(filter) = &input.tag {
+            parsed.tags.retain(|tag| tag_matches(&tag.name, filter));
+        }
+        json_result(&ExtractWikilinksOutput {
+            links: parsed.links,
+            tags: parsed.tags,
+        })
+    }
+
+    #[tool(
+        description = "Find the notes in a directory (an Obsidian or Foam vault) that link to a note with wiki-links or embeds, with the line and text of each link. Links are resolved as Obsidian does: by note name, or by path when given.",
+        output_schema = output_schema::<BacklinksOutput>(),
+        annotations(read_only_hint = true, open_world_hint = false)
+    )]
+    fn backlinks(&self, Parameters(input): Parameters<BacklinksInput>) -> McpResult {
+        let vault = Vault::read(&self.scoped_path(&input.directory)?)?;
+        json_result(&backlinks(&vault, &input.note))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn link(target: &str, line: usize) -> WikiLink {
+        WikiLink {
+            target: target.to_string(),
+            heading: None,
+            block: None,
+            alias: None,
+            embed: false,
+            line,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let parsed = parse(
+            "---\ntags: [daily, \"#journal\"]\n---\n\
+             See [[Ada Lovelace|Ada]] and ![[diagram.png]].\n\
+             Back to [[#Goals]], or [[Projects/Engine#^a1b2]]. #project/active #2026\n\
+             ```\n[[Not a link]] #not-a-tag\n```\n\
+             Inline `[[code]] #code` and a [link](https://example.com/#anchor) #todo\n",
+        );
+        assert_eq!(
+            parsed.links,
+            vec![
+                WikiLink {
+                    alias: Some("Ada".to_string()),
+                    ..link("Ada Lovelace", 4)
+                },
+                WikiLink {
+                    embed: true,
+                    ..link("diagram.png", 4)
+                },
+                WikiLink {
+                    heading: Some("Goals".to_string()),
+                    ..link("", 5)
+                },
+                WikiLink {
+                    block: Some("a1b2".to_string()),
+                    ..link("Projects/Engine", 5)
+                },
+            ]
+        );
+        assert_eq!(
+            parsed
+                .tags
+                .iter()
+                .map(|tag| (tag.name.as_str(), tag.line))
+                .collect::<Vec<_>>(),
+            vec![
+                ("daily", None),
+                ("journal", None),
+                ("project/active", Some(5)),
+                ("todo", Some(9)),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("project/active", "project", true)]
+    #[case("project", "#Project", true)]
+    #[case("projects", "project", false)]
+    fn test_tag_matches(#[case] tag: &str, #[case] filter: &str, #[case] expected: bool) {
+        assert_eq!(tag_matches(tag, filter), expected);
+    }
+
+    #[test]
+    fn test_backlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("people")).unwrap();
+        std::fs::write(dir.path().join("people/Ada.md"), "# Ada\n\nSee [[Ada]].\n").unwrap();
+        std::fs::write(
+            dir.path().join("Home.md"),
+            "# Home\n\n- [[ada|Ada Lovelace]]\n- [[Grace]]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("Log.md"),
+            "Met ![[people/Ada#Early life]].\n",
+        )
+        .unwrap();
+        let server = Server::new(None).unwrap();
+        let vault = Vault::read(dir.path()).unwrap();
+
+        let output = backlinks(&vault, "Ada");
+        assert_eq!(output.note.as_deref(), Some("people/Ada.md"));
+        assert_eq!(
+            output
+                .backlinks
+                .iter()
+                .map(|b| (b.path.as_str(), b.link.line, b.context.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Home.md", 3, "- [[ada|Ada Lovelace]]"),
+                ("Log.md", 1, "Met ![[people/Ada#Early life]]."),
+            ]
+        );
+
+        // Links to a note that doesn't exist yet.
+        let output = backlinks(&vault, "Grace");
+        assert_eq!(output.note, None);
+        assert_eq!(output.backlinks.len(), 1);
+
+        assert!(
+            server
+                .backlinks(Parameters(BacklinksInput {
+                    directory: dir.path().join("Home.md").to_string_lossy().into_owned(),
+                    note: "Ada".to_string(),
+                }))
+                .is_err()
+        );
+    }
+}
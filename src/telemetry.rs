@@ -0,0 +1,79 @@
+//! `--otlp-endpoint`: exporting the `tool_call` spans and the tool call
+//! metrics to an OpenTelemetry collector over OTLP/HTTP, so mq-mcp shows up
+//! in the same traces and dashboards as the agent calling it.
+
+use miette::miette;
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{Resource, metrics::SdkMeterProvider, trace::SdkTracerProvider};
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::logging::LogLayer;
+
+/// Service name the spans and metrics are reported under.
+const SERVICE_NAME: &str = "mq-mcp";
+
+/// Spans exported regardless of `RUST_LOG`, which only filters the log
+/// output.
+const SPAN_FILTER: &str = "mq_mcp=info";
+
+/// The installed exporters. Dropping it flushes what hasn't been exported
+/// yet and shuts them down.
+pub struct Telemetry {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+}
+
+impl Telemetry {
+    /// Creates exporters sending to the collector at `endpoint` and
+    /// installs the meter provider globally.
+    pub fn init(endpoint: &str) -> miette::Result<Self> {
+        let endpoint = endpoint.trim_end_matches('/');
+        let resource = Resource::builder().with_service_name(SERVICE_NAME).build();
+
+        let spans = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{endpoint}/v1/traces"))
+            .build()
+            .map_err(|e| miette!("failed to create the OTLP span exporter: {e}"))?;
+        let metrics = MetricExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{endpoint}/v1/metrics"))
+            .build()
+            .map_err(|e| miette!("failed to create the OTLP metric exporter: {e}"))?;
+
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_resource(resource.clone())
+            .with_batch_exporter(spans)
+            .build();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_resource(resource)
+            .with_periodic_exporter(metrics)
+            .build();
+        global::set_meter_provider(meter_provider.clone());
+
+        Ok(Self {
+            tracer_provider,
+            meter_provider,
+        })
+    }
+
+    /// The layer turning tracing spans into exported OpenTelemetry spans.
+    pub fn layer(&self) -> LogLayer {
+        tracing_opentelemetry::layer()
+            .with_tracer(self.tracer_provider.tracer(SERVICE_NAME))
+            .with_filter(EnvFilter::new(SPAN_FILTER))
+            .boxed()
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(e) = self.tracer_provider.shutdown() {
+            eprintln!("failed to flush the OTLP span exporter: {e}");
+        }
+        if let Err(e) = self.meter_provider.shutdown() {
+            eprintln!("failed to flush the OTLP metric exporter: {e}");
+        }
+    }
+}
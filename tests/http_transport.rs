@@ -1,8 +1,6 @@
-use std::path::PathBuf;
+use mq_mcp::server::{HttpConfig, ServerConfig, start_http};
 
-use mq_mcp::server::{HttpConfig, start_http};
-
-async fn spawn_server_with_db(db_path: Option<PathBuf>) -> (String, tokio::task::JoinHandle<()>) {
+async fn spawn_server_with_config(config: ServerConfig) -> (String, tokio::task::JoinHandle<()>) {
     let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
         .await
         .expect("bind ephemeral port");
@@ -18,7 +16,7 @@ async fn spawn_server_with_db(db_path: Option<PathBuf>) -> (String, tokio::task:
                     bind,
                     allowed_hosts: vec![],
                 },
-                db_path,
+                config,
             )
             .await;
         }
@@ -41,8 +39,19 @@ async fn spawn_server_with_db(db_path: Option<PathBuf>) -> (String, tokio::task:
     (format!("http://{addr}/mcp"), handle)
 }
 
+#[cfg(feature = "db")]
+async fn spawn_server_with_db(
+    db_path: Option<std::path::PathBuf>,
+) -> (String, tokio::task::JoinHandle<()>) {
+    spawn_server_with_config(ServerConfig {
+        db_path,
+        ..Default::default()
+    })
+    .await
+}
+
 async fn spawn_server() -> (String, tokio::task::JoinHandle<()>) {
-    spawn_server_with_db(None).await
+    spawn_server_with_config(ServerConfig::default()).await
 }
 
 async fn init_session(client: &reqwest::Client, url: &str) -> String {
@@ -106,6 +115,7 @@ async fn test_streamable_http_initialize_and_call_tool() {
     handle.abort();
 }
 
+#[cfg(feature = "db")]
 #[tokio::test]
 async fn test_streamable_http_db_index_then_sql() {
     let dir = tempfile::tempdir().unwrap();
@@ -157,3 +167,54 @@ async fn test_streamable_http_db_index_then_sql() {
 
     handle.abort();
 }
+
+#[tokio::test]
+async fn test_streamable_http_large_result_offloaded_to_resource() {
+    let (url, handle) = spawn_server_with_config(ServerConfig {
+        resource_threshold: Some(16),
+        ..Default::default()
+    })
+    .await;
+    let client = reqwest::Client::new();
+    let session_id = init_session(&client, &url).await;
+
+    let call_body = client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .header("Accept", "application/json, text/event-stream")
+        .header("mcp-session-id", &session_id)
+        .body(
+            r##"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"extract_headings","arguments":{"markdown":"# A heading that is long enough"}}}"##,
+        )
+        .send()
+        .await
+        .expect("tool call")
+        .text()
+        .await
+        .expect("tool call body");
+    assert!(
+        call_body.contains("resource_link") && call_body.contains("mq://results/1"),
+        "expected a resource link: {call_body}"
+    );
+
+    let read_body = client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .header("Accept", "application/json, text/event-stream")
+        .header("mcp-session-id", &session_id)
+        .body(
+            r##"{"jsonrpc":"2.0","id":3,"method":"resources/read","params":{"uri":"mq://results/1"}}"##,
+        )
+        .send()
+        .await
+        .expect("resources/read")
+        .text()
+        .await
+        .expect("resources/read body");
+    assert!(
+        read_body.contains("# A heading that is long enough"),
+        "unexpected resources/read response: {read_body}"
+    );
+
+    handle.abort();
+}
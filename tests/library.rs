@@ -0,0 +1,65 @@
+use mq_mcp::{Server, ServerConfig};
+use serde_json::json;
+
+fn arguments(value: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+    value.as_object().expect("arguments object").clone()
+}
+
+#[tokio::test]
+async fn test_call_tool_in_process() {
+    let server = Server::new(None).expect("server");
+    let result = server
+        .call(
+            "extract_headings",
+            arguments(json!({ "markdown": "# Install\n\ntext\n\n## Usage" })),
+        )
+        .await
+        .expect("call extract_headings");
+    assert_eq!(
+        result.structured_content,
+        Some(json!({ "results": ["# Install", "## Usage"] }))
+    );
+}
+
+#[tokio::test]
+async fn test_calls_share_server_state() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let server = Server::with_config(ServerConfig {
+        state_path: Some(dir.path().join("state.db")),
+        ..Default::default()
+    })
+    .expect("server");
+    server
+        .call(
+            "save_query",
+            arguments(json!({ "name": "titles", "query": ".h1 | to_text()" })),
+        )
+        .await
+        .expect("call save_query");
+    let exported = server
+        .call("export_state", arguments(json!({})))
+        .await
+        .expect("call export_state");
+    assert_eq!(
+        exported.structured_content.expect("bundle")["saved_queries"][0]["name"],
+        "titles"
+    );
+}
+
+#[tokio::test]
+async fn test_call_reports_errors() {
+    let server = Server::new(None).expect("server");
+    assert!(
+        server
+            .call("no_such_tool", arguments(json!({})))
+            .await
+            .is_err()
+    );
+    // Invalid arguments are reported in the tool's result, not as a
+    // protocol error.
+    let result = server
+        .call("extract_headings", arguments(json!({ "markdown": 1 })))
+        .await
+        .expect("call extract_headings");
+    assert_eq!(result.is_error, Some(true));
+}